@@ -0,0 +1,74 @@
+//! Benchmarks for the per-frame work on a large buffer: tokenizing, parsing,
+//! evaluating and highlighting every line.
+//!
+//! Run with `cargo bench`; Criterion reports the throughput in lines per
+//! second and the change since the last run. Without `--bench` (as under
+//! `cargo test --all-targets`) each benchmark runs once as a smoke test.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+use crabculator::eval::token::Tokenizer;
+use crabculator::eval::{EvalContext, evaluate_all_lines_with_context, parse_expression};
+use crabculator::ui::highlight_line;
+
+/// Number of lines in the benchmark buffer.
+const BUFFER_LINES: usize = 1000;
+
+/// Builds a buffer mixing assignments, function calls and comments.
+fn buffer() -> Vec<String> {
+    (0..BUFFER_LINES)
+        .map(|i| match i % 4 {
+            0 => format!("x{i} = {i} * 1.5 + sqrt(16)"),
+            1 => format!("x{} / 3 + max(2, 7) ^ 2", i - 1),
+            2 => format!("price{i} = 1_250 * (1 + 0.19) - 42 // 5"),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+fn buffer_benchmarks(c: &mut Criterion) {
+    let lines = buffer();
+    let mut group = c.benchmark_group("buffer");
+    group.throughput(Throughput::Elements(BUFFER_LINES as u64));
+
+    group.bench_function("tokenize", |b| {
+        b.iter(|| {
+            for line in &lines {
+                black_box(Tokenizer::new(line).tokenize().ok());
+            }
+        });
+    });
+
+    group.bench_function("parse", |b| {
+        b.iter(|| {
+            for line in &lines {
+                black_box(parse_expression(line).ok());
+            }
+        });
+    });
+
+    group.bench_function("evaluate (cached)", |b| {
+        b.iter(|| {
+            let mut context = EvalContext::new();
+            black_box(evaluate_all_lines_with_context(
+                lines.iter().map(String::as_str),
+                &mut context,
+            ))
+        });
+    });
+
+    group.bench_function("highlight (cached)", |b| {
+        b.iter(|| {
+            for line in &lines {
+                black_box(highlight_line(line));
+            }
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, buffer_benchmarks);
+criterion_main!(benches);
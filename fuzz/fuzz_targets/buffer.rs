@@ -0,0 +1,101 @@
+//! Applies arbitrary sequences of edits to a buffer, as key presses, clicks
+//! and line commands of the editor do.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use crabculator::editor::Buffer;
+use libfuzzer_sys::fuzz_target;
+
+/// One edit of the buffer, with rows and columns small enough to hit the
+/// text most of the time.
+#[derive(Debug, Arbitrary)]
+enum Edit {
+    InsertChar(char),
+    InsertNewline,
+    DeleteBefore,
+    DeleteAt,
+    Left,
+    Right,
+    Up,
+    Down,
+    LineStart,
+    LineEnd,
+    MoveToCol(u8),
+    MoveTo(u8, u8),
+    Select(u8, u8),
+    ReplaceSelection(String),
+    InsertLine(u8, String),
+    SetLine(u8, String),
+    StartBlock,
+    EndBlock,
+    BlockToEnd(bool),
+    BlockInsert(String),
+    BlockDelete(bool),
+    Checkpoint,
+    Undo,
+    SortLines(u8, u8),
+    DedupeLines(u8, u8),
+    Clear,
+}
+
+/// Returns `text` as the text of a single line.
+fn line(text: &str) -> String {
+    text.replace(['\n', '\r'], " ")
+}
+
+fuzz_target!(|edits: Vec<Edit>| {
+    let mut buffer = Buffer::new();
+    for edit in edits {
+        match edit {
+            Edit::InsertChar(c) if c != '\n' && c != '\r' => buffer.insert_char(c),
+            Edit::InsertChar(_) | Edit::InsertNewline => buffer.insert_newline(),
+            Edit::DeleteBefore => {
+                buffer.delete_char_before();
+            }
+            Edit::DeleteAt => {
+                buffer.delete_char_at();
+            }
+            Edit::Left => buffer.move_cursor_left(),
+            Edit::Right => buffer.move_cursor_right(),
+            Edit::Up => buffer.move_cursor_up(),
+            Edit::Down => buffer.move_cursor_down(),
+            Edit::LineStart => buffer.move_cursor_to_line_start(),
+            Edit::LineEnd => buffer.move_cursor_to_line_end(),
+            Edit::MoveToCol(col) => buffer.move_cursor_to_col(col.into()),
+            Edit::MoveTo(row, col) => buffer.move_cursor_to(row.into(), col.into()),
+            Edit::Select(start, end) => buffer.select(start.into(), end.into()),
+            Edit::ReplaceSelection(text) => {
+                buffer.replace_selection(&line(&text));
+            }
+            Edit::InsertLine(row, text) => buffer.insert_line(row.into(), line(&text)),
+            Edit::SetLine(row, text) => buffer.set_line(row.into(), line(&text)),
+            Edit::StartBlock => buffer.start_block(),
+            Edit::EndBlock => buffer.end_block(),
+            Edit::BlockToEnd(to_end) => buffer.set_block_to_end(to_end),
+            Edit::BlockInsert(text) => buffer.block_insert(&line(&text)),
+            Edit::BlockDelete(forward) => {
+                buffer.block_delete(forward);
+            }
+            Edit::Checkpoint => buffer.checkpoint(),
+            Edit::Undo => {
+                buffer.undo();
+            }
+            Edit::SortLines(start, end) => {
+                buffer.sort_lines(usize::from(start)..usize::from(end));
+            }
+            Edit::DedupeLines(start, end) => {
+                buffer.dedupe_lines(usize::from(start)..usize::from(end));
+            }
+            Edit::Clear => buffer.clear(),
+        }
+
+        // The cursor and selection must stay inside the text.
+        let cursor = buffer.cursor();
+        let current = &buffer.lines()[cursor.row()];
+        assert!(current.is_char_boundary(cursor.col()));
+        let _ = buffer.selected_text();
+        let _ = buffer.current_line();
+        let _ = buffer.block();
+    }
+});
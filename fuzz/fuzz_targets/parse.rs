@@ -0,0 +1,18 @@
+//! Feeds arbitrary text to the parser and evaluates what it accepts, as the
+//! editor does for every line of the buffer.
+
+#![no_main]
+
+use crabculator::eval::ast::Parser;
+use crabculator::eval::token::Tokenizer;
+use crabculator::eval::{EvalContext, evaluate_all_lines_with_context, parse_line};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: &str| {
+    if let Ok(tokens) = Tokenizer::new(input).tokenize() {
+        let _ = Parser::new(tokens).parse();
+    }
+    let _ = parse_line(input);
+    let mut context = EvalContext::new();
+    let _ = evaluate_all_lines_with_context(input.lines(), &mut context);
+});
@@ -0,0 +1,21 @@
+//! Feeds arbitrary text to the tokenizer and the syntax highlighter, which
+//! see every keystroke of the editor, scrolled to arbitrary columns.
+
+#![no_main]
+
+use crabculator::eval::token::Tokenizer;
+use crabculator::ui::{highlight_line, highlight_line_with_offset};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: (&str, u8, u8)| {
+    let (input, offset, width) = data;
+    if let Ok(tokens) = Tokenizer::new(input).tokenize() {
+        for (_, span) in tokens {
+            // Spans must slice the input on character boundaries.
+            let _ = &input[span.start..span.end];
+        }
+    }
+    let _ = Tokenizer::new(input).tokenize_partial();
+    let _ = highlight_line(input);
+    let _ = highlight_line_with_offset(input, offset.into(), width.into());
+});
@@ -0,0 +1,661 @@
+//! Typed user actions and the key bindings that produce them.
+//!
+//! Key handling is split in two steps: `action_for_key` maps a key press to
+//! an `Action` for the current `Mode`, and `App::apply` performs it. Side
+//! effects that need the terminal or the file system are returned as
+//! `Effect`s for the event loop to carry out.
+
+use std::fmt;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// The view that currently receives key presses.
+///
+/// Modal views take precedence in declaration order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// The crash recovery prompt is shown.
+    Recovery,
+    /// The prompt for a state file changed on disk is shown.
+    Conflict,
+    /// The prompt confirming to quit with unsaved changes is shown.
+    ConfirmQuit,
+    /// The inspection popup is shown.
+    Inspection,
+    /// The explain popup is shown.
+    Explanation,
+    /// The dependency graph popup is shown.
+    Dependencies,
+    /// The `:version` popup is shown.
+    Version,
+    /// The `:new` template picker is shown.
+    Templates,
+    /// The table popup is shown.
+    Table,
+    /// The `:diff` popup is shown.
+    Diff,
+    /// The help overlay is shown.
+    Help,
+    /// The settings popup is shown; keys edit its `:set` command.
+    Settings,
+    /// The go-to-line prompt is shown; keys edit its line number.
+    GoToLine,
+    /// The prompt naming a list pasted with `:paste` is shown; keys edit
+    /// its name.
+    PasteList,
+    /// The what-if prompt is shown; keys edit its override.
+    WhatIf,
+    /// The welcome banner is shown over the editor, which still takes keys.
+    Welcome,
+    /// Part of a line is selected; `Esc` clears the selection.
+    Selection,
+    /// Block mode is on; keys edit every line of the block, and `Esc`
+    /// leaves block mode.
+    Block,
+    /// No modal view is shown; keys edit the buffer.
+    Editor,
+}
+
+/// A user action, independent of the key that triggered it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ConfirmQuit,
+    CancelQuit,
+    Suspend,
+    ClearAll,
+    InsertChar(char),
+    InsertNewline,
+    DeleteBackward,
+    DeleteForward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    MoveLineStart,
+    MoveLineEnd,
+    FormatLine,
+    FormatBuffer,
+    ToggleLineDisabled,
+    CompleteFunction,
+    ToggleMemoryPane,
+    ToggleBracketColors,
+    ToggleDecimalAlignment,
+    ToggleTimings,
+    ToggleWatch,
+    ToggleHelp,
+    CloseHelp,
+    ScrollHelpUp,
+    ScrollHelpDown,
+    PageHelpUp,
+    PageHelpDown,
+    Inspect,
+    CloseInspection,
+    Explain,
+    CloseExplanation,
+    ShowDependencies,
+    CloseDependencies,
+    CloseVersion,
+    TemplateUp,
+    TemplateDown,
+    /// Replace the worksheet with the template chosen in the picker.
+    PickTemplate,
+    CloseTemplates,
+    /// Show the `table` result of the cursor line in the table popup.
+    ShowTable,
+    CloseTable,
+    NextTablePage,
+    PreviousTablePage,
+    CloseDiff,
+    NextDiffPage,
+    PreviousDiffPage,
+    CycleResultHistory,
+    OpenWhatIf,
+    CloseWhatIf,
+    WhatIfChar(char),
+    WhatIfBackspace,
+    RunWhatIfCommand,
+    ShareResults,
+    RestoreRecovery,
+    DismissRecovery,
+    ReloadConflict,
+    OverwriteConflict,
+    OpenSettings,
+    CloseSettings,
+    SettingsChar(char),
+    SettingsBackspace,
+    RunSettingsCommand,
+    DismissWelcome,
+    OpenGoToLine,
+    CloseGoToLine,
+    GoToLineChar(char),
+    GoToLineBackspace,
+    RunGoToLine,
+    ClosePasteList,
+    PasteListChar(char),
+    PasteListBackspace,
+    /// Insert the line assigning the pasted numbers to the typed name.
+    RunPasteList,
+    NextBookmark,
+    PreviousBookmark,
+    /// Jump to the numbered bookmark named by the digit.
+    JumpToBookmark(char),
+    /// Select the next larger expression around the cursor or selection.
+    SelectEnclosing,
+    ClearSelection,
+    /// Move the selected expression into a new variable on the line above.
+    ExtractVariable,
+    Undo,
+    /// Start or leave block mode, editing the same columns of several lines.
+    ToggleBlock,
+    /// A left click on the screen cell at `column`, `row`.
+    Click {
+        column: u16,
+        row: u16,
+    },
+}
+
+/// An action shown without the character it types, for the debug log.
+///
+/// Typed characters are the contents of the worksheet, which must not end
+/// up in a plain-text log when the worksheet is encrypted.
+pub struct Redacted(pub Action);
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Action::InsertChar(_) => f.write_str("InsertChar(..)"),
+            Action::WhatIfChar(_) => f.write_str("WhatIfChar(..)"),
+            Action::SettingsChar(_) => f.write_str("SettingsChar(..)"),
+            Action::GoToLineChar(_) => f.write_str("GoToLineChar(..)"),
+            Action::PasteListChar(_) => f.write_str("PasteListChar(..)"),
+            action => fmt::Debug::fmt(&action, f),
+        }
+    }
+}
+
+/// Follow-up work the event loop must perform after an action.
+///
+/// Keeping disk and terminal side effects out of `App::apply` lets tests
+/// drive the full keybinding logic without touching either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// The buffer changed and should be persisted.
+    Save,
+    /// The settings changed and should be persisted.
+    SaveSettings,
+    /// The terminal should be suspended to the shell.
+    Suspend,
+    /// The worksheet and its results should be copied to the clipboard.
+    CopyResults,
+    /// The value of the result of this line should be copied to the clipboard.
+    CopyResult(usize),
+    /// The clipboard should be read and handed to `App::paste_column`.
+    ReadClipboard,
+}
+
+/// Maps a key press to an action for the given mode.
+///
+/// # Returns
+/// `None` if the key is not bound in this mode.
+#[must_use]
+pub fn action_for_key(mode: Mode, key: KeyEvent) -> Option<Action> {
+    match mode {
+        Mode::Recovery => recovery_action(key),
+        Mode::Conflict => conflict_action(key),
+        Mode::ConfirmQuit => confirm_quit_action(key),
+        Mode::Inspection => {
+            (is_ctrl(key, 'e') || key.code == KeyCode::Esc).then_some(Action::CloseInspection)
+        }
+        Mode::Explanation => {
+            (is_ctrl(key, 't') || key.code == KeyCode::Esc).then_some(Action::CloseExplanation)
+        }
+        Mode::Dependencies => {
+            (is_ctrl(key, 'g') || key.code == KeyCode::Esc).then_some(Action::CloseDependencies)
+        }
+        Mode::Version => (key.code == KeyCode::Esc).then_some(Action::CloseVersion),
+        Mode::Templates => template_action(key),
+        Mode::Table => table_action(key),
+        Mode::Diff => diff_action(key),
+        Mode::Help => help_action(key),
+        Mode::Settings => settings_action(key),
+        Mode::GoToLine => go_to_line_action(key),
+        Mode::PasteList => paste_list_action(key),
+        Mode::WhatIf => what_if_action(key),
+        Mode::Welcome if key.code == KeyCode::Esc => Some(Action::DismissWelcome),
+        Mode::Selection if key.code == KeyCode::Esc => Some(Action::ClearSelection),
+        Mode::Block if key.code == KeyCode::Esc => Some(Action::ToggleBlock),
+        Mode::Welcome | Mode::Selection | Mode::Block | Mode::Editor => editor_action(key),
+    }
+}
+
+/// Key bindings for the crash recovery prompt.
+const fn recovery_action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('y' | 'Y') => Some(Action::RestoreRecovery),
+        KeyCode::Char('n' | 'N') | KeyCode::Esc => Some(Action::DismissRecovery),
+        _ => None,
+    }
+}
+
+/// Key bindings for the prompt for a state file changed on disk.
+const fn conflict_action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('r' | 'R') => Some(Action::ReloadConflict),
+        KeyCode::Char('o' | 'O') => Some(Action::OverwriteConflict),
+        _ => None,
+    }
+}
+
+/// Key bindings for the prompt confirming to quit with unsaved changes.
+const fn confirm_quit_action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Char('y' | 'Y') => Some(Action::ConfirmQuit),
+        KeyCode::Char('n' | 'N') | KeyCode::Esc => Some(Action::CancelQuit),
+        _ => None,
+    }
+}
+
+/// Key bindings for the `:new` template picker.
+const fn template_action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Up => Some(Action::TemplateUp),
+        KeyCode::Down => Some(Action::TemplateDown),
+        KeyCode::Enter => Some(Action::PickTemplate),
+        KeyCode::Esc => Some(Action::CloseTemplates),
+        _ => None,
+    }
+}
+
+/// Key bindings for the table popup.
+const fn table_action(key: KeyEvent) -> Option<Action> {
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    match key.code {
+        KeyCode::Char('t') if alt => Some(Action::CloseTable),
+        KeyCode::Esc => Some(Action::CloseTable),
+        KeyCode::PageDown | KeyCode::Down | KeyCode::Right => Some(Action::NextTablePage),
+        KeyCode::PageUp | KeyCode::Up | KeyCode::Left => Some(Action::PreviousTablePage),
+        _ => None,
+    }
+}
+
+/// Key bindings for the `:diff` popup.
+const fn diff_action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Esc => Some(Action::CloseDiff),
+        KeyCode::PageDown | KeyCode::Down | KeyCode::Right => Some(Action::NextDiffPage),
+        KeyCode::PageUp | KeyCode::Up | KeyCode::Left => Some(Action::PreviousDiffPage),
+        _ => None,
+    }
+}
+
+/// Key bindings for the help overlay.
+fn help_action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        _ if is_ctrl(key, 'h') => Some(Action::ToggleHelp),
+        KeyCode::Esc => Some(Action::CloseHelp),
+        KeyCode::Up => Some(Action::ScrollHelpUp),
+        KeyCode::Down => Some(Action::ScrollHelpDown),
+        KeyCode::PageUp => Some(Action::PageHelpUp),
+        KeyCode::PageDown => Some(Action::PageHelpDown),
+        _ => None,
+    }
+}
+
+/// Key bindings for the settings popup.
+fn settings_action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        _ if is_ctrl(key, 'o') => Some(Action::CloseSettings),
+        KeyCode::Esc => Some(Action::CloseSettings),
+        KeyCode::Enter => Some(Action::RunSettingsCommand),
+        KeyCode::Backspace => Some(Action::SettingsBackspace),
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::SettingsChar(c))
+        }
+        _ => None,
+    }
+}
+
+/// Key bindings for the go-to-line prompt.
+fn go_to_line_action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        _ if is_ctrl(key, 'l') => Some(Action::CloseGoToLine),
+        KeyCode::Esc => Some(Action::CloseGoToLine),
+        KeyCode::Enter => Some(Action::RunGoToLine),
+        KeyCode::Backspace => Some(Action::GoToLineBackspace),
+        KeyCode::Char(c) if c.is_ascii_digit() => Some(Action::GoToLineChar(c)),
+        _ => None,
+    }
+}
+
+/// Key bindings for the prompt naming a pasted list.
+const fn paste_list_action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Esc => Some(Action::ClosePasteList),
+        KeyCode::Enter => Some(Action::RunPasteList),
+        KeyCode::Backspace => Some(Action::PasteListBackspace),
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::PasteListChar(c))
+        }
+        _ => None,
+    }
+}
+
+/// Key bindings for the what-if prompt.
+fn what_if_action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        _ if is_ctrl(key, 'x') => Some(Action::CloseWhatIf),
+        KeyCode::Esc => Some(Action::CloseWhatIf),
+        KeyCode::Enter => Some(Action::RunWhatIfCommand),
+        KeyCode::Backspace => Some(Action::WhatIfBackspace),
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::WhatIfChar(c))
+        }
+        _ => None,
+    }
+}
+
+/// Key bindings for the editor.
+const fn editor_action(key: KeyEvent) -> Option<Action> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+
+    let action = match key.code {
+        KeyCode::Char('c' | 'q') if ctrl => Action::Quit,
+        KeyCode::Char('h') if ctrl => Action::ToggleHelp,
+        KeyCode::Char('e') if ctrl => Action::Inspect,
+        KeyCode::Char('f') if ctrl => Action::FormatLine,
+        KeyCode::Char('f') if alt => Action::FormatBuffer,
+        KeyCode::Char(c @ '1'..='9') if alt => Action::JumpToBookmark(c),
+        KeyCode::Char('t') if ctrl => Action::Explain,
+        KeyCode::Char('g') if ctrl => Action::ShowDependencies,
+        KeyCode::Char('y') if ctrl => Action::CycleResultHistory,
+        KeyCode::Char('x') if ctrl => Action::OpenWhatIf,
+        KeyCode::Char('s') if ctrl => Action::ShareResults,
+        KeyCode::Char('z') if ctrl => Action::Suspend,
+        KeyCode::Char('r') if ctrl => Action::ClearAll,
+        KeyCode::Char('b') if ctrl => Action::ToggleBracketColors,
+        KeyCode::Char('d') if ctrl => Action::ToggleDecimalAlignment,
+        KeyCode::Char('p') if ctrl => Action::ToggleTimings,
+        KeyCode::Char('w') if ctrl => Action::ToggleWatch,
+        KeyCode::Char('k') if ctrl => Action::ToggleLineDisabled,
+        KeyCode::Char('o') if ctrl => Action::OpenSettings,
+        KeyCode::Char('l') if ctrl => Action::OpenGoToLine,
+        KeyCode::Char('a') if ctrl => Action::SelectEnclosing,
+        KeyCode::Char('u') if ctrl => Action::Undo,
+        KeyCode::Char('v') if ctrl => Action::ToggleBlock,
+        KeyCode::Char('e') if alt => Action::ExtractVariable,
+        KeyCode::Char('t') if alt => Action::ShowTable,
+        KeyCode::Char(c) => Action::InsertChar(c),
+        KeyCode::Enter => Action::InsertNewline,
+        KeyCode::Tab => Action::CompleteFunction,
+        KeyCode::Backspace => Action::DeleteBackward,
+        KeyCode::Delete => Action::DeleteForward,
+        KeyCode::Up if alt => Action::PreviousBookmark,
+        KeyCode::Down if alt => Action::NextBookmark,
+        KeyCode::Left | KeyCode::Right if ctrl => Action::ToggleMemoryPane,
+        KeyCode::Left => Action::MoveLeft,
+        KeyCode::Right => Action::MoveRight,
+        KeyCode::Up => Action::MoveUp,
+        KeyCode::Down => Action::MoveDown,
+        KeyCode::Home => Action::MoveLineStart,
+        KeyCode::End => Action::MoveLineEnd,
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// Returns `true` if `key` is `c` pressed with Control held.
+fn is_ctrl(key: KeyEvent, c: char) -> bool {
+    key.code == KeyCode::Char(c) && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn test_redacted_hides_typed_characters() {
+        assert_eq!(
+            format!("{:?}", Redacted(Action::InsertChar('7'))),
+            "InsertChar(..)"
+        );
+        assert_eq!(
+            format!("{:?}", Redacted(Action::SettingsChar('x'))),
+            "SettingsChar(..)"
+        );
+        assert_eq!(format!("{:?}", Redacted(Action::MoveLeft)), "MoveLeft");
+    }
+
+    #[test]
+    fn test_editor_plain_char_inserts() {
+        assert_eq!(
+            action_for_key(Mode::Editor, key(KeyCode::Char('q'))),
+            Some(Action::InsertChar('q'))
+        );
+    }
+
+    #[test]
+    fn test_editor_ctrl_char_is_command() {
+        assert_eq!(action_for_key(Mode::Editor, ctrl('q')), Some(Action::Quit));
+        assert_eq!(
+            action_for_key(Mode::Editor, ctrl('z')),
+            Some(Action::Suspend)
+        );
+        assert_eq!(
+            action_for_key(Mode::Editor, ctrl('y')),
+            Some(Action::CycleResultHistory)
+        );
+        assert_eq!(
+            action_for_key(Mode::Editor, ctrl('s')),
+            Some(Action::ShareResults)
+        );
+    }
+
+    #[test]
+    fn test_editor_alt_f_formats_buffer() {
+        let key = KeyEvent::new(KeyCode::Char('f'), KeyModifiers::ALT);
+        assert_eq!(
+            action_for_key(Mode::Editor, key),
+            Some(Action::FormatBuffer)
+        );
+    }
+
+    #[test]
+    fn test_editor_ctrl_arrow_moves_memory_pane() {
+        let key = KeyEvent::new(KeyCode::Left, KeyModifiers::CONTROL);
+        assert_eq!(
+            action_for_key(Mode::Editor, key),
+            Some(Action::ToggleMemoryPane)
+        );
+    }
+
+    #[test]
+    fn test_escape_clears_selection_without_quitting() {
+        assert_eq!(
+            action_for_key(Mode::Editor, ctrl('a')),
+            Some(Action::SelectEnclosing)
+        );
+        assert_eq!(
+            action_for_key(Mode::Selection, key(KeyCode::Esc)),
+            Some(Action::ClearSelection)
+        );
+        assert_eq!(action_for_key(Mode::Editor, key(KeyCode::Esc)), None);
+        let key = KeyEvent::new(KeyCode::Char('e'), KeyModifiers::ALT);
+        assert_eq!(
+            action_for_key(Mode::Selection, key),
+            Some(Action::ExtractVariable)
+        );
+    }
+
+    #[test]
+    fn test_help_ignores_typing() {
+        assert_eq!(action_for_key(Mode::Help, key(KeyCode::Char('x'))), None);
+        assert_eq!(
+            action_for_key(Mode::Help, key(KeyCode::PageDown)),
+            Some(Action::PageHelpDown)
+        );
+    }
+
+    #[test]
+    fn test_popups_close_with_escape_or_opening_key() {
+        assert_eq!(
+            action_for_key(Mode::Inspection, ctrl('e')),
+            Some(Action::CloseInspection)
+        );
+        assert_eq!(
+            action_for_key(Mode::Explanation, key(KeyCode::Esc)),
+            Some(Action::CloseExplanation)
+        );
+        assert_eq!(action_for_key(Mode::Explanation, ctrl('e')), None);
+        assert_eq!(
+            action_for_key(Mode::Editor, ctrl('g')),
+            Some(Action::ShowDependencies)
+        );
+        assert_eq!(
+            action_for_key(Mode::Dependencies, ctrl('g')),
+            Some(Action::CloseDependencies)
+        );
+    }
+
+    #[test]
+    fn test_settings_popup_bindings() {
+        assert_eq!(
+            action_for_key(Mode::Editor, ctrl('o')),
+            Some(Action::OpenSettings)
+        );
+        assert_eq!(
+            action_for_key(Mode::Settings, key(KeyCode::Char('q'))),
+            Some(Action::SettingsChar('q'))
+        );
+        assert_eq!(
+            action_for_key(Mode::Settings, key(KeyCode::Enter)),
+            Some(Action::RunSettingsCommand)
+        );
+        assert_eq!(
+            action_for_key(Mode::Settings, ctrl('o')),
+            Some(Action::CloseSettings)
+        );
+        assert_eq!(action_for_key(Mode::Settings, ctrl('q')), None);
+    }
+
+    #[test]
+    fn test_go_to_line_prompt_bindings() {
+        assert_eq!(
+            action_for_key(Mode::Editor, ctrl('l')),
+            Some(Action::OpenGoToLine)
+        );
+        assert_eq!(
+            action_for_key(Mode::GoToLine, key(KeyCode::Char('4'))),
+            Some(Action::GoToLineChar('4'))
+        );
+        assert_eq!(
+            action_for_key(Mode::GoToLine, key(KeyCode::Char('x'))),
+            None
+        );
+        assert_eq!(
+            action_for_key(Mode::GoToLine, key(KeyCode::Enter)),
+            Some(Action::RunGoToLine)
+        );
+        assert_eq!(
+            action_for_key(Mode::GoToLine, ctrl('l')),
+            Some(Action::CloseGoToLine)
+        );
+    }
+
+    #[test]
+    fn test_editor_alt_keys_follow_bookmarks() {
+        let alt = |code| KeyEvent::new(code, KeyModifiers::ALT);
+        assert_eq!(
+            action_for_key(Mode::Editor, alt(KeyCode::Char('3'))),
+            Some(Action::JumpToBookmark('3'))
+        );
+        assert_eq!(
+            action_for_key(Mode::Editor, alt(KeyCode::Down)),
+            Some(Action::NextBookmark)
+        );
+        assert_eq!(
+            action_for_key(Mode::Editor, alt(KeyCode::Up)),
+            Some(Action::PreviousBookmark)
+        );
+    }
+
+    #[test]
+    fn test_what_if_prompt_bindings() {
+        assert_eq!(
+            action_for_key(Mode::Editor, ctrl('x')),
+            Some(Action::OpenWhatIf)
+        );
+        assert_eq!(
+            action_for_key(Mode::WhatIf, key(KeyCode::Char('r'))),
+            Some(Action::WhatIfChar('r'))
+        );
+        assert_eq!(
+            action_for_key(Mode::WhatIf, key(KeyCode::Enter)),
+            Some(Action::RunWhatIfCommand)
+        );
+        assert_eq!(
+            action_for_key(Mode::WhatIf, key(KeyCode::Esc)),
+            Some(Action::CloseWhatIf)
+        );
+    }
+
+    #[test]
+    fn test_welcome_banner_escape_dismisses() {
+        assert_eq!(
+            action_for_key(Mode::Welcome, key(KeyCode::Esc)),
+            Some(Action::DismissWelcome)
+        );
+        assert_eq!(
+            action_for_key(Mode::Welcome, key(KeyCode::Char('x'))),
+            Some(Action::InsertChar('x'))
+        );
+    }
+
+    #[test]
+    fn test_recovery_prompt_bindings() {
+        assert_eq!(
+            action_for_key(Mode::Recovery, key(KeyCode::Char('Y'))),
+            Some(Action::RestoreRecovery)
+        );
+        assert_eq!(
+            action_for_key(Mode::Recovery, key(KeyCode::Esc)),
+            Some(Action::DismissRecovery)
+        );
+        assert_eq!(action_for_key(Mode::Recovery, key(KeyCode::Down)), None);
+    }
+
+    #[test]
+    fn test_conflict_prompt_bindings() {
+        assert_eq!(
+            action_for_key(Mode::Conflict, key(KeyCode::Char('r'))),
+            Some(Action::ReloadConflict)
+        );
+        assert_eq!(
+            action_for_key(Mode::Conflict, key(KeyCode::Char('O'))),
+            Some(Action::OverwriteConflict)
+        );
+        assert_eq!(action_for_key(Mode::Conflict, key(KeyCode::Esc)), None);
+    }
+
+    #[test]
+    fn test_confirm_quit_prompt_bindings() {
+        assert_eq!(
+            action_for_key(Mode::ConfirmQuit, key(KeyCode::Char('y'))),
+            Some(Action::ConfirmQuit)
+        );
+        assert_eq!(
+            action_for_key(Mode::ConfirmQuit, key(KeyCode::Esc)),
+            Some(Action::CancelQuit)
+        );
+        assert_eq!(action_for_key(Mode::ConfirmQuit, ctrl('q')), None);
+    }
+}
@@ -1,11 +1,81 @@
-use std::time::Instant;
+mod bookmarks;
+mod debug;
+mod diff;
+mod paste;
+mod profiles;
+mod quick_sum;
+mod settings;
+mod share;
+mod table_view;
+mod templates;
+mod version;
+mod welcome;
 
-use crate::editor::Buffer;
-use crate::eval::EvalContext;
-use crate::storage;
-use crate::ui::AppTheme;
+pub use bookmarks::Bookmarks;
+pub use debug::{DEBUG_EVENT_COUNT, DebugInfo};
+pub use diff::{DIFF_PAGE_SIZE, DiffKind, DiffLine, DiffView};
+pub use paste::ListPrompt;
+pub use profiles::BUILTIN_PROFILES;
+pub use quick_sum::QuickSum;
+pub use settings::{MAX_PRECISION, SETTING_KEYS, Settings};
+pub use table_view::{TABLE_PAGE_SIZE, TableView};
+pub use templates::{BUILTIN_TEMPLATES, TemplatePicker};
+pub use welcome::{SAMPLE_WORKSHEET, WELCOME_MESSAGE};
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, ErrorKind};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crossterm::event::{Event, KeyEvent, MouseButton, MouseEventKind};
+
+use crate::action::{Action, Effect, Mode, Redacted, action_for_key};
+use crate::cli::Persistence;
+use crate::editor::{Buffer, enclosing_expression, floor_boundary};
+use crate::eval::constants::is_math_constant;
+use crate::eval::deps::dependency_tree_lines;
+use crate::eval::explain::{Explanation, explain_line};
+use crate::eval::format::format_line;
+use crate::eval::functions::complete_function;
+use crate::eval::inspect::{Inspection, inspect_line};
+use crate::eval::{
+    DISABLED_PREFIX, EvalContext, EvalError, LineResult, ParsedLine,
+    evaluate_all_lines_with_context, evaluate_expression, evaluate_prefix, parse_line,
+};
+use crate::eval::{directive, list, shell};
+use crate::storage::{self, Revision, recovery};
+use crate::ui::{
+    AppTheme, HELP_CONTENT_HEIGHT, Hit, ScreenMap, format_result, result_comments, share_text,
+};
+
+/// How long a notification stays visible in the command bar.
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(2);
+
+/// How long a result stays highlighted after an edit elsewhere changed it.
+const RESULT_CHANGE_DURATION: Duration = Duration::from_millis(1500);
+
+/// Number of earlier results remembered for each line.
+const RESULT_HISTORY_LEN: usize = 10;
+
+/// Number of lines scrolled by `PageUp`/`PageDown` in the help overlay.
+const HELP_PAGE_SIZE: usize = 10;
+
+/// How often the buffer is checked for edits to record for a crash report.
+const RECOVERY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A transient message shown in the command bar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    /// The message text.
+    pub message: String,
+    /// When the notification was first displayed, `None` while still queued.
+    pub shown_at: Option<Instant>,
+}
 
 /// Application state for Crabculator.
+#[allow(clippy::struct_excessive_bools)]
 pub struct App {
     pub running: bool,
     pub buffer: Buffer,
@@ -17,21 +87,364 @@ pub struct App {
     pub memory_pane_left: bool,
     pub theme: AppTheme,
     pub last_edit_time: Option<Instant>,
+    pub pending_recovery: Option<Vec<String>>,
+    /// The revision of the buffer last recorded for a crash report.
+    pub recovery_revision: Option<Revision>,
+    /// When the buffer was last checked for edits to record.
+    pub recovery_checked: Option<Instant>,
+    pub notifications: VecDeque<Notification>,
+    pub inspection: Option<Inspection>,
+    pub explanation: Option<Explanation>,
+    /// The drawn dependency graph between lines, while its popup is open.
+    pub dependency_graph: Option<Vec<String>>,
+    /// The lines of the `:version` popup, while it is open.
+    pub version_info: Option<Vec<String>>,
+    /// The `:new` template picker, while it is open.
+    pub template_picker: Option<TemplatePicker>,
+    /// The `table` result shown in the table popup, while it is open.
+    pub table_view: Option<TableView>,
+    /// The differences shown in the `:diff` popup, if open.
+    pub diff_view: Option<DiffView>,
+    pub bracket_colors: bool,
+    pub align_decimals: bool,
+    /// Whether the memory pane shows how long each line took to evaluate.
+    pub show_timings: bool,
+    /// Results of the previous render, compared against to find changes.
+    pub previous_results: Vec<LineResult>,
+    /// When each recently changed result last changed, by line index.
+    pub result_changes: HashMap<usize, Instant>,
+    /// Earlier results of each line, most recent first, by line index.
+    pub result_history: HashMap<usize, VecDeque<LineResult>>,
+    /// The line and history entry shown by the last history step, if any.
+    pub history_position: Option<(usize, usize)>,
+    /// Expressions pinned to the watch panel.
+    pub watches: Vec<String>,
+    /// User settings, persisted separately from the buffer.
+    pub settings: Settings,
+    /// The terminal theme, used while the theme setting is `auto`.
+    pub detected_theme: AppTheme,
+    /// The `:set` command being typed while the settings popup is open.
+    pub settings_prompt: Option<String>,
+    /// Whether the first-run welcome banner is shown.
+    pub welcome_visible: bool,
+    /// The override being typed while what-if mode is active.
+    pub what_if_prompt: Option<String>,
+    /// The line number being typed while the go-to-line prompt is open.
+    pub go_to_line_prompt: Option<String>,
+    /// The numbers pasted with `:paste`, while their name is asked for.
+    pub list_prompt: Option<ListPrompt>,
+    /// Where the panels were last drawn, for mapping mouse clicks to lines.
+    pub screen: ScreenMap,
+    /// Named bookmarks on buffer lines, saved with the buffer.
+    pub bookmarks: Bookmarks,
+    /// Whether the last action typed a character, so that a run of typing
+    /// is undone at once.
+    pub typing: bool,
+    /// The line the cursor is on, while it has been edited since the cursor
+    /// came to it. Its shell commands are not run until it is left, so that
+    /// half-typed commands do not run.
+    pub edited_row: Option<usize>,
+    /// Variables assigned by the prelude worksheet, in definition order.
+    /// Every evaluation of the buffer starts with them defined.
+    pub prelude: Vec<(String, f64)>,
+    /// The project folder the settings and prelude are read from, while it
+    /// is not trusted. Shell commands stay off and the prelude is skipped
+    /// until `:trust` is run.
+    pub untrusted_project: Option<PathBuf>,
+    /// Where the buffer is loaded from and saved to.
+    pub persistence: Persistence,
+    /// The passphrase the buffer file is encrypted with, if any.
+    pub passphrase: Option<storage::Passphrase>,
+    /// The revision of the buffer file as last loaded or saved, `None` if
+    /// there was no such file.
+    pub disk_revision: Option<Revision>,
+    /// When the buffer file was last modified as of the last check, to
+    /// notice changes made on disk without reading it.
+    pub disk_modified: Option<SystemTime>,
+    /// Lines of a buffer file changed on disk by someone else, while the
+    /// user decides whether to reload them or overwrite them.
+    pub pending_conflict: Option<Vec<String>>,
+    /// Whether the user is asked to confirm quitting, because the buffer
+    /// has changes that would be lost.
+    pub quit_pending: bool,
+    /// Timings and recent events for the debug overlay, shown with
+    /// `--debug`.
+    pub debug: Option<DebugInfo>,
+}
+
+/// Loads the saved bookmarks for a buffer of `line_count` lines, or none if
+/// they cannot be read.
+fn load_bookmarks(line_count: usize) -> Bookmarks {
+    storage::load_bookmarks().map_or_else(
+        |_| Bookmarks::default(),
+        |entries| {
+            Bookmarks::from_entries(
+                entries
+                    .iter()
+                    .map(|(name, line)| (name.as_str(), line.as_str())),
+                line_count,
+            )
+        },
+    )
+}
+
+/// Reads the buffer file at `path`, recovering it if it is not valid UTF-8.
+///
+/// Invalid characters are replaced and a file that is not text at all
+/// starts an empty buffer, with a notice saying where the original was
+/// copied to.
+///
+/// # Errors
+/// Returns an error if the file cannot be read, or an `InvalidData` error if
+/// it is encrypted and the passphrase is wrong or missing, or if it is not
+/// valid UTF-8 and cannot be copied aside.
+fn read_or_recover_buffer(
+    path: &Path,
+    passphrase: Option<&storage::Passphrase>,
+) -> io::Result<(Buffer, Option<String>)> {
+    match storage::read_buffer(path, passphrase) {
+        Ok(state) => Ok((
+            state.map_or_else(Buffer::new, |state| Buffer::from_lines(state.buffer_lines)),
+            None,
+        )),
+        Err(e) if e.kind() == ErrorKind::InvalidData && !storage::is_encrypted_file(path) => {
+            let recovered = storage::recover_buffer(path).map_err(|copy_error| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{e}, and it could not be copied aside: {copy_error}"),
+                )
+            })?;
+            let backup = recovered.backup.display();
+            Ok(match recovered.state {
+                Some(state) => (
+                    Buffer::from_lines(state.buffer_lines),
+                    Some(format!(
+                        "The buffer file was not valid UTF-8: unreadable characters were replaced, the original is kept at {backup}"
+                    )),
+                ),
+                None => (
+                    Buffer::new(),
+                    Some(format!(
+                        "The buffer file was not text and was set aside at {backup}; starting empty"
+                    )),
+                ),
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Defines each of `variables` in `context`.
+fn define_variables(context: &mut EvalContext, variables: &[(String, f64)]) {
+    for (name, value) in variables {
+        context.set_variable(name, *value);
+    }
+}
+
+/// Returns the value of `result` as shown, without the name of an assigned
+/// variable.
+fn result_value(result: &LineResult) -> Option<String> {
+    match result {
+        LineResult::Assignment { value, .. } | LineResult::Total(value) => {
+            format_result(&LineResult::Value(*value))
+        }
+        LineResult::Warning { result, .. } => result_value(result),
+        _ => format_result(result),
+    }
+}
+
+/// Parses a `FROM-TO` range of 1-based line numbers, such as `3-10`, into
+/// 0-based rows.
+fn parse_line_range(range: &str) -> Option<Range<usize>> {
+    let (from, to) = range.split_once('-')?;
+    let (from, to) = (from.parse::<usize>().ok()?, to.parse::<usize>().ok()?);
+    (1 <= from && from <= to).then(|| from - 1..to)
+}
+
+/// Applies `edit` to a command prompt, if it is open.
+fn edit_prompt<R>(prompt: &mut Option<String>, edit: impl FnOnce(&mut String) -> R) {
+    if let Some(prompt) = prompt {
+        edit(prompt);
+    }
 }
 
 impl App {
     /// Creates a new application instance with running state set to true.
     ///
-    /// Attempts to load persisted buffer lines from disk. Variables are not
-    /// loaded; they are computed from evaluating the buffer lines.
+    /// Attempts to load persisted buffer lines and bookmarks from disk.
+    /// Variables are not loaded; they are computed from evaluating the
+    /// buffer lines.
     /// Detects terminal theme at startup, falling back to Dark if detection fails.
+    /// Saved settings are loaded and applied, falling back to the defaults.
+    /// If a crash report from a previous session exists, its buffer is offered
+    /// for restoration via `pending_recovery`.
+    ///
+    /// On first launch, when no state file exists yet, the sample worksheet is
+    /// loaded and saved, and the welcome banner is shown.
     #[must_use]
     pub fn new() -> Self {
-        let buffer = match storage::load() {
-            Ok(Some(state)) => Buffer::from_lines(state.buffer_lines),
-            Ok(None) | Err(_) => Buffer::new(),
+        Self::with_settings(Self::restore_state(None).unwrap_or_else(|_| {
+            // An encrypted state file is neither shown nor overwritten.
+            Self {
+                persistence: Persistence::None,
+                ..Self::with_buffer(Buffer::new())
+            }
+        }))
+    }
+
+    /// Creates an application instance whose buffer is loaded from and saved
+    /// to `persistence`, decrypted and encrypted with `passphrase` if one is
+    /// given.
+    ///
+    /// Like `new`, settings and the prelude are loaded. Bookmarks, the
+    /// cursor and scroll position of the last session and crash recovery
+    /// belong to the state file and are only used with `Persistence::State`. The buffer of an encrypted file is never
+    /// written to a crash report.
+    ///
+    /// # Errors
+    /// Returns an error if a worksheet file exists but cannot be read, or if
+    /// the buffer file is encrypted and `passphrase` is missing or wrong.
+    pub fn open(
+        persistence: Persistence,
+        passphrase: Option<storage::Passphrase>,
+    ) -> io::Result<Self> {
+        let app = match &persistence {
+            Persistence::State => {
+                let mut app = Self::restore_state(passphrase.as_ref())?;
+                if !app.welcome_visible
+                    && let Ok(position) = storage::load_position()
+                {
+                    app.restore_position(position);
+                }
+                app
+            }
+            Persistence::File(path) => {
+                let (buffer, notice) = read_or_recover_buffer(path, passphrase.as_ref())?;
+                let mut app = Self::with_buffer(buffer);
+                if let Some(notice) = notice {
+                    app.notify(notice);
+                }
+                app
+            }
+            Persistence::None => Self::with_buffer(Buffer::new()),
+        };
+        let mut app = Self::with_settings(Self {
+            persistence,
+            passphrase,
+            ..app
+        });
+        app.record_for_recovery();
+        Ok(app)
+    }
+
+    /// Loads the buffer, bookmarks and pending crash recovery of the state
+    /// file, or the sample worksheet on first launch.
+    ///
+    /// A state file that is not valid UTF-8 is recovered as far as possible.
+    /// Any other state file that cannot be read starts an empty buffer,
+    /// unless it is encrypted.
+    fn restore_state(passphrase: Option<&storage::Passphrase>) -> io::Result<Self> {
+        let path = storage::state_file();
+        let first_run = path.as_ref().is_some_and(|path| !path.exists());
+        let mut notice = None;
+        let buffer = match &path {
+            Some(path) if first_run => {
+                let state = storage::PersistedState::new(
+                    SAMPLE_WORKSHEET.iter().map(ToString::to_string).collect(),
+                );
+                let _ = storage::write_buffer(&state, path, passphrase);
+                Buffer::from_lines(state.buffer_lines)
+            }
+            Some(path) => match read_or_recover_buffer(path, passphrase) {
+                Ok((buffer, recovered)) => {
+                    notice = recovered;
+                    buffer
+                }
+                Err(e)
+                    if storage::is_encrypted_file(path) || e.kind() == ErrorKind::InvalidData =>
+                {
+                    return Err(e);
+                }
+                Err(_) => Buffer::new(),
+            },
+            None => Buffer::new(),
+        };
+        if passphrase.is_none() {
+            recovery::record_buffer(buffer.lines());
+        }
+
+        let bookmarks = load_bookmarks(buffer.line_count());
+
+        let pending_recovery = match recovery::load_recovery() {
+            Ok(Some(state)) if !state.buffer_lines.is_empty() => Some(state.buffer_lines),
+            Ok(_) | Err(_) => None,
+        };
+
+        let mut app = Self {
+            bookmarks,
+            pending_recovery,
+            welcome_visible: first_run,
+            ..Self::with_buffer(buffer)
+        };
+        if let Some(notice) = notice {
+            app.notify(notice);
+        }
+        Ok(app)
+    }
+
+    /// Loads and applies the saved settings and the prelude, detects the
+    /// terminal theme, and notes the revision of the buffer file.
+    fn with_settings(app: Self) -> Self {
+        let settings = storage::load_settings().map_or_else(
+            |_| Settings::default(),
+            |entries| {
+                Settings::from_entries(
+                    entries
+                        .iter()
+                        .map(|(key, value)| (key.as_str(), value.as_str())),
+                )
+            },
+        );
+
+        let mut app = Self {
+            detected_theme: AppTheme::detect(),
+            settings,
+            untrusted_project: storage::untrusted_project(),
+            ..app
         };
+        app.disk_modified = app
+            .buffer_path()
+            .and_then(|path| storage::modified_time(&path));
+        app.disk_revision = app.read_buffer_file().map(|state| state.revision());
+        app.apply_settings();
+        app.load_prelude();
+        app
+    }
+
+    /// Evaluates the prelude worksheet, unless it belongs to a project that
+    /// is not trusted.
+    fn load_prelude(&mut self) {
+        if self.untrusted_project.is_some() {
+            return;
+        }
+        if let Ok(Some(lines)) = storage::load_prelude()
+            && let Some((row, error)) = self.extend_prelude(&lines)
+        {
+            self.notify(format!(
+                "Prelude line {}: [{}] {}",
+                row + 1,
+                error.code(),
+                error.message()
+            ));
+        }
+    }
 
+    /// Creates an application instance around the given buffer.
+    ///
+    /// Unlike `new`, nothing is loaded from disk and the terminal is not
+    /// queried for its theme, which makes this suitable for tests.
+    #[must_use]
+    pub fn with_buffer(buffer: Buffer) -> Self {
         Self {
             running: true,
             buffer,
@@ -41,553 +454,4474 @@ impl App {
             help_visible: false,
             help_scroll_offset: 0,
             memory_pane_left: true,
-            theme: AppTheme::detect(),
+            theme: AppTheme::Dark,
             last_edit_time: None,
+            pending_recovery: None,
+            recovery_revision: None,
+            recovery_checked: None,
+            notifications: VecDeque::new(),
+            inspection: None,
+            explanation: None,
+            dependency_graph: None,
+            version_info: None,
+            template_picker: None,
+            table_view: None,
+            diff_view: None,
+            bracket_colors: true,
+            align_decimals: true,
+            show_timings: false,
+            previous_results: Vec::new(),
+            result_changes: HashMap::new(),
+            result_history: HashMap::new(),
+            history_position: None,
+            watches: Vec::new(),
+            settings: Settings::default(),
+            detected_theme: AppTheme::Dark,
+            settings_prompt: None,
+            welcome_visible: false,
+            what_if_prompt: None,
+            go_to_line_prompt: None,
+            list_prompt: None,
+            screen: ScreenMap::default(),
+            bookmarks: Bookmarks::default(),
+            typing: false,
+            edited_row: None,
+            prelude: Vec::new(),
+            untrusted_project: None,
+            persistence: Persistence::State,
+            passphrase: None,
+            disk_revision: None,
+            disk_modified: None,
+            pending_conflict: None,
+            quit_pending: false,
+            debug: None,
         }
     }
 
-    /// Quit the application.
-    pub const fn quit(&mut self) {
-        self.running = false;
+    /// Returns the view that currently receives key presses.
+    #[must_use]
+    pub const fn mode(&self) -> Mode {
+        if self.pending_recovery.is_some() {
+            Mode::Recovery
+        } else if self.pending_conflict.is_some() {
+            Mode::Conflict
+        } else if self.quit_pending {
+            Mode::ConfirmQuit
+        } else if self.inspection.is_some() {
+            Mode::Inspection
+        } else if self.explanation.is_some() {
+            Mode::Explanation
+        } else if self.dependency_graph.is_some() {
+            Mode::Dependencies
+        } else if self.version_info.is_some() {
+            Mode::Version
+        } else if self.template_picker.is_some() {
+            Mode::Templates
+        } else if self.table_view.is_some() {
+            Mode::Table
+        } else if self.diff_view.is_some() {
+            Mode::Diff
+        } else if self.help_visible {
+            Mode::Help
+        } else if self.settings_prompt.is_some() {
+            Mode::Settings
+        } else if self.go_to_line_prompt.is_some() {
+            Mode::GoToLine
+        } else if self.list_prompt.is_some() {
+            Mode::PasteList
+        } else if self.what_if_prompt.is_some() {
+            Mode::WhatIf
+        } else if self.welcome_visible {
+            Mode::Welcome
+        } else if self.buffer.in_block_mode() {
+            Mode::Block
+        } else if self.buffer.selection().is_some() {
+            Mode::Selection
+        } else {
+            Mode::Editor
+        }
     }
 
-    /// Saves the current state to disk.
+    /// Handles a terminal event.
     ///
-    /// Persists the buffer lines to the state file. Variables are not saved;
-    /// they are computed from evaluating the buffer lines on next load.
-    /// Errors are silently ignored (state persistence is best-effort).
-    pub fn save_state(&self) {
-        let state =
-            storage::PersistedState::new(self.buffer.lines().iter().map(String::clone).collect());
-        let _ = storage::save(&state);
+    /// Key presses are mapped to an `Action` for the current mode and
+    /// applied; other events need no handling beyond a redraw.
+    ///
+    /// # Returns
+    /// The effects the event loop must perform.
+    pub fn handle_event(&mut self, event: &Event) -> Vec<Effect> {
+        match event {
+            Event::Key(key) => self.handle_key(*key),
+            Event::Mouse(mouse)
+                if mouse.kind == MouseEventKind::Down(MouseButton::Left)
+                    && matches!(
+                        self.mode(),
+                        Mode::Editor | Mode::Selection | Mode::Block | Mode::Welcome
+                    ) =>
+            {
+                self.apply(Action::Click {
+                    column: mouse.column,
+                    row: mouse.row,
+                })
+            }
+            _ => Vec::new(),
+        }
     }
 
-    /// Resets the editor to its initial empty state.
-    pub fn clear_all(&mut self) {
-        self.buffer.clear();
-        self.context.clear();
-        self.scroll_offset = 0;
-        self.horizontal_scroll_offset = 0;
-        self.help_visible = false;
-        self.help_scroll_offset = 0;
+    /// Handles a single key press.
+    ///
+    /// # Returns
+    /// The effects the event loop must perform.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Vec<Effect> {
+        let mode = self.mode();
+        let Some(action) = action_for_key(mode, key) else {
+            return Vec::new();
+        };
+        tracing::debug!(?mode, action = ?Redacted(action), "key");
+        if let Some(debug) = &mut self.debug {
+            debug.record(format!("{action:?}"));
+        }
+        self.apply(action)
     }
 
-    /// Adjusts scroll offset to keep cursor within visible area.
+    /// Performs a single action.
     ///
-    /// Called after cursor movement to ensure the cursor row is visible.
-    /// If cursor is above visible area, scrolls up. If cursor is below
-    /// visible area, scrolls down to make it visible.
+    /// Every change to the text can be undone; a run of typed characters is
+    /// undone at once. Changes that would take the buffer past the limits
+    /// are refused with a notification.
     ///
-    /// # Arguments
-    /// * `visible_height` - The number of visible lines in the viewport
-    pub const fn adjust_scroll(&mut self, visible_height: usize) {
-        if visible_height == 0 {
-            return;
+    /// # Returns
+    /// The effects the event loop must perform.
+    pub fn apply(&mut self, action: Action) -> Vec<Effect> {
+        if action == Action::Undo {
+            self.typing = false;
+            return self.undo();
         }
-
-        let cursor_row = self.buffer.cursor().row();
-
-        if cursor_row < self.scroll_offset {
-            self.scroll_offset = cursor_row;
+        let typing = matches!(action, Action::InsertChar(_));
+        let limits = self.settings.limits;
+        if typing && self.buffer.current_line().chars().count() >= limits.line_length {
+            self.notify(format!("Not typed: {}", limits.line_length_message()));
+            return Vec::new();
         }
-
-        if cursor_row >= self.scroll_offset + visible_height {
-            self.scroll_offset = cursor_row - visible_height + 1;
+        let checkpoint = !(typing && self.typing);
+        if checkpoint {
+            self.buffer.checkpoint();
+        }
+        self.typing = typing;
+        let extent = checkpoint.then(|| self.buffer_extent());
+        let (row, line) = (
+            self.buffer.cursor().row(),
+            self.buffer.current_line().to_string(),
+        );
+        let effects = self.apply_to_selection(action);
+        self.track_edited_row(row, &line);
+        if let Some(message) = extent.and_then(|extent| self.exceeded_limit(extent)) {
+            self.buffer.undo();
+            self.notify(format!("Edit undone: {message}"));
+            return Vec::new();
         }
+        self.buffer.discard_unchanged_checkpoint();
+        effects
     }
 
-    /// Adjusts horizontal scroll offset to keep cursor within visible area.
-    ///
-    /// Called after cursor movement to ensure the cursor column is visible.
-    /// If cursor is before visible area, scrolls left. If cursor is after
-    /// visible area, scrolls right to make it visible.
-    ///
-    /// A margin is used to provide smoother scrolling experience by triggering
-    /// scroll before the cursor reaches the absolute edge.
-    ///
-    /// # Arguments
-    /// * `visible_width` - The number of visible columns in the viewport
-    pub fn adjust_horizontal_scroll(&mut self, visible_width: usize) {
-        if visible_width == 0 {
-            return;
+    /// Notes whether the cursor line, which was `line` on `row` before an
+    /// action, is being edited.
+    fn track_edited_row(&mut self, row: usize, line: &str) {
+        let now = self.buffer.cursor().row();
+        if now != row {
+            self.edited_row = None;
+        } else if self.buffer.current_line() != line {
+            self.edited_row = Some(row);
         }
+    }
 
-        let cursor_col = self.buffer.cursor().col();
-        let margin = visible_width.min(5).saturating_sub(1);
+    /// Starts the shell commands requested while the screen was drawn,
+    /// except those of the line being edited.
+    pub fn start_shell_commands(&self) {
+        let held = self
+            .edited_row
+            .and_then(|row| self.buffer.lines().get(row))
+            .map(|line| shell::commands_in(line))
+            .unwrap_or_default();
+        shell::start_requested_commands(|command| held.iter().any(|held| held == command));
+    }
 
-        if cursor_col < self.horizontal_scroll_offset + margin {
-            self.horizontal_scroll_offset = cursor_col.saturating_sub(margin);
-        }
+    /// Returns the number of lines of the buffer and how many of them are
+    /// longer than the limit.
+    fn buffer_extent(&self) -> (usize, usize) {
+        let line_length = self.settings.limits.line_length;
+        let long_lines = self
+            .buffer
+            .lines()
+            .iter()
+            .filter(|line| line.len() > line_length && line.chars().count() > line_length)
+            .count();
+        (self.buffer.lines().len(), long_lines)
+    }
 
-        if cursor_col >= self.horizontal_scroll_offset + visible_width - margin {
-            self.horizontal_scroll_offset = cursor_col.saturating_sub(visible_width - margin - 1);
+    /// Returns the limit the buffer broke since it had `extent`, if any.
+    ///
+    /// A buffer loaded past the limits may still be edited as long as it
+    /// does not grow further past them.
+    fn exceeded_limit(&self, (lines, long_lines): (usize, usize)) -> Option<String> {
+        let limits = self.settings.limits;
+        let (new_lines, new_long_lines) = self.buffer_extent();
+        if new_lines > limits.lines && new_lines > lines {
+            Some(limits.lines_message())
+        } else if new_long_lines > long_lines {
+            Some(limits.line_length_message())
+        } else {
+            None
         }
     }
 
-    /// Toggles the help overlay visibility.
+    /// Performs an action on the selection, if there is one.
     ///
-    /// When opening the help overlay, resets the scroll offset to 0.
-    pub const fn toggle_help(&mut self) {
-        self.help_visible = !self.help_visible;
-        if self.help_visible {
-            self.help_scroll_offset = 0;
+    /// With a selection, typing replaces the selected text and deleting
+    /// removes it; any other action clears the selection first.
+    fn apply_to_selection(&mut self, action: Action) -> Vec<Effect> {
+        if self.buffer.in_block_mode()
+            && let Some(effects) = self.apply_to_block(action)
+        {
+            return effects;
+        }
+        match action {
+            Action::SelectEnclosing => self.select_enclosing(),
+            Action::ExtractVariable => return self.extract_variable(),
+            Action::InsertChar(c) if self.buffer.selection().is_some() => {
+                self.buffer.replace_selection(c.encode_utf8(&mut [0; 4]));
+                return self.mark_edited();
+            }
+            Action::DeleteBackward | Action::DeleteForward if self.buffer.selection().is_some() => {
+                self.buffer.replace_selection("");
+                return self.mark_edited();
+            }
+            _ => {
+                self.buffer.clear_selection();
+                return self.apply_action(action);
+            }
         }
+        Vec::new()
     }
 
-    /// Closes the help overlay.
-    pub const fn close_help(&mut self) {
-        self.help_visible = false;
+    /// Performs an action that does not depend on the selection.
+    fn apply_action(&mut self, action: Action) -> Vec<Effect> {
+        match action {
+            Action::Quit => return self.request_quit(),
+            Action::ConfirmQuit | Action::CancelQuit => return self.answer_quit(action),
+            Action::Suspend => return vec![Effect::Suspend],
+            Action::ClearAll => {
+                self.clear_all();
+                return self.mark_edited();
+            }
+            Action::InsertChar(c) => {
+                self.buffer.insert_char(c);
+                return self.mark_edited();
+            }
+            Action::InsertNewline => return self.edit_lines(Buffer::insert_newline),
+            Action::DeleteBackward => return self.edit_lines(Buffer::delete_char_before),
+            Action::DeleteForward => return self.edit_lines(Buffer::delete_char_at),
+            Action::MoveLeft => self.move_left(),
+            Action::MoveRight => self.move_right(),
+            Action::MoveUp => self.buffer.move_cursor_up(),
+            Action::MoveDown => self.buffer.move_cursor_down(),
+            Action::MoveLineStart => self.buffer.move_cursor_to_line_start(),
+            Action::MoveLineEnd => self.buffer.move_cursor_to_line_end(),
+            Action::FormatLine | Action::FormatBuffer => return self.apply_format_action(action),
+            Action::CompleteFunction => {
+                if self.complete_function_name() {
+                    return self.mark_edited();
+                }
+            }
+            Action::ToggleLineDisabled => {
+                if self.toggle_line_disabled() {
+                    return self.mark_edited();
+                }
+            }
+            Action::ToggleMemoryPane => return self.toggle_memory_pane_position(),
+            Action::ToggleBracketColors => return self.toggle_bracket_colors(),
+            Action::ToggleDecimalAlignment => return self.toggle_decimal_alignment(),
+            Action::ToggleTimings => self.toggle_timings(),
+            Action::ToggleWatch => self.toggle_watch(),
+            Action::ToggleHelp => self.toggle_help(),
+            Action::CloseHelp => self.close_help(),
+            Action::ScrollHelpUp => self.scroll_help_up(),
+            Action::ScrollHelpDown => self.scroll_help_down(HELP_CONTENT_HEIGHT),
+            Action::PageHelpUp => self.page_help_up(),
+            Action::PageHelpDown => self.page_help_down(),
+            Action::Inspect => self.inspect_current_line(),
+            Action::CloseInspection => self.close_inspection(),
+            Action::Explain => self.explain_current_line(),
+            Action::CloseExplanation => self.close_explanation(),
+            Action::ShowDependencies => self.show_dependencies(),
+            Action::CloseDependencies => self.dependency_graph = None,
+            Action::CloseVersion => self.version_info = None,
+            Action::TemplateUp
+            | Action::TemplateDown
+            | Action::PickTemplate
+            | Action::CloseTemplates => return self.apply_template_action(action),
+            Action::ShowTable => self.show_table(),
+            Action::CloseTable | Action::NextTablePage | Action::PreviousTablePage => {
+                self.apply_table_action(action);
+            }
+            Action::CloseDiff | Action::NextDiffPage | Action::PreviousDiffPage => {
+                self.apply_diff_action(action);
+            }
+            Action::CycleResultHistory => self.cycle_result_history(),
+            Action::RestoreRecovery => self.restore_recovery(),
+            Action::DismissRecovery => self.dismiss_recovery(),
+            Action::ReloadConflict => self.reload_conflict(),
+            Action::OverwriteConflict => self.overwrite_conflict(),
+            Action::OpenSettings
+            | Action::CloseSettings
+            | Action::SettingsChar(_)
+            | Action::SettingsBackspace
+            | Action::RunSettingsCommand
+            | Action::OpenWhatIf
+            | Action::CloseWhatIf
+            | Action::WhatIfChar(_)
+            | Action::WhatIfBackspace
+            | Action::RunWhatIfCommand
+            | Action::OpenGoToLine
+            | Action::CloseGoToLine
+            | Action::GoToLineChar(_)
+            | Action::GoToLineBackspace
+            | Action::RunGoToLine
+            | Action::ClosePasteList
+            | Action::PasteListChar(_)
+            | Action::PasteListBackspace
+            | Action::RunPasteList => return self.apply_prompt_action(action),
+            Action::ShareResults => return self.share_results(),
+            Action::DismissWelcome => self.welcome_visible = false,
+            Action::NextBookmark | Action::PreviousBookmark | Action::JumpToBookmark(_) => {
+                self.apply_bookmark_action(action);
+            }
+            Action::Click { column, row } => return self.click(column, row),
+            Action::ToggleBlock => self.buffer.start_block(),
+            Action::SelectEnclosing
+            | Action::ClearSelection
+            | Action::ExtractVariable
+            | Action::Undo => {}
+        }
+        Vec::new()
     }
 
-    /// Scrolls the help overlay content down by one line.
-    ///
-    /// # Arguments
-    /// * `content_height` - The total height of the help content in lines
-    pub const fn scroll_help_down(&mut self, content_height: usize) {
-        if self.help_scroll_offset < content_height.saturating_sub(1) {
-            self.help_scroll_offset += 1;
+    /// Performs an action of the settings, what-if or go-to-line prompt.
+    fn apply_prompt_action(&mut self, action: Action) -> Vec<Effect> {
+        match action {
+            Action::OpenSettings => self.settings_prompt = Some(String::new()),
+            Action::CloseSettings => self.settings_prompt = None,
+            Action::SettingsChar(c) => {
+                edit_prompt(&mut self.settings_prompt, |prompt| prompt.push(c));
+            }
+            Action::SettingsBackspace => edit_prompt(&mut self.settings_prompt, String::pop),
+            Action::RunSettingsCommand => return self.run_settings_command(),
+            Action::OpenWhatIf => self.what_if_prompt = Some(String::new()),
+            Action::CloseWhatIf => self.close_what_if(),
+            Action::WhatIfChar(c) => edit_prompt(&mut self.what_if_prompt, |prompt| prompt.push(c)),
+            Action::WhatIfBackspace => edit_prompt(&mut self.what_if_prompt, String::pop),
+            Action::RunWhatIfCommand => self.run_what_if_command(),
+            Action::OpenGoToLine => self.go_to_line_prompt = Some(String::new()),
+            Action::CloseGoToLine => self.go_to_line_prompt = None,
+            Action::ClosePasteList => self.list_prompt = None,
+            Action::PasteListChar(c) => {
+                if let Some(prompt) = &mut self.list_prompt {
+                    prompt.name.push(c);
+                }
+            }
+            Action::PasteListBackspace => {
+                if let Some(prompt) = &mut self.list_prompt {
+                    prompt.name.pop();
+                }
+            }
+            Action::RunPasteList => return self.run_paste_list(),
+            Action::GoToLineChar(c) => {
+                edit_prompt(&mut self.go_to_line_prompt, |prompt| prompt.push(c));
+            }
+            Action::GoToLineBackspace => edit_prompt(&mut self.go_to_line_prompt, String::pop),
+            Action::RunGoToLine => {
+                if let Some(line) = self
+                    .go_to_line_prompt
+                    .take()
+                    .and_then(|prompt| prompt.parse().ok())
+                {
+                    self.go_to_line(line);
+                }
+            }
+            _ => {}
         }
+        Vec::new()
     }
 
-    /// Scrolls the help overlay content up by one line.
-    pub const fn scroll_help_up(&mut self) {
-        self.help_scroll_offset = self.help_scroll_offset.saturating_sub(1);
+    /// Moves the cursor to the start of the 1-based line `number`, clamped
+    /// to the buffer. The next render scrolls the line into view.
+    pub fn go_to_line(&mut self, number: usize) {
+        self.buffer.move_cursor_to(number.saturating_sub(1), 0);
     }
 
-    /// Toggles the memory pane position between left and right.
-    pub const fn toggle_memory_pane_position(&mut self) {
-        self.memory_pane_left = !self.memory_pane_left;
+    /// Handles a click: a click in the input panel moves the cursor there, a
+    /// click on a result moves it to the end of the result's line and, with
+    /// the `clickcopy` setting, copies the result.
+    ///
+    /// # Returns
+    /// The effects the event loop must perform.
+    pub fn click(&mut self, column: u16, row: u16) -> Vec<Effect> {
+        let hit = self.screen.hit(
+            column,
+            row,
+            self.scroll_offset,
+            self.horizontal_scroll_offset,
+        );
+        match hit {
+            Some(Hit::Input { line, col }) => self.buffer.move_cursor_to(line, col),
+            Some(Hit::Result(line)) => {
+                self.buffer.move_cursor_to(line, usize::MAX);
+                if self.settings.clickcopy
+                    && let Some(value) = self.result_value(line)
+                {
+                    self.notify(format!("Copied {value}"));
+                    return vec![Effect::CopyResult(line)];
+                }
+            }
+            None => {}
+        }
+        Vec::new()
     }
-}
 
-impl Default for App {
-    fn default() -> Self {
-        Self::new()
+    /// Returns the value last shown for `line`, without the name of an
+    /// assigned variable.
+    #[must_use]
+    pub fn result_value(&self, line: usize) -> Option<String> {
+        self.previous_results.get(line).and_then(result_value)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[test]
-    fn test_app_new_initializes_running() {
-        let app = App::new();
-        assert!(app.running);
+    /// Returns the sum, mean and count of the results of the block's lines
+    /// while block mode spans several lines.
+    #[must_use]
+    pub fn quick_sum(&self) -> Option<QuickSum> {
+        let block = self
+            .buffer
+            .block()
+            .filter(|block| block.bottom > block.top)?;
+        self.previous_results
+            .get(block.top..=block.bottom)
+            .map(QuickSum::of)
     }
 
-    #[test]
-    fn test_app_new_initializes_buffer() {
-        let app = App::new();
-        assert!(app.buffer.line_count() >= 1);
+    /// Moves the cursor left, onto the previous line only if `wrap` is set.
+    fn move_left(&mut self) {
+        if self.settings.wrap || self.buffer.cursor().col() > 0 {
+            self.buffer.move_cursor_left();
+        }
     }
 
-    #[test]
-    fn test_app_new_initializes_context() {
-        let app = App::new();
-        let _ = app.context.extract_variables();
+    /// Moves the cursor right, onto the next line only if `wrap` is set.
+    fn move_right(&mut self) {
+        if self.settings.wrap || self.buffer.cursor().col() < self.buffer.current_line_len() {
+            self.buffer.move_cursor_right();
+        }
     }
 
-    #[test]
-    fn test_app_quit_sets_running_false() {
-        let mut app = App::new();
-        app.quit();
-        assert!(!app.running);
+    /// Applies a buffer edit that may split or join lines, moving bookmarks
+    /// along with their lines.
+    fn edit_lines<R>(&mut self, edit: impl FnOnce(&mut Buffer) -> R) -> Vec<Effect> {
+        let cursor = *self.buffer.cursor();
+        let count = self.buffer.line_count();
+        edit(&mut self.buffer);
+        match self.buffer.line_count().cmp(&count) {
+            // Splitting at the start of a line moves the whole line down.
+            Ordering::Greater if cursor.col() == 0 => self.bookmarks.line_inserted(cursor.row()),
+            Ordering::Greater => self.bookmarks.line_inserted(cursor.row() + 1),
+            Ordering::Less => self.bookmarks.line_removed(self.buffer.cursor().row() + 1),
+            Ordering::Equal => {}
+        }
+        self.mark_edited()
     }
 
-    #[test]
-    fn test_app_new_initializes_scroll_offset_to_zero() {
-        let app = App::new();
-        assert_eq!(app.scroll_offset, 0);
+    /// Performs an action in block mode: typing and deleting edit every line
+    /// of the block, and moving the cursor resizes it. `End` makes edits go
+    /// to the end of each line.
+    ///
+    /// # Returns
+    /// `None` if the action leaves block mode, so it still has to be
+    /// performed.
+    fn apply_to_block(&mut self, action: Action) -> Option<Vec<Effect>> {
+        let col = self.buffer.cursor().col();
+        match action {
+            Action::ToggleBlock => self.buffer.end_block(),
+            Action::InsertChar(c) => {
+                self.buffer.block_insert(c.encode_utf8(&mut [0; 4]));
+                return Some(self.mark_edited());
+            }
+            Action::DeleteBackward | Action::DeleteForward => {
+                let changed = self.buffer.block_delete(action == Action::DeleteForward);
+                if changed {
+                    self.last_edit_time = Some(Instant::now());
+                }
+                return Some(self.save_if(changed));
+            }
+            Action::MoveLeft if col > 0 => self.buffer.move_cursor_left(),
+            Action::MoveRight if col < self.buffer.current_line_len() => {
+                self.buffer.move_cursor_right();
+            }
+            Action::MoveUp => self.buffer.move_cursor_up(),
+            Action::MoveDown => self.buffer.move_cursor_down(),
+            Action::MoveLineStart => self.buffer.move_cursor_to_line_start(),
+            Action::MoveLineEnd => {
+                self.buffer.move_cursor_to_line_end();
+                self.buffer.set_block_to_end(true);
+                return Some(Vec::new());
+            }
+            Action::MoveLeft | Action::MoveRight => {}
+            _ => {
+                self.buffer.end_block();
+                return None;
+            }
+        }
+        if matches!(
+            action,
+            Action::MoveLeft | Action::MoveRight | Action::MoveLineStart
+        ) {
+            self.buffer.set_block_to_end(false);
+        }
+        Some(Vec::new())
     }
 
-    #[test]
-    fn test_app_save_state_extracts_buffer() {
-        let mut app = App::new();
-        app.buffer.insert_char('a');
-        app.buffer.insert_char('b');
-
-        app.save_state();
+    /// Selects the next larger expression around the selection, or around
+    /// the cursor if nothing is selected.
+    fn select_enclosing(&mut self) {
+        let col = self.buffer.cursor().col();
+        let (start, end) = self
+            .buffer
+            .selection()
+            .map_or((col, col), |selection| (selection.start, selection.end));
+        match enclosing_expression(self.buffer.current_line(), start, end) {
+            Some((start, end)) => self.buffer.select(start, end),
+            None => self.notify("No enclosing expression"),
+        }
     }
 
-    #[test]
-    fn test_clear_all_clears_buffer() {
-        let mut app = App::new();
-        app.buffer.insert_char('x');
-        app.buffer.insert_newline();
-        app.buffer.insert_char('y');
-
-        app.clear_all();
-
-        assert_eq!(app.buffer.line_count(), 1);
-        assert_eq!(app.buffer.lines()[0], "");
+    /// Moves the selected expression into a new variable assigned on the
+    /// line above, and puts the variable's name in its place.
+    ///
+    /// The variable is named `v1`, `v2`, ... after the first name no line
+    /// assigns yet.
+    fn extract_variable(&mut self) -> Vec<Effect> {
+        let Some(text) = self.buffer.selected_text().map(str::to_string) else {
+            self.notify("Select an expression first (Ctrl+A)");
+            return Vec::new();
+        };
+        let name = (1..=usize::MAX)
+            .map(|n| format!("v{n}"))
+            .find(|name| !self.buffer_assigns(name) && self.context.get_variable(name).is_none())
+            .unwrap_or_default();
+        let row = self.buffer.cursor().row();
+        self.buffer.insert_line(row, format!("{name} = {text}"));
+        self.bookmarks.line_inserted(row);
+        self.buffer.replace_selection(&name);
+        self.notify(format!("Extracted '{text}' into {name}"));
+        self.mark_edited()
     }
 
-    #[test]
-    fn test_clear_all_resets_cursor() {
-        let mut app = App::new();
-        app.buffer.insert_char('a');
-        app.buffer.insert_newline();
-        app.buffer.insert_char('b');
-
-        app.clear_all();
+    /// Moves the cursor to the next, previous or a numbered bookmark.
+    fn apply_bookmark_action(&mut self, action: Action) {
+        let row = self.buffer.cursor().row();
+        let line = match action {
+            Action::NextBookmark => self.bookmarks.next_after(row),
+            Action::PreviousBookmark => self.bookmarks.previous_before(row),
+            Action::JumpToBookmark(c) => self.bookmarks.get(c.encode_utf8(&mut [0; 4])),
+            _ => return,
+        };
+        self.jump_to_bookmark(line);
+    }
 
-        assert_eq!(app.buffer.cursor().row(), 0);
-        assert_eq!(app.buffer.cursor().col(), 0);
+    /// Moves the cursor to a bookmarked line, or tells the user there is
+    /// none.
+    fn jump_to_bookmark(&mut self, line: Option<usize>) {
+        match line {
+            Some(line) => self.go_to_line(line + 1),
+            None => self.notify("No bookmark"),
+        }
     }
 
-    #[test]
-    fn test_clear_all_clears_context() {
-        let mut app = App::new();
-        app.context.set_variable("x", 42.0);
-        app.context.set_variable("y", 3.15);
+    /// Runs a bookmark command typed in the settings popup: `mark name` sets
+    /// a bookmark on the cursor line, `unmark name` removes one, `'name`
+    /// jumps to one and `marks` lists them.
+    ///
+    /// # Returns
+    /// `None` if `command` is not a bookmark command, otherwise the effects
+    /// the event loop must perform.
+    fn run_bookmark_command(&mut self, command: &str) -> Option<Vec<Effect>> {
+        let (verb, name) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, ""), |(verb, name)| (verb, name.trim()));
+        let row = self.buffer.cursor().row();
+        match verb {
+            "mark" => match self.bookmarks.set(name, row) {
+                Ok(()) => self.notify(format!("Bookmark '{name}' set on line {}", row + 1)),
+                Err(message) => {
+                    self.notify(message);
+                    return Some(Vec::new());
+                }
+            },
+            "unmark" if self.bookmarks.remove(name) => {
+                self.notify(format!("Bookmark '{name}' removed"));
+            }
+            "unmark" => {
+                self.notify(format!("No bookmark '{name}'"));
+                return Some(Vec::new());
+            }
+            "marks" if self.bookmarks.is_empty() => self.notify("No bookmarks"),
+            "marks" => self.notify(format!("Bookmarks: {}", self.bookmarks.list())),
+            _ => {
+                let name = command.strip_prefix('\'')?;
+                match self.bookmarks.get(name) {
+                    Some(line) => {
+                        self.settings_prompt = None;
+                        self.go_to_line(line + 1);
+                    }
+                    None => self.notify(format!("No bookmark '{name}'")),
+                }
+                return Some(Vec::new());
+            }
+        }
+        self.settings_prompt = None;
+        Some(vec![Effect::Save])
+    }
 
-        app.clear_all();
+    /// Copies the worksheet with its results to the clipboard.
+    fn share_results(&mut self) -> Vec<Effect> {
+        self.notify("Copied worksheet to clipboard");
+        vec![Effect::CopyResults]
+    }
 
-        assert!(app.context.get_variable("x").is_none());
-        assert!(app.context.get_variable("y").is_none());
+    /// Undoes the last change to the text, or tells the user there is none.
+    fn undo(&mut self) -> Vec<Effect> {
+        if self.buffer.undo() {
+            self.notify("Undone");
+            self.mark_edited()
+        } else {
+            self.notify("Nothing to undo");
+            Vec::new()
+        }
     }
 
-    #[test]
-    fn test_clear_all_resets_scroll_offset() {
+    /// Runs a line command typed in the settings popup: `sort` orders lines
+    /// alphabetically, `sort value` by their results, and `uniq` removes
+    /// repeated lines. Each takes an optional `FROM-TO` range of line
+    /// numbers and works on the whole worksheet without one.
+    ///
+    /// # Returns
+    /// `None` if `command` is not a line command, otherwise the effects the
+    /// event loop must perform.
+    fn run_line_command(&mut self, command: &str) -> Option<Vec<Effect>> {
+        let mut words = command.split_whitespace();
+        let verb = words
+            .next()
+            .filter(|verb| matches!(*verb, "sort" | "uniq"))?;
+        let mut words = words.peekable();
+        let by_value = verb == "sort" && words.next_if_eq(&"value").is_some();
+        let rows = match (words.next(), words.next()) {
+            (None, _) => 0..self.buffer.line_count(),
+            (Some(range), None) => {
+                let Some(rows) = parse_line_range(range) else {
+                    self.notify(format!("Invalid line range '{range}', expected e.g. 3-10"));
+                    return Some(Vec::new());
+                };
+                rows
+            }
+            (Some(_), Some(_)) => {
+                self.notify("Usage: sort [value] [FROM-TO] or uniq [FROM-TO]");
+                return Some(Vec::new());
+            }
+        };
+        let changed = match verb {
+            "uniq" => {
+                let removed = self.buffer.dedupe_lines(rows);
+                self.notify(format!("Removed {removed} duplicate line(s)"));
+                removed > 0
+            }
+            _ if by_value => {
+                let keys = self.line_numbers(rows.clone());
+                let changed = self.buffer.sort_lines_by_key(rows, &keys);
+                self.notify(if changed {
+                    "Sorted lines by value"
+                } else {
+                    "Lines already sorted"
+                });
+                changed
+            }
+            _ => {
+                let changed = self.buffer.sort_lines(rows);
+                self.notify(if changed {
+                    "Sorted lines"
+                } else {
+                    "Lines already sorted"
+                });
+                changed
+            }
+        };
+        self.settings_prompt = None;
+        if changed {
+            self.last_edit_time = Some(Instant::now());
+        }
+        Some(self.save_if(changed))
+    }
+
+    /// Runs `loadvars FILE` typed in the settings popup, defining the
+    /// variables of a JSON object such as `{"vat": 0.19}` for the worksheet.
+    ///
+    /// # Returns
+    /// `None` if `command` is not `loadvars`, otherwise the effects the event
+    /// loop must perform.
+    fn run_variables_command(&mut self, command: &str) -> Option<Vec<Effect>> {
+        let path = command.strip_prefix("loadvars")?;
+        if !path.is_empty() && !path.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let path = path.trim();
+        if path.is_empty() {
+            self.notify("Usage: loadvars FILE.json");
+            return Some(Vec::new());
+        }
+        let loaded = storage::read_variables_file(path)
+            .map_err(|e| format!("Cannot read {path}: {e}"))
+            .and_then(|text| {
+                self.context
+                    .import_json(&text)
+                    .map_err(|e| format!("Invalid variables in {path}: {e}"))
+            });
+        match loaded {
+            Ok(count) => {
+                self.settings_prompt = None;
+                self.notify(format!("Loaded {count} variable(s) from {path}"));
+            }
+            Err(message) => self.notify(message),
+        }
+        Some(Vec::new())
+    }
+
+    /// Runs `share FILE` typed in the settings popup, writing the worksheet
+    /// with its results to a share file, or `import FILE`, replacing the
+    /// worksheet with the one in a share file.
+    ///
+    /// # Returns
+    /// `None` if `command` is neither, otherwise the effects the event loop
+    /// must perform.
+    fn run_share_command(&mut self, command: &str) -> Option<Vec<Effect>> {
+        let (verb, path) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, ""), |(verb, path)| (verb, path.trim()));
+        if !matches!(verb, "share" | "import") {
+            return None;
+        }
+        if path.is_empty() {
+            self.notify(format!("Usage: {verb} FILE"));
+            return Some(Vec::new());
+        }
+        if verb == "share" {
+            let contents = share::share_file(
+                self.buffer.lines(),
+                &self.previous_results,
+                &self.settings.get("angle").unwrap_or_default(),
+            );
+            match storage::write_share_file(path, &contents) {
+                Ok(()) => {
+                    self.settings_prompt = None;
+                    self.notify(format!("Shared worksheet to {path}"));
+                }
+                Err(e) => self.notify(format!("Cannot write {path}: {e}")),
+            }
+            return Some(Vec::new());
+        }
+        let shared = storage::read_share_file(path)
+            .map_err(|e| format!("Cannot read {path}: {e}"))
+            .and_then(|text| {
+                share::parse_share_file(&text)
+                    .map_err(|e| format!("Invalid share file {path}: {e}"))
+            });
+        let shared = match shared {
+            Ok(shared) => shared,
+            Err(message) => {
+                self.notify(message);
+                return Some(Vec::new());
+            }
+        };
+        self.settings_prompt = None;
+        let count = shared.lines.len();
+        let changed = self.buffer.set_lines(shared.lines);
+        self.scroll_offset = 0;
+        self.horizontal_scroll_offset = 0;
+        self.notify(format!("Imported {count} line(s) from {path}"));
+        let mut effects = if changed {
+            self.mark_edited()
+        } else {
+            Vec::new()
+        };
+        if let Some(angle) = shared.angle
+            && self.settings.get("angle").as_ref() != Some(&angle)
+            && self.settings.set("angle", &angle).is_ok()
+        {
+            self.apply_settings();
+            self.notify(format!("Angle unit set to {angle}, as shared"));
+            effects.push(Effect::SaveSettings);
+        }
+        Some(effects)
+    }
+
+    /// Runs `new NAME` typed in the settings popup, replacing the worksheet
+    /// with a template, or `new` to choose one in the template picker.
+    ///
+    /// # Returns
+    /// `None` if `command` is not `new`, otherwise the effects the event loop
+    /// must perform.
+    fn run_new_command(&mut self, command: &str) -> Option<Vec<Effect>> {
+        let name = command.strip_prefix("new")?;
+        if !name.is_empty() && !name.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let name = name.trim();
+        if name.is_empty() {
+            let mut names: Vec<String> = BUILTIN_TEMPLATES
+                .iter()
+                .map(|(name, _)| (*name).to_string())
+                .chain(storage::template_names())
+                .collect();
+            names.sort();
+            names.dedup();
+            self.settings_prompt = None;
+            self.template_picker = Some(TemplatePicker { names, selected: 0 });
+            return Some(Vec::new());
+        }
+        match self.start_template(name) {
+            Ok(effects) => {
+                self.settings_prompt = None;
+                Some(effects)
+            }
+            Err(message) => {
+                self.notify(message);
+                Some(Vec::new())
+            }
+        }
+    }
+
+    /// Opens the table popup for the `table` result of the cursor line.
+    fn show_table(&mut self) {
+        match self.previous_results.get(self.buffer.cursor().row()) {
+            Some(LineResult::Table(table)) => self.table_view = Some(TableView::new(table.clone())),
+            _ => self.notify("No table on this line"),
+        }
+    }
+
+    /// Performs an action of the table popup.
+    fn apply_table_action(&mut self, action: Action) {
+        let Some(view) = &mut self.table_view else {
+            return;
+        };
+        match action {
+            Action::NextTablePage => view.next_page(),
+            Action::PreviousTablePage => view.previous_page(),
+            _ => self.table_view = None,
+        }
+    }
+
+    /// Reformats the current line or the whole buffer.
+    fn apply_format_action(&mut self, action: Action) -> Vec<Effect> {
+        let changed = if action == Action::FormatLine {
+            self.format_current_line()
+        } else {
+            self.format_buffer()
+        };
+        self.save_if(changed)
+    }
+
+    /// Performs an action of the `:diff` popup.
+    fn apply_diff_action(&mut self, action: Action) {
+        let Some(view) = &mut self.diff_view else {
+            return;
+        };
+        match action {
+            Action::NextDiffPage => view.next_page(),
+            Action::PreviousDiffPage => view.previous_page(),
+            _ => self.diff_view = None,
+        }
+    }
+
+    /// Performs an action of the template picker.
+    fn apply_template_action(&mut self, action: Action) -> Vec<Effect> {
+        let Some(picker) = &mut self.template_picker else {
+            return Vec::new();
+        };
+        match action {
+            Action::TemplateUp => picker.up(),
+            Action::TemplateDown => picker.down(),
+            Action::PickTemplate => {
+                let name = picker.chosen().map(str::to_string);
+                self.template_picker = None;
+                if let Some(name) = name {
+                    return self.start_template(&name).unwrap_or_else(|message| {
+                        self.notify(message);
+                        Vec::new()
+                    });
+                }
+            }
+            _ => self.template_picker = None,
+        }
+        Vec::new()
+    }
+
+    /// Replaces the worksheet with the template `name`, from a template
+    /// file or a built-in template. The change can be undone.
+    ///
+    /// # Errors
+    /// Returns a message if there is no such template or its file cannot be
+    /// read.
+    fn start_template(&mut self, name: &str) -> Result<Vec<Effect>, String> {
+        let lines = match storage::load_template(name) {
+            Ok(Some(lines)) => lines,
+            Ok(None) => templates::builtin_template(name)
+                .ok_or_else(|| format!("Unknown template '{name}'"))?,
+            Err(e) => return Err(format!("Cannot read template '{name}': {e}")),
+        };
+        let changed = self.buffer.set_lines(lines);
+        self.scroll_offset = 0;
+        self.horizontal_scroll_offset = 0;
+        self.notify(format!("New worksheet from template '{name}'"));
+        Ok(if changed {
+            self.mark_edited()
+        } else {
+            Vec::new()
+        })
+    }
+
+    /// Runs `q` or `quit` typed in the settings popup.
+    ///
+    /// # Returns
+    /// `None` if `command` is not `q`, otherwise the effects the event loop
+    /// must perform.
+    fn run_quit_command(&mut self, command: &str) -> Option<Vec<Effect>> {
+        if !matches!(command, "q" | "quit") {
+            return None;
+        }
+        self.settings_prompt = None;
+        Some(self.request_quit())
+    }
+
+    /// Runs `version` typed in the settings popup, opening the popup with
+    /// the version, build and file locations.
+    ///
+    /// # Returns
+    /// `None` if `command` is not `version`, otherwise the effects the event
+    /// loop must perform.
+    fn run_version_command(&mut self, command: &str) -> Option<Vec<Effect>> {
+        if command != "version" {
+            return None;
+        }
+        self.settings_prompt = None;
+        self.version_info = Some(version::version_lines(&self.persistence));
+        Some(Vec::new())
+    }
+
+    /// Runs `diff` typed in the settings popup, opening the popup with the
+    /// differences from the saved buffer file, or `diff FILE` for the
+    /// differences from another worksheet file.
+    ///
+    /// # Returns
+    /// `None` if `command` is not `diff`, otherwise the effects the event
+    /// loop must perform.
+    fn run_diff_command(&mut self, command: &str) -> Option<Vec<Effect>> {
+        let path = command.strip_prefix("diff")?;
+        if !path.is_empty() && !path.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let path = path.trim();
+        let (source, other) = if path.is_empty() {
+            (
+                "the saved version".to_string(),
+                self.read_buffer_file()
+                    .ok_or_else(|| "No saved version to compare with".to_string()),
+            )
+        } else {
+            (
+                path.to_string(),
+                storage::read_buffer(&storage::paths::expand_home(path), None)
+                    .map_err(|e| format!("Cannot read {path}: {e}"))
+                    .and_then(|state| state.ok_or_else(|| format!("No file {path}"))),
+            )
+        };
+        let other = match other {
+            Ok(other) => other.buffer_lines,
+            Err(message) => {
+                self.notify(message);
+                return Some(Vec::new());
+            }
+        };
+        let evaluate = |lines: &[String]| {
+            evaluate_all_lines_with_context(
+                lines.iter().map(String::as_str),
+                &mut self.evaluation_context(),
+            )
+        };
+        let lines = diff::diff_lines(
+            &source,
+            &other,
+            &evaluate(&other),
+            self.buffer.lines(),
+            &evaluate(self.buffer.lines()),
+        );
+        self.settings_prompt = None;
+        self.diff_view = Some(DiffView::new(lines));
+        Some(Vec::new())
+    }
+
+    /// Runs `trust` typed in the settings popup, trusting the project the
+    /// settings are read from so that they may turn on shell commands and
+    /// its prelude is evaluated.
+    ///
+    /// # Returns
+    /// `None` if `command` is not `trust`, otherwise the effects the event
+    /// loop must perform.
+    fn run_trust_command(&mut self, command: &str) -> Option<Vec<Effect>> {
+        if command != "trust" {
+            return None;
+        }
+        self.settings_prompt = None;
+        let Some(project) = self.untrusted_project.clone() else {
+            self.notify("Nothing to trust: the settings are not a project's");
+            return Some(Vec::new());
+        };
+        if let Err(e) = storage::trust_project(&project) {
+            self.notify(format!("Could not trust {}: {e}", project.display()));
+            return Some(Vec::new());
+        }
+        self.untrusted_project = None;
+        self.apply_settings();
+        self.notify(format!("Trusted {}", project.display()));
+        self.load_prelude();
+        Some(Vec::new())
+    }
+
+    /// Runs `paste` typed in the settings popup, reading the clipboard to
+    /// turn a column of numbers into a list variable.
+    ///
+    /// # Returns
+    /// `None` if `command` is not `paste`, otherwise the effects the event
+    /// loop must perform.
+    fn run_paste_command(&mut self, command: &str) -> Option<Vec<Effect>> {
+        if command != "paste" {
+            return None;
+        }
+        self.settings_prompt = None;
+        Some(vec![Effect::ReadClipboard])
+    }
+
+    /// Takes the clipboard contents read for `:paste`, asking for a name
+    /// for them if they are one number per line.
+    pub fn paste_column(&mut self, contents: io::Result<String>) {
+        let values = contents
+            .map_err(|e| format!("Cannot read the clipboard: {e}"))
+            .and_then(|text| {
+                list::parse_column(&text, self.settings.limits.list)
+                    .map_err(|e| format!("The clipboard is not a column of numbers: {e}"))
+            });
+        match values {
+            Ok(values) => self.list_prompt = Some(ListPrompt::new(values)),
+            Err(message) => self.notify(message),
+        }
+    }
+
+    /// Inserts the line assigning the pasted numbers to the name typed in
+    /// the prompt, above the cursor line or in its place if it is empty.
+    fn run_paste_list(&mut self) -> Vec<Effect> {
+        let Some(prompt) = &self.list_prompt else {
+            return Vec::new();
+        };
+        let name = prompt.name.trim().to_string();
+        if let Err(message) = paste::check_list_name(&name) {
+            self.notify(message);
+            return Vec::new();
+        }
+        let line = list::list_line(&name, &prompt.values);
+        let limits = self.settings.limits;
+        if line.chars().count() > limits.line_length {
+            self.notify(limits.line_length_message());
+            return Vec::new();
+        }
+        let count = prompt.values.len();
+        self.list_prompt = None;
+        let row = self.buffer.cursor().row();
+        if self.buffer.current_line().trim().is_empty() {
+            self.buffer.set_line(row, line);
+        } else {
+            self.buffer.insert_line(row, line);
+            self.bookmarks.line_inserted(row);
+        }
+        self.notify(format!("Pasted {count} numbers into {name}"));
+        self.mark_edited()
+    }
+
+    /// Runs `profile NAME` typed in the settings popup, or `profile` to list
+    /// the available profiles.
+    ///
+    /// # Returns
+    /// `None` if `command` is not `profile`, otherwise the effects the event
+    /// loop must perform.
+    fn run_profile_command(&mut self, command: &str) -> Option<Vec<Effect>> {
+        let name = command.strip_prefix("profile")?;
+        if !name.is_empty() && !name.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let name = name.trim();
+        if name.is_empty() {
+            let mut names: Vec<String> = BUILTIN_PROFILES
+                .iter()
+                .map(|(name, _)| (*name).to_string())
+                .chain(storage::profile_names())
+                .collect();
+            names.sort();
+            names.dedup();
+            self.notify(format!("Profiles: {}", names.join(", ")));
+            return Some(Vec::new());
+        }
+        let before = self.settings.clone();
+        match self.apply_profile(name) {
+            Ok(message) => {
+                self.settings_prompt = None;
+                self.notify(message);
+            }
+            Err(message) => self.notify(message),
+        }
+        Some(if self.settings == before {
+            Vec::new()
+        } else {
+            vec![Effect::SaveSettings]
+        })
+    }
+
+    /// Applies the profile `name`: a profile file in the profiles directory,
+    /// or else a built-in profile.
+    ///
+    /// Its settings are changed as `:set` would, and its variables are
+    /// defined for the worksheet. Nothing changes if an entry is invalid.
+    ///
+    /// # Returns
+    /// A confirmation such as `Profile 'finance': 3 setting(s), 0 variable(s)`.
+    ///
+    /// # Errors
+    /// Returns a message for the user if there is no such profile, or if a
+    /// setting or variable of it is invalid.
+    pub fn apply_profile(&mut self, name: &str) -> Result<String, String> {
+        let entries = match storage::load_profile(name) {
+            Ok(Some(entries)) => entries,
+            Ok(None) => profiles::builtin_profile(name)
+                .ok_or_else(|| format!("Unknown profile '{name}'"))?,
+            Err(e) => return Err(format!("Cannot read profile '{name}': {e}")),
+        };
+        let (settings, variables): (Vec<_>, Vec<_>) = entries
+            .iter()
+            .partition(|(key, _)| profiles::is_setting_key(key));
+
+        let mut changed = self.settings.clone();
+        for (key, value) in &settings {
+            changed
+                .set(key, value)
+                .map_err(|e| format!("Profile '{name}': {e}"))?;
+        }
+        let mut values = Vec::with_capacity(variables.len());
+        for (key, expression) in &variables {
+            let value = match parse_line(&format!("{key} = {expression}")) {
+                ParsedLine::Assignment { name: assigned, .. } if assigned == *key => {
+                    evaluate_expression(expression, &self.context)
+                        .map_err(|e| format!("Profile '{name}': {key}: {}", e.message()))?
+                }
+                _ => return Err(format!("Profile '{name}': invalid variable name '{key}'")),
+            };
+            values.push((key, value));
+        }
+
+        self.settings = changed;
+        self.apply_settings();
+        for (key, value) in values {
+            self.context.set_variable(key, value);
+        }
+        Ok(format!(
+            "Profile '{name}': {} setting(s), {} variable(s)",
+            settings.len(),
+            variables.len()
+        ))
+    }
+
+    /// Evaluates the prelude worksheet `lines` after any earlier prelude
+    /// lines and keeps the variables they assign, which are then defined
+    /// before the buffer in every evaluation.
+    ///
+    /// # Returns
+    /// The index in `lines` and the error of the first line with an error.
+    pub fn extend_prelude(&mut self, lines: &[String]) -> Option<(usize, EvalError)> {
+        let mut context = self.fresh_context();
+        let results =
+            evaluate_all_lines_with_context(lines.iter().map(String::as_str), &mut context);
+        self.prelude = context
+            .iter()
+            .filter(|(name, _)| !is_math_constant(name))
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        define_variables(&mut self.context, &self.prelude);
+        results
+            .into_iter()
+            .enumerate()
+            .find_map(|(row, result)| match result {
+                LineResult::Error(error) if !error.is_warning() => Some((row, error)),
+                _ => None,
+            })
+    }
+
+    /// Returns a context for a fresh pass over the prelude: the constants,
+    /// the modes of the settings, and the earlier prelude variables.
+    fn fresh_context(&self) -> EvalContext {
+        let mut context = EvalContext::new();
+        context.set_angle_mode(self.settings.angle_mode);
+        context.set_division_mode(self.settings.division);
+        context.set_significant_figures_mode(self.settings.sigfigs);
+        context.set_rounding(self.settings.rounding);
+        context.set_engineering_prefixes(self.settings.prefixes);
+        context.set_format_presets(self.settings.format_presets());
+        context.set_limits(self.settings.limits);
+        define_variables(&mut context, &self.prelude);
+        context
+    }
+
+    /// Returns a copy of the context the buffer is shown with, to evaluate
+    /// it again outside of drawing, so that the results are the ones shown.
+    ///
+    /// It holds the prelude, profile and `loadvars` variables along with the
+    /// settings; only the what-if overrides, which are not saved, are left
+    /// out.
+    fn evaluation_context(&self) -> EvalContext {
+        let mut context = self.context.clone();
+        context.clear_overrides();
+        context
+    }
+
+    /// Evaluates the worksheet and returns the numeric results of the lines
+    /// in `rows`.
+    fn line_numbers(&self, rows: Range<usize>) -> Vec<Option<f64>> {
+        let mut context = self.evaluation_context();
+        let results = evaluate_all_lines_with_context(
+            self.buffer.lines().iter().map(String::as_str),
+            &mut context,
+        );
+        rows.map(|row| results.get(row).and_then(LineResult::number))
+            .collect()
+    }
+
+    /// Records a buffer edit for error debouncing and requests a save.
+    fn mark_edited(&mut self) -> Vec<Effect> {
+        self.last_edit_time = Some(Instant::now());
+        self.save_if(true)
+    }
+
+    /// Requests a save if the buffer changed and autosave is on.
+    fn save_if(&self, changed: bool) -> Vec<Effect> {
+        if changed && self.settings.autosave {
+            vec![Effect::Save]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Runs the `:set` command typed in the settings popup.
+    ///
+    /// The outcome is shown as a notification. A successful command clears
+    /// the prompt and requests the settings be saved; a failed one keeps the
+    /// text so it can be corrected. A line number such as `:42` closes the
+    /// popup and jumps to that line; bookmark commands are run by
+    /// `run_bookmark_command`.
+    fn run_settings_command(&mut self) -> Vec<Effect> {
+        let Some(command) = self.settings_prompt.clone() else {
+            return Vec::new();
+        };
+        let trimmed = command.trim();
+        let trimmed = trimmed.strip_prefix(':').unwrap_or(trimmed);
+        if let Ok(line) = trimmed.parse() {
+            self.settings_prompt = None;
+            self.go_to_line(line);
+            return Vec::new();
+        }
+        if let Some(effects) = self
+            .run_bookmark_command(trimmed)
+            .or_else(|| self.run_line_command(trimmed))
+            .or_else(|| self.run_variables_command(trimmed))
+            .or_else(|| self.run_share_command(trimmed))
+            .or_else(|| self.run_new_command(trimmed))
+            .or_else(|| self.run_profile_command(trimmed))
+            .or_else(|| self.run_quit_command(trimmed))
+            .or_else(|| self.run_version_command(trimmed))
+            .or_else(|| self.run_diff_command(trimmed))
+            .or_else(|| self.run_paste_command(trimmed))
+            .or_else(|| self.run_trust_command(trimmed))
+        {
+            return effects;
+        }
+        let before = self.settings.clone();
+        match self.settings.run_command(&command) {
+            Ok(message) => {
+                self.notify(message);
+                self.settings_prompt = Some(String::new());
+                if self.settings != before {
+                    self.apply_settings();
+                    if self.settings.shell && !before.shell && self.untrusted_project.is_some() {
+                        self.notify(
+                            "Shell commands stay off until the project is trusted with :trust",
+                        );
+                    }
+                    return vec![Effect::SaveSettings];
+                }
+            }
+            Err(message) => self.notify(message),
+        }
+        Vec::new()
+    }
+
+    /// Applies the `name = value` override typed in the what-if prompt.
+    ///
+    /// The value may be any expression over the current variables. Only
+    /// variables assigned somewhere in the buffer can be overridden.
+    pub fn run_what_if_command(&mut self) {
+        let Some(command) = self.what_if_prompt.clone() else {
+            return;
+        };
+        let ParsedLine::Assignment {
+            name, expression, ..
+        } = parse_line(&command)
+        else {
+            self.notify("Type name = value to override a variable");
+            return;
+        };
+        if !self.buffer_assigns(&name) {
+            self.notify(format!("'{name}' is not assigned in the buffer"));
+            return;
+        }
+        match evaluate_expression(&expression, &self.context) {
+            Ok(value) => {
+                self.context.set_override(&name, value);
+                let shown = format_result(&LineResult::Assignment { name, value });
+                self.notify(format!("What-if: {}", shown.unwrap_or_default()));
+                self.what_if_prompt = Some(String::new());
+            }
+            Err(e) => self.notify(e.message().to_string()),
+        }
+    }
+
+    /// Leaves what-if mode, dropping every override.
+    pub fn close_what_if(&mut self) {
+        self.what_if_prompt = None;
+        if !self.context.overrides().is_empty() {
+            self.context.clear_overrides();
+            self.notify("What-if overrides reset");
+        }
+    }
+
+    /// Returns the worksheet with the results last shown, formatted for
+    /// pasting into a chat message or an issue.
+    #[must_use]
+    pub fn share_text(&self) -> String {
+        share_text(self.buffer.lines(), &self.previous_results)
+    }
+
+    /// Returns `true` if some line of the buffer assigns `name`.
+    fn buffer_assigns(&self, name: &str) -> bool {
+        self.buffer
+            .lines()
+            .iter()
+            .any(|line| match parse_line(line) {
+                ParsedLine::Assignment { name: assigned, .. } => assigned == name,
+                ParsedLine::MultipleAssignment { names, .. } => names.iter().any(|n| n == name),
+                _ => false,
+            })
+    }
+
+    /// Brings the theme and evaluation context in line with the settings.
+    pub fn apply_settings(&mut self) {
+        self.theme = self.settings.theme.unwrap_or(self.detected_theme);
+        self.memory_pane_left = self.settings.pane_left;
+        self.bracket_colors = self.settings.brackets;
+        self.align_decimals = self.settings.align;
+        self.context.set_angle_mode(self.settings.angle_mode);
+        self.context.set_division_mode(self.settings.division);
+        self.context
+            .set_engineering_prefixes(self.settings.prefixes);
+        shell::set_shell_commands(self.settings.shell && self.untrusted_project.is_none());
+        self.context
+            .set_format_presets(self.settings.format_presets());
+        self.context
+            .set_significant_figures_mode(self.settings.sigfigs);
+        self.context.set_rounding(self.settings.rounding);
+        self.context.set_limits(self.settings.limits);
+    }
+
+    /// Saves the settings to disk.
+    ///
+    /// Like the buffer, settings are saved best-effort and a failure is
+    /// reported as a notification.
+    pub fn save_settings(&mut self) {
+        if let Err(e) = storage::save_settings(&self.settings.entries()) {
+            self.notify(format!("Could not save settings: {e}"));
+        }
+    }
+
+    /// Quit the application.
+    pub const fn quit(&mut self) {
+        self.running = false;
+    }
+
+    /// Quits, or asks for confirmation first if the buffer has changes that
+    /// would be lost.
+    fn request_quit(&mut self) -> Vec<Effect> {
+        if self.has_unsaved_changes() {
+            self.quit_pending = true;
+            return Vec::new();
+        }
+        self.leave()
+    }
+
+    /// Answers the prompt confirming to quit: `ConfirmQuit` quits and any
+    /// other action keeps editing.
+    fn answer_quit(&mut self, action: Action) -> Vec<Effect> {
+        self.quit_pending = false;
+        if action == Action::ConfirmQuit {
+            self.leave()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Quits and saves the buffer if it was not saved on every edit.
+    fn leave(&mut self) -> Vec<Effect> {
+        self.quit();
+        // Without autosave, edits are only written when leaving.
+        if self.settings.autosave {
+            Vec::new()
+        } else {
+            vec![Effect::Save]
+        }
+    }
+
+    /// Returns `true` if quitting would lose edits: with `--no-state`
+    /// nothing is saved, so any text typed into the buffer is lost.
+    #[must_use]
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.persistence == Persistence::None
+            && self
+                .buffer
+                .lines()
+                .iter()
+                .any(|line| !line.trim().is_empty())
+    }
+
+    /// Saves the current state to disk.
+    ///
+    /// Persists the buffer lines to the state file, or to the worksheet file
+    /// opened with `--file`; nothing is saved with `--no-state`. Variables are
+    /// not saved; they are computed from evaluating the buffer lines on next
+    /// load. With the `results` setting, each result is written after its
+    /// line as a `# =>` comment. State persistence is best-effort; a failed save is reported as a
+    /// notification rather than interrupting the user.
+    ///
+    /// If another instance changed the file since it was loaded or saved,
+    /// nothing is written and the user is asked whether to reload or
+    /// overwrite it. A conflict found while quitting keeps the application
+    /// open until it is resolved.
+    pub fn save_state(&mut self) {
+        if self.pending_conflict.is_some() {
+            return;
+        }
+        let mut state =
+            storage::PersistedState::new(self.buffer.lines().iter().map(String::clone).collect())
+                .with_position(self.position());
+        if self.settings.results {
+            let mut context = self.evaluation_context();
+            let results = evaluate_all_lines_with_context(
+                self.buffer.lines().iter().map(String::as_str),
+                &mut context,
+            );
+            state = state.with_results(result_comments(&results));
+        }
+        if let Some(on_disk) = self.read_buffer_file()
+            && Some(on_disk.revision()) != self.disk_revision
+            && on_disk.revision() != state.revision()
+        {
+            self.pending_conflict = Some(on_disk.buffer_lines);
+            self.running = true;
+            return;
+        }
+        let saved = match &self.persistence {
+            Persistence::State => storage::state_file()
+                .ok_or_else(|| io::Error::other("Could not determine state file path"))
+                .and_then(|path| storage::write_buffer(&state, &path, self.passphrase.as_ref())),
+            Persistence::File(path) => {
+                storage::write_buffer(&state, path, self.passphrase.as_ref())
+            }
+            Persistence::None => Ok(()),
+        };
+        match saved {
+            Ok(()) => {
+                tracing::debug!(lines = state.buffer_lines.len(), "saved buffer");
+                self.disk_revision = Some(state.revision());
+                self.disk_modified = self
+                    .buffer_path()
+                    .and_then(|path| storage::modified_time(&path));
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "could not save buffer");
+                self.notify(format!("Could not save state: {e}"));
+            }
+        }
+        self.save_sidecars(state.position);
+        self.record_for_recovery();
+    }
+
+    /// Saves the bookmarks and the position kept next to the state file.
+    ///
+    /// These are written after the buffer and apart from it, so that a
+    /// failure here neither loses the revision of the saved buffer nor
+    /// hides it.
+    fn save_sidecars(&mut self, position: storage::Position) {
+        if self.persistence != Persistence::State {
+            return;
+        }
+        let saved = storage::save_bookmarks(&self.bookmarks.entries())
+            .and_then(|()| storage::save_position(position));
+        if let Err(e) = saved {
+            tracing::warn!(error = %e, "could not save bookmarks or position");
+            self.notify(format!("Could not save bookmarks or position: {e}"));
+        }
+    }
+
+    /// Saves the cursor and scroll position for the next session, when the
+    /// buffer is kept in the state file.
+    ///
+    /// Called when leaving, since moving the cursor does not save the state.
+    pub fn save_position(&self) {
+        if self.persistence != Persistence::State {
+            return;
+        }
+        if let Err(e) = storage::save_position(self.position()) {
+            tracing::warn!(error = %e, "could not save position");
+        }
+    }
+
+    /// Returns the cursor and scroll position in the buffer.
+    #[must_use]
+    pub const fn position(&self) -> storage::Position {
+        let cursor = self.buffer.cursor();
+        storage::Position {
+            cursor_row: cursor.row(),
+            cursor_col: cursor.col(),
+            scroll_offset: self.scroll_offset,
+            horizontal_scroll_offset: self.horizontal_scroll_offset,
+        }
+    }
+
+    /// Moves the cursor and scrolls to `position`, clamped to the buffer.
+    ///
+    /// The view is scrolled further if needed to show the cursor when it is
+    /// next drawn.
+    pub fn restore_position(&mut self, position: storage::Position) {
+        self.buffer
+            .move_cursor_to(position.cursor_row, position.cursor_col);
+        self.scroll_offset = position.scroll_offset.min(self.buffer.cursor().row());
+        self.set_horizontal_scroll(position.horizontal_scroll_offset);
+    }
+
+    /// Returns the path of the file the buffer is saved to, if any.
+    fn buffer_path(&self) -> Option<PathBuf> {
+        self.persistence.path()
+    }
+
+    /// Reads the file the buffer is saved to, if it exists and is readable.
+    fn read_buffer_file(&self) -> Option<storage::PersistedState> {
+        storage::read_buffer(&self.buffer_path()?, self.passphrase.as_ref())
+            .ok()
+            .flatten()
+    }
+
+    /// Records the buffer for a crash report, unless it is kept encrypted.
+    fn record_for_recovery(&mut self) {
+        if self.passphrase.is_none() {
+            recovery::record_buffer(self.buffer.lines());
+            self.recovery_revision = Some(Revision::of(self.buffer.lines()));
+        }
+    }
+
+    /// Records the buffer for a crash report if it was edited since it was
+    /// last recorded, so that edits that are not saved, such as with
+    /// autosave off, are not lost in a crash either.
+    ///
+    /// Called on every pass of the event loop; the buffer is only looked at
+    /// once per `RECOVERY_INTERVAL`.
+    pub fn record_edits_for_recovery(&mut self) {
+        if self.passphrase.is_some()
+            || self
+                .recovery_checked
+                .is_some_and(|checked| checked.elapsed() < RECOVERY_INTERVAL)
+        {
+            return;
+        }
+        self.recovery_checked = Some(Instant::now());
+        if self.recovery_revision != Some(Revision::of(self.buffer.lines())) {
+            self.record_for_recovery();
+        }
+    }
+
+    /// Offers to reload the buffer file if it was changed on disk since it
+    /// was last loaded or saved, such as by another instance or an editor.
+    ///
+    /// Called on every pass of the event loop; the file is only read when
+    /// its modification time changed.
+    pub fn check_disk_changes(&mut self) {
+        if self.pending_conflict.is_some() {
+            return;
+        }
+        let Some(path) = self.buffer_path() else {
+            return;
+        };
+        let modified = storage::modified_time(&path);
+        if modified == self.disk_modified {
+            return;
+        }
+        self.disk_modified = modified;
+        let Some(on_disk) = self.read_buffer_file() else {
+            return;
+        };
+        if Some(on_disk.revision()) == self.disk_revision {
+            return;
+        }
+        if on_disk.revision() == Revision::of(self.buffer.lines()) {
+            self.disk_revision = Some(on_disk.revision());
+        } else {
+            tracing::info!("buffer file changed on disk");
+            self.pending_conflict = Some(on_disk.buffer_lines);
+        }
+    }
+
+    /// Replaces the buffer with the lines another instance saved, discarding
+    /// the edits made here.
+    pub fn reload_conflict(&mut self) {
+        let Some(lines) = self.pending_conflict.take() else {
+            return;
+        };
+        self.disk_revision = Some(Revision::of(&lines));
+        self.disk_modified = self
+            .buffer_path()
+            .and_then(|path| storage::modified_time(&path));
+        self.buffer = Buffer::from_lines(lines);
+        if self.persistence == Persistence::State {
+            self.bookmarks = load_bookmarks(self.buffer.line_count());
+        }
+        self.context.clear();
+        define_variables(&mut self.context, &self.prelude);
+        self.scroll_offset = 0;
+        self.horizontal_scroll_offset = 0;
+        self.record_for_recovery();
+        self.notify("Reloaded the buffer from disk");
+    }
+
+    /// Saves the buffer over the lines another instance saved.
+    pub fn overwrite_conflict(&mut self) {
+        let Some(lines) = self.pending_conflict.take() else {
+            return;
+        };
+        self.disk_revision = Some(Revision::of(&lines));
+        self.save_state();
+        self.notify("Overwrote the buffer on disk");
+    }
+
+    /// Queues a transient notification for display in the command bar.
+    pub fn notify(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        // Notifications can quote the worksheet, such as a copied result,
+        // which must not reach the log of an encrypted worksheet.
+        if self.passphrase.is_none() {
+            tracing::debug!(%message, "notification");
+        } else {
+            tracing::debug!("notification");
+        }
+        if self
+            .notifications
+            .back()
+            .is_some_and(|n| n.message == message)
+        {
+            return;
+        }
+        self.notifications.push_back(Notification {
+            message,
+            shown_at: None,
+        });
+    }
+
+    /// Returns the notification that should currently be displayed.
+    ///
+    /// Expired notifications are removed from the queue; the next queued
+    /// notification starts its display timer when it becomes active.
+    pub fn active_notification(&mut self) -> Option<&str> {
+        self.active_notification_at(Instant::now())
+    }
+
+    /// Records which results differ from the previous render and returns the
+    /// line indices that should currently be highlighted as changed.
+    pub fn track_result_changes(&mut self, results: &[LineResult]) -> Vec<usize> {
+        self.track_result_changes_at(results, Instant::now())
+    }
+
+    /// Like `track_result_changes`, at the given instant.
+    ///
+    /// The line being edited is never highlighted, since its change is
+    /// expected. Inserting or removing lines shifts every result below, so the
+    /// comparison only runs while the line count stays the same.
+    pub fn track_result_changes_at(&mut self, results: &[LineResult], now: Instant) -> Vec<usize> {
+        if results.len() == self.previous_results.len() {
+            let edited = self.buffer.cursor().row();
+            for (index, (result, previous)) in
+                results.iter().zip(&self.previous_results).enumerate()
+            {
+                if index != edited && result != previous {
+                    self.result_changes.insert(index, now);
+                }
+            }
+        } else {
+            self.result_changes.clear();
+        }
+        self.record_result_history(results);
+        self.previous_results = results.to_vec();
+
+        self.result_changes.retain(|_, changed_at| {
+            now.saturating_duration_since(*changed_at) < RESULT_CHANGE_DURATION
+        });
+        let mut lines: Vec<usize> = self.result_changes.keys().copied().collect();
+        lines.sort_unstable();
+        lines
+    }
+
+    /// Remembers the results that `results` replaces in each line's history.
+    ///
+    /// Empty and failed results are not remembered. Inserting or removing
+    /// lines shifts the lines below the cursor, so their histories are
+    /// dropped.
+    fn record_result_history(&mut self, results: &[LineResult]) {
+        if results.len() != self.previous_results.len() {
+            let row = self.buffer.cursor().row();
+            self.result_history.retain(|&index, _| index < row);
+            self.history_position = None;
+            return;
+        }
+        for (index, (result, previous)) in results.iter().zip(&self.previous_results).enumerate() {
+            if result == previous || format_result(previous).is_none() {
+                continue;
+            }
+            let history = self.result_history.entry(index).or_default();
+            history.push_front(previous.clone());
+            history.truncate(RESULT_HISTORY_LEN);
+            self.history_position = None;
+        }
+    }
+
+    /// Steps back through the earlier results of the line under the cursor.
+    ///
+    /// Each call shows the next older result in a notification, returning to
+    /// the current result after the oldest one.
+    pub fn cycle_result_history(&mut self) {
+        let row = self.buffer.cursor().row();
+        let count = self.result_history.get(&row).map_or(0, VecDeque::len);
+        if count == 0 {
+            self.notify("No earlier results for this line");
+            return;
+        }
+        let step = match self.history_position {
+            Some((line, step)) if line == row => (step + 1) % (count + 1),
+            _ => 1,
+        };
+        self.history_position = Some((row, step));
+
+        let message = if step == 0 {
+            let current = self.previous_results.get(row).and_then(format_result);
+            format!("Current result: {}", current.unwrap_or_default())
+        } else {
+            let previous = format_result(&self.result_history[&row][step - 1]);
+            format!(
+                "Earlier result {step} of {count}: {}",
+                previous.unwrap_or_default()
+            )
+        };
+        self.notify(message);
+    }
+
+    /// Returns the notification that should be displayed at the given instant.
+    pub fn active_notification_at(&mut self, now: Instant) -> Option<&str> {
+        while self
+            .notifications
+            .front()
+            .and_then(|n| n.shown_at)
+            .is_some_and(|shown_at| {
+                now.saturating_duration_since(shown_at) >= NOTIFICATION_DURATION
+            })
+        {
+            self.notifications.pop_front();
+        }
+
+        let front = self.notifications.front_mut()?;
+        front.shown_at.get_or_insert(now);
+        Some(front.message.as_str())
+    }
+
+    /// Replaces the buffer with the lines recovered from a crash report.
+    ///
+    /// The recovery file is removed and the restored buffer is saved.
+    pub fn restore_recovery(&mut self) {
+        if let Some(lines) = self.pending_recovery.take() {
+            let count = lines.len();
+            self.buffer = Buffer::from_lines(lines);
+            self.bookmarks.clear();
+            self.context.clear();
+            define_variables(&mut self.context, &self.prelude);
+            self.scroll_offset = 0;
+            self.horizontal_scroll_offset = 0;
+            let _ = recovery::discard_recovery();
+            self.save_state();
+            self.notify(format!("Restored {count} line(s) from crash report"));
+        }
+    }
+
+    /// Discards the pending crash recovery and removes the recovery file.
+    pub fn dismiss_recovery(&mut self) {
+        if self.pending_recovery.take().is_some() {
+            let _ = recovery::discard_recovery();
+            self.notify("Crash report discarded");
+        }
+    }
+
+    /// Resets the editor to its initial empty state.
+    pub fn clear_all(&mut self) {
+        self.welcome_visible = false;
+        self.buffer.clear();
+        self.bookmarks.clear();
+        self.context.clear();
+        define_variables(&mut self.context, &self.prelude);
+        self.scroll_offset = 0;
+        self.horizontal_scroll_offset = 0;
+        self.help_visible = false;
+        self.help_scroll_offset = 0;
+        self.notify("Cleared");
+    }
+
+    /// Adjusts scroll offset to keep cursor within visible area.
+    ///
+    /// Called after cursor movement to ensure the cursor row is visible.
+    /// If cursor is above visible area, scrolls up. If cursor is below
+    /// visible area, scrolls down to make it visible.
+    ///
+    /// # Arguments
+    /// * `visible_height` - The number of visible lines in the viewport
+    pub const fn adjust_scroll(&mut self, visible_height: usize) {
+        if visible_height == 0 {
+            return;
+        }
+
+        let cursor_row = self.buffer.cursor().row();
+
+        if cursor_row < self.scroll_offset {
+            self.scroll_offset = cursor_row;
+        }
+
+        if cursor_row >= self.scroll_offset + visible_height {
+            self.scroll_offset = cursor_row - visible_height + 1;
+        }
+    }
+
+    /// Adjusts horizontal scroll offset to keep cursor within visible area.
+    ///
+    /// Called after cursor movement to ensure the cursor column is visible.
+    /// If cursor is before visible area, scrolls left. If cursor is after
+    /// visible area, scrolls right to make it visible.
+    ///
+    /// A margin is used to provide smoother scrolling experience by triggering
+    /// scroll before the cursor reaches the absolute edge.
+    ///
+    /// # Arguments
+    /// * `visible_width` - The number of visible columns in the viewport
+    pub fn adjust_horizontal_scroll(&mut self, visible_width: usize) {
+        if visible_width == 0 {
+            return;
+        }
+
+        let cursor_col = self.buffer.cursor().col();
+        let margin = visible_width.min(5).saturating_sub(1);
+
+        if cursor_col < self.horizontal_scroll_offset + margin {
+            self.set_horizontal_scroll(cursor_col.saturating_sub(margin));
+        }
+
+        if cursor_col >= self.horizontal_scroll_offset + visible_width - margin {
+            self.set_horizontal_scroll(cursor_col.saturating_sub(visible_width - margin - 1));
+        }
+    }
+
+    /// Scrolls horizontally to byte `offset`, moved back to a character
+    /// boundary of the cursor line.
+    fn set_horizontal_scroll(&mut self, offset: usize) {
+        self.horizontal_scroll_offset = floor_boundary(self.buffer.current_line(), offset);
+    }
+
+    /// Toggles the help overlay visibility.
+    ///
+    /// When opening the help overlay, resets the scroll offset to 0.
+    pub const fn toggle_help(&mut self) {
+        self.help_visible = !self.help_visible;
+        if self.help_visible {
+            self.help_scroll_offset = 0;
+        }
+    }
+
+    /// Closes the help overlay.
+    pub const fn close_help(&mut self) {
+        self.help_visible = false;
+    }
+
+    /// Scrolls the help overlay content down by one line.
+    ///
+    /// # Arguments
+    /// * `content_height` - The total height of the help content in lines
+    pub const fn scroll_help_down(&mut self, content_height: usize) {
+        if self.help_scroll_offset < content_height.saturating_sub(1) {
+            self.help_scroll_offset += 1;
+        }
+    }
+
+    /// Scrolls the help overlay content up by one line.
+    pub const fn scroll_help_up(&mut self) {
+        self.help_scroll_offset = self.help_scroll_offset.saturating_sub(1);
+    }
+
+    /// Scrolls the help overlay content up by a page.
+    fn page_help_up(&mut self) {
+        for _ in 0..HELP_PAGE_SIZE {
+            self.scroll_help_up();
+        }
+    }
+
+    /// Scrolls the help overlay content down by a page.
+    fn page_help_down(&mut self) {
+        for _ in 0..HELP_PAGE_SIZE {
+            self.scroll_help_down(HELP_CONTENT_HEIGHT);
+        }
+    }
+
+    /// Reformats the line under the cursor in canonical form.
+    ///
+    /// Returns `true` if the line changed.
+    pub fn format_current_line(&mut self) -> bool {
+        let row = self.buffer.cursor().row();
+        let changed = self.format_row(row);
+        if changed {
+            self.last_edit_time = Some(Instant::now());
+        } else {
+            self.notify("Line already formatted");
+        }
+        changed
+    }
+
+    /// Reformats every line in the buffer in canonical form.
+    ///
+    /// Lines that cannot be parsed are left untouched.
+    /// Returns `true` if any line changed.
+    pub fn format_buffer(&mut self) -> bool {
+        let changed = (0..self.buffer.line_count())
+            .filter(|&row| self.format_row(row))
+            .count();
+        if changed > 0 {
+            self.last_edit_time = Some(Instant::now());
+        }
+        self.notify(format!("Formatted {changed} line(s)"));
+        changed > 0
+    }
+
+    /// Reformats a single buffer row, returning `true` if it changed.
+    fn format_row(&mut self, row: usize) -> bool {
+        match format_line(&self.buffer.lines()[row], &self.context) {
+            Some(formatted) if formatted != self.buffer.lines()[row] => {
+                self.buffer.set_line(row, formatted);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Disables the current line by prefixing it with `# `, or re-enables it.
+    ///
+    /// The prefix goes after the line's indentation, and the cursor stays on
+    /// the same character. Returns `true` if the buffer changed.
+    pub fn toggle_line_disabled(&mut self) -> bool {
+        let line = self.buffer.current_line();
+        let col = self.buffer.cursor().col();
+        let indent = line.len() - line.trim_start().len();
+        if indent == line.len() {
+            self.notify("Nothing to disable on this line");
+            return false;
+        }
+
+        let (head, body) = line.split_at(indent);
+        let (text, col) = body.strip_prefix(DISABLED_PREFIX).map_or_else(
+            || {
+                let col = if col >= indent { col + 2 } else { col };
+                (format!("{head}{DISABLED_PREFIX} {body}"), col)
+            },
+            |rest| {
+                let rest = rest.strip_prefix(' ').unwrap_or(rest);
+                let removed = body.len() - rest.len();
+                let col = col.saturating_sub(removed).max(indent.min(col));
+                (format!("{head}{rest}"), col)
+            },
+        );
+
+        let row = self.buffer.cursor().row();
+        self.buffer.set_line(row, text);
+        self.buffer.move_cursor_to_col(col);
+        true
+    }
+
+    /// Completes the function name before the cursor.
+    ///
+    /// A unique match is completed including its opening parenthesis and its
+    /// signature is shown as a notification. With several matches, the name
+    /// is extended to their longest common prefix and the candidates are listed.
+    ///
+    /// Returns `true` if the buffer changed.
+    pub fn complete_function_name(&mut self) -> bool {
+        let line = self.buffer.current_line();
+        let col = self.buffer.cursor().col().min(line.len());
+        let start = line[..col]
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(0, |i| i + 1);
+        let prefix = line[start..col].to_string();
+        if prefix.is_empty() || prefix.starts_with(|c: char| c.is_ascii_digit()) {
+            return false;
+        }
+
+        let matches = complete_function(&prefix);
+        let insertion = match matches.as_slice() {
+            [] => {
+                self.notify(format!("No function starts with '{prefix}'"));
+                return false;
+            }
+            [only] => {
+                let insertion = format!("{}(", &only.name[prefix.len()..]);
+                self.notify(format!("{} — {}", only.signature, only.description));
+                insertion
+            }
+            [first, rest @ ..] => {
+                let common = rest.iter().fold(first.name, |common, f| {
+                    let len = common
+                        .bytes()
+                        .zip(f.name.bytes())
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    &common[..len]
+                });
+                let names: Vec<&str> = matches.iter().map(|f| f.name).collect();
+                self.notify(names.join(", "));
+                common[prefix.len()..].to_string()
+            }
+        };
+
+        for c in insertion.chars() {
+            self.buffer.insert_char(c);
+        }
+        !insertion.is_empty()
+    }
+
+    /// Opens the inspection popup for the line under the cursor.
+    ///
+    /// Lines above the cursor are evaluated first so that variables defined
+    /// earlier in the buffer are available. If the line has no value, a
+    /// notification is shown instead.
+    pub fn inspect_current_line(&mut self) {
+        let context = self.context_before_cursor();
+        self.inspection = inspect_line(self.buffer.current_line(), &context);
+        if self.inspection.is_none() {
+            self.notify("Nothing to inspect on this line");
+        }
+    }
+
+    /// Closes the inspection popup.
+    pub fn close_inspection(&mut self) {
+        self.inspection = None;
+    }
+
+    /// Opens the explain popup with the step-by-step reduction of the cursor line.
+    ///
+    /// If the line cannot be parsed, a notification is shown instead.
+    pub fn explain_current_line(&mut self) {
+        let context = self.context_before_cursor();
+        self.explanation = explain_line(self.buffer.current_line(), &context);
+        if self.explanation.is_none() {
+            self.notify("Nothing to explain on this line");
+        }
+    }
+
+    /// Closes the explain popup.
+    pub fn close_explanation(&mut self) {
+        self.explanation = None;
+    }
+
+    /// Returns the intermediate result of the cursor line while its tail is
+    /// still being typed, such as 8 for `5 + 3 +`, or `None` if the line is
+    /// complete or has no valid prefix.
+    #[must_use]
+    pub fn ghost_result(&self) -> Option<f64> {
+        let (line, _) = directive::split_format_directive(
+            self.buffer.current_line(),
+            self.context.format_presets(),
+        );
+        let (ParsedLine::Expression(expression) | ParsedLine::Assignment { expression, .. }) =
+            parse_line(line)
+        else {
+            return None;
+        };
+        evaluate_prefix(&expression, &self.context_before_cursor())
+    }
+
+    /// Opens the popup showing which lines feed which other lines.
+    ///
+    /// If no line uses a variable assigned on another line, a notification
+    /// is shown instead.
+    pub fn show_dependencies(&mut self) {
+        let tree = dependency_tree_lines(self.buffer.lines(), &self.context);
+        if tree.is_empty() {
+            self.notify("No dependencies between lines");
+        } else {
+            self.dependency_graph = Some(tree);
+        }
+    }
+
+    /// Builds a context with the variables defined on the lines above the cursor.
+    fn context_before_cursor(&self) -> EvalContext {
+        let row = self.buffer.cursor().row();
+        let mut context = self.fresh_context();
+        evaluate_all_lines_with_context(
+            self.buffer.lines()[..row].iter().map(String::as_str),
+            &mut context,
+        );
+        context
+    }
+
+    /// Toggles depth-based coloring of parentheses in the editor, and
+    /// keeps the choice in the `brackets` setting.
+    pub fn toggle_bracket_colors(&mut self) -> Vec<Effect> {
+        self.bracket_colors = !self.bracket_colors;
+        self.settings.brackets = self.bracket_colors;
+        let state = if self.bracket_colors { "on" } else { "off" };
+        self.notify(format!("Bracket colors {state}"));
+        vec![Effect::SaveSettings]
+    }
+
+    /// Toggles lining up the decimal points of results in the memory pane,
+    /// and keeps the choice in the `align` setting.
+    pub fn toggle_decimal_alignment(&mut self) -> Vec<Effect> {
+        self.align_decimals = !self.align_decimals;
+        self.settings.align = self.align_decimals;
+        let state = if self.align_decimals { "on" } else { "off" };
+        self.notify(format!("Decimal alignment {state}"));
+        vec![Effect::SaveSettings]
+    }
+
+    /// Toggles the per-line evaluation timings in the memory pane.
+    pub fn toggle_timings(&mut self) {
+        self.show_timings = !self.show_timings;
+        let state = if self.show_timings { "on" } else { "off" };
+        self.notify(format!("Evaluation timing {state}"));
+    }
+
+    /// Pins the current line to the watch panel, or unpins it if already pinned.
+    ///
+    /// An assignment pins its variable name, so the panel follows the variable
+    /// wherever it is reassigned; an expression pins its text.
+    pub fn toggle_watch(&mut self) {
+        let watch = match parse_line(self.buffer.current_line()) {
+            ParsedLine::Assignment { name, .. } => name,
+            ParsedLine::Expression(expression) => expression,
+            ParsedLine::Empty
+            | ParsedLine::SectionStart(_)
+            | ParsedLine::SectionEnd
+            | ParsedLine::Total
+            | ParsedLine::Disabled
+            | ParsedLine::MultipleAssignment { .. }
+            | ParsedLine::Unset(_)
+            | ParsedLine::ClearVariables => {
+                self.notify("Nothing to pin on this line");
+                return;
+            }
+        };
+
+        if let Some(index) = self.watches.iter().position(|w| *w == watch) {
+            self.watches.remove(index);
+            self.notify(format!("Unpinned {watch}"));
+        } else {
+            self.notify(format!("Pinned {watch}"));
+            self.watches.push(watch);
+        }
+    }
+
+    /// Toggles the memory pane position between left and right, and keeps
+    /// the choice in the `pane` setting.
+    pub fn toggle_memory_pane_position(&mut self) -> Vec<Effect> {
+        self.memory_pane_left = !self.memory_pane_left;
+        self.settings.pane_left = self.memory_pane_left;
+        let side = if self.memory_pane_left {
+            "left"
+        } else {
+            "right"
+        };
+        self.notify(format!("Memory pane moved {side}"));
+        vec![Effect::SaveSettings]
+    }
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::limits::Limits;
+    use crate::eval::{ErrorCode, evaluate_all_lines};
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    #[test]
+    fn test_app_new_initializes_running() {
+        let app = App::new();
+        assert!(app.running);
+    }
+
+    #[test]
+    fn test_app_new_initializes_buffer() {
+        let app = App::new();
+        assert!(app.buffer.line_count() >= 1);
+    }
+
+    #[test]
+    fn test_app_new_initializes_context() {
+        let app = App::new();
+        let _ = app.context.extract_variables();
+    }
+
+    #[test]
+    fn test_app_quit_sets_running_false() {
+        let mut app = App::new();
+        app.quit();
+        assert!(!app.running);
+    }
+
+    #[test]
+    fn test_app_new_initializes_scroll_offset_to_zero() {
+        let app = App::new();
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_app_save_state_extracts_buffer() {
+        let mut app = App::new();
+        app.buffer.insert_char('a');
+        app.buffer.insert_char('b');
+
+        app.save_state();
+    }
+
+    #[test]
+    fn test_clear_all_clears_buffer() {
+        let mut app = App::new();
+        app.buffer.insert_char('x');
+        app.buffer.insert_newline();
+        app.buffer.insert_char('y');
+
+        app.clear_all();
+
+        assert_eq!(app.buffer.line_count(), 1);
+        assert_eq!(app.buffer.lines()[0], "");
+    }
+
+    #[test]
+    fn test_clear_all_resets_cursor() {
+        let mut app = App::new();
+        app.buffer.insert_char('a');
+        app.buffer.insert_newline();
+        app.buffer.insert_char('b');
+
+        app.clear_all();
+
+        assert_eq!(app.buffer.cursor().row(), 0);
+        assert_eq!(app.buffer.cursor().col(), 0);
+    }
+
+    #[test]
+    fn test_clear_all_clears_context() {
+        let mut app = App::new();
+        app.context.set_variable("x", 42.0);
+        app.context.set_variable("y", 3.15);
+
+        app.clear_all();
+
+        assert!(app.context.get_variable("x").is_none());
+        assert!(app.context.get_variable("y").is_none());
+    }
+
+    #[test]
+    fn test_clear_all_resets_scroll_offset() {
+        let mut app = App::new();
+        app.scroll_offset = 10;
+
+        app.clear_all();
+
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_adjust_scroll_cursor_above_visible_area() {
+        let mut app = App::new();
+        // Create buffer with 10 lines
+        for i in 0..10 {
+            for c in format!("line {i}").chars() {
+                app.buffer.insert_char(c);
+            }
+            app.buffer.insert_newline();
+        }
+        // Move cursor to line 0 (should be at end due to newlines)
+        for _ in 0..10 {
+            app.buffer.move_cursor_up();
+        }
+        // Set scroll offset to 5 (viewing lines 5-9)
+        app.scroll_offset = 5;
+        // Cursor is at line 0, which is above visible area
+        let visible_height = 5;
+
+        app.adjust_scroll(visible_height);
+
+        // Scroll should adjust to show cursor (line 0)
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_adjust_scroll_cursor_below_visible_area() {
+        let mut app = App::new();
+        // Create buffer with 10 lines
+        for i in 0..10 {
+            for c in format!("line {i}").chars() {
+                app.buffer.insert_char(c);
+            }
+            if i < 9 {
+                app.buffer.insert_newline();
+            }
+        }
+        // Cursor should be at line 9
+        assert_eq!(app.buffer.cursor().row(), 9);
+        // Set scroll offset to 0 (viewing lines 0-4)
+        app.scroll_offset = 0;
+        let visible_height = 5;
+
+        app.adjust_scroll(visible_height);
+
+        // Scroll should adjust so cursor (line 9) is visible
+        // visible area: scroll_offset to scroll_offset + visible_height - 1
+        // line 9 should be visible: scroll_offset + 4 >= 9, so scroll_offset >= 5
+        assert_eq!(app.scroll_offset, 5);
+    }
+
+    #[test]
+    fn test_adjust_scroll_cursor_within_visible_area_no_change() {
+        let mut app = App::new();
+        // Create buffer with 5 lines
+        for i in 0..5 {
+            for c in format!("line {i}").chars() {
+                app.buffer.insert_char(c);
+            }
+            if i < 4 {
+                app.buffer.insert_newline();
+            }
+        }
+        // Move cursor to line 2
+        app.buffer.move_cursor_up();
+        app.buffer.move_cursor_up();
+        assert_eq!(app.buffer.cursor().row(), 2);
+        // Set scroll offset to 0 (viewing lines 0-4)
+        app.scroll_offset = 0;
+        let visible_height = 5;
+
+        app.adjust_scroll(visible_height);
+
+        // No change needed, cursor is within visible area
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_adjust_scroll_with_zero_visible_height() {
+        let mut app = App::new();
+        app.buffer.insert_char('a');
+        app.scroll_offset = 0;
+
+        // visible_height of 0 should not crash
+        app.adjust_scroll(0);
+
+        // Scroll offset should remain unchanged
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_adjust_scroll_single_line_buffer() {
+        let mut app = App::new();
+        app.buffer.insert_char('a');
+        app.scroll_offset = 0;
+        let visible_height = 10;
+
+        app.adjust_scroll(visible_height);
+
+        // Single line, scroll should stay at 0
+        assert_eq!(app.scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_app_new_initializes_help_visible_to_false() {
+        let app = App::new();
+        assert!(!app.help_visible);
+    }
+
+    #[test]
+    fn test_app_new_initializes_help_scroll_offset_to_zero() {
+        let app = App::new();
+        assert_eq!(app.help_scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_app_new_initializes_memory_pane_left_to_true() {
+        let app = App::new();
+        assert!(app.memory_pane_left);
+    }
+
+    #[test]
+    fn test_app_new_detects_theme() {
+        let app = App::new();
+        // Theme detection returns a valid theme (Light or Dark)
+        assert!(matches!(app.theme, AppTheme::Light | AppTheme::Dark));
+    }
+
+    #[test]
+    fn test_toggle_memory_pane_position_changes_from_left_to_right() {
+        let mut app = App::new();
+        assert!(app.memory_pane_left);
+        app.toggle_memory_pane_position();
+        assert!(!app.memory_pane_left);
+    }
+
+    #[test]
+    fn test_toggle_memory_pane_position_changes_from_right_to_left() {
+        let mut app = App::new();
+        app.toggle_memory_pane_position();
+        assert!(!app.memory_pane_left);
+        app.toggle_memory_pane_position();
+        assert!(app.memory_pane_left);
+    }
+
+    #[test]
+    fn test_toggle_help_shows_overlay_when_hidden() {
+        let mut app = App::new();
+        assert!(!app.help_visible);
+
+        app.toggle_help();
+
+        assert!(app.help_visible);
+    }
+
+    #[test]
+    fn test_toggle_help_hides_overlay_when_visible() {
+        let mut app = App::new();
+        app.help_visible = true;
+
+        app.toggle_help();
+
+        assert!(!app.help_visible);
+    }
+
+    #[test]
+    fn test_toggle_help_resets_scroll_offset_when_opening() {
+        let mut app = App::new();
+        app.help_scroll_offset = 5;
+
+        app.toggle_help(); // Open help
+
+        assert_eq!(app.help_scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_close_help_hides_overlay() {
+        let mut app = App::new();
+        app.help_visible = true;
+
+        app.close_help();
+
+        assert!(!app.help_visible);
+    }
+
+    #[test]
+    fn test_close_help_when_already_hidden_stays_hidden() {
+        let mut app = App::new();
+        assert!(!app.help_visible);
+
+        app.close_help();
+
+        assert!(!app.help_visible);
+    }
+
+    #[test]
+    fn test_scroll_help_down_increases_offset() {
+        let mut app = App::new();
+        app.help_visible = true;
+        app.help_scroll_offset = 0;
+
+        app.scroll_help_down(10); // content_height
+
+        assert_eq!(app.help_scroll_offset, 1);
+    }
+
+    #[test]
+    fn test_scroll_help_up_decreases_offset() {
+        let mut app = App::new();
+        app.help_visible = true;
+        app.help_scroll_offset = 5;
+
+        app.scroll_help_up();
+
+        assert_eq!(app.help_scroll_offset, 4);
+    }
+
+    #[test]
+    fn test_scroll_help_up_does_not_go_below_zero() {
+        let mut app = App::new();
+        app.help_visible = true;
+        app.help_scroll_offset = 0;
+
+        app.scroll_help_up();
+
+        assert_eq!(app.help_scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_clear_all_resets_help_state() {
+        let mut app = App::new();
+        app.help_visible = true;
+        app.help_scroll_offset = 5;
+
+        app.clear_all();
+
+        assert!(!app.help_visible);
+        assert_eq!(app.help_scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_adjust_horizontal_scroll_cursor_before_visible_area_scrolls_left() {
+        let mut app = App::new();
+        // Create a line with some content
+        for c in "0123456789abcdef".chars() {
+            app.buffer.insert_char(c);
+        }
+        // Move cursor to start
+        app.buffer.move_cursor_to_line_start();
+        // Set horizontal scroll offset as if we scrolled right
+        app.horizontal_scroll_offset = 10;
+        // Cursor is at column 0, which is before visible area
+        let visible_width = 10;
+
+        app.adjust_horizontal_scroll(visible_width);
+
+        // Scroll should adjust to show cursor (column 0)
+        assert_eq!(app.horizontal_scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_adjust_horizontal_scroll_cursor_after_visible_area_scrolls_right() {
+        let mut app = App::new();
+        // Create a line with content extending beyond visible area
+        for c in "0123456789abcdefghij".chars() {
+            app.buffer.insert_char(c);
+        }
+        // Cursor should be at column 20 (end of line)
+        assert_eq!(app.buffer.cursor().col(), 20);
+        // Start with scroll at 0
+        app.horizontal_scroll_offset = 0;
+        let visible_width = 10;
+
+        app.adjust_horizontal_scroll(visible_width);
+
+        // Scroll should adjust so cursor is visible
+        // With margin of 4 (min(10, 5) - 1), cursor at 20 needs offset >= 20 - (10 - 4 - 1) = 15
+        assert!(app.horizontal_scroll_offset >= 15);
+    }
+
+    #[test]
+    fn test_adjust_horizontal_scroll_cursor_within_visible_area_no_change() {
+        let mut app = App::new();
+        // Create a line with some content
+        for c in "0123456789".chars() {
+            app.buffer.insert_char(c);
+        }
+        // Move cursor to middle (column 5)
+        app.buffer.move_cursor_to_line_start();
+        for _ in 0..5 {
+            app.buffer.move_cursor_right();
+        }
+        assert_eq!(app.buffer.cursor().col(), 5);
+        // Set scroll offset to 0
+        app.horizontal_scroll_offset = 0;
+        let visible_width = 20;
+
+        app.adjust_horizontal_scroll(visible_width);
+
+        // No change needed, cursor is within visible area
+        assert_eq!(app.horizontal_scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_adjust_horizontal_scroll_with_zero_visible_width() {
+        let mut app = App::new();
+        app.buffer.insert_char('a');
+        app.horizontal_scroll_offset = 5;
+
+        // visible_width of 0 should not crash and not change offset
+        app.adjust_horizontal_scroll(0);
+
+        // Scroll offset should remain unchanged
+        assert_eq!(app.horizontal_scroll_offset, 5);
+    }
+
+    #[test]
+    fn test_clear_all_resets_horizontal_scroll_offset() {
+        let mut app = App::new();
+        app.horizontal_scroll_offset = 10;
+
+        app.clear_all();
+
+        assert_eq!(app.horizontal_scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_app_new_initializes_horizontal_scroll_offset_to_zero() {
+        let app = App::new();
+        assert_eq!(app.horizontal_scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_dismiss_recovery_clears_pending_recovery() {
+        let mut app = App::new();
+        app.pending_recovery = None;
+
+        app.dismiss_recovery();
+
+        assert!(app.pending_recovery.is_none());
+    }
+
+    #[test]
+    fn test_restore_recovery_without_pending_keeps_buffer() {
+        let mut app = App::new();
+        app.pending_recovery = None;
+        app.buffer = Buffer::from_lines(vec!["1 + 1".to_string()]);
+
+        app.restore_recovery();
+
+        assert_eq!(app.buffer.lines(), ["1 + 1"]);
+    }
+
+    #[test]
+    fn test_notify_shows_notification() {
+        let mut app = App::new();
+        app.notifications.clear();
+
+        app.notify("Saved");
+
+        assert_eq!(app.active_notification(), Some("Saved"));
+    }
+
+    #[test]
+    fn test_notification_expires_after_duration() {
+        let mut app = App::new();
+        app.notifications.clear();
+        let start = Instant::now();
+
+        app.notify("Saved");
+        assert_eq!(app.active_notification_at(start), Some("Saved"));
+
+        let later = start + NOTIFICATION_DURATION;
+        assert_eq!(app.active_notification_at(later), None);
+    }
+
+    #[test]
+    fn test_notifications_are_shown_in_order() {
+        let mut app = App::new();
+        app.notifications.clear();
+        let start = Instant::now();
+
+        app.notify("first");
+        app.notify("second");
+
+        assert_eq!(app.active_notification_at(start), Some("first"));
+        let later = start + NOTIFICATION_DURATION;
+        assert_eq!(app.active_notification_at(later), Some("second"));
+    }
+
+    #[test]
+    fn test_notify_skips_duplicate_of_last_message() {
+        let mut app = App::new();
+        app.notifications.clear();
+
+        app.notify("Cleared");
+        app.notify("Cleared");
+
+        assert_eq!(app.notifications.len(), 1);
+    }
+
+    #[test]
+    fn test_clear_all_notifies() {
+        let mut app = App::new();
+        app.notifications.clear();
+
+        app.clear_all();
+
+        assert_eq!(app.active_notification(), Some("Cleared"));
+    }
+
+    #[test]
+    fn test_inspect_current_line_uses_earlier_variables() {
+        let mut app = App::new();
+        app.buffer = Buffer::from_lines(vec!["a = 2".to_string(), "a * 21".to_string()]);
+        app.buffer.move_cursor_down();
+
+        app.inspect_current_line();
+
+        let inspection = app.inspection.as_ref().expect("should open inspection");
+        assert!((inspection.value - 42.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_inspect_current_line_on_empty_line_notifies() {
+        let mut app = App::new();
+        app.buffer = Buffer::new();
+        app.notifications.clear();
+
+        app.inspect_current_line();
+
+        assert!(app.inspection.is_none());
+        assert_eq!(
+            app.active_notification(),
+            Some("Nothing to inspect on this line")
+        );
+    }
+
+    #[test]
+    fn test_ghost_result_previews_the_valid_prefix() {
+        let mut app = App::new();
+        app.buffer = Buffer::from_lines(vec!["a = 2".to_string(), "b = a * 21 +".to_string()]);
+        app.buffer.move_cursor_down();
+
+        assert_eq!(app.ghost_result(), Some(42.0));
+
+        app.buffer = Buffer::from_lines(vec!["2 * 21".to_string()]);
+        assert_eq!(app.ghost_result(), None);
+    }
+
+    #[test]
+    fn test_open_worksheet_file() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("sheet.calc");
+        std::fs::write(&path, "a = 2\nb = a * 3").expect("write file");
+
+        let mut app = App::open(Persistence::File(path.clone()), None).expect("open worksheet");
+        assert_eq!(app.buffer.lines(), ["a = 2", "b = a * 3"]);
+        assert!(app.pending_recovery.is_none());
+
+        app.buffer.move_cursor_to_line_end();
+        app.buffer.insert_char('0');
+        app.save_state();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a = 20\nb = a * 3");
+    }
+
+    #[test]
+    fn test_open_recovers_file_that_is_not_utf8() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("sheet.calc");
+        let backup = dir.path().join("sheet.calc.corrupt");
+        std::fs::write(&path, b"a = 2\nb = \xFF").expect("write file");
+
+        let mut app = App::open(Persistence::File(path.clone()), None).expect("open worksheet");
+        assert_eq!(app.buffer.lines(), ["a = 2", "b = \u{FFFD}"]);
+        assert_eq!(
+            app.active_notification(),
+            Some(
+                format!(
+                    "The buffer file was not valid UTF-8: unreadable characters were replaced, the original is kept at {}",
+                    backup.display()
+                )
+                .as_str()
+            )
+        );
+        app.save_state();
+        assert_eq!(std::fs::read(&backup).unwrap(), b"a = 2\nb = \xFF");
+
+        std::fs::write(&path, [0x00, 0xFF, 0xFE]).expect("write file");
+        let app = App::open(Persistence::File(path), None).expect("open worksheet");
+        assert_eq!(app.buffer.lines(), [""]);
+        assert_eq!(std::fs::read(&backup).unwrap(), [0x00, 0xFF, 0xFE]);
+    }
+
+    #[test]
+    fn test_horizontal_scroll_stays_on_character_boundaries() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["🦀".repeat(30)]));
+        app.restore_position(storage::Position {
+            cursor_row: 0,
+            cursor_col: 120,
+            scroll_offset: 0,
+            horizontal_scroll_offset: 98,
+        });
+        assert_eq!(app.horizontal_scroll_offset, 96);
+
+        app.horizontal_scroll_offset = 0;
+        app.adjust_horizontal_scroll(10);
+        assert_eq!(app.horizontal_scroll_offset, 112);
+        app.buffer.move_cursor_to(0, 8);
+        app.adjust_horizontal_scroll(10);
+        assert_eq!(app.horizontal_scroll_offset, 4);
+    }
+
+    #[test]
+    fn test_restore_position_clamps_to_buffer() {
+        let mut app = App::with_buffer(Buffer::from_lines(
+            (0..50).map(|n| format!("x = {n}")).collect(),
+        ));
+        app.restore_position(storage::Position {
+            cursor_row: 40,
+            cursor_col: 3,
+            scroll_offset: 30,
+            horizontal_scroll_offset: 2,
+        });
+        assert_eq!(app.position().cursor_row, 40);
+        assert_eq!(app.position().cursor_col, 3);
+        assert_eq!(app.scroll_offset, 30);
+        assert_eq!(app.horizontal_scroll_offset, 2);
+
+        app.restore_position(storage::Position {
+            cursor_row: 80,
+            cursor_col: 99,
+            scroll_offset: 75,
+            horizontal_scroll_offset: 0,
+        });
+        let position = app.position();
+        assert_eq!((position.cursor_row, position.cursor_col), (49, 6));
+        assert_eq!(app.scroll_offset, 49);
+    }
+
+    #[test]
+    fn test_save_writes_result_comments() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("sheet.calc");
+        std::fs::write(
+            &path,
+            format!("{}\na = 2  # => a = 1\na * 3", storage::RESULTS_HEADER),
+        )
+        .expect("write file");
+        let mut app = App::open(Persistence::File(path.clone()), None).expect("open worksheet");
+        assert_eq!(app.buffer.lines(), ["a = 2", "a * 3"]);
+
+        app.settings.results = true;
+        app.save_state();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!(
+                "{}\na = 2  # => a = 2\na * 3  # => 6",
+                storage::RESULTS_HEADER
+            )
+        );
+        app.disk_modified = None;
+        app.check_disk_changes();
+        assert_eq!(app.mode(), Mode::Editor);
+    }
+
+    #[test]
+    fn test_result_comments_match_the_shown_results() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("sheet.calc");
+        std::fs::write(&path, "100 * vat").expect("write file");
+        let mut app = App::open(Persistence::File(path.clone()), None).expect("open worksheet");
+        app.context.set_variable("vat", 0.19);
+        app.context.set_override("vat", 0.5);
+        app.settings.results = true;
+
+        app.save_state();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{}\n100 * vat  # => 19", storage::RESULTS_HEADER)
+        );
+    }
+
+    #[test]
+    fn test_save_asks_before_overwriting_changes_on_disk() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("sheet.calc");
+        std::fs::write(&path, "a = 1").expect("write file");
+        let mut app = App::open(Persistence::File(path.clone()), None).expect("open worksheet");
+
+        std::fs::write(&path, "a = 2\nb = 3").expect("write file");
+        app.buffer.move_cursor_to_line_end();
+        app.buffer.insert_char('0');
+        app.quit();
+        app.save_state();
+        assert_eq!(app.mode(), Mode::Conflict);
+        assert!(app.running);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a = 2\nb = 3");
+
+        app.apply(Action::OverwriteConflict);
+        assert_eq!(app.mode(), Mode::Editor);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a = 10");
+
+        std::fs::write(&path, "c = 4").expect("write file");
+        app.save_state();
+        app.apply(Action::ReloadConflict);
+        assert_eq!(app.buffer.lines(), ["c = 4"]);
+        app.save_state();
+        assert!(app.pending_conflict.is_none());
+    }
+
+    #[test]
+    fn test_check_disk_changes_offers_reload() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("sheet.calc");
+        std::fs::write(&path, "a = 1").expect("write file");
+        let mut app = App::open(Persistence::File(path.clone()), None).expect("open worksheet");
+
+        app.check_disk_changes();
+        assert_eq!(app.mode(), Mode::Editor);
+
+        std::fs::write(&path, "a = 2").expect("write file");
+        // Modification times may be too coarse to tell both writes apart.
+        app.disk_modified = None;
+        app.check_disk_changes();
+        assert_eq!(app.mode(), Mode::Conflict);
+
+        app.apply(Action::ReloadConflict);
+        assert_eq!(app.buffer.lines(), ["a = 2"]);
+        app.check_disk_changes();
+        assert_eq!(app.mode(), Mode::Editor);
+    }
+
+    #[test]
+    fn test_open_refuses_encrypted_file_without_passphrase() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("sheet.calc");
+        let mut contents = storage::ENCRYPTED_MAGIC.to_vec();
+        contents.extend_from_slice(&[0; 64]);
+        std::fs::write(&path, &contents).expect("write file");
+
+        assert!(App::open(Persistence::File(path.clone()), None).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), contents);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_open_encrypted_worksheet() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("sheet.calc");
+        let persistence = Persistence::File(path.clone());
+        std::fs::write(&path, "salary = 5000").expect("write file");
+
+        let mut app = App::open(
+            persistence.clone(),
+            Some(storage::Passphrase::new("secret")),
+        )
+        .unwrap();
+        app.save_state();
+        assert!(storage::is_encrypted_file(&path));
+
+        assert!(App::open(persistence.clone(), Some(storage::Passphrase::new("guess"))).is_err());
+        let app = App::open(persistence, Some(storage::Passphrase::new("secret"))).unwrap();
+        assert_eq!(app.buffer.lines(), ["salary = 5000"]);
+    }
+
+    #[test]
+    fn test_open_without_state_starts_empty() {
+        let app = App::open(Persistence::None, None).expect("open without state");
+        assert_eq!(app.buffer.lines(), [""]);
+        assert!(!app.welcome_visible);
+        assert!(app.pending_recovery.is_none());
+    }
+
+    #[test]
+    fn test_prelude_variables_are_defined_before_the_buffer() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["vat * 100 +".to_string()]));
+        let error = app.extend_prelude(&[
+            "# rates".to_string(),
+            "vat = 0.19".to_string(),
+            "oops +".to_string(),
+        ]);
+        assert_eq!(app.prelude, vec![("vat".to_string(), 0.19)]);
+        assert!(matches!(error, Some((2, error)) if error.code() == ErrorCode::Syntax));
+        assert_eq!(app.ghost_result(), Some(19.0));
+
+        assert!(
+            app.extend_prelude(&["net = 100 * (1 + vat)".to_string()])
+                .is_none()
+        );
+        assert_eq!(app.prelude.len(), 2);
+
+        app.clear_all();
+        assert_eq!(app.context.get_variable("vat"), Some(0.19));
+    }
+
+    #[test]
+    fn test_close_inspection() {
+        let mut app = App::new();
+        app.buffer = Buffer::from_lines(vec!["1 + 1".to_string()]);
+        app.inspect_current_line();
+
+        app.close_inspection();
+
+        assert!(app.inspection.is_none());
+    }
+
+    #[test]
+    fn test_format_current_line_reformats_cursor_line() {
+        let mut app = App::new();
+        app.buffer = Buffer::from_lines(vec!["x=5+3*2".to_string(), "1+1".to_string()]);
+
+        assert!(app.format_current_line());
+
+        assert_eq!(app.buffer.lines(), ["x = 5 + 3 * 2", "1+1"]);
+    }
+
+    #[test]
+    fn test_format_buffer_skips_unparseable_lines() {
+        let mut app = App::new();
+        app.buffer = Buffer::from_lines(vec![
+            "1+1".to_string(),
+            "# note".to_string(),
+            "2*(3)".to_string(),
+        ]);
+
+        assert!(app.format_buffer());
+
+        assert_eq!(app.buffer.lines(), ["1 + 1", "# note", "2 * 3"]);
+    }
+
+    #[test]
+    fn test_format_buffer_without_changes_returns_false() {
+        let mut app = App::new();
+        app.buffer = Buffer::from_lines(vec!["1 + 1".to_string()]);
+
+        assert!(!app.format_buffer());
+    }
+
+    #[test]
+    fn test_explain_current_line_records_steps() {
+        let mut app = App::new();
+        app.buffer = Buffer::from_lines(vec!["a = 3".to_string(), "2 + a * 4".to_string()]);
+        app.buffer.move_cursor_down();
+
+        app.explain_current_line();
+
+        let explanation = app.explanation.as_ref().expect("should open explanation");
+        assert_eq!(
+            explanation.steps,
+            ["2 + a * 4", "2 + 3 * 4", "2 + 12", "14"]
+        );
+    }
+
+    #[test]
+    fn test_explain_current_line_on_empty_line_notifies() {
+        let mut app = App::new();
+        app.buffer = Buffer::new();
+        app.notifications.clear();
+
+        app.explain_current_line();
+
+        assert!(app.explanation.is_none());
+        assert_eq!(
+            app.active_notification(),
+            Some("Nothing to explain on this line")
+        );
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn test_with_buffer_starts_without_recovery() {
+        let app = App::with_buffer(Buffer::from_lines(vec!["1 + 1".to_string()]));
+
+        assert!(app.running);
+        assert!(app.pending_recovery.is_none());
+        assert_eq!(app.buffer.lines(), ["1 + 1"]);
+    }
+
+    #[test]
+    fn test_handle_key_typing_requests_save() {
+        let mut app = App::with_buffer(Buffer::new());
+
+        assert_eq!(app.handle_key(key(KeyCode::Char('7'))), [Effect::Save]);
+        assert_eq!(app.buffer.lines(), ["7"]);
+        assert!(app.last_edit_time.is_some());
+    }
+
+    #[test]
+    fn test_handle_key_navigation_does_not_save() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["12".to_string()]));
+
+        assert!(app.handle_key(key(KeyCode::Left)).is_empty());
+        assert!(app.last_edit_time.is_none());
+    }
+
+    #[test]
+    fn test_handle_key_ctrl_z_requests_suspend() {
+        let mut app = App::with_buffer(Buffer::new());
+
+        assert_eq!(app.handle_key(ctrl('z')), [Effect::Suspend]);
+    }
+
+    #[test]
+    fn test_handle_key_help_is_modal() {
+        let mut app = App::with_buffer(Buffer::new());
+        app.handle_key(ctrl('h'));
+
+        app.handle_key(key(KeyCode::Char('x')));
+        app.handle_key(key(KeyCode::Down));
+
+        assert!(app.help_visible);
+        assert_eq!(app.help_scroll_offset, 1);
+        assert_eq!(app.buffer.lines(), [""]);
+
+        app.handle_key(key(KeyCode::Esc));
+        assert!(!app.help_visible);
+        assert!(app.running);
+    }
+
+    #[test]
+    fn test_show_dependencies_draws_graph() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec![
+            "a = 3".to_string(),
+            "a * 4".to_string(),
+        ]));
+
+        app.handle_key(ctrl('g'));
+        assert_eq!(
+            app.dependency_graph.as_deref(),
+            Some(["1: a = 3".to_string(), "└── 2: a * 4".to_string()].as_slice())
+        );
+
+        app.handle_key(ctrl('g'));
+        assert!(app.dependency_graph.is_none());
+    }
+
+    #[test]
+    fn test_show_dependencies_without_dependencies_notifies() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["1 + 2".to_string()]));
+        app.notifications.clear();
+
+        app.show_dependencies();
+
+        assert!(app.dependency_graph.is_none());
+        assert_eq!(
+            app.active_notification(),
+            Some("No dependencies between lines")
+        );
+    }
+
+    #[test]
+    fn test_handle_key_explain_popup_closes_with_same_key() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["1 + 2".to_string()]));
+
+        app.handle_key(ctrl('t'));
+        assert!(app.explanation.is_some());
+
+        app.handle_key(ctrl('t'));
+        assert!(app.explanation.is_none());
+    }
+
+    #[test]
+    fn test_handle_key_recovery_prompt_takes_precedence() {
+        let mut app = App::with_buffer(Buffer::new());
+        app.pending_recovery = Some(vec!["1".to_string()]);
+        app.help_visible = true;
+
+        app.handle_key(key(KeyCode::Down));
+
+        assert_eq!(app.help_scroll_offset, 0);
+        assert!(app.pending_recovery.is_some());
+    }
+
+    #[test]
+    fn test_mode_prefers_recovery_over_popups() {
+        let mut app = App::with_buffer(Buffer::new());
+        assert_eq!(app.mode(), Mode::Editor);
+
+        app.help_visible = true;
+        assert_eq!(app.mode(), Mode::Help);
+
+        app.pending_recovery = Some(vec!["1".to_string()]);
+        assert_eq!(app.mode(), Mode::Recovery);
+    }
+
+    #[test]
+    fn test_handle_event_ignores_non_key_events() {
+        let mut app = App::with_buffer(Buffer::new());
+
+        assert!(app.handle_event(&Event::Resize(80, 24)).is_empty());
+        assert_eq!(app.buffer.lines(), [""]);
+    }
+
+    #[test]
+    fn test_apply_page_help_down_scrolls_a_page() {
+        let mut app = App::with_buffer(Buffer::new());
+        app.toggle_help();
+
+        app.apply(Action::PageHelpDown);
+
+        assert_eq!(app.help_scroll_offset, HELP_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_toggle_bracket_colors_flips_and_notifies() {
+        let mut app = App::with_buffer(Buffer::new());
+        assert!(app.bracket_colors);
+
+        assert_eq!(app.toggle_bracket_colors(), [Effect::SaveSettings]);
+
+        assert!(!app.bracket_colors);
+        assert!(!app.settings.brackets);
+        assert_eq!(app.active_notification(), Some("Bracket colors off"));
+    }
+
+    #[test]
+    fn test_layout_follows_settings() {
+        let mut app = App::with_buffer(Buffer::new());
+        app.settings.set("pane", "right").unwrap();
+        app.settings.set("align", "off").unwrap();
+        app.apply_settings();
+        assert!(!app.memory_pane_left);
+        assert!(!app.align_decimals);
+
+        assert_eq!(app.apply(Action::ToggleMemoryPane), [Effect::SaveSettings]);
+        assert!(app.memory_pane_left);
+        assert_eq!(app.settings.get("pane").as_deref(), Some("left"));
+    }
+
+    #[test]
+    fn test_track_result_changes_highlights_downstream_results() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec![
+            "a = 1".to_string(),
+            "a * 2".to_string(),
+            "5".to_string(),
+        ]));
+        let start = Instant::now();
+        let before = evaluate_all_lines(app.buffer.lines().iter().map(String::as_str));
+        assert!(app.track_result_changes_at(&before, start).is_empty());
+
+        app.buffer.move_cursor_to_line_end();
+        app.apply(Action::InsertChar('0'));
+        let after = evaluate_all_lines(app.buffer.lines().iter().map(String::as_str));
+
+        assert_eq!(app.track_result_changes_at(&after, start), vec![1]);
+        assert!(
+            app.track_result_changes_at(&after, start + RESULT_CHANGE_DURATION)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_track_result_changes_ignores_line_count_changes() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["1".to_string()]));
+        let now = Instant::now();
+        app.track_result_changes_at(&[LineResult::Value(1.0)], now);
+
+        let changed =
+            app.track_result_changes_at(&[LineResult::Value(2.0), LineResult::Value(1.0)], now);
+
+        assert!(changed.is_empty());
+    }
+
+    #[test]
+    fn test_cycle_result_history_steps_back_through_results() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["x = 1".to_string()]));
+        let now = Instant::now();
+        for value in [1.0, 2.0, 2.0, 3.0] {
+            let result = LineResult::Assignment {
+                name: "x".to_string(),
+                value,
+            };
+            app.track_result_changes_at(&[result], now);
+        }
+        app.notifications.clear();
+
+        app.cycle_result_history();
+        assert_eq!(
+            app.active_notification_at(now),
+            Some("Earlier result 1 of 2: x = 2")
+        );
+        app.notifications.clear();
+        app.cycle_result_history();
+        assert_eq!(
+            app.active_notification_at(now),
+            Some("Earlier result 2 of 2: x = 1")
+        );
+        app.notifications.clear();
+        app.cycle_result_history();
+        assert_eq!(
+            app.active_notification_at(now),
+            Some("Current result: x = 3")
+        );
+    }
+
+    #[test]
+    fn test_result_history_is_bounded_and_skips_errors() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["1".to_string()]));
+        let now = Instant::now();
+        app.track_result_changes_at(&[LineResult::Error(EvalError::new("bad"))], now);
+        for value in 0..20 {
+            app.track_result_changes_at(&[LineResult::Value(f64::from(value))], now);
+        }
+
+        let history = &app.result_history[&0];
+        assert_eq!(history.len(), RESULT_HISTORY_LEN);
+        assert_eq!(history.front(), Some(&LineResult::Value(18.0)));
+    }
+
+    #[test]
+    fn test_cycle_result_history_without_history_notifies() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["1".to_string()]));
+        app.notifications.clear();
+
+        app.handle_key(ctrl('y'));
+
+        assert_eq!(
+            app.active_notification(),
+            Some("No earlier results for this line")
+        );
+    }
+
+    #[test]
+    fn test_go_to_line_prompt_moves_cursor_and_clamps() {
+        let lines: Vec<String> = (1..=60).map(|i| i.to_string()).collect();
+        let mut app = App::with_buffer(Buffer::from_lines(lines));
+
+        app.handle_key(ctrl('l'));
+        assert_eq!(app.mode(), Mode::GoToLine);
+        app.handle_key(key(KeyCode::Char('4')));
+        app.handle_key(key(KeyCode::Char('2')));
+        app.handle_key(key(KeyCode::Enter));
+
+        assert_eq!(app.mode(), Mode::Editor);
+        assert_eq!(app.buffer.cursor().row(), 41);
+        app.adjust_scroll(10);
+        assert_eq!(app.scroll_offset, 32);
+
+        app.go_to_line(500);
+        assert_eq!(app.buffer.cursor().row(), 59);
+        app.go_to_line(0);
+        assert_eq!(app.buffer.cursor().row(), 0);
+    }
+
+    #[test]
+    fn test_settings_prompt_jumps_to_line_number() {
+        let lines: Vec<String> = (1..=5).map(|i| i.to_string()).collect();
+        let mut app = App::with_buffer(Buffer::from_lines(lines));
+
+        assert!(run_settings_command(&mut app, ":3").is_empty());
+
+        assert_eq!(app.settings_prompt, None);
+        assert_eq!(app.buffer.cursor().row(), 2);
+    }
+
+    #[test]
+    fn test_loadvars_defines_variables_for_the_worksheet() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("rates.json");
+        std::fs::write(&path, r#"{"vat": 0.19, "discount": 0.05}"#).expect("write file");
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["100 * vat".to_string()]));
+
+        let command = format!(":loadvars {}", path.display());
+        assert!(run_settings_command(&mut app, &command).is_empty());
+
+        assert_eq!(app.settings_prompt, None);
+        assert_eq!(
+            app.active_notification(),
+            Some(format!("Loaded 2 variable(s) from {}", path.display()).as_str())
+        );
+        let results = evaluate_all_lines_with_context(["100 * vat"], &mut app.context);
+        assert_eq!(results[0].number(), Some(19.0));
+    }
+
+    #[test]
+    fn test_loadvars_reports_bad_files_and_keeps_prompt() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("rates.json");
+        std::fs::write(&path, r#"{"vat": "high"}"#).expect("write file");
         let mut app = App::new();
-        app.scroll_offset = 10;
 
-        app.clear_all();
+        let command = format!(":loadvars {}", path.display());
+        run_settings_command(&mut app, &command);
 
-        assert_eq!(app.scroll_offset, 0);
+        assert_eq!(app.settings_prompt.as_deref(), Some(command.as_str()));
+        assert_eq!(
+            app.active_notification(),
+            Some(
+                format!(
+                    "Invalid variables in {}: expected a number for 'vat' at 8",
+                    path.display()
+                )
+                .as_str()
+            )
+        );
+        assert!(app.context.get_variable("vat").is_none());
+
+        app.notifications.clear();
+        run_settings_command(&mut app, ":loadvars");
+        assert_eq!(app.active_notification(), Some("Usage: loadvars FILE.json"));
+    }
+
+    #[test]
+    fn test_share_and_import_round_trip_the_worksheet() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("sheet.txt");
+        let lines = vec!["x = 90".to_string(), "sin(x)".to_string()];
+        let mut app = App::with_buffer(Buffer::from_lines(lines.clone()));
+        app.settings.set("angle", "deg").unwrap();
+
+        run_settings_command(&mut app, &format!(":share {}", path.display()));
+        assert_eq!(
+            app.active_notification(),
+            Some(format!("Shared worksheet to {}", path.display()).as_str())
+        );
+
+        let mut other = App::with_buffer(Buffer::from_lines(vec!["1 + 1".to_string()]));
+        let effects = run_settings_command(&mut other, &format!(":import {}", path.display()));
+        assert_eq!(other.buffer.lines(), lines);
+        assert_eq!(other.settings.get("angle").as_deref(), Some("deg"));
+        assert!(effects.contains(&Effect::SaveSettings));
+        assert_eq!(other.settings_prompt, None);
+        assert!(other.buffer.undo());
+        assert_eq!(other.buffer.lines(), ["1 + 1"]);
+    }
+
+    #[test]
+    fn test_import_reports_bad_files() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("sheet.txt");
+        std::fs::write(&path, "a = 1").expect("write file");
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["b = 2".to_string()]));
+
+        run_settings_command(&mut app, &format!(":import {}", path.display()));
+        assert_eq!(
+            app.active_notification(),
+            Some(
+                format!(
+                    "Invalid share file {}: not a Crabculator share file",
+                    path.display()
+                )
+                .as_str()
+            )
+        );
+        assert_eq!(app.buffer.lines(), ["b = 2"]);
+
+        app.notifications.clear();
+        run_settings_command(&mut app, ":share");
+        assert_eq!(app.active_notification(), Some("Usage: share FILE"));
+    }
+
+    #[test]
+    fn test_profile_applies_settings_and_variables() {
+        let mut app = App::with_buffer(Buffer::new());
+
+        let effects = run_settings_command(&mut app, ":profile electronics");
+
+        assert_eq!(app.settings_prompt, None);
+        assert_eq!(
+            app.active_notification(),
+            Some("Profile 'electronics': 2 setting(s), 2 variable(s)")
+        );
+        assert!(app.settings.prefixes && app.settings.sigfigs);
+        assert_eq!(app.context.get_variable("eps0"), Some(8.854_187_812_8e-12));
+        assert_eq!(effects, vec![Effect::SaveSettings]);
+
+        app.notifications.clear();
+        run_settings_command(&mut app, ":profile");
+        assert_eq!(
+            app.active_notification()
+                .map(|n| n.starts_with("Profiles: ")),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_profile_rejects_unknown_names() {
+        let mut app = App::with_buffer(Buffer::new());
+
+        run_settings_command(&mut app, ":profile astronomy");
+
+        assert_eq!(app.settings_prompt.as_deref(), Some(":profile astronomy"));
+        assert_eq!(
+            app.active_notification(),
+            Some("Unknown profile 'astronomy'")
+        );
+        assert_eq!(app.settings, Settings::default());
+    }
+
+    #[test]
+    fn test_bookmarks_set_jump_and_follow_edits() {
+        let lines: Vec<String> = (1..=20).map(|i| i.to_string()).collect();
+        let mut app = App::with_buffer(Buffer::from_lines(lines));
+        app.go_to_line(5);
+        assert_eq!(
+            run_settings_command(&mut app, ":mark budget"),
+            vec![Effect::Save]
+        );
+        app.go_to_line(12);
+        run_settings_command(&mut app, "mark 1");
+        assert_eq!(app.bookmarks.list(), "budget: 5, 1: 12");
+
+        app.go_to_line(1);
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.bookmarks.list(), "budget: 6, 1: 13");
+
+        app.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::ALT));
+        assert_eq!(app.buffer.cursor().row(), 5);
+        app.handle_key(KeyEvent::new(KeyCode::Char('1'), KeyModifiers::ALT));
+        assert_eq!(app.buffer.cursor().row(), 12);
+
+        run_settings_command(&mut app, ":'budget");
+        assert_eq!(app.settings_prompt, None);
+        assert_eq!(app.buffer.cursor().row(), 5);
+        app.handle_key(key(KeyCode::Backspace));
+        assert_eq!(app.bookmarks.get("budget"), Some(4));
+
+        run_settings_command(&mut app, ":unmark budget");
+        assert_eq!(app.bookmarks.list(), "1: 12");
+    }
+
+    #[test]
+    fn test_select_enclosing_and_extract_variable() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec![
+            "v1 = 3".to_string(),
+            "total = (price + tax) * v1".to_string(),
+        ]));
+        app.buffer.move_cursor_to(1, 10);
+        app.handle_key(ctrl('a'));
+        assert_eq!(app.buffer.selected_text(), Some("price + tax"));
+        assert_eq!(app.mode(), Mode::Selection);
+        app.handle_key(ctrl('a'));
+        assert_eq!(app.buffer.selected_text(), Some("(price + tax)"));
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::ALT));
+        assert_eq!(
+            app.buffer.lines(),
+            ["v1 = 3", "v2 = (price + tax)", "total = v2 * v1"]
+        );
+        assert_eq!(app.buffer.selection(), None);
+        assert_eq!(app.buffer.cursor().row(), 2);
+    }
+
+    #[test]
+    fn test_selection_is_replaced_by_typing_and_cleared_by_escape() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["2 * (3 + 4)".to_string()]));
+        app.buffer.move_cursor_to(0, 6);
+        app.handle_key(ctrl('a'));
+        app.handle_key(key(KeyCode::Char('9')));
+        assert_eq!(app.buffer.lines(), ["2 * (9)"]);
+
+        app.handle_key(ctrl('a'));
+        app.handle_key(key(KeyCode::Esc));
+        assert!(app.running);
+        assert_eq!(app.buffer.selection(), None);
+
+        app.handle_key(ctrl('a'));
+        app.handle_key(key(KeyCode::Left));
+        assert_eq!(app.buffer.selection(), None);
+        app.handle_key(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::ALT));
+        assert_eq!(app.buffer.lines(), ["2 * (9)"]);
+    }
+
+    #[test]
+    fn test_sort_and_uniq_commands_with_undo() {
+        let lines = ["c = 1", "a = 30", "b = 2", "a = 30", "# done"];
+        let mut app = App::with_buffer(Buffer::from_lines(lines.map(String::from).to_vec()));
+
+        run_settings_command(&mut app, ":sort 1-4");
+        assert_eq!(
+            app.buffer.lines(),
+            ["a = 30", "a = 30", "b = 2", "c = 1", "# done"]
+        );
+        run_settings_command(&mut app, ":sort value");
+        assert_eq!(
+            app.buffer.lines(),
+            ["c = 1", "b = 2", "a = 30", "a = 30", "# done"]
+        );
+        run_settings_command(&mut app, ":uniq 2-5");
+        assert_eq!(app.buffer.lines(), ["c = 1", "b = 2", "a = 30", "# done"]);
+
+        app.handle_key(ctrl('u'));
+        assert_eq!(app.buffer.lines().len(), 5);
+        app.handle_key(ctrl('u'));
+        app.handle_key(ctrl('u'));
+        assert_eq!(app.buffer.lines(), lines);
+        app.handle_key(ctrl('u'));
+        assert_eq!(app.notifications.back().unwrap().message, "Nothing to undo");
+
+        run_settings_command(&mut app, ":sort 4-2");
+        assert_eq!(app.buffer.lines(), lines);
+        assert!(app.settings_prompt.is_some());
+    }
+
+    #[test]
+    fn test_sort_by_value_uses_the_results_shown() {
+        let lines = ["3 * rate", "20", "1 * rate"];
+        let mut app = App::with_buffer(Buffer::from_lines(lines.map(String::from).to_vec()));
+        app.context.set_variable("rate", 10.0);
+
+        run_settings_command(&mut app, ":sort value");
+        assert_eq!(app.buffer.lines(), ["1 * rate", "20", "3 * rate"]);
+    }
+
+    #[test]
+    fn test_undo_takes_back_a_run_of_typing_at_once() {
+        let mut app = App::with_buffer(Buffer::new());
+        for c in "12".chars() {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        app.handle_key(key(KeyCode::Enter));
+        for c in "34".chars() {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        app.handle_key(key(KeyCode::Left));
+
+        app.handle_key(ctrl('u'));
+        assert_eq!(app.buffer.lines(), ["12", ""]);
+        app.handle_key(ctrl('u'));
+        assert_eq!(app.buffer.lines(), ["12"]);
+        app.handle_key(ctrl('u'));
+        assert_eq!(app.buffer.lines(), [""]);
+    }
+
+    #[test]
+    fn test_block_mode_edits_every_line() {
+        let lines = ["12.50", "3", "100"].map(String::from).to_vec();
+        let mut app = App::with_buffer(Buffer::from_lines(lines));
+        app.handle_key(ctrl('v'));
+        assert_eq!(app.mode(), Mode::Block);
+        app.handle_key(key(KeyCode::Down));
+        app.handle_key(key(KeyCode::Down));
+        app.handle_key(key(KeyCode::End));
+        for c in " * 1.19".chars() {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(
+            app.buffer.lines(),
+            ["12.50 * 1.19", "3 * 1.19", "100 * 1.19"]
+        );
+
+        app.handle_key(key(KeyCode::Esc));
+        assert!(app.running);
+        assert_eq!(app.mode(), Mode::Editor);
+        app.handle_key(ctrl('u'));
+        assert_eq!(app.buffer.lines(), ["12.50", "3", "100"]);
+    }
+
+    #[test]
+    fn test_quick_sum_of_block_lines() {
+        let lines = ["# prices", "12.50", "x = 3", "100"]
+            .map(String::from)
+            .to_vec();
+        let mut app = App::with_buffer(Buffer::from_lines(lines));
+        let results = evaluate_all_lines(app.buffer.lines().iter().map(String::as_str));
+        app.track_result_changes(&results);
+
+        app.handle_key(ctrl('v'));
+        assert_eq!(app.quick_sum(), None);
+        app.handle_key(key(KeyCode::Down));
+        app.handle_key(key(KeyCode::Down));
+        app.handle_key(key(KeyCode::Down));
+        let quick_sum = app.quick_sum().expect("quick sum");
+        assert_eq!((quick_sum.lines, quick_sum.count), (4, 3));
+        assert!((quick_sum.sum - 115.5).abs() < 1e-9);
+
+        app.handle_key(key(KeyCode::Esc));
+        assert_eq!(app.quick_sum(), None);
+    }
+
+    #[test]
+    fn test_block_mode_ends_on_other_actions() {
+        let lines = ["a1", "b2"].map(String::from).to_vec();
+        let mut app = App::with_buffer(Buffer::from_lines(lines));
+        app.handle_key(ctrl('v'));
+        app.handle_key(key(KeyCode::Down));
+        app.handle_key(key(KeyCode::Delete));
+        assert_eq!(app.buffer.lines(), ["1", "2"]);
+
+        app.handle_key(key(KeyCode::Enter));
+        assert!(!app.buffer.in_block_mode());
+        assert_eq!(app.buffer.lines(), ["1", "", "2"]);
+    }
+
+    #[test]
+    fn test_click_moves_cursor_and_copies_result() {
+        use crate::ui::RowMap;
+        use crossterm::event::MouseEvent;
+        use ratatui::layout::Rect;
+
+        let mut app = App::with_buffer(Buffer::from_lines(vec![
+            "x = 20".to_string(),
+            "x * 2".to_string(),
+        ]));
+        let results = evaluate_all_lines(app.buffer.lines().iter().map(String::as_str));
+        app.track_result_changes(&results);
+        app.screen = ScreenMap {
+            input: Rect::new(20, 0, 30, 10),
+            memory: Rect::new(0, 0, 20, 10),
+            gutter_width: 3,
+            rows: RowMap::new(&results, true),
+        };
+        let click = |column, row| {
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                modifiers: KeyModifiers::NONE,
+            })
+        };
+        let cursor = |app: &App| (app.buffer.cursor().row(), app.buffer.cursor().col());
+
+        assert!(app.handle_event(&click(25, 2)).is_empty());
+        assert_eq!(cursor(&app), (1, 2));
+
+        assert!(app.handle_event(&click(5, 1)).is_empty());
+        assert_eq!(cursor(&app), (0, 6));
+
+        run_settings_command(&mut app, ":set clickcopy on");
+        app.apply(Action::CloseSettings);
+        assert_eq!(app.handle_event(&click(5, 2)), vec![Effect::CopyResult(1)]);
+        assert_eq!(app.result_value(1).as_deref(), Some("40"));
+        assert_eq!(app.result_value(0).as_deref(), Some("20"));
+    }
+
+    #[test]
+    fn test_what_if_override_recomputes_and_resets_on_exit() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec![
+            "price = 40".to_string(),
+            "price * 2".to_string(),
+        ]));
+        let lines = app.buffer.lines().to_vec();
+        evaluate_all_lines_with_context(lines.iter().map(String::as_str), &mut app.context);
+
+        app.handle_key(ctrl('x'));
+        assert_eq!(app.mode(), Mode::WhatIf);
+        for c in "price = 50".chars() {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.what_if_prompt.as_deref(), Some(""));
+
+        let results =
+            evaluate_all_lines_with_context(lines.iter().map(String::as_str), &mut app.context);
+        assert_eq!(results[1], LineResult::Value(100.0));
+
+        app.handle_key(key(KeyCode::Esc));
+        assert_eq!(app.mode(), Mode::Editor);
+        let results =
+            evaluate_all_lines_with_context(lines.iter().map(String::as_str), &mut app.context);
+        assert_eq!(results[1], LineResult::Value(80.0));
+    }
+
+    #[test]
+    fn test_share_results_copies_shown_results() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec![
+            "a = 2".to_string(),
+            "a * 21".to_string(),
+        ]));
+        let results = evaluate_all_lines(app.buffer.lines().iter().map(String::as_str));
+        app.track_result_changes(&results);
+
+        assert_eq!(app.handle_key(ctrl('s')), vec![Effect::CopyResults]);
+        assert_eq!(
+            app.share_text(),
+            "a = 2   =>  a = 2
+a * 21  =>  42"
+        );
+    }
+
+    #[test]
+    fn test_what_if_rejects_unassigned_variables() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["x = 1".to_string()]));
+        app.notifications.clear();
+        app.what_if_prompt = Some("y = 2".to_string());
+
+        app.run_what_if_command();
+
+        assert!(app.context.overrides().is_empty());
+        assert_eq!(
+            app.active_notification(),
+            Some("'y' is not assigned in the buffer")
+        );
+    }
+
+    #[test]
+    fn test_toggle_line_disabled_round_trip_keeps_cursor() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["  x = 5".to_string()]));
+        app.buffer.move_cursor_to_line_end();
+
+        assert_eq!(app.apply(Action::ToggleLineDisabled), vec![Effect::Save]);
+        assert_eq!(app.buffer.lines(), ["  # x = 5"]);
+        assert_eq!(app.buffer.cursor().col(), 9);
+
+        app.apply(Action::ToggleLineDisabled);
+        assert_eq!(app.buffer.lines(), ["  x = 5"]);
+        assert_eq!(app.buffer.cursor().col(), 7);
+    }
+
+    #[test]
+    fn test_toggle_line_disabled_without_space_after_hash() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["#x".to_string()]));
+
+        app.apply(Action::ToggleLineDisabled);
+
+        assert_eq!(app.buffer.lines(), ["x"]);
+        assert_eq!(app.buffer.cursor().col(), 0);
+    }
+
+    #[test]
+    fn test_toggle_line_disabled_ignores_blank_line() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["   ".to_string()]));
+
+        assert!(app.apply(Action::ToggleLineDisabled).is_empty());
+        assert_eq!(app.buffer.lines(), ["   "]);
+    }
+
+    #[test]
+    fn test_toggle_watch_pins_and_unpins() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec![
+            "budget = 500".to_string(),
+            "budget - 120".to_string(),
+        ]));
+
+        app.apply(Action::ToggleWatch);
+        app.buffer.move_cursor_down();
+        app.apply(Action::ToggleWatch);
+        assert_eq!(app.watches, vec!["budget", "budget - 120"]);
+
+        app.apply(Action::ToggleWatch);
+        assert_eq!(app.watches, vec!["budget"]);
+    }
+
+    #[test]
+    fn test_toggle_watch_on_empty_line_notifies() {
+        let mut app = App::with_buffer(Buffer::new());
+
+        app.apply(Action::ToggleWatch);
+
+        assert!(app.watches.is_empty());
+        assert_eq!(
+            app.active_notification(),
+            Some("Nothing to pin on this line")
+        );
+    }
+
+    #[test]
+    fn test_toggle_decimal_alignment_flips_and_notifies() {
+        let mut app = App::with_buffer(Buffer::new());
+        assert!(app.align_decimals);
+
+        app.apply(Action::ToggleDecimalAlignment);
+
+        assert!(!app.align_decimals);
+        assert_eq!(app.active_notification(), Some("Decimal alignment off"));
+    }
+
+    #[test]
+    fn test_toggle_timings_flips_and_notifies() {
+        let mut app = App::with_buffer(Buffer::new());
+        assert!(!app.show_timings);
+
+        app.apply(Action::ToggleTimings);
+
+        assert!(app.show_timings);
+        assert_eq!(app.active_notification(), Some("Evaluation timing on"));
+    }
+
+    fn run_settings_command(app: &mut App, command: &str) -> Vec<Effect> {
+        app.apply(Action::OpenSettings);
+        for c in command.chars() {
+            app.apply(Action::SettingsChar(c));
+        }
+        app.apply(Action::RunSettingsCommand)
+    }
+
+    #[test]
+    fn test_settings_command_applies_and_requests_save() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["sin(90)".to_string()]));
+        assert_eq!(app.mode(), Mode::Editor);
+
+        let effects = run_settings_command(&mut app, ":set angle deg");
+
+        assert_eq!(effects, vec![Effect::SaveSettings]);
+        assert_eq!(app.mode(), Mode::Settings);
+        assert_eq!(app.settings_prompt.as_deref(), Some(""));
+        assert_eq!(app.active_notification(), Some("angle = deg"));
+        let results = evaluate_all_lines_with_context(
+            app.buffer.lines().iter().map(String::as_str),
+            &mut app.context,
+        );
+        assert_eq!(results, vec![LineResult::Value(1.0)]);
+
+        app.apply(Action::CloseSettings);
+        assert_eq!(app.mode(), Mode::Editor);
+    }
+
+    #[test]
+    fn test_settings_command_error_keeps_prompt() {
+        let mut app = App::with_buffer(Buffer::new());
+
+        let effects = run_settings_command(&mut app, ":set colour red");
+
+        assert!(effects.is_empty());
+        assert_eq!(app.settings_prompt.as_deref(), Some(":set colour red"));
+        assert_eq!(app.active_notification(), Some("Unknown setting 'colour'"));
     }
 
     #[test]
-    fn test_adjust_scroll_cursor_above_visible_area() {
-        let mut app = App::new();
-        // Create buffer with 10 lines
-        for i in 0..10 {
-            for c in format!("line {i}").chars() {
-                app.buffer.insert_char(c);
-            }
-            app.buffer.insert_newline();
-        }
-        // Move cursor to line 0 (should be at end due to newlines)
-        for _ in 0..10 {
-            app.buffer.move_cursor_up();
-        }
-        // Set scroll offset to 5 (viewing lines 5-9)
-        app.scroll_offset = 5;
-        // Cursor is at line 0, which is above visible area
-        let visible_height = 5;
+    fn test_shell_setting_allows_commands() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["`echo 21` * 2".to_string()]));
+        let evaluate = |app: &mut App| {
+            evaluate_all_lines_with_context(
+                app.buffer.lines().iter().map(String::as_str),
+                &mut app.context,
+            )[0]
+            .clone()
+        };
+        assert!(matches!(evaluate(&mut app), LineResult::Error(_)));
 
-        app.adjust_scroll(visible_height);
+        run_settings_command(&mut app, ":set shell on");
+        assert!(matches!(evaluate(&mut app), LineResult::Error(_)));
+        app.start_shell_commands();
+        let started = std::time::Instant::now();
+        while evaluate(&mut app) != LineResult::Value(42.0) {
+            assert!(started.elapsed() < std::time::Duration::from_secs(5));
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
 
-        // Scroll should adjust to show cursor (line 0)
-        assert_eq!(app.scroll_offset, 0);
+        run_settings_command(&mut app, ":set shell off");
+        assert!(matches!(evaluate(&mut app), LineResult::Error(_)));
     }
 
     #[test]
-    fn test_adjust_scroll_cursor_below_visible_area() {
-        let mut app = App::new();
-        // Create buffer with 10 lines
-        for i in 0..10 {
-            for c in format!("line {i}").chars() {
-                app.buffer.insert_char(c);
-            }
-            if i < 9 {
-                app.buffer.insert_newline();
-            }
+    fn test_shell_commands_wait_until_their_line_is_left() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec![String::new(), String::new()]));
+        for c in "`echo 3`".chars() {
+            app.apply(Action::InsertChar(c));
         }
-        // Cursor should be at line 9
-        assert_eq!(app.buffer.cursor().row(), 9);
-        // Set scroll offset to 0 (viewing lines 0-4)
-        app.scroll_offset = 0;
-        let visible_height = 5;
+        assert_eq!(app.edited_row, Some(0));
 
-        app.adjust_scroll(visible_height);
+        app.apply(Action::MoveDown);
+        assert_eq!(app.edited_row, None);
+        app.apply(Action::MoveRight);
+        assert_eq!(app.edited_row, None);
+    }
 
-        // Scroll should adjust so cursor (line 9) is visible
-        // visible area: scroll_offset to scroll_offset + visible_height - 1
-        // line 9 should be visible: scroll_offset + 4 >= 9, so scroll_offset >= 5
-        assert_eq!(app.scroll_offset, 5);
+    #[test]
+    fn test_untrusted_project_keeps_shell_commands_off() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["`echo 21` * 2".to_string()]));
+        app.untrusted_project = Some(PathBuf::from("/tmp/.crabculator"));
+
+        run_settings_command(&mut app, ":set shell on");
+        assert!(app.settings.shell);
+        assert!(!shell::shell_commands());
+        assert_eq!(
+            app.notifications.back().map(|n| n.message.as_str()),
+            Some("Shell commands stay off until the project is trusted with :trust")
+        );
+        let result = evaluate_all_lines_with_context(
+            app.buffer.lines().iter().map(String::as_str),
+            &mut app.context,
+        );
+        assert!(matches!(result[0], LineResult::Error(_)));
     }
 
     #[test]
-    fn test_adjust_scroll_cursor_within_visible_area_no_change() {
-        let mut app = App::new();
-        // Create buffer with 5 lines
-        for i in 0..5 {
-            for c in format!("line {i}").chars() {
-                app.buffer.insert_char(c);
-            }
-            if i < 4 {
-                app.buffer.insert_newline();
-            }
-        }
-        // Move cursor to line 2
-        app.buffer.move_cursor_up();
-        app.buffer.move_cursor_up();
-        assert_eq!(app.buffer.cursor().row(), 2);
-        // Set scroll offset to 0 (viewing lines 0-4)
-        app.scroll_offset = 0;
-        let visible_height = 5;
+    fn test_format_presets_apply_to_lines() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec![
+            "1234.56 | money".to_string(),
+            "2 * 3".to_string(),
+        ]));
+        let evaluate = |app: &mut App| {
+            evaluate_all_lines_with_context(
+                app.buffer.lines().iter().map(String::as_str),
+                &mut app.context,
+            )
+        };
+        assert!(matches!(evaluate(&mut app)[0], LineResult::Error(_)));
 
-        app.adjust_scroll(visible_height);
+        run_settings_command(&mut app, ":set format.money 2dp, thousands, prefix \"€\"");
+        run_settings_command(&mut app, ":set format money");
+        let results = evaluate(&mut app);
+        assert_eq!(
+            crate::ui::format_result(&results[0]).as_deref(),
+            Some("€1,234.56")
+        );
+        assert_eq!(
+            crate::ui::format_result(&results[1]).as_deref(),
+            Some("€6.00")
+        );
 
-        // No change needed, cursor is within visible area
-        assert_eq!(app.scroll_offset, 0);
+        run_settings_command(&mut app, ":set format auto");
+        assert_eq!(evaluate(&mut app)[1], LineResult::Value(6.0));
+        run_settings_command(&mut app, ":set format.money 0dp");
+        assert_eq!(
+            crate::ui::format_result(&evaluate(&mut app)[0]).as_deref(),
+            Some("1235")
+        );
     }
 
     #[test]
-    fn test_adjust_scroll_with_zero_visible_height() {
-        let mut app = App::new();
-        app.buffer.insert_char('a');
-        app.scroll_offset = 0;
+    fn test_prefixes_setting_changes_literals() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec![
+            "k = 3".to_string(),
+            "2k".to_string(),
+        ]));
+        let evaluate = |app: &mut App| {
+            evaluate_all_lines_with_context(
+                app.buffer.lines().iter().map(String::as_str),
+                &mut app.context,
+            )[1]
+            .clone()
+        };
+        assert_eq!(evaluate(&mut app), LineResult::Value(6.0));
 
-        // visible_height of 0 should not crash
-        app.adjust_scroll(0);
+        run_settings_command(&mut app, ":set prefixes on");
+        assert_eq!(evaluate(&mut app), LineResult::Value(2000.0));
 
-        // Scroll offset should remain unchanged
-        assert_eq!(app.scroll_offset, 0);
+        run_settings_command(&mut app, ":set prefixes off");
+        assert_eq!(evaluate(&mut app), LineResult::Value(6.0));
     }
 
     #[test]
-    fn test_adjust_scroll_single_line_buffer() {
-        let mut app = App::new();
-        app.buffer.insert_char('a');
-        app.scroll_offset = 0;
-        let visible_height = 10;
+    fn test_theme_setting_overrides_detected_theme() {
+        let mut app = App::with_buffer(Buffer::new());
 
-        app.adjust_scroll(visible_height);
+        run_settings_command(&mut app, ":set theme light");
+        assert_eq!(app.theme, AppTheme::Light);
 
-        // Single line, scroll should stay at 0
-        assert_eq!(app.scroll_offset, 0);
+        run_settings_command(&mut app, ":set theme auto");
+        assert_eq!(app.theme, app.detected_theme);
     }
 
     #[test]
-    fn test_app_new_initializes_help_visible_to_false() {
-        let app = App::new();
-        assert!(!app.help_visible);
+    fn test_autosave_off_saves_only_on_quit() {
+        let mut app = App::with_buffer(Buffer::new());
+        app.settings.autosave = false;
+
+        assert!(app.apply(Action::InsertChar('1')).is_empty());
+        assert_eq!(app.apply(Action::Quit), vec![Effect::Save]);
+        assert!(!app.running);
     }
 
     #[test]
-    fn test_app_new_initializes_help_scroll_offset_to_zero() {
-        let app = App::new();
-        assert_eq!(app.help_scroll_offset, 0);
+    fn test_unsaved_edits_are_recorded_for_recovery() {
+        let mut app = App::with_buffer(Buffer::new());
+        app.settings.autosave = false;
+        app.record_edits_for_recovery();
+        let empty = app.recovery_revision;
+        assert!(empty.is_some());
+
+        app.apply(Action::InsertChar('1'));
+        app.record_edits_for_recovery();
+        assert_eq!(
+            app.recovery_revision, empty,
+            "checked at most once per interval"
+        );
+
+        app.recovery_checked = None;
+        app.record_edits_for_recovery();
+        assert_eq!(
+            app.recovery_revision,
+            Some(Revision::of(&["1".to_string()]))
+        );
     }
 
     #[test]
-    fn test_app_new_initializes_memory_pane_left_to_true() {
-        let app = App::new();
-        assert!(app.memory_pane_left);
+    fn test_wrap_off_keeps_cursor_on_line() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["1".to_string(), "2".to_string()]));
+        app.settings.wrap = false;
+
+        app.apply(Action::MoveRight);
+        app.apply(Action::MoveRight);
+        assert_eq!(
+            (app.buffer.cursor().row(), app.buffer.cursor().col()),
+            (0, 1)
+        );
+
+        app.settings.wrap = true;
+        app.apply(Action::MoveRight);
+        assert_eq!(
+            (app.buffer.cursor().row(), app.buffer.cursor().col()),
+            (1, 0)
+        );
     }
 
     #[test]
-    fn test_app_new_detects_theme() {
-        let app = App::new();
-        // Theme detection returns a valid theme (Light or Dark)
-        assert!(matches!(app.theme, AppTheme::Light | AppTheme::Dark));
+    fn test_welcome_banner_dismissed_by_escape_without_quitting() {
+        let mut app = App::with_buffer(Buffer::new());
+        app.welcome_visible = true;
+        assert_eq!(app.mode(), Mode::Welcome);
+
+        app.handle_key(KeyEvent::new(KeyCode::Char('1'), KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+
+        assert!(app.running);
+        assert!(!app.welcome_visible);
+        assert_eq!(app.buffer.current_line(), "1");
     }
 
     #[test]
-    fn test_toggle_memory_pane_position_changes_from_left_to_right() {
-        let mut app = App::new();
-        assert!(app.memory_pane_left);
-        app.toggle_memory_pane_position();
-        assert!(!app.memory_pane_left);
+    fn test_clear_all_dismisses_welcome_banner() {
+        let mut app = App::with_buffer(Buffer::new());
+        app.welcome_visible = true;
+
+        app.apply(Action::ClearAll);
+
+        assert!(!app.welcome_visible);
     }
 
     #[test]
-    fn test_toggle_memory_pane_position_changes_from_right_to_left() {
-        let mut app = App::new();
-        app.toggle_memory_pane_position();
-        assert!(!app.memory_pane_left);
-        app.toggle_memory_pane_position();
-        assert!(app.memory_pane_left);
+    fn test_complete_function_name_unique_match_adds_paren() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["2 * hyp".to_string()]));
+        app.buffer.move_cursor_to_line_end();
+
+        assert!(app.complete_function_name());
+
+        assert_eq!(app.buffer.lines(), ["2 * hypot("]);
+        assert_eq!(app.buffer.cursor().col(), 10);
+        assert_eq!(
+            app.active_notification(),
+            Some("hypot(x, y) — Length of the hypotenuse")
+        );
     }
 
     #[test]
-    fn test_toggle_help_shows_overlay_when_hidden() {
-        let mut app = App::new();
-        assert!(!app.help_visible);
+    fn test_complete_function_name_extends_common_prefix() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["ac".to_string()]));
+        app.buffer.move_cursor_to_line_end();
 
-        app.toggle_help();
+        assert!(app.complete_function_name());
 
-        assert!(app.help_visible);
+        assert_eq!(app.buffer.lines(), ["acos"]);
+        assert_eq!(app.active_notification(), Some("acos, acosh"));
     }
 
     #[test]
-    fn test_toggle_help_hides_overlay_when_visible() {
-        let mut app = App::new();
-        app.help_visible = true;
+    fn test_complete_function_name_without_match_notifies() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["xyz".to_string()]));
+        app.buffer.move_cursor_to_line_end();
 
-        app.toggle_help();
+        assert!(!app.complete_function_name());
 
-        assert!(!app.help_visible);
+        assert_eq!(app.buffer.lines(), ["xyz"]);
+        assert_eq!(
+            app.active_notification(),
+            Some("No function starts with 'xyz'")
+        );
     }
 
     #[test]
-    fn test_toggle_help_resets_scroll_offset_when_opening() {
-        let mut app = App::new();
-        app.help_scroll_offset = 5;
-
-        app.toggle_help(); // Open help
+    fn test_complete_function_name_ignores_numbers() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["12".to_string()]));
+        app.buffer.move_cursor_to_line_end();
 
-        assert_eq!(app.help_scroll_offset, 0);
+        assert!(!app.complete_function_name());
+        assert!(app.notifications.is_empty());
     }
 
     #[test]
-    fn test_close_help_hides_overlay() {
-        let mut app = App::new();
-        app.help_visible = true;
+    fn test_handle_key_escape_does_not_quit_editor() {
+        let mut app = App::with_buffer(Buffer::new());
 
-        app.close_help();
+        app.handle_key(key(KeyCode::Esc));
+        assert!(app.running);
 
-        assert!(!app.help_visible);
+        app.handle_key(ctrl('c'));
+        assert!(!app.running);
     }
 
     #[test]
-    fn test_close_help_when_already_hidden_stays_hidden() {
-        let mut app = App::new();
-        assert!(!app.help_visible);
+    fn test_quit_without_state_asks_to_discard_changes() {
+        let mut app = App::open(Persistence::None, None).expect("open without state");
+        assert!(app.apply(Action::Quit).is_empty());
+        assert!(!app.running);
 
-        app.close_help();
+        let mut app = App::open(Persistence::None, None).expect("open without state");
+        app.apply(Action::InsertChar('1'));
+        app.apply(Action::Quit);
+        assert_eq!(app.mode(), Mode::ConfirmQuit);
+        assert!(app.running);
 
-        assert!(!app.help_visible);
+        app.handle_key(key(KeyCode::Esc));
+        assert_eq!(app.mode(), Mode::Editor);
+        assert!(app.running);
+
+        run_settings_command(&mut app, ":q");
+        assert_eq!(app.mode(), Mode::ConfirmQuit);
+        app.handle_key(key(KeyCode::Char('y')));
+        assert!(!app.running);
     }
 
     #[test]
-    fn test_scroll_help_down_increases_offset() {
-        let mut app = App::new();
-        app.help_visible = true;
-        app.help_scroll_offset = 0;
+    fn test_version_command_opens_popup() {
+        let mut app = App::with_buffer(Buffer::new());
 
-        app.scroll_help_down(10); // content_height
+        run_settings_command(&mut app, ":version");
 
-        assert_eq!(app.help_scroll_offset, 1);
+        assert_eq!(app.mode(), Mode::Version);
+        let lines = app.version_info.as_deref().unwrap_or_default();
+        assert!(lines[0].starts_with("Crabculator "));
+        assert!(
+            lines
+                .iter()
+                .any(|line| line.trim_start().starts_with("Buffer"))
+        );
+
+        app.handle_key(key(KeyCode::Esc));
+        assert_eq!(app.mode(), Mode::Editor);
     }
 
     #[test]
-    fn test_scroll_help_up_decreases_offset() {
-        let mut app = App::new();
-        app.help_visible = true;
-        app.help_scroll_offset = 5;
+    fn test_diff_command_compares_with_saved_version() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("sheet.calc");
+        std::fs::write(&path, "a = 2\nb = a * 3").expect("write file");
+        let mut app = App::open(Persistence::File(path), None).expect("open worksheet");
 
-        app.scroll_help_up();
+        run_settings_command(&mut app, ":diff");
+        assert_eq!(app.mode(), Mode::Diff);
+        let view = app.diff_view.as_ref().expect("diff view");
+        assert_eq!(view.lines[0].text, "No differences from the saved version");
+        app.handle_key(key(KeyCode::Esc));
+        assert_eq!(app.mode(), Mode::Editor);
 
-        assert_eq!(app.help_scroll_offset, 4);
+        app.buffer.move_cursor_to_line_end();
+        app.buffer.insert_char('0');
+        run_settings_command(&mut app, ":diff");
+        let view = app.diff_view.as_ref().expect("diff view");
+        assert_eq!(
+            view.lines[0].text,
+            "Since the saved version: 1 changed, 0 added, 0 removed, 1 other result(s) changed"
+        );
+        assert_eq!(view.lines[2].kind, DiffKind::Changed);
+        assert_eq!(view.lines[2].text, "~    1  a = 2  →  a = 20");
+        assert_eq!(view.lines[3].text, "        => a = 2 → a = 20 (+18)");
+        assert_eq!(view.lines[4].kind, DiffKind::Result);
+
+        run_settings_command(&mut app, ":diff missing.calc");
+        assert_eq!(app.active_notification(), Some("No file missing.calc"));
     }
 
     #[test]
-    fn test_scroll_help_up_does_not_go_below_zero() {
-        let mut app = App::new();
-        app.help_visible = true;
-        app.help_scroll_offset = 0;
+    fn test_diff_command_shows_the_results_shown() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("sheet.calc");
+        std::fs::write(&path, "100 * vat").expect("write file");
+        let mut app = App::open(Persistence::File(path), None).expect("open worksheet");
+        app.context.set_variable("vat", 0.19);
 
-        app.scroll_help_up();
-
-        assert_eq!(app.help_scroll_offset, 0);
+        app.buffer.insert_char('2');
+        run_settings_command(&mut app, ":diff");
+        let view = app.diff_view.as_ref().expect("diff view");
+        assert_eq!(view.lines[3].text, "        => 19 → 399 (+380)");
     }
 
     #[test]
-    fn test_clear_all_resets_help_state() {
-        let mut app = App::new();
-        app.help_visible = true;
-        app.help_scroll_offset = 5;
+    fn test_paste_column_inserts_list_variable() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["sum(prices)".to_string()]));
 
-        app.clear_all();
+        assert_eq!(
+            run_settings_command(&mut app, ":paste"),
+            vec![Effect::ReadClipboard]
+        );
+        app.paste_column(Ok("12.5\n\n3\n".to_string()));
+        assert_eq!(app.mode(), Mode::PasteList);
+        assert_eq!(app.list_prompt.as_ref().map(ListPrompt::sum), Some(15.5));
 
-        assert!(!app.help_visible);
-        assert_eq!(app.help_scroll_offset, 0);
+        app.handle_key(key(KeyCode::Char('p')));
+        app.handle_key(key(KeyCode::Char('i')));
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.active_notification(), Some("'pi' is a built-in name"));
+        assert_eq!(app.mode(), Mode::PasteList);
+        app.notifications.clear();
+
+        app.handle_key(key(KeyCode::Backspace));
+        for c in "rices".chars() {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.mode(), Mode::Editor);
+        assert_eq!(
+            app.buffer.lines(),
+            ["prices = list(12.5, 3)", "sum(prices)"]
+        );
+        assert_eq!(
+            app.active_notification(),
+            Some("Pasted 2 numbers into prices")
+        );
+        app.notifications.clear();
+
+        app.paste_column(Ok("12.5\ntotal\n".to_string()));
+        assert_eq!(app.mode(), Mode::Editor);
+        assert_eq!(
+            app.active_notification(),
+            Some("The clipboard is not a column of numbers: line 2: \"total\" is not a number")
+        );
     }
 
     #[test]
-    fn test_adjust_horizontal_scroll_cursor_before_visible_area_scrolls_left() {
-        let mut app = App::new();
-        // Create a line with some content
-        for c in "0123456789abcdef".chars() {
-            app.buffer.insert_char(c);
-        }
-        // Move cursor to start
-        app.buffer.move_cursor_to_line_start();
-        // Set horizontal scroll offset as if we scrolled right
-        app.horizontal_scroll_offset = 10;
-        // Cursor is at column 0, which is before visible area
-        let visible_width = 10;
+    fn test_show_table_pages_through_rows() {
+        let lines = vec!["table(x^2, x, 1, 25)".to_string(), "1 + 1".to_string()];
+        let mut app = App::with_buffer(Buffer::from_lines(lines.clone()));
+        let results = evaluate_all_lines(lines.iter().map(String::as_str));
+        app.track_result_changes(&results);
+        let alt_t = KeyEvent::new(KeyCode::Char('t'), KeyModifiers::ALT);
 
-        app.adjust_horizontal_scroll(visible_width);
+        app.handle_key(alt_t);
+        assert_eq!(app.mode(), Mode::Table);
+        app.handle_key(key(KeyCode::PageDown));
+        app.handle_key(key(KeyCode::PageDown));
+        app.handle_key(key(KeyCode::PageDown));
+        let view = app.table_view.clone().unwrap();
+        assert_eq!((view.page, view.page_count()), (2, 3));
+        assert_eq!(view.page_rows()[0], (21.0, 441.0));
 
-        // Scroll should adjust to show cursor (column 0)
-        assert_eq!(app.horizontal_scroll_offset, 0);
+        app.handle_key(key(KeyCode::Esc));
+        assert_eq!(app.mode(), Mode::Editor);
+        app.buffer.move_cursor_down();
+        app.handle_key(alt_t);
+        assert_eq!(app.table_view, None);
+        assert_eq!(app.active_notification(), Some("No table on this line"));
     }
 
     #[test]
-    fn test_adjust_horizontal_scroll_cursor_after_visible_area_scrolls_right() {
-        let mut app = App::new();
-        // Create a line with content extending beyond visible area
-        for c in "0123456789abcdefghij".chars() {
-            app.buffer.insert_char(c);
-        }
-        // Cursor should be at column 20 (end of line)
-        assert_eq!(app.buffer.cursor().col(), 20);
-        // Start with scroll at 0
-        app.horizontal_scroll_offset = 0;
-        let visible_width = 10;
+    fn test_edits_past_the_limits_are_refused() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["12".to_string()]));
+        app.buffer.move_cursor_to(0, 2);
+        app.settings.limits = Limits {
+            line_length: 3,
+            lines: 2,
+            ..Limits::default()
+        };
+        app.apply_settings();
 
-        app.adjust_horizontal_scroll(visible_width);
+        app.handle_key(key(KeyCode::Char('3')));
+        app.handle_key(key(KeyCode::Char('4')));
+        assert_eq!(app.buffer.lines(), ["123"]);
+        assert_eq!(
+            app.active_notification(),
+            Some("Not typed: line is longer than 3 characters (see maxlength)")
+        );
 
-        // Scroll should adjust so cursor is visible
-        // With margin of 4 (min(10, 5) - 1), cursor at 20 needs offset >= 20 - (10 - 4 - 1) = 15
-        assert!(app.horizontal_scroll_offset >= 15);
+        app.notifications.clear();
+        app.handle_key(key(KeyCode::Enter));
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.buffer.lines(), ["123", ""]);
+        assert_eq!(
+            app.active_notification(),
+            Some("Edit undone: worksheet has more than 2 lines (see maxlines)")
+        );
+        app.handle_key(ctrl('u'));
+        assert_eq!(app.buffer.lines(), ["123"]);
     }
 
     #[test]
-    fn test_adjust_horizontal_scroll_cursor_within_visible_area_no_change() {
-        let mut app = App::new();
-        // Create a line with some content
-        for c in "0123456789".chars() {
-            app.buffer.insert_char(c);
-        }
-        // Move cursor to middle (column 5)
-        app.buffer.move_cursor_to_line_start();
-        for _ in 0..5 {
-            app.buffer.move_cursor_right();
-        }
-        assert_eq!(app.buffer.cursor().col(), 5);
-        // Set scroll offset to 0
-        app.horizontal_scroll_offset = 0;
-        let visible_width = 20;
+    fn test_new_command_starts_template_worksheet() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["1 + 1".to_string()]));
 
-        app.adjust_horizontal_scroll(visible_width);
+        run_settings_command(&mut app, ":new tip");
 
-        // No change needed, cursor is within visible area
-        assert_eq!(app.horizontal_scroll_offset, 0);
+        assert_eq!(app.mode(), Mode::Editor);
+        assert_eq!(app.buffer.lines()[1], "bill = 84.50");
+        assert_eq!(
+            app.active_notification(),
+            Some("New worksheet from template 'tip'")
+        );
+        app.apply(Action::Undo);
+        assert_eq!(app.buffer.lines(), ["1 + 1"]);
+
+        app.notifications.clear();
+        run_settings_command(&mut app, ":new nothing");
+        assert_eq!(
+            app.active_notification(),
+            Some("Unknown template 'nothing'")
+        );
+        assert_eq!(app.buffer.lines(), ["1 + 1"]);
     }
 
     #[test]
-    fn test_adjust_horizontal_scroll_with_zero_visible_width() {
-        let mut app = App::new();
-        app.buffer.insert_char('a');
-        app.horizontal_scroll_offset = 5;
+    fn test_new_command_opens_template_picker() {
+        let mut app = App::with_buffer(Buffer::new());
 
-        // visible_width of 0 should not crash and not change offset
-        app.adjust_horizontal_scroll(0);
+        run_settings_command(&mut app, ":new");
 
-        // Scroll offset should remain unchanged
-        assert_eq!(app.horizontal_scroll_offset, 5);
+        assert_eq!(app.mode(), Mode::Templates);
+        let picker = app.template_picker.clone().unwrap();
+        assert!(picker.names.iter().any(|name| name == "loan"));
+        assert_eq!(picker.chosen(), Some(picker.names[0].as_str()));
+
+        let chosen = picker.names[1].clone();
+        app.handle_key(key(KeyCode::Down));
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.mode(), Mode::Editor);
+        assert_eq!(
+            app.active_notification(),
+            Some(format!("New worksheet from template '{chosen}'").as_str())
+        );
+
+        run_settings_command(&mut app, ":new");
+        app.handle_key(key(KeyCode::Esc));
+        assert_eq!(app.mode(), Mode::Editor);
     }
 
     #[test]
-    fn test_clear_all_resets_horizontal_scroll_offset() {
-        let mut app = App::new();
-        app.horizontal_scroll_offset = 10;
+    fn test_handle_key_records_debug_events() {
+        let mut app = App::with_buffer(Buffer::new());
+        app.handle_key(key(KeyCode::Char('1')));
+        assert!(app.debug.is_none());
 
-        app.clear_all();
+        app.debug = Some(DebugInfo::default());
+        app.handle_key(key(KeyCode::Char('2')));
+        app.handle_key(key(KeyCode::F(9)));
 
-        assert_eq!(app.horizontal_scroll_offset, 0);
+        let events = &app.debug.as_ref().unwrap().events;
+        assert_eq!(events.iter().collect::<Vec<_>>(), ["InsertChar('2')"]);
     }
 
     #[test]
-    fn test_app_new_initializes_horizontal_scroll_offset_to_zero() {
-        let app = App::new();
-        assert_eq!(app.horizontal_scroll_offset, 0);
+    fn test_quit_command_quits() {
+        let mut app = App::with_buffer(Buffer::from_lines(vec!["1".to_string()]));
+
+        run_settings_command(&mut app, ":quit");
+
+        assert!(!app.running);
+        assert!(app.settings_prompt.is_none());
     }
 
     #[test]
@@ -0,0 +1,212 @@
+//! Named bookmarks on buffer lines.
+//!
+//! A bookmark is set with `:mark name` and followed with `:'name`, with
+//! `Alt+Up`/`Alt+Down` to the previous or next one, or with `Alt+1` to
+//! `Alt+9` for numbered ones. Bookmarks move with their lines when lines are
+//! inserted or removed above them, and are saved with the session as
+//! `name = line` pairs.
+
+/// Bookmarks by name, each on a 0-based buffer line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bookmarks {
+    marks: Vec<(String, usize)>,
+}
+
+impl Bookmarks {
+    /// Builds bookmarks from persisted `name = line` pairs with 1-based line
+    /// numbers. Invalid names, invalid numbers and lines past `line_count`
+    /// are ignored.
+    #[must_use]
+    pub fn from_entries<'a>(
+        entries: impl IntoIterator<Item = (&'a str, &'a str)>,
+        line_count: usize,
+    ) -> Self {
+        let mut bookmarks = Self::default();
+        for (name, line) in entries {
+            if let Ok(number @ 1..) = line.parse::<usize>()
+                && number <= line_count
+            {
+                let _ = bookmarks.set(name, number - 1);
+            }
+        }
+        bookmarks
+    }
+
+    /// Returns the bookmarks as `name = line` pairs with 1-based line numbers.
+    #[must_use]
+    pub fn entries(&self) -> Vec<(String, String)> {
+        self.marks
+            .iter()
+            .map(|(name, line)| (name.clone(), (line + 1).to_string()))
+            .collect()
+    }
+
+    /// Returns true if there are no bookmarks.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.marks.is_empty()
+    }
+
+    /// Sets the bookmark `name` on `line`, moving it if it exists.
+    ///
+    /// # Errors
+    /// Returns a message for the user if `name` is not made of letters,
+    /// digits and underscores.
+    pub fn set(&mut self, name: &str, line: usize) -> Result<(), String> {
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!("Invalid bookmark name '{name}'"));
+        }
+        match self.marks.iter_mut().find(|(mark, _)| mark == name) {
+            Some((_, existing)) => *existing = line,
+            None => self.marks.push((name.to_string(), line)),
+        }
+        Ok(())
+    }
+
+    /// Removes the bookmark `name`, returning whether it existed.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let count = self.marks.len();
+        self.marks.retain(|(mark, _)| mark != name);
+        self.marks.len() != count
+    }
+
+    /// Removes every bookmark.
+    pub fn clear(&mut self) {
+        self.marks.clear();
+    }
+
+    /// Returns the line of the bookmark `name`.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.marks
+            .iter()
+            .find(|(mark, _)| mark == name)
+            .map(|&(_, line)| line)
+    }
+
+    /// Lists the bookmarks in line order, e.g. `budget: 3, 1: 40`.
+    #[must_use]
+    pub fn list(&self) -> String {
+        let mut marks: Vec<_> = self.marks.iter().collect();
+        marks.sort_by_key(|&(_, line)| *line);
+        marks
+            .iter()
+            .map(|(name, line)| format!("{name}: {}", line + 1))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Returns the first bookmarked line below `line`, wrapping around to the
+    /// first bookmark.
+    #[must_use]
+    pub fn next_after(&self, line: usize) -> Option<usize> {
+        let lines = self.marks.iter().map(|&(_, mark)| mark);
+        lines
+            .clone()
+            .filter(|&mark| mark > line)
+            .min()
+            .or_else(|| lines.min())
+    }
+
+    /// Returns the last bookmarked line above `line`, wrapping around to the
+    /// last bookmark.
+    #[must_use]
+    pub fn previous_before(&self, line: usize) -> Option<usize> {
+        let lines = self.marks.iter().map(|&(_, mark)| mark);
+        lines
+            .clone()
+            .filter(|&mark| mark < line)
+            .max()
+            .or_else(|| lines.max())
+    }
+
+    /// Moves bookmarks down after a line was inserted at `at`.
+    pub fn line_inserted(&mut self, at: usize) {
+        for (_, line) in &mut self.marks {
+            if *line >= at {
+                *line += 1;
+            }
+        }
+    }
+
+    /// Moves bookmarks up after the line at `at` was joined to the line
+    /// above it; bookmarks on the removed line move along to that line.
+    pub fn line_removed(&mut self, at: usize) {
+        for (_, line) in &mut self.marks {
+            if *line >= at {
+                *line = line.saturating_sub(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmarks() -> Bookmarks {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.set("budget", 2).unwrap();
+        bookmarks.set("1", 10).unwrap();
+        bookmarks
+    }
+
+    #[test]
+    fn test_set_moves_existing_bookmark() {
+        let mut bookmarks = bookmarks();
+        bookmarks.set("budget", 5).unwrap();
+
+        assert_eq!(bookmarks.get("budget"), Some(5));
+        assert_eq!(bookmarks.list(), "budget: 6, 1: 11");
+        assert!(bookmarks.set("a b", 1).is_err());
+    }
+
+    #[test]
+    fn test_next_and_previous_wrap_around() {
+        let bookmarks = bookmarks();
+
+        assert_eq!(bookmarks.next_after(0), Some(2));
+        assert_eq!(bookmarks.next_after(2), Some(10));
+        assert_eq!(bookmarks.next_after(10), Some(2));
+        assert_eq!(bookmarks.previous_before(10), Some(2));
+        assert_eq!(bookmarks.previous_before(2), Some(10));
+        assert_eq!(Bookmarks::default().next_after(0), None);
+    }
+
+    #[test]
+    fn test_bookmarks_move_with_their_lines() {
+        let mut bookmarks = bookmarks();
+
+        bookmarks.line_inserted(2);
+        assert_eq!(bookmarks.get("budget"), Some(3));
+        assert_eq!(bookmarks.get("1"), Some(11));
+
+        bookmarks.line_removed(3);
+        assert_eq!(bookmarks.get("budget"), Some(2));
+        bookmarks.line_removed(5);
+        assert_eq!(bookmarks.get("budget"), Some(2));
+        assert_eq!(bookmarks.get("1"), Some(9));
+    }
+
+    #[test]
+    fn test_entries_round_trip_with_one_based_lines() {
+        let bookmarks = bookmarks();
+        let entries = bookmarks.entries();
+
+        assert_eq!(entries[0], ("budget".to_string(), "3".to_string()));
+        let loaded = Bookmarks::from_entries(
+            entries
+                .iter()
+                .map(|(name, line)| (name.as_str(), line.as_str())),
+            20,
+        );
+        assert_eq!(loaded, bookmarks);
+    }
+
+    #[test]
+    fn test_from_entries_skips_lines_past_the_buffer() {
+        let loaded = Bookmarks::from_entries([("a", "3"), ("b", "30"), ("c", "0")], 20);
+
+        assert_eq!(loaded.entries(), [("a".to_string(), "3".to_string())]);
+    }
+}
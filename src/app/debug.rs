@@ -0,0 +1,43 @@
+//! Timings and recent events for the debug overlay shown with `--debug`.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How many recent events the overlay lists.
+pub const DEBUG_EVENT_COUNT: usize = 5;
+
+/// What the debug overlay shows, updated as the application runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    /// How long drawing the last frame took, evaluation included.
+    pub frame_time: Duration,
+    /// How long evaluating the buffer took for the last frame.
+    pub eval_time: Duration,
+    /// The most recent events, oldest first.
+    pub events: VecDeque<String>,
+}
+
+impl DebugInfo {
+    /// Records an event, dropping the oldest beyond `DEBUG_EVENT_COUNT`.
+    pub fn record(&mut self, event: impl Into<String>) {
+        if self.events.len() == DEBUG_EVENT_COUNT {
+            self.events.pop_front();
+        }
+        self.events.push_back(event.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_keeps_recent_events() {
+        let mut info = DebugInfo::default();
+        for i in 0..=DEBUG_EVENT_COUNT {
+            info.record(format!("event {i}"));
+        }
+        assert_eq!(info.events.len(), DEBUG_EVENT_COUNT);
+        assert_eq!(info.events.front().map(String::as_str), Some("event 1"));
+    }
+}
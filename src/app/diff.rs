@@ -0,0 +1,416 @@
+//! The `:diff` popup: how the worksheet differs from its saved version, or
+//! from another worksheet file, line by line and result by result.
+//!
+//! Lines are matched by their longest common subsequence. A removed line
+//! followed by an added one in the same place counts as changed, and a line
+//! left as it was whose result changed, through a variable it uses, is
+//! listed too. Numeric results show how much they changed.
+
+use crate::eval::LineResult;
+use crate::ui::{NumberFormat, result_comments};
+
+/// Rows shown on one page of the diff popup.
+pub const DIFF_PAGE_SIZE: usize = 15;
+
+/// Largest number of line pairs compared to match the lines; larger edits
+/// are shown as the old lines removed and the new ones added.
+const MAX_COMPARISONS: usize = 1_000_000;
+
+/// How a row of the diff popup differs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    /// A summary of the differences.
+    Summary,
+    /// A line only in the current worksheet.
+    Added,
+    /// A line only in the other worksheet.
+    Removed,
+    /// A line edited in place.
+    Changed,
+    /// A line left as it was, with a different result.
+    Result,
+}
+
+/// A row of the diff popup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    /// How the row differs.
+    pub kind: DiffKind,
+    /// The text of the row.
+    pub text: String,
+}
+
+impl DiffLine {
+    /// Creates a row of `kind` reading `text`.
+    #[must_use]
+    pub const fn new(kind: DiffKind, text: String) -> Self {
+        Self { kind, text }
+    }
+}
+
+/// The differences shown in the diff popup, and the page in view.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffView {
+    /// The rows of the popup.
+    pub lines: Vec<DiffLine>,
+    /// The page in view, from 0.
+    pub page: usize,
+}
+
+impl DiffView {
+    /// Shows `lines` from the first page.
+    #[must_use]
+    pub const fn new(lines: Vec<DiffLine>) -> Self {
+        Self { lines, page: 0 }
+    }
+
+    /// Returns the number of pages, at least one.
+    #[must_use]
+    pub const fn page_count(&self) -> usize {
+        if self.lines.is_empty() {
+            1
+        } else {
+            self.lines.len().div_ceil(DIFF_PAGE_SIZE)
+        }
+    }
+
+    /// Returns the rows of the page in view.
+    #[must_use]
+    pub fn page_lines(&self) -> &[DiffLine] {
+        let start = (self.page * DIFF_PAGE_SIZE).min(self.lines.len());
+        let end = (start + DIFF_PAGE_SIZE).min(self.lines.len());
+        &self.lines[start..end]
+    }
+
+    /// Turns to the next page, if there is one.
+    pub const fn next_page(&mut self) {
+        if self.page + 1 < self.page_count() {
+            self.page += 1;
+        }
+    }
+
+    /// Turns to the previous page, if there is one.
+    pub const fn previous_page(&mut self) {
+        self.page = self.page.saturating_sub(1);
+    }
+}
+
+/// A line of either worksheet with its result.
+#[derive(Clone, Copy)]
+struct Side<'a> {
+    lines: &'a [String],
+    results: &'a [LineResult],
+    comments: &'a [Option<String>],
+}
+
+impl Side<'_> {
+    /// Formats line `row` with its result, as `12  a = 2  => a = 2`.
+    fn describe(&self, row: usize) -> String {
+        let line = self.lines[row].trim_end();
+        self.comments.get(row).cloned().flatten().map_or_else(
+            || format!("{:>4}  {line}", row + 1),
+            |result| format!("{:>4}  {line}  => {result}", row + 1),
+        )
+    }
+
+    /// Returns the result text of line `row`, or `-` if it has none.
+    fn result(&self, row: usize) -> String {
+        self.comments
+            .get(row)
+            .cloned()
+            .flatten()
+            .unwrap_or_else(|| "-".to_string())
+    }
+
+    /// Returns the number line `row` evaluates to, if any.
+    fn number(&self, row: usize) -> Option<f64> {
+        self.results.get(row).and_then(LineResult::number)
+    }
+}
+
+/// A step from the old lines to the new ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    /// Old line `.0` is new line `.1`.
+    Same(usize, usize),
+    /// Old line `.0` was removed.
+    Removed(usize),
+    /// New line `.0` was added.
+    Added(usize),
+}
+
+/// Returns the steps turning `old` into `new`, keeping the longest common
+/// subsequence of lines.
+fn edits(old: &[String], new: &[String]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut steps: Vec<Edit> = (0..prefix).map(|i| Edit::Same(i, i)).collect();
+    if old_middle.len() * new_middle.len() > MAX_COMPARISONS {
+        steps.extend((0..old_middle.len()).map(|i| Edit::Removed(prefix + i)));
+        steps.extend((0..new_middle.len()).map(|j| Edit::Added(prefix + j)));
+    } else {
+        steps.extend(common_subsequence(old_middle, new_middle).into_iter().map(
+            |edit| match edit {
+                Edit::Same(i, j) => Edit::Same(prefix + i, prefix + j),
+                Edit::Removed(i) => Edit::Removed(prefix + i),
+                Edit::Added(j) => Edit::Added(prefix + j),
+            },
+        ));
+    }
+    let old_end = old.len() - suffix;
+    let new_end = new.len() - suffix;
+    steps.extend((0..suffix).map(|k| Edit::Same(old_end + k, new_end + k)));
+    steps
+}
+
+/// Returns the steps turning `old` into `new` by dynamic programming over
+/// every pair of lines.
+fn common_subsequence(old: &[String], new: &[String]) -> Vec<Edit> {
+    let width = new.len() + 1;
+    // lengths[i * width + j]: longest common subsequence of old[i..], new[j..].
+    let mut lengths = vec![0usize; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut steps = Vec::new();
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            steps.push(Edit::Same(i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            steps.push(Edit::Removed(i));
+            i += 1;
+        } else {
+            steps.push(Edit::Added(j));
+            j += 1;
+        }
+    }
+    steps.extend((i..old.len()).map(Edit::Removed));
+    steps.extend((j..new.len()).map(Edit::Added));
+    steps
+}
+
+/// Splits a run of edits into the old lines removed and the new lines added.
+fn split_run(run: &[Edit]) -> (Vec<usize>, Vec<usize>) {
+    let mut gone = Vec::new();
+    let mut came = Vec::new();
+    for step in run {
+        match *step {
+            Edit::Removed(i) => gone.push(i),
+            Edit::Added(j) => came.push(j),
+            Edit::Same(..) => {}
+        }
+    }
+    (gone, came)
+}
+
+/// Describes how the result of a changed line changed, such as
+/// `2 → 3 (+1)`.
+fn result_change(old: Side, old_row: usize, new: Side, new_row: usize) -> String {
+    let change = format!("{} → {}", old.result(old_row), new.result(new_row));
+    match (old.number(old_row), new.number(new_row)) {
+        (Some(before), Some(after)) if before.to_bits() != after.to_bits() => {
+            let delta = after - before;
+            let sign = if delta > 0.0 { "+" } else { "" };
+            format!("{change} ({sign}{})", NumberFormat::default().format(delta))
+        }
+        _ => change,
+    }
+}
+
+/// Builds the rows of the diff popup, from the worksheet `old_lines` named
+/// `source` to `new_lines`, each with its results.
+#[must_use]
+pub fn diff_lines(
+    source: &str,
+    old_lines: &[String],
+    old_results: &[LineResult],
+    new_lines: &[String],
+    new_results: &[LineResult],
+) -> Vec<DiffLine> {
+    let old_comments = result_comments(old_results);
+    let new_comments = result_comments(new_results);
+    let old = Side {
+        lines: old_lines,
+        results: old_results,
+        comments: &old_comments,
+    };
+    let new = Side {
+        lines: new_lines,
+        results: new_results,
+        comments: &new_comments,
+    };
+
+    let mut rows = Vec::new();
+    let (mut added, mut removed, mut changed, mut results) = (0, 0, 0, 0);
+    let steps = edits(old_lines, new_lines);
+    let mut index = 0;
+    while index < steps.len() {
+        if let Edit::Same(i, j) = steps[index] {
+            if old_comments.get(i) != new_comments.get(j) {
+                results += 1;
+                rows.push(DiffLine::new(
+                    DiffKind::Result,
+                    format!("= {:>4}  {}", j + 1, new_lines[j].trim_end()),
+                ));
+                rows.push(DiffLine::new(
+                    DiffKind::Result,
+                    format!("        => {}", result_change(old, i, new, j)),
+                ));
+            }
+            index += 1;
+            continue;
+        }
+        // A run of removed and added lines between two unchanged ones.
+        let end = steps[index..]
+            .iter()
+            .position(|step| matches!(step, Edit::Same(..)))
+            .map_or(steps.len(), |offset| index + offset);
+        let (gone, came) = split_run(&steps[index..end]);
+        for (&i, &j) in gone.iter().zip(&came) {
+            changed += 1;
+            rows.push(DiffLine::new(
+                DiffKind::Changed,
+                format!(
+                    "~ {:>4}  {}  →  {}",
+                    j + 1,
+                    old_lines[i].trim_end(),
+                    new_lines[j].trim_end()
+                ),
+            ));
+            rows.push(DiffLine::new(
+                DiffKind::Changed,
+                format!("        => {}", result_change(old, i, new, j)),
+            ));
+        }
+        for &i in gone.iter().skip(came.len()) {
+            removed += 1;
+            rows.push(DiffLine::new(
+                DiffKind::Removed,
+                format!("- {}", old.describe(i)),
+            ));
+        }
+        for &j in came.iter().skip(gone.len()) {
+            added += 1;
+            rows.push(DiffLine::new(
+                DiffKind::Added,
+                format!("+ {}", new.describe(j)),
+            ));
+        }
+        index = end;
+    }
+
+    let summary = if rows.is_empty() {
+        format!("No differences from {source}")
+    } else {
+        format!(
+            "Since {source}: {changed} changed, {added} added, {removed} removed, {results} other result(s) changed"
+        )
+    };
+    let mut lines = vec![
+        DiffLine::new(DiffKind::Summary, summary),
+        DiffLine::new(DiffKind::Summary, String::new()),
+    ];
+    lines.extend(rows);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::evaluate_all_lines;
+
+    fn to_lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(ToString::to_string).collect()
+    }
+
+    fn diff(old: &[&str], new: &[&str]) -> Vec<String> {
+        let old = to_lines(old);
+        let new = to_lines(new);
+        let old_results = evaluate_all_lines(old.iter().map(String::as_str));
+        let new_results = evaluate_all_lines(new.iter().map(String::as_str));
+        diff_lines("the saved version", &old, &old_results, &new, &new_results)
+            .into_iter()
+            .map(|line| line.text)
+            .collect()
+    }
+
+    #[test]
+    fn test_edits_keep_common_lines() {
+        let old = to_lines(&["a", "b", "c", "d"]);
+        let new = to_lines(&["a", "c", "x", "d"]);
+
+        assert_eq!(
+            edits(&old, &new),
+            [
+                Edit::Same(0, 0),
+                Edit::Removed(1),
+                Edit::Same(2, 1),
+                Edit::Added(2),
+                Edit::Same(3, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_lists_changes_with_result_deltas() {
+        let lines = diff(
+            &["a = 2", "b = a * 3", "c = 4", "# note"],
+            &["a = 3", "b = a * 3", "# note", "d = 1"],
+        );
+
+        assert_eq!(
+            lines,
+            [
+                "Since the saved version: 1 changed, 1 added, 1 removed, 1 other result(s) changed",
+                "",
+                "~    1  a = 2  →  a = 3",
+                "        => a = 2 → a = 3 (+1)",
+                "=    2  b = a * 3",
+                "        => b = 6 → b = 9 (+3)",
+                "-    3  c = 4  => c = 4",
+                "+    4  d = 1  => d = 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_without_differences() {
+        assert_eq!(
+            diff(&["a = 1"], &["a = 1"]),
+            ["No differences from the saved version", ""]
+        );
+    }
+
+    #[test]
+    fn test_pages_hold_page_size_rows() {
+        let mut view = DiffView::new(
+            (0..20)
+                .map(|i| DiffLine::new(DiffKind::Added, i.to_string()))
+                .collect(),
+        );
+
+        assert_eq!(view.page_count(), 2);
+        view.next_page();
+        view.next_page();
+        assert_eq!(view.page_lines().len(), 5);
+        view.previous_page();
+        assert_eq!(view.page_lines()[0].text, "0");
+    }
+}
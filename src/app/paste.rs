@@ -0,0 +1,74 @@
+//! The `:paste` command: a column of numbers on the clipboard, such as
+//! cells copied from a spreadsheet, becomes a list variable.
+//!
+//! The clipboard is read by the event loop, which hands its contents to
+//! `App::paste_column`. If they are one number per line, a prompt asks for
+//! the name of the list, and a line such as `prices = list(12.5, 3, 7)` is
+//! inserted above the cursor.
+
+use crate::eval::constants::is_math_constant;
+use crate::eval::functions::is_builtin_function;
+use crate::eval::parser::is_valid_identifier;
+
+/// The numbers read from the clipboard and the name being typed for them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListPrompt {
+    /// The name typed so far.
+    pub name: String,
+    /// The numbers to assign.
+    pub values: Vec<f64>,
+}
+
+impl ListPrompt {
+    /// Asks for the name of `values`.
+    #[must_use]
+    pub const fn new(values: Vec<f64>) -> Self {
+        Self {
+            name: String::new(),
+            values,
+        }
+    }
+
+    /// Returns the sum of the numbers.
+    #[must_use]
+    pub fn sum(&self) -> f64 {
+        self.values.iter().sum()
+    }
+}
+
+/// Checks that `name` can hold a list.
+///
+/// # Errors
+/// Returns a message for the user if `name` is not a variable name or is
+/// taken by a constant or function.
+pub fn check_list_name(name: &str) -> Result<(), String> {
+    if !is_valid_identifier(name) {
+        Err(format!("'{name}' is not a valid variable name"))
+    } else if is_math_constant(name) || is_builtin_function(name) {
+        Err(format!("'{name}' is a built-in name"))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_list_name() {
+        assert_eq!(check_list_name("prices"), Ok(()));
+        assert_eq!(
+            check_list_name("2x"),
+            Err("'2x' is not a valid variable name".to_string())
+        );
+        assert_eq!(
+            check_list_name("pi"),
+            Err("'pi' is a built-in name".to_string())
+        );
+        assert_eq!(
+            check_list_name("sum"),
+            Err("'sum' is a built-in name".to_string())
+        );
+    }
+}
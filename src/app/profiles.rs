@@ -0,0 +1,76 @@
+//! Named profiles that set up the calculator for a kind of work.
+//!
+//! A profile is a list of `key = value` entries in the settings format:
+//! keys that name a setting or a format preset change it as `:set` would,
+//! and any other key defines a variable with the value of its expression.
+//! Profiles are chosen at launch with `--profile name` or later with
+//! `:profile name`. Files in `~/.crabculator/profiles/` define new profiles
+//! or replace the built-in ones of the same name.
+
+use super::settings::{PRESET_KEY_PREFIX, SETTING_KEYS};
+
+/// Profiles available without a profile file, by name.
+pub const BUILTIN_PROFILES: &[(&str, &[(&str, &str)])] = &[
+    (
+        "electronics",
+        &[
+            ("prefixes", "on"),
+            ("sigfigs", "on"),
+            ("eps0", "8.8541878128e-12"),
+            ("mu0", "1.25663706212e-6"),
+        ],
+    ),
+    (
+        "finance",
+        &[
+            ("separators", "on"),
+            ("format.money", "2dp, thousands"),
+            ("format", "money"),
+        ],
+    ),
+];
+
+/// Returns the entries of the built-in profile `name`.
+#[must_use]
+pub fn builtin_profile(name: &str) -> Option<Vec<(String, String)>> {
+    BUILTIN_PROFILES
+        .iter()
+        .find(|(profile, _)| *profile == name)
+        .map(|(_, entries)| {
+            entries
+                .iter()
+                .map(|&(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        })
+}
+
+/// Returns `true` if a profile entry with `key` changes a setting rather
+/// than defining a variable.
+#[must_use]
+pub fn is_setting_key(key: &str) -> bool {
+    SETTING_KEYS.contains(&key) || key.starts_with(PRESET_KEY_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_profiles_are_valid() {
+        for (name, _) in BUILTIN_PROFILES {
+            let entries = builtin_profile(name).expect("profile exists");
+            let mut settings = super::super::Settings::default();
+            for (key, value) in entries.iter().filter(|(key, _)| is_setting_key(key)) {
+                assert_eq!(settings.set(key, value), Ok(()), "{name}: {key}");
+            }
+        }
+        assert!(builtin_profile("astronomy").is_none());
+    }
+
+    #[test]
+    fn test_is_setting_key() {
+        assert!(is_setting_key("precision"));
+        assert!(is_setting_key("format.money"));
+        assert!(!is_setting_key("vat"));
+    }
+}
@@ -0,0 +1,70 @@
+//! The sum, mean and count of the results of the lines of a block, shown in
+//! the command bar the way a spreadsheet's status bar shows them for the
+//! selected cells.
+//!
+//! The figures are taken from the results of the last render, so they
+//! follow edits and cursor moves without evaluating the lines again. Lines
+//! without a numeric result, such as comments, errors and lists, are
+//! skipped.
+
+use crate::eval::LineResult;
+
+/// Figures of the numeric results of several lines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuickSum {
+    /// Number of lines looked at.
+    pub lines: usize,
+    /// Number of lines with a numeric result.
+    pub count: usize,
+    /// Sum of the numeric results.
+    pub sum: f64,
+}
+
+impl QuickSum {
+    /// Adds up the numeric results of `results`.
+    #[must_use]
+    pub fn of(results: &[LineResult]) -> Self {
+        let numbers: Vec<f64> = results.iter().filter_map(LineResult::number).collect();
+        Self {
+            lines: results.len(),
+            count: numbers.len(),
+            sum: numbers.iter().sum(),
+        }
+    }
+
+    /// Returns the mean of the numeric results, if there are any.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quick_sum_skips_lines_without_numbers() {
+        let results = [
+            LineResult::Value(12.5),
+            LineResult::Empty,
+            LineResult::Assignment {
+                name: "b".to_string(),
+                value: 3.5,
+            },
+            LineResult::Symbolic("[1, 2] (2 values)".to_string()),
+        ];
+        let quick_sum = QuickSum::of(&results);
+        assert_eq!(
+            quick_sum,
+            QuickSum {
+                lines: 4,
+                count: 2,
+                sum: 16.0
+            }
+        );
+        assert_eq!(quick_sum.mean(), Some(8.0));
+        assert_eq!(QuickSum::of(&[LineResult::Empty]).mean(), None);
+    }
+}
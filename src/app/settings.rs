@@ -0,0 +1,605 @@
+//! User settings and the `:set` command that changes them.
+//!
+//! Settings are persisted as `key = value` text, so `Settings` converts to and
+//! from such pairs; the storage layer only reads and writes them.
+//!
+//! Besides the fixed settings, keys of the form `format.name` define named
+//! result format presets, such as `format.money = 2dp, thousands, prefix "€"`,
+//! which lines can use with `| money` and the `format` setting can make the
+//! default.
+
+use crate::eval::directive::FormatPresets;
+use crate::eval::limits::Limits;
+use crate::eval::money::Rounding;
+use crate::eval::numformat::FormatSpec;
+use crate::eval::{AngleMode, DivisionMode};
+use crate::ui::{
+    AppTheme, DEFAULT_MEMORY_PERCENT, ErrorPlacement, MEMORY_PERCENT_RANGE, NumberFormat,
+};
+
+/// Prefix of the keys that define format presets, e.g. `format.money`.
+pub const PRESET_KEY_PREFIX: &str = "format.";
+
+/// Largest number of decimal places accepted by the `precision` setting.
+pub const MAX_PRECISION: usize = 15;
+
+/// Names of all settings, in display order.
+pub const SETTING_KEYS: &[&str] = &[
+    "precision",
+    "angle",
+    "division",
+    "theme",
+    "pane",
+    "ratio",
+    "brackets",
+    "align",
+    "wrap",
+    "autosave",
+    "results",
+    "separators",
+    "prefixes",
+    "sigfigs",
+    "rounding",
+    "shell",
+    "mouse",
+    "clickcopy",
+    "printexit",
+    "overview",
+    "number",
+    "relativenumber",
+    "markers",
+    "errors",
+    "accessible",
+    "ascii",
+    "motion",
+    "maxlength",
+    "maxlines",
+    "maxdepth",
+    "maxlist",
+    "format",
+];
+
+/// User-adjustable settings.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// Decimal places results are rounded to, `None` for full precision.
+    pub precision: Option<usize>,
+    /// The unit of angles used by trigonometric functions.
+    pub angle_mode: AngleMode,
+    /// What `/` does with two whole numbers.
+    pub division: DivisionMode,
+    /// The color theme, `None` to follow the terminal.
+    pub theme: Option<AppTheme>,
+    /// Whether the memory pane is left of the input panel.
+    pub pane_left: bool,
+    /// The share of the width, in percent, taken by the memory pane.
+    pub ratio: u16,
+    /// Whether parentheses are colored by depth in the editor.
+    pub brackets: bool,
+    /// Whether the decimal points of results line up in the memory pane.
+    pub align: bool,
+    /// Whether the cursor wraps to the neighbouring line at line ends.
+    pub wrap: bool,
+    /// Whether the buffer is saved after every edit rather than on quit.
+    pub autosave: bool,
+    /// Whether results are saved as `# =>` comments after their lines.
+    pub results: bool,
+    /// Whether results group thousands with commas.
+    pub separators: bool,
+    /// Whether number literals accept engineering prefixes such as `4.7k`.
+    pub prefixes: bool,
+    /// Whether results are shown rounded to the significant figures of their
+    /// inputs.
+    pub sigfigs: bool,
+    /// How amounts of money are rounded to minor units.
+    pub rounding: Rounding,
+    /// Whether backtick shell commands such as `` `wc -l < file` `` may run.
+    pub shell: bool,
+    /// Whether mouse clicks are captured, which keeps the terminal from
+    /// selecting text without `Shift`.
+    pub mouse: bool,
+    /// Whether clicking a result copies its value to the clipboard.
+    pub clickcopy: bool,
+    /// Whether the worksheet with its results is printed to the terminal
+    /// on quit.
+    pub printexit: bool,
+    /// Whether an overview strip of the worksheet is shown beside the
+    /// memory pane.
+    pub overview: bool,
+    /// Whether the input panel's gutter shows line numbers.
+    pub number: bool,
+    /// Whether the gutter numbers lines by their distance from the cursor
+    /// line.
+    pub relativenumber: bool,
+    /// Whether the gutter marks lines that assign variables, have errors or
+    /// warnings, or are disabled.
+    pub markers: bool,
+    /// Where error and warning messages are shown.
+    pub errors: ErrorPlacement,
+    /// Whether results are shown as text after their lines in a single
+    /// panel, without relying on color, for screen readers.
+    pub accessible: bool,
+    /// Whether box-drawing characters, arrows and markers are drawn in
+    /// ASCII, without background highlights.
+    pub ascii: bool,
+    /// Whether results changed by an edit elsewhere are briefly highlighted.
+    pub motion: bool,
+    /// The largest lines, worksheets and expressions that are evaluated.
+    pub limits: Limits,
+    /// The format for results of lines without a directive, `None` for the
+    /// usual display.
+    pub format: Option<String>,
+    /// Named format presets with their definitions, in definition order.
+    pub presets: Vec<(String, String)>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            precision: None,
+            angle_mode: AngleMode::Radians,
+            division: DivisionMode::True,
+            theme: None,
+            pane_left: true,
+            ratio: DEFAULT_MEMORY_PERCENT,
+            brackets: true,
+            align: true,
+            wrap: true,
+            autosave: true,
+            results: false,
+            separators: false,
+            prefixes: false,
+            sigfigs: false,
+            rounding: Rounding::HalfUp,
+            shell: false,
+            mouse: false,
+            clickcopy: false,
+            printexit: false,
+            overview: false,
+            number: true,
+            relativenumber: false,
+            markers: true,
+            errors: ErrorPlacement::Inline,
+            accessible: false,
+            ascii: false,
+            motion: true,
+            limits: Limits::default(),
+            format: None,
+            presets: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Builds settings from persisted `key = value` pairs.
+    ///
+    /// Unknown keys and invalid values are ignored, keeping their defaults.
+    /// Presets are defined first, so that `format` can name a preset defined
+    /// further down.
+    #[must_use]
+    pub fn from_entries<'a>(entries: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let (presets, others): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .partition(|(key, _)| key.starts_with(PRESET_KEY_PREFIX));
+        let mut settings = Self::default();
+        for (key, value) in presets.into_iter().chain(others) {
+            let _ = settings.set(key, value);
+        }
+        settings
+    }
+
+    /// Returns every setting with its current value, in display order,
+    /// followed by the format presets.
+    #[must_use]
+    pub fn entries(&self) -> Vec<(String, String)> {
+        SETTING_KEYS
+            .iter()
+            .filter_map(|&key| self.get(key).map(|value| (key.to_string(), value)))
+            .chain(self.presets.iter().map(|(name, definition)| {
+                (format!("{PRESET_KEY_PREFIX}{name}"), definition.clone())
+            }))
+            .collect()
+    }
+
+    /// Returns the format presets parsed into specs, with the default
+    /// format.
+    ///
+    /// Definitions are checked when they are set, so none are skipped in
+    /// practice.
+    #[must_use]
+    pub fn format_presets(&self) -> FormatPresets {
+        let presets = self
+            .presets
+            .iter()
+            .filter_map(|(name, definition)| {
+                FormatSpec::parse(definition)
+                    .ok()
+                    .map(|spec| (name.clone(), spec))
+            })
+            .collect();
+        FormatPresets::new(presets, self.format.as_deref())
+    }
+
+    /// Returns the current value of a setting as text.
+    ///
+    /// Returns `None` if there is no setting called `key`.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<String> {
+        let value = match key {
+            "precision" => self
+                .precision
+                .map_or_else(|| "auto".to_string(), |places| places.to_string()),
+            "angle" => match self.angle_mode {
+                AngleMode::Radians => "rad",
+                AngleMode::Degrees => "deg",
+            }
+            .to_string(),
+            "division" => match self.division {
+                DivisionMode::True => "true",
+                DivisionMode::Floor => "floor",
+            }
+            .to_string(),
+            "theme" => match self.theme {
+                None => "auto",
+                Some(AppTheme::Light) => "light",
+                Some(AppTheme::Dark) => "dark",
+            }
+            .to_string(),
+            "pane" => if self.pane_left { "left" } else { "right" }.to_string(),
+            "ratio" => self.ratio.to_string(),
+            "brackets" => on_off(self.brackets),
+            "align" => on_off(self.align),
+            "wrap" => on_off(self.wrap),
+            "autosave" => on_off(self.autosave),
+            "results" => on_off(self.results),
+            "separators" => on_off(self.separators),
+            "prefixes" => on_off(self.prefixes),
+            "sigfigs" => on_off(self.sigfigs),
+            "rounding" => self.rounding.name().to_string(),
+            "shell" => on_off(self.shell),
+            "mouse" => on_off(self.mouse),
+            "clickcopy" => on_off(self.clickcopy),
+            "printexit" => on_off(self.printexit),
+            "overview" => on_off(self.overview),
+            "number" => on_off(self.number),
+            "relativenumber" => on_off(self.relativenumber),
+            "markers" => on_off(self.markers),
+            "errors" => match self.errors {
+                ErrorPlacement::Inline => "inline",
+                ErrorPlacement::Bar => "bar",
+            }
+            .to_string(),
+            "accessible" => on_off(self.accessible),
+            "ascii" => on_off(self.ascii),
+            "motion" => on_off(self.motion),
+            "maxlength" => self.limits.line_length.to_string(),
+            "maxlines" => self.limits.lines.to_string(),
+            "maxdepth" => self.limits.depth.to_string(),
+            "maxlist" => self.limits.list.to_string(),
+            "format" => self.format.clone().unwrap_or_else(|| "auto".to_string()),
+            _ => {
+                let name = key.strip_prefix(PRESET_KEY_PREFIX)?;
+                return self
+                    .presets
+                    .iter()
+                    .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+                    .map(|(_, definition)| definition.clone());
+            }
+        };
+        Some(value)
+    }
+
+    /// Changes a setting from its text value.
+    ///
+    /// # Errors
+    /// Returns a message for the user if the key is unknown or the value is
+    /// not valid for it.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("Invalid value '{value}' for {key}");
+        match key {
+            "precision" => {
+                self.precision = match value {
+                    "auto" => None,
+                    _ => match value.parse() {
+                        Ok(places) if places <= MAX_PRECISION => Some(places),
+                        _ => {
+                            return Err(format!("precision must be auto or 0 to {MAX_PRECISION}"));
+                        }
+                    },
+                };
+            }
+            "angle" => {
+                self.angle_mode = match value {
+                    "rad" | "radians" => AngleMode::Radians,
+                    "deg" | "degrees" => AngleMode::Degrees,
+                    _ => return Err(invalid()),
+                };
+            }
+            "division" => {
+                self.division = match value {
+                    "true" => DivisionMode::True,
+                    "floor" => DivisionMode::Floor,
+                    _ => return Err(invalid()),
+                };
+            }
+            "theme" => {
+                self.theme = match value {
+                    "auto" => None,
+                    "light" => Some(AppTheme::Light),
+                    "dark" => Some(AppTheme::Dark),
+                    _ => return Err(invalid()),
+                };
+            }
+            "pane" => {
+                self.pane_left = match value {
+                    "left" => true,
+                    "right" => false,
+                    _ => return Err(invalid()),
+                };
+            }
+            "ratio" => {
+                self.ratio = value
+                    .parse()
+                    .ok()
+                    .filter(|ratio| MEMORY_PERCENT_RANGE.contains(ratio))
+                    .ok_or_else(|| {
+                        format!(
+                            "ratio must be {} to {}",
+                            MEMORY_PERCENT_RANGE.start(),
+                            MEMORY_PERCENT_RANGE.end()
+                        )
+                    })?;
+            }
+            "brackets" => self.brackets = parse_on_off(value).ok_or_else(invalid)?,
+            "align" => self.align = parse_on_off(value).ok_or_else(invalid)?,
+            "wrap" => self.wrap = parse_on_off(value).ok_or_else(invalid)?,
+            "autosave" => self.autosave = parse_on_off(value).ok_or_else(invalid)?,
+            "results" => self.results = parse_on_off(value).ok_or_else(invalid)?,
+            "separators" => self.separators = parse_on_off(value).ok_or_else(invalid)?,
+            "prefixes" => self.prefixes = parse_on_off(value).ok_or_else(invalid)?,
+            "sigfigs" => self.sigfigs = parse_on_off(value).ok_or_else(invalid)?,
+            "rounding" => self.rounding = Rounding::parse(value).ok_or_else(invalid)?,
+            "shell" => self.shell = parse_on_off(value).ok_or_else(invalid)?,
+            "mouse" => self.mouse = parse_on_off(value).ok_or_else(invalid)?,
+            "clickcopy" => self.clickcopy = parse_on_off(value).ok_or_else(invalid)?,
+            "printexit" => self.printexit = parse_on_off(value).ok_or_else(invalid)?,
+            "overview" => self.overview = parse_on_off(value).ok_or_else(invalid)?,
+            "number" => self.number = parse_on_off(value).ok_or_else(invalid)?,
+            "relativenumber" => self.relativenumber = parse_on_off(value).ok_or_else(invalid)?,
+            "markers" => self.markers = parse_on_off(value).ok_or_else(invalid)?,
+            "errors" => {
+                self.errors = match value {
+                    "inline" => ErrorPlacement::Inline,
+                    "bar" => ErrorPlacement::Bar,
+                    _ => return Err(invalid()),
+                };
+            }
+            "accessible" => self.accessible = parse_on_off(value).ok_or_else(invalid)?,
+            "ascii" => self.ascii = parse_on_off(value).ok_or_else(invalid)?,
+            "motion" => self.motion = parse_on_off(value).ok_or_else(invalid)?,
+            "maxlength" => self.limits.line_length = parse_limit(value).ok_or_else(invalid)?,
+            "maxlines" => self.limits.lines = parse_limit(value).ok_or_else(invalid)?,
+            "maxdepth" => self.limits.depth = parse_limit(value).ok_or_else(invalid)?,
+            "maxlist" => self.limits.list = parse_limit(value).ok_or_else(invalid)?,
+            "format" => self.set_default_format(value)?,
+            _ => match key.strip_prefix(PRESET_KEY_PREFIX) {
+                Some(name) => self.set_preset(name, value)?,
+                None => return Err(format!("Unknown setting '{key}'")),
+            },
+        }
+        Ok(())
+    }
+
+    /// Chooses the default result format: `auto`, a preset or a built-in
+    /// format name.
+    fn set_default_format(&mut self, value: &str) -> Result<(), String> {
+        let name = value.to_ascii_lowercase();
+        let known = FormatSpec::builtin(&name).is_some()
+            || self.presets.iter().any(|(preset, _)| *preset == name);
+        self.format = match name.as_str() {
+            "auto" | "off" => None,
+            _ if known => Some(name),
+            _ => return Err(format!("Unknown format '{value}'")),
+        };
+        Ok(())
+    }
+
+    /// Defines or replaces the format preset `name`.
+    fn set_preset(&mut self, name: &str, definition: &str) -> Result<(), String> {
+        let valid_name = name.chars().next().is_some_and(char::is_alphabetic)
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(format!("Invalid format name '{name}'"));
+        }
+        FormatSpec::parse(definition).map_err(|e| format!("Invalid format: {e}"))?;
+        let name = name.to_ascii_lowercase();
+        let definition = definition.trim().to_string();
+        match self.presets.iter_mut().find(|(preset, _)| *preset == name) {
+            Some((_, existing)) => *existing = definition,
+            None => self.presets.push((name, definition)),
+        }
+        Ok(())
+    }
+
+    /// Runs a `:set key value` command, or `:set key` to show a value.
+    ///
+    /// The leading colon is optional.
+    ///
+    /// # Returns
+    /// A confirmation such as `precision = 4`.
+    ///
+    /// # Errors
+    /// Returns a message for the user if the command is malformed or the
+    /// setting cannot be changed.
+    pub fn run_command(&mut self, command: &str) -> Result<String, String> {
+        let command = command.trim();
+        let command = command.strip_prefix(':').unwrap_or(command);
+        let Some(rest) = command
+            .strip_prefix("set")
+            .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+        else {
+            return Err("Usage: :set key value".to_string());
+        };
+        let rest = rest.trim();
+        if rest.is_empty() {
+            return Err(format!("Settings: {}", SETTING_KEYS.join(", ")));
+        }
+        let (key, value) = rest
+            .split_once(char::is_whitespace)
+            .map_or((rest, ""), |(key, value)| (key, value.trim()));
+        if !value.is_empty() {
+            self.set(key, value)?;
+        }
+        let current = self
+            .get(key)
+            .ok_or_else(|| format!("Unknown setting '{key}'"))?;
+        Ok(format!("{key} = {current}"))
+    }
+
+    /// Returns how results are displayed under these settings.
+    #[must_use]
+    pub const fn number_format(&self) -> NumberFormat {
+        NumberFormat {
+            precision: self.precision,
+            separators: self.separators,
+        }
+    }
+}
+
+/// Formats a boolean setting.
+fn on_off(value: bool) -> String {
+    if value { "on" } else { "off" }.to_string()
+}
+
+/// Parses a boolean setting.
+fn parse_on_off(value: &str) -> Option<bool> {
+    match value {
+        "on" | "true" | "yes" => Some(true),
+        "off" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a limit, a whole number of at least 1.
+fn parse_limit(value: &str) -> Option<usize> {
+    value.parse().ok().filter(|&limit| limit > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::directive::FormatDirective;
+
+    #[test]
+    fn test_entries_round_trip() {
+        let mut settings = Settings::default();
+        settings.set("precision", "4").unwrap();
+        settings.set("angle", "deg").unwrap();
+        settings.set("division", "floor").unwrap();
+        settings.set("theme", "light").unwrap();
+        settings.set("pane", "right").unwrap();
+        settings.set("ratio", "35").unwrap();
+        settings.set("brackets", "off").unwrap();
+        settings.set("autosave", "off").unwrap();
+        settings.set("prefixes", "on").unwrap();
+        settings.set("rounding", "bankers").unwrap();
+        settings.set("sigfigs", "on").unwrap();
+        settings.set("shell", "on").unwrap();
+        settings.set("mouse", "on").unwrap();
+        settings.set("errors", "bar").unwrap();
+        settings.set("accessible", "on").unwrap();
+        settings.set("ascii", "on").unwrap();
+        settings.set("motion", "off").unwrap();
+        settings.set("maxdepth", "50").unwrap();
+        settings.set("format.money", "2dp, prefix \"€\"").unwrap();
+        settings.set("format", "money").unwrap();
+
+        let entries = settings.entries();
+        let restored = Settings::from_entries(
+            entries
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str())),
+        );
+
+        assert_eq!(restored, settings);
+        assert_eq!(entries.len(), SETTING_KEYS.len() + 1);
+    }
+
+    #[test]
+    fn test_format_presets() {
+        let mut settings = Settings::default();
+
+        assert_eq!(
+            settings.run_command(":set format.Money 2dp, thousands, prefix \"€ \""),
+            Ok("format.Money = 2dp, thousands, prefix \"€ \"".to_string())
+        );
+        assert_eq!(settings.presets[0].0, "money");
+        let money = FormatDirective::parse("money", &settings.format_presets()).unwrap();
+        assert_eq!(money.format(1234.5).as_deref(), Ok("€ 1,234.50"));
+        assert!(settings.set("format.bad", "2dp, bold").is_err());
+        assert!(settings.set("format.2x", "2dp").is_err());
+
+        settings.set("format", "money").unwrap();
+        assert_eq!(settings.format.as_deref(), Some("money"));
+        settings.set("format", "hex").unwrap();
+        assert_eq!(settings.format.as_deref(), Some("hex"));
+        assert!(settings.set("format", "bold").is_err());
+        settings.set("format", "auto").unwrap();
+        assert_eq!(settings.format, None);
+    }
+
+    #[test]
+    fn test_from_entries_defines_presets_first() {
+        let settings = Settings::from_entries([("format", "money"), ("format.money", "2dp")]);
+
+        assert_eq!(settings.format.as_deref(), Some("money"));
+    }
+
+    #[test]
+    fn test_from_entries_ignores_invalid_values() {
+        let settings = Settings::from_entries([
+            ("precision", "lots"),
+            ("colour", "red"),
+            ("separators", "on"),
+        ]);
+
+        assert_eq!(settings.precision, None);
+        assert!(settings.separators);
+    }
+
+    #[test]
+    fn test_run_command_sets_and_shows_values() {
+        let mut settings = Settings::default();
+
+        assert_eq!(
+            settings.run_command(":set precision 2"),
+            Ok("precision = 2".to_string())
+        );
+        assert_eq!(settings.precision, Some(2));
+        assert_eq!(
+            settings.run_command("set angle"),
+            Ok("angle = rad".to_string())
+        );
+    }
+
+    #[test]
+    fn test_run_command_rejects_bad_input() {
+        let mut settings = Settings::default();
+
+        assert!(settings.run_command(":precision 2").is_err());
+        assert!(settings.run_command(":set precision 99").is_err());
+        assert!(settings.run_command(":set wrap maybe").is_err());
+        assert!(settings.run_command(":set errors popup").is_err());
+        assert!(settings.run_command(":set pane top").is_err());
+        assert_eq!(
+            settings.run_command(":set ratio 90"),
+            Err("ratio must be 10 to 60".to_string())
+        );
+        assert_eq!(
+            settings.run_command(":set colour red"),
+            Err("Unknown setting 'colour'".to_string())
+        );
+        assert_eq!(settings, Settings::default());
+    }
+}
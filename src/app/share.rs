@@ -0,0 +1,143 @@
+//! Share files written by `:share` and read by `:import`.
+//!
+//! A share file describes itself in a few comment lines: a header, the
+//! version that wrote it, the angle unit the results depend on and the
+//! number of worksheet lines. The worksheet follows as it is, then its
+//! results as comments, so the whole file is itself a valid worksheet:
+//!
+//! ```text
+//! # Crabculator worksheet, shared with :share
+//! # version: 0.1.0
+//! # angle: rad
+//! # lines: 2
+//! #
+//! price = 40
+//! price * 1.2
+//! #
+//! # Results:
+//! # price = 40   =>  price = 40
+//! # price * 1.2  =>  48
+//! ```
+
+use crate::eval::LineResult;
+use crate::ui::share_text;
+
+/// First line of every share file.
+const SHARE_HEADER: &str = "# Crabculator worksheet, shared with :share";
+
+/// A worksheet read from a share file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedWorksheet {
+    /// The lines of the worksheet.
+    pub lines: Vec<String>,
+    /// The angle unit the worksheet was shared with, if named.
+    pub angle: Option<String>,
+}
+
+/// Formats the worksheet `lines` with their `results`, evaluated with the
+/// angle unit `angle`, as the contents of a share file.
+#[must_use]
+pub fn share_file(lines: &[String], results: &[LineResult], angle: &str) -> String {
+    let mut output = vec![
+        SHARE_HEADER.to_string(),
+        format!("# version: {}", env!("CARGO_PKG_VERSION")),
+        format!("# angle: {angle}"),
+        format!("# lines: {}", lines.len()),
+        "#".to_string(),
+    ];
+    output.extend(lines.iter().cloned());
+    output.push("#".to_string());
+    output.push("# Results:".to_string());
+    output.extend(
+        share_text(lines, results)
+            .lines()
+            .map(|line| format!("# {line}").trim_end().to_string()),
+    );
+    output.push(String::new());
+    output.join("\n")
+}
+
+/// Reads the worksheet from the contents of a share file.
+///
+/// Unknown metadata is skipped, so files from later versions can still be
+/// read.
+///
+/// # Errors
+/// Returns a message if `text` is not a share file or is cut short.
+pub fn parse_share_file(text: &str) -> Result<SharedWorksheet, String> {
+    let mut lines = text.lines();
+    if lines.next().map(str::trim_end) != Some(SHARE_HEADER) {
+        return Err("not a Crabculator share file".to_string());
+    }
+    let mut angle = None;
+    let mut count = None;
+    for line in lines.by_ref() {
+        let Some((key, value)) = line
+            .strip_prefix("# ")
+            .and_then(|rest| rest.split_once(':'))
+        else {
+            break;
+        };
+        match key {
+            "angle" => angle = Some(value.trim().to_string()),
+            "lines" => count = value.trim().parse::<usize>().ok(),
+            _ => {}
+        }
+    }
+    let count = count.ok_or("missing line count")?;
+    let worksheet: Vec<String> = lines.take(count).map(ToString::to_string).collect();
+    if worksheet.len() < count {
+        return Err(format!(
+            "expected {count} line(s), found {}",
+            worksheet.len()
+        ));
+    }
+    Ok(SharedWorksheet {
+        lines: worksheet,
+        angle,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::evaluate_all_lines;
+
+    fn to_lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_share_file_round_trips() {
+        let lines = to_lines(&["price = 40", "# note", "", "price * 1.2"]);
+        let results = evaluate_all_lines(lines.iter().map(String::as_str));
+        let text = share_file(&lines, &results, "deg");
+
+        assert!(text.starts_with(SHARE_HEADER));
+        assert!(text.contains("# angle: deg\n# lines: 4\n#\nprice = 40\n"));
+        assert!(text.ends_with("# price * 1.2  =>  48\n"));
+        assert_eq!(
+            parse_share_file(&text),
+            Ok(SharedWorksheet {
+                lines,
+                angle: Some("deg".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_share_file_rejects_other_files() {
+        assert_eq!(
+            parse_share_file("a = 1\nb = 2"),
+            Err("not a Crabculator share file".to_string())
+        );
+        assert_eq!(
+            parse_share_file(&format!("{SHARE_HEADER}\n# angle: rad\n#\na = 1")),
+            Err("missing line count".to_string())
+        );
+        assert_eq!(
+            parse_share_file(&format!("{SHARE_HEADER}\n# lines: 3\n#\na = 1")),
+            Err("expected 3 line(s), found 1".to_string())
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! The table popup, showing the rows of a `table` result a page at a time.
+
+use crate::eval::calculus::Table;
+
+/// Rows shown on one page of the table popup.
+pub const TABLE_PAGE_SIZE: usize = 10;
+
+/// A `table` result shown in the table popup, and the page in view.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableView {
+    /// The table being shown.
+    pub table: Table,
+    /// The page in view, from 0.
+    pub page: usize,
+}
+
+impl TableView {
+    /// Shows `table` from its first page.
+    #[must_use]
+    pub const fn new(table: Table) -> Self {
+        Self { table, page: 0 }
+    }
+
+    /// Returns the number of pages, at least one.
+    #[must_use]
+    pub const fn page_count(&self) -> usize {
+        if self.table.rows.is_empty() {
+            1
+        } else {
+            self.table.rows.len().div_ceil(TABLE_PAGE_SIZE)
+        }
+    }
+
+    /// Returns the rows of the page in view.
+    #[must_use]
+    pub fn page_rows(&self) -> &[(f64, f64)] {
+        let start = (self.page * TABLE_PAGE_SIZE).min(self.table.rows.len());
+        let end = (start + TABLE_PAGE_SIZE).min(self.table.rows.len());
+        &self.table.rows[start..end]
+    }
+
+    /// Turns to the next page, if there is one.
+    pub const fn next_page(&mut self) {
+        if self.page + 1 < self.page_count() {
+            self.page += 1;
+        }
+    }
+
+    /// Turns to the previous page, if there is one.
+    pub const fn previous_page(&mut self) {
+        self.page = self.page.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages_hold_page_size_rows() {
+        #[allow(clippy::cast_precision_loss)]
+        let rows = (0..25).map(|i| (f64::from(i), f64::from(i * i))).collect();
+        let mut view = TableView::new(Table {
+            variable: "x".to_string(),
+            function: "x ^ 2".to_string(),
+            rows,
+        });
+
+        assert_eq!(view.page_count(), 3);
+        assert_eq!(view.page_rows().len(), TABLE_PAGE_SIZE);
+        view.next_page();
+        view.next_page();
+        view.next_page();
+        assert_eq!(view.page, 2);
+        assert_eq!(
+            view.page_rows(),
+            [
+                (20.0, 400.0),
+                (21.0, 441.0),
+                (22.0, 484.0),
+                (23.0, 529.0),
+                (24.0, 576.0)
+            ]
+        );
+        view.previous_page();
+        assert_eq!(view.page_rows()[0], (10.0, 100.0));
+    }
+}
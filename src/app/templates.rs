@@ -0,0 +1,123 @@
+//! Worksheet templates, started with `:new name`.
+//!
+//! A template is a worksheet to start from, with its inputs at the top and
+//! the calculations below. Files in `~/.crabculator/templates/` define new
+//! templates or replace the built-in ones of the same name.
+
+/// Templates available without a template file, by name.
+pub const BUILTIN_TEMPLATES: &[(&str, &[&str])] = &[
+    (
+        "loan",
+        &[
+            "# Loan amortization: change the inputs, the rest follows",
+            "principal = 250000",
+            "rate = 4.5 / 100",
+            "years = 30",
+            "",
+            "monthly_rate = rate / 12",
+            "months = years * 12",
+            "payment = principal * monthly_rate / (1 - (1 + monthly_rate) ^ -months)",
+            "paid = payment * months",
+            "interest = paid - principal",
+        ],
+    ),
+    (
+        "tip",
+        &[
+            "# Tip splitter: change the bill, tip and number of people",
+            "bill = 84.50",
+            "tip_percent = 18",
+            "people = 3",
+            "",
+            "tip = bill * tip_percent / 100",
+            "with_tip = bill + tip",
+            "per_person = with_tip / people",
+        ],
+    ),
+    (
+        "units",
+        &[
+            "# Unit converter: change a value or a unit",
+            "convert(26.2, \"mi\", \"km\")",
+            "convert(70, \"kg\", \"lb\")",
+            "convert(350, \"F\", \"C\")",
+            "convert(5, \"gal\", \"l\")",
+        ],
+    ),
+];
+
+/// Returns the lines of the built-in template `name`.
+#[must_use]
+pub fn builtin_template(name: &str) -> Option<Vec<String>> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .find(|(template, _)| *template == name)
+        .map(|(_, lines)| lines.iter().map(ToString::to_string).collect())
+}
+
+/// The `:new` picker: the names of the templates and the one chosen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplatePicker {
+    /// Names of the available templates, sorted.
+    pub names: Vec<String>,
+    /// Index of the chosen name.
+    pub selected: usize,
+}
+
+impl TemplatePicker {
+    /// Moves the choice up by one, wrapping to the last name.
+    pub const fn up(&mut self) {
+        self.selected = match self.selected {
+            0 => self.names.len().saturating_sub(1),
+            selected => selected - 1,
+        };
+    }
+
+    /// Moves the choice down by one, wrapping to the first name.
+    pub const fn down(&mut self) {
+        self.selected = if self.selected + 1 < self.names.len() {
+            self.selected + 1
+        } else {
+            0
+        };
+    }
+
+    /// Returns the chosen name.
+    #[must_use]
+    pub fn chosen(&self) -> Option<&str> {
+        self.names.get(self.selected).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{LineResult, evaluate_all_lines};
+
+    #[test]
+    fn test_builtin_templates_evaluate_without_errors() {
+        for (name, _) in BUILTIN_TEMPLATES {
+            let lines = builtin_template(name).expect("template exists");
+            let results = evaluate_all_lines(lines.iter().map(String::as_str));
+            for (line, result) in lines.iter().zip(&results) {
+                assert!(
+                    !matches!(result, LineResult::Error(_) | LineResult::Warning { .. }),
+                    "{name}: {line}: {result:?}"
+                );
+            }
+        }
+        assert!(builtin_template("mortgage").is_none());
+    }
+
+    #[test]
+    fn test_picker_wraps_around() {
+        let mut picker = TemplatePicker {
+            names: vec!["loan".to_string(), "tip".to_string()],
+            selected: 0,
+        };
+        picker.up();
+        assert_eq!(picker.chosen(), Some("tip"));
+        picker.down();
+        assert_eq!(picker.chosen(), Some("loan"));
+    }
+}
@@ -0,0 +1,116 @@
+//! The `:version` popup: the version, how this binary was built and where
+//! its files are, for bug reports.
+
+use std::path::PathBuf;
+
+use crate::cli::Persistence;
+use crate::storage;
+
+/// Optional features and whether this binary was built with them.
+const FEATURES: &[(&str, bool)] = &[
+    ("scripting", cfg!(feature = "scripting")),
+    ("encryption", cfg!(feature = "encryption")),
+];
+
+/// Builds the lines of the `:version` popup for a buffer saved as
+/// `persistence`.
+#[must_use]
+pub fn version_lines(persistence: &Persistence) -> Vec<String> {
+    let buffer = match persistence {
+        Persistence::None => Err("not saved (--no-state)"),
+        _ => persistence.path().ok_or("unknown"),
+    };
+    let files = [
+        ("State dir", storage::state_dir().ok_or("unknown")),
+        ("Buffer", buffer),
+        ("Settings", storage::settings_file().ok_or("unknown")),
+        ("Bookmarks", storage::bookmarks_file().ok_or("unknown")),
+        ("Session", storage::session_file().ok_or("unknown")),
+        ("Profiles", storage::profiles_dir().ok_or("unknown")),
+        ("Templates", storage::templates_dir().ok_or("unknown")),
+        ("Prelude", storage::prelude_file().ok_or("unknown")),
+        ("Recovery", storage::recovery_file().ok_or("unknown")),
+        ("Config", storage::paths::config_file().ok_or("unknown")),
+        ("Trusted", storage::paths::trusted_file().ok_or("unknown")),
+        ("Debug log", storage::log_file().ok_or("unknown")),
+    ];
+    format_version_lines(&files)
+}
+
+/// Formats the popup lines for `files`, each a label with its path or the
+/// reason there is none. Paths that do not exist are marked as such.
+fn format_version_lines(files: &[(&str, Result<PathBuf, &str>)]) -> Vec<String> {
+    let features: Vec<&str> = FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+
+    let mut lines = vec![
+        format!("Crabculator {}", env!("CARGO_PKG_VERSION")),
+        format!(
+            "Build: {profile}, {} {}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        ),
+        format!(
+            "Features: {}",
+            if features.is_empty() {
+                "none".to_string()
+            } else {
+                features.join(", ")
+            }
+        ),
+        String::new(),
+        "Files:".to_string(),
+    ];
+    let width = files
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+    for (label, path) in files {
+        let location = match path {
+            Ok(path) if path.exists() => path.display().to_string(),
+            Ok(path) => format!("{} (not found)", path.display()),
+            Err(reason) => (*reason).to_string(),
+        };
+        lines.push(format!("  {label:<width$}  {location}"));
+    }
+    lines.push(String::new());
+    lines.push("Include these details in bug reports.".to_string());
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_version_lines_lists_build_and_files() {
+        let dir = std::env::temp_dir();
+        let missing = dir.join("crabculator-version-test-missing.txt");
+        let lines = format_version_lines(&[
+            ("State dir", Ok(dir.clone())),
+            ("Buffer", Err("not saved (--no-state)")),
+            ("Config", Ok(missing.clone())),
+        ]);
+
+        assert_eq!(
+            lines[0],
+            format!("Crabculator {}", env!("CARGO_PKG_VERSION"))
+        );
+        assert!(lines[1].contains(std::env::consts::OS));
+        assert_eq!(lines[5], format!("  State dir  {}", dir.display()));
+        assert_eq!(lines[6], "  Buffer     not saved (--no-state)");
+        assert_eq!(
+            lines[7],
+            format!("  Config     {} (not found)", missing.display())
+        );
+    }
+}
@@ -0,0 +1,55 @@
+//! First-run content: a sample worksheet and the welcome banner text.
+
+/// Buffer loaded when Crabculator is started for the first time.
+///
+/// Comment lines explain each part; the last expression is deliberately
+/// incomplete to show how errors are reported.
+pub const SAMPLE_WORKSHEET: &[&str] = &[
+    "# Welcome to Crabculator! Lines starting with # are comments.",
+    "# Every line is evaluated as you type; results appear alongside.",
+    "2 + 3 * 4",
+    "",
+    "# Assign variables and use them in later lines",
+    "price = 24.99",
+    "qty = 3",
+    "subtotal = price * qty",
+    "tax = subtotal * 0.08",
+    "subtotal + tax",
+    "",
+    "# Functions, constants, equations and units",
+    "sqrt(144) + sin(pi / 2)",
+    "round(tau * 100) / 100",
+    "solve(2*x + 3 = 11, x)",
+    "convert(26.2, \"mi\", \"km\")",
+    "2 GiB / 512 MiB",
+    "",
+    "# Mistakes are underlined and explained below the line",
+    "10 / (2 -",
+    "",
+    "# Ctrl+H opens the help, Ctrl+R clears this worksheet",
+];
+
+/// Text of the banner shown above the command bar on first launch.
+pub const WELCOME_MESSAGE: &str =
+    "Welcome to Crabculator! Edit the sample worksheet or press Ctrl+R to start fresh.";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::{EvalContext, LineResult, evaluate_all_lines_with_context};
+
+    #[test]
+    fn test_sample_worksheet_has_exactly_one_error() {
+        let mut context = EvalContext::new();
+        let results =
+            evaluate_all_lines_with_context(SAMPLE_WORKSHEET.iter().copied(), &mut context);
+
+        let errors: Vec<&str> = SAMPLE_WORKSHEET
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| matches!(result, LineResult::Error(_)))
+            .map(|(line, _)| *line)
+            .collect();
+        assert_eq!(errors, vec!["10 / (2 -"]);
+    }
+}
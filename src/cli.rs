@@ -0,0 +1,192 @@
+//! Command-line options for Crabculator.
+//!
+//! Options are parsed before the terminal is set up, so that invalid
+//! arguments can be reported on stderr.
+
+use std::path::PathBuf;
+
+use crate::storage;
+
+/// Usage shown for invalid command-line arguments.
+pub const USAGE: &str = "\
+Usage: crabculator [OPTIONS]
+
+Options:
+  --profile NAME   Apply the settings and variables of a profile
+  --expr EXPR      Evaluate EXPR before the worksheet (repeatable)
+  --file PATH      Open the worksheet PATH instead of the saved buffer
+  --no-state       Start empty and do not save the buffer
+  --encrypt        Encrypt the saved buffer with a new passphrase
+  --decrypt        Save an encrypted buffer as plain text again
+  --print          Print the worksheet with its results on quit
+  --debug          Log to debug.log in the state directory and show timings
+  -h, --help       Show this help";
+
+/// Where the buffer is loaded from and saved to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Persistence {
+    /// The state file in `~/.crabculator/`, together with the bookmarks
+    /// and the crash recovery file.
+    #[default]
+    State,
+    /// A worksheet file opened with `--file`.
+    File(PathBuf),
+    /// Nothing is loaded or saved, as with `--no-state`.
+    None,
+}
+
+impl Persistence {
+    /// Returns the path of the file the buffer is saved to, if any.
+    #[must_use]
+    pub fn path(&self) -> Option<PathBuf> {
+        match self {
+            Self::State => storage::state_file(),
+            Self::File(path) => Some(path.clone()),
+            Self::None => None,
+        }
+    }
+}
+
+/// The options given on the command line.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    /// The profile to apply at launch.
+    pub profile: Option<String>,
+    /// Expressions evaluated before the worksheet, in order.
+    pub expressions: Vec<String>,
+    /// Where the buffer is loaded from and saved to.
+    pub persistence: Persistence,
+    /// Whether the saved buffer should be encrypted with a new passphrase.
+    pub encrypt: bool,
+    /// Whether an encrypted buffer should be saved as plain text again.
+    pub decrypt: bool,
+    /// Whether the worksheet with its results is printed on quit.
+    pub print: bool,
+    /// Whether events are logged and the debug overlay is shown.
+    pub debug: bool,
+    /// Whether only the usage should be printed.
+    pub help: bool,
+}
+
+impl Options {
+    /// Parses the arguments following the program name.
+    ///
+    /// Options taking a value accept it as the next argument or after `=`,
+    /// as in `--profile finance` or `--profile=finance`.
+    ///
+    /// # Errors
+    /// Returns a message naming the invalid argument.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut file = None;
+        let mut no_state = false;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg, None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .filter(|value| !value.is_empty())
+                    .ok_or_else(|| format!("Missing value for {flag}"))
+            };
+            match flag.as_str() {
+                "--profile" => options.profile = Some(value()?),
+                "--expr" => options.expressions.push(value()?),
+                "--file" => file = Some(PathBuf::from(value()?)),
+                "--no-state" if inline.is_none() => no_state = true,
+                "--encrypt" if inline.is_none() => options.encrypt = true,
+                "--decrypt" if inline.is_none() => options.decrypt = true,
+                "--print" if inline.is_none() => options.print = true,
+                "--debug" if inline.is_none() => options.debug = true,
+                "-h" | "--help" if inline.is_none() => options.help = true,
+                _ => return Err(format!("Unknown argument '{flag}'")),
+            }
+        }
+        if options.encrypt && options.decrypt {
+            return Err("--encrypt and --decrypt cannot be combined".to_string());
+        }
+        options.persistence = match (file, no_state) {
+            (Some(_), true) => return Err("--file and --no-state cannot be combined".to_string()),
+            (Some(path), false) => Persistence::File(path),
+            (None, true) => Persistence::None,
+            (None, false) => Persistence::State,
+        };
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(ToString::to_string))
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        assert_eq!(parse(&[]), Ok(Options::default()));
+    }
+
+    #[test]
+    fn test_parse_all_options() {
+        let options = parse(&[
+            "--profile",
+            "finance",
+            "--expr",
+            "a = 2",
+            "--expr=b = a * 3",
+            "--file=sheet.calc",
+        ])
+        .unwrap();
+        assert_eq!(options.profile.as_deref(), Some("finance"));
+        assert_eq!(options.expressions, vec!["a = 2", "b = a * 3"]);
+        assert_eq!(
+            options.persistence,
+            Persistence::File(PathBuf::from("sheet.calc"))
+        );
+        assert_eq!(
+            parse(&["--no-state"]).unwrap().persistence,
+            Persistence::None
+        );
+        assert!(parse(&["-h"]).unwrap().help);
+        assert!(parse(&["--encrypt"]).unwrap().encrypt);
+        assert!(parse(&["--print"]).unwrap().print);
+        assert!(parse(&["--debug"]).unwrap().debug);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_arguments() {
+        assert_eq!(
+            parse(&["--profile"]),
+            Err("Missing value for --profile".to_string())
+        );
+        assert_eq!(
+            parse(&["--expr="]),
+            Err("Missing value for --expr".to_string())
+        );
+        assert_eq!(
+            parse(&["sheet.calc"]),
+            Err("Unknown argument 'sheet.calc'".to_string())
+        );
+        assert_eq!(
+            parse(&["--no-state=yes"]),
+            Err("Unknown argument '--no-state'".to_string())
+        );
+        assert_eq!(
+            parse(&["--encrypt", "--decrypt"]),
+            Err("--encrypt and --decrypt cannot be combined".to_string())
+        );
+        assert_eq!(
+            parse(&["--file", "a.calc", "--no-state"]),
+            Err("--file and --no-state cannot be combined".to_string())
+        );
+    }
+}
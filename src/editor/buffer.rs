@@ -1,8 +1,23 @@
 //! Text buffer management for the expression editor.
 //!
-//! Provides a multi-line text buffer with editing operations.
+//! Provides a multi-line text buffer with editing operations and an undo
+//! history.
 
-use super::Cursor;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ops::Range;
+
+use super::{Block, Cursor, Selection};
+
+/// Maximum number of earlier states kept for `undo`.
+const UNDO_LIMIT: usize = 100;
+
+/// An earlier state of the buffer, restored by `undo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Snapshot {
+    lines: Vec<String>,
+    cursor: Cursor,
+}
 
 /// A multi-line text buffer for editing expressions.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -11,6 +26,23 @@ pub struct Buffer {
     lines: Vec<String>,
     /// The cursor position within the buffer.
     cursor: Cursor,
+    /// The selected part of a line, if any.
+    selection: Option<Selection>,
+    /// Earlier states, most recent last.
+    history: Vec<Snapshot>,
+    /// The corner of the block opposite the cursor, while in block mode.
+    block_anchor: Option<Cursor>,
+    /// Whether block edits go to the end of each line.
+    block_to_end: bool,
+}
+
+/// Returns `col` clamped to `line` and moved back to a character boundary.
+pub fn floor_boundary(line: &str, col: usize) -> usize {
+    let mut col = col.min(line.len());
+    while !line.is_char_boundary(col) {
+        col -= 1;
+    }
+    col
 }
 
 impl Buffer {
@@ -20,6 +52,10 @@ impl Buffer {
         Self {
             lines: vec![String::new()],
             cursor: Cursor::default(),
+            selection: None,
+            history: Vec::new(),
+            block_anchor: None,
+            block_to_end: false,
         }
     }
 
@@ -37,6 +73,10 @@ impl Buffer {
         Self {
             lines,
             cursor: Cursor::default(),
+            selection: None,
+            history: Vec::new(),
+            block_anchor: None,
+            block_to_end: false,
         }
     }
 
@@ -76,7 +116,7 @@ impl Buffer {
         let row = self.cursor.row();
         let col = self.cursor.col();
         self.lines[row].insert(col, ch);
-        self.cursor.set_col(col + 1);
+        self.cursor.set_col(col + ch.len_utf8());
     }
 
     /// Creates a new line at the cursor position (Enter key behavior).
@@ -103,8 +143,9 @@ impl Buffer {
         let col = self.cursor.col();
 
         if col > 0 {
-            self.lines[row].remove(col - 1);
-            self.cursor.set_col(col - 1);
+            let start = floor_boundary(&self.lines[row], col - 1);
+            self.lines[row].remove(start);
+            self.cursor.set_col(start);
             true
         } else if row > 0 {
             let current_line = self.lines.remove(row);
@@ -143,7 +184,11 @@ impl Buffer {
     ///
     /// At line start, moves to end of previous line.
     pub fn move_cursor_left(&mut self) {
-        if !self.cursor.move_left() && self.cursor.row() > 0 {
+        let col = self.cursor.col();
+        if col > 0 {
+            self.cursor
+                .set_col(floor_boundary(self.current_line(), col - 1));
+        } else if self.cursor.row() > 0 {
             let prev_line_len = self.lines[self.cursor.row() - 1].len();
             self.cursor.move_to_prev_line_end(prev_line_len);
         }
@@ -153,8 +198,10 @@ impl Buffer {
     ///
     /// At line end, moves to start of next line.
     pub fn move_cursor_right(&mut self) {
-        let line_len = self.current_line_len();
-        if !self.cursor.move_right(line_len) && self.cursor.row() + 1 < self.lines.len() {
+        let col = self.cursor.col();
+        if let Some(c) = self.current_line()[col..].chars().next() {
+            self.cursor.set_col(col + c.len_utf8());
+        } else if self.cursor.row() + 1 < self.lines.len() {
             self.cursor.move_to_next_line_start(self.lines.len());
         }
     }
@@ -166,6 +213,7 @@ impl Buffer {
         if self.cursor.row() > 0 {
             let prev_line_len = self.lines[self.cursor.row() - 1].len();
             self.cursor.move_up(prev_line_len);
+            self.move_cursor_to_col(self.cursor.col());
         }
     }
 
@@ -176,6 +224,7 @@ impl Buffer {
         if self.cursor.row() + 1 < self.lines.len() {
             let next_line_len = self.lines[self.cursor.row() + 1].len();
             self.cursor.move_down(self.lines.len(), next_line_len);
+            self.move_cursor_to_col(self.cursor.col());
         }
     }
 
@@ -190,6 +239,357 @@ impl Buffer {
         self.cursor.move_to_line_end(line_len);
     }
 
+    /// Moves the cursor to `col` on the current line, clamped to its length.
+    ///
+    /// A column inside a multi-byte character moves back to its start.
+    pub fn move_cursor_to_col(&mut self, col: usize) {
+        self.cursor
+            .set_col(floor_boundary(self.current_line(), col));
+    }
+
+    /// Moves the cursor to `row` and `col`, both clamped to the buffer.
+    ///
+    /// A column inside a multi-byte character moves back to its start.
+    pub fn move_cursor_to(&mut self, row: usize, col: usize) {
+        let row = row.min(self.lines.len().saturating_sub(1));
+        self.cursor.set_row(row);
+        self.cursor.set_col(floor_boundary(&self.lines[row], col));
+    }
+
+    /// Selects `start..end` of the current line and moves the cursor to the
+    /// end of the selection.
+    ///
+    /// Both ends are clamped to the line and moved back to a character
+    /// boundary.
+    pub fn select(&mut self, start: usize, end: usize) {
+        let row = self.cursor.row();
+        let line = &self.lines[row];
+        let (start, end) = (floor_boundary(line, start), floor_boundary(line, end));
+        self.selection = Some(Selection::new(row, start.min(end), end));
+        self.cursor.set_col(end);
+    }
+
+    /// Returns the selection, if any.
+    #[must_use]
+    pub const fn selection(&self) -> Option<Selection> {
+        self.selection
+    }
+
+    /// Returns the selected text, if any.
+    #[must_use]
+    pub fn selected_text(&self) -> Option<&str> {
+        let selection = self.selection?;
+        self.lines
+            .get(selection.row)?
+            .get(selection.start..selection.end)
+    }
+
+    /// Clears the selection without changing the text.
+    pub const fn clear_selection(&mut self) {
+        self.selection = None;
+    }
+
+    /// Replaces the selected text with `text` and clears the selection.
+    ///
+    /// The cursor is placed after the inserted text. Returns `true` if there
+    /// was a selection. A selection the text has shrunk under since is
+    /// clamped to its line.
+    pub fn replace_selection(&mut self, text: &str) -> bool {
+        let Some(selection) = self.selection.take() else {
+            return false;
+        };
+        let row = selection.row.min(self.lines.len() - 1);
+        let line = &mut self.lines[row];
+        let start = floor_boundary(line, selection.start);
+        let end = floor_boundary(line, selection.end).max(start);
+        line.replace_range(start..end, text);
+        self.cursor.set_row(row);
+        self.cursor.set_col(start + text.len());
+        true
+    }
+
+    /// Inserts a new line with `text` before `row`, keeping the cursor on
+    /// its line.
+    ///
+    /// A `row` past the end appends the line.
+    pub fn insert_line(&mut self, row: usize, text: String) {
+        let row = row.min(self.lines.len());
+        self.lines.insert(row, text);
+        if self.cursor.row() >= row {
+            self.cursor.set_row(self.cursor.row() + 1);
+        }
+        if let Some(selection) = &mut self.selection
+            && selection.row >= row
+        {
+            selection.row += 1;
+        }
+    }
+
+    /// Starts block mode with one corner of the block at the cursor.
+    pub const fn start_block(&mut self) {
+        self.block_anchor = Some(self.cursor);
+        self.block_to_end = false;
+    }
+
+    /// Leaves block mode.
+    pub const fn end_block(&mut self) {
+        self.block_anchor = None;
+    }
+
+    /// Returns true while in block mode.
+    #[must_use]
+    pub const fn in_block_mode(&self) -> bool {
+        self.block_anchor.is_some()
+    }
+
+    /// Makes block edits go to the end of each line, or back to the
+    /// block's columns.
+    pub const fn set_block_to_end(&mut self, to_end: bool) {
+        self.block_to_end = to_end;
+    }
+
+    /// Returns the block between its anchor and the cursor, while in block
+    /// mode.
+    ///
+    /// An anchor on a line removed since is moved to the last line.
+    #[must_use]
+    pub fn block(&self) -> Option<Block> {
+        let anchor = self.block_anchor?;
+        let cursor = self.cursor;
+        let anchor_row = anchor.row().min(self.lines.len() - 1);
+        Some(Block {
+            top: anchor_row.min(cursor.row()),
+            bottom: anchor_row.max(cursor.row()),
+            left: anchor.col().min(cursor.col()),
+            right: anchor.col().max(cursor.col()),
+            to_end: self.block_to_end,
+        })
+    }
+
+    /// Replaces the block's columns on each of its lines with `text`, or
+    /// appends `text` to each line if the block goes to the line ends.
+    ///
+    /// Lines too short to reach the block are left alone. Afterwards the
+    /// block is an insertion column after the inserted text.
+    pub fn block_insert(&mut self, text: &str) {
+        let Some(block) = self.block() else {
+            return;
+        };
+        if !block.to_end {
+            self.delete_block_columns(block.left, block.right);
+        }
+        for row in block.top..=block.bottom {
+            let line = &mut self.lines[row];
+            if block.to_end {
+                line.push_str(text);
+            } else if line.len() >= block.left && line.is_char_boundary(block.left) {
+                line.insert_str(block.left, text);
+            }
+        }
+        self.set_block_column(block, block.left + text.len());
+    }
+
+    /// Deletes the block's columns on each of its lines. A block that is
+    /// only an insertion column deletes the character before it, or with
+    /// `forward` the character after it; at the line ends it deletes the
+    /// last character.
+    ///
+    /// Returns `true` if the text changed.
+    pub fn block_delete(&mut self, forward: bool) -> bool {
+        let Some(block) = self.block() else {
+            return false;
+        };
+        let before = self.lines[block.top..=block.bottom].to_vec();
+        let column = if block.to_end {
+            for row in block.top..=block.bottom {
+                self.lines[row].pop();
+            }
+            block.left
+        } else if block.left < block.right {
+            self.delete_block_columns(block.left, block.right);
+            block.left
+        } else if forward {
+            let end = block.left + 1;
+            self.delete_block_columns(block.left, end);
+            block.left
+        } else {
+            let start = block.left.saturating_sub(1);
+            self.delete_block_columns(start, block.left);
+            start
+        };
+        self.set_block_column(block, column);
+        self.lines[block.top..=block.bottom] != before[..]
+    }
+
+    /// Removes the bytes `start..end` from the lines of the block, as far as
+    /// each line reaches.
+    fn delete_block_columns(&mut self, start: usize, end: usize) {
+        let Some(block) = self.block() else {
+            return;
+        };
+        for line in &mut self.lines[block.top..=block.bottom] {
+            let (start, end) = (floor_boundary(line, start), floor_boundary(line, end));
+            if start < end {
+                line.replace_range(start..end, "");
+            }
+        }
+    }
+
+    /// Collapses `block` to an insertion column at `col`, keeping the cursor
+    /// on its line.
+    fn set_block_column(&mut self, block: Block, col: usize) {
+        let anchor_row = if self.cursor.row() == block.top {
+            block.bottom
+        } else {
+            block.top
+        };
+        self.block_anchor = Some(Cursor::new(anchor_row, col));
+        let row = self.cursor.row();
+        let col = if block.to_end {
+            self.lines[row].len()
+        } else {
+            col
+        };
+        self.cursor.set_col(floor_boundary(&self.lines[row], col));
+    }
+
+    /// Records the current state, so that `undo` can return to it.
+    ///
+    /// Only the last `UNDO_LIMIT` states are kept.
+    pub fn checkpoint(&mut self) {
+        if self.history.len() == UNDO_LIMIT {
+            self.history.remove(0);
+        }
+        self.history.push(Snapshot {
+            lines: self.lines.clone(),
+            cursor: self.cursor,
+        });
+    }
+
+    /// Drops the last recorded state if the text has not changed since.
+    pub fn discard_unchanged_checkpoint(&mut self) {
+        if self
+            .history
+            .last()
+            .is_some_and(|snapshot| snapshot.lines == self.lines)
+        {
+            self.history.pop();
+        }
+    }
+
+    /// Returns to the last recorded state and clears the selection.
+    ///
+    /// Returns `false` if there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self.history.pop() else {
+            return false;
+        };
+        self.lines = snapshot.lines;
+        self.cursor = snapshot.cursor;
+        self.selection = None;
+        self.block_anchor = None;
+        true
+    }
+
+    /// Clamps `rows` to the lines of the buffer.
+    fn clamp_rows(&self, rows: Range<usize>) -> Range<usize> {
+        let end = rows.end.min(self.lines.len());
+        rows.start.min(end)..end
+    }
+
+    /// Sorts the lines in `rows` alphabetically.
+    ///
+    /// Returns `true` if the order changed.
+    pub fn sort_lines(&mut self, rows: Range<usize>) -> bool {
+        let rows = self.clamp_rows(rows);
+        let mut sorted = self.lines[rows.clone()].to_vec();
+        sorted.sort();
+        self.replace_lines(rows, sorted)
+    }
+
+    /// Sorts the lines in `rows` by `keys`, one per line of the range.
+    ///
+    /// Lines without a key go last; lines with equal keys keep their order.
+    /// Returns `true` if the order changed.
+    pub fn sort_lines_by_key(&mut self, rows: Range<usize>, keys: &[Option<f64>]) -> bool {
+        let rows = self.clamp_rows(rows);
+        let mut keyed: Vec<_> = self.lines[rows.clone()]
+            .iter()
+            .zip(keys.iter().chain(std::iter::repeat(&None)))
+            .collect();
+        keyed.sort_by(|(_, a), (_, b)| match (a, b) {
+            (Some(a), Some(b)) => a.total_cmp(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        let sorted = keyed.into_iter().map(|(line, _)| line.clone()).collect();
+        self.replace_lines(rows, sorted)
+    }
+
+    /// Removes lines in `rows` that repeat an earlier line of the range,
+    /// keeping blank lines.
+    ///
+    /// Returns the number of lines removed.
+    pub fn dedupe_lines(&mut self, rows: Range<usize>) -> usize {
+        let rows = self.clamp_rows(rows);
+        let mut seen = HashSet::new();
+        let unique: Vec<String> = self.lines[rows.clone()]
+            .iter()
+            .filter(|line| line.trim().is_empty() || seen.insert(line.as_str()))
+            .cloned()
+            .collect();
+        let removed = rows.len() - unique.len();
+        self.replace_lines(rows, unique);
+        removed
+    }
+
+    /// Replaces the lines in `rows` with `lines`, keeping at least one line.
+    ///
+    /// The cursor stays on the same row, clamped to the buffer, and the
+    /// selection is cleared. Returns `true` if the text changed.
+    fn replace_lines(&mut self, rows: Range<usize>, lines: Vec<String>) -> bool {
+        if self.lines[rows.clone()] == lines[..] {
+            return false;
+        }
+        self.lines.splice(rows, lines);
+        if self.lines.is_empty() {
+            self.lines.push(String::new());
+        }
+        self.selection = None;
+        self.move_cursor_to(self.cursor.row(), self.cursor.col());
+        true
+    }
+
+    /// Replaces every line of the buffer with `lines`, keeping at least one
+    /// line, and moves the cursor to the start.
+    ///
+    /// Returns `true` if the text changed.
+    pub fn set_lines(&mut self, lines: Vec<String>) -> bool {
+        self.block_anchor = None;
+        let changed = self.replace_lines(0..self.lines.len(), lines);
+        self.move_cursor_to(0, 0);
+        changed
+    }
+
+    /// Replaces the text of the line at `row`.
+    ///
+    /// If the cursor is on that line, its column is clamped to the new length.
+    /// A selection on that line is cleared. Does nothing if `row` is out of
+    /// bounds.
+    pub fn set_line(&mut self, row: usize, text: String) {
+        let Some(line) = self.lines.get_mut(row) else {
+            return;
+        };
+        *line = text;
+        if self.selection.is_some_and(|selection| selection.row == row) {
+            self.selection = None;
+        }
+        if self.cursor.row() == row {
+            self.move_cursor_to_col(self.cursor.col());
+        }
+    }
+
     /// Returns the entire buffer content as a single string with newlines.
     #[must_use]
     pub fn content(&self) -> String {
@@ -201,6 +601,8 @@ impl Buffer {
     /// Resets the buffer to a single empty line and positions
     /// the cursor at the origin (row 0, column 0).
     pub fn clear(&mut self) {
+        self.selection = None;
+        self.block_anchor = None;
         self.lines.clear();
         self.lines.push(String::new());
         self.cursor.set_row(0);
@@ -218,6 +620,10 @@ impl Default for Buffer {
 mod tests {
     use super::*;
 
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(ToString::to_string).collect()
+    }
+
     #[test]
     fn test_buffer_new_has_one_empty_line() {
         let buffer = Buffer::new();
@@ -239,6 +645,48 @@ mod tests {
         assert_eq!(buffer1, buffer2);
     }
 
+    #[test]
+    fn test_edit_multibyte_characters() {
+        let mut buffer = Buffer::new();
+        for c in "5€×2".chars() {
+            buffer.insert_char(c);
+        }
+        assert_eq!(buffer.lines()[0], "5€×2");
+        assert_eq!(buffer.cursor().col(), 7);
+
+        buffer.move_cursor_left();
+        buffer.move_cursor_left();
+        assert_eq!(buffer.cursor().col(), 4);
+        assert!(buffer.delete_char_before());
+        assert_eq!(buffer.lines()[0], "5×2");
+        buffer.move_cursor_right();
+        assert_eq!(buffer.cursor().col(), 3);
+
+        buffer.insert_newline();
+        buffer.insert_char('é');
+        buffer.move_cursor_up();
+        assert_eq!(buffer.cursor().col(), 1);
+    }
+
+    #[test]
+    fn test_stale_selection_and_block_are_clamped() {
+        let mut buffer = Buffer::from_lines(vec!["abc".to_string(), "de".to_string()]);
+        buffer.select(0, 3);
+        buffer.set_line(1, String::new());
+        buffer.move_cursor_to(0, 3);
+        buffer.delete_char_before();
+        buffer.delete_char_before();
+        assert!(buffer.replace_selection("x"));
+        assert_eq!(buffer.lines()[0], "x");
+
+        buffer.move_cursor_to(1, 0);
+        buffer.start_block();
+        buffer.move_cursor_to(0, 0);
+        buffer.delete_char_at();
+        buffer.delete_char_at();
+        assert!(buffer.block().is_some_and(|block| block.bottom == 0));
+    }
+
     #[test]
     fn test_insert_char_at_start() {
         let mut buffer = Buffer::new();
@@ -581,6 +1029,137 @@ mod tests {
         assert_eq!(buffer.lines()[0], "single");
     }
 
+    #[test]
+    fn test_sort_lines_in_range() {
+        let mut buffer = Buffer::from_lines(lines(&["z", "c", "a", "b", "y"]));
+
+        assert!(buffer.sort_lines(1..4));
+        assert_eq!(buffer.lines(), lines(&["z", "a", "b", "c", "y"]));
+        assert!(!buffer.sort_lines(1..40));
+    }
+
+    #[test]
+    fn test_sort_lines_by_key_puts_lines_without_key_last() {
+        let mut buffer = Buffer::from_lines(lines(&["10", "oops", "2", "x = 5"]));
+
+        buffer.sort_lines_by_key(0..4, &[Some(10.0), None, Some(2.0), Some(5.0)]);
+
+        assert_eq!(buffer.lines(), lines(&["2", "x = 5", "10", "oops"]));
+    }
+
+    #[test]
+    fn test_dedupe_lines_keeps_first_and_blank_lines() {
+        let mut buffer = Buffer::from_lines(lines(&["a", "", "a", "b", "", "b"]));
+        buffer.move_cursor_to(5, 1);
+
+        assert_eq!(buffer.dedupe_lines(0..6), 2);
+        assert_eq!(buffer.lines(), lines(&["a", "", "b", ""]));
+        assert_eq!(buffer.cursor().row(), 3);
+    }
+
+    #[test]
+    fn test_set_lines_replaces_all_lines() {
+        let mut buffer = Buffer::from_lines(lines(&["a", "bb", "c"]));
+        buffer.move_cursor_to(2, 1);
+
+        assert!(buffer.set_lines(lines(&["x = 1"])));
+        assert_eq!(buffer.lines(), lines(&["x = 1"]));
+        assert_eq!((buffer.cursor().row(), buffer.cursor().col()), (0, 0));
+        assert!(buffer.set_lines(Vec::new()));
+        assert_eq!(buffer.lines(), lines(&[""]));
+    }
+
+    #[test]
+    fn test_block_insert_at_column_skips_short_lines() {
+        let mut buffer = Buffer::from_lines(lines(&["a = 12", "b", "c = 3", "d = 4"]));
+        buffer.move_cursor_to(0, 4);
+        buffer.start_block();
+        buffer.move_cursor_down();
+        buffer.move_cursor_down();
+        buffer.move_cursor_to_col(4);
+
+        buffer.block_insert("-");
+
+        assert_eq!(buffer.lines(), lines(&["a = -12", "b", "c = -3", "d = 4"]));
+        assert_eq!(buffer.cursor().col(), 5);
+        let block = buffer.block().unwrap();
+        assert_eq!(
+            (block.top, block.bottom, block.left, block.right),
+            (0, 2, 5, 5)
+        );
+    }
+
+    #[test]
+    fn test_block_insert_at_line_ends() {
+        let mut buffer = Buffer::from_lines(lines(&["12.50", "3", "100"]));
+        buffer.start_block();
+        buffer.move_cursor_down();
+        buffer.move_cursor_down();
+        buffer.set_block_to_end(true);
+
+        for c in " * 1.19".chars() {
+            buffer.block_insert(c.encode_utf8(&mut [0; 4]));
+        }
+
+        assert_eq!(
+            buffer.lines(),
+            lines(&["12.50 * 1.19", "3 * 1.19", "100 * 1.19"])
+        );
+        assert!(buffer.block_delete(false));
+        assert_eq!(
+            buffer.lines(),
+            lines(&["12.50 * 1.1", "3 * 1.1", "100 * 1.1"])
+        );
+    }
+
+    #[test]
+    fn test_block_delete_columns_and_characters() {
+        let mut buffer = Buffer::from_lines(lines(&["x = 10", "y = 200", "z"]));
+        buffer.move_cursor_to(0, 4);
+        buffer.start_block();
+        buffer.move_cursor_to(2, 0);
+        buffer.move_cursor_to(1, 5);
+
+        assert!(buffer.block_delete(false));
+        assert_eq!(buffer.lines(), lines(&["x = 0", "y = 00", "z"]));
+
+        assert!(buffer.block_delete(false));
+        assert_eq!(buffer.lines(), lines(&["x =0", "y =00", "z"]));
+        assert!(buffer.block_delete(true));
+        assert!(buffer.block_delete(true));
+        assert_eq!(buffer.lines(), lines(&["x =", "y =", "z"]));
+        assert!(!buffer.block_delete(true));
+    }
+
+    #[test]
+    fn test_undo_returns_to_checkpoints() {
+        let mut buffer = Buffer::from_lines(lines(&["b", "a"]));
+        buffer.checkpoint();
+        buffer.sort_lines(0..2);
+        buffer.checkpoint();
+        buffer.discard_unchanged_checkpoint();
+
+        assert!(buffer.undo());
+        assert_eq!(buffer.lines(), lines(&["b", "a"]));
+        assert!(!buffer.undo());
+    }
+
+    #[test]
+    fn test_undo_history_is_bounded() {
+        let mut buffer = Buffer::new();
+        for _ in 0..UNDO_LIMIT + 5 {
+            buffer.checkpoint();
+            buffer.insert_char('x');
+        }
+
+        let mut undone = 0;
+        while buffer.undo() {
+            undone += 1;
+        }
+        assert_eq!(undone, UNDO_LIMIT);
+        assert_eq!(buffer.current_line(), "xxxxx");
+    }
+
     #[test]
     fn test_clear_resets_to_single_empty_line() {
         let mut buffer = Buffer::new();
@@ -632,4 +1211,74 @@ mod tests {
         assert_eq!(buffer.lines()[0], "y");
         assert_eq!(buffer.cursor().col(), 1);
     }
+
+    #[test]
+    fn test_set_line_replaces_text() {
+        let mut buffer = Buffer::from_lines(vec!["a".to_string(), "b".to_string()]);
+
+        buffer.set_line(1, "c".to_string());
+
+        assert_eq!(buffer.lines(), ["a", "c"]);
+    }
+
+    #[test]
+    fn test_set_line_clamps_cursor_column() {
+        let mut buffer = Buffer::from_lines(vec!["12345".to_string()]);
+        buffer.move_cursor_to_line_end();
+
+        buffer.set_line(0, "12".to_string());
+
+        assert_eq!(buffer.cursor().col(), 2);
+    }
+
+    #[test]
+    fn test_select_and_replace_selection() {
+        let mut buffer = Buffer::from_lines(vec!["a".to_string(), "2 * (3 + 4)".to_string()]);
+        buffer.move_cursor_to(1, 0);
+        buffer.select(5, 10);
+
+        assert_eq!(buffer.selected_text(), Some("3 + 4"));
+        assert_eq!(buffer.cursor().col(), 10);
+        buffer.insert_line(0, "x = 1".to_string());
+        assert_eq!(buffer.selection(), Some(Selection::new(2, 5, 10)));
+        assert!(buffer.replace_selection("v1"));
+        assert_eq!(buffer.lines()[2], "2 * (v1)");
+        assert_eq!((buffer.cursor().row(), buffer.cursor().col()), (2, 7));
+        assert_eq!(buffer.selection(), None);
+        assert!(!buffer.replace_selection("v2"));
+    }
+
+    #[test]
+    fn test_move_cursor_to_col_clamps_to_line() {
+        let mut buffer = Buffer::from_lines(vec!["123".to_string()]);
+
+        buffer.move_cursor_to_col(2);
+        assert_eq!(buffer.cursor().col(), 2);
+
+        buffer.move_cursor_to_col(10);
+        assert_eq!(buffer.cursor().col(), 3);
+    }
+
+    #[test]
+    fn test_move_cursor_to_clamps_to_buffer() {
+        let mut buffer = Buffer::from_lines(vec!["123".to_string(), "€5".to_string()]);
+
+        buffer.move_cursor_to(0, 2);
+        assert_eq!((buffer.cursor().row(), buffer.cursor().col()), (0, 2));
+
+        buffer.move_cursor_to(5, 1);
+        assert_eq!((buffer.cursor().row(), buffer.cursor().col()), (1, 0));
+
+        buffer.move_cursor_to(1, 10);
+        assert_eq!(buffer.cursor().col(), 4);
+    }
+
+    #[test]
+    fn test_set_line_out_of_bounds_is_ignored() {
+        let mut buffer = Buffer::from_lines(vec!["a".to_string()]);
+
+        buffer.set_line(5, "b".to_string());
+
+        assert_eq!(buffer.lines(), ["a"]);
+    }
 }
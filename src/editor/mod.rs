@@ -4,6 +4,9 @@
 
 mod buffer;
 mod cursor;
+mod selection;
 
 pub use buffer::Buffer;
+pub(crate) use buffer::floor_boundary;
 pub use cursor::Cursor;
+pub use selection::{Block, Selection, enclosing_expression};
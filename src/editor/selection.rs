@@ -0,0 +1,147 @@
+//! Expression and block selection for the expression editor.
+//!
+//! A selection covers part of a single line. It is grown with the tokenizer:
+//! each step selects the next larger expression around it, from the inside
+//! of the innermost parentheses outward to the whole line. A block covers
+//! the same columns of several lines, which are edited at once.
+
+use crate::eval::token::{Spanned, Token, Tokenizer};
+
+/// A selected byte range of one buffer line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    /// The line the selection is on.
+    pub row: usize,
+    /// Start of the selection (byte offset, inclusive).
+    pub start: usize,
+    /// End of the selection (byte offset, exclusive).
+    pub end: usize,
+}
+
+impl Selection {
+    /// Creates a new selection.
+    #[must_use]
+    pub const fn new(row: usize, start: usize, end: usize) -> Self {
+        Self { row, start, end }
+    }
+}
+
+/// The same columns of consecutive lines, edited at once in block mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block {
+    /// The first line of the block.
+    pub top: usize,
+    /// The last line of the block (inclusive).
+    pub bottom: usize,
+    /// The first column of the block (byte offset, inclusive).
+    pub left: usize,
+    /// The end column of the block (byte offset, exclusive). Equal to `left`
+    /// for a block that is only an insertion column.
+    pub right: usize,
+    /// Whether edits go to the end of each line instead of a column.
+    pub to_end: bool,
+}
+
+impl Block {
+    /// Returns true if `row` is one of the block's lines.
+    #[must_use]
+    pub const fn contains_row(&self, row: usize) -> bool {
+        self.top <= row && row <= self.bottom
+    }
+}
+
+/// Returns the smallest expression of `line` that encloses the range
+/// `start..end` and is larger than it.
+///
+/// The candidates, from small to large, are for each pair of parentheses
+/// around the range its contents, the parentheses themselves and a function
+/// call they belong to; then the right-hand side of an assignment and
+/// finally the whole line. Parts of the line the tokenizer cannot read are
+/// left out.
+#[must_use]
+pub fn enclosing_expression(line: &str, start: usize, end: usize) -> Option<(usize, usize)> {
+    let (tokens, _) = Tokenizer::new(line).tokenize_partial();
+    expression_ranges(&tokens)
+        .into_iter()
+        .filter(|&(from, to)| from <= start && end <= to && to - from > end - start)
+        .min_by_key(|&(from, to)| to - from)
+}
+
+/// Collects the ranges of the expressions in `tokens` that a selection can
+/// grow to.
+fn expression_ranges(tokens: &[Spanned<Token>]) -> Vec<(usize, usize)> {
+    let (Some((_, first)), Some((_, last))) = (tokens.first(), tokens.last()) else {
+        return Vec::new();
+    };
+    let mut ranges = vec![(first.start, last.end)];
+    let mut open = Vec::new();
+    for (index, (token, span)) in tokens.iter().enumerate() {
+        match token {
+            Token::LParen => open.push(index),
+            Token::RParen => {
+                let Some(opening) = open.pop() else {
+                    continue;
+                };
+                if opening + 1 < index {
+                    ranges.push((tokens[opening + 1].1.start, tokens[index - 1].1.end));
+                }
+                ranges.push((tokens[opening].1.start, span.end));
+                if let Some((Token::Identifier(_), name)) =
+                    opening.checked_sub(1).map(|before| &tokens[before])
+                {
+                    ranges.push((name.start, span.end));
+                }
+            }
+            Token::Equals if open.is_empty() && index + 1 < tokens.len() => {
+                ranges.push((tokens[index + 1].1.start, last.end));
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grow(line: &str, col: usize, steps: usize) -> Option<&str> {
+        let (mut start, mut end) = (col, col);
+        for _ in 0..steps {
+            (start, end) = enclosing_expression(line, start, end)?;
+        }
+        Some(&line[start..end])
+    }
+
+    #[test]
+    fn test_grows_from_innermost_parentheses_outward() {
+        let line = "total = (price + ( tax * 2 )) / 3";
+        let col = line.find("tax").unwrap();
+
+        assert_eq!(grow(line, col, 1), Some("tax * 2"));
+        assert_eq!(grow(line, col, 2), Some("( tax * 2 )"));
+        assert_eq!(grow(line, col, 3), Some("price + ( tax * 2 )"));
+        assert_eq!(grow(line, col, 4), Some("(price + ( tax * 2 ))"));
+        assert_eq!(grow(line, col, 5), Some("(price + ( tax * 2 )) / 3"));
+        assert_eq!(grow(line, col, 6), Some(line));
+        assert_eq!(grow(line, col, 7), None);
+    }
+
+    #[test]
+    fn test_includes_function_name_of_call() {
+        let line = "sqrt(a + b) * 2";
+        let col = line.find('a').unwrap();
+
+        assert_eq!(grow(line, col, 1), Some("a + b"));
+        assert_eq!(grow(line, col, 2), Some("(a + b)"));
+        assert_eq!(grow(line, col, 3), Some("sqrt(a + b)"));
+        assert_eq!(grow(line, col, 4), Some(line));
+    }
+
+    #[test]
+    fn test_skips_empty_parentheses_and_untokenizable_lines() {
+        assert_eq!(grow("pi()", 3, 1), Some("()"));
+        assert_eq!(grow("# disabled", 3, 1), None);
+        assert_eq!(grow("", 0, 1), None);
+    }
+}
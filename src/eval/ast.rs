@@ -3,6 +3,8 @@
 //! This module provides the AST types and a recursive descent parser
 //! for parsing tokenized math expressions.
 
+use crate::eval::error::ErrorCode;
+use crate::eval::limits::Limits;
 use crate::eval::token::{Span, Spanned, Token};
 
 /// Binary operators for arithmetic expressions.
@@ -12,10 +14,27 @@ pub enum BinaryOp {
     Sub,
     Mul,
     Div,
+    FloorDiv,
     Mod,
     Pow,
 }
 
+impl BinaryOp {
+    /// Returns the source symbol for this operator.
+    #[must_use]
+    pub const fn symbol(&self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::FloorDiv => "//",
+            Self::Mod => "%",
+            Self::Pow => "^",
+        }
+    }
+}
+
 /// AST node representing a mathematical expression.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
@@ -35,6 +54,40 @@ pub enum Expr {
     Factorial(Box<Self>),
     /// A function call with arguments.
     FunctionCall { name: String, args: Vec<Self> },
+    /// A text literal, only meaningful as a function argument.
+    Text(String),
+    /// An equation `left = right`, only meaningful as a function argument.
+    Equation { left: Box<Self>, right: Box<Self> },
+}
+
+impl Expr {
+    /// Returns the number of levels of the tree, 1 for a single number or
+    /// variable.
+    ///
+    /// The tree is walked without recursion, so that long chains such as
+    /// `1 + 1 + ... + 1` can be measured safely.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        let mut deepest = 0;
+        let mut pending = vec![(self, 1)];
+        while let Some((expr, depth)) = pending.pop() {
+            deepest = deepest.max(depth);
+            match expr {
+                Self::BinaryOp { left, right, .. } | Self::Equation { left, right } => {
+                    pending.push((left, depth + 1));
+                    pending.push((right, depth + 1));
+                }
+                Self::UnaryMinus(inner) | Self::Factorial(inner) => {
+                    pending.push((inner, depth + 1));
+                }
+                Self::FunctionCall { args, .. } => {
+                    pending.extend(args.iter().map(|arg| (arg, depth + 1)));
+                }
+                Self::Number(_) | Self::Variable(_) | Self::Text(_) => {}
+            }
+        }
+        deepest
+    }
 }
 
 /// Error from parsing.
@@ -44,6 +97,8 @@ pub struct ParseError {
     pub message: String,
     /// Optional span indicating where the error occurred (start, end).
     pub span: Option<(usize, usize)>,
+    /// The kind of error, `ErrorCode::Syntax` unless a limit was exceeded.
+    pub code: ErrorCode,
 }
 
 impl ParseError {
@@ -52,6 +107,15 @@ impl ParseError {
         Self {
             message: message.into(),
             span: None,
+            code: ErrorCode::Syntax,
+        }
+    }
+
+    /// Creates a parse error for an expression beyond the parsing limits.
+    pub fn limit(message: impl Into<String>) -> Self {
+        Self {
+            code: ErrorCode::Limit,
+            ..Self::new(message)
         }
     }
 
@@ -60,6 +124,7 @@ impl ParseError {
         Self {
             message: message.into(),
             span: Some((span.start, span.end)),
+            code: ErrorCode::Syntax,
         }
     }
 }
@@ -77,26 +142,43 @@ impl std::error::Error for ParseError {}
 /// Grammar (precedence from low to high):
 /// ```text
 /// expr       -> term (('+' | '-') term)*
-/// term       -> power (('*' | '/' | '%' | implicit_mul) power)*
+/// term       -> power (('*' | '/' | '//' | '%' | implicit_mul) power)*
 /// power      -> unary ('^' power)?     // right-associative via recursion
 /// unary      -> '-' unary | postfix
 /// postfix    -> primary ('!')*
-/// primary    -> NUMBER | IDENTIFIER | IDENTIFIER '(' args ')' | '(' expr ')'
-/// args       -> expr (',' expr)* | empty
+/// primary    -> NUMBER | TEXT | IDENTIFIER | IDENTIFIER '(' args ')' | '(' expr ')'
+/// args       -> arg (',' arg)* | empty
+/// arg        -> expr ('=' expr)?       // an equation, e.g. for solve()
 ///
 /// implicit_mul: inserted between adjacent tokens when prev is
 ///   Number|RParen|Exclaim and next is Number|Identifier|LParen
 /// ```
+///
+/// Expressions nested deeper than the `maxdepth` limit and calls with more
+/// arguments than the `maxlist` limit are rejected.
 pub struct Parser {
     tokens: Vec<Spanned<Token>>,
     pos: usize,
+    nesting: usize,
+    limits: Limits,
 }
 
 impl Parser {
-    /// Creates a new parser with the given tokens.
+    /// Creates a new parser with the given tokens and the default limits.
     #[must_use]
-    pub const fn new(tokens: Vec<Spanned<Token>>) -> Self {
-        Self { tokens, pos: 0 }
+    pub fn new(tokens: Vec<Spanned<Token>>) -> Self {
+        Self::with_limits(tokens, Limits::default())
+    }
+
+    /// Creates a new parser with the given tokens and `limits`.
+    #[must_use]
+    pub const fn with_limits(tokens: Vec<Spanned<Token>>, limits: Limits) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            nesting: 0,
+            limits,
+        }
     }
 
     /// Parses the tokens into an expression AST.
@@ -105,9 +187,27 @@ impl Parser {
     ///
     /// Returns a `ParseError` if the token stream is invalid or cannot be parsed
     /// into a valid expression. This includes syntax errors, unexpected tokens,
-    /// and unclosed parentheses.
+    /// and unclosed parentheses. When a prefix of the tokens is valid and the
+    /// error has no position of its own, it spans the invalid tail. Input
+    /// past the limits is rejected without searching for such a prefix.
     pub fn parse(&mut self) -> Result<Expr, ParseError> {
+        self.parse_complete().map_err(|err| {
+            if err.span.is_some() || err.code == ErrorCode::Limit {
+                return err;
+            }
+            match self.parse_prefix() {
+                Some((_, tail)) => ParseError::with_span(err.message, tail),
+                None => err,
+            }
+        })
+    }
+
+    /// Parses the tokens into an expression AST without narrowing the error.
+    fn parse_complete(&mut self) -> Result<Expr, ParseError> {
         let expr = self.parse_expr()?;
+        if expr.depth() > self.limits.depth {
+            return Err(ParseError::limit(self.limits.depth_message()));
+        }
 
         // Ensure we consumed all tokens
         if !self.is_at_end() {
@@ -121,6 +221,22 @@ impl Parser {
         Ok(expr)
     }
 
+    /// Parses the longest proper prefix of the tokens that forms a complete
+    /// expression, for input whose tail is invalid, such as `5 + 3 +`.
+    ///
+    /// Returns the expression and the span of the remaining tokens, or `None`
+    /// if no prefix is a complete expression.
+    #[must_use]
+    pub fn parse_prefix(&self) -> Option<(Expr, Span)> {
+        let (_, last) = self.tokens.last()?;
+        (1..self.tokens.len()).rev().find_map(|end| {
+            let expr = Self::new(self.tokens[..end].to_vec())
+                .parse_complete()
+                .ok()?;
+            Some((expr, Span::new(self.tokens[end].1.start, last.end)))
+        })
+    }
+
     // Parse addition and subtraction (lowest precedence)
     fn parse_expr(&mut self) -> Result<Expr, ParseError> {
         let mut left = self.parse_term()?;
@@ -170,7 +286,7 @@ impl Parser {
 
         if self.match_token(&Token::Caret) {
             // Right-associative: recurse for the exponent
-            let exponent = self.parse_power()?;
+            let exponent = self.nested(Self::parse_power)?;
             Ok(Expr::BinaryOp {
                 left: Box::new(base),
                 op: BinaryOp::Pow,
@@ -184,7 +300,7 @@ impl Parser {
     // Parse unary minus
     fn parse_unary(&mut self) -> Result<Expr, ParseError> {
         if self.match_token(&Token::Minus) {
-            let operand = self.parse_unary()?;
+            let operand = self.nested(Self::parse_unary)?;
             Ok(Expr::UnaryMinus(Box::new(operand)))
         } else {
             self.parse_postfix()
@@ -209,17 +325,34 @@ impl Parser {
         let (token, span) = self.tokens[self.pos].clone();
 
         match &token {
-            Token::Number(n) => {
+            Token::Number(n)
+            | Token::Duration(n)
+            | Token::Size { bytes: n, .. }
+            | Token::Quantity { value: n, .. }
+            | Token::Money { amount: n, .. } => {
                 self.advance();
                 Ok(Expr::Number(*n))
             }
+            Token::Text(text) => {
+                let text = text.clone();
+                self.advance();
+                Ok(Expr::Text(text))
+            }
+            Token::Command(command) => {
+                let command = command.clone();
+                self.advance();
+                Ok(Expr::FunctionCall {
+                    name: "shell".to_string(),
+                    args: vec![Expr::Text(command)],
+                })
+            }
             Token::Identifier(name) => {
                 let name = name.clone();
                 self.advance();
 
                 // Check if it's a function call
                 if self.match_token(&Token::LParen) {
-                    let args = self.parse_args()?;
+                    let args = self.nested(Self::parse_args)?;
                     if !self.match_token(&Token::RParen) {
                         return Err(ParseError::new("Expected ')' after function arguments"));
                     }
@@ -230,7 +363,7 @@ impl Parser {
             }
             Token::LParen => {
                 self.advance();
-                let expr = self.parse_expr()?;
+                let expr = self.nested(Self::parse_expr)?;
                 if !self.match_token(&Token::RParen) {
                     return Err(ParseError::new("Expected ')' after expression"));
                 }
@@ -253,16 +386,47 @@ impl Parser {
         }
 
         // Parse first argument
-        args.push(self.parse_expr()?);
+        args.push(self.parse_arg()?);
 
         // Parse remaining arguments
         while self.match_token(&Token::Comma) {
-            args.push(self.parse_expr()?);
+            if args.len() == self.limits.list {
+                return Err(ParseError::limit(self.limits.list_message()));
+            }
+            args.push(self.parse_arg()?);
         }
 
         Ok(args)
     }
 
+    // Parse a function argument: an expression or an equation `expr = expr`
+    fn parse_arg(&mut self) -> Result<Expr, ParseError> {
+        let left = self.parse_expr()?;
+        if self.match_token(&Token::Equals) {
+            let right = self.parse_expr()?;
+            return Ok(Expr::Equation {
+                left: Box::new(left),
+                right: Box::new(right),
+            });
+        }
+        Ok(left)
+    }
+
+    // Parse one level deeper, failing beyond the depth limit so that deeply
+    // nested input cannot overflow the stack
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if self.nesting >= self.limits.depth {
+            return Err(ParseError::limit(self.limits.depth_message()));
+        }
+        self.nesting += 1;
+        let result = parse(self);
+        self.nesting -= 1;
+        result
+    }
+
     // Helper methods for token manipulation
 
     const fn is_at_end(&self) -> bool {
@@ -324,6 +488,10 @@ impl Parser {
                 self.advance();
                 Some(BinaryOp::Div)
             }
+            Token::DoubleSlash => {
+                self.advance();
+                Some(BinaryOp::FloorDiv)
+            }
             Token::Percent => {
                 self.advance();
                 Some(BinaryOp::Mod)
@@ -335,7 +503,7 @@ impl Parser {
     /// Returns true when the parser should insert an implicit multiplication.
     ///
     /// Implicit `*` is inserted between adjacent tokens only in these cases:
-    /// - `Number` followed by `Identifier` or `LParen`
+    /// - `Number`, `Duration` or `Size` followed by `Identifier` or `LParen`
     /// - `RParen` followed by `Identifier` or `LParen`
     /// - `Exclaim` (factorial) followed by `Identifier` or `LParen`
     fn should_implicit_multiply(&self) -> bool {
@@ -347,7 +515,13 @@ impl Parser {
 
         let left_ok = matches!(
             prev_token,
-            Token::Number(_) | Token::RParen | Token::Exclaim
+            Token::Number(_)
+                | Token::Duration(_)
+                | Token::Size { .. }
+                | Token::Quantity { .. }
+                | Token::Money { .. }
+                | Token::RParen
+                | Token::Exclaim
         );
         let right_ok = matches!(next_token, Token::Identifier(_) | Token::LParen);
 
@@ -882,6 +1056,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_equation_argument() {
+        // solve(x = 1, x)
+        let tokens = vec![
+            tok(Token::Identifier("solve".to_string())),
+            tok(Token::LParen),
+            tok(Token::Identifier("x".to_string())),
+            tok(Token::Equals),
+            tok(Token::Number(1.0)),
+            tok(Token::Comma),
+            tok(Token::Identifier("x".to_string())),
+            tok(Token::RParen),
+        ];
+        let mut parser = Parser::new(tokens);
+
+        assert_eq!(
+            parser.parse().unwrap(),
+            Expr::FunctionCall {
+                name: "solve".to_string(),
+                args: vec![
+                    Expr::Equation {
+                        left: Box::new(Expr::Variable("x".to_string())),
+                        right: Box::new(Expr::Number(1.0)),
+                    },
+                    Expr::Variable("x".to_string()),
+                ],
+            }
+        );
+    }
+
     #[test]
     fn test_parse_function_call_one_arg() {
         // sqrt(16)
@@ -1082,6 +1286,45 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_error_trailing_operator_spans_the_tail() {
+        // 5 + 3 +
+        let tokens = vec![
+            spanned(Token::Number(5.0), 0, 1),
+            spanned(Token::Plus, 2, 3),
+            spanned(Token::Number(3.0), 4, 5),
+            spanned(Token::Plus, 6, 7),
+        ];
+
+        let error = Parser::new(tokens.clone()).parse().unwrap_err();
+        let (prefix, tail) = Parser::new(tokens).parse_prefix().unwrap();
+
+        assert_eq!(error.span, Some((6, 7)));
+        assert_eq!(
+            prefix,
+            Expr::BinaryOp {
+                left: Box::new(Expr::Number(5.0)),
+                op: BinaryOp::Add,
+                right: Box::new(Expr::Number(3.0)),
+            }
+        );
+        assert_eq!(tail, Span::new(6, 7));
+    }
+
+    #[test]
+    fn test_parse_prefix_without_valid_prefix() {
+        // (5 + 3
+        let tokens = vec![
+            tok(Token::LParen),
+            tok(Token::Number(5.0)),
+            tok(Token::Plus),
+            tok(Token::Number(3.0)),
+        ];
+
+        assert!(Parser::new(tokens).parse_prefix().is_none());
+        assert!(Parser::new(vec![]).parse_prefix().is_none());
+    }
+
     #[test]
     fn test_parse_error_consecutive_operators() {
         // 5 + + 3 - this actually parses as 5 + (+3), but we don't have unary plus
@@ -1454,4 +1697,54 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_parse_rejects_expressions_past_the_limits() {
+        let parse = |tokens: Vec<Token>| Parser::new(tokens.into_iter().map(tok).collect()).parse();
+        let nested = |depth: usize| {
+            let mut tokens = vec![Token::LParen; depth];
+            tokens.push(Token::Number(1.0));
+            tokens.extend(vec![Token::RParen; depth]);
+            tokens
+        };
+        let chain = |terms: usize| {
+            let mut tokens = vec![Token::Number(1.0)];
+            for _ in 1..terms {
+                tokens.extend([Token::Plus, Token::Number(1.0)]);
+            }
+            tokens
+        };
+
+        let err = parse(nested(100_000)).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Limit);
+        assert_eq!(
+            err.message,
+            "expression is nested more than 200 levels deep (see maxdepth)"
+        );
+        assert!(parse(nested(200)).is_ok());
+        assert!(parse(chain(200)).is_ok());
+        assert_eq!(parse(chain(201)).unwrap_err().code, ErrorCode::Limit);
+
+        let limits = Limits {
+            list: 2,
+            ..Limits::default()
+        };
+        let call = |args: usize| {
+            let mut tokens = vec![Token::Identifier("max".to_string()), Token::LParen];
+            for i in 0..args {
+                if i > 0 {
+                    tokens.push(Token::Comma);
+                }
+                tokens.push(Token::Number(1.0));
+            }
+            tokens.push(Token::RParen);
+            tokens
+        };
+        let parse = |tokens: Vec<Token>| {
+            Parser::with_limits(tokens.into_iter().map(tok).collect(), limits).parse()
+        };
+        assert!(parse(call(2)).is_ok());
+        let err = parse(call(3)).unwrap_err();
+        assert_eq!(err.message, "more than 2 arguments (see maxlist)");
+    }
 }
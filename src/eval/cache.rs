@@ -0,0 +1,115 @@
+//! Per-line memoization for work repeated on every frame.
+//!
+//! The whole buffer is highlighted and evaluated each time the screen is
+//! drawn, although typically only the line being edited has changed. A
+//! `LineCache` remembers a value computed from a line's text, so unchanged
+//! lines are not tokenized or parsed again.
+
+use std::collections::HashMap;
+
+/// Number of distinct lines remembered before a cache starts over.
+pub const LINE_CACHE_CAPACITY: usize = 4096;
+
+/// Values computed from line contents and options `O`, keyed by the line
+/// text.
+///
+/// Once `capacity` entries are stored the cache is emptied, which bounds its
+/// memory while the user types through many intermediate versions of a line.
+/// It is also emptied when it is asked for a value with other options than
+/// before, such as when engineering prefixes are switched, the limits
+/// change or a variable starts or stops shadowing a unit, since they change
+/// how the same text is tokenized and parsed.
+#[derive(Debug)]
+pub struct LineCache<O, V> {
+    entries: HashMap<String, V>,
+    capacity: usize,
+    options: Option<O>,
+}
+
+impl<O: Clone + PartialEq, V: Clone> LineCache<O, V> {
+    /// Creates an empty cache holding at most `capacity` lines.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity,
+            options: None,
+        }
+    }
+
+    /// Returns the value for `line` with `options`, computing and storing it
+    /// on a miss.
+    pub fn get_or_insert_with(
+        &mut self,
+        line: &str,
+        options: &O,
+        compute: impl FnOnce(&str) -> V,
+    ) -> V {
+        if self.options.as_ref() != Some(options) {
+            self.entries.clear();
+            self.options = Some(options.clone());
+        }
+        if let Some(value) = self.entries.get(line) {
+            return value.clone();
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.clear();
+        }
+        let value = compute(line);
+        self.entries.insert(line.to_string(), value.clone());
+        value
+    }
+
+    /// Returns the number of cached lines.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true when no line is cached.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_computes_each_line_once() {
+        let mut cache = LineCache::new(8);
+        let mut calls = 0;
+
+        for line in ["a", "b", "a", "a"] {
+            cache.get_or_insert_with(line, &(), |line| {
+                calls += 1;
+                line.len()
+            });
+        }
+
+        assert_eq!(calls, 2);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_starts_over_when_full() {
+        let mut cache = LineCache::new(2);
+        cache.get_or_insert_with("a", &(), str::len);
+        cache.get_or_insert_with("b", &(), str::len);
+
+        assert_eq!(cache.get_or_insert_with("cc", &(), str::len), 2);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_starts_over_when_options_change() {
+        let mut cache = LineCache::new(8);
+        cache.get_or_insert_with("4k", &false, |_| 1);
+
+        assert_eq!(cache.get_or_insert_with("4k", &true, |_| 2), 2);
+        assert_eq!(cache.get_or_insert_with("4k", &true, |_| 3), 2);
+        assert_eq!(cache.get_or_insert_with("4k", &false, |_| 3), 3);
+    }
+}
@@ -0,0 +1,369 @@
+//! Numeric root finding, differentiation and value tables for
+//! `root(f, x, a, b)`, `deriv(f, x, at)` and `table(f, x, from, to, step)`.
+//!
+//! `f` is an expression in the variable `x`, evaluated with the worksheet
+//! variables in scope. `root` also accepts an equation `left = right`, which
+//! is treated as `left - right`.
+
+use std::collections::HashMap;
+
+use crate::eval::ast::Expr;
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::evaluator::{AngleMode, evaluate_in_mode};
+use crate::eval::format::format_ast;
+
+/// Maximum bisection steps for `root`; enough to exhaust `f64` precision.
+const ROOT_MAX_ITERATIONS: usize = 200;
+
+/// Relative step size for `deriv`.
+const DERIV_STEP: f64 = 1e-3;
+
+/// Significant digits kept by `deriv`, dropping the difference quotient's
+/// rounding noise so that `deriv(x^3, x, 2)` is exactly 12.
+const DERIV_DIGITS: i32 = 10;
+
+/// Significant digits kept for the points of a `table`, so that stepping by
+/// `0.1` gives `0.3` rather than `0.30000000000000004`.
+const TABLE_POINT_DIGITS: i32 = 12;
+
+/// Upper bound on the rows of a `table`, which are shown below its line.
+pub const MAX_TABLE_ROWS: usize = 50;
+
+/// The values of a function at evenly spaced points, from `table(...)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    /// The variable the function is evaluated in.
+    pub variable: String,
+    /// The function, formatted for the table header.
+    pub function: String,
+    /// Each point and the function's value there.
+    pub rows: Vec<(f64, f64)>,
+}
+
+/// An expression in one variable that can be evaluated at any point.
+struct Function<'a> {
+    expression: &'a Expr,
+    subtrahend: Option<&'a Expr>,
+    unknown: &'a str,
+    scope: HashMap<String, f64>,
+    angle_mode: AngleMode,
+}
+
+impl<'a> Function<'a> {
+    /// Reads the function and its variable from the first two arguments.
+    fn new(
+        name: &str,
+        args: &'a [Expr],
+        variables: &HashMap<String, f64>,
+        angle_mode: AngleMode,
+    ) -> Result<Self, EvalError> {
+        let Expr::Variable(unknown) = &args[1] else {
+            return Err(EvalError::new(format!(
+                "{name} expects the variable as its second argument"
+            ))
+            .with_code(ErrorCode::InvalidArgument));
+        };
+        let (expression, subtrahend) = match &args[0] {
+            Expr::Equation { left, right } => (left.as_ref(), Some(right.as_ref())),
+            expression => (expression, None),
+        };
+        Ok(Self {
+            expression,
+            subtrahend,
+            unknown,
+            scope: variables.clone(),
+            angle_mode,
+        })
+    }
+
+    /// Evaluates the function at `x`.
+    fn at(&mut self, x: f64) -> Result<f64, EvalError> {
+        self.scope.insert(self.unknown.to_string(), x);
+        let value = evaluate_in_mode(self.expression, &self.scope, self.angle_mode)?;
+        match self.subtrahend {
+            Some(right) => Ok(value - evaluate_in_mode(right, &self.scope, self.angle_mode)?),
+            None => Ok(value),
+        }
+    }
+}
+
+/// Implements the `root(f, x, a, b)` built-in.
+///
+/// Finds a root of `f` between `a` and `b` by bisection, which requires `f`
+/// to change sign over the interval.
+pub(crate) fn root(
+    args: &[Expr],
+    variables: &HashMap<String, f64>,
+    angle_mode: AngleMode,
+) -> Result<f64, EvalError> {
+    let mut f = Function::new("root", args, variables, angle_mode)?;
+    let a = evaluate_in_mode(&args[2], variables, angle_mode)?;
+    let b = evaluate_in_mode(&args[3], variables, angle_mode)?;
+    let (mut low, mut high) = (a.min(b), a.max(b));
+
+    let mut f_low = f.at(low)?;
+    let f_high = f.at(high)?;
+    if f_low == 0.0 {
+        return Ok(low);
+    }
+    if f_high == 0.0 {
+        return Ok(high);
+    }
+    if !f_low.is_finite() || !f_high.is_finite() || f_low.signum() == f_high.signum() {
+        return Err(EvalError::new(format!(
+            "root requires {0} to change sign between {1} and {2}",
+            f.unknown, a, b
+        ))
+        .with_code(ErrorCode::Domain));
+    }
+
+    for _ in 0..ROOT_MAX_ITERATIONS {
+        let mid = low.midpoint(high);
+        if !(low < mid && mid < high) {
+            break;
+        }
+        let f_mid = f.at(mid)?;
+        if f_mid == 0.0 {
+            return Ok(mid);
+        }
+        if f_mid.signum() == f_low.signum() {
+            low = mid;
+            f_low = f_mid;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low.midpoint(high))
+}
+
+/// Implements the `deriv(f, x, at)` built-in.
+///
+/// Uses a five-point central difference with a step relative to `at`.
+pub(crate) fn deriv(
+    args: &[Expr],
+    variables: &HashMap<String, f64>,
+    angle_mode: AngleMode,
+) -> Result<f64, EvalError> {
+    if matches!(args[0], Expr::Equation { .. }) {
+        return Err(
+            EvalError::new("deriv expects an expression, not an equation")
+                .with_code(ErrorCode::InvalidArgument),
+        );
+    }
+    let mut f = Function::new("deriv", args, variables, angle_mode)?;
+    let at = evaluate_in_mode(&args[2], variables, angle_mode)?;
+
+    let h = DERIV_STEP * at.abs().max(1.0);
+    let outer = f.at(2.0f64.mul_add(-h, at))? - f.at(2.0f64.mul_add(h, at))?;
+    let inner = f.at(at + h)? - f.at(at - h)?;
+    let slope = 8.0f64.mul_add(inner, outer) / (12.0 * h);
+    if !slope.is_finite() {
+        return Err(
+            EvalError::new(format!("deriv is undefined at {at}")).with_code(ErrorCode::Domain)
+        );
+    }
+    Ok(round_significant(slope, DERIV_DIGITS))
+}
+
+/// Returns the table for `expr` if it is a `table(f, x, from, to, [step])`
+/// call, which is displayed below its line rather than evaluated to a value.
+///
+/// The step defaults to 1 and must lead from `from` towards `to`; `to` is
+/// included when a whole number of steps reaches it.
+#[must_use]
+pub(crate) fn table_form(
+    expr: &Expr,
+    variables: &HashMap<String, f64>,
+    angle_mode: AngleMode,
+) -> Option<Result<Table, EvalError>> {
+    let Expr::FunctionCall { name, args } = expr else {
+        return None;
+    };
+    (name == "table" && (4..=5).contains(&args.len()))
+        .then(|| build_table(args, variables, angle_mode))
+}
+
+/// Evaluates the function of a `table` call at each of its points.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss,
+    clippy::float_cmp
+)]
+fn build_table(
+    args: &[Expr],
+    variables: &HashMap<String, f64>,
+    angle_mode: AngleMode,
+) -> Result<Table, EvalError> {
+    if matches!(args[0], Expr::Equation { .. }) {
+        return Err(
+            EvalError::new("table expects an expression, not an equation")
+                .with_code(ErrorCode::InvalidArgument),
+        );
+    }
+    let mut f = Function::new("table", args, variables, angle_mode)?;
+    let from = evaluate_in_mode(&args[2], variables, angle_mode)?;
+    let to = evaluate_in_mode(&args[3], variables, angle_mode)?;
+    let step = match args.get(4) {
+        Some(step) => evaluate_in_mode(step, variables, angle_mode)?,
+        None => 1.0,
+    };
+
+    let steps = if from == to { 0.0 } else { (to - from) / step };
+    if !steps.is_finite() || steps < 0.0 {
+        return Err(EvalError::new(format!(
+            "table step {step} does not lead from {from} to {to}"
+        ))
+        .with_code(ErrorCode::Domain));
+    }
+    let count = round_significant(steps, TABLE_POINT_DIGITS).floor() + 1.0;
+    if count > MAX_TABLE_ROWS as f64 {
+        return Err(EvalError::new(format!(
+            "table would have {count} rows, at most {MAX_TABLE_ROWS} are allowed"
+        ))
+        .with_code(ErrorCode::Limit));
+    }
+
+    let rows = (0..count as usize)
+        .map(|i| {
+            let x = round_significant((i as f64).mul_add(step, from), TABLE_POINT_DIGITS);
+            Ok((x, f.at(x)?))
+        })
+        .collect::<Result<_, EvalError>>()?;
+    Ok(Table {
+        variable: f.unknown.to_string(),
+        function: format_ast(f.expression),
+        rows,
+    })
+}
+
+/// Implements the `table(f, x, from, to, [step])` built-in inside larger
+/// expressions, where it has no value.
+pub(crate) fn table_value(
+    _args: &[Expr],
+    _variables: &HashMap<String, f64>,
+    _angle_mode: AngleMode,
+) -> Result<f64, EvalError> {
+    Err(
+        EvalError::new("table can only be used on a line of its own")
+            .with_code(ErrorCode::Statement),
+    )
+}
+
+/// Rounds `value` to `digits` significant digits.
+#[allow(clippy::cast_possible_truncation)]
+fn round_significant(value: f64, digits: i32) -> f64 {
+    if value == 0.0 {
+        return 0.0;
+    }
+    let scale = 10f64.powi(digits - 1 - value.abs().log10().floor() as i32);
+    (value * scale).round() / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::parse_expression;
+
+    fn call(input: &str) -> Result<f64, EvalError> {
+        let Expr::FunctionCall { name, args } = parse_expression(input).unwrap() else {
+            panic!("expected a function call");
+        };
+        let variables = HashMap::from([("k".to_string(), 2.0)]);
+        match name.as_str() {
+            "root" => root(&args, &variables, AngleMode::Radians),
+            _ => deriv(&args, &variables, AngleMode::Radians),
+        }
+    }
+
+    fn assert_close(actual: Result<f64, EvalError>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_root_finds_sign_change() {
+        assert_close(call("root(x^2 - k, x, 0, 2)"), std::f64::consts::SQRT_2);
+        assert_close(call("root(x^3 - 8, x, -10, 10)"), 2.0);
+        assert_close(call("root(x^2 - 4, x, 2, 5)"), 2.0);
+    }
+
+    #[test]
+    fn test_root_accepts_equation() {
+        assert_close(call("root(cos(x) = x, x, 0, 1)"), 0.739_085_133);
+    }
+
+    #[test]
+    fn test_root_requires_sign_change() {
+        assert_eq!(
+            call("root(x^2 + 1, x, -1, 1)"),
+            Err(
+                EvalError::new("root requires x to change sign between -1 and 1")
+                    .with_code(ErrorCode::Domain)
+            )
+        );
+    }
+
+    #[test]
+    fn test_deriv_of_polynomial_and_builtins() {
+        assert_eq!(call("deriv(x^2, x, 3)"), Ok(6.0));
+        assert_eq!(call("deriv(x^3, x, 2)"), Ok(12.0));
+        assert_close(call("deriv(k * x^3, x, 10)"), 600.0);
+        assert_close(call("deriv(sin(x), x, 0)"), 1.0);
+        assert_close(call("deriv(exp(x), x, 1)"), std::f64::consts::E);
+    }
+
+    #[test]
+    fn test_deriv_rejects_equation_and_undefined_points() {
+        assert!(call("deriv(x = 1, x, 0)").is_err());
+        assert!(call("deriv(x^2, 3, 0)").is_err());
+        assert!(call("deriv(sqrt(x), x, 0)").is_err());
+    }
+
+    fn tabulate(input: &str) -> Result<Table, EvalError> {
+        let expr = parse_expression(input).unwrap();
+        let variables = HashMap::from([("k".to_string(), 2.0)]);
+        table_form(&expr, &variables, AngleMode::Radians).expect("should be a table call")
+    }
+
+    #[test]
+    fn test_table_evaluates_function_at_each_step() {
+        let table = tabulate("table(k * x^2, x, 0, 1, 0.25)").unwrap();
+        assert_eq!(table.variable, "x");
+        assert_eq!(table.function, "k * x ^ 2");
+        assert_eq!(
+            table.rows,
+            [
+                (0.0, 0.0),
+                (0.25, 0.125),
+                (0.5, 0.5),
+                (0.75, 1.125),
+                (1.0, 2.0)
+            ]
+        );
+
+        let rows = tabulate("table(x, x, 0, 0.3, 0.1)").unwrap().rows;
+        assert_eq!(rows.last(), Some(&(0.3, 0.3)));
+        assert_eq!(tabulate("table(x, x, 3, 1, -1)").unwrap().rows.len(), 3);
+        assert_eq!(tabulate("table(x, x, 0, 10)").unwrap().rows.len(), 11);
+    }
+
+    #[test]
+    fn test_table_rejects_bad_ranges() {
+        assert!(tabulate("table(x, x, 0, 10, -1)").is_err());
+        assert!(tabulate("table(x, x, 0, 10, 0)").is_err());
+        assert!(tabulate("table(x, x, 0, 1000)").is_err());
+        assert!(tabulate("table(x = 1, x, 0, 1)").is_err());
+        assert!(
+            table_form(
+                &parse_expression("root(x, x, 0, 1)").unwrap(),
+                &HashMap::new(),
+                AngleMode::Radians
+            )
+            .is_none()
+        );
+    }
+}
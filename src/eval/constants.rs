@@ -17,6 +17,12 @@ pub static MATH_CONSTANTS: &[(&str, f64)] = &[
 
 const EPSILON: f64 = 1e-10;
 
+/// Returns `true` if `name` is a predefined mathematical constant.
+#[must_use]
+pub fn is_math_constant(name: &str) -> bool {
+    MATH_CONSTANTS.iter().any(|&(constant, _)| constant == name)
+}
+
 /// Recognizes if a value matches a known mathematical constant,
 /// a small integer multiple (2-4), or a simple fraction (1/2, 1/3, 1/4).
 ///
@@ -1,16 +1,68 @@
 //! Variable context management for expression evaluation.
 //!
 //! Provides variable storage and retrieval that persists across line evaluations.
+//! Worksheet sections push a scope so that their variables are discarded when
+//! the section ends.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::eval::constants::is_math_constant;
+use crate::eval::directive::FormatPresets;
+use crate::eval::evaluator::{AngleMode, DivisionMode};
+use crate::eval::functions::is_builtin_function;
+use crate::eval::json;
+use crate::eval::limits::Limits;
+use crate::eval::money::{Currency, Rounding};
+use crate::eval::parser::is_valid_identifier;
+use crate::eval::token::TokenOptions;
 
 /// Evaluation context that manages variable bindings.
 ///
 /// Stores variables as `f64` values in a `HashMap` and provides methods for
 /// storing and retrieving variables during expression evaluation.
-#[derive(Debug, Default)]
+///
+/// The map always holds the currently visible bindings. Each open scope keeps
+/// the values its assignments replaced, so popping the scope restores the
+/// bindings that were visible before it was pushed. Names declared with
+/// `const` are flagged as immutable, variables holding data sizes remember
+/// their prefix family for display, variables holding durations or rates
+/// remember their power of time, variables holding money remember their
+/// currency, and variables remember the significant figures of the inputs
+/// they were computed from. Variables holding whole numbers too large for an
+/// `f64` keep their exact value beside it. The context also keeps the running
+/// sum shown by the next `total` marker, the angle unit used by trigonometric
+/// functions, what `/` does with whole numbers,
+/// whether results are shown to their significant figures, how amounts of
+/// money are rounded, how number literals are read, the format presets and
+/// default format chosen in the settings, the limits on the worksheet and
+/// its expressions, the what-if overrides
+/// that replace the values assigned to some variables, and the list
+/// variables, which hold several values and are kept apart from the numbers.
+///
+/// The names are also kept in the order they were first defined, so that
+/// listing the variables is deterministic.
+#[derive(Debug, Default, Clone)]
 pub struct EvalContext {
     inner: HashMap<String, f64>,
+    order: Vec<String>,
+    scopes: Vec<HashMap<String, Option<f64>>>,
+    constants: HashSet<String>,
+    sizes: HashMap<String, bool>,
+    times: HashMap<String, i32>,
+    currencies: HashMap<String, &'static Currency>,
+    figures: HashMap<String, u32>,
+    integers: HashMap<String, i64>,
+    subtotal: f64,
+    angle_mode: AngleMode,
+    division_mode: DivisionMode,
+    significant_figures_mode: bool,
+    rounding: Rounding,
+    token_options: TokenOptions,
+    format_presets: FormatPresets,
+    limits: Limits,
+    overrides: HashMap<String, f64>,
+    lists: HashMap<String, Rc<[f64]>>,
 }
 
 impl EvalContext {
@@ -28,13 +80,336 @@ impl EvalContext {
     /// Initializes mathematical constants in the context.
     fn init_constants(&mut self) {
         for &(name, value) in crate::eval::constants::MATH_CONSTANTS {
-            self.inner.insert(name.to_string(), value);
+            self.insert(name, value);
         }
     }
 
+    /// Binds `name` to `value`, appending new names to the definition order
+    /// and reading a unit of the same name as the variable.
+    fn insert(&mut self, name: &str, value: f64) -> Option<f64> {
+        let previous = self.inner.insert(name.to_string(), value);
+        if previous.is_none() {
+            self.order.push(name.to_string());
+            self.token_options.add_variable_unit(name);
+        }
+        previous
+    }
+
+    /// Removes the binding of `name`, its place in the definition order and
+    /// any unit it shadows.
+    fn remove(&mut self, name: &str) -> Option<f64> {
+        let previous = self.inner.remove(name)?;
+        self.order.retain(|n| n != name);
+        self.token_options.remove_variable_unit(name);
+        Some(previous)
+    }
+
     /// Stores a variable with the given name and value.
+    ///
+    /// Inside a scope, the previous binding is remembered the first time a
+    /// name is assigned so that it can be restored by `pop_scope`.
     pub fn set_variable(&mut self, name: &str, value: f64) {
-        self.inner.insert(name.to_string(), value);
+        self.lists.remove(name);
+        self.integers.remove(name);
+        let previous = self.insert(name, value);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.entry(name.to_string()).or_insert(previous);
+        }
+    }
+
+    /// Stores an immutable constant with the given name and value.
+    ///
+    /// The context does not reject the assignment itself; callers check
+    /// `is_constant` first so they can report the error.
+    pub fn set_constant(&mut self, name: &str, value: f64) {
+        self.set_variable(name, value);
+        self.constants.insert(name.to_string());
+    }
+
+    /// Returns `true` if `name` was declared as a constant.
+    #[must_use]
+    pub fn is_constant(&self, name: &str) -> bool {
+        self.constants.contains(name)
+    }
+
+    /// Removes the variable `name`.
+    ///
+    /// Inside a scope, the removed binding is remembered so that `pop_scope`
+    /// restores it. Returns `false` if the variable was not defined.
+    pub fn unset_variable(&mut self, name: &str) -> bool {
+        if self.lists.remove(name).is_some() {
+            return true;
+        }
+        let Some(previous) = self.remove(name) else {
+            return false;
+        };
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.entry(name.to_string()).or_insert(Some(previous));
+        }
+        self.sizes.remove(name);
+        self.times.remove(name);
+        self.currencies.remove(name);
+        self.figures.remove(name);
+        self.integers.remove(name);
+        true
+    }
+
+    /// Stores a list variable, replacing any number of the same name.
+    ///
+    /// Lists are not scoped: one assigned inside a section stays after it.
+    pub fn set_list(&mut self, name: &str, values: Rc<[f64]>) {
+        self.unset_variable(name);
+        self.lists.insert(name.to_string(), values);
+    }
+
+    /// Returns the list variables by name.
+    #[must_use]
+    pub const fn lists(&self) -> &HashMap<String, Rc<[f64]>> {
+        &self.lists
+    }
+
+    /// Removes every variable except the predefined mathematical constants.
+    pub fn clear_variables(&mut self) {
+        self.clear();
+        self.init_constants();
+    }
+
+    /// Records whether `name` holds a data size, and if so whether it is shown
+    /// with binary prefixes.
+    pub fn set_size_prefixes(&mut self, name: &str, binary: Option<bool>) {
+        match binary {
+            Some(binary) => self.sizes.insert(name.to_string(), binary),
+            None => self.sizes.remove(name),
+        };
+    }
+
+    /// Returns the prefix family of `name` if it holds a data size.
+    #[must_use]
+    pub fn size_prefixes(&self, name: &str) -> Option<bool> {
+        self.sizes.get(name).copied()
+    }
+
+    /// Records the power of time of the value held by `name`: 1 for a
+    /// duration, -1 for a rate and 0 for a plain number.
+    pub fn set_time_power(&mut self, name: &str, power: i32) {
+        if power == 0 {
+            self.times.remove(name);
+        } else {
+            self.times.insert(name.to_string(), power);
+        }
+    }
+
+    /// Returns the power of time of the value held by `name`.
+    #[must_use]
+    pub fn time_power(&self, name: &str) -> i32 {
+        self.times.get(name).copied().unwrap_or(0)
+    }
+
+    /// Records the currency of `name` if it holds money.
+    pub fn set_currency(&mut self, name: &str, currency: Option<&'static Currency>) {
+        match currency {
+            Some(currency) => self.currencies.insert(name.to_string(), currency),
+            None => self.currencies.remove(name),
+        };
+    }
+
+    /// Returns the currency of `name` if it holds money.
+    #[must_use]
+    pub fn currency(&self, name: &str) -> Option<&'static Currency> {
+        self.currencies.get(name).copied()
+    }
+
+    /// Records the significant figures of the value held by `name`, `None`
+    /// if it is exact.
+    pub fn set_significant_figures(&mut self, name: &str, figures: Option<u32>) {
+        match figures {
+            Some(figures) => self.figures.insert(name.to_string(), figures),
+            None => self.figures.remove(name),
+        };
+    }
+
+    /// Records the exact value of `name` when it holds a whole number its
+    /// `f64` binding cannot, `None` otherwise.
+    pub fn set_exact_integer(&mut self, name: &str, value: Option<i64>) {
+        match value {
+            Some(value) => self.integers.insert(name.to_string(), value),
+            None => self.integers.remove(name),
+        };
+    }
+
+    /// Returns the exact values of the variables holding whole numbers,
+    /// next to their `f64` bindings.
+    #[must_use]
+    pub const fn integers(&self) -> &HashMap<String, i64> {
+        &self.integers
+    }
+
+    /// Returns the significant figures of `name`, if it holds a measured
+    /// value.
+    #[must_use]
+    pub fn significant_figures(&self, name: &str) -> Option<u32> {
+        self.figures.get(name).copied()
+    }
+
+    /// Adds a line result to the running sum for the next `total` marker.
+    pub fn add_to_subtotal(&mut self, value: f64) {
+        self.subtotal += value;
+    }
+
+    /// Returns the running sum and starts a new one.
+    pub const fn take_subtotal(&mut self) -> f64 {
+        let subtotal = self.subtotal;
+        self.subtotal = 0.0;
+        subtotal
+    }
+
+    /// Returns the unit of angles used by trigonometric functions.
+    #[must_use]
+    pub const fn angle_mode(&self) -> AngleMode {
+        self.angle_mode
+    }
+
+    /// Sets the unit of angles used by trigonometric functions.
+    pub const fn set_angle_mode(&mut self, angle_mode: AngleMode) {
+        self.angle_mode = angle_mode;
+    }
+
+    /// Returns what `/` does with two whole numbers.
+    #[must_use]
+    pub const fn division_mode(&self) -> DivisionMode {
+        self.division_mode
+    }
+
+    /// Sets what `/` does with two whole numbers.
+    pub const fn set_division_mode(&mut self, division_mode: DivisionMode) {
+        self.division_mode = division_mode;
+    }
+
+    /// Returns whether results are shown rounded to their significant figures.
+    #[must_use]
+    pub const fn significant_figures_mode(&self) -> bool {
+        self.significant_figures_mode
+    }
+
+    /// Sets whether results are shown rounded to their significant figures.
+    pub const fn set_significant_figures_mode(&mut self, enabled: bool) {
+        self.significant_figures_mode = enabled;
+    }
+
+    /// Returns how amounts of money are rounded to minor units.
+    #[must_use]
+    pub const fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    /// Sets how amounts of money are rounded to minor units.
+    pub const fn set_rounding(&mut self, rounding: Rounding) {
+        self.rounding = rounding;
+    }
+
+    /// Returns how number literals are read.
+    #[must_use]
+    pub const fn token_options(&self) -> &TokenOptions {
+        &self.token_options
+    }
+
+    /// Turns engineering prefixes on number literals, such as `4.7k`, on or
+    /// off.
+    pub const fn set_engineering_prefixes(&mut self, enabled: bool) {
+        self.token_options.engineering_prefixes = enabled;
+    }
+
+    /// Returns the format presets and the default format for results.
+    #[must_use]
+    pub const fn format_presets(&self) -> &FormatPresets {
+        &self.format_presets
+    }
+
+    /// Sets the format presets and the default format for results.
+    pub fn set_format_presets(&mut self, presets: FormatPresets) {
+        self.format_presets = presets;
+    }
+
+    /// Returns the limits on the worksheet and its expressions.
+    #[must_use]
+    pub const fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Sets the limits on the worksheet and its expressions.
+    pub const fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Makes every assignment to `name` store `value` instead, until the
+    /// overrides are cleared.
+    pub fn set_override(&mut self, name: &str, value: f64) {
+        self.overrides.insert(name.to_string(), value);
+    }
+
+    /// Returns the value overriding assignments to `name`, if any.
+    #[must_use]
+    pub fn override_value(&self, name: &str) -> Option<f64> {
+        self.overrides.get(name).copied()
+    }
+
+    /// Returns the what-if overrides by variable name.
+    #[must_use]
+    pub const fn overrides(&self) -> &HashMap<String, f64> {
+        &self.overrides
+    }
+
+    /// Removes all what-if overrides.
+    pub fn clear_overrides(&mut self) {
+        self.overrides.clear();
+    }
+
+    /// Prepares the context for a fresh pass over the buffer.
+    ///
+    /// Closes scopes and clears constant flags and the running sum left by a
+    /// previous pass, while keeping variable values.
+    pub fn begin_evaluation(&mut self) {
+        self.pop_all_scopes();
+        self.constants.clear();
+        self.subtotal = 0.0;
+    }
+
+    /// Opens a new variable scope.
+    ///
+    /// Variables from enclosing scopes remain visible; assignments made until
+    /// the matching `pop_scope` are discarded when it is called.
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Closes the innermost scope, restoring the bindings it replaced.
+    ///
+    /// Returns `false` if no scope is open.
+    pub fn pop_scope(&mut self) -> bool {
+        let Some(scope) = self.scopes.pop() else {
+            return false;
+        };
+        for (name, previous) in scope {
+            // A name that was constant before the scope cannot have been
+            // assigned inside it, so any constant flag belongs to the scope.
+            self.constants.remove(&name);
+            match previous {
+                Some(value) => self.insert(&name, value),
+                None => self.remove(&name),
+            };
+        }
+        true
+    }
+
+    /// Closes all open scopes.
+    pub fn pop_all_scopes(&mut self) {
+        while self.pop_scope() {}
+    }
+
+    /// Returns the number of open scopes.
+    #[must_use]
+    pub const fn scope_depth(&self) -> usize {
+        self.scopes.len()
     }
 
     /// Retrieves a variable by name.
@@ -45,6 +420,72 @@ impl EvalContext {
         self.inner.get(name).copied()
     }
 
+    /// Retrieves a variable holding a whole number.
+    ///
+    /// Returns `None` if the variable is not defined, has a fractional part,
+    /// or is out of the range of `i64`.
+    #[must_use]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::float_cmp
+    )]
+    pub fn get_int(&self, name: &str) -> Option<i64> {
+        let value = self.get_variable(name)?;
+        self.integers
+            .get(name)
+            .copied()
+            .filter(|&n| n as f64 == value)
+            .or_else(|| {
+                (value.fract() == 0.0 && value.abs() < i64::MAX as f64).then_some(value as i64)
+            })
+    }
+
+    /// Stores a whole number, exactly even beyond 2^53.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn set_int(&mut self, name: &str, value: i64) {
+        self.set_variable(name, value as f64);
+        self.set_exact_integer(name, Some(value));
+    }
+
+    /// Retrieves a variable holding a number, such as a fraction.
+    ///
+    /// Every variable holds a number, so this is `get_variable`.
+    #[must_use]
+    pub fn get_float(&self, name: &str) -> Option<f64> {
+        self.get_variable(name)
+    }
+
+    /// Stores a number, like `set_variable`.
+    pub fn set_float(&mut self, name: &str, value: f64) {
+        self.set_variable(name, value);
+    }
+
+    /// Retrieves a variable holding a truth value, stored as 1 or 0.
+    ///
+    /// Returns `None` if the variable is not defined or holds another number.
+    #[must_use]
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.get_variable(name) {
+            Some(1.0) => Some(true),
+            Some(0.0) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Stores a truth value as 1 or 0.
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.set_variable(name, f64::from(u8::from(value)));
+    }
+
+    /// Returns the variables with their values, in the order they were first
+    /// defined, starting with the predefined constants.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.order
+            .iter()
+            .map(|name| (name.as_str(), self.inner[name]))
+    }
+
     /// Returns a reference to the inner variable map.
     ///
     /// Used when evaluating expressions with variable references.
@@ -53,9 +494,24 @@ impl EvalContext {
         &self.inner
     }
 
-    /// Clears all variables from the context.
+    /// Clears all variables, lists, open scopes, constant flags, size flags,
+    /// currencies, significant figures, and the running sum. The angle mode,
+    /// significant-figures mode, rounding, token options, format presets and
+    /// limits are settings and are kept, as are what-if overrides, which belong to the editor rather
+    /// than the buffer.
     pub fn clear(&mut self) {
         self.inner.clear();
+        self.order.clear();
+        self.token_options.variable_units.clear();
+        self.scopes.clear();
+        self.constants.clear();
+        self.sizes.clear();
+        self.times.clear();
+        self.currencies.clear();
+        self.figures.clear();
+        self.integers.clear();
+        self.lists.clear();
+        self.subtotal = 0.0;
     }
 
     /// Extracts all variables as a `HashMap<String, f64>`.
@@ -64,10 +520,62 @@ impl EvalContext {
         self.inner.clone()
     }
 
+    /// Returns the variables as a JSON object in definition order, leaving
+    /// out the predefined mathematical constants.
+    ///
+    /// # Errors
+    /// Returns a message naming the first variable whose value JSON cannot
+    /// hold, such as infinity or NaN.
+    pub fn to_json(&self) -> Result<String, String> {
+        json::write_object(self.iter().filter(|(name, _)| !is_math_constant(name)))
+    }
+
+    /// Creates a context with the predefined constants and the variables of
+    /// a JSON object such as `{"vat": 0.19}`.
+    ///
+    /// # Errors
+    /// Returns a message for the user if `text` is not such an object.
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let mut context = Self::new();
+        context.import_json(text)?;
+        Ok(context)
+    }
+
+    /// Defines the variables of a JSON object such as `{"vat": 0.19}`, in
+    /// file order, replacing variables of the same name.
+    ///
+    /// Returns the number of variables defined. Nothing is defined if any
+    /// entry is invalid.
+    ///
+    /// # Errors
+    /// Returns a message for the user if `text` is not such an object, if a
+    /// number is too large for a float, if a name is not a valid variable
+    /// name, or if it is a mathematical constant or a built-in function.
+    pub fn import_json(&mut self, text: &str) -> Result<usize, String> {
+        let entries = json::parse_object(text)?;
+        if let Some((name, _)) = entries.iter().find(|(name, _)| !is_valid_identifier(name)) {
+            return Err(format!("invalid variable name '{name}'"));
+        }
+        if let Some((name, _)) = entries.iter().find(|(name, _)| is_math_constant(name)) {
+            return Err(format!("cannot import constant '{name}'"));
+        }
+        if let Some((name, _)) = entries.iter().find(|(name, _)| is_builtin_function(name)) {
+            return Err(format!("cannot import function name '{name}'"));
+        }
+        for (name, value) in &entries {
+            self.set_variable(name, *value);
+        }
+        Ok(entries.len())
+    }
+
     /// Loads variables from a `HashMap<String, f64>`.
+    ///
+    /// Names that are new to the context are defined in alphabetical order.
     pub fn load_variables(&mut self, variables: &HashMap<String, f64>) {
-        for (name, &value) in variables {
-            self.inner.insert(name.clone(), value);
+        let mut names: Vec<&String> = variables.keys().collect();
+        names.sort();
+        for name in names {
+            self.insert(name, variables[name]);
         }
     }
 }
@@ -137,6 +645,141 @@ mod tests {
         assert!(context.get_variable("x").is_none());
     }
 
+    #[test]
+    fn test_pop_scope_discards_new_variables() {
+        let mut context = EvalContext::new();
+        context.push_scope();
+        context.set_variable("x", 1.0);
+
+        assert!(context.pop_scope());
+        assert!(context.get_variable("x").is_none());
+    }
+
+    #[test]
+    fn test_pop_scope_restores_shadowed_variable() {
+        let mut context = EvalContext::new();
+        context.set_variable("x", 1.0);
+        context.push_scope();
+        context.set_variable("x", 2.0);
+        context.set_variable("x", 3.0);
+        assert_eq!(context.get_variable("x"), Some(3.0));
+
+        context.pop_scope();
+        assert_eq!(context.get_variable("x"), Some(1.0));
+    }
+
+    #[test]
+    fn test_variables_named_like_units_shadow_them() {
+        let mut context = EvalContext::new();
+        context.set_variable("EUR", 4.0);
+        context.set_variable("rate", 2.0);
+        context.push_scope();
+        context.set_variable("km", 1.0);
+        assert_eq!(context.token_options().variable_units, ["EUR", "km"]);
+
+        context.pop_scope();
+        context.unset_variable("EUR");
+        assert!(context.token_options().variable_units.is_empty());
+    }
+
+    #[test]
+    fn test_nested_scopes_unwind_in_order() {
+        let mut context = EvalContext::new();
+        context.push_scope();
+        context.set_variable("a", 1.0);
+        context.push_scope();
+        context.set_variable("b", 2.0);
+        assert_eq!(context.scope_depth(), 2);
+
+        context.pop_scope();
+        assert_eq!(context.get_variable("a"), Some(1.0));
+        assert!(context.get_variable("b").is_none());
+
+        context.pop_all_scopes();
+        assert!(context.get_variable("a").is_none());
+        assert_eq!(context.scope_depth(), 0);
+    }
+
+    #[test]
+    fn test_pop_scope_without_scope_returns_false() {
+        let mut context = EvalContext::new();
+        assert!(!context.pop_scope());
+    }
+
+    #[test]
+    fn test_set_constant_marks_name_immutable() {
+        let mut context = EvalContext::new();
+        context.set_constant("tax", 0.19);
+
+        assert!(context.is_constant("tax"));
+        assert!(!context.is_constant("x"));
+        assert_eq!(context.get_variable("tax"), Some(0.19));
+    }
+
+    #[test]
+    fn test_begin_evaluation_clears_constant_flags() {
+        let mut context = EvalContext::new();
+        context.set_constant("tax", 0.19);
+
+        context.begin_evaluation();
+
+        assert!(!context.is_constant("tax"));
+        assert_eq!(context.get_variable("tax"), Some(0.19));
+    }
+
+    #[test]
+    fn test_clear_keeps_angle_mode() {
+        let mut ctx = EvalContext::new();
+        ctx.set_angle_mode(AngleMode::Degrees);
+
+        ctx.clear();
+
+        assert_eq!(ctx.angle_mode(), AngleMode::Degrees);
+    }
+
+    #[test]
+    fn test_take_subtotal_resets_running_sum() {
+        let mut ctx = EvalContext::new();
+        ctx.add_to_subtotal(2.0);
+        ctx.add_to_subtotal(3.5);
+
+        assert!((ctx.take_subtotal() - 5.5).abs() < f64::EPSILON);
+        assert!(ctx.take_subtotal().abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_unset_variable_removes_binding() {
+        let mut ctx = EvalContext::new();
+        ctx.set_variable("x", 1.0);
+
+        assert!(ctx.unset_variable("x"));
+        assert_eq!(ctx.get_variable("x"), None);
+        assert!(!ctx.unset_variable("x"));
+    }
+
+    #[test]
+    fn test_pop_scope_restores_unset_variable() {
+        let mut ctx = EvalContext::new();
+        ctx.set_variable("x", 1.0);
+        ctx.push_scope();
+
+        ctx.unset_variable("x");
+        ctx.pop_scope();
+
+        assert_eq!(ctx.get_variable("x"), Some(1.0));
+    }
+
+    #[test]
+    fn test_clear_variables_keeps_math_constants() {
+        let mut ctx = EvalContext::new();
+        ctx.set_variable("x", 1.0);
+
+        ctx.clear_variables();
+
+        assert_eq!(ctx.get_variable("x"), None);
+        assert_eq!(ctx.get_variable("pi"), Some(std::f64::consts::PI));
+    }
+
     #[test]
     fn test_default_trait() {
         let context = EvalContext::default();
@@ -326,4 +969,126 @@ mod tests {
         let fresh_context = EvalContext::new();
         assert!(fresh_context.get_variable("pi").is_some());
     }
+
+    #[test]
+    fn test_typed_getters_and_setters() {
+        let mut context = EvalContext::new();
+        context.set_int("n", 42);
+        context.set_float("rate", 0.19);
+        context.set_bool("gross", true);
+
+        assert_eq!(context.get_int("n"), Some(42));
+        assert_eq!(context.get_int("rate"), None);
+        assert_eq!(context.get_float("rate"), Some(0.19));
+        assert_eq!(context.get_bool("gross"), Some(true));
+        assert_eq!(context.get_bool("n"), None);
+        assert_eq!(context.get_int("missing"), None);
+
+        context.set_variable("huge", 1e300);
+        assert_eq!(context.get_int("huge"), None);
+
+        context.set_int("big", (1 << 60) + 1);
+        assert_eq!(context.get_int("big"), Some((1 << 60) + 1));
+        context.set_variable("big", 1.5);
+        assert_eq!(context.get_int("big"), None);
+    }
+
+    #[test]
+    fn test_iter_preserves_definition_order() {
+        let mut context = EvalContext::new();
+        context.clear();
+        context.set_variable("b", 1.0);
+        context.set_variable("a", 2.0);
+        context.set_variable("b", 3.0);
+        context.set_variable("c", 4.0);
+        context.unset_variable("a");
+        context.set_variable("a", 5.0);
+
+        let variables: Vec<(&str, f64)> = context.iter().collect();
+        assert_eq!(variables, vec![("b", 3.0), ("c", 4.0), ("a", 5.0)]);
+    }
+
+    #[test]
+    fn test_iter_drops_variables_of_popped_scope() {
+        let mut context = EvalContext::new();
+        context.clear();
+        context.set_variable("x", 1.0);
+        context.push_scope();
+        context.set_variable("y", 2.0);
+        context.set_variable("x", 3.0);
+        context.pop_scope();
+
+        let variables: Vec<(&str, f64)> = context.iter().collect();
+        assert_eq!(variables, vec![("x", 1.0)]);
+    }
+
+    #[test]
+    fn test_iter_starts_with_constants() {
+        let context = EvalContext::new();
+        let first = context.iter().next().map(|(name, _)| name);
+        assert_eq!(first, Some(crate::eval::constants::MATH_CONSTANTS[0].0));
+    }
+
+    #[test]
+    fn test_json_round_trip_keeps_order_without_constants() {
+        let mut context = EvalContext::new();
+        context.set_variable("vat", 0.19);
+        context.set_variable("discount", 0.05);
+
+        let text = context.to_json().unwrap();
+        assert_eq!(text, "{\n  \"vat\": 0.19,\n  \"discount\": 0.05\n}\n");
+
+        let loaded = EvalContext::from_json(&text).unwrap();
+        assert_eq!(loaded.get_variable("vat"), Some(0.19));
+        assert_eq!(loaded.get_variable("pi"), Some(std::f64::consts::PI));
+        assert_eq!(loaded.to_json(), Ok(text));
+    }
+
+    #[test]
+    fn test_to_json_rejects_values_json_cannot_hold() {
+        let mut context = EvalContext::new();
+        context.set_variable("vat", 0.19);
+        context.set_variable("limit", f64::INFINITY);
+        assert_eq!(
+            context.to_json(),
+            Err("'limit' is inf, which JSON cannot hold".to_string())
+        );
+
+        context.set_variable("limit", 1.0);
+        context.set_variable("ratio", f64::NAN);
+        assert_eq!(
+            context.to_json(),
+            Err("'ratio' is NaN, which JSON cannot hold".to_string())
+        );
+    }
+
+    #[test]
+    fn test_from_json_rejects_numbers_out_of_range() {
+        assert_eq!(
+            EvalContext::from_json(r#"{"a": 1e999}"#).err(),
+            Some("invalid number '1e999' for 'a'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_import_json_rejects_invalid_names() {
+        let mut context = EvalContext::new();
+        context.set_variable("x", 1.0);
+
+        assert_eq!(
+            context.import_json(r#"{"y": 2, "2x": 3}"#),
+            Err("invalid variable name '2x'".to_string())
+        );
+        assert_eq!(
+            context.import_json(r#"{"pi": 3}"#),
+            Err("cannot import constant 'pi'".to_string())
+        );
+        assert_eq!(
+            context.import_json(r#"{"y": 2, "sin": 1}"#),
+            Err("cannot import function name 'sin'".to_string())
+        );
+        assert_eq!(context.get_variable("y"), None);
+        assert_eq!(context.import_json(r#"{"x": 5, "y": 2}"#), Ok(2));
+        assert_eq!(context.get_variable("x"), Some(5.0));
+    }
 }
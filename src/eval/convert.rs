@@ -0,0 +1,562 @@
+//! Roman numerals, numbers in words, other bases, and character codes.
+//!
+//! `roman("XIV")` reads a Roman numeral as a number. `to_roman(x)` and
+//! `words(x)` keep their argument as the value, so they can be used in
+//! further arithmetic, but a line whose expression is one of these calls
+//! shows its result as text: `to_roman(2024)` displays `MMXXIV`.
+//!
+//! `frombase("z1", 36)` reads digits in any base from 2 to 36, or base 64,
+//! and `tobase(1261, 36)` shows a number in one: `z1`.
+//!
+//! For byte math, `ord("A")` gives a character's code point and `chr(65)`
+//! shows the character for a code point the same way. `hexdump("Hi")`
+//! counts the UTF-8 bytes of its text and shows them in hex: `48 69`.
+
+use crate::eval::ast::Expr;
+use crate::eval::context::EvalContext;
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::evaluator;
+use crate::eval::functions::Argument;
+
+/// Largest number that can be written as a standard Roman numeral.
+pub const MAX_ROMAN: u32 = 3999;
+
+/// Numbers from this magnitude on are not spelled out by `words`.
+pub const WORDS_LIMIT: f64 = 1e15;
+
+/// Largest integer up to which every integer is exactly representable as f64.
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// Digits of bases up to 36.
+const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Digits of base 64, in the order of RFC 4648.
+const BASE64_DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Roman numeral symbols with their values, including subtractive pairs,
+/// from largest to smallest.
+const ROMAN_SYMBOLS: &[(u32, &str)] = &[
+    (1000, "M"),
+    (900, "CM"),
+    (500, "D"),
+    (400, "CD"),
+    (100, "C"),
+    (90, "XC"),
+    (50, "L"),
+    (40, "XL"),
+    (10, "X"),
+    (9, "IX"),
+    (5, "V"),
+    (4, "IV"),
+    (1, "I"),
+];
+
+/// Names of the numbers below twenty.
+const ONES: &[&str] = &[
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+/// Names of the multiples of ten, starting at twenty.
+const TENS: &[&str] = &[
+    "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+/// Names of the groups of three digits, from thousands up.
+const SCALES: &[&str] = &["thousand", "million", "billion", "trillion"];
+
+/// Writes `n` as a Roman numeral, e.g. `2024` as `MMXXIV`.
+///
+/// `n` must be between 1 and `MAX_ROMAN`.
+#[must_use]
+pub fn to_roman(mut n: u32) -> String {
+    let mut numeral = String::new();
+    for &(value, symbol) in ROMAN_SYMBOLS {
+        while n >= value {
+            numeral.push_str(symbol);
+            n -= value;
+        }
+    }
+    numeral
+}
+
+/// Reads a Roman numeral, ignoring case.
+///
+/// Only numerals in standard form are accepted: `XIV`, but not `XIIII`.
+#[must_use]
+pub fn parse_roman(text: &str) -> Option<u32> {
+    let upper = text.trim().to_ascii_uppercase();
+    let mut rest = upper.as_str();
+    let mut total = 0;
+    for &(value, symbol) in ROMAN_SYMBOLS {
+        while let Some(tail) = rest.strip_prefix(symbol) {
+            total += value;
+            rest = tail;
+        }
+    }
+    (rest.is_empty() && (1..=MAX_ROMAN).contains(&total) && to_roman(total) == upper)
+        .then_some(total)
+}
+
+/// Spells out a whole number in English, e.g. `1234` as
+/// `one thousand two hundred thirty-four`.
+#[must_use]
+pub fn number_to_words(n: i64) -> String {
+    if n == 0 {
+        return ONES[0].to_string();
+    }
+
+    let mut groups = Vec::new();
+    let mut rest = n.unsigned_abs();
+    for scale in std::iter::once("").chain(SCALES.iter().copied()) {
+        let group = rest % 1000;
+        if group > 0 {
+            let words = hundreds_to_words(group);
+            groups.push(if scale.is_empty() {
+                words
+            } else {
+                format!("{words} {scale}")
+            });
+        }
+        rest /= 1000;
+    }
+    if n < 0 {
+        groups.push("minus".to_string());
+    }
+    groups.reverse();
+    groups.join(" ")
+}
+
+/// Spells out a number from 1 to 999.
+#[allow(clippy::cast_possible_truncation)]
+fn hundreds_to_words(n: u64) -> String {
+    let (hundreds, rest) = ((n / 100) as usize, (n % 100) as usize);
+    let mut words = Vec::new();
+    if hundreds > 0 {
+        words.push(format!("{} hundred", ONES[hundreds]));
+    }
+    if rest >= 20 {
+        let tens = TENS[rest / 10 - 2];
+        words.push(match rest % 10 {
+            0 => tens.to_string(),
+            ones => format!("{tens}-{}", ONES[ones]),
+        });
+    } else if rest > 0 {
+        words.push(ONES[rest].to_string());
+    }
+    words.join(" ")
+}
+
+/// Returns the digits of `base`, if it is 2 to 36 or 64.
+fn base_digits(base: u32) -> Option<&'static [u8]> {
+    match base {
+        2..=36 => Some(&DIGITS[..base as usize]),
+        64 => Some(BASE64_DIGITS),
+        _ => None,
+    }
+}
+
+/// Reads `text` as a whole number in `base`, with an optional leading `-`.
+///
+/// Digits of bases up to 36 ignore case; base 64 uses the RFC 4648
+/// alphabet, so its digits are case-sensitive. Returns `None` if `base` is
+/// not supported, a digit is invalid, or the number is too large to be
+/// exact.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn parse_in_base(text: &str, base: u32) -> Option<f64> {
+    let digits = base_digits(base)?;
+    let text = text.trim();
+    let (sign, text) = text
+        .strip_prefix('-')
+        .map_or((1.0, text), |rest| (-1.0, rest));
+    if text.is_empty() {
+        return None;
+    }
+    let mut value: u64 = 0;
+    for c in text.bytes() {
+        let c = if base <= 36 {
+            c.to_ascii_lowercase()
+        } else {
+            c
+        };
+        let digit = digits.iter().position(|&d| d == c)?;
+        value = value
+            .checked_mul(u64::from(base))?
+            .checked_add(digit as u64)?;
+    }
+    let value = value as f64;
+    (value <= MAX_EXACT_INTEGER).then_some(sign * value)
+}
+
+/// Writes `n` in `base`, which must be 2 to 36 or 64.
+#[must_use]
+pub fn format_in_base(n: i64, base: u32) -> String {
+    let Some(digits) = base_digits(base) else {
+        return n.to_string();
+    };
+    let mut magnitude = n.unsigned_abs();
+    let mut text = Vec::new();
+    loop {
+        text.push(digits[usize::try_from(magnitude % u64::from(base)).unwrap_or(0)]);
+        magnitude /= u64::from(base);
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if n < 0 {
+        text.push(b'-');
+    }
+    text.reverse();
+    String::from_utf8(text).unwrap_or_default()
+}
+
+/// Checks that `base` is a supported base and returns it as an integer.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn base_argument(base: f64) -> Result<u32, EvalError> {
+    let supported = base.fract() == 0.0 && (2.0..=64.0).contains(&base);
+    supported
+        .then_some(base as u32)
+        .filter(|&base| base_digits(base).is_some())
+        .ok_or_else(|| {
+            EvalError::new("base must be from 2 to 36, or 64").with_code(ErrorCode::Domain)
+        })
+}
+
+/// Implements the `frombase("z1", 36)` built-in.
+pub(crate) fn frombase_argument(args: &[Argument]) -> Result<f64, EvalError> {
+    let [Argument::Text(text), Argument::Number(base)] = args else {
+        return Err(EvalError::new(
+            "frombase expects digits in quotes and a base, e.g. frombase(\"z1\", 36)",
+        )
+        .with_code(ErrorCode::InvalidArgument));
+    };
+    let base = base_argument(*base)?;
+    parse_in_base(text, base).ok_or_else(|| {
+        EvalError::new(format!("\"{text}\" is not a base-{base} number"))
+            .with_code(ErrorCode::InvalidArgument)
+    })
+}
+
+/// Implements the `tobase(x, base)` built-in, which checks that `x` can be
+/// written in `base` and returns it unchanged.
+pub(crate) fn check_base(args: &[f64]) -> Result<f64, EvalError> {
+    base_argument(args[1])?;
+    let n = args[0];
+    if n.fract() == 0.0 && n.abs() <= MAX_EXACT_INTEGER {
+        Ok(n)
+    } else {
+        Err(
+            EvalError::new("tobase expects a whole number of at most 2^53")
+                .with_code(ErrorCode::Domain),
+        )
+    }
+}
+
+/// Implements the `roman("XIV")` built-in.
+pub(crate) fn roman_argument(args: &[Argument]) -> Result<f64, EvalError> {
+    match args {
+        [Argument::Text(text)] => parse_roman(text).map(f64::from).ok_or_else(|| {
+            EvalError::new(format!("invalid Roman numeral \"{text}\""))
+                .with_code(ErrorCode::InvalidArgument)
+        }),
+        _ => Err(
+            EvalError::new("roman expects a numeral in quotes, e.g. roman(\"XIV\")")
+                .with_code(ErrorCode::InvalidArgument),
+        ),
+    }
+}
+
+/// Implements the `to_roman(x)` built-in, which checks that `x` can be
+/// written as a Roman numeral and returns it unchanged.
+pub(crate) fn check_roman(args: &[f64]) -> Result<f64, EvalError> {
+    let n = args[0];
+    if n.fract() == 0.0 && (1.0..=f64::from(MAX_ROMAN)).contains(&n) {
+        Ok(n)
+    } else {
+        Err(EvalError::new(format!(
+            "to_roman expects a whole number from 1 to {MAX_ROMAN}"
+        ))
+        .with_code(ErrorCode::Domain))
+    }
+}
+
+/// Implements the `words(x)` built-in, which checks that `x` can be spelled
+/// out and returns it unchanged.
+pub(crate) fn check_words(args: &[f64]) -> Result<f64, EvalError> {
+    let n = args[0];
+    if n.fract() == 0.0 && n.abs() < WORDS_LIMIT {
+        Ok(n)
+    } else {
+        Err(
+            EvalError::new("words expects a whole number below one quadrillion")
+                .with_code(ErrorCode::Domain),
+        )
+    }
+}
+
+/// Implements the `ord("A")` built-in.
+pub(crate) fn ord_argument(args: &[Argument]) -> Result<f64, EvalError> {
+    if let [Argument::Text(text)] = args {
+        let mut chars = text.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(f64::from(u32::from(c)));
+        }
+    }
+    Err(
+        EvalError::new("ord expects a single character in quotes, e.g. ord(\"A\")")
+            .with_code(ErrorCode::InvalidArgument),
+    )
+}
+
+/// Returns the character with code point `value`, if there is one.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn char_for(value: f64) -> Option<char> {
+    if value.fract() == 0.0 && (0.0..=f64::from(u32::MAX)).contains(&value) {
+        char::from_u32(value as u32)
+    } else {
+        None
+    }
+}
+
+/// Implements the `chr(x)` built-in, which checks that `x` is a character
+/// code point and returns it unchanged.
+pub(crate) fn check_char(args: &[f64]) -> Result<f64, EvalError> {
+    char_for(args[0]).map(|_| args[0]).ok_or_else(|| {
+        EvalError::new(format!("{} is not a character code", args[0])).with_code(ErrorCode::Domain)
+    })
+}
+
+/// Implements the `hexdump("text")` built-in, which returns the number of
+/// UTF-8 bytes in the text.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn hexdump_argument(args: &[Argument]) -> Result<f64, EvalError> {
+    match args {
+        [Argument::Text(text)] => Ok(text.len() as f64),
+        _ => Err(
+            EvalError::new("hexdump expects text in quotes, e.g. hexdump(\"Hi\")")
+                .with_code(ErrorCode::InvalidArgument),
+        ),
+    }
+}
+
+/// Formats the UTF-8 bytes of `text` as space-separated hex pairs.
+#[must_use]
+pub fn hexdump(text: &str) -> String {
+    text.bytes()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns the text shown for a line whose expression is a call to
+/// `to_roman`, `words`, `tobase`, `chr` or `hexdump` with the given value.
+///
+/// Arguments that decide the text, such as the base of `tobase`, are
+/// evaluated in `context`.
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn text_form(expr: &Expr, value: f64, context: &EvalContext) -> Option<String> {
+    let Expr::FunctionCall { name, args } = expr else {
+        return None;
+    };
+    match (name.as_str(), args.as_slice()) {
+        ("to_roman", _) => Some(to_roman(value as u32)),
+        ("words", _) => Some(number_to_words(value as i64)),
+        ("tobase", [_, base]) => {
+            let base = evaluator::evaluate_in_mode(base, context.variables(), context.angle_mode())
+                .ok()?;
+            Some(format_in_base(value as i64, base_argument(base).ok()?))
+        }
+        ("chr", _) => char_for(value).map(|c| format!("{c:?}")),
+        ("hexdump", [Expr::Text(text)]) => Some(hexdump(text)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::parse_expression;
+
+    #[test]
+    fn test_to_roman() {
+        assert_eq!(to_roman(14), "XIV");
+        assert_eq!(to_roman(2024), "MMXXIV");
+        assert_eq!(to_roman(3999), "MMMCMXCIX");
+    }
+
+    #[test]
+    fn test_parse_roman_round_trips() {
+        for n in 1..=MAX_ROMAN {
+            assert_eq!(parse_roman(&to_roman(n)), Some(n));
+        }
+        assert_eq!(parse_roman("xiv"), Some(14));
+    }
+
+    #[test]
+    fn test_parse_roman_rejects_non_standard_numerals() {
+        for text in ["", "IIII", "VX", "IC", "MMMM", "XIV2", "ABC"] {
+            assert_eq!(parse_roman(text), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn test_number_to_words() {
+        assert_eq!(number_to_words(0), "zero");
+        assert_eq!(number_to_words(13), "thirteen");
+        assert_eq!(number_to_words(40), "forty");
+        assert_eq!(
+            number_to_words(1234),
+            "one thousand two hundred thirty-four"
+        );
+        assert_eq!(number_to_words(-1_000_005), "minus one million five");
+        assert_eq!(
+            number_to_words(999_999_999_999_999),
+            "nine hundred ninety-nine trillion nine hundred ninety-nine billion \
+             nine hundred ninety-nine million nine hundred ninety-nine thousand \
+             nine hundred ninety-nine"
+        );
+    }
+
+    #[test]
+    fn test_checks_reject_unrepresentable_numbers() {
+        assert_eq!(check_roman(&[2024.0]), Ok(2024.0));
+        assert!(check_roman(&[0.0]).is_err());
+        assert!(check_roman(&[4000.0]).is_err());
+        assert!(check_roman(&[1.5]).is_err());
+        assert!(check_words(&[1e15]).is_err());
+        assert!(check_words(&[0.5]).is_err());
+    }
+
+    #[test]
+    fn test_parse_in_base() {
+        assert_eq!(parse_in_base("z1", 36), Some(1261.0));
+        assert_eq!(parse_in_base("Z1", 36), Some(1261.0));
+        assert_eq!(parse_in_base("-ff", 16), Some(-255.0));
+        assert_eq!(parse_in_base("vv", 32), Some(1023.0));
+        assert_eq!(parse_in_base("BA", 64), Some(64.0));
+        assert_eq!(parse_in_base("/", 64), Some(63.0));
+        assert_eq!(parse_in_base("12", 2), None);
+        assert_eq!(parse_in_base("", 10), None);
+        assert_eq!(parse_in_base("1", 40), None);
+        assert_eq!(parse_in_base("zzzzzzzzzzzzzzzz", 36), None);
+    }
+
+    #[test]
+    fn test_format_in_base_round_trips() {
+        for base in (2..=36).chain([64]) {
+            for n in [0, 1, 35, 1261, -4096, 9_007_199_254_740_992] {
+                let text = format_in_base(n, base);
+                #[allow(clippy::cast_precision_loss)]
+                let expected = n as f64;
+                assert_eq!(parse_in_base(&text, base), Some(expected), "{n} in {base}");
+            }
+        }
+        assert_eq!(format_in_base(1261, 36), "z1");
+        assert_eq!(format_in_base(-255, 16), "-ff");
+        assert_eq!(format_in_base(64, 64), "BA");
+    }
+
+    #[test]
+    fn test_base_functions_validate_arguments() {
+        let frombase = |text: &str, base| {
+            frombase_argument(&[Argument::Text(text.to_string()), Argument::Number(base)])
+        };
+        assert_eq!(frombase("z1", 36.0), Ok(1261.0));
+        assert_eq!(
+            frombase("19", 8.0).unwrap_err().message(),
+            "\"19\" is not a base-8 number"
+        );
+        assert!(frombase("1", 37.0).is_err());
+        assert!(frombase("1", 2.5).is_err());
+
+        assert_eq!(check_base(&[1261.0, 36.0]), Ok(1261.0));
+        assert!(check_base(&[1.5, 36.0]).is_err());
+        assert!(check_base(&[10.0, 1.0]).is_err());
+        assert!(check_base(&[10.0, 63.0]).is_err());
+    }
+
+    #[test]
+    fn test_text_form_evaluates_base_in_context() {
+        let mut context = EvalContext::new();
+        context.set_variable("b", 2.0);
+
+        let call = parse_expression("tobase(10, b)").unwrap();
+        assert_eq!(text_form(&call, 10.0, &context), Some("1010".to_string()));
+
+        let undefined = parse_expression("tobase(10, c)").unwrap();
+        assert_eq!(text_form(&undefined, 10.0, &context), None);
+    }
+
+    #[test]
+    fn test_ord_and_chr() {
+        let text = |s: &str| [Argument::Text(s.to_string())];
+        assert_eq!(ord_argument(&text("A")), Ok(65.0));
+        assert_eq!(ord_argument(&text("€")), Ok(8364.0));
+        assert!(ord_argument(&text("AB")).is_err());
+        assert!(ord_argument(&[Argument::Number(65.0)]).is_err());
+
+        assert_eq!(check_char(&[97.0]), Ok(97.0));
+        assert!(check_char(&[f64::from(0xD800)]).is_err());
+        assert!(check_char(&[-1.0]).is_err());
+        assert!(check_char(&[65.5]).is_err());
+    }
+
+    #[test]
+    fn test_hexdump() {
+        assert_eq!(hexdump("Hi!"), "48 69 21");
+        assert_eq!(hexdump("é"), "C3 A9");
+        assert_eq!(
+            hexdump_argument(&[Argument::Text("é".to_string())]),
+            Ok(2.0)
+        );
+    }
+
+    #[test]
+    fn test_text_form_for_character_functions() {
+        let context = EvalContext::new();
+        let chr = parse_expression("chr(65)").unwrap();
+        assert_eq!(text_form(&chr, 65.0, &context), Some("'A'".to_string()));
+
+        let newline = parse_expression("chr(10)").unwrap();
+        assert_eq!(
+            text_form(&newline, 10.0, &context),
+            Some("'\\n'".to_string())
+        );
+
+        let dump = parse_expression("hexdump(\"AB\")").unwrap();
+        assert_eq!(text_form(&dump, 2.0, &context), Some("41 42".to_string()));
+    }
+
+    #[test]
+    fn test_text_form_only_for_top_level_calls() {
+        let context = EvalContext::new();
+        let call = parse_expression("to_roman(2000 + 24)").unwrap();
+        assert_eq!(
+            text_form(&call, 2024.0, &context),
+            Some("MMXXIV".to_string())
+        );
+
+        let sum = parse_expression("to_roman(2024) + 1").unwrap();
+        assert_eq!(text_form(&sum, 2025.0, &context), None);
+    }
+}
@@ -0,0 +1,321 @@
+//! Numeric columns of CSV files for `sum(csv("data.csv", "amount"))`.
+//!
+//! `csv` reads one column of a file, found by its header or by its number
+//! from 1, as a list of values. A list is not a number: it is spread into
+//! the arguments of the function it is passed to, so it can be summed,
+//! averaged or counted. The first row is the header, empty cells are
+//! skipped, and a cell that is not a number is an error naming its row.
+//! Files larger than `MAX_FILE_SIZE` or columns with more values than the
+//! `maxlist` limit allows are refused, and a column is read again only when
+//! its file or the limit changes, rather than on every redraw.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::functions::Argument;
+use crate::eval::list;
+
+/// The name of the function that reads a column.
+pub const CSV_FUNCTION: &str = "csv";
+
+/// Largest file that is read, in bytes.
+pub const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// A column read from a file, with the file's modification time and size
+/// and the most values allowed when it was read.
+type CachedColumn = (Option<SystemTime>, u64, usize, Result<Rc<[f64]>, String>);
+
+thread_local! {
+    /// Columns read before, by file path and column.
+    static COLUMNS: RefCell<HashMap<(String, String), CachedColumn>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Implements `csv("file", "column")` where a single number is expected.
+pub(crate) fn csv_argument(args: &[Argument]) -> Result<f64, EvalError> {
+    column_values(args, usize::MAX)?;
+    Err(list::csv_error())
+}
+
+/// Returns the values of the column named by the arguments of a `csv`
+/// call: a file path and a column header or number.
+///
+/// # Errors
+/// Returns an error if the arguments are not a path and a column, or if the
+/// column cannot be read or has more than `max_values` values.
+pub fn column_values(args: &[Argument], max_values: usize) -> Result<Rc<[f64]>, EvalError> {
+    let (path, column) = match args {
+        [Argument::Text(path), Argument::Text(name)] => (path, Column::Name(name)),
+        [Argument::Text(path), Argument::Number(index)] => (path, Column::Index(*index)),
+        _ => {
+            return Err(EvalError::new(
+                "csv expects a file and a column in quotes, e.g. csv(\"data.csv\", \"amount\")",
+            )
+            .with_code(ErrorCode::InvalidArgument));
+        }
+    };
+    let metadata = fs::metadata(path).map_err(|e| {
+        EvalError::new(format!("cannot read {path}: {e}")).with_code(ErrorCode::Data)
+    })?;
+    if metadata.len() > MAX_FILE_SIZE {
+        return Err(EvalError::new(format!(
+            "{path} is larger than {} MiB",
+            MAX_FILE_SIZE / (1024 * 1024)
+        ))
+        .with_code(ErrorCode::Data));
+    }
+    let modified = metadata.modified().ok();
+    let key = (path.clone(), column.key());
+    let cached = COLUMNS.with_borrow(|columns| {
+        columns
+            .get(&key)
+            .filter(|(time, size, max, _)| {
+                modified.is_some()
+                    && *time == modified
+                    && *size == metadata.len()
+                    && *max == max_values
+            })
+            .map(|(_, _, _, values)| values.clone())
+    });
+    let values = cached.unwrap_or_else(|| {
+        let values = fs::read(path)
+            .map_err(|e| format!("cannot read {path}: {e}"))
+            .and_then(|bytes| {
+                read_column(path, &String::from_utf8_lossy(&bytes), &column, max_values)
+            })
+            .map(Rc::from);
+        COLUMNS.with_borrow_mut(|columns| {
+            columns.insert(key, (modified, metadata.len(), max_values, values.clone()));
+        });
+        values
+    });
+    values.map_err(|e| EvalError::new(e).with_code(ErrorCode::Data))
+}
+
+/// A column of a file, by header or by number from 1.
+enum Column<'a> {
+    Name(&'a str),
+    Index(f64),
+}
+
+impl Column<'_> {
+    /// Returns the text the column is cached under.
+    fn key(&self) -> String {
+        match self {
+            Self::Name(name) => format!("\"{name}\""),
+            Self::Index(index) => index.to_string(),
+        }
+    }
+
+    /// Returns the position of the column in `header`, from 0.
+    fn position(&self, header: &[String]) -> Result<usize, String> {
+        match self {
+            Self::Name(name) => header
+                .iter()
+                .position(|field| field.trim() == *name)
+                .or_else(|| {
+                    header
+                        .iter()
+                        .position(|field| field.trim().eq_ignore_ascii_case(name))
+                })
+                .ok_or_else(|| format!("no column \"{name}\"")),
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            Self::Index(index)
+                if index.fract() == 0.0 && *index >= 1.0 && *index as usize <= header.len() =>
+            {
+                Ok(*index as usize - 1)
+            }
+            Self::Index(index) => Err(format!("no column {index}")),
+        }
+    }
+}
+
+/// Reads `column` from the CSV `text` of the file at `path`, refusing more
+/// than `max_values` values.
+fn read_column(
+    path: &str,
+    text: &str,
+    column: &Column,
+    max_values: usize,
+) -> Result<Vec<f64>, String> {
+    let mut records = records(text);
+    let Some(header) = records.next() else {
+        return Err(format!("{path} is empty"));
+    };
+    let position = column
+        .position(&header)
+        .map_err(|e| format!("{e} in {path}"))?;
+    let mut values = Vec::new();
+    for (index, record) in records.enumerate() {
+        let Some(cell) = record.get(position).map(|cell| cell.trim()) else {
+            continue;
+        };
+        if cell.is_empty() {
+            continue;
+        }
+        if values.len() == max_values {
+            return Err(format!(
+                "{path} has more than {max_values} values (see maxlist)"
+            ));
+        }
+        let value = parse_number(cell)
+            .ok_or_else(|| format!("row {} of {path}: \"{cell}\" is not a number", index + 2))?;
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// Parses a cell as a number, allowing commas between thousands.
+pub(crate) fn parse_number(cell: &str) -> Option<f64> {
+    cell.parse()
+        .ok()
+        .or_else(|| cell.replace(',', "").parse().ok())
+        .filter(|value: &f64| value.is_finite())
+}
+
+/// Splits CSV `text` into records of fields.
+///
+/// Fields are separated by commas and records by line breaks. A field in
+/// double quotes may contain both, and `""` inside it stands for a quote.
+fn records(text: &str) -> impl Iterator<Item = Vec<String>> + '_ {
+    let mut chars = text.trim_start_matches('\u{FEFF}').chars().peekable();
+    std::iter::from_fn(move || {
+        chars.peek()?;
+        let mut record = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => record.push(std::mem::take(&mut field)),
+                '\r' if !quoted => {}
+                '\n' if !quoted => break,
+                c => field.push(c),
+            }
+        }
+        record.push(field);
+        Some(record)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::limits::Limits;
+
+    fn column(text: &str, column: &Column) -> Result<Vec<f64>, String> {
+        read_column("data.csv", text, column, Limits::default().list)
+    }
+
+    #[test]
+    fn test_records_split_quoted_fields() {
+        let text = "name,note\r\n\"Smith, J\",\"say \"\"hi\"\"\nthere\"\nlast,";
+        let records: Vec<Vec<String>> = records(text).collect();
+        assert_eq!(
+            records,
+            [
+                vec!["name", "note"],
+                vec!["Smith, J", "say \"hi\"\nthere"],
+                vec!["last", ""],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_column_by_name_and_number() {
+        let text = "date,Amount\n2024-01-01,12.5\n2024-01-02,\"1,000\"\n2024-01-03,\n";
+        assert_eq!(
+            column(text, &Column::Name("Amount")),
+            Ok(vec![12.5, 1000.0])
+        );
+        assert_eq!(
+            column(text, &Column::Name("amount")),
+            Ok(vec![12.5, 1000.0])
+        );
+        assert_eq!(column(text, &Column::Index(2.0)), Ok(vec![12.5, 1000.0]));
+        assert_eq!(
+            column(text, &Column::Name("price")),
+            Err("no column \"price\" in data.csv".to_string())
+        );
+        assert_eq!(
+            column(text, &Column::Index(3.0)),
+            Err("no column 3 in data.csv".to_string())
+        );
+        assert_eq!(
+            column(text, &Column::Index(1.0)),
+            Err("row 2 of data.csv: \"2024-01-01\" is not a number".to_string())
+        );
+        assert_eq!(
+            column("", &Column::Index(1.0)),
+            Err("data.csv is empty".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_column_caps_values() {
+        let max = Limits::default().list;
+        let text = format!("n\n{}", "1\n".repeat(max + 1));
+        assert_eq!(
+            column(&text, &Column::Name("n")),
+            Err(format!("data.csv has more than {max} values (see maxlist)"))
+        );
+
+        let capped = |text: &str| read_column("data.csv", text, &Column::Name("n"), 2);
+        assert_eq!(
+            capped("n\n1\n2\n3\n"),
+            Err("data.csv has more than 2 values (see maxlist)".to_string())
+        );
+        assert_eq!(capped("n\n1\n2\n"), Ok(vec![1.0, 2.0]));
+    }
+
+    #[test]
+    fn test_column_values_reads_file_again_when_changed() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("data.csv");
+        let path_text = path.to_string_lossy().to_string();
+        let args = [
+            Argument::Text(path_text.clone()),
+            Argument::Text("amount".to_string()),
+        ];
+        fs::write(&path, "amount\n1\n2\n").expect("write file");
+        assert_eq!(
+            column_values(&args, Limits::default().list)
+                .unwrap()
+                .as_ref(),
+            [1.0, 2.0]
+        );
+
+        fs::write(&path, "amount\n1\n2\n3\n").expect("write file");
+        assert_eq!(
+            column_values(&args, Limits::default().list)
+                .unwrap()
+                .as_ref(),
+            [1.0, 2.0, 3.0]
+        );
+
+        assert_eq!(
+            csv_argument(&args).unwrap_err().message(),
+            "csv gives a list of values; pass it to a function such as sum, avg or count"
+        );
+        assert!(
+            column_values(
+                &[
+                    Argument::Text(format!("{path_text}.missing")),
+                    Argument::Number(1.0)
+                ],
+                Limits::default().list
+            )
+            .unwrap_err()
+            .message()
+            .starts_with("cannot read ")
+        );
+    }
+}
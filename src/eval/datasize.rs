@@ -0,0 +1,89 @@
+//! Data-size literals and formatting.
+//!
+//! Numbers followed by a byte unit (`1.5 GiB`, `300 MB`) are read as a
+//! number of bytes. A line that involves such a size, directly or through a
+//! variable assigned from one, shows its result in a human-friendly unit of
+//! the same prefix family: binary (`KiB`, `MiB`, ...) or SI (`kB`, `MB`, ...).
+//! The byte units come from the unit conversion catalog.
+
+use crate::eval::context::EvalContext;
+use crate::eval::token::{Token, Tokenizer};
+use crate::eval::units::{Dimension, UNITS, Unit};
+
+/// Returns the byte unit with exactly this symbol, e.g. `MiB`.
+///
+/// Bit units are not accepted as literals.
+#[must_use]
+pub fn size_unit(symbol: &str) -> Option<&'static Unit> {
+    UNITS
+        .iter()
+        .find(|u| u.dimension == Dimension::Data && u.symbol == symbol && u.symbol.ends_with('B'))
+}
+
+/// Returns the prefix family of the sizes in `expression`, if it involves any.
+///
+/// The first size literal, or variable holding a size, decides whether the
+/// result is shown with binary (`true`) or SI (`false`) prefixes.
+#[must_use]
+pub fn size_prefixes(expression: &str, context: &EvalContext) -> Option<bool> {
+    let (tokens, _) =
+        Tokenizer::with_options(expression, context.token_options()).tokenize_partial();
+    tokens.iter().find_map(|(token, _)| match token {
+        Token::Size { binary, .. } => Some(*binary),
+        Token::Identifier(name) => context.size_prefixes(name),
+        _ => None,
+    })
+}
+
+/// Formats a number of bytes with the largest unit of the family that keeps
+/// the value at or above 1, rounded to two decimals: `1.78 GiB`.
+#[must_use]
+pub fn format_size(bytes: f64, binary: bool) -> String {
+    let (factor, symbol) = UNITS
+        .iter()
+        .filter(|u| size_unit(u.symbol).is_some())
+        .filter(|u| u.symbol == "B" || u.symbol.contains('i') == binary)
+        .filter(|u| u.factor() <= bytes.abs())
+        .max_by(|a, b| a.factor().total_cmp(&b.factor()))
+        .map_or((1.0, "B"), |u| (u.factor(), u.symbol));
+
+    let scaled = format!("{:.2}", bytes / factor);
+    let scaled = scaled.trim_end_matches('0').trim_end_matches('.');
+    format!("{scaled} {symbol}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_unit_accepts_byte_units_only() {
+        assert!(size_unit("GiB").is_some());
+        assert!(size_unit("kB").is_some());
+        assert!(size_unit("b").is_none());
+        assert!(size_unit("Mbit").is_none());
+        assert!(size_unit("gib").is_none());
+    }
+
+    #[test]
+    fn test_format_size_picks_unit() {
+        assert_eq!(format_size(1_910_612_736.0, true), "1.78 GiB");
+        assert_eq!(format_size(1_500_000.0, false), "1.5 MB");
+        assert_eq!(format_size(1024.0, true), "1 KiB");
+        assert_eq!(format_size(512.0, true), "512 B");
+        assert_eq!(format_size(0.0, false), "0 B");
+        assert_eq!(format_size(-2048.0, true), "-2 KiB");
+    }
+
+    #[test]
+    fn test_size_prefixes_from_literal_or_variable() {
+        let mut context = EvalContext::new();
+        assert_eq!(size_prefixes("1 GiB + 300 MB", &context), Some(true));
+        assert_eq!(size_prefixes("300 MB + 1 GiB", &context), Some(false));
+        assert_eq!(size_prefixes("1 + 2", &context), None);
+
+        context.set_variable("disk", 1e12);
+        context.set_size_prefixes("disk", Some(false));
+        assert_eq!(size_prefixes("disk / 2", &context), Some(false));
+    }
+}
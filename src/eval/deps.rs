@@ -0,0 +1,219 @@
+//! Dependencies between buffer lines.
+//!
+//! A line depends on the line that most recently assigned each variable it
+//! reads, following the same scoping as evaluation: `unset` and `clearvars`
+//! forget assignments, and assignments made inside a `@section` are
+//! forgotten at its `@end`. The graph is drawn as a tree rooted at the
+//! assignments that read no other line, with every line that uses a result
+//! shown beneath it.
+
+use std::collections::HashMap;
+
+use crate::eval::inspect::referenced_variables;
+use crate::eval::parser::ParsedLine;
+use crate::eval::{EvalContext, parse_expression_in, parse_line_with_presets};
+
+/// Returns, for each line, the indices of the earlier lines it reads
+/// variables from, in order of first use, reading the lines with the
+/// settings of `context`.
+#[must_use]
+pub fn line_dependencies<S: AsRef<str>>(lines: &[S], context: &EvalContext) -> Vec<Vec<usize>> {
+    let mut assigned: HashMap<String, usize> = HashMap::new();
+    let mut scopes: Vec<HashMap<String, usize>> = Vec::new();
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            match parse_line_with_presets(line.as_ref(), context.format_presets()) {
+                ParsedLine::Assignment {
+                    name, expression, ..
+                } => {
+                    let reads = reads_of(&[expression], &assigned, context);
+                    assigned.insert(name, index);
+                    reads
+                }
+                ParsedLine::MultipleAssignment {
+                    names, expressions, ..
+                } => {
+                    let reads = reads_of(&expressions, &assigned, context);
+                    for name in names {
+                        assigned.insert(name, index);
+                    }
+                    reads
+                }
+                ParsedLine::Expression(expression) => reads_of(&[expression], &assigned, context),
+                ParsedLine::Unset(names) => {
+                    for name in &names {
+                        assigned.remove(name);
+                    }
+                    Vec::new()
+                }
+                ParsedLine::ClearVariables => {
+                    assigned.clear();
+                    Vec::new()
+                }
+                ParsedLine::SectionStart(_) => {
+                    scopes.push(assigned.clone());
+                    Vec::new()
+                }
+                ParsedLine::SectionEnd => {
+                    if let Some(outer) = scopes.pop() {
+                        assigned = outer;
+                    }
+                    Vec::new()
+                }
+                ParsedLine::Total | ParsedLine::Disabled | ParsedLine::Empty => Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the lines assigning the variables read by `expressions`.
+fn reads_of(
+    expressions: &[String],
+    assigned: &HashMap<String, usize>,
+    context: &EvalContext,
+) -> Vec<usize> {
+    let mut reads = Vec::new();
+    for expr in expressions
+        .iter()
+        .filter_map(|e| parse_expression_in(e, context).ok())
+    {
+        for name in referenced_variables(&expr) {
+            if let Some(&line) = assigned.get(&name)
+                && !reads.contains(&line)
+            {
+                reads.push(line);
+            }
+        }
+    }
+    reads
+}
+
+/// Draws the dependency graph of `lines` as an indented tree.
+///
+/// Each node is labelled with its one-based line number and text. A line
+/// that feeds several others appears under each of them, but its own
+/// dependents are only expanded the first time; later occurrences are
+/// marked with `↑`.
+///
+/// # Returns
+/// An empty vector if no line depends on another.
+#[must_use]
+pub fn dependency_tree_lines<S: AsRef<str>>(lines: &[S], context: &EvalContext) -> Vec<String> {
+    let dependencies = line_dependencies(lines, context);
+    let mut dependents = vec![Vec::new(); lines.len()];
+    for (index, reads) in dependencies.iter().enumerate() {
+        for &read in reads {
+            dependents[read].push(index);
+        }
+    }
+
+    let mut tree = Vec::new();
+    let mut expanded = vec![false; lines.len()];
+    for root in (0..lines.len()).filter(|&i| dependencies[i].is_empty()) {
+        if dependents[root].is_empty() {
+            continue;
+        }
+        tree.push(node_label(lines, root));
+        expanded[root] = true;
+        push_dependents(lines, &dependents, root, "", &mut expanded, &mut tree);
+    }
+    tree
+}
+
+/// Appends the lines that use `index` to `tree`, prefixed for tree drawing.
+fn push_dependents<S: AsRef<str>>(
+    lines: &[S],
+    dependents: &[Vec<usize>],
+    index: usize,
+    prefix: &str,
+    expanded: &mut [bool],
+    tree: &mut Vec<String>,
+) {
+    let count = dependents[index].len();
+    for (i, &child) in dependents[index].iter().enumerate() {
+        let (branch, indent) = if i + 1 == count {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        let label = node_label(lines, child);
+        if expanded[child] {
+            let marker = if dependents[child].is_empty() {
+                ""
+            } else {
+                " ↑"
+            };
+            tree.push(format!("{prefix}{branch}{label}{marker}"));
+            continue;
+        }
+        expanded[child] = true;
+        tree.push(format!("{prefix}{branch}{label}"));
+        push_dependents(
+            lines,
+            dependents,
+            child,
+            &format!("{prefix}{indent}"),
+            expanded,
+            tree,
+        );
+    }
+}
+
+/// Returns the display label for a line: its number and trimmed text.
+fn node_label<S: AsRef<str>>(lines: &[S], index: usize) -> String {
+    format!("{}: {}", index + 1, lines[index].as_ref().trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_dependencies_follow_latest_assignment() {
+        let lines = ["a = 2", "b = a * 3", "a = 5", "a + b", "pi * c"];
+        assert_eq!(
+            line_dependencies(&lines, &EvalContext::new()),
+            [vec![], vec![0], vec![], vec![2, 1], vec![]]
+        );
+    }
+
+    #[test]
+    fn test_line_dependencies_respect_scopes() {
+        let lines = [
+            "a = 1", "@section", "a = 2", "a + 1", "@end", "a * 2", "unset a", "a",
+        ];
+        let dependencies = line_dependencies(&lines, &EvalContext::new());
+        assert_eq!(dependencies[3], [2]);
+        assert_eq!(dependencies[5], [0]);
+        assert!(dependencies[7].is_empty());
+    }
+
+    #[test]
+    fn test_dependency_tree_lines() {
+        let lines = [
+            "price = 40",
+            "qty = 3",
+            "",
+            "cost = price * qty",
+            "cost * 1.2",
+        ];
+        assert_eq!(
+            dependency_tree_lines(&lines, &EvalContext::new()),
+            [
+                "1: price = 40",
+                "└── 4: cost = price * qty",
+                "    └── 5: cost * 1.2",
+                "2: qty = 3",
+                "└── 4: cost = price * qty ↑",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dependency_tree_lines_without_dependencies() {
+        assert!(dependency_tree_lines(&["1 + 2", "x = 3"], &EvalContext::new()).is_empty());
+    }
+}
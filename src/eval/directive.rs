@@ -0,0 +1,172 @@
+//! Per-line result formatting directives.
+//!
+//! A line can end in `| name` to choose how its result is shown, without
+//! changing the value later lines compute with: `255 | hex` shows `0xff`,
+//! `1/3 | 2dp` shows `0.33`, and `1234.5 | eur` shows `€1,234.50`. Besides
+//! these built-in formats, the name can be a preset defined in the settings,
+//! and one format can be chosen as the default for lines without a
+//! directive. The directive is split off before the line is parsed, so the
+//! rest of the line is evaluated as usual.
+
+use std::fmt;
+
+use crate::eval::numformat::FormatSpec;
+
+/// Separates a line from its formatting directive.
+pub const DIRECTIVE_SEPARATOR: char = '|';
+
+/// The named format presets from the settings, and the format applied to
+/// lines without a directive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FormatPresets {
+    presets: Vec<(String, FormatSpec)>,
+    default: Option<FormatDirective>,
+}
+
+impl FormatPresets {
+    /// Creates the presets with a default format.
+    ///
+    /// The default names a preset or a built-in format; an unknown name
+    /// leaves lines without a default.
+    #[must_use]
+    pub fn new(presets: Vec<(String, FormatSpec)>, default: Option<&str>) -> Self {
+        let mut formats = Self {
+            presets,
+            default: None,
+        };
+        formats.default = default.and_then(|name| FormatDirective::parse(name, &formats));
+        formats
+    }
+
+    /// Returns the format applied to lines without a directive, if any.
+    #[must_use]
+    pub const fn default_format(&self) -> Option<&FormatDirective> {
+        self.default.as_ref()
+    }
+
+    /// Returns the preset named `name`, ignoring case.
+    fn preset(&self, name: &str) -> Option<&FormatSpec> {
+        self.presets
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|(_, spec)| spec)
+    }
+}
+
+/// A named way of showing a line's result, chosen with a trailing `| name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatDirective {
+    /// The format name, in lowercase.
+    name: String,
+    /// How the result is written.
+    spec: FormatSpec,
+}
+
+impl FormatDirective {
+    /// Looks up a format name, ignoring case.
+    ///
+    /// The `presets` take precedence over the built-in formats `hex`, `bin`,
+    /// `oct`, `sci`, `2dp`, `eur`, `usd` and `gbp`.
+    #[must_use]
+    pub fn parse(name: &str, presets: &FormatPresets) -> Option<Self> {
+        let name = name.trim().to_ascii_lowercase();
+        let spec = presets
+            .preset(&name)
+            .cloned()
+            .or_else(|| FormatSpec::builtin(&name))?;
+        Some(Self { name, spec })
+    }
+
+    /// Formats `value` as the directive asks.
+    ///
+    /// # Errors
+    /// Returns a message when the value cannot be shown this way, such as a
+    /// fraction in hexadecimal.
+    pub fn format(&self, value: f64) -> Result<String, String> {
+        self.spec
+            .format(value)
+            .map_err(|message| format!("| {} {message}", self.name))
+    }
+}
+
+impl fmt::Display for FormatDirective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Splits a trailing `| name` directive off `line`.
+///
+/// Returns the line unchanged with `None` if it does not end in a built-in
+/// format or one of the `presets`, so that text such as `` `ls | wc -l` ``
+/// is left alone.
+#[must_use]
+pub fn split_format_directive<'a>(
+    line: &'a str,
+    presets: &FormatPresets,
+) -> (&'a str, Option<FormatDirective>) {
+    line.rsplit_once(DIRECTIVE_SEPARATOR)
+        .and_then(|(head, name)| {
+            Some((
+                head.trim_end(),
+                Some(FormatDirective::parse(name, presets)?),
+            ))
+        })
+        .unwrap_or((line, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directive(name: &str) -> Option<FormatDirective> {
+        FormatDirective::parse(name, &FormatPresets::default())
+    }
+
+    fn split(line: &str) -> (&str, Option<FormatDirective>) {
+        split_format_directive(line, &FormatPresets::default())
+    }
+
+    #[test]
+    fn test_split_format_directive() {
+        assert_eq!(split("255 | hex"), ("255", directive("hex")));
+        assert_eq!(split("x = 1/3 |2DP"), ("x = 1/3", directive("2dp")));
+        assert_eq!(split("`ls | wc -l`"), ("`ls | wc -l`", None));
+        assert_eq!(split("1 | 16dp"), ("1 | 16dp", None));
+    }
+
+    #[test]
+    fn test_directive_format_names_the_directive_in_errors() {
+        let hex = directive("HEX").unwrap();
+        assert_eq!(hex.to_string(), "hex");
+        assert_eq!(hex.format(255.0).as_deref(), Ok("0xff"));
+        assert_eq!(
+            hex.format(1.5),
+            Err("| hex needs a whole number".to_string())
+        );
+    }
+
+    #[test]
+    fn test_presets_and_default_format() {
+        let money = FormatSpec::parse("2dp, thousands, prefix \"€\"").unwrap();
+        let presets = FormatPresets::new(vec![("Money".to_string(), money)], Some("money"));
+
+        let (_, preset) = split_format_directive("1234.5 | money", &presets);
+        assert_eq!(preset.unwrap().format(1234.5).as_deref(), Ok("€1,234.50"));
+        assert_eq!(
+            presets.default_format().map(ToString::to_string).as_deref(),
+            Some("money")
+        );
+        assert_eq!(
+            FormatPresets::new(Vec::new(), Some("money")).default_format(),
+            None
+        );
+
+        let presets = FormatPresets::default();
+        assert_eq!(
+            split_format_directive("1 | money", &presets),
+            ("1 | money", None)
+        );
+        assert_eq!(presets.default_format(), None);
+    }
+}
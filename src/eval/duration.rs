@@ -0,0 +1,231 @@
+//! Duration literals and formatting.
+//!
+//! A number directly followed by `d`, `h`, `m` or `s` is a duration in
+//! seconds, and several such parts can be chained from largest to smallest
+//! unit: `1h30m` is 5400. Writing a space between the number and the letter,
+//! as in `2 h`, still multiplies by a variable.
+//!
+//! A line shows its result as a duration when it is a time, following the
+//! powers of time through the expression: `(3h20m) / 5` shows `40m`. A
+//! quantity divided by a time is a rate and is shown per hour, so
+//! `42 / 3h30m` shows `12 per hour`, and `42 km / 3h30m` shows
+//! `12 km per hour`. Variables assigned a duration or a rate keep being shown
+//! as one in later lines.
+//!
+//! A letter that is the name of a variable is not a unit on its own: after
+//! `m = 5`, `2m` is 10. Chained durations such as `1h30m` are always read
+//! as durations.
+
+use crate::eval::ast::{BinaryOp, Expr, Parser};
+use crate::eval::context::EvalContext;
+use crate::eval::token::{Token, TokenOptions, Tokenizer};
+
+/// Duration units with their length in seconds, from largest to smallest.
+pub const DURATION_UNITS: &[(char, f64)] =
+    &[('d', 86_400.0), ('h', 3600.0), ('m', 60.0), ('s', 1.0)];
+
+/// Name standing in for duration literals when finding the power of time.
+const DURATION_MARKER: &str = "\u{0}duration";
+
+/// Functions whose result has the same dimension as their first argument.
+const DIMENSION_PRESERVING_FUNCTIONS: &[&str] = &[
+    "abs", "min", "max", "round", "floor", "ceil", "trunc", "hypot",
+];
+
+/// Returns the length in seconds of the duration unit `unit`.
+#[must_use]
+pub fn unit_seconds(unit: char) -> Option<f64> {
+    DURATION_UNITS
+        .iter()
+        .find(|(symbol, _)| *symbol == unit)
+        .map(|&(_, seconds)| seconds)
+}
+
+/// Returns the power of time of the result of `expression`: 1 for a
+/// duration, -1 for a rate and 0 for a plain number.
+///
+/// Variables count with the power recorded in `context`. Expressions that do
+/// not parse count as plain numbers.
+#[must_use]
+pub fn time_power(expression: &str, context: &EvalContext) -> i32 {
+    let (tokens, _) =
+        Tokenizer::with_options(expression, context.token_options()).tokenize_partial();
+    let timed = tokens.iter().any(|(token, _)| match token {
+        Token::Duration(_) => true,
+        Token::Identifier(name) => context.time_power(name) != 0,
+        _ => false,
+    });
+    if !timed {
+        return 0;
+    }
+    let tokens = tokens
+        .into_iter()
+        .map(|(token, span)| match token {
+            Token::Duration(_) => (Token::Identifier(DURATION_MARKER.to_string()), span),
+            token => (token, span),
+        })
+        .collect();
+    Parser::with_limits(tokens, context.limits())
+        .parse()
+        .map_or(0, |expr| expr_power(&expr, context))
+}
+
+/// Returns the power of time of `expr`.
+#[allow(clippy::cast_possible_truncation)]
+fn expr_power(expr: &Expr, context: &EvalContext) -> i32 {
+    match expr {
+        Expr::Variable(name) if name == DURATION_MARKER => 1,
+        Expr::Variable(name) => context.time_power(name),
+        Expr::UnaryMinus(inner) => expr_power(inner, context),
+        Expr::BinaryOp { left, op, right } => {
+            let (left_power, right_power) = (expr_power(left, context), expr_power(right, context));
+            match op {
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mod if left_power == 0 => right_power,
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mod => left_power,
+                BinaryOp::Mul => left_power + right_power,
+                BinaryOp::Div | BinaryOp::FloorDiv => left_power - right_power,
+                BinaryOp::Pow => match right.as_ref() {
+                    Expr::Number(n) if n.fract() == 0.0 && n.abs() < 8.0 => left_power * *n as i32,
+                    _ => 0,
+                },
+            }
+        }
+        Expr::FunctionCall { name, args }
+            if DIMENSION_PRESERVING_FUNCTIONS.contains(&name.as_str()) =>
+        {
+            args.first().map_or(0, |arg| expr_power(arg, context))
+        }
+        Expr::Number(_)
+        | Expr::Factorial(_)
+        | Expr::FunctionCall { .. }
+        | Expr::Text(_)
+        | Expr::Equation { .. } => 0,
+    }
+}
+
+/// Formats a number of seconds as days, hours, minutes and seconds, leaving
+/// out zero parts: `1h 30m`, `2d 4h`, `12.5s`.
+///
+/// Seconds are rounded to two decimals. Returns `None` for values that are
+/// not finite or too large to split into parts.
+#[must_use]
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+pub fn format_duration(seconds: f64) -> Option<String> {
+    let centiseconds = (seconds.abs() * 100.0).round();
+    if !centiseconds.is_finite() || centiseconds >= u64::MAX as f64 {
+        return None;
+    }
+    let mut rest = centiseconds as u64;
+    let mut parts = Vec::new();
+    for &(symbol, length) in &DURATION_UNITS[..DURATION_UNITS.len() - 1] {
+        let unit = length as u64 * 100;
+        if rest >= unit {
+            parts.push(format!("{}{symbol}", rest / unit));
+            rest %= unit;
+        }
+    }
+    if rest > 0 || parts.is_empty() {
+        let scaled = format!("{}.{:02}", rest / 100, rest % 100);
+        parts.push(format!(
+            "{}s",
+            scaled.trim_end_matches('0').trim_end_matches('.')
+        ));
+    }
+    let sign = if seconds < 0.0 && centiseconds > 0.0 {
+        "-"
+    } else {
+        ""
+    };
+    Some(format!("{sign}{}", parts.join(" ")))
+}
+
+/// Formats a rate in units per second as units per hour, rounded to two
+/// decimals: `12 per hour`, or `12 km per hour` with the unit `km`.
+#[must_use]
+pub fn format_rate(per_second: f64, unit: Option<&str>) -> Option<String> {
+    let per_hour = per_second * 3600.0;
+    if !per_hour.is_finite() {
+        return None;
+    }
+    let scaled = format!("{per_hour:.2}");
+    let scaled = scaled.trim_end_matches('0').trim_end_matches('.');
+    Some(unit.map_or_else(
+        || format!("{scaled} per hour"),
+        |unit| format!("{scaled} {unit} per hour"),
+    ))
+}
+
+/// Returns the unit that labels the quantities of `expression`, such as the
+/// `km` of `42 km / 3h30m`, if they all have the same one.
+#[must_use]
+pub fn rate_unit(expression: &str, options: &TokenOptions) -> Option<&'static str> {
+    let (tokens, _) = Tokenizer::with_options(expression, options).tokenize_partial();
+    let mut units = tokens.iter().filter_map(|(token, _)| match token {
+        Token::Quantity { unit, .. } => Some(*unit),
+        _ => None,
+    });
+    let unit = units.next()?;
+    units.all(|other| other == unit).then_some(unit)
+}
+
+/// Returns the text shown for a value with the power of time `power`, or
+/// `None` if it is shown as a plain number. Rates are labelled with `unit`.
+#[must_use]
+pub fn duration_text(value: f64, power: i32, unit: Option<&str>) -> Option<String> {
+    match power {
+        1 => format_duration(value),
+        -1 => format_rate(value, unit),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(5400.0).as_deref(), Some("1h 30m"));
+        assert_eq!(format_duration(2400.0).as_deref(), Some("40m"));
+        assert_eq!(format_duration(187_212.5).as_deref(), Some("2d 4h 12.5s"));
+        assert_eq!(format_duration(59.999).as_deref(), Some("1m"));
+        assert_eq!(format_duration(0.0).as_deref(), Some("0s"));
+        assert_eq!(format_duration(-90.0).as_deref(), Some("-1m 30s"));
+        assert_eq!(format_duration(f64::INFINITY), None);
+    }
+
+    #[test]
+    fn test_format_rate() {
+        assert_eq!(
+            format_rate(42.0 / 12_600.0, None).as_deref(),
+            Some("12 per hour")
+        );
+        assert_eq!(
+            format_rate(1.0 / 7200.0, Some("km")).as_deref(),
+            Some("0.5 km per hour")
+        );
+        let options = TokenOptions::default();
+        assert_eq!(rate_unit("42 km / 3h30m", &options), Some("km"));
+        assert_eq!(rate_unit("(42 km + 1 mi) / 3h", &options), None);
+        assert_eq!(rate_unit("42 / 3h", &options), None);
+    }
+
+    #[test]
+    fn test_time_power() {
+        let mut context = EvalContext::new();
+        assert_eq!(time_power("1h30m", &context), 1);
+        assert_eq!(time_power("(3h20m) / 5", &context), 1);
+        assert_eq!(time_power("42 / 3h30m", &context), -1);
+        assert_eq!(time_power("1h / 30m", &context), 0);
+        assert_eq!(time_power("2 * 3", &context), 0);
+        assert_eq!(time_power("abs(-1h) + 5", &context), 1);
+
+        context.set_time_power("lap", 1);
+        assert_eq!(time_power("lap * 3", &context), 1);
+        assert_eq!(time_power("10 / lap", &context), -1);
+    }
+}
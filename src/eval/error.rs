@@ -20,6 +20,99 @@ impl ErrorSpan {
     }
 }
 
+/// How serious an evaluation problem is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Severity {
+    /// The expression has no result.
+    #[default]
+    Error,
+    /// The result is shown, but may not be what was meant, e.g. after an
+    /// overflow or a loss of precision.
+    Warning,
+}
+
+/// Stable code identifying the kind of an evaluation problem, so that
+/// errors can be searched for and documented independently of their wording.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// Any error without a more specific code.
+    #[default]
+    Other,
+    /// A variable is used before it is assigned.
+    UndefinedVariable,
+    /// A function is called with the wrong number of arguments.
+    ArgumentCount,
+    /// A function name is not known.
+    UnknownFunction,
+    /// The expression cannot be tokenized or parsed.
+    Syntax,
+    /// A unit name is unknown or ambiguous.
+    UnknownUnit,
+    /// A conversion between units of different dimensions.
+    IncompatibleUnits,
+    /// An assignment to a constant, or an attempt to unset one.
+    ReassignedConstant,
+    /// A line or expression is larger than the limits allow.
+    Limit,
+    /// An argument is outside the range its function accepts, such as a
+    /// negative factorial.
+    Domain,
+    /// An argument is of the wrong kind, such as text where a number is
+    /// expected.
+    InvalidArgument,
+    /// A CSV file or pasted column cannot be read.
+    Data,
+    /// A list is used where a single number is expected.
+    List,
+    /// A statement is misplaced or malformed, such as `@end` without
+    /// `@section`.
+    Statement,
+    /// A shell command is off or failed.
+    Shell,
+    /// A script line failed.
+    Script,
+    /// Any warning without a more specific code.
+    Warning,
+    /// The result overflowed, lost precision or is not a number.
+    Precision,
+    /// The result uses a what-if override.
+    Override,
+}
+
+impl ErrorCode {
+    /// Returns the code as shown to the user, such as `E001`.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Other => "E000",
+            Self::UndefinedVariable => "E001",
+            Self::ArgumentCount => "E002",
+            Self::UnknownFunction => "E003",
+            Self::Syntax => "E004",
+            Self::UnknownUnit => "E005",
+            Self::IncompatibleUnits => "E006",
+            Self::ReassignedConstant => "E007",
+            Self::Limit => "E008",
+            Self::Domain => "E009",
+            Self::InvalidArgument => "E010",
+            Self::Data => "E011",
+            Self::List => "E012",
+            Self::Statement => "E013",
+            Self::Shell => "E014",
+            Self::Script => "E015",
+            Self::Warning => "W000",
+            Self::Precision => "W001",
+            Self::Override => "W002",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Error returned from expression evaluation.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EvalError {
@@ -27,6 +120,10 @@ pub struct EvalError {
     message: String,
     /// Optional span indicating where the error occurred.
     span: Option<ErrorSpan>,
+    /// Whether the problem is fatal or only a warning.
+    severity: Severity,
+    /// Stable code of the kind of problem.
+    code: ErrorCode,
 }
 
 impl EvalError {
@@ -36,6 +133,19 @@ impl EvalError {
         Self {
             message: message.into(),
             span: None,
+            severity: Severity::Error,
+            code: ErrorCode::Other,
+        }
+    }
+
+    /// Creates a non-fatal problem with a message, which is shown alongside
+    /// the result.
+    #[must_use]
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            code: ErrorCode::Warning,
+            ..Self::new(message)
         }
     }
 
@@ -43,30 +153,34 @@ impl EvalError {
     #[must_use]
     pub fn with_span(message: impl Into<String>, span: ErrorSpan) -> Self {
         Self {
-            message: message.into(),
             span: Some(span),
+            ..Self::new(message)
         }
     }
 
     /// Creates a new evaluation error with a message and span from start/end positions.
     #[must_use]
     pub fn with_span_range(message: impl Into<String>, start: usize, end: usize) -> Self {
-        Self {
-            message: message.into(),
-            span: Some(ErrorSpan::new(start, end)),
-        }
+        Self::with_span(message, ErrorSpan::new(start, end))
+    }
+
+    /// Replaces the code of the error.
+    #[must_use]
+    pub const fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = code;
+        self
     }
 
     /// Creates an error for an undefined variable.
     #[must_use]
     pub fn undefined_variable(name: &str) -> Self {
-        Self::new(format!("undefined variable '{name}'"))
+        Self::new(format!("undefined variable '{name}'")).with_code(ErrorCode::UndefinedVariable)
     }
 
     /// Creates an error for an unknown function.
     #[must_use]
     pub fn unknown_function(name: &str) -> Self {
-        Self::new(format!("unknown function '{name}'"))
+        Self::new(format!("unknown function '{name}'")).with_code(ErrorCode::UnknownFunction)
     }
 
     /// Creates an error for invalid argument count.
@@ -75,6 +189,7 @@ impl EvalError {
         Self::new(format!(
             "function '{name}' expects {expected} argument(s), got {got}"
         ))
+        .with_code(ErrorCode::ArgumentCount)
     }
 
     /// Returns the error message.
@@ -88,6 +203,24 @@ impl EvalError {
     pub const fn span(&self) -> Option<ErrorSpan> {
         self.span
     }
+
+    /// Returns how serious the problem is.
+    #[must_use]
+    pub const fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Returns the stable code of the problem.
+    #[must_use]
+    pub const fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    /// Returns true if the problem is only a warning.
+    #[must_use]
+    pub const fn is_warning(&self) -> bool {
+        matches!(self.severity, Severity::Warning)
+    }
 }
 
 impl fmt::Display for EvalError {
@@ -100,17 +233,18 @@ impl std::error::Error for EvalError {}
 
 impl From<crate::eval::token::TokenError> for EvalError {
     fn from(err: crate::eval::token::TokenError) -> Self {
-        // Create a span of length 1 at the error position
-        Self::with_span_range(err.message, err.position, err.position + 1)
+        // Create a span covering the character at the error position
+        Self::with_span_range(err.message, err.position, err.end).with_code(ErrorCode::Syntax)
     }
 }
 
 impl From<crate::eval::ast::ParseError> for EvalError {
     fn from(err: crate::eval::ast::ParseError) -> Self {
-        match err.span {
+        let error = match err.span {
             Some((start, end)) => Self::with_span_range(err.message, start, end),
             None => Self::new(err.message),
-        }
+        };
+        error.with_code(err.code)
     }
 }
 
@@ -147,6 +281,38 @@ mod tests {
         assert_eq!(error.span(), Some(ErrorSpan::new(3, 7)));
     }
 
+    #[test]
+    fn test_eval_error_severity() {
+        let error = EvalError::with_span_range("syntax error", 3, 7);
+        let warning = EvalError::warning("precision loss");
+
+        assert_eq!(error.severity(), Severity::Error);
+        assert!(!error.is_warning());
+        assert_eq!(warning.severity(), Severity::Warning);
+        assert!(warning.is_warning());
+        assert_eq!(warning.message(), "precision loss");
+        assert!(warning.span().is_none());
+    }
+
+    #[test]
+    fn test_eval_error_codes() {
+        assert_eq!(EvalError::new("oops").code(), ErrorCode::Other);
+        assert_eq!(EvalError::warning("rounded").code(), ErrorCode::Warning);
+        assert_eq!(EvalError::undefined_variable("x").code().as_str(), "E001");
+        assert_eq!(
+            EvalError::invalid_argument_count("sin", 1, 2)
+                .code()
+                .as_str(),
+            "E002"
+        );
+        assert_eq!(EvalError::unknown_function("f").code().as_str(), "E003");
+        assert_eq!(ErrorCode::Domain.as_str(), "E009");
+        assert_eq!(ErrorCode::Script.as_str(), "E015");
+        let error = EvalError::new("rounded").with_code(ErrorCode::Precision);
+        assert_eq!(format!("{}", error.code()), "W001");
+        assert_eq!(format!("{error}"), "rounded");
+    }
+
     #[test]
     fn test_eval_error_display() {
         let error = EvalError::new("division by zero");
@@ -184,6 +350,7 @@ mod tests {
         let eval_error: EvalError = token_error.into();
         assert_eq!(eval_error.message(), "invalid character");
         assert_eq!(eval_error.span(), Some(ErrorSpan::new(5, 6)));
+        assert_eq!(eval_error.code(), ErrorCode::Syntax);
     }
 
     #[test]
@@ -2,17 +2,106 @@
 //!
 //! This module provides the `evaluate` function that evaluates an AST
 //! against a variable context, returning a numeric result or an error.
+//!
+//! Intermediate values are integers while they stay integral, so that
+//! `2^62 + 1 - 2^62` is exactly 1. An integer operation that overflows `i64`
+//! or has a fractional result promotes to `f64`. The result is returned as
+//! `f64`, together with the exact integer when the `f64` cannot hold it, so
+//! that a variable assigned `2^60 + 1` keeps its value for later lines.
 
 use std::collections::HashMap;
 use std::hash::BuildHasher;
+use std::rc::Rc;
 
 use crate::eval::ast::{BinaryOp, Expr};
-use crate::eval::error::EvalError;
+use crate::eval::context::EvalContext;
+use crate::eval::csv::{CSV_FUNCTION, column_values};
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::functions::{Argument, lookup_function};
+use crate::eval::limits::Limits;
+use crate::eval::list::{
+    LIST_FUNCTION, is_list_function, list_variable_error, numeric_list_function,
+};
 
 /// Maximum input value for the factorial operator.
 /// 170! is the largest factorial that fits in an f64 without overflowing to infinity.
 const MAX_FACTORIAL_INPUT: f64 = 170.0;
 
+/// Largest magnitude below which every integer is exactly representable in an f64.
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// 2^63, the smallest magnitude outside the `i64` range.
+const I64_RANGE: f64 = 9_223_372_036_854_775_808.0;
+
+/// Warning for an integer operation that left the `i64` range.
+const INTEGER_OVERFLOW_WARNING: &str = "integer overflow: result exceeds the 64-bit range";
+
+/// Warning for a finite computation that overflowed to infinity.
+const FLOAT_OVERFLOW_WARNING: &str = "overflow: result is too large to represent";
+
+/// Warning for an addition or subtraction that had no effect.
+const ABSORPTION_WARNING: &str = "precision loss: the smaller operand has no effect";
+
+/// Warning for a result that is not a number, such as `sqrt(-1)`.
+const NAN_WARNING: &str = "result is not a number";
+
+/// Warning for an integer result that cannot be displayed exactly.
+const INEXACT_RESULT_WARNING: &str = "precision loss: result is too large to display exactly";
+
+/// Functions whose result is an angle, converted to degrees in degree mode.
+const INVERSE_TRIG_FUNCTIONS: &[&str] = &["asin", "acos", "atan", "atan2"];
+
+/// The unit of angles taken by `sin`, `cos` and `tan` and returned by their
+/// inverses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AngleMode {
+    #[default]
+    Radians,
+    Degrees,
+}
+
+/// What `/` does with two whole numbers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DivisionMode {
+    /// True division: `7 / 2` is 3.5.
+    #[default]
+    True,
+    /// Floor division, like `//`: `7 / 2` is 3.
+    Floor,
+}
+
+/// An intermediate value: an exact integer or a float.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+impl Number {
+    /// Converts an f64 to an integer when it is integral and exactly representable.
+    #[allow(clippy::cast_possible_truncation)]
+    fn from_f64(value: f64) -> Self {
+        if value.fract() == 0.0 && value.abs() <= MAX_EXACT_INTEGER {
+            Self::Int(value as i64)
+        } else {
+            Self::Float(value)
+        }
+    }
+
+    /// Converts an integer result back to a number, promoting to f64 on overflow.
+    fn checked(result: Option<i64>, fallback: impl FnOnce() -> f64) -> Self {
+        result.map_or_else(|| Self::Float(fallback()), Self::Int)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    const fn to_f64(self) -> f64 {
+        match self {
+            Self::Int(n) => n as f64,
+            Self::Float(x) => x,
+        }
+    }
+}
+
 /// Evaluates an expression AST with the given variable bindings.
 ///
 /// # Arguments
@@ -31,65 +120,462 @@ pub fn evaluate<S: BuildHasher>(
     expr: &Expr,
     variables: &HashMap<String, f64, S>,
 ) -> Result<f64, EvalError> {
+    evaluate_in_mode(expr, variables, AngleMode::Radians)
+}
+
+/// Like `evaluate`, with trigonometric functions using `angle_mode`.
+///
+/// # Errors
+/// Returns an `EvalError` under the same conditions as `evaluate`.
+pub fn evaluate_in_mode<S: BuildHasher>(
+    expr: &Expr,
+    variables: &HashMap<String, f64, S>,
+    angle_mode: AngleMode,
+) -> Result<f64, EvalError> {
+    evaluate_with_warning(expr, variables, angle_mode).map(|(value, _)| value)
+}
+
+/// Like `evaluate_in_mode`, but also returns a warning when the computation
+/// overflowed or lost precision, e.g. `2^1024` or `1e20 + 1`, or has no
+/// numeric result, e.g. `sqrt(-1)`.
+///
+/// # Errors
+/// Returns an `EvalError` under the same conditions as `evaluate`.
+pub fn evaluate_with_warning<S: BuildHasher>(
+    expr: &Expr,
+    variables: &HashMap<String, f64, S>,
+    angle_mode: AngleMode,
+) -> Result<(f64, Option<&'static str>), EvalError> {
+    evaluate_with_state(
+        expr,
+        variables,
+        Evaluation {
+            angle_mode,
+            division: DivisionMode::True,
+            lists: None,
+            integers: None,
+            list_limit: Limits::default().list,
+            warning: None,
+        },
+    )
+    .map(|(number, warning)| (number.to_f64(), inexact_warning(number, warning)))
+}
+
+/// Like `evaluate_with_warning`, with the variables, list variables and
+/// angle mode of `context`.
+///
+/// # Errors
+/// Returns an `EvalError` under the same conditions as `evaluate`, or if a
+/// list variable is used as a number.
+pub fn evaluate_in_context(
+    expr: &Expr,
+    context: &EvalContext,
+) -> Result<(f64, Option<&'static str>), EvalError> {
+    evaluate_exact_in_context(expr, context).map(|(value, exact, warning)| {
+        let warning = if exact.is_some() {
+            warning.or(Some(INEXACT_RESULT_WARNING))
+        } else {
+            warning
+        };
+        (value, warning)
+    })
+}
+
+/// Like `evaluate_in_context`, but returns the exact integer result when
+/// the `f64` value cannot hold it, such as for `2^60 + 1`, instead of a
+/// precision warning.
+///
+/// # Errors
+/// Returns an `EvalError` under the same conditions as `evaluate_in_context`.
+pub fn evaluate_exact_in_context(
+    expr: &Expr,
+    context: &EvalContext,
+) -> Result<(f64, Option<i64>, Option<&'static str>), EvalError> {
+    let (number, warning) = evaluate_with_state(
+        expr,
+        context.variables(),
+        Evaluation {
+            angle_mode: context.angle_mode(),
+            division: context.division_mode(),
+            lists: Some(context.lists()),
+            integers: Some(context.integers()),
+            list_limit: context.limits().list,
+            warning: None,
+        },
+    )?;
+    let value = number.to_f64();
+    let exact = match number {
+        Number::Int(n) if !is_exact(n, value) => Some(n),
+        _ => None,
+    };
+    Ok((value, exact, warning))
+}
+
+/// Returns the values of `expr` if it is a list: a `list` or `csv` call, or
+/// a list variable of `context`. The result is an error if the values
+/// cannot be evaluated.
+#[must_use]
+pub fn evaluate_list(expr: &Expr, context: &EvalContext) -> Option<Result<Vec<f64>, EvalError>> {
+    let is_list = match expr {
+        Expr::FunctionCall { name, .. } => is_list_function(name),
+        Expr::Variable(name) => {
+            context.get_variable(name).is_none() && context.lists().contains_key(name)
+        }
+        _ => false,
+    };
+    if !is_list {
+        return None;
+    }
+    let mut state = Evaluation {
+        angle_mode: context.angle_mode(),
+        division: context.division_mode(),
+        lists: Some(context.lists()),
+        integers: Some(context.integers()),
+        list_limit: context.limits().list,
+        warning: None,
+    };
+    Some(
+        evaluate_arguments(std::slice::from_ref(expr), context.variables(), &mut state)
+            .and_then(|args| list_numbers(LIST_FUNCTION, args)),
+    )
+}
+
+/// Evaluates `expr` with `state` and checks the result.
+fn evaluate_with_state<S: BuildHasher>(
+    expr: &Expr,
+    variables: &HashMap<String, f64, S>,
+    mut state: Evaluation,
+) -> Result<(Number, Option<&'static str>), EvalError> {
+    let number = evaluate_number(expr, variables, &mut state)?;
+    if number.to_f64().is_nan() {
+        state.warning.get_or_insert(NAN_WARNING);
+    }
+    Ok((number, state.warning))
+}
+
+/// Returns true when `value`, the `f64` of `n`, is exactly `n`.
+#[allow(clippy::cast_possible_truncation)]
+fn is_exact(n: i64, value: f64) -> bool {
+    i128::from(n) == value as i128
+}
+
+/// Adds the precision warning to `warning` when `number` is an integer its
+/// `f64` cannot hold.
+fn inexact_warning(number: Number, warning: Option<&'static str>) -> Option<&'static str> {
+    match number {
+        Number::Int(n) if !is_exact(n, number.to_f64()) => warning.or(Some(INEXACT_RESULT_WARNING)),
+        _ => warning,
+    }
+}
+
+/// Settings and findings carried through one evaluation.
+struct Evaluation<'a> {
+    angle_mode: AngleMode,
+    division: DivisionMode,
+    /// The list variables, spread into the arguments of function calls.
+    lists: Option<&'a HashMap<String, Rc<[f64]>>>,
+    /// The exact values of variables holding integers their `f64` cannot.
+    integers: Option<&'a HashMap<String, i64>>,
+    /// The most values a `csv` column or a `split` may have.
+    list_limit: usize,
+    /// The first overflow or precision loss, if any.
+    warning: Option<&'static str>,
+}
+
+/// Evaluates an expression, keeping integral intermediate values exact and
+/// recording the first overflow or precision loss in `state`.
+fn evaluate_number<S: BuildHasher>(
+    expr: &Expr,
+    variables: &HashMap<String, f64, S>,
+    state: &mut Evaluation<'_>,
+) -> Result<Number, EvalError> {
     match expr {
-        Expr::Number(n) => Ok(*n),
+        Expr::Number(n) => Ok(Number::from_f64(*n)),
         Expr::Variable(name) => variables
             .get(name)
-            .copied()
-            .ok_or_else(|| EvalError::undefined_variable(name)),
+            .map(|value| {
+                state
+                    .integer(name, *value)
+                    .map_or_else(|| Number::from_f64(*value), Number::Int)
+            })
+            .ok_or_else(|| {
+                if state.list(name).is_some() {
+                    list_variable_error(name)
+                } else {
+                    EvalError::undefined_variable(name)
+                }
+            }),
         Expr::BinaryOp { left, op, right } => {
-            let left_val = evaluate(left, variables)?;
-            let right_val = evaluate(right, variables)?;
-            Ok(apply_binary_op(left_val, op, right_val))
+            let left_val = evaluate_number(left, variables, state)?;
+            let right_val = evaluate_number(right, variables, state)?;
+            let op = match (op, state.division, left_val, right_val) {
+                (BinaryOp::Div, DivisionMode::Floor, Number::Int(_), Number::Int(_)) => {
+                    &BinaryOp::FloorDiv
+                }
+                _ => op,
+            };
+            Ok(apply_binary_op(left_val, op, right_val, &mut state.warning))
         }
-        Expr::UnaryMinus(inner) => {
-            let val = evaluate(inner, variables)?;
-            Ok(-val)
+        Expr::UnaryMinus(inner) => Ok(match evaluate_number(inner, variables, state)? {
+            Number::Int(n) => Number::checked(n.checked_neg(), || -Number::Int(n).to_f64()),
+            Number::Float(x) => Number::Float(-x),
+        }),
+        Expr::Factorial(inner) => factorial(evaluate_number(inner, variables, state)?.to_f64()),
+        Expr::FunctionCall { name, args } => {
+            if let Some(function) = lookup_function(name)
+                && function.takes_expressions()
+            {
+                let variables: HashMap<String, f64> = variables
+                    .iter()
+                    .map(|(name, value)| (name.clone(), *value))
+                    .collect();
+                return function
+                    .call_unevaluated(args, &variables, state.angle_mode)
+                    .map(Number::from_f64);
+            }
+            let mut evaluated_args = evaluate_arguments(args, variables, state)?;
+            if state.angle_mode == AngleMode::Degrees
+                && let Some(exact) = degrees_to_radians(name, &mut evaluated_args)
+            {
+                return Ok(Number::from_f64(exact));
+            }
+            let mut result = call_builtin(name, &evaluated_args)?;
+            if state.angle_mode == AngleMode::Degrees
+                && INVERSE_TRIG_FUNCTIONS.contains(&name.as_str())
+            {
+                result = result.to_degrees();
+            }
+            let finite_args = evaluated_args.iter().all(|arg| match arg {
+                Argument::Number(value) => value.is_finite(),
+                Argument::Text(_) => true,
+            });
+            if result.is_infinite() && finite_args {
+                state.warning.get_or_insert(FLOAT_OVERFLOW_WARNING);
+            }
+            Ok(Number::from_f64(result))
         }
-        Expr::Factorial(inner) => {
-            let val = evaluate(inner, variables)?;
-            if val < 0.0 {
-                return Err(EvalError::new("factorial requires a non-negative integer"));
+        Expr::Text(text) => Err(EvalError::new(format!(
+            "text \"{text}\" can only be used as a function argument"
+        ))
+        .with_code(ErrorCode::InvalidArgument)),
+        Expr::Equation { .. } => Err(EvalError::new(
+            "equations can only be used inside solve() or root()",
+        )
+        .with_code(ErrorCode::Statement)),
+    }
+}
+
+impl Evaluation<'_> {
+    /// Returns the values of the list variable `name`, if there is one.
+    fn list(&self, name: &str) -> Option<Rc<[f64]>> {
+        self.lists.and_then(|lists| lists.get(name)).cloned()
+    }
+
+    /// Returns the exact integer held by the variable `name`, if its `f64`
+    /// `value` is the rounding of one.
+    #[allow(clippy::cast_precision_loss, clippy::float_cmp)]
+    fn integer(&self, name: &str, value: f64) -> Option<i64> {
+        self.integers
+            .and_then(|integers| integers.get(name))
+            .copied()
+            .filter(|&n| n as f64 == value)
+    }
+}
+
+/// Computes `val!`, exactly while the result fits an integer.
+fn factorial(val: f64) -> Result<Number, EvalError> {
+    if val < 0.0 {
+        return Err(EvalError::new("factorial requires a non-negative integer")
+            .with_code(ErrorCode::Domain));
+    }
+    if val.fract() != 0.0 {
+        return Err(
+            EvalError::new("factorial requires an integer argument").with_code(ErrorCode::Domain)
+        );
+    }
+    if val > MAX_FACTORIAL_INPUT {
+        return Err(
+            EvalError::new("factorial overflow: argument must be <= 170")
+                .with_code(ErrorCode::Domain),
+        );
+    }
+    #[allow(
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss
+    )]
+    let n = val as i64;
+    #[allow(clippy::cast_precision_loss)]
+    let result = (1..=n).try_fold(1i64, i64::checked_mul).map_or_else(
+        || Number::Float((1..=n).fold(1.0f64, |acc, i| acc * i as f64)),
+        Number::Int,
+    );
+    Ok(result)
+}
+
+/// Returns the numbers of the evaluated arguments of a `list` call.
+fn list_numbers(name: &str, args: Vec<Argument>) -> Result<Vec<f64>, EvalError> {
+    args.into_iter()
+        .map(|arg| match arg {
+            Argument::Number(value) => Ok(value),
+            Argument::Text(text) => Err(EvalError::new(format!(
+                "{name} expects numbers, got \"{text}\""
+            ))
+            .with_code(ErrorCode::InvalidArgument)),
+        })
+        .collect()
+}
+
+/// Evaluates the arguments of a function call, spreading the values of a
+/// list into separate arguments.
+fn evaluate_arguments<S: BuildHasher>(
+    args: &[Expr],
+    variables: &HashMap<String, f64, S>,
+    state: &mut Evaluation<'_>,
+) -> Result<Vec<Argument>, EvalError> {
+    let mut evaluated = Vec::with_capacity(args.len());
+    for arg in args {
+        match arg {
+            Expr::Text(text) => evaluated.push(Argument::Text(text.clone())),
+            Expr::FunctionCall { name, args } if name == CSV_FUNCTION => {
+                let args = evaluate_arguments(args, variables, state)?;
+                let values = column_values(&args, state.list_limit)?;
+                evaluated.extend(values.iter().copied().map(Argument::Number));
             }
-            if val.fract() != 0.0 {
-                return Err(EvalError::new("factorial requires an integer argument"));
+            Expr::FunctionCall { name, args }
+                if let Some(function) = numeric_list_function(name) =>
+            {
+                let numbers = list_numbers(name, evaluate_arguments(args, variables, state)?)?;
+                if let Some(info) = lookup_function(name) {
+                    info.check_argument_count(numbers.len())?;
+                }
+                let values = function(&numbers, state.list_limit)?;
+                evaluated.extend(values.into_iter().map(Argument::Number));
             }
-            if val > MAX_FACTORIAL_INPUT {
-                return Err(EvalError::new(
-                    "factorial overflow: argument must be <= 170",
-                ));
+            Expr::FunctionCall { name, args } if name == LIST_FUNCTION => {
+                let values = list_numbers(name, evaluate_arguments(args, variables, state)?)?;
+                evaluated.extend(values.into_iter().map(Argument::Number));
             }
-            #[allow(
-                clippy::cast_sign_loss,
-                clippy::cast_possible_truncation,
-                clippy::cast_precision_loss
-            )]
-            let n = val as u64;
-            #[allow(clippy::cast_precision_loss)]
-            let result = (1..=n).fold(1.0f64, |acc, i| acc * i as f64);
-            Ok(result)
+            Expr::Variable(name)
+                if !variables.contains_key(name)
+                    && let Some(values) = state.list(name) =>
+            {
+                evaluated.extend(values.iter().copied().map(Argument::Number));
+            }
+            _ => evaluated.push(Argument::Number(
+                evaluate_number(arg, variables, state)?.to_f64(),
+            )),
         }
-        Expr::FunctionCall { name, args } => {
-            let evaluated_args: Result<Vec<f64>, EvalError> =
-                args.iter().map(|arg| evaluate(arg, variables)).collect();
-            call_builtin(name, &evaluated_args?)
+    }
+    Ok(evaluated)
+}
+
+/// Applies a binary operator, staying exact while both operands are integers.
+///
+/// `/` is true division and only stays an integer when it divides evenly;
+/// `//` is floor division. Division or modulo by zero follows f64 semantics.
+fn apply_binary_op(
+    left: Number,
+    op: &BinaryOp,
+    right: Number,
+    warning: &mut Option<&'static str>,
+) -> Number {
+    let (x, y) = (left.to_f64(), right.to_f64());
+    let float = apply_float_op(x, op, y);
+
+    if let (Number::Int(a), Number::Int(b)) = (left, right) {
+        let exact = match op {
+            BinaryOp::Add => Some(a.checked_add(b)),
+            BinaryOp::Sub => Some(a.checked_sub(b)),
+            BinaryOp::Mul => Some(a.checked_mul(b)),
+            BinaryOp::Div if b != 0 && a.checked_rem(b) == Some(0) => Some(a.checked_div(b)),
+            BinaryOp::FloorDiv if b != 0 => Some(a.checked_div(b).map(|q| {
+                if a % b != 0 && (a < 0) != (b < 0) {
+                    q - 1
+                } else {
+                    q
+                }
+            })),
+            BinaryOp::Mod if b != 0 => Some(a.checked_rem(b)),
+            BinaryOp::Pow if b >= 0 => {
+                Some(u32::try_from(b).ok().and_then(|exp| a.checked_pow(exp)))
+            }
+            _ => None,
+        };
+        match exact {
+            Some(Some(n)) => return Number::Int(n),
+            Some(None) if float.is_finite() && float.abs() >= I64_RANGE => {
+                warning.get_or_insert(INTEGER_OVERFLOW_WARNING);
+            }
+            _ => {}
+        }
+    }
+
+    let divides_by_zero =
+        matches!(op, BinaryOp::Div | BinaryOp::FloorDiv | BinaryOp::Mod) && y == 0.0;
+    if float.is_infinite() && x.is_finite() && y.is_finite() && !divides_by_zero {
+        warning.get_or_insert(FLOAT_OVERFLOW_WARNING);
+    } else if float.is_finite() && absorbs_operand(x, op, y, float) {
+        warning.get_or_insert(ABSORPTION_WARNING);
+    }
+    Number::from_f64(float)
+}
+
+/// Returns true when adding or subtracting a non-zero operand left the other
+/// operand unchanged, as in `1e20 + 1`.
+#[allow(clippy::float_cmp)]
+fn absorbs_operand(x: f64, op: &BinaryOp, y: f64, result: f64) -> bool {
+    let y = match op {
+        BinaryOp::Add => y,
+        BinaryOp::Sub => -y,
+        _ => return false,
+    };
+    (y != 0.0 && result == x) || (x != 0.0 && result == y)
+}
+
+/// Converts the angle argument of `sin`, `cos` and `tan` from degrees to
+/// radians.
+///
+/// Returns the exact result for multiples of 90°, where the radian
+/// computation would leave rounding noise such as `sin(180) = 1.2e-16`.
+fn degrees_to_radians(name: &str, args: &mut [Argument]) -> Option<f64> {
+    let [Argument::Number(angle)] = args else {
+        return None;
+    };
+    if !matches!(name, "sin" | "cos" | "tan") {
+        return None;
+    }
+    if angle.rem_euclid(90.0) == 0.0 {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let quadrant = (*angle / 90.0).rem_euclid(4.0) as u8;
+        let exact = match (name, quadrant) {
+            ("sin" | "tan", 0 | 2) | ("cos", 1 | 3) => Some(0.0),
+            ("sin", 1) | ("cos", 0) => Some(1.0),
+            ("sin", 3) | ("cos", 2) => Some(-1.0),
+            _ => None,
+        };
+        if exact.is_some() {
+            return exact;
         }
     }
+    *angle = angle.to_radians();
+    None
 }
 
 /// Applies a binary operator to two f64 operands.
-fn apply_binary_op(left: f64, op: &BinaryOp, right: f64) -> f64 {
+fn apply_float_op(left: f64, op: &BinaryOp, right: f64) -> f64 {
     match op {
         BinaryOp::Add => left + right,
         BinaryOp::Sub => left - right,
         BinaryOp::Mul => left * right,
         BinaryOp::Div => left / right,
+        BinaryOp::FloorDiv => (left / right).floor(),
         BinaryOp::Mod => left % right,
         BinaryOp::Pow => left.powf(right),
     }
 }
 
-/// Calls a built-in mathematical function.
+/// Calls a built-in mathematical function from the function registry.
 ///
 /// # Arguments
 /// * `name` - The function name
@@ -97,139 +583,10 @@ fn apply_binary_op(left: f64, op: &BinaryOp, right: f64) -> f64 {
 ///
 /// # Returns
 /// The function result, or an error if the function is unknown or has wrong arity.
-fn call_builtin(name: &str, args: &[f64]) -> Result<f64, EvalError> {
-    let arg_count = args.len();
-
-    match name {
-        "sqrt" => expect_args(name, 1, arg_count).map(|()| args[0].sqrt()),
-        "cbrt" => expect_args(name, 1, arg_count).map(|()| args[0].cbrt()),
-        "abs" => expect_args(name, 1, arg_count).map(|()| args[0].abs()),
-
-        "pow" => expect_args(name, 2, arg_count).map(|()| args[0].powf(args[1])),
-
-        "sin" => expect_args(name, 1, arg_count).map(|()| args[0].sin()),
-        "cos" => expect_args(name, 1, arg_count).map(|()| args[0].cos()),
-        "tan" => expect_args(name, 1, arg_count).map(|()| args[0].tan()),
-        "asin" => expect_args(name, 1, arg_count).map(|()| args[0].asin()),
-        "acos" => expect_args(name, 1, arg_count).map(|()| args[0].acos()),
-        "atan" => expect_args(name, 1, arg_count).map(|()| args[0].atan()),
-
-        "atan2" => expect_args(name, 2, arg_count).map(|()| args[0].atan2(args[1])),
-
-        "sinh" => expect_args(name, 1, arg_count).map(|()| args[0].sinh()),
-        "cosh" => expect_args(name, 1, arg_count).map(|()| args[0].cosh()),
-        "tanh" => expect_args(name, 1, arg_count).map(|()| args[0].tanh()),
-        "asinh" => expect_args(name, 1, arg_count).map(|()| args[0].asinh()),
-        "acosh" => expect_args(name, 1, arg_count).map(|()| args[0].acosh()),
-        "atanh" => expect_args(name, 1, arg_count).map(|()| args[0].atanh()),
-
-        "ln" => expect_args(name, 1, arg_count).map(|()| args[0].ln()),
-        "log2" => expect_args(name, 1, arg_count).map(|()| args[0].log2()),
-        "log10" => expect_args(name, 1, arg_count).map(|()| args[0].log10()),
-        "exp" => expect_args(name, 1, arg_count).map(|()| args[0].exp()),
-        "exp2" => expect_args(name, 1, arg_count).map(|()| args[0].exp2()),
-
-        "log" => expect_args(name, 2, arg_count).map(|()| args[0].log(args[1])),
-
-        "floor" => expect_args(name, 1, arg_count).map(|()| args[0].floor()),
-        "ceil" => expect_args(name, 1, arg_count).map(|()| args[0].ceil()),
-        "round" => expect_args(name, 1, arg_count).map(|()| args[0].round()),
-
-        "sgn" => expect_args(name, 1, arg_count).map(|()| {
-            if args[0] == 0.0 {
-                0.0
-            } else {
-                args[0].signum()
-            }
-        }),
-        "trunc" => expect_args(name, 1, arg_count).map(|()| args[0].trunc()),
-        "frac" => expect_args(name, 1, arg_count).map(|()| args[0].fract()),
-
-        "degrees" => expect_args(name, 1, arg_count).map(|()| args[0].to_degrees()),
-        "radians" => expect_args(name, 1, arg_count).map(|()| args[0].to_radians()),
-
-        "cot" => expect_args(name, 1, arg_count).map(|()| 1.0 / args[0].tan()),
-        "sec" => expect_args(name, 1, arg_count).map(|()| 1.0 / args[0].cos()),
-        "csc" => expect_args(name, 1, arg_count).map(|()| 1.0 / args[0].sin()),
-
-        "min" => expect_args(name, 2, arg_count).map(|()| args[0].min(args[1])),
-        "max" => expect_args(name, 2, arg_count).map(|()| args[0].max(args[1])),
-        "hypot" => expect_args(name, 2, arg_count).map(|()| args[0].hypot(args[1])),
-
-        "gcd" => {
-            expect_args(name, 2, arg_count)?;
-            if !args[0].is_finite() || !args[1].is_finite() {
-                return Err(EvalError::new("gcd requires finite arguments"));
-            }
-            Ok(compute_gcd(args[0], args[1]))
-        }
-        "ncr" => {
-            expect_args(name, 2, arg_count)?;
-            compute_ncr(args[0], args[1])
-        }
-        "npr" => {
-            expect_args(name, 2, arg_count)?;
-            compute_npr(args[0], args[1])
-        }
-
-        _ => Err(EvalError::unknown_function(name)),
-    }
-}
-
-/// Helper to check argument count.
-fn expect_args(name: &str, expected: usize, got: usize) -> Result<(), EvalError> {
-    if expected == got {
-        Ok(())
-    } else {
-        Err(EvalError::invalid_argument_count(name, expected, got))
-    }
-}
-
-/// Computes the greatest common divisor using the Euclidean algorithm.
-#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
-const fn compute_gcd(a: f64, b: f64) -> f64 {
-    let mut a = a as i64;
-    let mut b = b as i64;
-    while b != 0 {
-        let t = b;
-        b = a % b;
-        a = t;
-    }
-    a.unsigned_abs() as f64
-}
-
-/// Computes the binomial coefficient C(n, k) = n! / (k! * (n-k)!) iteratively.
-#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
-fn compute_ncr(n: f64, k: f64) -> Result<f64, EvalError> {
-    let n = n.trunc() as i64;
-    let k = k.trunc() as i64;
-    if k < 0 || n < 0 || k > n {
-        return Err(EvalError::new(format!(
-            "ncr requires 0 <= k <= n, got n={n}, k={k}"
-        )));
-    }
-    let mut result: f64 = 1.0;
-    for i in 0..k {
-        result = result * (n - i) as f64 / (i + 1) as f64;
-    }
-    Ok(result)
-}
-
-/// Computes the permutation P(n, k) = n! / (n-k)! iteratively.
-#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
-fn compute_npr(n: f64, k: f64) -> Result<f64, EvalError> {
-    let n = n.trunc() as i64;
-    let k = k.trunc() as i64;
-    if k < 0 || n < 0 || k > n {
-        return Err(EvalError::new(format!(
-            "npr requires 0 <= k <= n, got n={n}, k={k}"
-        )));
-    }
-    let mut result: f64 = 1.0;
-    for i in 0..k {
-        result *= (n - i) as f64;
-    }
-    Ok(result)
+fn call_builtin(name: &str, args: &[Argument]) -> Result<f64, EvalError> {
+    lookup_function(name)
+        .ok_or_else(|| EvalError::unknown_function(name))?
+        .call_with(args)
 }
 
 #[cfg(test)]
@@ -1264,4 +1621,100 @@ mod tests {
         assert!(result.is_ok());
         assert!(result.unwrap().is_finite());
     }
+
+    fn eval_str(input: &str) -> Result<f64, EvalError> {
+        evaluate(
+            &crate::eval::parse_expression(input).unwrap(),
+            &empty_vars(),
+        )
+    }
+
+    #[test]
+    fn test_integer_arithmetic_stays_exact() {
+        assert_eq!(eval_str("2^62 + 1 - 2^62"), Ok(1.0));
+        assert_eq!(eval_str("(2^53 + 1) - 2^53"), Ok(1.0));
+        assert_eq!(eval_str("21! / 20!"), Ok(21.0));
+    }
+
+    #[test]
+    fn test_integer_overflow_promotes_to_float() {
+        assert_eq!(eval_str("2^63"), Ok(9_223_372_036_854_775_808.0));
+        assert_eq!(eval_str("2^62 * 4"), Ok(2f64.powi(64)));
+        assert_eq!(eval_str("2^-1"), Ok(0.5));
+    }
+
+    #[test]
+    fn test_division_promotes_when_inexact() {
+        assert_eq!(eval_str("5 / 2"), Ok(2.5));
+        assert_eq!(eval_str("6 / 3"), Ok(2.0));
+        assert_eq!(eval_str("2.5 * 2 - 5"), Ok(0.0));
+    }
+
+    #[test]
+    fn test_floor_division() {
+        assert_eq!(eval_str("7 // 2"), Ok(3.0));
+        assert_eq!(eval_str("-7 // 2"), Ok(-4.0));
+        assert_eq!(eval_str("7 // -2"), Ok(-4.0));
+        assert_eq!(eval_str("7.5 // 2"), Ok(3.0));
+        assert!(eval_str("1 // 0").unwrap().is_infinite());
+    }
+
+    fn warning_of(input: &str) -> Option<&'static str> {
+        let expr = crate::eval::parse_expression(input).unwrap();
+        evaluate_with_warning(&expr, &empty_vars(), AngleMode::Radians)
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn test_warning_for_overflow() {
+        assert_eq!(warning_of("2^1024"), Some(FLOAT_OVERFLOW_WARNING));
+        assert_eq!(warning_of("exp(1000)"), Some(FLOAT_OVERFLOW_WARNING));
+        assert_eq!(warning_of("2^62 * 4"), Some(INTEGER_OVERFLOW_WARNING));
+        assert_eq!(warning_of("2^63 - 1"), Some(INTEGER_OVERFLOW_WARNING));
+    }
+
+    #[test]
+    fn test_warning_for_precision_loss() {
+        assert_eq!(warning_of("1e20 + 1"), Some(ABSORPTION_WARNING));
+        assert_eq!(warning_of("1 - 1e-17"), Some(ABSORPTION_WARNING));
+        assert_eq!(warning_of("2^62 + 1"), Some(INEXACT_RESULT_WARNING));
+    }
+
+    #[test]
+    fn test_warning_for_nan_result() {
+        assert_eq!(warning_of("sqrt(-1)"), Some(NAN_WARNING));
+        assert_eq!(warning_of("0 / 0"), Some(NAN_WARNING));
+    }
+
+    #[test]
+    fn test_no_warning_for_exact_or_expected_results() {
+        assert_eq!(warning_of("2^62 + 1 - 2^62"), None);
+        assert_eq!(warning_of("0.1 + 0.2"), None);
+        assert_eq!(warning_of("1 / 0"), None);
+        assert_eq!(warning_of("1^(2^40)"), None);
+        assert_eq!(warning_of("0 + 2.5"), None);
+    }
+
+    fn eval_degrees(input: &str) -> Result<f64, EvalError> {
+        let expr = crate::eval::parse_expression(input).unwrap();
+        evaluate_in_mode(&expr, &empty_vars(), AngleMode::Degrees)
+    }
+
+    #[test]
+    fn test_degrees_mode_trigonometry() {
+        assert_eq!(eval_degrees("sin(90)"), Ok(1.0));
+        assert_eq!(eval_degrees("sin(180)"), Ok(0.0));
+        assert_eq!(eval_degrees("cos(-180)"), Ok(-1.0));
+        assert_eq!(eval_degrees("tan(360)"), Ok(0.0));
+        assert!((eval_degrees("sin(30)").unwrap() - 0.5).abs() < 1e-12);
+        assert!(eval_degrees("tan(90)").unwrap().abs() > 1e15);
+    }
+
+    #[test]
+    fn test_degrees_mode_inverse_trigonometry() {
+        assert!((eval_degrees("asin(1)").unwrap() - 90.0).abs() < 1e-12);
+        assert!((eval_degrees("atan2(1, 1)").unwrap() - 45.0).abs() < 1e-12);
+        assert_eq!(eval_degrees("sqrt(16)"), Ok(4.0));
+    }
 }
@@ -0,0 +1,215 @@
+//! Step-by-step evaluation for explain mode.
+//!
+//! Reduces an expression one operation at a time, in the order the evaluator
+//! applies them, and records the expression after each step. This makes
+//! operator precedence visible: `2 + 3 * 4` first becomes `2 + 12`, then `14`.
+
+use crate::eval::ast::Expr;
+use crate::eval::error::EvalError;
+use crate::eval::evaluator;
+use crate::eval::format::{format_ast, format_expression};
+use crate::eval::functions::{FunctionInfo, lookup_function};
+use crate::eval::list::is_list_function;
+use crate::eval::parser::ParsedLine;
+use crate::eval::{EvalContext, parse_expression_in, parse_line_with_presets};
+
+/// Upper bound on reduction steps, guarding against pathological input.
+const MAX_STEPS: usize = 256;
+
+/// The reduction steps for a single line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// The variable name, if the line is an assignment.
+    pub name: Option<String>,
+    /// The expression after each reduction step, starting with the original.
+    pub steps: Vec<String>,
+    /// The error that stopped the reduction, if any.
+    pub error: Option<String>,
+}
+
+/// Explains how a line is evaluated using the given context.
+///
+/// # Returns
+/// `None` if the line is empty or cannot be parsed.
+#[must_use]
+pub fn explain_line(line: &str, context: &EvalContext) -> Option<Explanation> {
+    let (name, expression) = match parse_line_with_presets(line, context.format_presets()) {
+        ParsedLine::Empty
+        | ParsedLine::SectionStart(_)
+        | ParsedLine::SectionEnd
+        | ParsedLine::Total
+        | ParsedLine::Disabled
+        | ParsedLine::MultipleAssignment { .. }
+        | ParsedLine::Unset(_)
+        | ParsedLine::ClearVariables => return None,
+        ParsedLine::Expression(expr) => (None, expr),
+        ParsedLine::Assignment {
+            name, expression, ..
+        } => (Some(name), expression),
+    };
+
+    let mut expr = parse_expression_in(&expression, context).ok()?;
+    let mut steps = vec![format_expression(&expression, context).ok()?];
+    let mut error = None;
+
+    while !matches!(expr, Expr::Number(_)) && steps.len() < MAX_STEPS {
+        match reduce_once(&expr, context) {
+            Ok(next) => {
+                let text = format_ast(&next);
+                if steps.last() != Some(&text) {
+                    steps.push(text);
+                }
+                expr = next;
+            }
+            Err(e) => {
+                error = Some(e.message().to_string());
+                break;
+            }
+        }
+    }
+
+    Some(Explanation { name, steps, error })
+}
+
+/// Performs a single reduction step on the first operation the evaluator would apply.
+///
+/// Children are reduced left to right before their parent, so the step taken
+/// is always the innermost, leftmost operation whose operands are known.
+fn reduce_once(expr: &Expr, context: &EvalContext) -> Result<Expr, EvalError> {
+    match expr {
+        Expr::Number(_) => Ok(expr.clone()),
+        Expr::BinaryOp { left, op, right } => {
+            if !is_number(left) {
+                Ok(Expr::BinaryOp {
+                    left: Box::new(reduce_once(left, context)?),
+                    op: op.clone(),
+                    right: right.clone(),
+                })
+            } else if !is_number(right) {
+                Ok(Expr::BinaryOp {
+                    left: left.clone(),
+                    op: op.clone(),
+                    right: Box::new(reduce_once(right, context)?),
+                })
+            } else {
+                evaluator::evaluate_in_context(expr, context).map(|(value, _)| Expr::Number(value))
+            }
+        }
+        Expr::UnaryMinus(inner) if !is_number(inner) => {
+            Ok(Expr::UnaryMinus(Box::new(reduce_once(inner, context)?)))
+        }
+        Expr::Factorial(inner) if !is_number(inner) => {
+            Ok(Expr::Factorial(Box::new(reduce_once(inner, context)?)))
+        }
+        Expr::Variable(_)
+        | Expr::Text(_)
+        | Expr::Equation { .. }
+        | Expr::UnaryMinus(_)
+        | Expr::Factorial(_) => {
+            evaluator::evaluate_in_context(expr, context).map(|(value, _)| Expr::Number(value))
+        }
+        Expr::FunctionCall { name, args } => {
+            // Functions such as solve() receive their arguments unevaluated,
+            // and a list is spread into the arguments as a whole.
+            let pending = if lookup_function(name).is_some_and(FunctionInfo::takes_expressions) {
+                None
+            } else {
+                args.iter().position(|arg| match arg {
+                    Expr::Number(_) | Expr::Text(_) => false,
+                    Expr::FunctionCall { name, .. } => !is_list_function(name),
+                    Expr::Variable(name) => {
+                        context.get_variable(name).is_some() || !context.lists().contains_key(name)
+                    }
+                    _ => true,
+                })
+            };
+            if let Some(pos) = pending {
+                let mut args = args.clone();
+                args[pos] = reduce_once(&args[pos], context)?;
+                Ok(Expr::FunctionCall {
+                    name: name.clone(),
+                    args,
+                })
+            } else {
+                evaluator::evaluate_in_context(expr, context).map(|(value, _)| Expr::Number(value))
+            }
+        }
+    }
+}
+
+/// Returns `true` if the expression is already a numeric literal.
+const fn is_number(expr: &Expr) -> bool {
+    matches!(expr, Expr::Number(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steps(line: &str) -> Vec<String> {
+        explain_line(line, &EvalContext::new())
+            .expect("should explain")
+            .steps
+    }
+
+    #[test]
+    fn test_explain_respects_precedence() {
+        assert_eq!(steps("2+3*4"), vec!["2 + 3 * 4", "2 + 12", "14"]);
+    }
+
+    #[test]
+    fn test_explain_parentheses_first() {
+        assert_eq!(steps("(2+3)*4"), vec!["(2 + 3) * 4", "5 * 4", "20"]);
+    }
+
+    #[test]
+    fn test_explain_substitutes_variables() {
+        let mut context = EvalContext::new();
+        context.set_variable("a", 5.0);
+
+        let explanation = explain_line("a * 2", &context).expect("should explain");
+
+        assert_eq!(explanation.steps, vec!["a * 2", "5 * 2", "10"]);
+    }
+
+    #[test]
+    fn test_explain_function_arguments_then_call() {
+        assert_eq!(
+            steps("sqrt(3^2+4^2)"),
+            vec![
+                "sqrt(3 ^ 2 + 4 ^ 2)",
+                "sqrt(9 + 4 ^ 2)",
+                "sqrt(9 + 16)",
+                "sqrt(25)",
+                "5"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_explain_assignment_keeps_name() {
+        let explanation = explain_line("x = 1 + 1", &EvalContext::new()).expect("should explain");
+
+        assert_eq!(explanation.name.as_deref(), Some("x"));
+        assert_eq!(explanation.steps, vec!["1 + 1", "2"]);
+    }
+
+    #[test]
+    fn test_explain_stops_at_error() {
+        let explanation = explain_line("1 + y", &EvalContext::new()).expect("should explain");
+
+        assert_eq!(explanation.steps, vec!["1 + y"]);
+        assert_eq!(explanation.error.as_deref(), Some("undefined variable 'y'"));
+    }
+
+    #[test]
+    fn test_explain_single_number_has_one_step() {
+        assert_eq!(steps("42"), vec!["42"]);
+    }
+
+    #[test]
+    fn test_explain_unparseable_returns_none() {
+        assert!(explain_line("1 +", &EvalContext::new()).is_none());
+        assert!(explain_line("", &EvalContext::new()).is_none());
+    }
+}
@@ -0,0 +1,253 @@
+//! Financial functions for budget and loan worksheets.
+//!
+//! Follows spreadsheet conventions: payments are made at the end of each
+//! period, and money paid out is negative while money received is positive.
+//! A loan of 1000 (`pv = 1000`) therefore has a negative payment.
+//!
+//! Also provides helpers for dividing amounts: `split` shares a bill evenly
+//! to the cent, and `ratio` allocates a total proportionally.
+
+use crate::eval::error::{ErrorCode, EvalError};
+
+/// Maximum Newton iterations for `irr`.
+const IRR_MAX_ITERATIONS: usize = 100;
+
+/// Convergence tolerance for `irr`.
+const IRR_TOLERANCE: f64 = 1e-10;
+
+/// Returns `(1 + rate)^periods`.
+fn growth(rate: f64, periods: f64) -> f64 {
+    (1.0 + rate).powf(periods)
+}
+
+/// `fv(rate, n, pmt, [pv])`: value after `n` periods of payment `pmt` on a
+/// starting balance `pv`.
+pub(crate) fn future_value(args: &[f64]) -> f64 {
+    let (rate, periods, payment) = (args[0], args[1], args[2]);
+    let present = args.get(3).copied().unwrap_or(0.0);
+    if rate == 0.0 {
+        return -payment.mul_add(periods, present);
+    }
+    let growth = growth(rate, periods);
+    -present.mul_add(growth, payment * (growth - 1.0) / rate)
+}
+
+/// `pv(rate, n, pmt, [fv])`: value today of `n` payments of `pmt` plus a
+/// final amount `fv`.
+pub(crate) fn present_value(args: &[f64]) -> f64 {
+    let (rate, periods, payment) = (args[0], args[1], args[2]);
+    let future = args.get(3).copied().unwrap_or(0.0);
+    if rate == 0.0 {
+        return -payment.mul_add(periods, future);
+    }
+    let growth = growth(rate, periods);
+    -(payment * (growth - 1.0) / rate + future) / growth
+}
+
+/// `pmt(rate, n, pv, [fv])`: payment per period that turns `pv` into `fv`
+/// over `n` periods, e.g. a loan repayment.
+pub(crate) fn payment(args: &[f64]) -> Result<f64, EvalError> {
+    let (rate, periods, present) = (args[0], args[1], args[2]);
+    let future = args.get(3).copied().unwrap_or(0.0);
+    if periods == 0.0 {
+        return Err(EvalError::new("pmt requires a non-zero number of periods")
+            .with_code(ErrorCode::Domain));
+    }
+    if rate == 0.0 {
+        return Ok(-(present + future) / periods);
+    }
+    let growth = growth(rate, periods);
+    Ok(-rate * present.mul_add(growth, future) / (growth - 1.0))
+}
+
+/// `npv(rate, cf1, cf2, ...)`: present value of cash flows at the end of
+/// periods 1, 2, ...
+pub(crate) fn net_present_value(args: &[f64]) -> f64 {
+    let rate = args[0];
+    discount(&args[1..], rate, 1)
+}
+
+/// `irr(cf0, cf1, ...)`: the rate at which cash flows starting at period 0
+/// have a net present value of zero.
+pub(crate) fn internal_rate_of_return(args: &[f64]) -> Result<f64, EvalError> {
+    if !(args.iter().any(|&cf| cf > 0.0) && args.iter().any(|&cf| cf < 0.0)) {
+        return Err(
+            EvalError::new("irr requires both positive and negative cash flows")
+                .with_code(ErrorCode::Domain),
+        );
+    }
+
+    let mut rate = 0.1;
+    for _ in 0..IRR_MAX_ITERATIONS {
+        let value = discount(args, rate, 0);
+        let slope = discount_slope(args, rate);
+        if slope == 0.0 || !slope.is_finite() {
+            break;
+        }
+        let next = rate - value / slope;
+        if !next.is_finite() || next <= -1.0 {
+            break;
+        }
+        if (next - rate).abs() < IRR_TOLERANCE {
+            return Ok(next);
+        }
+        rate = next;
+    }
+    Err(EvalError::new("irr did not converge").with_code(ErrorCode::Domain))
+}
+
+/// `split(total, n)`: the `n` shares of `total` in whole cents, as a list
+/// of at most `max_shares` values.
+///
+/// Shares differ by at most one cent and always add up to `total`; the
+/// leftover cents go to the first shares.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+pub(crate) fn split(args: &[f64], max_shares: usize) -> Result<Vec<f64>, EvalError> {
+    let (total, count) = (args[0], args[1]);
+    if count < 1.0 || count.fract() != 0.0 {
+        return Err(
+            EvalError::new("split requires a positive whole number of shares")
+                .with_code(ErrorCode::Domain),
+        );
+    }
+    if count > max_shares as f64 {
+        return Err(EvalError::new(format!(
+            "split gives more than {max_shares} shares (see maxlist)"
+        ))
+        .with_code(ErrorCode::Limit));
+    }
+
+    let cents = (total.abs() * 100.0).round() as i64;
+    let count = count as i64;
+    Ok((0..count)
+        .map(|index| {
+            let extra = i64::from(index < cents % count);
+            ((cents / count + extra) as f64 / 100.0).copysign(total)
+        })
+        .collect())
+}
+
+/// `ratio(a, b, total)`: the parts of `total` allocated to `a` and to `b`
+/// when it is divided in the ratio `a : b`.
+pub(crate) fn ratio(args: &[f64]) -> Result<[f64; 2], EvalError> {
+    let (a, b, total) = (args[0], args[1], args[2]);
+    if a + b == 0.0 {
+        return Err(
+            EvalError::new("ratio requires a non-zero sum of parts").with_code(ErrorCode::Domain)
+        );
+    }
+    Ok([total * a / (a + b), total * b / (a + b)])
+}
+
+/// Sums `cash_flows` discounted at `rate`, the first flow at period `first`.
+fn discount(cash_flows: &[f64], rate: f64, first: i32) -> f64 {
+    (first..)
+        .zip(cash_flows)
+        .map(|(period, cf)| cf / (1.0 + rate).powi(period))
+        .sum()
+}
+
+/// Derivative of `discount(cash_flows, rate, 0)` with respect to `rate`.
+fn discount_slope(cash_flows: &[f64], rate: f64) -> f64 {
+    (0..)
+        .zip(cash_flows)
+        .map(|(period, cf)| -f64::from(period) * cf / (1.0 + rate).powi(period + 1))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_future_value_compound_interest() {
+        assert_close(future_value(&[0.05, 10.0, 0.0, -1000.0]), 1_628.894_627);
+        assert_close(future_value(&[0.0, 12.0, -100.0]), 1200.0);
+    }
+
+    #[test]
+    fn test_present_value_of_annuity() {
+        assert_close(present_value(&[0.05, 10.0, -100.0]), 772.173_493);
+        assert_close(present_value(&[0.0, 10.0, -100.0, -50.0]), 1050.0);
+    }
+
+    #[test]
+    fn test_payment_for_loan() {
+        assert_close(
+            payment(&[0.05 / 12.0, 360.0, 200_000.0]).unwrap(),
+            -1_073.643_246,
+        );
+        assert_close(payment(&[0.0, 10.0, 1000.0]).unwrap(), -100.0);
+        assert!(payment(&[0.05, 0.0, 1000.0]).is_err());
+    }
+
+    #[test]
+    fn test_payment_and_present_value_round_trip() {
+        let pmt = payment(&[0.01, 24.0, 5000.0]).unwrap();
+        assert_close(present_value(&[0.01, 24.0, pmt]), 5000.0);
+    }
+
+    #[test]
+    fn test_net_present_value() {
+        assert_close(
+            net_present_value(&[0.1, -10_000.0, 3000.0, 4200.0, 6800.0]),
+            1_188.443_412,
+        );
+    }
+
+    #[test]
+    fn test_internal_rate_of_return() {
+        let rate =
+            internal_rate_of_return(&[-70_000.0, 12_000.0, 15_000.0, 18_000.0, 21_000.0, 26_000.0])
+                .unwrap();
+        assert_close(rate, 0.086_630_948);
+    }
+
+    #[test]
+    fn test_split_distributes_leftover_cents() {
+        assert_eq!(split(&[100.0, 3.0], 10).unwrap(), [33.34, 33.33, 33.33]);
+        assert_eq!(split(&[-10.0, 4.0], 10).unwrap(), [-2.5; 4]);
+    }
+
+    #[test]
+    fn test_split_shares_add_up_to_total() {
+        let total: f64 = split(&[123.45, 7.0], 10).unwrap().iter().sum();
+        assert_close(total, 123.45);
+    }
+
+    #[test]
+    fn test_split_rejects_invalid_shares() {
+        assert!(split(&[100.0, 0.0], 10).is_err());
+        assert!(split(&[100.0, 2.5], 10).is_err());
+        assert_eq!(
+            split(&[100.0, 1e12], 10).unwrap_err().code(),
+            ErrorCode::Limit
+        );
+    }
+
+    #[test]
+    fn test_ratio_allocates_proportionally() {
+        let [a, b] = ratio(&[2.0, 3.0, 1000.0]).unwrap();
+        assert_close(a, 400.0);
+        assert_close(b, 600.0);
+        assert!(ratio(&[1.0, -1.0, 10.0]).is_err());
+    }
+
+    #[test]
+    fn test_irr_requires_sign_change() {
+        assert_eq!(
+            internal_rate_of_return(&[100.0, 200.0]),
+            Err(
+                EvalError::new("irr requires both positive and negative cash flows")
+                    .with_code(ErrorCode::Domain)
+            )
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Canonical formatting of expressions.
+//!
+//! Reprints a parsed expression with consistent spacing (`5+3*2` becomes
+//! `5 + 3 * 2`), emitting only the parentheses required by operator
+//! precedence. Number literals keep their original spelling, so `0xFF` or
+//! `1e3` are not rewritten as decimals.
+
+use crate::eval::EvalContext;
+use crate::eval::ast::{BinaryOp, Expr, Parser};
+use crate::eval::directive::split_format_directive;
+use crate::eval::error::EvalError;
+use crate::eval::parser::{ParsedLine, parse_line};
+use crate::eval::token::{Token, Tokenizer};
+
+/// Binding strength of an equation inside a function call.
+const PREC_EQUATION: u8 = 0;
+/// Binding strength of additive operators.
+const PREC_ADDITIVE: u8 = 1;
+/// Binding strength of multiplicative operators (including implicit multiplication).
+const PREC_MULTIPLICATIVE: u8 = 2;
+/// Binding strength of the power operator.
+const PREC_POWER: u8 = 3;
+/// Binding strength of unary minus.
+const PREC_UNARY: u8 = 4;
+/// Binding strength of the postfix factorial operator.
+const PREC_POSTFIX: u8 = 5;
+/// Binding strength of literals, variables, and function calls.
+const PREC_ATOM: u8 = 6;
+
+/// Formats a single line in canonical form.
+///
+/// Assignments are printed as `name = expression`, and multiple assignments
+/// as `a, b = x, y`. A formatting directive is kept as ` | name`. Lines that
+/// are empty or cannot be parsed (including comments and partial input) are
+/// left to the caller unchanged. Number literals and directives are read
+/// with the settings of `context`.
+///
+/// # Returns
+/// `Some(String)` with the formatted line, or `None` if the line cannot be formatted.
+#[must_use]
+pub fn format_line(line: &str, context: &EvalContext) -> Option<String> {
+    let (line, directive) = split_format_directive(line, context.format_presets());
+    let formatted = format_statement(line, context)?;
+    Some(match directive {
+        Some(directive) => format!("{formatted} | {directive}"),
+        None => formatted,
+    })
+}
+
+/// Formats a line without a formatting directive in canonical form.
+fn format_statement(line: &str, context: &EvalContext) -> Option<String> {
+    match parse_line(line) {
+        ParsedLine::Empty
+        | ParsedLine::SectionStart(_)
+        | ParsedLine::SectionEnd
+        | ParsedLine::Total
+        | ParsedLine::Disabled
+        | ParsedLine::Unset(_)
+        | ParsedLine::ClearVariables => None,
+        ParsedLine::Expression(expr) => format_expression(&expr, context).ok(),
+        ParsedLine::Assignment {
+            name,
+            expression,
+            constant,
+        } => {
+            let keyword = if constant { "const " } else { "" };
+            format_expression(&expression, context)
+                .ok()
+                .map(|formatted| format!("{keyword}{name} = {formatted}"))
+        }
+        ParsedLine::MultipleAssignment {
+            names,
+            expressions,
+            constant,
+        } => {
+            let keyword = if constant { "const " } else { "" };
+            let formatted = expressions
+                .iter()
+                .map(|expression| format_expression(expression, context))
+                .collect::<Result<Vec<_>, _>>()
+                .ok()?;
+            Some(format!(
+                "{keyword}{} = {}",
+                names.join(", "),
+                formatted.join(", ")
+            ))
+        }
+    }
+}
+
+/// Formats an expression string in canonical form, reading it with the
+/// settings of `context`.
+///
+/// # Errors
+/// Returns an `EvalError` if the expression cannot be tokenized or parsed.
+pub fn format_expression(expression: &str, context: &EvalContext) -> Result<String, EvalError> {
+    let tokens = Tokenizer::with_options(expression, context.token_options()).tokenize()?;
+    let ast = Parser::with_limits(tokens.clone(), context.limits()).parse()?;
+
+    let mut literals = tokens
+        .iter()
+        .filter(|(token, _)| {
+            matches!(
+                token,
+                Token::Number(_)
+                    | Token::Duration(_)
+                    | Token::Size { .. }
+                    | Token::Quantity { .. }
+                    | Token::Money { .. }
+            )
+        })
+        .map(|(_, span)| &expression[span.start..span.end]);
+    let mut output = String::new();
+    write_expr(&ast, &mut literals, &mut output);
+    Ok(output)
+}
+
+/// Formats an AST in canonical form, printing numbers as decimals.
+#[must_use]
+pub fn format_ast(expr: &Expr) -> String {
+    let mut output = String::new();
+    write_expr(expr, &mut std::iter::empty(), &mut output);
+    output
+}
+
+/// Returns the binding strength of an expression node.
+const fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::BinaryOp { op, .. } => match op {
+            BinaryOp::Add | BinaryOp::Sub => PREC_ADDITIVE,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::FloorDiv | BinaryOp::Mod => {
+                PREC_MULTIPLICATIVE
+            }
+            BinaryOp::Pow => PREC_POWER,
+        },
+        Expr::Equation { .. } => PREC_EQUATION,
+        Expr::UnaryMinus(_) => PREC_UNARY,
+        Expr::Factorial(_) => PREC_POSTFIX,
+        Expr::Number(_) | Expr::Variable(_) | Expr::FunctionCall { .. } | Expr::Text(_) => {
+            PREC_ATOM
+        }
+    }
+}
+
+/// Writes `expr` to `output`, consuming number literal spellings in source order.
+///
+/// The AST is traversed in the same left-to-right order in which the parser
+/// consumed number tokens, so each `Expr::Number` pairs with its literal.
+fn write_expr<'a>(expr: &Expr, literals: &mut impl Iterator<Item = &'a str>, output: &mut String) {
+    match expr {
+        Expr::Number(n) => match literals.next() {
+            Some(literal) => output.push_str(literal),
+            None => output.push_str(&n.to_string()),
+        },
+        Expr::Variable(name) => output.push_str(name),
+        Expr::Text(text) => {
+            output.push('"');
+            output.push_str(text);
+            output.push('"');
+        }
+        Expr::Equation { left, right } => {
+            write_expr(left, literals, output);
+            output.push_str(" = ");
+            write_expr(right, literals, output);
+        }
+        Expr::BinaryOp { left, op, right } => {
+            let prec = precedence(expr);
+            let (left_min, right_min) = if *op == BinaryOp::Pow {
+                (PREC_UNARY, PREC_POWER)
+            } else {
+                (prec, prec + 1)
+            };
+            write_operand(left, left_min, literals, output);
+            output.push(' ');
+            output.push_str(op.symbol());
+            output.push(' ');
+            write_operand(right, right_min, literals, output);
+        }
+        Expr::UnaryMinus(inner) => {
+            output.push('-');
+            write_operand(inner, PREC_UNARY, literals, output);
+        }
+        Expr::Factorial(inner) => {
+            write_operand(inner, PREC_POSTFIX, literals, output);
+            output.push('!');
+        }
+        Expr::FunctionCall { name, args } => {
+            output.push_str(name);
+            output.push('(');
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    output.push_str(", ");
+                }
+                write_expr(arg, literals, output);
+            }
+            output.push(')');
+        }
+    }
+}
+
+/// Writes an operand, wrapping it in parentheses if it binds looser than `min_prec`.
+fn write_operand<'a>(
+    expr: &Expr,
+    min_prec: u8,
+    literals: &mut impl Iterator<Item = &'a str>,
+    output: &mut String,
+) {
+    if precedence(expr) < min_prec {
+        output.push('(');
+        write_expr(expr, literals, output);
+        output.push(')');
+    } else {
+        write_expr(expr, literals, output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(input: &str) -> String {
+        format_expression(input, &EvalContext::new()).expect("should format")
+    }
+
+    #[test]
+    fn test_format_adds_spacing() {
+        assert_eq!(fmt("5+3*2"), "5 + 3 * 2");
+    }
+
+    #[test]
+    fn test_format_keeps_required_parentheses() {
+        assert_eq!(fmt("(5+3)*2"), "(5 + 3) * 2");
+    }
+
+    #[test]
+    fn test_format_drops_redundant_parentheses() {
+        assert_eq!(fmt("((5))+(3*2)"), "5 + 3 * 2");
+    }
+
+    #[test]
+    fn test_format_equation_argument() {
+        assert_eq!(fmt("solve(2x+3=11,x)"), "solve(2 * x + 3 = 11, x)");
+    }
+
+    #[test]
+    fn test_format_left_associative_subtraction() {
+        assert_eq!(fmt("a-(b-c)"), "a - (b - c)");
+        assert_eq!(fmt("(a-b)-c"), "a - b - c");
+    }
+
+    #[test]
+    fn test_format_right_associative_power() {
+        assert_eq!(fmt("2^(3^2)"), "2 ^ 3 ^ 2");
+        assert_eq!(fmt("(2^3)^2"), "(2 ^ 3) ^ 2");
+    }
+
+    #[test]
+    fn test_format_unary_and_factorial() {
+        assert_eq!(fmt("-(a+b)"), "-(a + b)");
+        assert_eq!(fmt("(3+2)!"), "(3 + 2)!");
+        assert_eq!(fmt("3!^2"), "3! ^ 2");
+    }
+
+    #[test]
+    fn test_format_function_arguments() {
+        assert_eq!(fmt("max(1,2+3)"), "max(1, 2 + 3)");
+    }
+
+    #[test]
+    fn test_format_implicit_multiplication_becomes_explicit() {
+        assert_eq!(fmt("2pi"), "2 * pi");
+    }
+
+    #[test]
+    fn test_format_preserves_literal_spelling() {
+        assert_eq!(fmt("0xFF+1e3*.5"), "0xFF + 1e3 * .5");
+    }
+
+    #[test]
+    fn test_format_line_assignment() {
+        assert_eq!(
+            format_line("x=5+3", &EvalContext::new()).as_deref(),
+            Some("x = 5 + 3")
+        );
+    }
+
+    #[test]
+    fn test_format_line_keeps_directive() {
+        assert_eq!(
+            format_line("x=1/3|2DP", &EvalContext::new()).as_deref(),
+            Some("x = 1 / 3 | 2dp")
+        );
+    }
+
+    #[test]
+    fn test_format_line_const_declaration() {
+        assert_eq!(
+            format_line("const  tax=0.19", &EvalContext::new()).as_deref(),
+            Some("const tax = 0.19")
+        );
+    }
+
+    #[test]
+    fn test_format_line_multiple_assignment() {
+        assert_eq!(
+            format_line("a,b=b,max(a,1)", &EvalContext::new()).as_deref(),
+            Some("a, b = b, max(a, 1)")
+        );
+    }
+
+    #[test]
+    fn test_format_line_unparseable_returns_none() {
+        assert_eq!(format_line("# a comment", &EvalContext::new()), None);
+        assert_eq!(format_line("5 +", &EvalContext::new()), None);
+        assert_eq!(format_line("   ", &EvalContext::new()), None);
+    }
+
+    #[test]
+    fn test_format_roundtrip_evaluates_identically() {
+        let context = crate::eval::EvalContext::new();
+        for input in ["-2^2", "2^-1", "10-4-3", "2*3!", "8/2/2", "-(3)!"] {
+            let formatted = fmt(input);
+            assert_eq!(
+                crate::eval::evaluate_expression(input, &context),
+                crate::eval::evaluate_expression(&formatted, &context),
+                "{input} formatted as {formatted}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_ast_prints_decimal_numbers() {
+        let ast = crate::eval::parse_expression("0x10+1").expect("should parse");
+        assert_eq!(format_ast(&ast), "16 + 1");
+    }
+}
@@ -0,0 +1,715 @@
+//! Built-in function registry.
+//!
+//! Provides a single static table of built-in functions with their arity,
+//! signature, description, and implementation. The evaluator dispatches
+//! through it, and the highlighter and completion read names from it, so the
+//! three can never disagree about which functions exist.
+
+use std::collections::HashMap;
+
+use crate::eval::ast::Expr;
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::evaluator::AngleMode;
+use crate::eval::{
+    calculus, convert, csv, finance, inspect, list, network, shell, simplify, solve, units,
+};
+
+/// An evaluated function argument.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    /// A numeric value.
+    Number(f64),
+    /// A text literal, such as a unit name.
+    Text(String),
+}
+
+/// A function applied to unevaluated arguments and the variables in scope.
+type UnevaluatedFn = fn(&[Expr], &HashMap<String, f64>, AngleMode) -> Result<f64, EvalError>;
+
+/// How a built-in function computes its result.
+#[derive(Debug)]
+enum Implementation {
+    /// Takes numeric arguments only.
+    Numeric(fn(&[f64]) -> Result<f64, EvalError>),
+    /// Takes a mix of numbers and text.
+    Mixed(fn(&[Argument]) -> Result<f64, EvalError>),
+    /// Takes its arguments unevaluated, together with the variables in scope.
+    Unevaluated(UnevaluatedFn),
+}
+
+/// Metadata and implementation of a built-in function.
+#[derive(Debug)]
+pub struct FunctionInfo {
+    /// The name used to call the function.
+    pub name: &'static str,
+    /// The minimum number of arguments the function takes.
+    pub arity: usize,
+    /// The maximum number of arguments, or `None` if unlimited.
+    pub max_arity: Option<usize>,
+    /// The call signature shown in documentation, e.g. `log(x, b)`.
+    pub signature: &'static str,
+    /// A short description of what the function computes.
+    pub description: &'static str,
+    /// Computes the result from an accepted number of arguments.
+    apply: Implementation,
+}
+
+impl FunctionInfo {
+    /// Allows up to `count` trailing optional arguments.
+    const fn optional(mut self, count: usize) -> Self {
+        self.max_arity = Some(self.arity + count);
+        self
+    }
+
+    /// Allows any number of arguments beyond `arity`.
+    const fn variadic(mut self) -> Self {
+        self.max_arity = None;
+        self
+    }
+
+    /// Returns an error if `count` arguments are not accepted.
+    pub(crate) fn check_argument_count(&self, count: usize) -> Result<(), EvalError> {
+        if self.max_arity == Some(self.arity) && count != self.arity {
+            return Err(EvalError::invalid_argument_count(
+                self.name, self.arity, count,
+            ));
+        }
+        if count < self.arity {
+            return Err(EvalError::new(format!(
+                "function '{}' expects at least {} argument(s), got {count}",
+                self.name, self.arity
+            ))
+            .with_code(ErrorCode::ArgumentCount));
+        }
+        if let Some(max) = self.max_arity
+            && count > max
+        {
+            return Err(EvalError::new(format!(
+                "function '{}' expects at most {max} argument(s), got {count}",
+                self.name
+            ))
+            .with_code(ErrorCode::ArgumentCount));
+        }
+        Ok(())
+    }
+
+    /// Calls the function, checking the argument count first.
+    ///
+    /// # Errors
+    /// Returns an `EvalError` if the argument count does not match the arity,
+    /// or if the function rejects its arguments.
+    pub fn call(&self, args: &[f64]) -> Result<f64, EvalError> {
+        let args: Vec<Argument> = args.iter().copied().map(Argument::Number).collect();
+        self.call_with(&args)
+    }
+
+    /// Calls the function with arguments that may include text.
+    ///
+    /// # Errors
+    /// Returns an `EvalError` if the argument count does not match the arity,
+    /// if a numeric function is given text, or if the function rejects its
+    /// arguments.
+    pub fn call_with(&self, args: &[Argument]) -> Result<f64, EvalError> {
+        self.check_argument_count(args.len())?;
+        match self.apply {
+            Implementation::Numeric(apply) => {
+                let numbers = args
+                    .iter()
+                    .map(|arg| match arg {
+                        Argument::Number(n) => Ok(*n),
+                        Argument::Text(text) => Err(EvalError::new(format!(
+                            "{} expects numbers, got \"{text}\"",
+                            self.name
+                        ))
+                        .with_code(ErrorCode::InvalidArgument)),
+                    })
+                    .collect::<Result<Vec<f64>, EvalError>>()?;
+                apply(&numbers)
+            }
+            Implementation::Mixed(apply) => apply(args),
+            Implementation::Unevaluated(_) => Err(EvalError::new(format!(
+                "{} cannot be called with evaluated arguments",
+                self.name
+            ))
+            .with_code(ErrorCode::InvalidArgument)),
+        }
+    }
+
+    /// Returns `true` if the function takes its arguments unevaluated.
+    #[must_use]
+    pub const fn takes_expressions(&self) -> bool {
+        matches!(self.apply, Implementation::Unevaluated(_))
+    }
+
+    /// Calls a function that takes unevaluated arguments, such as `solve`.
+    ///
+    /// # Errors
+    /// Returns an `EvalError` if the argument count does not match the arity,
+    /// if the function takes evaluated arguments, or if it fails.
+    pub fn call_unevaluated(
+        &self,
+        args: &[Expr],
+        variables: &HashMap<String, f64>,
+        angle_mode: AngleMode,
+    ) -> Result<f64, EvalError> {
+        self.check_argument_count(args.len())?;
+        match self.apply {
+            Implementation::Unevaluated(apply) => apply(args, variables, angle_mode),
+            _ => Err(EvalError::new(format!(
+                "{} must be called with evaluated arguments",
+                self.name
+            ))
+            .with_code(ErrorCode::InvalidArgument)),
+        }
+    }
+}
+
+/// Builds a registry entry.
+const fn entry(
+    name: &'static str,
+    arity: usize,
+    signature: &'static str,
+    description: &'static str,
+    apply: fn(&[f64]) -> Result<f64, EvalError>,
+) -> FunctionInfo {
+    FunctionInfo {
+        name,
+        arity,
+        max_arity: Some(arity),
+        signature,
+        description,
+        apply: Implementation::Numeric(apply),
+    }
+}
+
+/// Builds a registry entry for a function that takes unevaluated arguments.
+const fn unevaluated_entry(
+    name: &'static str,
+    arity: usize,
+    signature: &'static str,
+    description: &'static str,
+    apply: UnevaluatedFn,
+) -> FunctionInfo {
+    FunctionInfo {
+        name,
+        arity,
+        max_arity: Some(arity),
+        signature,
+        description,
+        apply: Implementation::Unevaluated(apply),
+    }
+}
+
+/// Builds a registry entry for a function that also takes text arguments.
+const fn mixed_entry(
+    name: &'static str,
+    arity: usize,
+    signature: &'static str,
+    description: &'static str,
+    apply: fn(&[Argument]) -> Result<f64, EvalError>,
+) -> FunctionInfo {
+    FunctionInfo {
+        name,
+        arity,
+        max_arity: Some(arity),
+        signature,
+        description,
+        apply: Implementation::Mixed(apply),
+    }
+}
+
+/// All built-in functions.
+pub static BUILTIN_FUNCTIONS: &[FunctionInfo] = &[
+    entry("sqrt", 1, "sqrt(x)", "Square root", |a| Ok(a[0].sqrt())),
+    entry("cbrt", 1, "cbrt(x)", "Cube root", |a| Ok(a[0].cbrt())),
+    entry("abs", 1, "abs(x)", "Absolute value", |a| Ok(a[0].abs())),
+    entry("pow", 2, "pow(x, y)", "x raised to the power y", |a| {
+        Ok(a[0].powf(a[1]))
+    }),
+    entry("sin", 1, "sin(x)", "Sine (radians)", |a| Ok(a[0].sin())),
+    entry("cos", 1, "cos(x)", "Cosine (radians)", |a| Ok(a[0].cos())),
+    entry("tan", 1, "tan(x)", "Tangent (radians)", |a| Ok(a[0].tan())),
+    entry("asin", 1, "asin(x)", "Arc sine", |a| Ok(a[0].asin())),
+    entry("acos", 1, "acos(x)", "Arc cosine", |a| Ok(a[0].acos())),
+    entry("atan", 1, "atan(x)", "Arc tangent", |a| Ok(a[0].atan())),
+    entry(
+        "atan2",
+        2,
+        "atan2(y, x)",
+        "Arc tangent of y/x by quadrant",
+        |a| Ok(a[0].atan2(a[1])),
+    ),
+    entry("sinh", 1, "sinh(x)", "Hyperbolic sine", |a| Ok(a[0].sinh())),
+    entry("cosh", 1, "cosh(x)", "Hyperbolic cosine", |a| {
+        Ok(a[0].cosh())
+    }),
+    entry("tanh", 1, "tanh(x)", "Hyperbolic tangent", |a| {
+        Ok(a[0].tanh())
+    }),
+    entry("asinh", 1, "asinh(x)", "Inverse hyperbolic sine", |a| {
+        Ok(a[0].asinh())
+    }),
+    entry("acosh", 1, "acosh(x)", "Inverse hyperbolic cosine", |a| {
+        Ok(a[0].acosh())
+    }),
+    entry("atanh", 1, "atanh(x)", "Inverse hyperbolic tangent", |a| {
+        Ok(a[0].atanh())
+    }),
+    entry("ln", 1, "ln(x)", "Natural logarithm", |a| Ok(a[0].ln())),
+    entry(
+        "log2",
+        1,
+        "log2(x)",
+        "Base-2 logarithm",
+        |a| Ok(a[0].log2()),
+    ),
+    entry("log10", 1, "log10(x)", "Base-10 logarithm", |a| {
+        Ok(a[0].log10())
+    }),
+    entry("exp", 1, "exp(x)", "e^x", |a| Ok(a[0].exp())),
+    entry("exp2", 1, "exp2(x)", "2^x", |a| Ok(a[0].exp2())),
+    entry("log", 2, "log(x, b)", "Logarithm of x to base b", |a| {
+        Ok(a[0].log(a[1]))
+    }),
+    entry("floor", 1, "floor(x)", "Round down", |a| Ok(a[0].floor())),
+    entry("ceil", 1, "ceil(x)", "Round up", |a| Ok(a[0].ceil())),
+    entry("round", 1, "round(x)", "Round to nearest", |a| {
+        Ok(a[0].round())
+    }),
+    entry("sgn", 1, "sgn(x)", "Sign (-1, 0, or 1)", |a| {
+        Ok(if a[0] == 0.0 { 0.0 } else { a[0].signum() })
+    }),
+    entry("trunc", 1, "trunc(x)", "Truncate to integer", |a| {
+        Ok(a[0].trunc())
+    }),
+    entry(
+        "frac",
+        1,
+        "frac(x)",
+        "Fractional part",
+        |a| Ok(a[0].fract()),
+    ),
+    entry("degrees", 1, "degrees(x)", "Radians to degrees", |a| {
+        Ok(a[0].to_degrees())
+    }),
+    entry("radians", 1, "radians(x)", "Degrees to radians", |a| {
+        Ok(a[0].to_radians())
+    }),
+    entry("cot", 1, "cot(x)", "Cotangent (radians)", |a| {
+        Ok(1.0 / a[0].tan())
+    }),
+    entry("sec", 1, "sec(x)", "Secant (radians)", |a| {
+        Ok(1.0 / a[0].cos())
+    }),
+    entry("csc", 1, "csc(x)", "Cosecant (radians)", |a| {
+        Ok(1.0 / a[0].sin())
+    }),
+    entry("min", 2, "min(a, b)", "Minimum value", |a| {
+        Ok(a[0].min(a[1]))
+    }),
+    entry("max", 2, "max(a, b)", "Maximum value", |a| {
+        Ok(a[0].max(a[1]))
+    }),
+    entry("hypot", 2, "hypot(x, y)", "Length of the hypotenuse", |a| {
+        Ok(a[0].hypot(a[1]))
+    }),
+    entry("gcd", 2, "gcd(a, b)", "Greatest common divisor", |a| {
+        if !a[0].is_finite() || !a[1].is_finite() {
+            return Err(
+                EvalError::new("gcd requires finite arguments").with_code(ErrorCode::Domain)
+            );
+        }
+        Ok(compute_gcd(a[0], a[1]))
+    }),
+    entry("ncr", 2, "ncr(n, k)", "Combinations of k from n", |a| {
+        compute_ncr(a[0], a[1])
+    }),
+    entry("npr", 2, "npr(n, k)", "Permutations of k from n", |a| {
+        compute_npr(a[0], a[1])
+    }),
+    entry("sum", 0, "sum(a, b, ...)", "Sum of the values", |a| {
+        Ok(a.iter().sum())
+    })
+    .variadic(),
+    entry("avg", 1, "avg(a, b, ...)", "Mean of the values", |a| {
+        #[allow(clippy::cast_precision_loss)]
+        Ok(a.iter().sum::<f64>() / a.len() as f64)
+    })
+    .variadic(),
+    entry("count", 0, "count(a, b, ...)", "Number of values", |a| {
+        #[allow(clippy::cast_precision_loss)]
+        Ok(a.len() as f64)
+    })
+    .variadic(),
+    mixed_entry(
+        "convert",
+        3,
+        "convert(x, \"from\", \"to\")",
+        "Convert x between units",
+        units::convert_arguments,
+    ),
+    entry(
+        "fv",
+        3,
+        "fv(rate, n, pmt, [pv])",
+        "Future value of payments",
+        |a| Ok(finance::future_value(a)),
+    )
+    .optional(1),
+    entry(
+        "pv",
+        3,
+        "pv(rate, n, pmt, [fv])",
+        "Present value of payments",
+        |a| Ok(finance::present_value(a)),
+    )
+    .optional(1),
+    entry(
+        "pmt",
+        3,
+        "pmt(rate, n, pv, [fv])",
+        "Payment per period",
+        finance::payment,
+    )
+    .optional(1),
+    entry(
+        "npv",
+        2,
+        "npv(rate, cf1, cf2, ...)",
+        "Net present value of cash flows",
+        |a| Ok(finance::net_present_value(a)),
+    )
+    .variadic(),
+    entry(
+        "split",
+        2,
+        "split(total, n)",
+        "Shares of total split n ways in cents, as a list",
+        |_| Err(list::list_call_error("split")),
+    ),
+    entry(
+        "ratio",
+        3,
+        "ratio(a, b, total)",
+        "Parts of total in the ratio a:b, as a list",
+        |_| Err(list::list_call_error("ratio")),
+    ),
+    unevaluated_entry(
+        "solve",
+        2,
+        "solve(equation, x, [root])",
+        "Solve a linear or quadratic equation for x",
+        solve::solve,
+    )
+    .optional(1),
+    unevaluated_entry(
+        "root",
+        4,
+        "root(f, x, a, b)",
+        "Root of f in x between a and b",
+        calculus::root,
+    ),
+    unevaluated_entry(
+        "deriv",
+        3,
+        "deriv(f, x, at)",
+        "Derivative of f in x at a point",
+        calculus::deriv,
+    ),
+    unevaluated_entry(
+        "table",
+        4,
+        "table(f, x, from, to, [step])",
+        "Table of f in x from one value to another",
+        calculus::table_value,
+    )
+    .optional(1),
+    unevaluated_entry(
+        "simplify",
+        1,
+        "simplify(expr)",
+        "Show expr with like terms collected",
+        simplify::simplify_value,
+    ),
+    unevaluated_entry(
+        "vars",
+        1,
+        "vars(expr)",
+        "List the variables expr uses and their values",
+        inspect::vars_value,
+    ),
+    entry(
+        "irr",
+        2,
+        "irr(cf0, cf1, ...)",
+        "Internal rate of return",
+        finance::internal_rate_of_return,
+    )
+    .variadic(),
+    mixed_entry(
+        "roman",
+        1,
+        "roman(\"XIV\")",
+        "Value of a Roman numeral",
+        convert::roman_argument,
+    ),
+    entry(
+        "to_roman",
+        1,
+        "to_roman(x)",
+        "Show x as a Roman numeral",
+        convert::check_roman,
+    ),
+    entry(
+        "words",
+        1,
+        "words(x)",
+        "Show x spelled out in words",
+        convert::check_words,
+    ),
+    mixed_entry(
+        "frombase",
+        2,
+        "frombase(\"z1\", b)",
+        "Read digits in base b (2-36 or 64)",
+        convert::frombase_argument,
+    ),
+    entry(
+        "tobase",
+        2,
+        "tobase(x, b)",
+        "Show x in base b (2-36 or 64)",
+        convert::check_base,
+    ),
+    mixed_entry(
+        "cidr_hosts",
+        1,
+        "cidr_hosts(\"10.0.0.0/24\")",
+        "Usable host addresses in a network",
+        network::cidr_hosts_argument,
+    ),
+    entry(
+        "netmask",
+        1,
+        "netmask(prefix)",
+        "Show the IPv4 mask for a prefix length",
+        network::netmask_value,
+    ),
+    mixed_entry(
+        "in_subnet",
+        2,
+        "in_subnet(\"ip\", \"cidr\")",
+        "Show whether an address is in a network",
+        network::in_subnet_argument,
+    ),
+    mixed_entry(
+        "shell",
+        1,
+        "shell(\"command\")",
+        "Number printed by a shell command",
+        shell::shell_argument,
+    ),
+    mixed_entry(
+        "ord",
+        1,
+        "ord(\"A\")",
+        "Code point of a character",
+        convert::ord_argument,
+    ),
+    entry(
+        "chr",
+        1,
+        "chr(x)",
+        "Show the character with code point x",
+        convert::check_char,
+    ),
+    mixed_entry(
+        "hexdump",
+        1,
+        "hexdump(\"text\")",
+        "Show the UTF-8 bytes of text in hex",
+        convert::hexdump_argument,
+    ),
+    mixed_entry(
+        "list",
+        0,
+        "list(a, b, ...)",
+        "The values as a list",
+        list::list_argument,
+    )
+    .variadic(),
+    mixed_entry(
+        "csv",
+        2,
+        "csv(\"file\", \"column\")",
+        "Values of a column of a CSV file, as a list",
+        csv::csv_argument,
+    ),
+];
+
+/// Looks up a built-in function by name.
+#[must_use]
+pub fn lookup_function(name: &str) -> Option<&'static FunctionInfo> {
+    BUILTIN_FUNCTIONS.iter().find(|f| f.name == name)
+}
+
+/// Returns `true` if `name` is a built-in function.
+#[must_use]
+pub fn is_builtin_function(name: &str) -> bool {
+    lookup_function(name).is_some()
+}
+
+/// Returns the built-in functions whose names start with `prefix`, sorted by name.
+#[must_use]
+pub fn complete_function(prefix: &str) -> Vec<&'static FunctionInfo> {
+    let mut matches: Vec<&'static FunctionInfo> = BUILTIN_FUNCTIONS
+        .iter()
+        .filter(|f| f.name.starts_with(prefix))
+        .collect();
+    matches.sort_by_key(|f| f.name);
+    matches
+}
+
+/// Computes the greatest common divisor using the Euclidean algorithm.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+const fn compute_gcd(a: f64, b: f64) -> f64 {
+    let mut a = a as i64;
+    let mut b = b as i64;
+    while b != 0 {
+        let t = b;
+        b = a % b;
+        a = t;
+    }
+    a.unsigned_abs() as f64
+}
+
+/// Computes the binomial coefficient C(n, k) = n! / (k! * (n-k)!) iteratively.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn compute_ncr(n: f64, k: f64) -> Result<f64, EvalError> {
+    let n = n.trunc() as i64;
+    let k = k.trunc() as i64;
+    if k < 0 || n < 0 || k > n {
+        return Err(
+            EvalError::new(format!("ncr requires 0 <= k <= n, got n={n}, k={k}"))
+                .with_code(ErrorCode::Domain),
+        );
+    }
+    let mut result: f64 = 1.0;
+    for i in 0..k {
+        result = result * (n - i) as f64 / (i + 1) as f64;
+    }
+    Ok(result)
+}
+
+/// Computes the permutation P(n, k) = n! / (n-k)! iteratively.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn compute_npr(n: f64, k: f64) -> Result<f64, EvalError> {
+    let n = n.trunc() as i64;
+    let k = k.trunc() as i64;
+    if k < 0 || n < 0 || k > n {
+        return Err(
+            EvalError::new(format!("npr requires 0 <= k <= n, got n={n}, k={k}"))
+                .with_code(ErrorCode::Domain),
+        );
+    }
+    let mut result: f64 = 1.0;
+    for i in 0..k {
+        result *= (n - i) as f64;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_registry_names_are_unique() {
+        let names: HashSet<&str> = BUILTIN_FUNCTIONS.iter().map(|f| f.name).collect();
+        assert_eq!(names.len(), BUILTIN_FUNCTIONS.len());
+    }
+
+    #[test]
+    fn test_signatures_start_with_name() {
+        for f in BUILTIN_FUNCTIONS {
+            assert!(
+                f.signature.starts_with(&format!("{}(", f.name)),
+                "{} has signature {}",
+                f.name,
+                f.signature
+            );
+        }
+    }
+
+    #[test]
+    fn test_lookup_function() {
+        assert_eq!(lookup_function("hypot").map(|f| f.arity), Some(2));
+        assert!(lookup_function("nope").is_none());
+    }
+
+    #[test]
+    fn test_call_checks_arity() {
+        let sqrt = lookup_function("sqrt").expect("sqrt is registered");
+
+        assert_eq!(sqrt.call(&[9.0]), Ok(3.0));
+        assert_eq!(
+            sqrt.call(&[1.0, 2.0]),
+            Err(EvalError::invalid_argument_count("sqrt", 1, 2))
+        );
+    }
+
+    #[test]
+    fn test_optional_and_variadic_argument_counts() {
+        let fv = lookup_function("fv").expect("fv is registered");
+        let npv = lookup_function("npv").expect("npv is registered");
+
+        assert!(fv.call(&[0.1, 1.0, 0.0]).is_ok());
+        assert!(fv.call(&[0.1, 1.0, 0.0, -100.0]).is_ok());
+        assert_eq!(
+            fv.call(&[0.1]),
+            Err(
+                EvalError::new("function 'fv' expects at least 3 argument(s), got 1")
+                    .with_code(ErrorCode::ArgumentCount)
+            )
+        );
+        assert_eq!(
+            fv.call(&[0.1, 1.0, 0.0, 0.0, 0.0]),
+            Err(
+                EvalError::new("function 'fv' expects at most 4 argument(s), got 5")
+                    .with_code(ErrorCode::ArgumentCount)
+            )
+        );
+        assert!(npv.call(&[0.1, 1.0, 2.0, 3.0, 4.0, 5.0]).is_ok());
+    }
+
+    #[test]
+    fn test_numeric_functions_reject_text() {
+        let sqrt = lookup_function("sqrt").expect("sqrt is registered");
+
+        assert_eq!(
+            sqrt.call_with(&[Argument::Text("km".to_string())]),
+            Err(EvalError::new("sqrt expects numbers, got \"km\"")
+                .with_code(ErrorCode::InvalidArgument))
+        );
+    }
+
+    #[test]
+    fn test_convert_is_registered() {
+        let convert = lookup_function("convert").expect("convert is registered");
+        let args = [
+            Argument::Number(1.0),
+            Argument::Text("km".to_string()),
+            Argument::Text("m".to_string()),
+        ];
+
+        assert_eq!(convert.call_with(&args), Ok(1000.0));
+    }
+
+    #[test]
+    fn test_complete_function_sorted_prefix_matches() {
+        let names: Vec<&str> = complete_function("sin").iter().map(|f| f.name).collect();
+        assert_eq!(names, ["sin", "sinh"]);
+        assert!(complete_function("zzz").is_empty());
+    }
+}
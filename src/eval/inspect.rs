@@ -0,0 +1,363 @@
+//! Result inspection for a single line.
+//!
+//! Provides the full-precision value of a line alongside alternative
+//! representations (hex, binary, scientific, fraction) and a tree view of the
+//! parsed expression, for use when the memory pane rounds or truncates.
+//!
+//! A line consisting of a `vars(expr)` call lists the variables `expr` uses
+//! and their current values, which helps trace a chain of "undefined
+//! variable" errors back to its start.
+
+use std::collections::HashMap;
+
+use crate::eval::ast::Expr;
+use crate::eval::constants::is_math_constant;
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::evaluator::AngleMode;
+use crate::eval::{
+    EvalContext, ParsedLine, evaluator, parse_expression_in, parse_line_with_presets,
+};
+
+/// Largest integer magnitude that an `f64` represents exactly (2^53).
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// Largest denominator considered when approximating a value as a fraction.
+const MAX_FRACTION_DENOMINATOR: i64 = 1_000_000;
+
+/// Tolerance for accepting a fraction approximation.
+const FRACTION_EPSILON: f64 = 1e-12;
+
+/// Detailed view of an evaluated line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inspection {
+    /// The variable name, if the line is an assignment.
+    pub name: Option<String>,
+    /// The evaluated value.
+    pub value: f64,
+    /// The value printed with full round-trip precision.
+    pub full_precision: String,
+    /// The value in scientific notation.
+    pub scientific: String,
+    /// Hexadecimal representation, for exactly representable integers.
+    pub hex: Option<String>,
+    /// Binary representation, for exactly representable integers.
+    pub binary: Option<String>,
+    /// Simple fraction approximation, for non-integer values.
+    pub fraction: Option<String>,
+    /// The parsed expression rendered as an indented tree.
+    pub ast: Vec<String>,
+}
+
+/// Inspects a line using the given context for variable bindings.
+///
+/// # Returns
+/// `None` if the line is empty or fails to evaluate.
+#[must_use]
+pub fn inspect_line(line: &str, context: &EvalContext) -> Option<Inspection> {
+    let (name, expression) = match parse_line_with_presets(line, context.format_presets()) {
+        ParsedLine::Empty
+        | ParsedLine::SectionStart(_)
+        | ParsedLine::SectionEnd
+        | ParsedLine::Total
+        | ParsedLine::Disabled
+        | ParsedLine::MultipleAssignment { .. }
+        | ParsedLine::Unset(_)
+        | ParsedLine::ClearVariables => return None,
+        ParsedLine::Expression(expr) => (None, expr),
+        ParsedLine::Assignment {
+            name, expression, ..
+        } => (Some(name), expression),
+    };
+
+    let ast = parse_expression_in(&expression, context).ok()?;
+    let value =
+        evaluator::evaluate_in_mode(&ast, context.variables(), context.angle_mode()).ok()?;
+
+    Some(Inspection {
+        name,
+        value,
+        full_precision: format!("{value}"),
+        scientific: format!("{value:e}"),
+        hex: format_integer(value, 16),
+        binary: format_integer(value, 2),
+        fraction: format_fraction(value),
+        ast: ast_tree_lines(&ast),
+    })
+}
+
+/// Formats an exactly representable integer in base 16 or 2 with its prefix.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn format_integer(value: f64, base: u32) -> Option<String> {
+    if !value.is_finite() || value.fract() != 0.0 || value.abs() > MAX_EXACT_INTEGER {
+        return None;
+    }
+
+    let magnitude = value.abs() as u64;
+    let sign = if value < 0.0 { "-" } else { "" };
+    let digits = match base {
+        16 => format!("0x{magnitude:X}"),
+        2 => format!("0b{magnitude:b}"),
+        _ => return None,
+    };
+    Some(format!("{sign}{digits}"))
+}
+
+/// Approximates a non-integer value as a fraction using continued fractions.
+///
+/// Returns `None` for integers, non-finite values, or when no fraction with a
+/// denominator up to one million matches the value closely enough.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn format_fraction(value: f64) -> Option<String> {
+    if !value.is_finite() || value.fract() == 0.0 || value.abs() > MAX_EXACT_INTEGER {
+        return None;
+    }
+
+    let (mut h_prev, mut h) = (0_i64, 1_i64);
+    let (mut k_prev, mut k) = (1_i64, 0_i64);
+    let mut x = value;
+
+    loop {
+        let a = x.floor();
+        let a_int = a as i64;
+        let h_next = a_int.checked_mul(h)?.checked_add(h_prev)?;
+        let k_next = a_int.checked_mul(k)?.checked_add(k_prev)?;
+        if k_next.abs() > MAX_FRACTION_DENOMINATOR {
+            return None;
+        }
+        (h_prev, h) = (h, h_next);
+        (k_prev, k) = (k, k_next);
+
+        if (value - h as f64 / k as f64).abs() < FRACTION_EPSILON {
+            return Some(format!("{h}/{k}"));
+        }
+
+        let remainder = x - a;
+        if remainder == 0.0 {
+            return None;
+        }
+        x = 1.0 / remainder;
+    }
+}
+
+/// Renders an expression AST as an indented tree using box-drawing characters.
+#[must_use]
+pub fn ast_tree_lines(expr: &Expr) -> Vec<String> {
+    let mut lines = vec![node_label(expr)];
+    push_children(expr, "", &mut lines);
+    lines
+}
+
+/// Appends the children of `expr` to `lines`, prefixed for tree drawing.
+fn push_children(expr: &Expr, prefix: &str, lines: &mut Vec<String>) {
+    let children = node_children(expr);
+    let count = children.len();
+    for (i, child) in children.into_iter().enumerate() {
+        let last = i + 1 == count;
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        lines.push(format!("{prefix}{branch}{}", node_label(child)));
+        push_children(child, &format!("{prefix}{indent}"), lines);
+    }
+}
+
+/// Returns the display label for a single AST node.
+fn node_label(expr: &Expr) -> String {
+    match expr {
+        Expr::Number(n) => format!("{n}"),
+        Expr::Variable(name) => name.clone(),
+        Expr::BinaryOp { op, .. } => op.symbol().to_string(),
+        Expr::UnaryMinus(_) => "neg".to_string(),
+        Expr::Factorial(_) => "!".to_string(),
+        Expr::FunctionCall { name, .. } => format!("{name}()"),
+        Expr::Text(text) => format!("\"{text}\""),
+        Expr::Equation { .. } => "=".to_string(),
+    }
+}
+
+/// Returns the direct children of an AST node.
+fn node_children(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::Number(_) | Expr::Variable(_) | Expr::Text(_) => Vec::new(),
+        Expr::BinaryOp { left, right, .. } | Expr::Equation { left, right } => {
+            vec![left, right]
+        }
+        Expr::UnaryMinus(inner) | Expr::Factorial(inner) => vec![inner],
+        Expr::FunctionCall { args, .. } => args.iter().collect(),
+    }
+}
+
+/// Returns the variables `expr` refers to, in order of first use, leaving
+/// out predefined constants such as `pi`.
+#[must_use]
+pub fn referenced_variables(expr: &Expr) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut pending = vec![expr];
+    while let Some(expr) = pending.pop() {
+        if let Expr::Variable(name) = expr
+            && !is_math_constant(name)
+            && !names.contains(name)
+        {
+            names.push(name.clone());
+        }
+        pending.extend(node_children(expr).into_iter().rev());
+    }
+    names
+}
+
+/// Returns the text shown for a line whose expression is a `vars` call,
+/// e.g. `a = 2, b undefined`.
+#[must_use]
+pub fn variables_form(expr: &Expr, context: &EvalContext) -> Option<String> {
+    let Expr::FunctionCall { name, args } = expr else {
+        return None;
+    };
+    let [arg] = args.as_slice() else {
+        return None;
+    };
+    if name != "vars" {
+        return None;
+    }
+    let described: Vec<String> = referenced_variables(arg)
+        .into_iter()
+        .map(|name| {
+            context.get_variable(&name).map_or_else(
+                || format!("{name} undefined"),
+                |value| format!("{name} = {value}"),
+            )
+        })
+        .collect();
+    Some(if described.is_empty() {
+        "no variables".to_string()
+    } else {
+        described.join(", ")
+    })
+}
+
+/// Implements the `vars(expr)` built-in inside larger expressions, where it
+/// has no value.
+pub(crate) fn vars_value(
+    _args: &[Expr],
+    _variables: &HashMap<String, f64>,
+    _angle_mode: AngleMode,
+) -> Result<f64, EvalError> {
+    Err(EvalError::new("vars can only be used on a line of its own")
+        .with_code(ErrorCode::Statement))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::parse_expression;
+
+    #[test]
+    fn test_inspect_empty_line_returns_none() {
+        assert!(inspect_line("   ", &EvalContext::new()).is_none());
+    }
+
+    #[test]
+    fn test_inspect_error_line_returns_none() {
+        assert!(inspect_line("1 +", &EvalContext::new()).is_none());
+    }
+
+    #[test]
+    fn test_inspect_integer_has_hex_and_binary() {
+        let inspection = inspect_line("255", &EvalContext::new()).expect("should inspect");
+
+        assert!((inspection.value - 255.0).abs() < 1e-10);
+        assert_eq!(inspection.hex.as_deref(), Some("0xFF"));
+        assert_eq!(inspection.binary.as_deref(), Some("0b11111111"));
+        assert!(inspection.fraction.is_none());
+    }
+
+    #[test]
+    fn test_inspect_negative_integer_hex() {
+        let inspection = inspect_line("-16", &EvalContext::new()).expect("should inspect");
+
+        assert_eq!(inspection.hex.as_deref(), Some("-0x10"));
+    }
+
+    #[test]
+    fn test_inspect_fraction() {
+        let inspection = inspect_line("1/3", &EvalContext::new()).expect("should inspect");
+
+        assert_eq!(inspection.fraction.as_deref(), Some("1/3"));
+        assert!(inspection.hex.is_none());
+    }
+
+    #[test]
+    fn test_inspect_irrational_has_no_fraction() {
+        let inspection = inspect_line("sqrt(2)", &EvalContext::new()).expect("should inspect");
+
+        assert!(inspection.fraction.is_none());
+    }
+
+    #[test]
+    fn test_inspect_full_precision_and_scientific() {
+        let inspection = inspect_line("1234567.891", &EvalContext::new()).expect("should inspect");
+
+        assert_eq!(inspection.full_precision, "1234567.891");
+        assert_eq!(inspection.scientific, "1.234567891e6");
+    }
+
+    #[test]
+    fn test_inspect_assignment_keeps_name() {
+        let inspection = inspect_line("x = 2 * 3", &EvalContext::new()).expect("should inspect");
+
+        assert_eq!(inspection.name.as_deref(), Some("x"));
+        assert!((inspection.value - 6.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_inspect_uses_context_variables() {
+        let mut context = EvalContext::new();
+        context.set_variable("a", 4.0);
+
+        let inspection = inspect_line("a * 2", &context).expect("should inspect");
+
+        assert!((inspection.value - 8.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_ast_tree_lines_respects_precedence() {
+        let ast = parse_expression("5 + 3 * 2").expect("should parse");
+
+        assert_eq!(
+            ast_tree_lines(&ast),
+            vec!["+", "├── 5", "└── *", "    ├── 3", "    └── 2"]
+        );
+    }
+
+    #[test]
+    fn test_ast_tree_lines_function_call() {
+        let ast = parse_expression("max(1, -x)").expect("should parse");
+
+        assert_eq!(
+            ast_tree_lines(&ast),
+            vec!["max()", "├── 1", "└── neg", "    └── x"]
+        );
+    }
+
+    #[test]
+    fn test_referenced_variables_in_order_without_constants() {
+        let ast = parse_expression("b * pi + max(a, b) + c!").expect("should parse");
+
+        assert_eq!(referenced_variables(&ast), vec!["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_variables_form_marks_undefined_variables() {
+        let mut context = EvalContext::new();
+        context.set_variable("a", 2.0);
+        let vars = |input| variables_form(&parse_expression(input).unwrap(), &context);
+
+        assert_eq!(
+            vars("vars(a * rate + a)").as_deref(),
+            Some("a = 2, rate undefined")
+        );
+        assert_eq!(vars("vars(2 * pi)").as_deref(), Some("no variables"));
+        assert_eq!(vars("sqrt(a)"), None);
+    }
+}
@@ -0,0 +1,257 @@
+//! Minimal JSON for variable files.
+//!
+//! Variables are exchanged as one flat object mapping names to numbers, such
+//! as `{"vat": 0.19, "discount": 0.05}`. Only that shape is read and written;
+//! `true` and `false` are read as 1 and 0.
+
+use std::fmt::Write as _;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// Writes `entries` as a JSON object, one entry per line.
+///
+/// # Errors
+/// Returns a message naming the first entry whose value JSON cannot
+/// represent, such as infinity or NaN.
+pub fn write_object<'a>(
+    entries: impl IntoIterator<Item = (&'a str, f64)>,
+) -> Result<String, String> {
+    let fields = entries
+        .into_iter()
+        .map(|(name, value)| {
+            if !value.is_finite() {
+                return Err(format!("'{name}' is {value}, which JSON cannot hold"));
+            }
+            let mut field = String::from("  ");
+            write_string(&mut field, name);
+            let _ = write!(field, ": {value}");
+            Ok(field)
+        })
+        .collect::<Result<Vec<String>, String>>()?;
+    if fields.is_empty() {
+        Ok("{}\n".to_string())
+    } else {
+        Ok(format!("{{\n{}\n}}\n", fields.join(",\n")))
+    }
+}
+
+/// Appends `text` as a quoted JSON string.
+fn write_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Reads a JSON object mapping names to numbers, in file order.
+///
+/// # Errors
+/// Returns a message naming the position of the first problem if `text` is
+/// not such an object, or naming the entry whose number is too large for a
+/// float, such as `1e999`.
+pub fn parse_object(text: &str) -> Result<Vec<(String, f64)>, String> {
+    let mut reader = Reader {
+        text,
+        chars: text.char_indices().peekable(),
+    };
+    reader.expect('{')?;
+    let mut entries = Vec::new();
+    if !reader.eat('}') {
+        loop {
+            let name = reader.string()?;
+            reader.expect(':')?;
+            let value = reader.value(&name)?;
+            entries.push((name, value));
+            if reader.eat('}') {
+                break;
+            }
+            reader.expect(',')?;
+        }
+    }
+    reader.skip_whitespace();
+    match reader.chars.peek() {
+        None => Ok(entries),
+        Some(&(position, _)) => Err(format!("unexpected text after the object at {position}")),
+    }
+}
+
+/// Reads JSON tokens from a string.
+struct Reader<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Reader<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    /// Returns the byte position of the next character, or the end.
+    fn position(&mut self) -> usize {
+        self.chars.peek().map_or(self.text.len(), |&(i, _)| i)
+    }
+
+    /// Consumes `expected` after any whitespace, if it comes next.
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        self.chars.next_if(|&(_, c)| c == expected).is_some()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(format!("expected '{expected}' at {}", self.position()))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            let position = self.position();
+            match self.chars.next() {
+                None => return Err("unterminated string".to_string()),
+                Some((_, '"')) => return Ok(text),
+                Some((_, '\\')) => {
+                    let escaped = match self.chars.next().map(|(_, c)| c) {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let digits: String = (0..4)
+                                .filter_map(|_| self.chars.next())
+                                .map(|(_, c)| c)
+                                .collect();
+                            u32::from_str_radix(&digits, 16)
+                                .ok()
+                                .filter(|_| digits.len() == 4)
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| format!("invalid escape at {position}"))?
+                        }
+                        _ => return Err(format!("invalid escape at {position}")),
+                    };
+                    text.push(escaped);
+                }
+                Some((_, c)) => text.push(c),
+            }
+        }
+    }
+
+    /// Reads the value of the entry `name`.
+    fn value(&mut self, name: &str) -> Result<f64, String> {
+        self.skip_whitespace();
+        let start = self.position();
+        while self
+            .chars
+            .next_if(|(_, c)| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.'))
+            .is_some()
+        {}
+        let word = &self.text[start..self.position()];
+        match word {
+            "true" => Ok(1.0),
+            "false" => Ok(0.0),
+            _ if word.starts_with(|c: char| c == '-' || c.is_ascii_digit()) => word
+                .parse()
+                .ok()
+                .filter(|value: &f64| value.is_finite())
+                .ok_or_else(|| format!("invalid number '{word}' for '{name}'")),
+            _ => Err(format!("expected a number for '{name}' at {start}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_object_in_file_order() {
+        let entries = parse_object(r#" { "vat": 0.19, "n": -2e3, "gross": true } "#).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ("vat".to_string(), 0.19),
+                ("n".to_string(), -2000.0),
+                ("gross".to_string(), 1.0),
+            ]
+        );
+        assert_eq!(parse_object("{}"), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_parse_object_rejects_other_shapes() {
+        assert_eq!(
+            parse_object(r#"{"a": "x"}"#),
+            Err("expected a number for 'a' at 6".to_string())
+        );
+        assert_eq!(
+            parse_object(r#"{"a": [1]}"#),
+            Err("expected a number for 'a' at 6".to_string())
+        );
+        assert_eq!(
+            parse_object(r#"{"a": inf}"#),
+            Err("expected a number for 'a' at 6".to_string())
+        );
+        assert_eq!(parse_object("[1]"), Err("expected '{' at 0".to_string()));
+        assert_eq!(
+            parse_object(r#"{"a": 1} x"#),
+            Err("unexpected text after the object at 9".to_string())
+        );
+        assert!(parse_object(r#"{"a": 1"#).is_err());
+        assert!(parse_object(r#"{"a": 1.2.3}"#).is_err());
+        assert_eq!(
+            parse_object(r#"{"a": 1e999}"#),
+            Err("invalid number '1e999' for 'a'".to_string())
+        );
+        assert_eq!(
+            parse_object(r#"{"a": -inf}"#),
+            Err("invalid number '-inf' for 'a'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_string_escapes() {
+        let entries = parse_object(r#"{"ab\"": 1}"#).unwrap();
+        assert_eq!(entries, vec![("ab\"".to_string(), 1.0)]);
+    }
+
+    #[test]
+    fn test_write_object_round_trips() {
+        let text = write_object([("vat", 0.19), ("big", 1e21)]).unwrap();
+        assert_eq!(
+            text,
+            "{\n  \"vat\": 0.19,\n  \"big\": 1000000000000000000000\n}\n"
+        );
+        assert_eq!(
+            parse_object(&text).unwrap(),
+            vec![("vat".to_string(), 0.19), ("big".to_string(), 1e21)]
+        );
+        assert_eq!(write_object([]), Ok("{}\n".to_string()));
+    }
+
+    #[test]
+    fn test_write_object_rejects_values_json_cannot_hold() {
+        assert_eq!(
+            write_object([("vat", 0.19), ("limit", f64::INFINITY)]),
+            Err("'limit' is inf, which JSON cannot hold".to_string())
+        );
+        assert_eq!(
+            write_object([("ratio", f64::NAN)]),
+            Err("'ratio' is NaN, which JSON cannot hold".to_string())
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Limits on the size of worksheets and expressions.
+//!
+//! The whole buffer is evaluated on every frame, so a pasted megabyte of
+//! text or thousands of nested parentheses could freeze the interface or
+//! overflow the stack. Lines longer than the limit and lines past the last
+//! allowed one are reported as errors instead of being evaluated, and the
+//! parser rejects expressions nested too deeply or calls with too many
+//! arguments. The editor refuses edits that would break the limits.
+
+/// The largest worksheet and expressions that are evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The most characters a line may have.
+    pub line_length: usize,
+    /// The most lines a worksheet may have.
+    pub lines: usize,
+    /// The most levels an expression may be nested.
+    pub depth: usize,
+    /// The most arguments a function call may have.
+    pub list: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            line_length: 10_000,
+            lines: 10_000,
+            depth: 200,
+            list: 1_000,
+        }
+    }
+}
+
+impl Limits {
+    /// Returns the message for a line longer than allowed.
+    #[must_use]
+    pub fn line_length_message(self) -> String {
+        format!(
+            "line is longer than {} characters (see maxlength)",
+            self.line_length
+        )
+    }
+
+    /// Returns the message for a line past the last allowed one.
+    #[must_use]
+    pub fn lines_message(self) -> String {
+        format!(
+            "worksheet has more than {} lines (see maxlines)",
+            self.lines
+        )
+    }
+
+    /// Returns the message for an expression nested too deeply.
+    #[must_use]
+    pub fn depth_message(self) -> String {
+        format!(
+            "expression is nested more than {} levels deep (see maxdepth)",
+            self.depth
+        )
+    }
+
+    /// Returns the message for a call with too many arguments.
+    #[must_use]
+    pub fn list_message(self) -> String {
+        format!("more than {} arguments (see maxlist)", self.list)
+    }
+}
@@ -0,0 +1,163 @@
+//! Lists of values: `list(12.5, 3, 7)`, `csv(...)`, the shares of
+//! `split(100, 3)` and `ratio(2, 3, 1000)`, and variables assigned from
+//! them.
+//!
+//! A list is not a number. Passed to a function it is spread into separate
+//! arguments, so `sum(prices)` adds its values; anywhere else it is an
+//! error. A line that is a list on its own shows its first values and how
+//! many it has, and `prices = list(...)` keeps the values under a name.
+//! Columns of numbers pasted from a spreadsheet become such a line.
+
+use crate::eval::csv::{self, CSV_FUNCTION};
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::finance;
+use crate::eval::functions::Argument;
+
+/// The name of the function that writes a list.
+pub const LIST_FUNCTION: &str = "list";
+
+/// A function of numbers giving a list of at most the given number of
+/// values.
+pub(crate) type NumericListFn = fn(&[f64], usize) -> Result<Vec<f64>, EvalError>;
+
+/// Functions of numbers that give a list of values.
+const NUMERIC_LIST_FUNCTIONS: &[(&str, NumericListFn)] = &[
+    ("split", finance::split),
+    ("ratio", |args, _| finance::ratio(args).map(Vec::from)),
+];
+
+/// Number of values shown for a list.
+const SHOWN_VALUES: usize = 5;
+
+/// What to do with a list where a number is expected.
+const LIST_HINT: &str = "pass it to a function such as sum, avg or count";
+
+/// Returns `true` if calls to function `name` give a list.
+#[must_use]
+pub fn is_list_function(name: &str) -> bool {
+    name == LIST_FUNCTION || name == CSV_FUNCTION || numeric_list_function(name).is_some()
+}
+
+/// Returns the function of numbers named `name` that gives a list, such as
+/// `split`.
+pub(crate) fn numeric_list_function(name: &str) -> Option<NumericListFn> {
+    NUMERIC_LIST_FUNCTIONS
+        .iter()
+        .find(|(function, _)| *function == name)
+        .map(|&(_, function)| function)
+}
+
+/// Returns the error for a call to the list function `name` where a single
+/// number is expected.
+pub(crate) fn list_call_error(name: &str) -> EvalError {
+    EvalError::new(format!("{name} gives a list of values; {LIST_HINT}")).with_code(ErrorCode::List)
+}
+
+/// Implements `list(a, b, ...)` where a single number is expected.
+pub(crate) fn list_argument(_args: &[Argument]) -> Result<f64, EvalError> {
+    Err(list_call_error(LIST_FUNCTION))
+}
+
+/// Returns the error for the list variable `name` used as a number.
+#[must_use]
+pub fn list_variable_error(name: &str) -> EvalError {
+    EvalError::new(format!("{name} is a list; {LIST_HINT}")).with_code(ErrorCode::List)
+}
+
+/// Returns the error for `csv(...)` used as a number.
+pub(crate) fn csv_error() -> EvalError {
+    list_call_error(CSV_FUNCTION)
+}
+
+/// Describes a list by its first values and its length, such as
+/// `[12.5, 3, 7] (3 values)`.
+#[must_use]
+pub fn format_list(values: &[f64]) -> String {
+    let mut shown: Vec<String> = values
+        .iter()
+        .take(SHOWN_VALUES)
+        .map(f64::to_string)
+        .collect();
+    if values.len() > SHOWN_VALUES {
+        shown.push("…".to_string());
+    }
+    let count = match values.len() {
+        1 => "1 value".to_string(),
+        n => format!("{n} values"),
+    };
+    format!("[{}] ({count})", shown.join(", "))
+}
+
+/// Reads a column of numbers, one per line, such as cells copied from a
+/// spreadsheet. Blank lines are skipped and commas between thousands are
+/// allowed.
+///
+/// # Errors
+/// Returns a message naming the first line that is not a number, or saying
+/// there are no numbers or more than `max_values`, the `maxlist` limit.
+pub fn parse_column(text: &str, max_values: usize) -> Result<Vec<f64>, String> {
+    let mut values = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let cell = line.trim();
+        if cell.is_empty() {
+            continue;
+        }
+        if values.len() == max_values {
+            return Err(format!("more than {max_values} numbers (see maxlist)"));
+        }
+        let value = csv::parse_number(cell)
+            .ok_or_else(|| format!("line {}: \"{cell}\" is not a number", index + 1))?;
+        values.push(value);
+    }
+    if values.is_empty() {
+        return Err("no numbers".to_string());
+    }
+    Ok(values)
+}
+
+/// Writes the line assigning `values` to `name`, such as
+/// `prices = list(12.5, 3, 7)`.
+#[must_use]
+pub fn list_line(name: &str, values: &[f64]) -> String {
+    let values: Vec<String> = values.iter().map(f64::to_string).collect();
+    format!("{name} = {LIST_FUNCTION}({})", values.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_column() {
+        assert_eq!(
+            parse_column("12.5\r\n\n  3\n\"1,000\"\n-7\n", 10),
+            Err("line 4: \"\"1,000\"\" is not a number".to_string())
+        );
+        assert_eq!(
+            parse_column("12.5\r\n\n  3\n1,000\n-7\n", 10),
+            Ok(vec![12.5, 3.0, 1000.0, -7.0])
+        );
+        assert_eq!(parse_column(" \n", 10), Err("no numbers".to_string()));
+
+        assert_eq!(
+            parse_column("1\n2\n3\n", 2),
+            Err("more than 2 numbers (see maxlist)".to_string())
+        );
+        assert_eq!(parse_column("1\n2\n", 2), Ok(vec![1.0, 2.0]));
+    }
+
+    #[test]
+    fn test_format_list_and_line() {
+        assert_eq!(format_list(&[12.5, 3.0]), "[12.5, 3] (2 values)");
+        assert_eq!(format_list(&[1.0]), "[1] (1 value)");
+        assert_eq!(
+            format_list(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+            "[1, 2, 3, 4, 5, …] (6 values)"
+        );
+        assert_eq!(format_list(&[]), "[] (0 values)");
+        assert_eq!(
+            list_line("prices", &[12.5, -3.0]),
+            "prices = list(12.5, -3)"
+        );
+    }
+}
@@ -4,19 +4,55 @@
 //! expressions, managing variable context, and producing results or errors.
 
 pub mod ast;
+pub mod cache;
+pub mod calculus;
 pub mod constants;
 pub mod context;
+pub mod convert;
+pub mod csv;
+pub mod datasize;
+pub mod deps;
+pub mod directive;
+pub mod duration;
 pub mod error;
 pub mod evaluator;
+pub mod explain;
+pub mod finance;
+pub mod format;
+pub mod functions;
+pub mod inspect;
+pub mod json;
+pub mod limits;
+pub mod list;
+pub mod money;
+pub mod network;
+pub mod numformat;
 pub mod parser;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod shell;
+pub mod sigfigs;
+pub mod simplify;
+pub mod solve;
 pub mod token;
+pub mod units;
 
-use crate::eval::ast::Parser;
-use crate::eval::token::Tokenizer;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::eval::ast::{Expr, Parser};
+use crate::eval::cache::{LINE_CACHE_CAPACITY, LineCache};
+use crate::eval::constants::is_math_constant;
+use crate::eval::directive::FormatDirective;
+use crate::eval::limits::Limits;
+use crate::eval::money::Money;
+use crate::eval::token::{TokenOptions, Tokenizer};
 
 pub use context::EvalContext;
-pub use error::{ErrorSpan, EvalError};
-pub use parser::{ParsedLine, parse_line};
+pub use error::{ErrorCode, ErrorSpan, EvalError, Severity};
+pub use evaluator::{AngleMode, DivisionMode};
+pub use parser::{DISABLED_PREFIX, ParsedLine, parse_line, parse_line_with_presets};
 
 /// Result of evaluating a single line.
 #[derive(Debug, Clone, PartialEq)]
@@ -30,10 +66,107 @@ pub enum LineResult {
         /// The value that was assigned.
         value: f64,
     },
+    /// Several variables assigned on one line, in order.
+    Assignments(Vec<(String, f64)>),
+    /// A data size in bytes, optionally assigned to a variable.
+    Bytes {
+        /// The variable name, if the line is an assignment.
+        name: Option<String>,
+        /// The size in bytes.
+        value: f64,
+        /// Whether to display the size with binary prefixes.
+        binary: bool,
+    },
+    /// An amount of money, optionally assigned to a variable.
+    Money {
+        /// The variable name, if the line is an assignment.
+        name: Option<String>,
+        /// The amount, rounded to minor units of its currency.
+        money: Money,
+    },
+    /// A number shown as text, such as a Roman numeral, optionally assigned
+    /// to a variable.
+    Text {
+        /// The variable name, if the line is an assignment.
+        name: Option<String>,
+        /// The numeric value, used in further calculations.
+        value: f64,
+        /// How the value is displayed.
+        text: String,
+    },
+    /// An expression shown in symbolic form, such as the result of
+    /// `simplify(2*x + 3*x)` or the variable listing of `vars(a + b)`.
+    Symbolic(String),
+    /// A function's values at several points, from `table(f, x, from, to)`,
+    /// shown as rows below the line.
+    Table(calculus::Table),
+    /// The sum of the numeric results since the previous `total` marker.
+    Total(f64),
     /// An empty line (no result).
     Empty,
     /// An evaluation error.
     Error(EvalError),
+    /// A result whose computation overflowed or lost precision.
+    Warning {
+        /// The result, which is still shown.
+        result: Box<Self>,
+        /// The problem, with `Severity::Warning`.
+        warning: EvalError,
+    },
+}
+
+impl LineResult {
+    /// Returns the result without any attached warning.
+    #[must_use]
+    pub fn without_warning(&self) -> &Self {
+        match self {
+            Self::Warning { result, .. } => result,
+            result => result,
+        }
+    }
+
+    /// Returns the numeric value of the line, if it has one.
+    #[must_use]
+    pub fn number(&self) -> Option<f64> {
+        match self.without_warning() {
+            Self::Value(value)
+            | Self::Assignment { value, .. }
+            | Self::Bytes { value, .. }
+            | Self::Text { value, .. }
+            | Self::Total(value) => Some(*value),
+            Self::Money { money, .. } => Some(money.value()),
+            _ => None,
+        }
+    }
+
+    /// Returns the attached warning message, if any.
+    #[must_use]
+    pub fn warning(&self) -> Option<&str> {
+        match self {
+            Self::Warning { warning, .. } => Some(warning.message()),
+            _ => None,
+        }
+    }
+
+    /// Returns the error of the line, or the warning attached to its result.
+    #[must_use]
+    pub const fn problem(&self) -> Option<&EvalError> {
+        match self {
+            Self::Error(error) | Self::Warning { warning: error, .. } => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Attaches `warning` to the result when there is one.
+    fn with_warning(self, warning: Option<EvalError>) -> Self {
+        match warning {
+            Some(warning) => Self::Warning {
+                result: Box::new(self),
+                warning,
+            },
+            None => self,
+        }
+    }
 }
 
 /// Evaluates a single expression string using the given context.
@@ -49,15 +182,199 @@ pub enum LineResult {
 /// Returns an `EvalError` if the expression is invalid, contains undefined
 /// variables, or results in a runtime error (e.g., division by zero).
 pub fn evaluate_expression(expression: &str, context: &EvalContext) -> Result<f64, EvalError> {
+    evaluate_expression_with_warning(expression, context).map(|(value, _, _)| value)
+}
+
+/// Like `evaluate_expression`, but also returns the exact integer result
+/// when the `f64` cannot hold it, and a warning when the computation
+/// overflowed or lost precision.
+fn evaluate_expression_with_warning(
+    expression: &str,
+    context: &EvalContext,
+) -> Result<(f64, Option<i64>, Option<EvalError>), EvalError> {
+    let ast = parse_expression_cached(expression, context);
+    let ast = ast.as_ref().as_ref().map_err(Clone::clone)?;
+    evaluator::evaluate_exact_in_context(ast, context).map(|(value, exact, warning)| {
+        let warning = warning.map(|w| EvalError::warning(w).with_code(ErrorCode::Precision));
+        (value, exact, warning)
+    })
+}
+
+/// Shows the whole number `exact`, which `value` cannot hold, in full, for
+/// lines such as `2^60 + 1`. Data sizes and durations are shown their own
+/// way instead.
+fn exact_result(
+    name: Option<String>,
+    value: f64,
+    exact: Option<i64>,
+    size: Option<bool>,
+    time: i32,
+) -> Option<LineResult> {
+    let exact = exact.filter(|_| size.is_none() && time == 0)?;
+    Some(LineResult::Text {
+        name,
+        value,
+        text: exact.to_string(),
+    })
+}
+
+/// Chooses how the value of `expression` is shown: as text for calls such
+/// as `to_roman(2024)`, as a data size when `size` gives its prefix family,
+/// as a duration or rate when `time` is its power of time, rounded to
+/// `figures` significant figures when that mode is on, or as a plain number,
+/// assigned to `name` if given.
+fn shaped_result(
+    expression: &str,
+    name: Option<String>,
+    value: f64,
+    size: Option<bool>,
+    time: i32,
+    figures: Option<u32>,
+    context: &EvalContext,
+) -> LineResult {
+    if let Some(text) = text_form(expression, value, context) {
+        return LineResult::Text { name, value, text };
+    }
+    if size.is_none()
+        && let Some(text) = duration::duration_text(
+            value,
+            time,
+            duration::rate_unit(expression, context.token_options()),
+        )
+    {
+        return LineResult::Text { name, value, text };
+    }
+    if size.is_none()
+        && context.significant_figures_mode()
+        && let Some(text) = figures.and_then(|figures| sigfigs::format_significant(value, figures))
+    {
+        return LineResult::Text { name, value, text };
+    }
+    match (size, name) {
+        (Some(binary), name) => LineResult::Bytes {
+            name,
+            value,
+            binary,
+        },
+        (None, Some(name)) => LineResult::Assignment { name, value },
+        (None, None) => LineResult::Value(value),
+    }
+}
+
+/// Returns the text shown for `expression` instead of `value`, for lines
+/// such as `to_roman(2024)` or `netmask(24)`.
+fn text_form(expression: &str, value: f64, context: &EvalContext) -> Option<String> {
+    let ast = parse_expression_cached(expression, context);
+    ast.as_ref().as_ref().ok().and_then(|ast| {
+        convert::text_form(ast, value, context).or_else(|| network::text_form(ast, value))
+    })
+}
+
+/// Returns the text shown for `expression` if it is a `simplify` or `vars`
+/// call, which are displayed without being evaluated.
+fn symbolic_form(expression: &str, context: &EvalContext) -> Option<String> {
+    let ast = parse_expression_cached(expression, context);
+    ast.as_ref().as_ref().ok().and_then(|ast| {
+        simplify::symbolic_form(ast, context.angle_mode())
+            .or_else(|| inspect::variables_form(ast, context))
+    })
+}
+
+/// Returns the table for `expression` if it is a `table` call, which is
+/// displayed below its line without being evaluated to a value.
+fn table_form(
+    expression: &str,
+    context: &EvalContext,
+) -> Option<Result<calculus::Table, EvalError>> {
+    let ast = parse_expression_cached(expression, context);
+    ast.as_ref()
+        .as_ref()
+        .ok()
+        .and_then(|ast| calculus::table_form(ast, context.variables(), context.angle_mode()))
+}
+
+/// Returns the values of `expression` if it is a list, such as a `csv`
+/// call, which is shown by its first values rather than evaluated to one.
+fn list_form(expression: &str, context: &EvalContext) -> Option<Result<Vec<f64>, EvalError>> {
+    let ast = parse_expression_cached(expression, context);
+    ast.as_ref()
+        .as_ref()
+        .ok()
+        .and_then(|ast| evaluator::evaluate_list(ast, context))
+}
+
+thread_local! {
+    /// Parse results by expression text, so unchanged lines are not parsed again.
+    static PARSED_EXPRESSIONS: RefCell<ParseCache> =
+        RefCell::new(LineCache::new(LINE_CACHE_CAPACITY));
+}
+
+/// Parse results by expression text, for the token options and limits they
+/// were parsed with.
+type ParseCache = LineCache<(TokenOptions, Limits), Rc<Result<Expr, EvalError>>>;
+
+/// Like `parse_expression_in`, reusing the result for text parsed before.
+fn parse_expression_cached(expression: &str, context: &EvalContext) -> Rc<Result<Expr, EvalError>> {
+    let options = (context.token_options().clone(), context.limits());
+    PARSED_EXPRESSIONS.with_borrow_mut(|cache| {
+        cache.get_or_insert_with(expression, &options, |expression| {
+            Rc::new(parse_expression_in(expression, context))
+        })
+    })
+}
+
+/// Parses an expression string into its AST without evaluating it.
+///
+/// # Errors
+/// Returns an `EvalError` if the expression cannot be tokenized or parsed.
+pub fn parse_expression(expression: &str) -> Result<Expr, EvalError> {
     let tokens = Tokenizer::new(expression).tokenize()?;
-    let ast = Parser::new(tokens).parse()?;
-    evaluator::evaluate(&ast, context.variables())
+    Ok(Parser::new(tokens).parse()?)
+}
+
+/// Like `parse_expression`, reading number literals and applying the
+/// limits as the settings of `context` say.
+///
+/// # Errors
+/// Returns an `EvalError` if the expression cannot be tokenized or parsed.
+pub fn parse_expression_in(expression: &str, context: &EvalContext) -> Result<Expr, EvalError> {
+    let tokens = Tokenizer::with_options(expression, context.token_options()).tokenize()?;
+    Ok(Parser::with_limits(tokens, context.limits()).parse()?)
+}
+
+/// Evaluates the longest valid prefix of an expression whose tail cannot be
+/// parsed, such as `5 + 3` of `5 + 3 +`, to show an intermediate result while
+/// the expression is being typed.
+///
+/// Returns `None` if the whole expression parses, or if no prefix of it is a
+/// complete expression that evaluates.
+#[must_use]
+pub fn evaluate_prefix(expression: &str, context: &EvalContext) -> Option<f64> {
+    let tokens = match Tokenizer::with_options(expression, context.token_options()).tokenize() {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            let valid = expression.get(..err.position)?;
+            return evaluate_expression(valid, context)
+                .ok()
+                .or_else(|| evaluate_prefix(valid, context));
+        }
+    };
+    let mut parser = Parser::with_limits(tokens, context.limits());
+    if parser
+        .parse()
+        .map_or_else(|err| err.code == ErrorCode::Limit, |_| true)
+    {
+        return None;
+    }
+    let (expr, _) = parser.parse_prefix()?;
+    evaluator::evaluate_in_mode(&expr, context.variables(), context.angle_mode()).ok()
 }
 
 /// Evaluates a single line and returns the result.
 ///
 /// This function parses the line, evaluates it if necessary, and updates
-/// the context for assignments.
+/// the context for assignments. Numeric results are added to the running sum
+/// shown by the next `total` marker.
 ///
 /// # Arguments
 /// * `line` - The line to evaluate
@@ -66,24 +383,412 @@ pub fn evaluate_expression(expression: &str, context: &EvalContext) -> Result<f6
 /// # Returns
 /// A `LineResult` indicating the outcome of evaluation.
 pub fn evaluate_line(line: &str, context: &mut EvalContext) -> LineResult {
-    match parse_line(line) {
-        ParsedLine::Empty => LineResult::Empty,
-        ParsedLine::Expression(expr) => match evaluate_expression(&expr, context) {
-            Ok(value) => LineResult::Value(value),
+    let limits = context.limits();
+    if line.chars().count() > limits.line_length {
+        return LineResult::Error(
+            EvalError::new(limits.line_length_message()).with_code(ErrorCode::Limit),
+        );
+    }
+    let (line, directive) = directive::split_format_directive(line, context.format_presets());
+    let result = evaluate_line_uncounted(line, context);
+    let result = match directive.as_ref() {
+        Some(directive) => formatted_result(result, directive),
+        None => default_formatted_result(result, context.format_presets().default_format()),
+    };
+    match result.without_warning() {
+        LineResult::Value(value)
+        | LineResult::Assignment { value, .. }
+        | LineResult::Bytes { value, .. }
+        | LineResult::Text { value, .. } => context.add_to_subtotal(*value),
+        LineResult::Money { money, .. } => context.add_to_subtotal(money.value()),
+        LineResult::Assignments(assigned) => {
+            for (_, value) in assigned {
+                context.add_to_subtotal(*value);
+            }
+        }
+        LineResult::Symbolic(_)
+        | LineResult::Table(_)
+        | LineResult::Total(_)
+        | LineResult::Empty
+        | LineResult::Error(_)
+        | LineResult::Warning { .. } => {}
+    }
+    result
+}
+
+/// Shows the value of `result` as `directive` asks, for lines such as
+/// `255 | hex`.
+///
+/// Results without a single value, such as totals and tables, are left as
+/// they are. A value the directive cannot show keeps its usual display with
+/// a warning.
+fn formatted_result(result: LineResult, directive: &FormatDirective) -> LineResult {
+    let (name, value) = match result {
+        LineResult::Value(value) => (None, value),
+        LineResult::Assignment { name, value } => (Some(name), value),
+        LineResult::Bytes { name, value, .. } | LineResult::Text { name, value, .. } => {
+            (name, value)
+        }
+        LineResult::Money { name, money } => (name, money.value()),
+        LineResult::Warning { result, warning } => {
+            return formatted_result(*result, directive).with_warning(Some(warning));
+        }
+        result => return result,
+    };
+    match directive.format(value) {
+        Ok(text) => LineResult::Text { name, value, text },
+        Err(message) => {
+            let result = name.map_or(LineResult::Value(value), |name| LineResult::Assignment {
+                name,
+                value,
+            });
+            result.with_warning(Some(EvalError::warning(message)))
+        }
+    }
+}
+
+/// Shows a plain number in the `default` format chosen in the settings, if
+/// any.
+///
+/// Results shown in their own way, such as data sizes and durations, and
+/// values the format cannot show keep their usual display.
+fn default_formatted_result(result: LineResult, default: Option<&FormatDirective>) -> LineResult {
+    let Some(directive) = default else {
+        return result;
+    };
+    match result.without_warning() {
+        LineResult::Value(value) | LineResult::Assignment { value, .. }
+            if directive.format(*value).is_ok() =>
+        {
+            formatted_result(result, directive)
+        }
+        _ => result,
+    }
+}
+
+/// Evaluates a single line without updating the running sum.
+fn evaluate_line_uncounted(line: &str, context: &mut EvalContext) -> LineResult {
+    #[cfg(feature = "scripting")]
+    if let Some(body) = script::script_body(line) {
+        return match script::run_script(body, context) {
+            Ok(Some(value)) => LineResult::Value(value),
+            Ok(None) => LineResult::Empty,
+            Err(e) => LineResult::Error(e),
+        };
+    }
+
+    let parsed = match parse_line_with_presets(line, context.format_presets()) {
+        // A variable named `total` takes precedence over the marker.
+        ParsedLine::Total if line.trim() == "total" && context.get_variable("total").is_some() => {
+            ParsedLine::Expression("total".to_string())
+        }
+        parsed => parsed,
+    };
+
+    match parsed {
+        ParsedLine::Empty | ParsedLine::Disabled => LineResult::Empty,
+        ParsedLine::SectionStart(_) => {
+            context.push_scope();
+            LineResult::Empty
+        }
+        ParsedLine::SectionEnd => end_section(context),
+        ParsedLine::Total => LineResult::Total(context.take_subtotal()),
+        ParsedLine::Unset(names) => match unset_variables(&names, context) {
+            Ok(()) => LineResult::Empty,
             Err(e) => LineResult::Error(e),
         },
-        ParsedLine::Assignment { name, expression } => {
-            match evaluate_expression(&expression, context) {
-                Ok(value) => {
-                    context.set_variable(&name, value);
-                    LineResult::Assignment { name, value }
+        ParsedLine::ClearVariables => clear_variables(context),
+        ParsedLine::Expression(expr) if let Some(text) = symbolic_form(&expr, context) => {
+            LineResult::Symbolic(text)
+        }
+        ParsedLine::Expression(expr) if let Some(table) = table_form(&expr, context) => {
+            table.map_or_else(LineResult::Error, LineResult::Table)
+        }
+        ParsedLine::Expression(expr) if let Some(values) = list_form(&expr, context) => values
+            .map_or_else(LineResult::Error, |values| {
+                LineResult::Symbolic(list::format_list(&values))
+            }),
+        ParsedLine::Expression(expr) => match evaluate_expression_with_warning(&expr, context) {
+            Ok((value, exact, warning)) => match money_form(&expr, value, context) {
+                Some(Ok(money)) => LineResult::Money { name: None, money }.with_warning(warning),
+                Some(Err(e)) => LineResult::Error(e),
+                None => {
+                    let size = datasize::size_prefixes(&expr, context);
+                    let time = duration::time_power(&expr, context);
+                    let figures = sigfigs::significant_figures(&expr, context);
+                    exact_result(None, value, exact, size, time)
+                        .unwrap_or_else(|| {
+                            shaped_result(&expr, None, value, size, time, figures, context)
+                        })
+                        .with_warning(warning)
                 }
+            },
+            Err(e) => LineResult::Error(e),
+        },
+        ParsedLine::Assignment {
+            name,
+            expression,
+            constant,
+        } => {
+            if context.is_constant(&name) {
+                return LineResult::Error(reassigned_constant_error(line, &name, constant));
+            }
+            if let Some(value) = context.override_value(&name) {
+                return overridden_assignment(name, value, constant, context);
+            }
+            if let Some(values) = list_form(&expression, context) {
+                return assign_list(&name, values, constant, context);
+            }
+            evaluate_assignment(name, &expression, constant, context)
+        }
+        ParsedLine::MultipleAssignment {
+            names,
+            expressions,
+            constant,
+        } => {
+            let warning = override_warning(&names, context);
+            match evaluate_multiple_assignment(line, names, &expressions, constant, context) {
+                Ok(assigned) => LineResult::Assignments(assigned).with_warning(warning),
                 Err(e) => LineResult::Error(e),
             }
         }
     }
 }
 
+/// Assigns the list `values` to `name`, showing its first values.
+fn assign_list(
+    name: &str,
+    values: Result<Vec<f64>, EvalError>,
+    constant: bool,
+    context: &mut EvalContext,
+) -> LineResult {
+    if constant {
+        return LineResult::Error(
+            EvalError::new("a list cannot be a constant").with_code(ErrorCode::List),
+        );
+    }
+    match values {
+        Ok(values) => {
+            let text = format!("{name} = {}", list::format_list(&values));
+            context.set_list(name, values.into());
+            LineResult::Symbolic(text)
+        }
+        Err(e) => LineResult::Error(e),
+    }
+}
+
+/// Evaluates `expression` and assigns its value to `name`, remembering how
+/// the value is shown for later lines that use it.
+fn evaluate_assignment(
+    name: String,
+    expression: &str,
+    constant: bool,
+    context: &mut EvalContext,
+) -> LineResult {
+    let (value, exact, warning) = match evaluate_expression_with_warning(expression, context) {
+        Ok(evaluated) => evaluated,
+        Err(e) => return LineResult::Error(e),
+    };
+    let money = match money_form(expression, value, context) {
+        Some(Ok(money)) => Some(money),
+        Some(Err(e)) => return LineResult::Error(e),
+        None => None,
+    };
+    let value = money.map_or(value, Money::value);
+    let size = datasize::size_prefixes(expression, context);
+    let time = duration::time_power(expression, context);
+    let figures = sigfigs::significant_figures(expression, context);
+    if constant {
+        context.set_constant(&name, value);
+    } else {
+        context.set_variable(&name, value);
+    }
+    context.set_size_prefixes(&name, size);
+    context.set_time_power(&name, time);
+    context.set_currency(&name, money.map(|money| money.currency));
+    context.set_significant_figures(&name, figures);
+    let exact = exact.filter(|_| money.is_none());
+    context.set_exact_integer(&name, exact);
+    match money {
+        Some(money) => LineResult::Money {
+            name: Some(name),
+            money,
+        },
+        None => exact_result(Some(name.clone()), value, exact, size, time).unwrap_or_else(|| {
+            shaped_result(expression, Some(name), value, size, time, figures, context)
+        }),
+    }
+    .with_warning(warning)
+}
+
+/// Rounds `value` to minor units of the currency of `expression`, if it
+/// involves money, for lines such as `$12.50 * 3`.
+fn money_form(
+    expression: &str,
+    value: f64,
+    context: &EvalContext,
+) -> Option<Result<Money, EvalError>> {
+    let currency = money::currency(expression, context).transpose()?;
+    Some(currency.and_then(|currency| {
+        Money::from_value(value, currency, context.rounding()).ok_or_else(|| {
+            EvalError::new("amount is too large to count in minor units")
+                .with_code(ErrorCode::Domain)
+        })
+    }))
+}
+
+/// Closes the innermost `@section`.
+fn end_section(context: &mut EvalContext) -> LineResult {
+    if context.pop_scope() {
+        LineResult::Empty
+    } else {
+        LineResult::Error(
+            EvalError::new("@end without matching @section").with_code(ErrorCode::Statement),
+        )
+    }
+}
+
+/// Removes all user variables, which is only allowed outside of sections.
+fn clear_variables(context: &mut EvalContext) -> LineResult {
+    if context.scope_depth() > 0 {
+        LineResult::Error(
+            EvalError::new("clearvars cannot be used inside a section")
+                .with_code(ErrorCode::Statement),
+        )
+    } else {
+        context.clear_variables();
+        LineResult::Empty
+    }
+}
+
+/// Removes each of `names`, which must be user variables.
+fn unset_variables(names: &[String], context: &mut EvalContext) -> Result<(), EvalError> {
+    for name in names {
+        if context.is_constant(name) || is_math_constant(name) {
+            return Err(EvalError::new(format!("cannot unset constant '{name}'"))
+                .with_code(ErrorCode::ReassignedConstant));
+        }
+        if !context.unset_variable(name) {
+            return Err(EvalError::undefined_variable(name));
+        }
+    }
+    Ok(())
+}
+
+/// Evaluates `a, b = x, y`: every expression is evaluated before any name is
+/// assigned, so the names can be swapped.
+fn evaluate_multiple_assignment(
+    line: &str,
+    names: Vec<String>,
+    expressions: &[String],
+    constant: bool,
+    context: &mut EvalContext,
+) -> Result<Vec<(String, f64)>, EvalError> {
+    if names.len() != expressions.len() {
+        return Err(EvalError::new(format!(
+            "expected {} values, got {}",
+            names.len(),
+            expressions.len()
+        ))
+        .with_code(ErrorCode::Statement));
+    }
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) {
+            return Err(EvalError::new(format!("'{name}' is assigned twice"))
+                .with_code(ErrorCode::Statement));
+        }
+        if context.is_constant(name) {
+            return Err(reassigned_constant_error(line, name, constant));
+        }
+    }
+
+    let values = expressions
+        .iter()
+        .map(|expression| {
+            let (value, exact, _) = evaluate_expression_with_warning(expression, context)?;
+            let money = money_form(expression, value, context).transpose()?;
+            Ok((
+                money.map_or(value, Money::value),
+                money.map(|money| money.currency),
+                datasize::size_prefixes(expression, context),
+                duration::time_power(expression, context),
+                sigfigs::significant_figures(expression, context),
+                exact.filter(|_| money.is_none()),
+            ))
+        })
+        .collect::<Result<Vec<_>, EvalError>>()?;
+
+    Ok(names
+        .into_iter()
+        .zip(values)
+        .map(|(name, (value, currency, size, time, figures, exact))| {
+            let overridden = context.override_value(&name);
+            let value = overridden.unwrap_or(value);
+            if constant {
+                context.set_constant(&name, value);
+            } else {
+                context.set_variable(&name, value);
+            }
+            context.set_size_prefixes(&name, size);
+            context.set_time_power(&name, time);
+            context.set_currency(&name, currency);
+            context.set_significant_figures(&name, figures);
+            context.set_exact_integer(&name, exact.filter(|_| overridden.is_none()));
+            (name, value)
+        })
+        .collect())
+}
+
+/// Assigns a what-if override to `name` without evaluating the expression
+/// the line assigns, so the override applies even where it would fail.
+fn overridden_assignment(
+    name: String,
+    value: f64,
+    constant: bool,
+    context: &mut EvalContext,
+) -> LineResult {
+    if constant {
+        context.set_constant(&name, value);
+    } else {
+        context.set_variable(&name, value);
+    }
+    context.set_size_prefixes(&name, None);
+    context.set_time_power(&name, 0);
+    context.set_currency(&name, None);
+    context.set_significant_figures(&name, None);
+    let warning = override_warning(std::slice::from_ref(&name), context);
+    LineResult::Assignment { name, value }.with_warning(warning)
+}
+
+/// Returns the hint marking a line whose assigned `names` have what-if
+/// overrides, or `None` if none of them has.
+fn override_warning(names: &[String], context: &EvalContext) -> Option<EvalError> {
+    let overridden: Vec<&str> = names
+        .iter()
+        .filter(|name| context.override_value(name).is_some())
+        .map(String::as_str)
+        .collect();
+    (!overridden.is_empty()).then(|| {
+        EvalError::warning(format!("what-if override of {}", overridden.join(", ")))
+            .with_code(ErrorCode::Override)
+    })
+}
+
+/// Builds the error for an assignment to a constant, spanning the name in `line`.
+fn reassigned_constant_error(line: &str, name: &str, constant: bool) -> EvalError {
+    let mut start = line.len() - line.trim_start().len();
+    if constant {
+        start += "const".len();
+    }
+    start += line[start..].find(name).unwrap_or(0);
+    EvalError::with_span_range(
+        format!("cannot reassign constant '{name}'"),
+        start,
+        start + name.len(),
+    )
+    .with_code(ErrorCode::ReassignedConstant)
+}
+
 /// Evaluates all lines in order, returning results for each line.
 ///
 /// Lines are evaluated from top to bottom. Variable assignments from earlier
@@ -108,6 +813,11 @@ pub fn evaluate_all_lines<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<L
 /// lines are available in later lines. Variables are stored in the provided
 /// context, allowing them to be persisted across evaluations.
 ///
+/// Sections and constant flags left by a previous evaluation are reset first,
+/// so that section variables never leak into the next pass and constants can
+/// be declared again. Sections still open at the end are kept, which lets
+/// callers evaluate the lines above a cursor inside a section.
+///
 /// # Arguments
 /// * `lines` - An iterator of lines to evaluate
 /// * `context` - The evaluation context for variable storage
@@ -118,12 +828,47 @@ pub fn evaluate_all_lines_with_context<'a>(
     lines: impl IntoIterator<Item = &'a str>,
     context: &mut EvalContext,
 ) -> Vec<LineResult> {
+    context.begin_evaluation();
     lines
         .into_iter()
-        .map(|line| evaluate_line(line, context))
+        .enumerate()
+        .map(|(index, line)| evaluate_numbered_line(index, line, context))
         .collect()
 }
 
+/// Like `evaluate_all_lines_with_context`, but also measures how long each
+/// line took to evaluate.
+///
+/// # Returns
+/// The results and the evaluation time of each line, in line order.
+pub fn evaluate_all_lines_timed<'a>(
+    lines: impl IntoIterator<Item = &'a str>,
+    context: &mut EvalContext,
+) -> (Vec<LineResult>, Vec<Duration>) {
+    context.begin_evaluation();
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(index, line)| {
+            let start = Instant::now();
+            let result = evaluate_numbered_line(index, line, context);
+            (result, start.elapsed())
+        })
+        .unzip()
+}
+
+/// Evaluates the line at `index` of a worksheet, unless it is past the last
+/// line the limits allow.
+fn evaluate_numbered_line(index: usize, line: &str, context: &mut EvalContext) -> LineResult {
+    let limits = context.limits();
+    if index >= limits.lines {
+        return LineResult::Error(
+            EvalError::new(limits.lines_message()).with_code(ErrorCode::Limit),
+        );
+    }
+    evaluate_line(line, context)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -247,6 +992,27 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_trailing_error_spans_the_invalid_tail() {
+        let context = EvalContext::new();
+        let err = evaluate_expression("5 + 3 +", &context).unwrap_err();
+        assert_eq!(err.span(), Some(ErrorSpan::new(6, 7)));
+        assert_eq!(err.code(), ErrorCode::Syntax);
+    }
+
+    #[test]
+    fn test_evaluate_prefix_of_partial_expression() {
+        let mut context = EvalContext::new();
+        context.set_variable("x", 4.0);
+
+        assert_eq!(evaluate_prefix("5 + 3 +", &context), Some(8.0));
+        assert_eq!(evaluate_prefix("x * 2 - ", &context), Some(8.0));
+        assert_eq!(evaluate_prefix("2 * 3 $", &context), Some(6.0));
+        assert_eq!(evaluate_prefix("5 + 3", &context), None);
+        assert_eq!(evaluate_prefix("(5 + 3", &context), None);
+        assert_eq!(evaluate_prefix("y + 1 +", &context), None);
+    }
+
     #[test]
     fn test_evaluate_with_predefined_variable() {
         let mut context = EvalContext::new();
@@ -453,6 +1219,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unnecessary_trailing_comma)]
     fn test_log_with_base() {
         let context = EvalContext::new();
         let result = evaluate_expression("log(100, 10)", &context);
@@ -630,6 +1397,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unnecessary_trailing_comma)]
     fn test_integration_cot() {
         let context = EvalContext::new();
         let result = evaluate_expression("cot(pi/4)", &context);
@@ -648,6 +1416,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unnecessary_trailing_comma)]
     fn test_integration_csc() {
         let context = EvalContext::new();
         let result = evaluate_expression("csc(pi/2)", &context);
@@ -868,4 +1637,986 @@ mod tests {
             panic!("Expected Ok result, got {result:?}");
         }
     }
+
+    #[test]
+    fn test_section_variables_do_not_leak() {
+        let results =
+            evaluate_all_lines(["@section a", "x = 1", "@end", "@section b", "x", "@end"]);
+
+        assert!(matches!(results[4], LineResult::Error(_)));
+    }
+
+    #[test]
+    fn test_section_sees_outer_variables_and_restores_shadowed() {
+        let results = evaluate_all_lines(["x = 1", "@section", "x = x + 1", "x", "@end", "x"]);
+
+        assert_eq!(results[3], LineResult::Value(2.0));
+        assert_eq!(results[5], LineResult::Value(1.0));
+    }
+
+    #[test]
+    fn test_section_directives_produce_empty_results() {
+        let results = evaluate_all_lines(["@section totals", "@end"]);
+
+        assert_eq!(results, vec![LineResult::Empty, LineResult::Empty]);
+    }
+
+    #[test]
+    fn test_unmatched_section_end_is_error() {
+        let results = evaluate_all_lines(["@end"]);
+
+        assert!(matches!(results[0], LineResult::Error(_)));
+    }
+
+    #[test]
+    fn test_reevaluation_closes_sections_left_open() {
+        let mut context = EvalContext::new();
+        evaluate_all_lines_with_context(["@section", "x = 1"], &mut context);
+        assert_eq!(context.get_variable("x"), Some(1.0));
+
+        let results = evaluate_all_lines_with_context(["x"], &mut context);
+
+        assert!(matches!(results[0], LineResult::Error(_)));
+        assert_eq!(context.scope_depth(), 0);
+    }
+
+    #[test]
+    fn test_const_declaration_assigns_value() {
+        let results = evaluate_all_lines(["const tax = 0.19", "100 * tax"]);
+
+        assert_eq!(
+            results[0],
+            LineResult::Assignment {
+                name: "tax".to_string(),
+                value: 0.19
+            }
+        );
+        assert_eq!(results[1], LineResult::Value(19.0));
+    }
+
+    #[test]
+    fn test_reassigning_const_is_error_with_span() {
+        let results = evaluate_all_lines(["const tax = 0.19", "  tax = 0.2"]);
+
+        let LineResult::Error(err) = &results[1] else {
+            panic!("expected error, got {:?}", results[1]);
+        };
+        assert_eq!(err.message(), "cannot reassign constant 'tax'");
+        assert_eq!(err.span(), Some(ErrorSpan::new(2, 5)));
+        assert_eq!(err.code(), ErrorCode::ReassignedConstant);
+    }
+
+    #[test]
+    fn test_redeclaring_const_is_error_with_span() {
+        let results = evaluate_all_lines(["const c = 1", "const c = 2"]);
+
+        let LineResult::Error(err) = &results[1] else {
+            panic!("expected error, got {:?}", results[1]);
+        };
+        assert_eq!(err.span(), Some(ErrorSpan::new(6, 7)));
+    }
+
+    #[test]
+    fn test_reassigning_const_keeps_original_value() {
+        let mut context = EvalContext::new();
+        evaluate_all_lines_with_context(["const k = 1", "k = 2"], &mut context);
+
+        assert_eq!(context.get_variable("k"), Some(1.0));
+    }
+
+    #[test]
+    fn test_const_survives_reevaluation() {
+        let mut context = EvalContext::new();
+        evaluate_all_lines_with_context(["const k = 1"], &mut context);
+
+        let results = evaluate_all_lines_with_context(["const k = 1"], &mut context);
+
+        assert!(matches!(results[0], LineResult::Assignment { .. }));
+    }
+
+    #[test]
+    fn test_convert_units_in_assignment() {
+        let mut context = EvalContext::new();
+        let result = evaluate_line("d = convert(26.2, \"miles\", \"km\")", &mut context);
+
+        let LineResult::Assignment { value, .. } = result else {
+            panic!("expected assignment, got {result:?}");
+        };
+        assert!((value - 42.164_812_8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_text_outside_function_call_is_an_error() {
+        let result = evaluate_line("\"km\" + 1", &mut EvalContext::new());
+
+        assert_eq!(
+            result,
+            LineResult::Error(
+                EvalError::new("text \"km\" can only be used as a function argument")
+                    .with_code(ErrorCode::InvalidArgument)
+            )
+        );
+    }
+
+    #[test]
+    fn test_size_literals_produce_bytes_result() {
+        let results = evaluate_all_lines(["1.5 GiB + 300 MB", "3 * 2"]);
+
+        assert_eq!(
+            results[0],
+            LineResult::Bytes {
+                name: None,
+                value: 1_910_612_736.0,
+                binary: true,
+            }
+        );
+        assert_eq!(results[1], LineResult::Value(6.0));
+    }
+
+    #[test]
+    fn test_size_variables_keep_their_prefixes() {
+        let results = evaluate_all_lines(["disk = 2 TB", "disk / 4", "disk = 5", "disk"]);
+
+        assert_eq!(
+            results[0],
+            LineResult::Bytes {
+                name: Some("disk".to_string()),
+                value: 2e12,
+                binary: false,
+            }
+        );
+        assert!(matches!(
+            results[1],
+            LineResult::Bytes { binary: false, .. }
+        ));
+        assert_eq!(results[3], LineResult::Value(5.0));
+    }
+
+    #[test]
+    fn test_variables_shadow_size_units() {
+        let results = evaluate_all_lines(["B = 2", "5 B", "MB = 3", "2 MB", "2MB", "1 GB"]);
+
+        assert_eq!(results[1], LineResult::Value(10.0));
+        assert_eq!(results[3], LineResult::Value(6.0));
+        assert_eq!(results[4], LineResult::Value(6.0));
+        assert!(matches!(results[5], LineResult::Bytes { value, .. } if value > 0.0));
+    }
+
+    #[test]
+    fn test_assigned_integers_stay_exact() {
+        let results = evaluate_all_lines([
+            "a = 2^60 + 1",
+            "a - 2^60",
+            "b, c = 2^60 + 3, 1",
+            "b - 2^60",
+            "2^60 + 1",
+        ]);
+
+        assert_eq!(
+            results[0],
+            LineResult::Text {
+                name: Some("a".to_string()),
+                value: 2f64.powi(60),
+                text: "1152921504606846977".to_string()
+            }
+        );
+        assert_eq!(results[1], LineResult::Value(1.0));
+        assert_eq!(results[3], LineResult::Value(3.0));
+        assert!(
+            matches!(&results[4], LineResult::Text { text, .. } if text == "1152921504606846977")
+        );
+    }
+
+    #[test]
+    fn test_division_mode_floors_whole_numbers() {
+        let mut context = EvalContext::new();
+        context.set_division_mode(DivisionMode::Floor);
+        let results =
+            evaluate_all_lines_with_context(["7 / 2", "-7 / 2", "7.5 / 2", "7 // 2"], &mut context);
+
+        assert_eq!(results[0], LineResult::Value(3.0));
+        assert_eq!(results[1], LineResult::Value(-4.0));
+        assert_eq!(results[2], LineResult::Value(3.75));
+        assert_eq!(results[3], LineResult::Value(3.0));
+        assert_eq!(evaluate_all_lines(["7 / 2"])[0], LineResult::Value(3.5));
+    }
+
+    #[test]
+    fn test_lines_past_the_limits_are_errors() {
+        let mut context = EvalContext::new();
+        context.set_limits(Limits {
+            line_length: 5,
+            lines: 2,
+            ..Limits::default()
+        });
+        let results = evaluate_all_lines_with_context(["1 + 2", "1 + 22", "3"], &mut context);
+
+        assert_eq!(results[0], LineResult::Value(3.0));
+        let LineResult::Error(err) = &results[1] else {
+            panic!("expected error, got {:?}", results[1]);
+        };
+        assert_eq!(
+            err.message(),
+            "line is longer than 5 characters (see maxlength)"
+        );
+        assert_eq!(err.code(), ErrorCode::Limit);
+        assert_eq!(
+            results[2],
+            LineResult::Error(
+                EvalError::new("worksheet has more than 2 lines (see maxlines)")
+                    .with_code(ErrorCode::Limit)
+            )
+        );
+
+        context.set_limits(Limits {
+            depth: 2,
+            ..Limits::default()
+        });
+        let results = evaluate_all_lines_with_context(["((1))", "(((1)))"], &mut context);
+        assert_eq!(results[0], LineResult::Value(1.0));
+        assert!(matches!(&results[1], LineResult::Error(err) if err.code() == ErrorCode::Limit));
+        assert_eq!(evaluate_all_lines(["(((1)))"])[0], LineResult::Value(1.0));
+    }
+
+    #[test]
+    fn test_money_lines_round_to_minor_units() {
+        let mut context = EvalContext::new();
+        let results = evaluate_all_lines_with_context(
+            ["coffee = $0.10 + $0.20", "coffee * 3", "0.125 EUR", "total"],
+            &mut context,
+        );
+
+        let usd = money::currency_by_code("USD").unwrap();
+        assert_eq!(
+            results[0],
+            LineResult::Money {
+                name: Some("coffee".to_string()),
+                money: Money {
+                    minor: 30,
+                    currency: usd,
+                },
+            }
+        );
+        assert_eq!(context.get_variable("coffee"), Some(0.3));
+        assert_eq!(results[1].number(), Some(0.9));
+        assert!(matches!(&results[2], LineResult::Money { money, .. } if money.minor == 13));
+        assert_eq!(results[3], LineResult::Total(1.33));
+
+        context.clear();
+        context.set_rounding(money::Rounding::Bankers);
+        let result = evaluate_line("0.125 EUR", &mut context);
+        assert!(matches!(result, LineResult::Money { money, .. } if money.minor == 12));
+    }
+
+    #[test]
+    fn test_money_in_different_currencies_is_an_error() {
+        let results =
+            evaluate_all_lines(["rent = 900 CHF", "rent + $10", "rent = 5", "rent + $10"]);
+
+        assert_eq!(
+            results[1],
+            LineResult::Error(
+                EvalError::new("cannot mix CHF and USD").with_code(ErrorCode::IncompatibleUnits)
+            )
+        );
+        assert!(matches!(&results[3], LineResult::Money { money, .. } if money.minor == 1500));
+    }
+
+    #[test]
+    fn test_variables_shadow_currency_codes() {
+        let results = evaluate_all_lines(["10 EUR", "EUR = 4", "10 EUR", "2 CHF", "€10"]);
+
+        assert!(matches!(&results[0], LineResult::Money { .. }));
+        assert_eq!(results[2], LineResult::Value(40.0));
+        assert!(matches!(&results[3], LineResult::Money { .. }));
+        assert!(matches!(&results[4], LineResult::Money { money, .. } if money.minor == 1000));
+
+        let results = evaluate_all_lines(["10 EUR"]);
+        assert!(matches!(&results[0], LineResult::Money { .. }));
+    }
+
+    #[test]
+    fn test_variables_shadow_duration_units() {
+        let results = evaluate_all_lines(["m = 5", "2m", "h = 3", "2h", "1h30m", "2s"]);
+
+        assert_eq!(results[1], LineResult::Value(10.0));
+        assert_eq!(results[3], LineResult::Value(6.0));
+        assert!(matches!(&results[4], LineResult::Text { text, .. } if text == "1h 30m"));
+        assert!(matches!(&results[5], LineResult::Text { text, .. } if text == "2s"));
+    }
+
+    #[test]
+    fn test_rates_are_labelled_with_their_unit() {
+        let results = evaluate_all_lines(["42 km / 3h30m", "km = 2", "42 km / 3h30m"]);
+
+        let text = |result: &LineResult| match result {
+            LineResult::Text { text, .. } => text.clone(),
+            other => panic!("expected text, got {other:?}"),
+        };
+        assert_eq!(text(&results[0]), "12 km per hour");
+        assert_eq!(text(&results[2]), "24 per hour");
+    }
+
+    #[test]
+    fn test_units_outside_rates_are_undefined() {
+        let results = evaluate_all_lines(["2 km + 3 kg", "5 g", "2 m + 3", "2km * 3h"]);
+
+        for result in &results {
+            assert!(
+                matches!(result, LineResult::Error(e) if e.message().contains("undefined")),
+                "expected an undefined variable, got {result:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_duration_lines_show_durations_and_rates() {
+        let results = evaluate_all_lines([
+            "run = 3h30m",
+            "run / 42",
+            "42 / run",
+            "(3h20m) / 5 + 90s",
+            "1h / 30m",
+        ]);
+
+        let text = |result: &LineResult| match result {
+            LineResult::Text { text, .. } => text.clone(),
+            other => panic!("expected text, got {other:?}"),
+        };
+        assert_eq!(text(&results[0]), "3h 30m");
+        assert_eq!(text(&results[1]), "5m");
+        assert_eq!(text(&results[2]), "12 per hour");
+        assert_eq!(text(&results[3]), "41m 30s");
+        assert_eq!(results[4], LineResult::Value(2.0));
+    }
+
+    #[test]
+    fn test_format_directives_change_display_only() {
+        let results =
+            evaluate_all_lines(["mask = 255 | hex", "mask + 1", "1/3 | 2dp", "1.5 | bin"]);
+
+        assert_eq!(
+            results[0],
+            LineResult::Text {
+                name: Some("mask".to_string()),
+                value: 255.0,
+                text: "0xff".to_string(),
+            }
+        );
+        assert_eq!(results[1], LineResult::Value(256.0));
+        assert_eq!(
+            results[2],
+            LineResult::Text {
+                name: None,
+                value: 1.0 / 3.0,
+                text: "0.33".to_string(),
+            }
+        );
+        assert_eq!(results[3].warning(), Some("| bin needs a whole number"));
+        assert_eq!(results[3].without_warning(), &LineResult::Value(1.5));
+    }
+
+    #[test]
+    fn test_format_presets_come_from_the_context() {
+        let money = numformat::FormatSpec::parse("2dp, prefix \"€\"").unwrap();
+        let mut context = EvalContext::new();
+        context.set_format_presets(directive::FormatPresets::new(
+            vec![("money".to_string(), money)],
+            Some("hex"),
+        ));
+
+        let results = evaluate_all_lines_with_context(["1.5 | money", "255"], &mut context);
+        assert_eq!(
+            results[0],
+            LineResult::Text {
+                name: None,
+                value: 1.5,
+                text: "€1.50".to_string(),
+            }
+        );
+        assert_eq!(
+            results[1],
+            LineResult::Text {
+                name: None,
+                value: 255.0,
+                text: "0xff".to_string(),
+            }
+        );
+
+        let results = evaluate_all_lines(["1.5 | money", "255"]);
+        assert!(matches!(results[0], LineResult::Error(_)));
+        assert_eq!(results[1], LineResult::Value(255.0));
+    }
+
+    #[test]
+    fn test_total_sums_results_since_previous_marker() {
+        let results = evaluate_all_lines([
+            "rent = 1200",
+            "food = 450",
+            "30 * 4",
+            "",
+            "bad +",
+            "----",
+            "5",
+            "total",
+        ]);
+
+        assert_eq!(results[5], LineResult::Total(1770.0));
+        assert_eq!(results[7], LineResult::Total(5.0));
+    }
+
+    #[test]
+    fn test_multiple_assignment_sets_all_names() {
+        let mut context = EvalContext::new();
+
+        let result = evaluate_line("a, b = 3, 4", &mut context);
+
+        assert_eq!(
+            result,
+            LineResult::Assignments(vec![("a".to_string(), 3.0), ("b".to_string(), 4.0)])
+        );
+        assert_eq!(context.get_variable("a"), Some(3.0));
+        assert_eq!(context.get_variable("b"), Some(4.0));
+    }
+
+    #[test]
+    fn test_override_replaces_assigned_value_downstream() {
+        let mut context = EvalContext::new();
+        context.set_override("rate", 0.07);
+
+        let results = evaluate_all_lines_with_context(
+            ["rate = 0.05", "rate * 2", "a, rate = 1, 2"],
+            &mut context,
+        );
+
+        assert_eq!(
+            results[0],
+            LineResult::Warning {
+                result: Box::new(LineResult::Assignment {
+                    name: "rate".to_string(),
+                    value: 0.07,
+                }),
+                warning: EvalError::warning("what-if override of rate")
+                    .with_code(ErrorCode::Override),
+            }
+        );
+        assert_eq!(results[1], LineResult::Value(0.14));
+        assert_eq!(
+            results[2].without_warning(),
+            &LineResult::Assignments(vec![("a".to_string(), 1.0), ("rate".to_string(), 0.07)])
+        );
+        assert_eq!(results[2].warning(), Some("what-if override of rate"));
+    }
+
+    #[test]
+    fn test_multiple_assignment_swaps_variables() {
+        let results = evaluate_all_lines(["a, b = 3, 4", "a, b = b, a", "a - b"]);
+
+        assert_eq!(
+            results[1],
+            LineResult::Assignments(vec![("a".to_string(), 4.0), ("b".to_string(), 3.0)])
+        );
+        assert_eq!(results[2], LineResult::Value(1.0));
+    }
+
+    #[test]
+    fn test_multiple_assignment_errors_leave_variables_unchanged() {
+        let mut context = EvalContext::new();
+        evaluate_line("a = 1", &mut context);
+
+        assert_eq!(
+            evaluate_line("a, b = 2", &mut context),
+            LineResult::Error(
+                EvalError::new("expected 2 values, got 1").with_code(ErrorCode::Statement)
+            )
+        );
+        assert_eq!(
+            evaluate_line("a, a = 2, 3", &mut context),
+            LineResult::Error(
+                EvalError::new("'a' is assigned twice").with_code(ErrorCode::Statement)
+            )
+        );
+        assert!(matches!(
+            evaluate_line("a, b = 2, missing", &mut context),
+            LineResult::Error(_)
+        ));
+        assert_eq!(context.get_variable("a"), Some(1.0));
+        assert_eq!(context.get_variable("b"), None);
+    }
+
+    #[test]
+    fn test_multiple_const_declaration_is_immutable() {
+        let results = evaluate_all_lines(["const lo, hi = 1, 9", "hi = 10"]);
+
+        assert!(matches!(results[0], LineResult::Assignments(_)));
+        assert!(matches!(results[1], LineResult::Error(_)));
+    }
+
+    #[test]
+    fn test_unset_removes_variable_for_later_lines() {
+        let results = evaluate_all_lines(["x = 5", "unset x", "x + 1"]);
+
+        assert_eq!(results[1], LineResult::Empty);
+        assert!(matches!(results[2], LineResult::Error(_)));
+    }
+
+    #[test]
+    fn test_unset_rejects_constants_and_unknown_names() {
+        let results = evaluate_all_lines(["const k = 2", "unset k", "unset pi", "unset nope"]);
+
+        assert_eq!(
+            results[1],
+            LineResult::Error(
+                EvalError::new("cannot unset constant 'k'")
+                    .with_code(ErrorCode::ReassignedConstant)
+            )
+        );
+        assert_eq!(
+            results[2],
+            LineResult::Error(
+                EvalError::new("cannot unset constant 'pi'")
+                    .with_code(ErrorCode::ReassignedConstant)
+            )
+        );
+        assert_eq!(
+            results[3],
+            LineResult::Error(EvalError::undefined_variable("nope"))
+        );
+    }
+
+    #[test]
+    fn test_known_errors_have_their_own_codes() {
+        let cases = [
+            ("foo(1)", ErrorCode::UnknownFunction),
+            ("sin(1, 2)", ErrorCode::ArgumentCount),
+            ("1 +", ErrorCode::Syntax),
+            ("nope + 1", ErrorCode::UndefinedVariable),
+            ("(-1)!", ErrorCode::Domain),
+            ("ncr(2, 3)", ErrorCode::Domain),
+            ("sqrt(\"km\")", ErrorCode::InvalidArgument),
+            ("tobase(\"z\", 2)", ErrorCode::InvalidArgument),
+            ("csv(\"/no/such/file.csv\", \"amount\")", ErrorCode::Data),
+            ("xs + 1", ErrorCode::List),
+            ("@end", ErrorCode::Statement),
+            ("a, b = 1", ErrorCode::Statement),
+            ("k = 3", ErrorCode::ReassignedConstant),
+            ("unset k", ErrorCode::ReassignedConstant),
+            ("5 USD + 1 EUR", ErrorCode::IncompatibleUnits),
+        ];
+        for (line, code) in cases {
+            let results = evaluate_all_lines(["const k = 2", "xs = list(1, 2)", line]);
+            let LineResult::Error(error) = &results[2] else {
+                panic!("{line} should be an error, got {:?}", results[2]);
+            };
+            assert_ne!(error.code(), ErrorCode::Other, "{line}: {error}");
+            assert_eq!(error.code(), code, "{line}: {error}");
+        }
+    }
+
+    #[test]
+    fn test_clearvars_removes_stale_variables() {
+        let mut context = EvalContext::new();
+        evaluate_all_lines_with_context(["stale = 1"], &mut context);
+
+        let results = evaluate_all_lines_with_context(["clearvars", "stale", "pi"], &mut context);
+
+        assert!(matches!(results[1], LineResult::Error(_)));
+        assert_eq!(results[2], LineResult::Value(std::f64::consts::PI));
+    }
+
+    #[test]
+    fn test_clearvars_inside_section_is_an_error() {
+        let results = evaluate_all_lines(["@section", "clearvars", "@end"]);
+
+        assert!(matches!(results[1], LineResult::Error(_)));
+        assert_eq!(results[2], LineResult::Empty);
+    }
+
+    #[test]
+    fn test_disabled_line_is_skipped() {
+        let results = evaluate_all_lines(["a = 1", "# a = 100", "a + 1", "----"]);
+
+        assert_eq!(results[1], LineResult::Empty);
+        assert_eq!(results[2], LineResult::Value(2.0));
+        assert_eq!(results[3], LineResult::Total(3.0));
+    }
+
+    #[test]
+    fn test_total_of_nothing_is_zero() {
+        let results = evaluate_all_lines(["total"]);
+
+        assert_eq!(results[0], LineResult::Total(0.0));
+    }
+
+    #[test]
+    fn test_total_variable_takes_precedence_over_marker() {
+        let results = evaluate_all_lines(["total = 12", "total", "---"]);
+
+        assert_eq!(results[1], LineResult::Value(12.0));
+        assert_eq!(results[2], LineResult::Total(24.0));
+    }
+
+    #[test]
+    fn test_overflow_warning_keeps_the_result() {
+        let results = evaluate_all_lines(["big = 2^1024", "1e20 + 1", "2^62 + 1", "2 + 3"]);
+
+        assert_eq!(
+            results[0],
+            LineResult::Warning {
+                result: Box::new(LineResult::Assignment {
+                    name: "big".to_string(),
+                    value: f64::INFINITY,
+                }),
+                warning: EvalError::warning("overflow: result is too large to represent")
+                    .with_code(ErrorCode::Precision),
+            }
+        );
+        assert_eq!(results[1].without_warning(), &LineResult::Value(1e20));
+        assert_eq!(
+            results[1].warning(),
+            Some("precision loss: the smaller operand has no effect")
+        );
+        assert_eq!(results[2].warning(), None);
+        assert!(
+            matches!(&results[2], LineResult::Text { text, .. } if text == "4611686018427387905")
+        );
+        assert_eq!(results[3], LineResult::Value(5.0));
+    }
+
+    #[test]
+    fn test_cached_parse_repeats_errors_and_values() {
+        let context = EvalContext::new();
+
+        for _ in 0..2 {
+            assert_eq!(evaluate_expression("2 * (3 + 4)", &context), Ok(14.0));
+            assert!(evaluate_expression("2 * (3 +", &context).is_err());
+        }
+    }
+
+    #[test]
+    fn test_evaluate_all_lines_timed_matches_untimed() {
+        let lines = ["x = 2", "x * 3", ""];
+        let mut context = EvalContext::new();
+
+        let (results, timings) = evaluate_all_lines_timed(lines, &mut context);
+
+        assert_eq!(results, evaluate_all_lines(lines));
+        assert_eq!(timings.len(), 3);
+    }
+
+    #[test]
+    fn test_warned_results_count_towards_total() {
+        let results = evaluate_all_lines(["1e20 + 1", "1", "total"]);
+
+        assert_eq!(results[2], LineResult::Total(1e20 + 1.0));
+    }
+
+    #[test]
+    fn test_solve_result_can_be_assigned() {
+        let results = evaluate_all_lines(["total = 11", "x = solve(2x + 3 = total, x)"]);
+
+        assert_eq!(
+            results[1],
+            LineResult::Assignment {
+                name: "x".to_string(),
+                value: 4.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_equation_outside_solve_is_an_error() {
+        let result = evaluate_line("max(x = 1, 2)", &mut EvalContext::new());
+
+        assert_eq!(
+            result,
+            LineResult::Error(
+                EvalError::new("equations can only be used inside solve() or root()")
+                    .with_code(ErrorCode::Statement)
+            )
+        );
+    }
+
+    #[test]
+    fn test_const_in_section_is_released_at_end() {
+        let results = evaluate_all_lines(["@section", "const k = 1", "@end", "k = 2"]);
+
+        assert!(matches!(results[3], LineResult::Assignment { .. }));
+    }
+
+    #[test]
+    fn test_roman_and_words_lines_show_text() {
+        let results = evaluate_all_lines([
+            "year = to_roman(2024)",
+            "words(year - 1000)",
+            "roman(\"XIV\") + year",
+        ]);
+
+        assert_eq!(
+            results,
+            vec![
+                LineResult::Text {
+                    name: Some("year".to_string()),
+                    value: 2024.0,
+                    text: "MMXXIV".to_string(),
+                },
+                LineResult::Text {
+                    name: None,
+                    value: 1024.0,
+                    text: "one thousand twenty-four".to_string(),
+                },
+                LineResult::Value(2038.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_network_lines_show_text() {
+        let results = evaluate_all_lines([
+            "mask = netmask(24)",
+            "in_subnet(\"10.0.0.7\", \"10.0.0.0/24\")",
+            "cidr_hosts(\"10.0.0.0/24\")",
+        ]);
+
+        assert_eq!(
+            results,
+            vec![
+                LineResult::Text {
+                    name: Some("mask".to_string()),
+                    value: 4_294_967_040.0,
+                    text: "255.255.255.0".to_string(),
+                },
+                LineResult::Text {
+                    name: None,
+                    value: 1.0,
+                    text: "true".to_string(),
+                },
+                LineResult::Value(254.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_results_count_toward_total() {
+        let results = evaluate_all_lines(["to_roman(10)", "5", "total"]);
+
+        assert_eq!(results[2], LineResult::Total(15.0));
+    }
+
+    #[test]
+    fn test_significant_figures_mode_rounds_display_only() {
+        let mut context = EvalContext::new();
+        context.set_significant_figures_mode(true);
+        let results = evaluate_all_lines_with_context(
+            [
+                "r = 2.0",
+                "area = pi * r^2",
+                "area * 1.000",
+                "0xff * 2",
+                "12 * 2.50",
+            ],
+            &mut context,
+        );
+
+        assert_eq!(
+            results[1],
+            LineResult::Text {
+                name: Some("area".to_string()),
+                value: std::f64::consts::PI * 4.0,
+                text: "13".to_string(),
+            }
+        );
+        assert_eq!(
+            results[2],
+            LineResult::Text {
+                name: None,
+                value: std::f64::consts::PI * 4.0,
+                text: "13".to_string(),
+            }
+        );
+        assert_eq!(results[3], LineResult::Value(510.0));
+        assert_eq!(
+            results[4],
+            LineResult::Text {
+                name: None,
+                value: 30.0,
+                text: "30.0".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_significant_figures_mode_off_shows_full_values() {
+        let results = evaluate_all_lines(["2.0 * 1.234"]);
+
+        assert_eq!(results[0], LineResult::Value(2.468));
+    }
+
+    #[test]
+    fn test_simplify_lines_show_symbolic_form() {
+        let results = evaluate_all_lines([
+            "simplify(2*x + 3*x)",
+            "x = 2",
+            "simplify(x * 3) + 1",
+            "total",
+        ]);
+
+        assert_eq!(results[0], LineResult::Symbolic("5 * x".to_string()));
+        assert_eq!(results[2], LineResult::Value(7.0));
+        assert_eq!(results[3], LineResult::Total(9.0));
+    }
+
+    #[test]
+    fn test_table_lines_show_rows_and_are_not_totaled() {
+        let results = evaluate_all_lines([
+            "k = 3",
+            "table(k * x, x, 1, 2)",
+            "1 + table(x, x, 0, 1)",
+            "total",
+        ]);
+
+        let LineResult::Table(table) = &results[1] else {
+            panic!("expected a table, got {:?}", results[1]);
+        };
+        assert_eq!(table.rows, [(1.0, 3.0), (2.0, 6.0)]);
+        assert_eq!(
+            results[2],
+            LineResult::Error(
+                EvalError::new("table can only be used on a line of its own")
+                    .with_code(ErrorCode::Statement)
+            )
+        );
+        assert_eq!(results[3], LineResult::Total(3.0));
+    }
+
+    #[test]
+    fn test_vars_lines_list_dependencies() {
+        let results = evaluate_all_lines([
+            "price = 10",
+            "net = price * (1 + rate)",
+            "vars(net * qty)",
+            "vars(price * (1 + rate))",
+            "vars(price) + 1",
+        ]);
+
+        assert_eq!(
+            results[2],
+            LineResult::Symbolic("net undefined, qty undefined".to_string())
+        );
+        assert_eq!(
+            results[3],
+            LineResult::Symbolic("price = 10, rate undefined".to_string())
+        );
+        assert!(matches!(results[4], LineResult::Error(_)));
+    }
+
+    #[test]
+    fn test_csv_columns_spread_into_functions() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("data.csv");
+        std::fs::write(
+            &path,
+            "date,amount\n2024-01-01,10\n2024-01-02,20\n2024-01-03,60\n",
+        )
+        .expect("write file");
+        let csv = format!("csv(\"{}\", \"amount\")", path.display());
+
+        let lines = [
+            format!("total_spent = sum({csv})"),
+            format!("avg({csv})"),
+            format!("count({csv}) + 1"),
+            format!("sum({csv}, 10)"),
+            csv,
+        ];
+        let results = evaluate_all_lines(lines.iter().map(String::as_str));
+
+        assert_eq!(
+            results[0],
+            LineResult::Assignment {
+                name: "total_spent".to_string(),
+                value: 90.0
+            }
+        );
+        assert_eq!(results[1], LineResult::Value(30.0));
+        assert_eq!(results[2], LineResult::Value(4.0));
+        assert_eq!(results[3], LineResult::Value(100.0));
+        assert_eq!(
+            results[4],
+            LineResult::Symbolic("[10, 20, 60] (3 values)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_list_variables() {
+        let results = evaluate_all_lines([
+            "prices = list(12.5, 3, 4.5)",
+            "sum(prices)",
+            "avg(prices, 20)",
+            "prices * 2",
+            "prices = 7",
+            "prices * 2",
+            "const fixed = list(1)",
+            "list(1, \"a\")",
+        ]);
+
+        assert_eq!(
+            results[0],
+            LineResult::Symbolic("prices = [12.5, 3, 4.5] (3 values)".to_string())
+        );
+        assert_eq!(results[1], LineResult::Value(20.0));
+        assert_eq!(results[2], LineResult::Value(10.0));
+        assert_eq!(
+            results[3],
+            LineResult::Error(
+                EvalError::new("prices is a list; pass it to a function such as sum, avg or count")
+                    .with_code(ErrorCode::List)
+            )
+        );
+        assert_eq!(results[5], LineResult::Value(14.0));
+        assert!(matches!(results[6], LineResult::Error(_)));
+        assert_eq!(
+            results[7],
+            LineResult::Error(
+                EvalError::new("list expects numbers, got \"a\"")
+                    .with_code(ErrorCode::InvalidArgument)
+            )
+        );
+    }
+
+    #[test]
+    fn test_split_and_ratio_give_lists() {
+        let results = evaluate_all_lines([
+            "split(100, 3)",
+            "shares = split(123.45, 7)",
+            "sum(shares)",
+            "ratio(2, 3, 1000)",
+            "max(ratio(2, 3, 1000))",
+            "split(100, 3) + 1",
+            "split(100)",
+        ]);
+
+        assert_eq!(
+            results[0],
+            LineResult::Symbolic("[33.34, 33.33, 33.33] (3 values)".to_string())
+        );
+        assert!(matches!(&results[1], LineResult::Symbolic(text) if text.ends_with("(7 values)")));
+        assert!(matches!(results[2], LineResult::Value(sum) if (sum - 123.45).abs() < 1e-9));
+        assert_eq!(
+            results[3],
+            LineResult::Symbolic("[400, 600] (2 values)".to_string())
+        );
+        assert_eq!(results[4], LineResult::Value(600.0));
+        assert_eq!(
+            results[5],
+            LineResult::Error(
+                EvalError::new(
+                    "split gives a list of values; pass it to a function such as sum, avg or count"
+                )
+                .with_code(ErrorCode::List)
+            )
+        );
+        assert!(
+            matches!(&results[6], LineResult::Error(err) if err.code() == ErrorCode::ArgumentCount)
+        );
+    }
 }
@@ -0,0 +1,274 @@
+//! Money amounts and currency literals.
+//!
+//! A number written after a currency symbol (`$12.50`, `€3`) or followed by
+//! a currency code (`12.50 USD`, `3 CHF`) is an amount of money. A line that
+//! involves money, directly or through a variable assigned from it, rounds
+//! its result to whole minor units of the currency (cents for most) and keeps
+//! them as an integer, so a variable assigned from money holds exactly the
+//! amount shown. Halves round away from zero, or to the even neighbour with
+//! banker's rounding. Amounts in different currencies cannot be mixed.
+
+use crate::eval::context::EvalContext;
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::numformat::group_thousands;
+use crate::eval::token::{Token, Tokenizer};
+
+/// A currency amounts can be written in.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Currency {
+    /// The ISO 4217 code, e.g. `USD`.
+    pub code: &'static str,
+    /// The symbol written before amounts, if the currency has its own.
+    pub symbol: Option<char>,
+    /// The number of decimal places of the minor unit.
+    pub decimals: u32,
+}
+
+/// The currencies amounts can be written in.
+pub const CURRENCIES: &[Currency] = &[
+    Currency {
+        code: "USD",
+        symbol: Some('$'),
+        decimals: 2,
+    },
+    Currency {
+        code: "EUR",
+        symbol: Some('€'),
+        decimals: 2,
+    },
+    Currency {
+        code: "GBP",
+        symbol: Some('£'),
+        decimals: 2,
+    },
+    Currency {
+        code: "JPY",
+        symbol: Some('¥'),
+        decimals: 0,
+    },
+    Currency {
+        code: "CHF",
+        symbol: None,
+        decimals: 2,
+    },
+    Currency {
+        code: "CAD",
+        symbol: None,
+        decimals: 2,
+    },
+    Currency {
+        code: "AUD",
+        symbol: None,
+        decimals: 2,
+    },
+    Currency {
+        code: "SEK",
+        symbol: None,
+        decimals: 2,
+    },
+];
+
+/// Largest number of minor units an amount may have, so that it converts
+/// to `f64` without loss.
+const MAX_MINOR_UNITS: f64 = 9_007_199_254_740_992.0;
+
+/// How far from a half the minor units may be and still count as one, so
+/// that `2.675` rounds as written rather than as stored in binary.
+const TIE_TOLERANCE: f64 = 1e-6;
+
+/// Returns the currency written with `symbol`, e.g. `$`.
+#[must_use]
+pub fn currency_by_symbol(symbol: char) -> Option<&'static Currency> {
+    CURRENCIES.iter().find(|c| c.symbol == Some(symbol))
+}
+
+/// Returns the currency with exactly this code, e.g. `EUR`.
+#[must_use]
+pub fn currency_by_code(code: &str) -> Option<&'static Currency> {
+    CURRENCIES.iter().find(|c| c.code == code)
+}
+
+/// How amounts halfway between two minor units are rounded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rounding {
+    /// Halves round away from zero: `0.125` becomes `0.13`.
+    #[default]
+    HalfUp,
+    /// Halves round to the even neighbour, as banks do: `0.125` becomes
+    /// `0.12`.
+    Bankers,
+}
+
+impl Rounding {
+    /// Parses the name of a rounding rule, `half-up` or `bankers`.
+    #[must_use]
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "half-up" => Some(Self::HalfUp),
+            "bankers" => Some(Self::Bankers),
+            _ => None,
+        }
+    }
+
+    /// Returns the name the rule is parsed from.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::HalfUp => "half-up",
+            Self::Bankers => "bankers",
+        }
+    }
+
+    /// Rounds `value` to a whole number by this rule.
+    fn round(self, value: f64) -> f64 {
+        let whole = value.trunc();
+        if ((value - whole).abs() - 0.5).abs() > TIE_TOLERANCE {
+            return value.round();
+        }
+        if self == Self::Bankers && whole % 2.0 == 0.0 {
+            whole
+        } else {
+            whole + value.signum()
+        }
+    }
+}
+
+/// An amount of money, as a whole number of minor units of its currency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Money {
+    /// The amount in minor units, e.g. cents.
+    pub minor: i64,
+    /// The currency of the amount.
+    pub currency: &'static Currency,
+}
+
+impl Money {
+    /// Rounds `value` to minor units of `currency` with `rounding`.
+    ///
+    /// Returns `None` for values that are not finite or too large to be
+    /// counted in minor units exactly.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn from_value(value: f64, currency: &'static Currency, rounding: Rounding) -> Option<Self> {
+        let minor = rounding.round(value * minor_units_per_major(currency));
+        (minor.abs() < MAX_MINOR_UNITS).then_some(Self {
+            minor: minor as i64,
+            currency,
+        })
+    }
+
+    /// Returns the amount in major units, e.g. dollars.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn value(self) -> f64 {
+        self.minor as f64 / minor_units_per_major(self.currency)
+    }
+
+    /// Formats the amount with all decimals of the minor unit and grouped
+    /// thousands, after the currency symbol or code: `$1,234.50`,
+    /// `-€3.00`, `CHF 12.00`.
+    #[must_use]
+    pub fn format(self) -> String {
+        let digits = self.minor.unsigned_abs().to_string();
+        let decimals = self.currency.decimals as usize;
+        let digits = format!("{digits:0>width$}", width = decimals + 1);
+        let (major, minor) = digits.split_at(digits.len() - decimals);
+        let mut number = group_thousands(major);
+        if !minor.is_empty() {
+            number = format!("{number}.{minor}");
+        }
+        let sign = if self.minor < 0 { "-" } else { "" };
+        self.currency.symbol.map_or_else(
+            || format!("{sign}{} {number}", self.currency.code),
+            |symbol| format!("{sign}{symbol}{number}"),
+        )
+    }
+}
+
+/// Returns how many minor units make up one major unit of `currency`.
+fn minor_units_per_major(currency: &Currency) -> f64 {
+    10f64.powi(currency.decimals.cast_signed())
+}
+
+/// Returns the currency of the money in `expression`, if it involves any.
+///
+/// # Errors
+/// Returns an error if amounts in different currencies are mixed.
+pub fn currency(
+    expression: &str,
+    context: &EvalContext,
+) -> Result<Option<&'static Currency>, EvalError> {
+    let (tokens, _) =
+        Tokenizer::with_options(expression, context.token_options()).tokenize_partial();
+    let mut found: Option<&'static Currency> = None;
+    for (token, _) in &tokens {
+        let currency = match token {
+            Token::Money { currency, .. } => Some(*currency),
+            Token::Identifier(name) => context.currency(name),
+            _ => None,
+        };
+        match (found, currency) {
+            (Some(first), Some(other)) if first != other => {
+                return Err(EvalError::new(format!(
+                    "cannot mix {} and {}",
+                    first.code, other.code
+                ))
+                .with_code(ErrorCode::IncompatibleUnits));
+            }
+            (None, Some(_)) => found = currency,
+            _ => {}
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(value: f64, code: &str, rounding: Rounding) -> Money {
+        Money::from_value(value, currency_by_code(code).unwrap(), rounding).unwrap()
+    }
+
+    #[test]
+    fn test_rounding_rules() {
+        assert_eq!(money(0.125, "USD", Rounding::HalfUp).minor, 13);
+        assert_eq!(money(0.125, "USD", Rounding::Bankers).minor, 12);
+        assert_eq!(money(0.135, "USD", Rounding::Bankers).minor, 14);
+        assert_eq!(money(2.675, "USD", Rounding::HalfUp).minor, 268);
+        assert_eq!(money(-2.5, "JPY", Rounding::HalfUp).minor, -3);
+        assert_eq!(money(-2.5, "JPY", Rounding::Bankers).minor, -2);
+        assert_eq!(money(0.1 + 0.2, "EUR", Rounding::Bankers).minor, 30);
+        assert_eq!(
+            Money::from_value(1e20, currency_by_code("USD").unwrap(), Rounding::HalfUp),
+            None
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(money(1234.5, "USD", Rounding::HalfUp).format(), "$1,234.50");
+        assert_eq!(money(-3.0, "EUR", Rounding::HalfUp).format(), "-€3.00");
+        assert_eq!(money(0.05, "GBP", Rounding::HalfUp).format(), "£0.05");
+        assert_eq!(money(12.0, "CHF", Rounding::HalfUp).format(), "CHF 12.00");
+        assert_eq!(money(5000.0, "JPY", Rounding::HalfUp).format(), "¥5,000");
+    }
+
+    #[test]
+    fn test_currency_of_expression() {
+        let mut context = EvalContext::new();
+        context.set_currency("rent", currency_by_code("EUR"));
+
+        assert_eq!(currency("1 + 2", &context), Ok(None));
+        assert_eq!(currency("$5 * 3", &context), Ok(currency_by_code("USD")));
+        assert_eq!(currency("rent / 2", &context), Ok(currency_by_code("EUR")));
+        assert_eq!(
+            currency("rent + 10 EUR", &context),
+            Ok(currency_by_code("EUR"))
+        );
+        assert_eq!(
+            currency("rent + $10", &context).unwrap_err().message(),
+            "cannot mix EUR and USD"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! IPv4 network math for quick subnet questions.
+//!
+//! `cidr_hosts("10.0.0.0/24")` counts the usable host addresses of a
+//! network, `netmask(24)` shows the mask for a prefix length as
+//! `255.255.255.0`, and `in_subnet("10.0.0.7", "10.0.0.0/24")` shows whether
+//! an address lies in a network. Like `to_roman`, `netmask` and `in_subnet`
+//! keep a number as their value, the mask as a 32-bit integer and 1 or 0, and
+//! only a line of their own shows the text.
+
+use crate::eval::ast::Expr;
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::functions::Argument;
+
+/// Number of bits in an IPv4 address.
+const ADDRESS_BITS: u32 = 32;
+
+/// Parses a dotted-quad IPv4 address such as `192.168.1.10`.
+fn parse_address(text: &str) -> Option<u32> {
+    let mut octets = text.trim().split('.');
+    let mut address = 0;
+    for _ in 0..4 {
+        let octet: u8 = octets.next()?.parse().ok()?;
+        address = (address << 8) | u32::from(octet);
+    }
+    octets.next().is_none().then_some(address)
+}
+
+/// Parses a network in CIDR notation, such as `10.0.0.0/24`, into its
+/// address and prefix length.
+fn parse_network(text: &str) -> Option<(u32, u32)> {
+    let (address, prefix) = text.split_once('/')?;
+    let prefix = prefix.trim().parse().ok().filter(|&p| p <= ADDRESS_BITS)?;
+    Some((parse_address(address)?, prefix))
+}
+
+/// Returns the mask for a prefix length of at most 32.
+const fn mask(prefix: u32) -> u32 {
+    if prefix == 0 {
+        0
+    } else {
+        u32::MAX << (ADDRESS_BITS - prefix)
+    }
+}
+
+/// Formats a 32-bit value as a dotted-quad IPv4 address.
+#[must_use]
+pub fn format_address(address: u32) -> String {
+    let [a, b, c, d] = address.to_be_bytes();
+    format!("{a}.{b}.{c}.{d}")
+}
+
+/// Returns the error for text that is not a network in CIDR notation.
+fn network_error(function: &str, text: &str) -> EvalError {
+    EvalError::new(format!(
+        "{function} expects a network such as \"10.0.0.0/24\", not '{text}'"
+    ))
+    .with_code(ErrorCode::InvalidArgument)
+}
+
+/// Implements the `cidr_hosts("10.0.0.0/24")` built-in.
+///
+/// The network and broadcast addresses are not counted, except for `/31`
+/// point-to-point links and `/32` single hosts, which use every address.
+pub(crate) fn cidr_hosts_argument(args: &[Argument]) -> Result<f64, EvalError> {
+    let [Argument::Text(text)] = args else {
+        return Err(EvalError::new(
+            "cidr_hosts expects a network in quotes, e.g. cidr_hosts(\"10.0.0.0/24\")",
+        )
+        .with_code(ErrorCode::InvalidArgument));
+    };
+    let (_, prefix) = parse_network(text).ok_or_else(|| network_error("cidr_hosts", text))?;
+    let addresses = 2_f64.powi(i32::try_from(ADDRESS_BITS - prefix).unwrap_or_default());
+    Ok(if prefix >= ADDRESS_BITS - 1 {
+        addresses
+    } else {
+        addresses - 2.0
+    })
+}
+
+/// Implements the `netmask(prefix)` built-in, which returns the mask for a
+/// prefix length as a 32-bit integer.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(crate) fn netmask_value(args: &[f64]) -> Result<f64, EvalError> {
+    let prefix = args[0];
+    if prefix.fract() == 0.0 && (0.0..=f64::from(ADDRESS_BITS)).contains(&prefix) {
+        Ok(f64::from(mask(prefix as u32)))
+    } else {
+        Err(
+            EvalError::new("netmask expects a prefix length from 0 to 32")
+                .with_code(ErrorCode::Domain),
+        )
+    }
+}
+
+/// Implements the `in_subnet("10.0.0.7", "10.0.0.0/24")` built-in, which
+/// returns 1 if the address lies in the network and 0 otherwise.
+pub(crate) fn in_subnet_argument(args: &[Argument]) -> Result<f64, EvalError> {
+    let [Argument::Text(address), Argument::Text(network)] = args else {
+        return Err(EvalError::new(
+            "in_subnet expects an address and a network in quotes, \
+             e.g. in_subnet(\"10.0.0.7\", \"10.0.0.0/24\")",
+        )
+        .with_code(ErrorCode::InvalidArgument));
+    };
+    let address = parse_address(address).ok_or_else(|| {
+        EvalError::new(format!("'{address}' is not an IPv4 address"))
+            .with_code(ErrorCode::InvalidArgument)
+    })?;
+    let (network, prefix) =
+        parse_network(network).ok_or_else(|| network_error("in_subnet", network))?;
+    let mask = mask(prefix);
+    Ok(if address & mask == network & mask {
+        1.0
+    } else {
+        0.0
+    })
+}
+
+/// Returns the text shown for a line whose expression is a call to
+/// `netmask` or `in_subnet` with the given value.
+#[must_use]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn text_form(expr: &Expr, value: f64) -> Option<String> {
+    let Expr::FunctionCall { name, .. } = expr else {
+        return None;
+    };
+    match name.as_str() {
+        "netmask" => Some(format_address(value as u32)),
+        "in_subnet" => Some((value != 0.0).to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::parse_expression;
+
+    fn text(value: &str) -> Argument {
+        Argument::Text(value.to_string())
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("192.168.1.10"), Some(0xC0A8_010A));
+        for invalid in ["", "10.0.0", "10.0.0.0.1", "10.0.0.256", "a.b.c.d"] {
+            assert_eq!(parse_address(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_cidr_hosts() {
+        let hosts = |network| cidr_hosts_argument(&[text(network)]);
+        assert_eq!(hosts("10.0.0.0/24"), Ok(254.0));
+        assert_eq!(hosts("10.0.0.0/8"), Ok(16_777_214.0));
+        assert_eq!(hosts("10.0.0.0/31"), Ok(2.0));
+        assert_eq!(hosts("10.0.0.1/32"), Ok(1.0));
+        assert_eq!(hosts("0.0.0.0/0"), Ok(4_294_967_294.0));
+        assert!(hosts("10.0.0.0/33").is_err());
+        assert!(hosts("10.0.0.0").is_err());
+    }
+
+    #[test]
+    fn test_netmask() {
+        assert_eq!(netmask_value(&[24.0]), Ok(4_294_967_040.0));
+        assert_eq!(netmask_value(&[0.0]), Ok(0.0));
+        assert!(netmask_value(&[33.0]).is_err());
+        assert!(netmask_value(&[8.5]).is_err());
+    }
+
+    #[test]
+    fn test_in_subnet() {
+        let check = |address, network| in_subnet_argument(&[text(address), text(network)]);
+        assert_eq!(check("10.0.0.7", "10.0.0.0/24"), Ok(1.0));
+        assert_eq!(check("10.0.1.7", "10.0.0.0/24"), Ok(0.0));
+        assert_eq!(check("192.168.5.5", "0.0.0.0/0"), Ok(1.0));
+        assert!(check("10.0.0", "10.0.0.0/24").is_err());
+    }
+
+    #[test]
+    fn test_text_form() {
+        let netmask = parse_expression("netmask(20)").unwrap();
+        assert_eq!(
+            text_form(&netmask, 4_294_963_200.0),
+            Some("255.255.240.0".to_string())
+        );
+        let check = parse_expression("in_subnet(\"10.0.0.7\", \"10.0.0.0/24\")").unwrap();
+        assert_eq!(text_form(&check, 1.0), Some("true".to_string()));
+        assert_eq!(text_form(&check, 0.0), Some("false".to_string()));
+    }
+}
@@ -0,0 +1,313 @@
+//! Number formatting engine.
+//!
+//! A `FormatSpec` describes how a number is written: in decimal, scientific
+//! notation or another base, rounded to some decimal places, with grouped
+//! thousands, and with text before and after it. The precision and
+//! separator settings, per-line directives such as `| hex`, and named
+//! presets from the settings file are all specs, so results are written the
+//! same way wherever they are shown.
+//!
+//! A preset is defined as a comma-separated list of items, for example
+//! `2dp, thousands, prefix "€"`:
+//!
+//! | Item            | Effect                                       |
+//! |-----------------|----------------------------------------------|
+//! | `2dp`           | Exactly 2 decimal places (0 to 15)           |
+//! | `thousands`     | Group thousands with commas                  |
+//! | `sci`           | Scientific notation                          |
+//! | `hex`/`bin`/`oct` | Whole numbers in base 16, 2 or 8           |
+//! | `prefix "…"`    | Text before the number, after any minus sign |
+//! | `suffix "…"`    | Text after the number                        |
+
+use crate::eval::convert::format_in_base;
+
+/// Threshold above which whole numbers are displayed in scientific notation.
+pub const MAX_WHOLE_NUMBER_DISPLAY: f64 = 1e15;
+
+/// Most decimal places a spec can round to.
+pub const MAX_DECIMALS: usize = 15;
+
+/// Largest magnitude shown in another base, beyond which integers lose
+/// precision.
+const MAX_BASE_MAGNITUDE: f64 = 9_007_199_254_740_992.0;
+
+/// How the digits of a number are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Notation {
+    /// Ordinary decimal notation.
+    #[default]
+    Decimal,
+    /// Scientific notation, `1.5e3`.
+    Scientific,
+    /// A whole number in the given base.
+    Base(u32),
+}
+
+/// How a number is written.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FormatSpec {
+    /// How the digits are written.
+    pub notation: Notation,
+    /// Decimal places to round to, `None` for full precision.
+    pub decimals: Option<usize>,
+    /// Whether trailing zeros after rounding are dropped.
+    pub trim_zeros: bool,
+    /// Whether thousands are grouped with commas.
+    pub thousands: bool,
+    /// Text written before the number, after any minus sign.
+    pub prefix: String,
+    /// Text written after the number.
+    pub suffix: String,
+}
+
+impl FormatSpec {
+    /// Returns the spec of a built-in format name: `hex`, `bin`, `oct`,
+    /// `sci`, `2dp`, or a currency `eur`, `usd` or `gbp`.
+    #[must_use]
+    pub fn builtin(name: &str) -> Option<Self> {
+        let currency = |symbol: &str| Self {
+            decimals: Some(2),
+            thousands: true,
+            prefix: symbol.to_string(),
+            ..Self::default()
+        };
+        match name {
+            "eur" => Some(currency("€")),
+            "usd" => Some(currency("$")),
+            "gbp" => Some(currency("£")),
+            _ => {
+                let mut spec = Self::default();
+                spec.apply_item(name).ok()?;
+                Some(spec)
+            }
+        }
+    }
+
+    /// Parses a preset definition such as `2dp, thousands, prefix "€"`.
+    ///
+    /// # Errors
+    /// Returns a message naming the first item that is not understood.
+    pub fn parse(definition: &str) -> Result<Self, String> {
+        let mut spec = Self::default();
+        for item in split_items(definition) {
+            spec.apply_item(item)?;
+        }
+        Ok(spec)
+    }
+
+    /// Applies one item of a preset definition.
+    fn apply_item(&mut self, item: &str) -> Result<(), String> {
+        let item = item.trim();
+        let (word, argument) = item
+            .split_once(char::is_whitespace)
+            .map_or((item, ""), |(word, argument)| (word, argument.trim()));
+        match (word, argument) {
+            ("thousands", "") => self.thousands = true,
+            ("sci", "") => self.notation = Notation::Scientific,
+            ("hex", "") => self.set_base(16, "0x"),
+            ("bin", "") => self.set_base(2, "0b"),
+            ("oct", "") => self.set_base(8, "0o"),
+            ("prefix", text) => self.prefix = quoted(text)?,
+            ("suffix", text) => self.suffix = quoted(text)?,
+            (word, "") if let Some(places) = word.strip_suffix("dp") => {
+                self.decimals = Some(
+                    places
+                        .parse()
+                        .ok()
+                        .filter(|&places| places <= MAX_DECIMALS)
+                        .ok_or_else(|| format!("decimal places must be 0 to {MAX_DECIMALS}"))?,
+                );
+            }
+            _ => return Err(format!("unknown format item '{item}'")),
+        }
+        Ok(())
+    }
+
+    /// Switches to whole numbers in `base`, written after `prefix`.
+    fn set_base(&mut self, base: u32, prefix: &str) {
+        self.notation = Notation::Base(base);
+        self.prefix = prefix.to_string();
+    }
+
+    /// Writes `value` as the spec describes.
+    ///
+    /// # Errors
+    /// Returns a message when the value cannot be written this way, such as
+    /// a fraction in another base.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn format(&self, value: f64) -> Result<String, String> {
+        let number = match self.notation {
+            Notation::Base(base) => {
+                if value.fract() != 0.0 || value.abs() > MAX_BASE_MAGNITUDE {
+                    return Err("needs a whole number".to_string());
+                }
+                format_in_base(value as i64, base)
+            }
+            Notation::Scientific => self.decimals.map_or_else(
+                || format!("{value:e}"),
+                |places| format!("{value:.places$e}"),
+            ),
+            Notation::Decimal => match self.decimals {
+                Some(places) if value.is_finite() && value.abs() < MAX_WHOLE_NUMBER_DISPLAY => {
+                    self.fixed(value, places)
+                }
+                _ => format_plain(value),
+            },
+        };
+        let number = if self.thousands {
+            group_thousands(&number)
+        } else {
+            number
+        };
+        let (sign, magnitude) = number
+            .strip_prefix('-')
+            .map_or(("", number.as_str()), |magnitude| ("-", magnitude));
+        Ok(format!("{sign}{}{magnitude}{}", self.prefix, self.suffix))
+    }
+
+    /// Writes `value` rounded to `places` decimals, without a sign on zero.
+    fn fixed(&self, value: f64, places: usize) -> String {
+        let rounded = format!("{value:.places$}");
+        let rounded = if self.trim_zeros && rounded.contains('.') {
+            rounded.trim_end_matches('0').trim_end_matches('.')
+        } else {
+            &rounded
+        };
+        match rounded.strip_prefix('-') {
+            Some(unsigned) if unsigned.bytes().all(|b| b == b'0' || b == b'.') => {
+                unsigned.to_string()
+            }
+            _ => rounded.to_string(),
+        }
+    }
+}
+
+/// Writes `value` in full: whole numbers without decimal places, others with
+/// their natural decimal representation.
+#[must_use]
+pub fn format_plain(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < MAX_WHOLE_NUMBER_DISPLAY {
+        format!("{value:.0}")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Inserts a comma between each group of three integer digits, e.g.
+/// `-1234567.5` becomes `-1,234,567.5`.
+#[must_use]
+pub fn group_thousands(text: &str) -> String {
+    let digits_start = usize::from(text.starts_with('-'));
+    let digits_end = text.find(['.', 'e']).unwrap_or(text.len());
+    let digits = &text[digits_start..digits_end];
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return text.to_string();
+    }
+
+    let mut grouped = String::with_capacity(text.len() + digits.len() / 3);
+    grouped.push_str(&text[..digits_start]);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped.push_str(&text[digits_end..]);
+    grouped
+}
+
+/// Splits a preset definition at the commas outside quotes.
+fn split_items(definition: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (i, c) in definition.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                items.push(&definition[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&definition[start..]);
+    items.retain(|item| !item.trim().is_empty());
+    items
+}
+
+/// Returns the text inside double quotes.
+fn quoted(text: &str) -> Result<String, String> {
+    text.strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .map(ToString::to_string)
+        .ok_or_else(|| format!("expected text in quotes, not '{text}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(spec: &FormatSpec, value: f64) -> String {
+        spec.format(value).unwrap()
+    }
+
+    #[test]
+    fn test_builtin_formats() {
+        let spec = |name| FormatSpec::builtin(name).unwrap();
+        assert_eq!(format(&spec("hex"), 255.0), "0xff");
+        assert_eq!(format(&spec("bin"), -5.0), "-0b101");
+        assert_eq!(format(&spec("oct"), 15.0), "0o17");
+        assert!(spec("hex").format(1.5).is_err());
+        assert_eq!(format(&spec("sci"), 1500.0), "1.5e3");
+        assert_eq!(format(&spec("2dp"), 3.5), "3.50");
+        assert_eq!(format(&spec("1dp"), -0.01), "0.0");
+        assert_eq!(format(&spec("eur"), 1234.5), "€1,234.50");
+        assert_eq!(format(&spec("usd"), -5.0), "-$5.00");
+        assert_eq!(FormatSpec::builtin("16dp"), None);
+        assert_eq!(FormatSpec::builtin("thousandz"), None);
+    }
+
+    #[test]
+    fn test_parse_preset() {
+        let money = FormatSpec::parse("2dp, thousands, prefix \"€ \"").unwrap();
+        assert_eq!(format(&money, 1_234_567.891), "€ 1,234,567.89");
+
+        let weight = FormatSpec::parse("1dp, suffix \" kg\"").unwrap();
+        assert_eq!(format(&weight, 72.46), "72.5 kg");
+
+        let odd = FormatSpec::parse("prefix \"a, b \"").unwrap();
+        assert_eq!(format(&odd, 1.0), "a, b 1");
+    }
+
+    #[test]
+    fn test_parse_preset_rejects_unknown_items() {
+        assert_eq!(
+            FormatSpec::parse("2dp, bold"),
+            Err("unknown format item 'bold'".to_string())
+        );
+        assert!(FormatSpec::parse("prefix €").is_err());
+        assert!(FormatSpec::parse("99dp").is_err());
+    }
+
+    #[test]
+    fn test_trimmed_decimals_match_plain_display() {
+        let spec = FormatSpec {
+            decimals: Some(4),
+            trim_zeros: true,
+            ..FormatSpec::default()
+        };
+        assert_eq!(format(&spec, 2.5), "2.5");
+        assert_eq!(format(&spec, 1.0 / 3.0), "0.3333");
+        assert_eq!(format(&spec, -0.00001), "0");
+        assert_eq!(format(&spec, 1e20), "100000000000000000000");
+    }
+
+    #[test]
+    fn test_group_thousands() {
+        assert_eq!(group_thousands("-1234567.5"), "-1,234,567.5");
+        assert_eq!(group_thousands("999"), "999");
+        assert_eq!(group_thousands("1234e3"), "1,234e3");
+        assert_eq!(group_thousands("ff"), "ff");
+    }
+}
@@ -2,24 +2,58 @@
 //!
 //! This module parses input lines to determine their type before evaluation.
 
+use crate::eval::directive::{FormatPresets, split_format_directive};
+
 /// Represents a parsed line of input.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ParsedLine {
-    /// An assignment expression: `name = expression`
+    /// An assignment expression: `name = expression` or `const name = expression`
     Assignment {
         /// The variable name being assigned.
         name: String,
         /// The expression to evaluate and assign.
         expression: String,
+        /// Whether the assignment declares an immutable constant.
+        constant: bool,
+    },
+    /// Several assignments on one line: `a, b = 3, 4` or `const a, b = 3, 4`
+    ///
+    /// All expressions are evaluated before any name is assigned, so
+    /// `a, b = b, a` swaps two variables.
+    MultipleAssignment {
+        /// The variable names being assigned, in order.
+        names: Vec<String>,
+        /// The expressions to evaluate, in order. May differ in number from
+        /// `names`, which is reported when the line is evaluated.
+        expressions: Vec<String>,
+        /// Whether the assignment declares immutable constants.
+        constant: bool,
     },
+    /// Removes variables: `unset a` or `unset a, b`
+    Unset(Vec<String>),
+    /// Removes every user variable: `clearvars`
+    ClearVariables,
     /// A standalone expression to evaluate.
     Expression(String),
+    /// The start of a worksheet section: `@section [name]`
+    SectionStart(Option<String>),
+    /// The end of the innermost worksheet section: `@end`
+    SectionEnd,
+    /// A running total marker: `total` or a rule of three or more dashes.
+    Total,
+    /// A line disabled with a leading `#`, kept in the buffer but not evaluated.
+    Disabled,
     /// An empty or whitespace-only line.
     Empty,
 }
 
+/// Marks a line that is kept in the buffer but skipped during evaluation.
+pub const DISABLED_PREFIX: char = '#';
+
 /// Parses a line of input into its structural form.
 ///
+/// A trailing formatting directive such as `| hex` is ignored.
+///
 /// # Arguments
 /// * `line` - The input line to parse
 ///
@@ -27,12 +61,44 @@ pub enum ParsedLine {
 /// A `ParsedLine` indicating whether this is an assignment, expression, or empty line.
 #[must_use]
 pub fn parse_line(line: &str) -> ParsedLine {
+    parse_line_with_presets(line, &FormatPresets::default())
+}
+
+/// Like `parse_line`, also ignoring a trailing directive that names one of
+/// the format `presets`.
+#[must_use]
+pub fn parse_line_with_presets(line: &str, presets: &FormatPresets) -> ParsedLine {
+    let (line, _) = split_format_directive(line, presets);
     let trimmed = line.trim();
 
     if trimmed.is_empty() {
         return ParsedLine::Empty;
     }
 
+    if trimmed.starts_with(DISABLED_PREFIX) {
+        return ParsedLine::Disabled;
+    }
+
+    if let Some(directive) = try_parse_directive(trimmed) {
+        return directive;
+    }
+
+    if is_total_marker(trimmed) {
+        return ParsedLine::Total;
+    }
+
+    if trimmed == "clearvars" {
+        return ParsedLine::ClearVariables;
+    }
+
+    if let Some(unset) = try_parse_unset(trimmed) {
+        return unset;
+    }
+
+    if let Some(declaration) = try_parse_const(trimmed) {
+        return declaration;
+    }
+
     if let Some(assignment) = try_parse_assignment(trimmed) {
         return assignment;
     }
@@ -40,6 +106,75 @@ pub fn parse_line(line: &str) -> ParsedLine {
     ParsedLine::Expression(trimmed.to_string())
 }
 
+/// Attempts to parse a section directive (`@section [name]` or `@end`).
+///
+/// Returns `None` for anything else, including unknown directives, which are
+/// then reported as expression errors.
+fn try_parse_directive(line: &str) -> Option<ParsedLine> {
+    let rest = line.strip_prefix('@')?;
+    let (keyword, argument) = rest
+        .split_once(char::is_whitespace)
+        .map_or((rest, ""), |(keyword, argument)| (keyword, argument.trim()));
+
+    match keyword {
+        "section" => Some(ParsedLine::SectionStart(
+            (!argument.is_empty()).then(|| argument.to_string()),
+        )),
+        "end" if argument.is_empty() => Some(ParsedLine::SectionEnd),
+        _ => None,
+    }
+}
+
+/// Returns `true` for a running total marker: `total` or `---`, `----`, ...
+fn is_total_marker(line: &str) -> bool {
+    line == "total" || (line.len() >= 3 && line.chars().all(|c| c == '-'))
+}
+
+/// Attempts to parse `unset name, ...`.
+///
+/// Returns `None` unless the keyword is followed by one or more valid names,
+/// so a variable called `unset` can still be assigned.
+fn try_parse_unset(line: &str) -> Option<ParsedLine> {
+    let rest = line.strip_prefix("unset")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let names: Vec<&str> = rest.split(',').map(str::trim).collect();
+    names
+        .iter()
+        .all(|name| is_valid_identifier(name))
+        .then(|| ParsedLine::Unset(names.into_iter().map(String::from).collect()))
+}
+
+/// Attempts to parse a constant declaration: `const name = expression`.
+///
+/// Returns `None` if the line does not start with the `const` keyword
+/// followed by a valid assignment.
+fn try_parse_const(line: &str) -> Option<ParsedLine> {
+    let rest = line.strip_prefix("const")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    match try_parse_assignment(rest.trim_start())? {
+        ParsedLine::Assignment {
+            name, expression, ..
+        } => Some(ParsedLine::Assignment {
+            name,
+            expression,
+            constant: true,
+        }),
+        ParsedLine::MultipleAssignment {
+            names, expressions, ..
+        } => Some(ParsedLine::MultipleAssignment {
+            names,
+            expressions,
+            constant: true,
+        }),
+        _ => None,
+    }
+}
+
 /// Attempts to parse an assignment expression.
 ///
 /// Returns `None` if the line is not a valid assignment.
@@ -67,25 +202,60 @@ fn try_parse_assignment(line: &str) -> Option<ParsedLine> {
     let name_part = line[..equals_pos].trim();
     let expr_part = line[equals_pos + 1..].trim();
 
-    if !is_valid_identifier(name_part) {
+    if expr_part.is_empty() {
         return None;
     }
 
-    if expr_part.is_empty() {
+    if name_part.contains(',') {
+        let names: Vec<&str> = name_part.split(',').map(str::trim).collect();
+        if !names.iter().all(|name| is_valid_identifier(name)) {
+            return None;
+        }
+        return Some(ParsedLine::MultipleAssignment {
+            names: names.into_iter().map(String::from).collect(),
+            expressions: split_top_level_commas(expr_part),
+            constant: false,
+        });
+    }
+
+    if !is_valid_identifier(name_part) {
         return None;
     }
 
     Some(ParsedLine::Assignment {
         name: name_part.to_string(),
         expression: expr_part.to_string(),
+        constant: false,
     })
 }
 
+/// Splits `text` at commas that are outside parentheses and text literals.
+fn split_top_level_commas(text: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut in_text = false;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => in_text = !in_text,
+            '(' if !in_text => depth += 1,
+            ')' if !in_text => depth = depth.saturating_sub(1),
+            ',' if !in_text && depth == 0 => {
+                parts.push(text[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(text[start..].trim().to_string());
+    parts
+}
+
 /// Checks if a string is a valid identifier.
 ///
 /// Valid identifiers start with a letter or underscore, followed by
 /// letters, digits, or underscores.
-fn is_valid_identifier(s: &str) -> bool {
+pub(crate) fn is_valid_identifier(s: &str) -> bool {
     if s.is_empty() {
         return false;
     }
@@ -104,6 +274,174 @@ fn is_valid_identifier(s: &str) -> bool {
 mod tests {
     use super::*;
 
+    // Constant declaration tests
+    #[test]
+    fn test_parse_const_declaration() {
+        assert_eq!(
+            parse_line("const tax = 0.19"),
+            ParsedLine::Assignment {
+                name: "tax".to_string(),
+                expression: "0.19".to_string(),
+                constant: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_variable_named_const_is_plain_assignment() {
+        assert_eq!(
+            parse_line("constant = 1"),
+            ParsedLine::Assignment {
+                name: "constant".to_string(),
+                expression: "1".to_string(),
+                constant: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_const_without_assignment_is_expression() {
+        assert_eq!(
+            parse_line("const x"),
+            ParsedLine::Expression("const x".to_string())
+        );
+    }
+
+    // Section directive tests
+    #[test]
+    fn test_parse_section_start_with_name() {
+        assert_eq!(
+            parse_line("@section rent costs"),
+            ParsedLine::SectionStart(Some("rent costs".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_section_start_without_name() {
+        assert_eq!(parse_line("  @section  "), ParsedLine::SectionStart(None));
+    }
+
+    #[test]
+    fn test_parse_section_end() {
+        assert_eq!(parse_line("@end"), ParsedLine::SectionEnd);
+    }
+
+    #[test]
+    fn test_parse_unknown_directive_is_expression() {
+        assert_eq!(
+            parse_line("@foo"),
+            ParsedLine::Expression("@foo".to_string())
+        );
+        assert_eq!(
+            parse_line("@end now"),
+            ParsedLine::Expression("@end now".to_string())
+        );
+    }
+
+    // Total marker tests
+    #[test]
+    fn test_parse_total_markers() {
+        assert_eq!(parse_line("total"), ParsedLine::Total);
+        assert_eq!(parse_line("  ----  "), ParsedLine::Total);
+        assert_eq!(parse_line("---"), ParsedLine::Total);
+    }
+
+    #[test]
+    fn test_parse_total_lookalikes_are_not_markers() {
+        assert_eq!(parse_line("--"), ParsedLine::Expression("--".to_string()));
+        assert_eq!(
+            parse_line("total * 2"),
+            ParsedLine::Expression("total * 2".to_string())
+        );
+        assert!(matches!(
+            parse_line("total = 5"),
+            ParsedLine::Assignment { .. }
+        ));
+    }
+
+    // Multiple assignment tests
+    #[test]
+    fn test_parse_multiple_assignment() {
+        assert_eq!(
+            parse_line("a, b = 3, max(1, 2)"),
+            ParsedLine::MultipleAssignment {
+                names: vec!["a".to_string(), "b".to_string()],
+                expressions: vec!["3".to_string(), "max(1, 2)".to_string()],
+                constant: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_multiple_const_declaration() {
+        assert_eq!(
+            parse_line("const lo, hi = 1, 9"),
+            ParsedLine::MultipleAssignment {
+                names: vec!["lo".to_string(), "hi".to_string()],
+                expressions: vec!["1".to_string(), "9".to_string()],
+                constant: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_multiple_assignment_keeps_text_commas() {
+        assert_eq!(
+            parse_line(r#"a, b = convert(1, "km, x", "m"), 2"#),
+            ParsedLine::MultipleAssignment {
+                names: vec!["a".to_string(), "b".to_string()],
+                expressions: vec![r#"convert(1, "km, x", "m")"#.to_string(), "2".to_string()],
+                constant: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_multiple_assignment_with_invalid_name_is_expression() {
+        assert_eq!(
+            parse_line("a, 2b = 1, 2"),
+            ParsedLine::Expression("a, 2b = 1, 2".to_string())
+        );
+    }
+
+    // Unset tests
+    #[test]
+    fn test_parse_unset() {
+        assert_eq!(
+            parse_line("unset x"),
+            ParsedLine::Unset(vec!["x".to_string()])
+        );
+        assert_eq!(
+            parse_line("unset a, b"),
+            ParsedLine::Unset(vec!["a".to_string(), "b".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_parse_variable_named_unset() {
+        assert!(matches!(
+            parse_line("unset = 1"),
+            ParsedLine::Assignment { .. }
+        ));
+        assert_eq!(
+            parse_line("unset x + 1"),
+            ParsedLine::Expression("unset x + 1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_clearvars() {
+        assert_eq!(parse_line(" clearvars "), ParsedLine::ClearVariables);
+    }
+
+    // Disabled line tests
+    #[test]
+    fn test_parse_disabled_line() {
+        assert_eq!(parse_line("# x = 5"), ParsedLine::Disabled);
+        assert_eq!(parse_line("  #total"), ParsedLine::Disabled);
+        assert_eq!(parse_line("#"), ParsedLine::Disabled);
+    }
+
     // Empty line tests
     #[test]
     fn test_parse_empty_line() {
@@ -125,6 +463,7 @@ mod tests {
             ParsedLine::Assignment {
                 name: "a".to_string(),
                 expression: "5".to_string(),
+                constant: false,
             }
         );
     }
@@ -136,6 +475,7 @@ mod tests {
             ParsedLine::Assignment {
                 name: "result".to_string(),
                 expression: "5 + 3 * 2".to_string(),
+                constant: false,
             }
         );
     }
@@ -147,6 +487,7 @@ mod tests {
             ParsedLine::Assignment {
                 name: "x".to_string(),
                 expression: "10".to_string(),
+                constant: false,
             }
         );
     }
@@ -158,6 +499,7 @@ mod tests {
             ParsedLine::Assignment {
                 name: "_private".to_string(),
                 expression: "42".to_string(),
+                constant: false,
             }
         );
     }
@@ -169,6 +511,7 @@ mod tests {
             ParsedLine::Assignment {
                 name: "my_var_123".to_string(),
                 expression: "100".to_string(),
+                constant: false,
             }
         );
     }
@@ -240,6 +583,7 @@ mod tests {
             ParsedLine::Assignment {
                 name: "a".to_string(),
                 expression: "b == c".to_string(),
+                constant: false,
             }
         );
     }
@@ -0,0 +1,262 @@
+//! Scripting lines for loops and conditionals (`scripting` feature).
+//!
+//! A line starting with `!` is run as a [Rhai](https://rhai.rs) script
+//! instead of being evaluated as an expression. The calculator's variables
+//! are in scope, whole numbers as integers and the others as floats, and
+//! list variables as arrays. Numbers the script assigns or declares with
+//! `let` are written back, so later lines can use them.
+//!
+//! Scripts are sandboxed: the engine has no access to files, modules or the
+//! terminal (`import` resolves nothing and `print` is discarded), and a
+//! script is stopped after [`MAX_OPERATIONS`] operations or when it nests
+//! calls, expressions, strings or arrays beyond small limits.
+
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Scope};
+
+use super::constants::is_math_constant;
+use super::error::ErrorCode;
+use super::{EvalContext, EvalError};
+
+/// Maximum number of operations a single script line may execute.
+pub const MAX_OPERATIONS: u64 = 100_000;
+
+/// Maximum depth of nested function calls in a script.
+const MAX_CALL_LEVELS: usize = 32;
+
+/// Maximum depth of nested expressions in a script, at the top level and
+/// inside functions.
+const MAX_EXPRESSION_DEPTHS: (usize, usize) = (64, 32);
+
+/// Maximum length of a string built by a script.
+const MAX_STRING_SIZE: usize = 10_000;
+
+/// Maximum number of elements of an array or object map built by a script.
+const MAX_COLLECTION_SIZE: usize = 10_000;
+
+thread_local! {
+    /// The sandboxed engine, built once per thread.
+    static ENGINE: Engine = sandboxed_engine();
+}
+
+/// Builds an engine that cannot reach outside of the script and stops
+/// runaway scripts.
+fn sandboxed_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_expr_depths(MAX_EXPRESSION_DEPTHS.0, MAX_EXPRESSION_DEPTHS.1)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_COLLECTION_SIZE)
+        .set_max_map_size(MAX_COLLECTION_SIZE)
+        .set_module_resolver(DummyModuleResolver::new())
+        .on_print(|_| {})
+        .on_debug(|_, _, _| {});
+    engine.disable_symbol("eval");
+    engine
+}
+
+/// Returns the script following the `!` prefix, if `line` is a script line.
+#[must_use]
+pub fn script_body(line: &str) -> Option<&str> {
+    line.trim_start().strip_prefix('!')
+}
+
+/// Runs a script against `context`.
+///
+/// Returns the value of the script's last expression, or `None` if it is
+/// not a number, as for a script ending in an assignment.
+///
+/// # Errors
+/// Returns an `EvalError` if the script cannot be parsed, fails while
+/// running, reassigns a constant, or exceeds the limits of the sandbox. The
+/// variables are only written back if the script succeeds.
+pub fn run_script(script: &str, context: &mut EvalContext) -> Result<Option<f64>, EvalError> {
+    let mut scope = scope_of(context);
+    let result = ENGINE
+        .with(|engine| engine.eval_with_scope::<Dynamic>(&mut scope, script))
+        .map_err(|e| script_error(&e))?;
+
+    for (name, constant, value) in scope.iter_raw() {
+        if constant {
+            continue;
+        }
+        if let Ok(n) = value.as_int() {
+            if context.get_int(name) != Some(n) {
+                context.set_int(name, n);
+            }
+        } else if let Ok(x) = value.as_float()
+            && context
+                .get_variable(name)
+                .is_none_or(|old| old.to_bits() != x.to_bits())
+        {
+            context.set_variable(name, x);
+        }
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let value = result
+        .as_int()
+        .map(|n| n as f64)
+        .or_else(|_| result.as_float())
+        .ok();
+    Ok(value)
+}
+
+/// Puts the variables of `context` into a script scope: constants as
+/// constants, whole numbers as integers, other numbers as floats, and list
+/// variables as arrays of floats.
+fn scope_of(context: &EvalContext) -> Scope<'static> {
+    let mut scope = Scope::new();
+    for (name, value) in context.iter() {
+        let value = context
+            .get_int(name)
+            .map_or_else(|| Dynamic::from_float(value), Dynamic::from_int);
+        if context.is_constant(name) || is_math_constant(name) {
+            scope.push_constant_dynamic(name.to_string(), value);
+        } else {
+            scope.push_dynamic(name.to_string(), value);
+        }
+    }
+    for (name, values) in context.lists() {
+        let array: Array = values.iter().copied().map(Dynamic::from_float).collect();
+        scope.push_constant(name.clone(), array);
+    }
+    scope
+}
+
+/// Turns a script failure into an error shown on the line.
+fn script_error(error: &EvalAltResult) -> EvalError {
+    match error {
+        EvalAltResult::ErrorTooManyOperations(_) => {
+            EvalError::new(format!("script exceeded {MAX_OPERATIONS} operations"))
+                .with_code(ErrorCode::Limit)
+        }
+        EvalAltResult::ErrorDataTooLarge(..) | EvalAltResult::ErrorStackOverflow(_) => {
+            EvalError::new(format!("script exceeded the sandbox limits: {error}"))
+                .with_code(ErrorCode::Limit)
+        }
+        EvalAltResult::ErrorAssignmentToConstant(name, _) => {
+            EvalError::new(format!("cannot reassign constant '{name}'"))
+                .with_code(ErrorCode::ReassignedConstant)
+        }
+        EvalAltResult::ErrorVariableNotFound(name, _) => EvalError::undefined_variable(name),
+        EvalAltResult::ErrorParsing(..) => {
+            EvalError::new(format!("script: {error}")).with_code(ErrorCode::Syntax)
+        }
+        _ => EvalError::new(format!("script: {error}")).with_code(ErrorCode::Script),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(script: &str, context: &mut EvalContext) -> Result<Option<f64>, EvalError> {
+        run_script(script, context)
+    }
+
+    #[test]
+    fn test_script_body_requires_bang_prefix() {
+        assert_eq!(script_body("  ! x = 1"), Some(" x = 1"));
+        assert_eq!(script_body("5!"), None);
+    }
+
+    #[test]
+    fn test_variables_are_read_and_written_back() {
+        let mut context = EvalContext::new();
+        context.set_variable("rate", 0.5);
+        assert_eq!(
+            run("let a = 2; let b = a * 3; rate = b * rate; b", &mut context),
+            Ok(Some(6.0))
+        );
+        assert_eq!(context.get_int("a"), Some(2));
+        assert_eq!(context.get_variable("rate"), Some(3.0));
+    }
+
+    #[test]
+    fn test_if_else() {
+        let mut context = EvalContext::new();
+        context.set_variable("x", 5.0);
+        run("let y = if x > 3 { 1 } else { 2 };", &mut context).unwrap();
+        assert_eq!(context.get_int("y"), Some(1));
+    }
+
+    #[test]
+    fn test_loops() {
+        let mut context = EvalContext::new();
+        context.set_variable("n", 64.0);
+        run(
+            "let s = 0; for i in 1..5 { s += i } let k = 0; while n > 1 { n /= 2; k += 1 }",
+            &mut context,
+        )
+        .unwrap();
+        assert_eq!(context.get_int("s"), Some(10));
+        assert_eq!(context.get_int("k"), Some(6));
+    }
+
+    #[test]
+    fn test_whole_numbers_stay_exact() {
+        let mut context = EvalContext::new();
+        context.set_int("big", (1 << 60) + 1);
+        run("big += 1;", &mut context).unwrap();
+        assert_eq!(context.get_int("big"), Some((1 << 60) + 2));
+    }
+
+    #[test]
+    fn test_lists_are_arrays() {
+        let mut context = EvalContext::new();
+        context.set_list("xs", vec![1.0, 2.5].into());
+        assert_eq!(
+            run("let s = 0.0; for x in xs { s += x } s", &mut context),
+            Ok(Some(3.5))
+        );
+    }
+
+    #[test]
+    fn test_infinite_loop_is_stopped() {
+        let mut context = EvalContext::new();
+        let err = run("loop { }", &mut context).unwrap_err();
+        assert_eq!(err.message(), "script exceeded 100000 operations");
+        assert_eq!(err.code(), ErrorCode::Limit);
+    }
+
+    #[test]
+    fn test_sandbox_has_no_modules_or_eval() {
+        let mut context = EvalContext::new();
+        assert!(run("import \"/etc/passwd\" as m;", &mut context).is_err());
+        assert!(run("eval(\"1\")", &mut context).is_err());
+        assert!(run("let s = \"x\"; loop { s += s }", &mut context).is_err());
+    }
+
+    #[test]
+    fn test_constants_cannot_be_reassigned() {
+        let mut context = EvalContext::new();
+        context.set_constant("rate", 0.2);
+        let err = run("rate = 1", &mut context).unwrap_err();
+        assert_eq!(err.message(), "cannot reassign constant 'rate'");
+        assert_eq!(err.code(), ErrorCode::ReassignedConstant);
+        assert!(run("pi = 3", &mut context).is_err());
+    }
+
+    #[test]
+    fn test_errors_leave_variables_unchanged() {
+        let mut context = EvalContext::new();
+        context.set_variable("x", 1.0);
+        let err = run("x = 2; throw \"stop\";", &mut context).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::Script);
+        assert!(run("x = ", &mut context).is_err());
+        assert_eq!(context.get_variable("x"), Some(1.0));
+
+        let err = run("let y = missing + 1;", &mut context).unwrap_err();
+        assert_eq!(err.code(), ErrorCode::UndefinedVariable);
+        assert!(err.span().is_none());
+    }
+
+    #[test]
+    fn test_script_without_a_value_shows_nothing() {
+        let mut context = EvalContext::new();
+        assert_eq!(run("", &mut context), Ok(None));
+        assert_eq!(run("let x = 1;", &mut context), Ok(None));
+    }
+}
@@ -0,0 +1,370 @@
+//! Shell command substitution for `` `wc -l < notes.txt` `` and
+//! `shell("du -b file")`.
+//!
+//! A command runs through the system shell and the first word of its
+//! standard output becomes the value, so file sizes and line counts can
+//! enter calculations. Running commands from a worksheet is dangerous, so it
+//! is off unless the `shell` setting is switched on.
+//!
+//! Evaluating a command never runs it: the screen is drawn with the last
+//! result, and a command without a fresh result is only requested. The event
+//! loop starts requested commands with `start_requested_commands`, which
+//! holds back those of the line being typed, and each runs on its own thread.
+//! Commands get no input, are killed with everything they started after
+//! `COMMAND_TIMEOUT`, and their results are reused for `CACHE_DURATION`.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::functions::Argument;
+use crate::eval::token::{Token, Tokenizer};
+
+/// How long a command may run before it is killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often a running command is checked for completion.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// How long the result of a command is reused before it runs again.
+const CACHE_DURATION: Duration = Duration::from_secs(10);
+
+/// The error shown until a command has finished for the first time.
+const RUNNING_MESSAGE: &str = "shell command is running";
+
+/// The outcome of a command and when it finished.
+type CachedResult = (Instant, Result<f64, String>);
+
+/// What is known about a command.
+#[derive(Debug)]
+enum Entry {
+    /// The command is running; the result of its last run, if any, is
+    /// shown meanwhile.
+    Running(Receiver<Result<f64, String>>, Option<CachedResult>),
+    /// The command has finished.
+    Finished(CachedResult),
+}
+
+thread_local! {
+    /// Whether shell commands may run.
+    static SHELL_COMMANDS_ENABLED: Cell<bool> = const { Cell::new(false) };
+
+    /// Running commands and recent results, by command text.
+    static RESULTS: RefCell<HashMap<String, Entry>> = RefCell::new(HashMap::new());
+
+    /// Commands evaluated without a fresh result, waiting to be started.
+    static REQUESTED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Allows or forbids running shell commands on the current thread.
+pub fn set_shell_commands(enabled: bool) {
+    SHELL_COMMANDS_ENABLED.set(enabled);
+}
+
+/// Returns whether shell commands may run.
+#[must_use]
+pub fn shell_commands() -> bool {
+    SHELL_COMMANDS_ENABLED.get()
+}
+
+/// Implements the `shell("command")` built-in and backtick substitution.
+///
+/// Returns the last result of the command, and requests a new run when
+/// there is none or it is older than `CACHE_DURATION`.
+pub(crate) fn shell_argument(args: &[Argument]) -> Result<f64, EvalError> {
+    let [Argument::Text(command)] = args else {
+        return Err(EvalError::new(
+            "shell expects a command in quotes, e.g. shell(\"wc -l < file\")",
+        )
+        .with_code(ErrorCode::InvalidArgument));
+    };
+    if !shell_commands() {
+        return Err(
+            EvalError::new("shell commands are off; enable them with :set shell on")
+                .with_code(ErrorCode::Shell),
+        );
+    }
+    let now = Instant::now();
+    let (last, fresh) = RESULTS.with_borrow_mut(|results| {
+        let entry = results.get_mut(command);
+        if let Some(Entry::Running(receiver, last)) = entry {
+            let finished = match receiver.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => Some(Err("shell command failed".to_string())),
+            };
+            match finished {
+                Some(result) => {
+                    results.insert(command.clone(), Entry::Finished((now, result)));
+                }
+                None => return (last.clone(), true),
+            }
+        }
+        match results.get(command) {
+            Some(Entry::Finished((finished_at, result))) => (
+                Some((*finished_at, result.clone())),
+                now.saturating_duration_since(*finished_at) < CACHE_DURATION,
+            ),
+            _ => (None, false),
+        }
+    });
+    if !fresh {
+        REQUESTED.with_borrow_mut(|requested| {
+            if !requested.contains(command) {
+                requested.push(command.clone());
+            }
+        });
+    }
+    let result = last.map_or_else(|| Err(RUNNING_MESSAGE.to_string()), |(_, result)| result);
+    result.map_err(|e| EvalError::new(e).with_code(ErrorCode::Shell))
+}
+
+/// Starts the commands requested since the last call, except those `held`
+/// back, each on its own thread.
+///
+/// Held commands are dropped; they are requested again the next time they
+/// are evaluated.
+pub fn start_requested_commands(held: impl Fn(&str) -> bool) {
+    let requested = REQUESTED.take();
+    if !shell_commands() {
+        return;
+    }
+    RESULTS.with_borrow_mut(|results| {
+        for command in requested.into_iter().filter(|command| !held(command)) {
+            let last = match results.remove(&command) {
+                Some(Entry::Running(receiver, last)) => {
+                    results.insert(command, Entry::Running(receiver, last));
+                    continue;
+                }
+                Some(Entry::Finished(last)) => Some(last),
+                None => None,
+            };
+            let (sender, receiver) = mpsc::channel();
+            let text = command.clone();
+            thread::spawn(move || sender.send(run(&text)));
+            results.insert(command, Entry::Running(receiver, last));
+        }
+    });
+}
+
+/// Returns the shell commands in `line`, in backticks or passed to `shell`.
+#[must_use]
+pub fn commands_in(line: &str) -> Vec<String> {
+    let Ok(tokens) = Tokenizer::new(line).tokenize() else {
+        return Vec::new();
+    };
+    let tokens: Vec<Token> = tokens.into_iter().map(|(token, _)| token).collect();
+    let mut commands = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        match (token, tokens.get(i + 1), tokens.get(i + 2)) {
+            (Token::Command(command), _, _) => commands.push(command.clone()),
+            (Token::Identifier(name), Some(Token::LParen), Some(Token::Text(command)))
+                if name == "shell" =>
+            {
+                commands.push(command.clone());
+            }
+            _ => {}
+        }
+    }
+    commands
+}
+
+/// Runs `command` and parses the first word of its output as a number.
+fn run(command: &str) -> Result<f64, String> {
+    let output = run_with_timeout(command)?;
+    let word = output.split_whitespace().next().unwrap_or_default();
+    word.parse()
+        .map_err(|_| format!("shell output is not a number: '{word}'"))
+}
+
+/// Runs `command` through the system shell and returns its standard output.
+///
+/// The output is read on another thread while the command runs, so that a
+/// command printing more than the pipe holds is not blocked.
+fn run_with_timeout(command: &str) -> Result<String, String> {
+    let mut child = spawn(command).map_err(|e| format!("could not run shell: {e}"))?;
+    let (sender, output) = mpsc::channel();
+    if let Some(mut stdout) = child.stdout.take() {
+        thread::spawn(move || {
+            let mut output = String::new();
+            let read = stdout.read_to_string(&mut output).map(|_| output);
+            sender.send(read)
+        });
+    }
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() < COMMAND_TIMEOUT => thread::sleep(POLL_INTERVAL),
+            Ok(None) => {
+                kill(&mut child);
+                return Err(timed_out());
+            }
+            Err(e) => {
+                kill(&mut child);
+                return Err(format!("shell command failed: {e}"));
+            }
+        }
+    };
+    if !status.success() {
+        return Err(format!("shell command failed with {status}"));
+    }
+
+    // A command may leave a process behind that still holds the output.
+    let remaining = COMMAND_TIMEOUT.saturating_sub(started.elapsed());
+    match output.recv_timeout(remaining) {
+        Ok(read) => read.map_err(|e| format!("could not read shell output: {e}")),
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            kill_group(&child);
+            Err(timed_out())
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => Ok(String::new()),
+    }
+}
+
+/// Returns the error of a command that ran too long.
+fn timed_out() -> String {
+    format!(
+        "shell command timed out after {}s",
+        COMMAND_TIMEOUT.as_secs()
+    )
+}
+
+/// Starts `command` through the system shell, in a process group of its own
+/// so that it can be killed together with the processes it starts.
+#[cfg(unix)]
+fn spawn(command: &str) -> std::io::Result<Child> {
+    use std::os::unix::process::CommandExt;
+
+    Command::new("sh")
+        .args(["-c", command])
+        .process_group(0)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+}
+
+/// Starts `command` through the system shell.
+#[cfg(not(unix))]
+fn spawn(command: &str) -> std::io::Result<Child> {
+    Command::new("cmd")
+        .args(["/C", command])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+}
+
+/// Kills a command with the processes it started, and waits for it.
+fn kill(child: &mut Child) {
+    kill_group(child);
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Kills the processes a command started, which are in its process group.
+#[cfg(unix)]
+fn kill_group(child: &Child) {
+    let _ = Command::new("kill")
+        .args(["-KILL", "--", &format!("-{}", child.id())])
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Kills the process tree of a command.
+#[cfg(not(unix))]
+fn kill_group(child: &Child) {
+    let _ = Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &child.id().to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Evaluates `command`, starting it and waiting until it has finished.
+    fn shell(command: &str) -> Result<f64, EvalError> {
+        let args = [Argument::Text(command.to_string())];
+        loop {
+            match shell_argument(&args) {
+                Err(e) if e.message() == RUNNING_MESSAGE => {
+                    start_requested_commands(|_| false);
+                    thread::sleep(POLL_INTERVAL);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    #[test]
+    fn test_shell_commands_are_off_by_default() {
+        let error = shell("echo 1").unwrap_err();
+        assert!(error.message().contains(":set shell on"));
+    }
+
+    #[test]
+    fn test_shell_output_becomes_value() {
+        set_shell_commands(true);
+        assert_eq!(shell("printf '42 files\\n'"), Ok(42.0));
+        assert!(shell("echo many").is_err());
+        assert!(shell("exit 3").is_err());
+        set_shell_commands(false);
+    }
+
+    #[test]
+    fn test_shell_command_runs_only_when_started() {
+        set_shell_commands(true);
+        let args = [Argument::Text("echo 7".to_string())];
+        assert_eq!(
+            shell_argument(&args).unwrap_err().message(),
+            RUNNING_MESSAGE
+        );
+        start_requested_commands(|command| command == "echo 7");
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(
+            shell_argument(&args).unwrap_err().message(),
+            RUNNING_MESSAGE
+        );
+        assert_eq!(shell("echo 7"), Ok(7.0));
+        set_shell_commands(false);
+    }
+
+    #[test]
+    fn test_large_output_is_read_while_the_command_runs() {
+        set_shell_commands(true);
+        let started = Instant::now();
+        let command = "printf '7 '; head -c 200000 /dev/zero | tr '\\0' x";
+        assert_eq!(shell(command), Ok(7.0));
+        assert!(started.elapsed() < COMMAND_TIMEOUT);
+        set_shell_commands(false);
+    }
+
+    #[test]
+    fn test_shell_command_times_out() {
+        set_shell_commands(true);
+        let started = Instant::now();
+        let error = shell("sleep 5 | cat").unwrap_err();
+        assert!(error.message().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(4));
+        set_shell_commands(false);
+    }
+
+    #[test]
+    fn test_commands_in_line() {
+        assert_eq!(
+            commands_in("`wc -l < a` + shell(\"du -b b\") * 2"),
+            ["wc -l < a", "du -b b"]
+        );
+        assert!(commands_in("`echo 1").is_empty());
+    }
+}
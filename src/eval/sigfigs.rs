@@ -0,0 +1,139 @@
+//! Significant figures of measured values.
+//!
+//! A decimal literal carries as many significant figures as it has digits,
+//! not counting leading zeros, nor trailing zeros of a number without a
+//! decimal point: `0.0120` has three, `1.2e3` has two. Whole numbers written
+//! without a decimal point or exponent, such as the `2` in `pi * r^2`, count
+//! as exact, as do base literals and constants. A line's result has the
+//! fewest significant figures among the literals it uses and the variables
+//! assigned from earlier results, so `2.0 * 3.14159` has two.
+//!
+//! The figures only affect how a result is displayed; later lines always
+//! compute with the full value.
+
+use crate::eval::context::EvalContext;
+use crate::eval::token::{Token, Tokenizer};
+
+/// Results at least this small are shown in scientific notation.
+const MIN_FIXED_MAGNITUDE: i32 = -4;
+
+/// Returns the significant figures of the result of `expression`, or `None`
+/// if it involves no measured value.
+#[must_use]
+pub fn significant_figures(expression: &str, context: &EvalContext) -> Option<u32> {
+    let (tokens, _) =
+        Tokenizer::with_options(expression, context.token_options()).tokenize_partial();
+    tokens
+        .iter()
+        .filter_map(|(token, span)| match token {
+            Token::Number(_) => literal_figures(&expression[span.start..span.end]),
+            Token::Identifier(name) => context.significant_figures(name),
+            _ => None,
+        })
+        .min()
+}
+
+/// Counts the significant figures of a decimal literal such as `0.0120`,
+/// `1.5e3` or `4.7k`.
+///
+/// Returns `None` for whole numbers, base literals and zero, which count as
+/// exact.
+#[allow(clippy::cast_possible_truncation)]
+fn literal_figures(literal: &str) -> Option<u32> {
+    let base_literal = ["0x", "0X", "0b", "0B", "0o", "0O"]
+        .iter()
+        .any(|prefix| literal.starts_with(prefix));
+    if base_literal || literal.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mantissa = literal
+        .split(['e', 'E'])
+        .next()
+        .unwrap_or(literal)
+        .trim_end_matches(|c: char| !(c.is_ascii_digit() || c == '.'));
+    let digits = if mantissa.contains('.') {
+        mantissa.replace('.', "")
+    } else {
+        mantissa.trim_end_matches('0').to_string()
+    };
+    let significant = digits.trim_start_matches('0').len();
+    (significant > 0).then_some(significant as u32)
+}
+
+/// Formats `value` rounded to `figures` significant figures.
+///
+/// Trailing zeros are kept, since they are significant: `2.0` to two
+/// figures is `2.0`. Values whose rounding would need zeros before the
+/// decimal point, or that are very small, use scientific notation:
+/// `12345` to two figures is `1.2e4`. Returns `None` for values that are not
+/// finite.
+#[must_use]
+pub fn format_significant(value: f64, figures: u32) -> Option<String> {
+    if !value.is_finite() || figures == 0 {
+        return None;
+    }
+    let places = figures as usize - 1;
+    let scientific = format!("{value:.places$e}");
+    let exponent: i32 = scientific.rsplit('e').next()?.parse().ok()?;
+    let decimals = i32::try_from(places).ok()? - exponent;
+    if value != 0.0 && (decimals < 0 || exponent < MIN_FIXED_MAGNITUDE) {
+        return Some(scientific);
+    }
+    let decimals = usize::try_from(decimals).unwrap_or(places);
+    Some(format!("{value:.decimals$}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_figures() {
+        assert_eq!(literal_figures("3.14159"), Some(6));
+        assert_eq!(literal_figures("0.0120"), Some(3));
+        assert_eq!(literal_figures("1200.0"), Some(5));
+        assert_eq!(literal_figures("1.50e3"), Some(3));
+        assert_eq!(literal_figures("1200e3"), Some(2));
+        assert_eq!(literal_figures(".5"), Some(1));
+        assert_eq!(literal_figures("1200"), None);
+        assert_eq!(literal_figures("0.0"), None);
+        assert_eq!(literal_figures("0xff"), None);
+        assert_eq!(literal_figures("0base36:z1"), None);
+    }
+
+    #[test]
+    fn test_literal_figures_ignore_engineering_prefix() {
+        let mut context = EvalContext::new();
+        context.set_engineering_prefixes(true);
+        assert_eq!(significant_figures("4.70k", &context), Some(3));
+        assert_eq!(significant_figures("470k", &context), Some(2));
+    }
+
+    #[test]
+    fn test_result_has_fewest_figures_of_inputs() {
+        let mut context = EvalContext::new();
+        assert_eq!(significant_figures("2.0 * 3.14159", &context), Some(2));
+        assert_eq!(significant_figures("pi * 0xff * 2", &context), None);
+
+        context.set_significant_figures("r", Some(3));
+        assert_eq!(significant_figures("pi * r^2", &context), Some(3));
+        assert_eq!(significant_figures("sqrt(r)", &context), Some(3));
+    }
+
+    #[test]
+    fn test_format_significant() {
+        assert_eq!(format_significant(2.468, 2).as_deref(), Some("2.5"));
+        assert_eq!(format_significant(2.0, 2).as_deref(), Some("2.0"));
+        assert_eq!(format_significant(9.96, 2).as_deref(), Some("10"));
+        assert_eq!(format_significant(1234.5, 4).as_deref(), Some("1234"));
+        assert_eq!(format_significant(12345.0, 2).as_deref(), Some("1.2e4"));
+        assert_eq!(format_significant(0.012_34, 2).as_deref(), Some("0.012"));
+        assert_eq!(
+            format_significant(0.000_012_34, 2).as_deref(),
+            Some("1.2e-5")
+        );
+        assert_eq!(format_significant(-2.5, 3).as_deref(), Some("-2.50"));
+        assert_eq!(format_significant(0.0, 3).as_deref(), Some("0.00"));
+        assert_eq!(format_significant(f64::NAN, 3), None);
+    }
+}
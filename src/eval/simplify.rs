@@ -0,0 +1,417 @@
+//! Symbolic simplification for `simplify(expression)`.
+//!
+//! The expression is rewritten as a polynomial: a sum of terms, each a
+//! coefficient times a product of factors raised to whole powers. Like terms
+//! are collected, so `2*x + 3*x` becomes `5 * x` and `(x + 1)^2` becomes
+//! `x^2 + 2 * x + 1`. Anything that is not a polynomial, such as `sqrt(x)` or
+//! a division by `x + 1`, is kept as an opaque factor with its arguments
+//! simplified, and parts without variables are evaluated to numbers.
+//!
+//! A line consisting of a `simplify` call shows the simplified expression.
+//! Used inside a larger expression, `simplify` returns the value of its
+//! argument.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::eval::ast::{BinaryOp, Expr};
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::evaluator::{AngleMode, evaluate_in_mode};
+use crate::eval::format::format_ast;
+
+/// Largest power to which a sum of several terms is expanded.
+const MAX_EXPANDED_POWER: u32 = 8;
+
+/// Significant digits kept in coefficients, hiding floating-point noise such
+/// as the `4` in `0.30000000000000004`.
+const COEFFICIENT_DIGITS: usize = 12;
+
+/// A factor of a term: a variable or an opaque subexpression, ordered by
+/// its printed form.
+#[derive(Debug, Clone)]
+struct Factor {
+    key: String,
+    expr: Expr,
+}
+
+impl Factor {
+    fn new(expr: Expr) -> Self {
+        Self {
+            key: format_ast(&expr),
+            expr,
+        }
+    }
+}
+
+impl PartialEq for Factor {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Factor {}
+
+impl PartialOrd for Factor {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Factor {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// A product of factors with their powers; empty for the constant term.
+type Monomial = BTreeMap<Factor, u32>;
+
+/// A sum of monomials with their coefficients.
+#[derive(Debug, Clone, Default)]
+struct Polynomial {
+    terms: BTreeMap<Monomial, f64>,
+}
+
+impl Polynomial {
+    fn constant(value: f64) -> Self {
+        let mut polynomial = Self::default();
+        polynomial.add_term(Monomial::new(), value);
+        polynomial
+    }
+
+    fn factor(expr: Expr) -> Self {
+        let mut polynomial = Self::default();
+        polynomial.add_term(Monomial::from([(Factor::new(expr), 1)]), 1.0);
+        polynomial
+    }
+
+    /// Returns the value if the polynomial has no factors.
+    fn as_constant(&self) -> Option<f64> {
+        match self.terms.len() {
+            0 => Some(0.0),
+            1 => self.terms.get(&Monomial::new()).copied(),
+            _ => None,
+        }
+    }
+
+    fn add_term(&mut self, monomial: Monomial, coefficient: f64) {
+        let sum = self.terms.get(&monomial).copied().unwrap_or(0.0) + coefficient;
+        if sum == 0.0 {
+            self.terms.remove(&monomial);
+        } else {
+            self.terms.insert(monomial, sum);
+        }
+    }
+
+    fn add(mut self, other: Self) -> Self {
+        for (monomial, coefficient) in other.terms {
+            self.add_term(monomial, coefficient);
+        }
+        self
+    }
+
+    fn scale(self, factor: f64) -> Self {
+        let mut scaled = Self::default();
+        for (monomial, coefficient) in self.terms {
+            scaled.add_term(monomial, coefficient * factor);
+        }
+        scaled
+    }
+
+    fn multiply(&self, other: &Self) -> Self {
+        let mut product = Self::default();
+        for (left, a) in &self.terms {
+            for (right, b) in &other.terms {
+                let mut monomial = left.clone();
+                for (factor, power) in right {
+                    *monomial.entry(factor.clone()).or_insert(0) += power;
+                }
+                product.add_term(monomial, a * b);
+            }
+        }
+        product
+    }
+
+    fn power(&self, exponent: u32) -> Option<Self> {
+        if self.terms.len() > 1 && exponent > MAX_EXPANDED_POWER {
+            return None;
+        }
+        Some((0..exponent).fold(Self::constant(1.0), |acc, _| acc.multiply(self)))
+    }
+
+    /// Divides by a single term, if it divides every term exactly.
+    fn divide(&self, divisor: &Self) -> Option<Self> {
+        let [(divisor, by)] = divisor.terms.iter().collect::<Vec<_>>()[..] else {
+            return None;
+        };
+        let mut quotient = Self::default();
+        for (monomial, coefficient) in &self.terms {
+            let mut monomial = monomial.clone();
+            for (factor, power) in divisor {
+                let remaining = monomial.get(factor)?.checked_sub(*power)?;
+                if remaining == 0 {
+                    monomial.remove(factor);
+                } else {
+                    monomial.insert(factor.clone(), remaining);
+                }
+            }
+            quotient.add_term(monomial, coefficient / by);
+        }
+        Some(quotient)
+    }
+
+    /// Converts back to an expression, highest degree first.
+    #[allow(clippy::float_cmp)]
+    fn to_expr(&self) -> Expr {
+        let mut terms: Vec<_> = self.terms.iter().collect();
+        terms.sort_by(|(a, _), (b, _)| degree(b).cmp(&degree(a)).then_with(|| a.cmp(b)));
+
+        let mut sum: Option<Expr> = None;
+        for (monomial, &coefficient) in terms {
+            let coefficient = tidy(coefficient);
+            sum = Some(match sum {
+                // A leading negative term carries its sign in the
+                // coefficient, or as `-x` when the coefficient is -1.
+                None if coefficient == -1.0 && !monomial.is_empty() => {
+                    Expr::UnaryMinus(Box::new(term_expr(monomial, 1.0)))
+                }
+                None => term_expr(monomial, coefficient),
+                Some(left) => binary(
+                    left,
+                    if coefficient < 0.0 {
+                        BinaryOp::Sub
+                    } else {
+                        BinaryOp::Add
+                    },
+                    term_expr(monomial, coefficient.abs()),
+                ),
+            });
+        }
+        sum.unwrap_or(Expr::Number(0.0))
+    }
+}
+
+/// Returns the total power of a monomial.
+fn degree(monomial: &Monomial) -> u32 {
+    monomial.values().sum()
+}
+
+/// Builds `coefficient * factor^power * ...`, leaving out a coefficient of 1.
+#[allow(clippy::float_cmp)]
+fn term_expr(monomial: &Monomial, coefficient: f64) -> Expr {
+    let factors = monomial.iter().map(|(factor, &power)| {
+        if power == 1 {
+            factor.expr.clone()
+        } else {
+            binary(
+                factor.expr.clone(),
+                BinaryOp::Pow,
+                Expr::Number(f64::from(power)),
+            )
+        }
+    });
+    let mut product =
+        (coefficient != 1.0 || monomial.is_empty()).then_some(Expr::Number(coefficient));
+    for factor in factors {
+        product = Some(match product {
+            None => factor,
+            Some(left) => binary(left, BinaryOp::Mul, factor),
+        });
+    }
+    product.unwrap_or(Expr::Number(coefficient))
+}
+
+fn binary(left: Expr, op: BinaryOp, right: Expr) -> Expr {
+    Expr::BinaryOp {
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+    }
+}
+
+/// Rounds a coefficient to `COEFFICIENT_DIGITS` significant digits.
+fn tidy(value: f64) -> f64 {
+    format!("{value:.prec$e}", prec = COEFFICIENT_DIGITS - 1)
+        .parse()
+        .unwrap_or(value)
+}
+
+/// Simplifies an expression, collecting like terms.
+///
+/// # Errors
+/// Returns an error for equations and text, which have no simplified form.
+pub fn simplify(expr: &Expr, angle_mode: AngleMode) -> Result<Expr, EvalError> {
+    Ok(polynomial(expr, angle_mode)?.to_expr())
+}
+
+/// Rewrites `expr` as a polynomial.
+fn polynomial(expr: &Expr, angle_mode: AngleMode) -> Result<Polynomial, EvalError> {
+    let polynomial = match expr {
+        Expr::Number(n) => Polynomial::constant(*n),
+        Expr::Variable(name) => Polynomial::factor(Expr::Variable(name.clone())),
+        Expr::UnaryMinus(operand) => polynomial(operand, angle_mode)?.scale(-1.0),
+        Expr::BinaryOp { left, op, right } => {
+            let left = polynomial(left, angle_mode)?;
+            let right = polynomial(right, angle_mode)?;
+            binary_polynomial(left, op, right, angle_mode)
+        }
+        Expr::Factorial(operand) => opaque(
+            Expr::Factorial(Box::new(simplify(operand, angle_mode)?)),
+            angle_mode,
+        ),
+        Expr::FunctionCall { name, args } => {
+            let args = args
+                .iter()
+                .map(|arg| simplify(arg, angle_mode).unwrap_or_else(|_| arg.clone()))
+                .collect();
+            opaque(
+                Expr::FunctionCall {
+                    name: name.clone(),
+                    args,
+                },
+                angle_mode,
+            )
+        }
+        Expr::Text(_) | Expr::Equation { .. } => {
+            return Err(
+                EvalError::new("simplify expects an expression, e.g. simplify(2*x + 3*x)")
+                    .with_code(ErrorCode::InvalidArgument),
+            );
+        }
+    };
+    Ok(polynomial)
+}
+
+/// Combines two polynomials with a binary operator.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn binary_polynomial(
+    left: Polynomial,
+    op: &BinaryOp,
+    right: Polynomial,
+    angle_mode: AngleMode,
+) -> Polynomial {
+    match op {
+        BinaryOp::Add => return left.add(right),
+        BinaryOp::Sub => return left.add(right.scale(-1.0)),
+        BinaryOp::Mul => return left.multiply(&right),
+        BinaryOp::Div => {
+            if let Some(quotient) = left.divide(&right) {
+                return quotient;
+            }
+        }
+        BinaryOp::Pow => {
+            if let Some(exponent) = right.as_constant()
+                && exponent.fract() == 0.0
+                && (0.0..=f64::from(u32::MAX)).contains(&exponent)
+                && let Some(power) = left.power(exponent as u32)
+            {
+                return power;
+            }
+        }
+        BinaryOp::FloorDiv | BinaryOp::Mod => {}
+    }
+    opaque(
+        binary(left.to_expr(), op.clone(), right.to_expr()),
+        angle_mode,
+    )
+}
+
+/// Keeps `expr` as a single factor, or evaluates it if it has no variables.
+fn opaque(expr: Expr, angle_mode: AngleMode) -> Polynomial {
+    match evaluate_in_mode(&expr, &HashMap::new(), angle_mode) {
+        Ok(value) if value.is_finite() => Polynomial::constant(value),
+        _ => Polynomial::factor(expr),
+    }
+}
+
+/// Implements the `simplify(expression)` built-in inside larger
+/// expressions, where it evaluates to the value of its argument.
+pub(crate) fn simplify_value(
+    args: &[Expr],
+    variables: &HashMap<String, f64>,
+    angle_mode: AngleMode,
+) -> Result<f64, EvalError> {
+    simplify(&args[0], angle_mode)?;
+    evaluate_in_mode(&args[0], variables, angle_mode)
+}
+
+/// Returns the simplified form shown for a line whose expression is a
+/// `simplify` call.
+#[must_use]
+pub fn symbolic_form(expr: &Expr, angle_mode: AngleMode) -> Option<String> {
+    match expr {
+        Expr::FunctionCall { name, args } if name == "simplify" && args.len() == 1 => {
+            simplify(&args[0], angle_mode)
+                .ok()
+                .map(|expr| format_ast(&expr))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::parse_expression;
+
+    fn simplified(input: &str) -> String {
+        let expr = parse_expression(input).unwrap();
+        format_ast(&simplify(&expr, AngleMode::Radians).unwrap())
+    }
+
+    #[test]
+    fn test_collects_like_terms() {
+        assert_eq!(simplified("2*x + 3*x"), "5 * x");
+        assert_eq!(simplified("x + y - x"), "y");
+        assert_eq!(simplified("x - x"), "0");
+        assert_eq!(simplified("3x - 5x"), "-2 * x");
+        assert_eq!(simplified("y - x - y - 3"), "-x - 3");
+        assert_eq!(simplified("0.1x + 0.2x"), "0.3 * x");
+    }
+
+    #[test]
+    fn test_expands_products_and_powers() {
+        assert_eq!(simplified("(x + 1)^2"), "x ^ 2 + 2 * x + 1");
+        assert_eq!(simplified("(x + y)(x - y)"), "x ^ 2 - y ^ 2");
+        assert_eq!(simplified("x * x * 2 * y"), "2 * x ^ 2 * y");
+        assert_eq!(simplified("(2x)^3"), "8 * x ^ 3");
+    }
+
+    #[test]
+    fn test_divides_by_constants_and_single_terms() {
+        assert_eq!(simplified("(4x + 2) / 2"), "2 * x + 1");
+        assert_eq!(simplified("(x^2 + x) / x"), "x + 1");
+        assert_eq!(simplified("2x / x"), "2");
+        assert_eq!(simplified("x / (x + 1)"), "x / (x + 1)");
+    }
+
+    #[test]
+    fn test_keeps_other_expressions_as_factors() {
+        assert_eq!(simplified("sqrt(x) + sqrt(x)"), "2 * sqrt(x)");
+        assert_eq!(simplified("sin(2x - x)"), "sin(x)");
+        assert_eq!(simplified("x ^ y * x ^ y"), "(x ^ y) ^ 2");
+        assert_eq!(simplified("x ^ 20 + (x + 1) ^ 20 - (x + 1) ^ 20"), "x ^ 20");
+    }
+
+    #[test]
+    fn test_evaluates_parts_without_variables() {
+        assert_eq!(simplified("sqrt(16) * x + 3!"), "4 * x + 6");
+        assert_eq!(simplified("2 + 3"), "5");
+        assert_eq!(simplified("pi * r^2"), "pi * r ^ 2");
+    }
+
+    #[test]
+    fn test_symbolic_form_only_for_simplify_calls() {
+        let call = parse_expression("simplify(2*x + 3*x)").unwrap();
+        assert_eq!(
+            symbolic_form(&call, AngleMode::Radians),
+            Some("5 * x".to_string())
+        );
+
+        let other = parse_expression("sqrt(2*x)").unwrap();
+        assert_eq!(symbolic_form(&other, AngleMode::Radians), None);
+
+        let equation = parse_expression("simplify(x = 1)").unwrap();
+        assert_eq!(symbolic_form(&equation, AngleMode::Radians), None);
+    }
+}
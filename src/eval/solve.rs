@@ -0,0 +1,177 @@
+//! Equation solving for `solve(equation, x, [root])`.
+//!
+//! The equation `left = right` is rewritten as `f(x) = left - right` and
+//! sampled at a few points. If the samples fit a line or a parabola exactly,
+//! the root is computed in closed form; anything else is rejected rather than
+//! approximated.
+
+use std::collections::HashMap;
+
+use crate::eval::ast::Expr;
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::evaluator::{AngleMode, evaluate_in_mode};
+
+/// Relative tolerance when checking that samples fit a polynomial.
+const FIT_TOLERANCE: f64 = 1e-9;
+
+/// Implements the `solve(equation, x, [root])` built-in.
+///
+/// Linear equations have a single root. Quadratic equations have up to two,
+/// ordered from smallest to largest; `root` selects one (default the first).
+pub(crate) fn solve(
+    args: &[Expr],
+    variables: &HashMap<String, f64>,
+    angle_mode: AngleMode,
+) -> Result<f64, EvalError> {
+    let Expr::Equation { left, right } = &args[0] else {
+        return Err(
+            EvalError::new("solve expects an equation, e.g. solve(2*x + 3 = 11, x)")
+                .with_code(ErrorCode::InvalidArgument),
+        );
+    };
+    let Expr::Variable(unknown) = &args[1] else {
+        return Err(
+            EvalError::new("solve expects the unknown variable as its second argument")
+                .with_code(ErrorCode::InvalidArgument),
+        );
+    };
+    let root = match args.get(2) {
+        Some(expr) => evaluate_in_mode(expr, variables, angle_mode)?,
+        None => 1.0,
+    };
+
+    let mut scope = variables.clone();
+    let mut f = |x: f64| -> Result<f64, EvalError> {
+        scope.insert(unknown.clone(), x);
+        Ok(evaluate_in_mode(left, &scope, angle_mode)?
+            - evaluate_in_mode(right, &scope, angle_mode)?)
+    };
+    let samples = [f(-1.0)?, f(0.0)?, f(1.0)?, f(2.0)?, f(3.0)?];
+    let roots = polynomial_roots(samples, unknown)?;
+
+    roots.get(selected_root(root)?).copied().ok_or_else(|| {
+        EvalError::new(format!("equation has only {} root(s)", roots.len()))
+            .with_code(ErrorCode::Domain)
+    })
+}
+
+/// Converts a 1-based root number into an index.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn selected_root(root: f64) -> Result<usize, EvalError> {
+    if root < 1.0 || root.fract() != 0.0 {
+        return Err(EvalError::new("solve root must be 1 or 2").with_code(ErrorCode::Domain));
+    }
+    Ok(root as usize - 1)
+}
+
+/// Finds the real roots of `f` from its values at x = -1, 0, 1, 2, 3.
+fn polynomial_roots(samples: [f64; 5], unknown: &str) -> Result<Vec<f64>, EvalError> {
+    let [at_minus_one, at_zero, at_one, at_two, at_three] = samples;
+    let c = at_zero;
+    let b = (at_one - at_minus_one) / 2.0;
+    let a = (at_one + at_minus_one).mul_add(0.5, -at_zero);
+
+    let fits = |x: f64, actual: f64| {
+        let expected = a.mul_add(x * x, b.mul_add(x, c));
+        (expected - actual).abs() <= FIT_TOLERANCE * expected.abs().max(actual.abs()).max(1.0)
+    };
+    if !samples.iter().all(|v| v.is_finite()) || !fits(2.0, at_two) || !fits(3.0, at_three) {
+        return Err(EvalError::new(format!(
+            "solve only supports linear and quadratic equations in {unknown}"
+        ))
+        .with_code(ErrorCode::Domain));
+    }
+
+    let scale = b.abs().max(c.abs()).max(1.0);
+    if a.abs() <= FIT_TOLERANCE * scale {
+        if b.abs() <= FIT_TOLERANCE * c.abs().max(1.0) {
+            return Err(EvalError::new(if c.abs() <= FIT_TOLERANCE {
+                format!("equation holds for every {unknown}")
+            } else {
+                "equation has no solution".to_string()
+            })
+            .with_code(ErrorCode::Domain));
+        }
+        return Ok(vec![-c / b]);
+    }
+
+    let discriminant = b.mul_add(b, -4.0 * a * c);
+    if discriminant < 0.0 {
+        return Err(EvalError::new("equation has no real solution").with_code(ErrorCode::Domain));
+    }
+    let sqrt = discriminant.sqrt();
+    let mut roots = vec![(-b - sqrt) / (2.0 * a), (-b + sqrt) / (2.0 * a)];
+    roots.sort_by(f64::total_cmp);
+    roots.dedup();
+    Ok(roots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::parse_expression;
+
+    fn solve_expr(input: &str) -> Result<f64, EvalError> {
+        let Expr::FunctionCall { args, .. } = parse_expression(input).unwrap() else {
+            panic!("expected a function call");
+        };
+        let variables = HashMap::from([("k".to_string(), 5.0)]);
+        solve(&args, &variables, AngleMode::Radians)
+    }
+
+    #[test]
+    fn test_solve_linear() {
+        assert_eq!(solve_expr("solve(2*x + 3 = 11, x)"), Ok(4.0));
+        assert_eq!(solve_expr("solve(x / 4 = k, x)"), Ok(20.0));
+    }
+
+    #[test]
+    fn test_solve_quadratic_roots_in_order() {
+        assert_eq!(solve_expr("solve(x^2 - 5x + 6 = 0, x)"), Ok(2.0));
+        assert_eq!(solve_expr("solve(x^2 - 5x + 6 = 0, x, 2)"), Ok(3.0));
+        assert_eq!(solve_expr("solve(x^2 = 0, x)"), Ok(0.0));
+    }
+
+    #[test]
+    fn test_solve_unknown_shadows_existing_variable() {
+        assert_eq!(solve_expr("solve(k + 1 = 3, k)"), Ok(2.0));
+    }
+
+    #[test]
+    fn test_solve_reports_degenerate_equations() {
+        assert_eq!(
+            solve_expr("solve(x + 1 = x, x)"),
+            Err(EvalError::new("equation has no solution").with_code(ErrorCode::Domain))
+        );
+        assert_eq!(
+            solve_expr("solve(2x = x + x, x)"),
+            Err(EvalError::new("equation holds for every x").with_code(ErrorCode::Domain))
+        );
+        assert_eq!(
+            solve_expr("solve(x^2 = -1, x)"),
+            Err(EvalError::new("equation has no real solution").with_code(ErrorCode::Domain))
+        );
+        assert_eq!(
+            solve_expr("solve(x^2 = 0, x, 2)"),
+            Err(EvalError::new("equation has only 1 root(s)").with_code(ErrorCode::Domain))
+        );
+    }
+
+    #[test]
+    fn test_solve_rejects_other_equations() {
+        assert_eq!(
+            solve_expr("solve(x^3 = 8, x)"),
+            Err(
+                EvalError::new("solve only supports linear and quadratic equations in x")
+                    .with_code(ErrorCode::Domain)
+            )
+        );
+        assert!(solve_expr("solve(sqrt(x) = 2, x)").is_err());
+    }
+
+    #[test]
+    fn test_solve_requires_equation_and_variable() {
+        assert!(solve_expr("solve(2 * x, x)").is_err());
+        assert!(solve_expr("solve(x = 1, 2)").is_err());
+    }
+}
@@ -5,6 +5,96 @@
 
 use std::fmt;
 
+use crate::eval::datasize::size_unit;
+use crate::eval::duration::unit_seconds;
+use crate::eval::money::{Currency, currency_by_code, currency_by_symbol};
+use crate::eval::units::quantity_unit;
+
+/// Largest base accepted in `0base<N>:` literals, whose digits are `0-9`
+/// and `a-z`.
+pub const MAX_LITERAL_BASE: u32 = 36;
+
+/// Engineering prefixes accepted after number literals, with their powers of
+/// ten.
+pub const ENGINEERING_PREFIXES: &[(char, i32)] = &[
+    ('f', -15),
+    ('p', -12),
+    ('n', -9),
+    ('u', -6),
+    ('µ', -6),
+    ('m', -3),
+    ('k', 3),
+    ('M', 6),
+    ('G', 9),
+    ('T', 12),
+];
+
+/// How a worksheet's number literals are read, which depends on its
+/// settings and variables.
+///
+/// An `EvalContext` holds the options of its worksheet; a tokenizer created
+/// with `Tokenizer::new` uses the defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenOptions {
+    /// Whether number literals accept engineering prefixes such as `4.7k`.
+    /// They are off by default, since `2k` otherwise means `2 * k`.
+    pub engineering_prefixes: bool,
+    /// Unit names that are also variables, sorted. A variable shadows the
+    /// unit of the same name, so with `EUR = 4`, `10 EUR` is `10 * EUR`
+    /// rather than an amount of money.
+    pub variable_units: Vec<String>,
+}
+
+/// The options of a tokenizer created without any.
+static DEFAULT_OPTIONS: TokenOptions = TokenOptions {
+    engineering_prefixes: false,
+    variable_units: Vec::new(),
+};
+
+impl TokenOptions {
+    /// Makes the unit `name` read as a variable after a number, if it is a
+    /// unit.
+    pub fn add_variable_unit(&mut self, name: &str) {
+        if let Err(index) = self
+            .variable_units
+            .binary_search_by(|unit| unit.as_str().cmp(name))
+            && is_unit_name(name)
+        {
+            self.variable_units.insert(index, name.to_string());
+        }
+    }
+
+    /// Makes the unit `name` read as a unit again.
+    pub fn remove_variable_unit(&mut self, name: &str) {
+        if let Ok(index) = self
+            .variable_units
+            .binary_search_by(|unit| unit.as_str().cmp(name))
+        {
+            self.variable_units.remove(index);
+        }
+    }
+
+    /// Returns true if the unit `name` is a variable and so is not read as a
+    /// unit.
+    fn is_variable_unit(&self, name: &str) -> bool {
+        self.variable_units
+            .binary_search_by(|unit| unit.as_str().cmp(name))
+            .is_ok()
+    }
+}
+
+/// Returns true if `name` is read as a unit after a number.
+fn is_unit_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let duration = chars
+        .next()
+        .is_some_and(|unit| chars.next().is_none() && unit_seconds(unit).is_some());
+    duration
+        || size_unit(name).is_some()
+        || currency_by_code(name).is_some()
+        || quantity_unit(name).is_some()
+}
+
 /// A span indicating the position of a token in the source string.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Span {
@@ -40,6 +130,8 @@ pub enum Token {
     Star,
     /// Division operator `/`.
     Slash,
+    /// Floor division operator `//`.
+    DoubleSlash,
     /// Modulo operator `%`.
     Percent,
     /// Power operator `^`.
@@ -54,6 +146,34 @@ pub enum Token {
     Equals,
     /// Factorial operator '!'
     Exclaim,
+    /// A double-quoted text literal, such as a unit name.
+    Text(String),
+    /// A backtick-quoted shell command, whose output is substituted.
+    Command(String),
+    /// A duration literal such as `1h30m`, converted to seconds.
+    Duration(f64),
+    /// A data-size literal such as `1.5 GiB`, converted to bytes.
+    Size {
+        /// The size in bytes.
+        bytes: f64,
+        /// Whether the literal used a binary prefix (`KiB`, `MiB`, ...).
+        binary: bool,
+    },
+    /// A quantity labelled with a unit, such as `42 km`, whose unit is only
+    /// shown in rates.
+    Quantity {
+        /// The number, in the unit.
+        value: f64,
+        /// The symbol of the unit.
+        unit: &'static str,
+    },
+    /// A money literal such as `$12.50` or `3 CHF`.
+    Money {
+        /// The amount in major units, e.g. dollars.
+        amount: f64,
+        /// The currency of the amount.
+        currency: &'static Currency,
+    },
 }
 
 /// An error that occurred during tokenization.
@@ -63,16 +183,30 @@ pub struct TokenError {
     pub message: String,
     /// Position in the input where the error occurred.
     pub position: usize,
+    /// End of the character at `position` (byte offset, exclusive).
+    pub end: usize,
 }
 
 impl TokenError {
-    /// Creates a new token error.
+    /// Creates a new token error covering one byte at `position`.
     pub fn new(message: impl Into<String>, position: usize) -> Self {
         Self {
             message: message.into(),
             position,
+            end: position + 1,
         }
     }
+
+    /// Makes the error cover the whole character of `input` at its
+    /// position, which may be longer than one byte.
+    fn covering_char(mut self, input: &str) -> Self {
+        self.end = self.position
+            + input
+                .get(self.position..)
+                .and_then(|rest| rest.chars().next())
+                .map_or(1, char::len_utf8);
+        self
+    }
 }
 
 impl fmt::Display for TokenError {
@@ -89,13 +223,27 @@ pub struct Tokenizer<'a> {
     input: &'a str,
     /// Current position in the input (byte offset).
     position: usize,
+    /// How number literals are read.
+    options: &'a TokenOptions,
 }
 
 impl<'a> Tokenizer<'a> {
-    /// Creates a new tokenizer for the given input.
+    /// Creates a new tokenizer for the given input, with the default
+    /// options.
     #[must_use]
     pub const fn new(input: &'a str) -> Self {
-        Self { input, position: 0 }
+        Self::with_options(input, &DEFAULT_OPTIONS)
+    }
+
+    /// Creates a new tokenizer for the given input, reading number literals
+    /// as `options` say.
+    #[must_use]
+    pub const fn with_options(input: &'a str, options: &'a TokenOptions) -> Self {
+        Self {
+            input,
+            position: 0,
+            options,
+        }
     }
 
     /// Tokenizes the entire input and returns a vector of spanned tokens.
@@ -105,6 +253,18 @@ impl<'a> Tokenizer<'a> {
     /// Returns a `TokenError` if the input contains invalid characters or
     /// malformed numbers (e.g., incomplete scientific notation).
     pub fn tokenize(&mut self) -> Result<Vec<Spanned<Token>>, TokenError> {
+        let (tokens, error) = self.tokenize_partial();
+        error.map_or(Ok(tokens), Err)
+    }
+
+    /// Tokenizes the input up to the first error.
+    ///
+    /// Unlike `tokenize`, the tokens before the error are kept, which lets
+    /// syntax highlighting color partial or invalid input.
+    ///
+    /// # Returns
+    /// The tokens before the first error, and the error if one occurred.
+    pub fn tokenize_partial(&mut self) -> (Vec<Spanned<Token>>, Option<TokenError>) {
         let mut tokens = Vec::new();
 
         while !self.is_at_end() {
@@ -113,11 +273,13 @@ impl<'a> Tokenizer<'a> {
                 break;
             }
 
-            let token = self.next_token()?;
-            tokens.push(token);
+            match self.next_token() {
+                Ok(token) => tokens.push(token),
+                Err(e) => return (tokens, Some(e.covering_char(self.input))),
+            }
         }
 
-        Ok(tokens)
+        (tokens, None)
     }
 
     /// Returns true if we've reached the end of the input.
@@ -178,7 +340,12 @@ impl<'a> Tokenizer<'a> {
             }
             '/' => {
                 self.advance();
-                Token::Slash
+                if self.peek() == Some('/') {
+                    self.advance();
+                    Token::DoubleSlash
+                } else {
+                    Token::Slash
+                }
             }
             '%' => {
                 self.advance();
@@ -208,7 +375,10 @@ impl<'a> Tokenizer<'a> {
                 self.advance();
                 Token::Exclaim
             }
+            '"' => self.parse_text()?,
+            '`' => self.parse_command()?,
             c if c.is_ascii_digit() || c == '.' => self.parse_number()?,
+            c if let Some(currency) = currency_by_symbol(c) => self.parse_money(currency)?,
             c if c.is_alphabetic() || c == '_' => self.parse_identifier(),
             _ => {
                 return Err(TokenError::new(
@@ -222,6 +392,32 @@ impl<'a> Tokenizer<'a> {
         Ok((token, Span::new(start, end)))
     }
 
+    /// Parses a double-quoted text literal.
+    fn parse_text(&mut self) -> Result<Token, TokenError> {
+        let start = self.position;
+        self.advance();
+        let rest = &self.input[self.position..];
+        let Some(len) = rest.find('"') else {
+            return Err(TokenError::new("unterminated text literal", start));
+        };
+        let text = rest[..len].to_string();
+        self.position += len + 1;
+        Ok(Token::Text(text))
+    }
+
+    /// Parses a backtick-quoted shell command.
+    fn parse_command(&mut self) -> Result<Token, TokenError> {
+        let start = self.position;
+        self.advance();
+        let rest = &self.input[self.position..];
+        let Some(len) = rest.find('`') else {
+            return Err(TokenError::new("unterminated shell command", start));
+        };
+        let command = rest[..len].to_string();
+        self.position += len + 1;
+        Ok(Token::Command(command))
+    }
+
     /// Parses a number (integer, float, scientific notation, or base-prefix literal).
     fn parse_number(&mut self) -> Result<Token, TokenError> {
         let start = self.position;
@@ -240,7 +436,10 @@ impl<'a> Tokenizer<'a> {
             }
         }
 
-        // Check for base-prefix literals (0x, 0b, 0o)
+        // Check for base-prefix literals (0base36:, 0x, 0b, 0o)
+        if !has_dot && self.input[self.position..].starts_with("0base") {
+            return self.parse_any_base_literal(start);
+        }
         if !has_dot
             && self.peek() == Some('0')
             && let Some(prefix) = self.peek_next()
@@ -321,7 +520,211 @@ impl<'a> Tokenizer<'a> {
             .parse()
             .map_err(|_| TokenError::new(format!("invalid number '{number_str}'"), start))?;
 
-        Ok(Token::Number(value))
+        if let Some(value) = self.parse_engineering_prefix(value) {
+            return Ok(Token::Number(value));
+        }
+        if let Some(seconds) = self.parse_duration_suffix(value) {
+            return Ok(Token::Duration(seconds));
+        }
+        Ok(self
+            .parse_size_suffix(value)
+            .or_else(|| self.parse_currency_suffix(value))
+            .or_else(|| self.parse_quantity_suffix(value))
+            .unwrap_or(Token::Number(value)))
+    }
+
+    /// Parses an amount written after a currency symbol, such as `$12.50`.
+    fn parse_money(&mut self, currency: &'static Currency) -> Result<Token, TokenError> {
+        let start = self.position;
+        self.advance();
+        if !self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+            return Err(TokenError::new(
+                "expected an amount after currency symbol",
+                start,
+            ));
+        }
+        match self.parse_number()? {
+            Token::Number(amount) => Ok(Token::Money { amount, currency }),
+            _ => Err(TokenError::new(
+                "expected an amount after currency symbol",
+                start,
+            )),
+        }
+    }
+
+    /// Consumes a currency code such as `EUR` following a number.
+    ///
+    /// The code may be separated from the number by spaces and must match a
+    /// known currency exactly, and not be the name of a variable. Returns
+    /// `None`, consuming nothing, otherwise.
+    fn parse_currency_suffix(&mut self, amount: f64) -> Option<Token> {
+        let rest = &self.input[self.position..];
+        let code_start = rest.len() - rest.trim_start_matches(' ').len();
+        let code_len = rest[code_start..]
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len() - code_start);
+        let code = &rest[code_start..code_start + code_len];
+        if self.options.is_variable_unit(code) {
+            return None;
+        }
+        let currency = currency_by_code(code)?;
+        self.position += code_start + code_len;
+        Some(Token::Money { amount, currency })
+    }
+
+    /// Consumes the symbol of a unit of length, mass or volume such as `km`
+    /// following a number, which labels a rate such as `42 km / 3h30m`.
+    ///
+    /// The symbol may be separated from the number by spaces and must match
+    /// exactly, not be the name of a variable, and be followed by a division
+    /// by a duration literal. Returns `None`, consuming nothing, otherwise,
+    /// so that `2 km + 3 kg` fails on the undefined `km` instead of silently
+    /// dropping the units.
+    fn parse_quantity_suffix(&mut self, value: f64) -> Option<Token> {
+        let rest = &self.input[self.position..];
+        let unit_start = rest.len() - rest.trim_start_matches(' ').len();
+        let unit_len = rest[unit_start..]
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len() - unit_start);
+        let symbol = &rest[unit_start..unit_start + unit_len];
+        if self.options.is_variable_unit(symbol) {
+            return None;
+        }
+        let unit = quantity_unit(symbol)?;
+        if !self.divides_by_duration(&rest[unit_start + unit_len..]) {
+            return None;
+        }
+        self.position += unit_start + unit_len;
+        Some(Token::Quantity {
+            value,
+            unit: unit.symbol,
+        })
+    }
+
+    /// Returns true if `rest` starts with a division by a duration literal,
+    /// such as the `/ 3h30m` of `42 km / 3h30m`.
+    fn divides_by_duration(&self, rest: &str) -> bool {
+        let Some(divisor) = rest.trim_start_matches(' ').strip_prefix('/') else {
+            return false;
+        };
+        let (tokens, _) = Tokenizer::with_options(divisor, self.options).tokenize_partial();
+        matches!(tokens.first(), Some((Token::Duration(_), _)))
+    }
+
+    /// Consumes an engineering prefix such as the `k` of `4.7k`, if they are
+    /// enabled, and returns the scaled value.
+    ///
+    /// The prefix must follow the number directly and must not start a
+    /// longer word, so `2km` and `2 k` are left alone.
+    fn parse_engineering_prefix(&mut self, value: f64) -> Option<f64> {
+        if !self.options.engineering_prefixes {
+            return None;
+        }
+        let mut rest = self.input[self.position..].chars();
+        let prefix = rest.next()?;
+        if rest.next().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            return None;
+        }
+        let &(_, exponent) = ENGINEERING_PREFIXES.iter().find(|(p, _)| *p == prefix)?;
+        self.position += prefix.len_utf8();
+        // Dividing by an exact power of ten rounds better than multiplying
+        // by an inexact negative one.
+        let scale = 10f64.powi(exponent.abs());
+        Some(if exponent < 0 {
+            value / scale
+        } else {
+            value * scale
+        })
+    }
+
+    /// Consumes the units of a duration such as `1h30m` following its first
+    /// number, and returns the whole duration in seconds.
+    ///
+    /// Each unit must follow its number directly, units must go from largest
+    /// to smallest, and the duration must not run into a longer word, so
+    /// `2 h`, `30m1h` and `2ms` are left alone. A single unit that is the
+    /// name of a variable, such as the `m` of `2m` after `m = 5`, is left
+    /// alone too. Returns `None`, consuming nothing, otherwise.
+    fn parse_duration_suffix(&mut self, value: f64) -> Option<f64> {
+        let rest = &self.input[self.position..];
+        let mut chars = rest.char_indices().peekable();
+        let mut seconds = 0.0;
+        let mut amount = value;
+        let mut largest = f64::INFINITY;
+        let end = loop {
+            let (_, unit) = chars.next()?;
+            let length = unit_seconds(unit).filter(|&length| length < largest)?;
+            let single = largest.is_infinite();
+            seconds += amount * length;
+            largest = length;
+            let number_start = match chars.peek() {
+                Some(&(i, c)) if c.is_ascii_digit() => i,
+                Some(&(_, c)) if c.is_alphanumeric() || c == '_' => return None,
+                _ if single && self.options.is_variable_unit(unit.encode_utf8(&mut [0; 4])) => {
+                    return None;
+                }
+                Some(&(i, _)) => break i,
+                None => break rest.len(),
+            };
+            while chars
+                .next_if(|(_, c)| c.is_ascii_digit() || *c == '.')
+                .is_some()
+            {}
+            let number_end = chars.peek().map_or(rest.len(), |&(i, _)| i);
+            amount = rest[number_start..number_end].parse().ok()?;
+        };
+        self.position += end;
+        Some(seconds)
+    }
+
+    /// Consumes a data-size unit such as `GiB` following a number.
+    ///
+    /// The unit may be separated from the number by spaces and must match a
+    /// byte unit exactly, and not be the name of a variable. Returns `None`,
+    /// consuming nothing, otherwise.
+    fn parse_size_suffix(&mut self, value: f64) -> Option<Token> {
+        let rest = &self.input[self.position..];
+        let unit_start = rest.len() - rest.trim_start_matches(' ').len();
+        let unit_len = rest[unit_start..]
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len() - unit_start);
+        let symbol = &rest[unit_start..unit_start + unit_len];
+        if self.options.is_variable_unit(symbol) {
+            return None;
+        }
+        let unit = size_unit(symbol)?;
+        self.position += unit_start + unit_len;
+        Some(Token::Size {
+            bytes: value * unit.factor(),
+            binary: unit.symbol.contains('i'),
+        })
+    }
+
+    /// Parses a literal in any base from 2 to 36, such as `0base36:z1`.
+    fn parse_any_base_literal(&mut self, start: usize) -> Result<Token, TokenError> {
+        self.position += "0base".len();
+        let base_start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.advance();
+        }
+        let base = match self.input[base_start..self.position].parse::<u32>() {
+            Ok(base) if (2..=MAX_LITERAL_BASE).contains(&base) => base,
+            Ok(_) => {
+                return Err(TokenError::new(
+                    format!("literal base must be from 2 to {MAX_LITERAL_BASE}"),
+                    base_start,
+                ));
+            }
+            Err(_) => return Err(TokenError::new("expected base after '0base'", base_start)),
+        };
+        if self.peek() != Some(':') {
+            return Err(TokenError::new(
+                format!("expected ':' after '0base{base}'"),
+                self.position,
+            ));
+        }
+        self.advance();
+        self.parse_base_digits(base, &format!("base-{base}"), start)
     }
 
     /// Parses digits in a given base after a prefix (e.g., after `0x`).
@@ -338,8 +741,8 @@ impl<'a> Tokenizer<'a> {
         if !self.peek().is_some_and(|c| c.is_digit(base)) {
             return Err(TokenError::new(
                 format!(
-                    "expected {base_name} digit after '0{}'",
-                    self.input[start + 1..start + 2].chars().next().unwrap()
+                    "expected {base_name} digit after '{}'",
+                    &self.input[start..digits_start]
                 ),
                 self.position,
             ));
@@ -393,12 +796,38 @@ mod tests {
     use super::*;
 
     fn tokenize(input: &str) -> Result<Vec<Token>, TokenError> {
-        let mut tokenizer = Tokenizer::new(input);
+        tokenize_with(input, &TokenOptions::default())
+    }
+
+    fn tokenize_with(input: &str, options: &TokenOptions) -> Result<Vec<Token>, TokenError> {
+        let mut tokenizer = Tokenizer::with_options(input, options);
         tokenizer
             .tokenize()
             .map(|v| v.into_iter().map(|(t, _)| t).collect())
     }
 
+    /// Options with engineering prefixes on.
+    fn prefixes() -> TokenOptions {
+        TokenOptions {
+            engineering_prefixes: true,
+            ..TokenOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_tokenize_partial_keeps_tokens_before_error() {
+        let (tokens, error) = Tokenizer::new("1 + $").tokenize_partial();
+
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::Number(1.0), Span::new(0, 1)),
+                (Token::Plus, Span::new(2, 3))
+            ]
+        );
+        assert_eq!(error.map(|e| e.position), Some(4));
+    }
+
     #[test]
     fn test_tokenize_integer() {
         let tokens = tokenize("42").unwrap();
@@ -531,6 +960,15 @@ mod tests {
         assert_eq!(tokens, vec![Token::Slash]);
     }
 
+    #[test]
+    fn test_tokenize_double_slash() {
+        let tokens = tokenize("7 // 2").unwrap();
+        assert_eq!(
+            tokens,
+            vec![Token::Number(7.0), Token::DoubleSlash, Token::Number(2.0)]
+        );
+    }
+
     #[test]
     fn test_tokenize_percent() {
         let tokens = tokenize("%").unwrap();
@@ -834,6 +1272,14 @@ mod tests {
         assert_eq!(err.position, 2);
     }
 
+    #[test]
+    fn test_error_span_covers_multibyte_character() {
+        let err = tokenize("5 ÷ 2").unwrap_err();
+        assert_eq!((err.position, err.end), (2, 4));
+        let err = tokenize("2 @ 3").unwrap_err();
+        assert_eq!((err.position, err.end), (2, 3));
+    }
+
     #[test]
     fn test_error_invalid_character_hash() {
         let result = tokenize("x # y");
@@ -962,6 +1408,189 @@ mod tests {
         assert_eq!(tokens, vec![Token::Number(8.0)]);
     }
 
+    #[test]
+    fn test_tokenize_any_base_literal() {
+        assert_eq!(tokenize("0base36:z1").unwrap(), vec![Token::Number(1261.0)]);
+        assert_eq!(tokenize("0base32:VV").unwrap(), vec![Token::Number(1023.0)]);
+        assert_eq!(tokenize("0base3:12").unwrap(), vec![Token::Number(5.0)]);
+    }
+
+    #[test]
+    fn test_tokenize_any_base_literal_in_expression() {
+        let tokens = tokenize("0base36:z + 1").unwrap();
+        assert_eq!(
+            tokens,
+            vec![Token::Number(35.0), Token::Plus, Token::Number(1.0)]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_any_base_literal_errors() {
+        let message = |input| tokenize(input).unwrap_err().message;
+        assert_eq!(message("0base"), "expected base after '0base'");
+        assert_eq!(message("0base37:1"), "literal base must be from 2 to 36");
+        assert_eq!(message("0base1:0"), "literal base must be from 2 to 36");
+        assert_eq!(message("0base16ff"), "expected ':' after '0base16'");
+        assert_eq!(
+            message("0base16:"),
+            "expected base-16 digit after '0base16:'"
+        );
+        assert_eq!(
+            message("0base8:87"),
+            "expected base-8 digit after '0base8:'"
+        );
+        assert_eq!(message("0base8:17z"), "invalid digit 'z' in base-8 literal");
+    }
+
+    #[test]
+    fn test_tokenize_engineering_prefixes() {
+        let options = prefixes();
+        assert_eq!(
+            tokenize_with("4.7k", &options).unwrap(),
+            vec![Token::Number(4700.0)]
+        );
+        assert_eq!(
+            tokenize_with("2.2u", &options).unwrap(),
+            vec![Token::Number(2.2e-6)]
+        );
+        assert_eq!(
+            tokenize_with("100n", &options).unwrap(),
+            vec![Token::Number(1e-7)]
+        );
+        assert_eq!(
+            tokenize_with("10µ", &options).unwrap(),
+            vec![Token::Number(1e-5)]
+        );
+        assert_eq!(
+            tokenize_with("4.7k*2.2M", &options).unwrap(),
+            vec![Token::Number(4700.0), Token::Star, Token::Number(2.2e6)]
+        );
+    }
+
+    #[test]
+    fn test_variables_shadow_duration_and_quantity_units() {
+        let mut options = TokenOptions::default();
+        for name in ["m", "km", "x"] {
+            options.add_variable_unit(name);
+        }
+        assert_eq!(options.variable_units, ["km", "m"]);
+        assert_eq!(
+            tokenize_with("2m", &options).unwrap(),
+            vec![Token::Number(2.0), Token::Identifier("m".to_string())]
+        );
+        assert_eq!(
+            tokenize_with("42 km", &options).unwrap(),
+            vec![Token::Number(42.0), Token::Identifier("km".to_string())]
+        );
+        assert_eq!(
+            tokenize_with("1h30m", &options).unwrap(),
+            vec![Token::Duration(5400.0)]
+        );
+        assert_eq!(
+            tokenize_with("2h", &options).unwrap(),
+            vec![Token::Duration(7200.0)]
+        );
+        options.remove_variable_unit("m");
+        assert_eq!(
+            tokenize_with("2m", &options).unwrap(),
+            vec![Token::Duration(120.0)]
+        );
+        assert_eq!(
+            tokenize("42 mi / 2h").unwrap(),
+            vec![
+                Token::Quantity {
+                    value: 42.0,
+                    unit: "mi"
+                },
+                Token::Slash,
+                Token::Duration(7200.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quantity_units_only_label_rates() {
+        assert_eq!(
+            tokenize("2 km + 3 kg").unwrap(),
+            vec![
+                Token::Number(2.0),
+                Token::Identifier("km".to_string()),
+                Token::Plus,
+                Token::Number(3.0),
+                Token::Identifier("kg".to_string())
+            ]
+        );
+        assert_eq!(
+            tokenize("5 g").unwrap(),
+            vec![Token::Number(5.0), Token::Identifier("g".to_string())]
+        );
+        assert_eq!(
+            tokenize("2km/x").unwrap(),
+            vec![
+                Token::Number(2.0),
+                Token::Identifier("km".to_string()),
+                Token::Slash,
+                Token::Identifier("x".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_engineering_prefixes_leave_words_alone() {
+        let options = prefixes();
+        assert_eq!(
+            tokenize_with("2km/1h", &options).unwrap(),
+            vec![
+                Token::Quantity {
+                    value: 2.0,
+                    unit: "km"
+                },
+                Token::Slash,
+                Token::Duration(3600.0)
+            ]
+        );
+        assert_eq!(
+            tokenize_with("2 k", &options).unwrap(),
+            vec![Token::Number(2.0), Token::Identifier("k".to_string())]
+        );
+        assert_eq!(
+            tokenize_with("2MB", &options).unwrap(),
+            vec![Token::Size {
+                bytes: 2e6,
+                binary: false
+            }]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_duration_literals() {
+        assert_eq!(tokenize("1h30m").unwrap(), vec![Token::Duration(5400.0)]);
+        assert_eq!(
+            tokenize("90s / 1.5m").unwrap(),
+            vec![Token::Duration(90.0), Token::Slash, Token::Duration(90.0)]
+        );
+        assert_eq!(
+            tokenize("2 h").unwrap(),
+            vec![Token::Number(2.0), Token::Identifier("h".to_string())]
+        );
+        assert_eq!(
+            tokenize("2ms").unwrap(),
+            vec![Token::Number(2.0), Token::Identifier("ms".to_string())]
+        );
+        assert_eq!(
+            tokenize("30m1h").unwrap(),
+            vec![Token::Number(30.0), Token::Identifier("m1h".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_engineering_prefixes_off_by_default() {
+        assert_eq!(
+            tokenize("4k").unwrap(),
+            vec![Token::Number(4.0), Token::Identifier("k".to_string())]
+        );
+    }
+
     #[test]
     fn test_tokenize_hex_no_digits_error() {
         let result = tokenize("0x");
@@ -1046,4 +1675,85 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_tokenize_text_literal() {
+        let tokens = tokenize("convert(5, \"mi\", \"km\")").unwrap();
+        assert_eq!(tokens[4], Token::Text("mi".to_string()));
+        assert_eq!(tokens[6], Token::Text("km".to_string()));
+    }
+
+    #[test]
+    fn test_tokenize_shell_command() {
+        let tokens = tokenize("`wc -l < notes.txt` * 2").unwrap();
+        assert_eq!(tokens[0], Token::Command("wc -l < notes.txt".to_string()));
+        assert_eq!(tokens[1], Token::Star);
+        assert!(tokenize("`ls").is_err());
+    }
+
+    #[test]
+    fn test_tokenize_size_literal() {
+        assert_eq!(
+            tokenize("1.5 GiB + 300MB").unwrap(),
+            vec![
+                Token::Size {
+                    bytes: 1_610_612_736.0,
+                    binary: true
+                },
+                Token::Plus,
+                Token::Size {
+                    bytes: 300_000_000.0,
+                    binary: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_size_literal_requires_exact_unit() {
+        assert_eq!(
+            tokenize("2 GiBs").unwrap(),
+            vec![Token::Number(2.0), Token::Identifier("GiBs".to_string())]
+        );
+        assert_eq!(
+            tokenize("2 gb").unwrap(),
+            vec![Token::Number(2.0), Token::Identifier("gb".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_money_literal() {
+        let usd = currency_by_code("USD").unwrap();
+        let chf = currency_by_code("CHF").unwrap();
+        assert_eq!(
+            tokenize("$12.50 * 3 + 4 CHF").unwrap(),
+            vec![
+                Token::Money {
+                    amount: 12.5,
+                    currency: usd
+                },
+                Token::Star,
+                Token::Number(3.0),
+                Token::Plus,
+                Token::Money {
+                    amount: 4.0,
+                    currency: chf
+                },
+            ]
+        );
+        assert_eq!(
+            tokenize("2 usd").unwrap(),
+            vec![Token::Number(2.0), Token::Identifier("usd".to_string())]
+        );
+        let err = tokenize("€ 5").unwrap_err();
+        assert_eq!(err.message, "expected an amount after currency symbol");
+        assert_eq!(err.position, 0);
+    }
+
+    #[test]
+    fn test_tokenize_unterminated_text_literal() {
+        let err = tokenize("convert(5, \"mi").unwrap_err();
+        assert_eq!(err.message, "unterminated text literal");
+        assert_eq!(err.position, 11);
+    }
 }
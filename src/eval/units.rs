@@ -0,0 +1,424 @@
+//! Unit conversion catalog.
+//!
+//! Provides the table behind `convert(value, "from", "to")`. Each unit maps
+//! linearly onto its dimension's base unit, with an offset for temperature
+//! scales. Unit names are matched leniently: exact symbols first, then
+//! case-insensitive names, plurals, and unambiguous prefixes.
+
+use crate::eval::error::{ErrorCode, EvalError};
+use crate::eval::functions::Argument;
+
+/// A physical (or digital) quantity that units measure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Length,
+    Mass,
+    Temperature,
+    Volume,
+    Data,
+    Speed,
+}
+
+impl Dimension {
+    /// All dimensions, in catalog order.
+    pub const ALL: [Self; 6] = [
+        Self::Length,
+        Self::Mass,
+        Self::Temperature,
+        Self::Volume,
+        Self::Data,
+        Self::Speed,
+    ];
+
+    /// Returns the lowercase name used in messages.
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Length => "length",
+            Self::Mass => "mass",
+            Self::Temperature => "temperature",
+            Self::Volume => "volume",
+            Self::Data => "data",
+            Self::Speed => "speed",
+        }
+    }
+}
+
+/// A unit in the conversion catalog.
+#[derive(Debug)]
+pub struct Unit {
+    /// The canonical symbol, e.g. `km`.
+    pub symbol: &'static str,
+    /// Alternative spellings, e.g. `kilometer`.
+    pub aliases: &'static [&'static str],
+    /// The dimension this unit measures.
+    pub dimension: Dimension,
+    /// Value of one unit in the base unit of its dimension.
+    factor: f64,
+    /// Added after scaling to reach the base unit (temperatures only).
+    offset: f64,
+}
+
+impl Unit {
+    /// Returns the value of one unit in the base unit of its dimension.
+    #[must_use]
+    pub const fn factor(&self) -> f64 {
+        self.factor
+    }
+
+    /// Returns `true` if `name` is this unit's symbol or one of its aliases.
+    fn is_named(&self, name: &str, ignore_case: bool) -> bool {
+        self.names().any(|n| {
+            if ignore_case {
+                n.eq_ignore_ascii_case(name)
+            } else {
+                n == name
+            }
+        })
+    }
+
+    fn names(&self) -> impl Iterator<Item = &'static str> {
+        std::iter::once(self.symbol).chain(self.aliases.iter().copied())
+    }
+}
+
+const fn unit(
+    symbol: &'static str,
+    aliases: &'static [&'static str],
+    dimension: Dimension,
+    factor: f64,
+) -> Unit {
+    Unit {
+        symbol,
+        aliases,
+        dimension,
+        factor,
+        offset: 0.0,
+    }
+}
+
+/// All units, grouped by dimension. Base units are metres, kilograms,
+/// kelvin, litres, bytes, and metres per second.
+pub static UNITS: &[Unit] = &[
+    unit("m", &["meter", "metre"], Dimension::Length, 1.0),
+    unit("km", &["kilometer", "kilometre"], Dimension::Length, 1000.0),
+    unit("cm", &["centimeter", "centimetre"], Dimension::Length, 0.01),
+    unit(
+        "mm",
+        &["millimeter", "millimetre"],
+        Dimension::Length,
+        0.001,
+    ),
+    unit("mi", &["mile"], Dimension::Length, 1609.344),
+    unit("yd", &["yard"], Dimension::Length, 0.9144),
+    unit("ft", &["foot", "feet"], Dimension::Length, 0.3048),
+    unit("in", &["inch", "inches"], Dimension::Length, 0.0254),
+    unit("nmi", &["nautical mile"], Dimension::Length, 1852.0),
+    unit("kg", &["kilogram"], Dimension::Mass, 1.0),
+    unit("g", &["gram"], Dimension::Mass, 0.001),
+    unit("mg", &["milligram"], Dimension::Mass, 1e-6),
+    unit("t", &["tonne", "ton"], Dimension::Mass, 1000.0),
+    unit("lb", &["pound", "lbs"], Dimension::Mass, 0.453_592_37),
+    unit("oz", &["ounce"], Dimension::Mass, 0.028_349_523_125),
+    unit("st", &["stone"], Dimension::Mass, 6.350_293_18),
+    unit("K", &["kelvin"], Dimension::Temperature, 1.0),
+    Unit {
+        symbol: "C",
+        aliases: &["celsius", "degC"],
+        dimension: Dimension::Temperature,
+        factor: 1.0,
+        offset: 273.15,
+    },
+    Unit {
+        symbol: "F",
+        aliases: &["fahrenheit", "degF"],
+        dimension: Dimension::Temperature,
+        factor: 5.0 / 9.0,
+        offset: 273.15 - 32.0 * 5.0 / 9.0,
+    },
+    unit("l", &["L", "liter", "litre"], Dimension::Volume, 1.0),
+    unit(
+        "ml",
+        &["mL", "milliliter", "millilitre"],
+        Dimension::Volume,
+        0.001,
+    ),
+    unit(
+        "m3",
+        &["cubic meter", "cubic metre"],
+        Dimension::Volume,
+        1000.0,
+    ),
+    unit("gal", &["gallon"], Dimension::Volume, 3.785_411_784),
+    unit("qt", &["quart"], Dimension::Volume, 0.946_352_946),
+    unit("pt", &["pint"], Dimension::Volume, 0.473_176_473),
+    unit("cup", &[], Dimension::Volume, 0.236_588_236_5),
+    unit(
+        "floz",
+        &["fluid ounce"],
+        Dimension::Volume,
+        0.029_573_529_562_5,
+    ),
+    unit(
+        "tbsp",
+        &["tablespoon"],
+        Dimension::Volume,
+        0.014_786_764_781_25,
+    ),
+    unit(
+        "tsp",
+        &["teaspoon"],
+        Dimension::Volume,
+        0.004_928_921_593_75,
+    ),
+    unit("b", &["bit"], Dimension::Data, 0.125),
+    unit("B", &["byte"], Dimension::Data, 1.0),
+    unit("kB", &["kilobyte"], Dimension::Data, 1e3),
+    unit("MB", &["megabyte"], Dimension::Data, 1e6),
+    unit("GB", &["gigabyte"], Dimension::Data, 1e9),
+    unit("TB", &["terabyte"], Dimension::Data, 1e12),
+    unit("KiB", &["kibibyte"], Dimension::Data, 1024.0),
+    unit("MiB", &["mebibyte"], Dimension::Data, 1_048_576.0),
+    unit("GiB", &["gibibyte"], Dimension::Data, 1_073_741_824.0),
+    unit("TiB", &["tebibyte"], Dimension::Data, 1_099_511_627_776.0),
+    unit("kbit", &["kilobit"], Dimension::Data, 125.0),
+    unit("Mbit", &["megabit"], Dimension::Data, 125_000.0),
+    unit("Gbit", &["gigabit"], Dimension::Data, 125_000_000.0),
+    unit("m/s", &["mps"], Dimension::Speed, 1.0),
+    unit("km/h", &["kph", "kmh"], Dimension::Speed, 1.0 / 3.6),
+    unit("mph", &[], Dimension::Speed, 0.447_04),
+    unit("kn", &["knot", "kt"], Dimension::Speed, 1852.0 / 3600.0),
+    unit("ft/s", &["fps"], Dimension::Speed, 0.3048),
+];
+
+/// Converts `value` from one unit to another.
+///
+/// # Errors
+/// Returns an `EvalError` if either unit is unknown or ambiguous, or if the
+/// units measure different dimensions.
+pub fn convert(value: f64, from: &str, to: &str) -> Result<f64, EvalError> {
+    let from_unit = find_unit(from)?;
+    let to_unit = find_unit(to)?;
+    if from_unit.dimension != to_unit.dimension {
+        return Err(EvalError::new(format!(
+            "cannot convert {} ({}) to {} ({})",
+            from_unit.symbol,
+            from_unit.dimension.name(),
+            to_unit.symbol,
+            to_unit.dimension.name()
+        ))
+        .with_code(ErrorCode::IncompatibleUnits));
+    }
+    let base = value.mul_add(from_unit.factor, from_unit.offset);
+    Ok((base - to_unit.offset) / to_unit.factor)
+}
+
+/// Implements the `convert(x, "from", "to")` built-in.
+pub(crate) fn convert_arguments(args: &[Argument]) -> Result<f64, EvalError> {
+    match args {
+        [
+            Argument::Number(value),
+            Argument::Text(from),
+            Argument::Text(to),
+        ] => convert(*value, from, to),
+        _ => Err(EvalError::new(
+            "convert expects a number and two unit names, e.g. convert(5, \"mi\", \"km\")",
+        )
+        .with_code(ErrorCode::InvalidArgument)),
+    }
+}
+
+/// Finds a unit by name, matching leniently.
+///
+/// Tries, in order: the exact symbol or alias, a case-insensitive match, the
+/// singular of a plural name, and a unique unit whose name starts with `name`.
+///
+/// # Errors
+/// Returns an `EvalError` naming the closest unit if nothing matches, or
+/// listing the candidates if the name is ambiguous.
+pub fn find_unit(name: &str) -> Result<&'static Unit, EvalError> {
+    let name = name.trim();
+    if let Some(unit) = UNITS.iter().find(|u| u.is_named(name, false)) {
+        return Ok(unit);
+    }
+
+    let singulars = [name.strip_suffix("es"), name.strip_suffix('s')];
+    let candidates = singulars.into_iter().flatten().filter(|s| s.len() >= 3);
+    for candidate in std::iter::once(name).chain(candidates) {
+        let matches: Vec<&Unit> = UNITS
+            .iter()
+            .filter(|u| u.is_named(candidate, true))
+            .collect();
+        match matches.as_slice() {
+            [] => {}
+            [unit] => return Ok(unit),
+            _ => return Err(ambiguous(name, &matches)),
+        }
+    }
+
+    if name.len() >= 3 {
+        let lower = name.to_ascii_lowercase();
+        let matches: Vec<&Unit> = UNITS
+            .iter()
+            .filter(|u| {
+                u.names()
+                    .any(|n| n.to_ascii_lowercase().starts_with(&lower))
+            })
+            .collect();
+        match matches.as_slice() {
+            [] => {}
+            [unit] => return Ok(unit),
+            _ => return Err(ambiguous(name, &matches)),
+        }
+    }
+
+    let suggestion = UNITS
+        .iter()
+        .flat_map(Unit::names)
+        .map(|n| {
+            (
+                edit_distance(&name.to_ascii_lowercase(), &n.to_ascii_lowercase()),
+                n,
+            )
+        })
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance);
+    Err(EvalError::new(match suggestion {
+        Some((_, n)) => format!("unknown unit '{name}', did you mean '{n}'?"),
+        None => format!("unknown unit '{name}'"),
+    })
+    .with_code(ErrorCode::UnknownUnit))
+}
+
+fn ambiguous(name: &str, matches: &[&Unit]) -> EvalError {
+    let symbols: Vec<&str> = matches.iter().map(|u| u.symbol).collect();
+    EvalError::new(format!("ambiguous unit '{name}': {}", symbols.join(", ")))
+        .with_code(ErrorCode::UnknownUnit)
+}
+
+/// Returns the Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Returns the unit of length, mass or volume whose symbol is exactly
+/// `symbol`, which labels a quantity written after a number.
+#[must_use]
+pub fn quantity_unit(symbol: &str) -> Option<&'static Unit> {
+    UNITS.iter().find(|u| {
+        u.symbol == symbol
+            && matches!(
+                u.dimension,
+                Dimension::Length | Dimension::Mass | Dimension::Volume
+            )
+    })
+}
+
+/// Returns the units of a dimension, in catalog order.
+pub fn units_of(dimension: Dimension) -> impl Iterator<Item = &'static Unit> {
+    UNITS.iter().filter(move |u| u.dimension == dimension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9 * expected.abs().max(1.0),
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_convert_length() {
+        assert_close(convert(1.0, "mi", "km").unwrap(), 1.609_344);
+        assert_close(convert(12.0, "in", "ft").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_convert_temperature_uses_offsets() {
+        assert_close(convert(100.0, "C", "F").unwrap(), 212.0);
+        assert_close(convert(32.0, "F", "C").unwrap(), 0.0);
+        assert_close(convert(0.0, "C", "K").unwrap(), 273.15);
+    }
+
+    #[test]
+    fn test_convert_mass_volume_data_speed() {
+        assert_close(convert(1.0, "kg", "lb").unwrap(), 2.204_622_621_848_776);
+        assert_close(convert(1.0, "gal", "l").unwrap(), 3.785_411_784);
+        assert_close(convert(1.0, "GiB", "MiB").unwrap(), 1024.0);
+        assert_close(convert(8.0, "b", "B").unwrap(), 1.0);
+        assert_close(convert(36.0, "km/h", "m/s").unwrap(), 10.0);
+    }
+
+    #[test]
+    fn test_convert_rejects_mismatched_dimensions() {
+        let err = convert(1.0, "km", "kg").unwrap_err();
+        assert_eq!(err.message(), "cannot convert km (length) to kg (mass)");
+    }
+
+    #[test]
+    fn test_find_unit_is_case_sensitive_first() {
+        assert_eq!(find_unit("b").unwrap().symbol, "b");
+        assert_eq!(find_unit("B").unwrap().symbol, "B");
+        assert_eq!(find_unit("mb").unwrap().symbol, "MB");
+    }
+
+    #[test]
+    fn test_find_unit_matches_names_and_plurals() {
+        assert_eq!(find_unit("Kilometers").unwrap().symbol, "km");
+        assert_eq!(find_unit("inches").unwrap().symbol, "in");
+        assert_eq!(find_unit("feet").unwrap().symbol, "ft");
+        assert_eq!(find_unit("celsius").unwrap().symbol, "C");
+    }
+
+    #[test]
+    fn test_find_unit_matches_unique_prefix() {
+        assert_eq!(find_unit("fahr").unwrap().symbol, "F");
+        assert!(
+            find_unit("kil")
+                .unwrap_err()
+                .message()
+                .starts_with("ambiguous unit 'kil'")
+        );
+    }
+
+    #[test]
+    fn test_find_unit_suggests_close_names() {
+        let err = find_unit("kmm").unwrap_err();
+        assert_eq!(err.message(), "unknown unit 'kmm', did you mean 'km'?");
+        assert_eq!(err.code(), ErrorCode::UnknownUnit);
+        assert_eq!(
+            find_unit("zzzzzz").unwrap_err().message(),
+            "unknown unit 'zzzzzz'"
+        );
+    }
+
+    #[test]
+    fn test_every_dimension_has_units() {
+        for dimension in Dimension::ALL {
+            assert!(units_of(dimension).next().is_some(), "{dimension:?}");
+        }
+    }
+
+    #[test]
+    fn test_convert_arguments_requires_text_units() {
+        let args = [
+            Argument::Number(5.0),
+            Argument::Number(1.0),
+            Argument::Text("km".to_string()),
+        ];
+        assert!(convert_arguments(&args).is_err());
+    }
+}
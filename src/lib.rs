@@ -3,7 +3,9 @@
 //! This crate provides the core functionality for the Crabculator application,
 //! including UI rendering and layout management.
 
+pub mod action;
 pub mod app;
+pub mod cli;
 pub mod editor;
 pub mod eval;
 pub mod storage;
@@ -1,122 +1,168 @@
 use std::io;
 use std::time::Instant;
 
-use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::event;
 
+use crabculator::action::Effect;
 use crabculator::app;
+use crabculator::cli::{Options, Persistence, USAGE};
+use crabculator::storage;
 use crabculator::terminal;
 use crabculator::ui;
 
-#[allow(clippy::too_many_lines)]
 fn main() -> io::Result<()> {
+    let options = Options::parse(std::env::args().skip(1)).unwrap_or_else(|message| {
+        eprintln!("{message}\n\n{USAGE}");
+        std::process::exit(2);
+    });
+    if options.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    let log_file = options.debug.then(|| {
+        storage::start_logging().unwrap_or_else(|e| {
+            eprintln!("Cannot write the debug log: {e}");
+            std::process::exit(2);
+        })
+    });
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), ?options, "started");
+    let passphrase = passphrase(&options).unwrap_or_else(|message| {
+        eprintln!("{message}");
+        std::process::exit(2);
+    });
+
     terminal::install_panic_hook();
 
     let mut terminal = terminal::setup_terminal()?;
-    let mut app = app::App::new();
+    let mut app = match open(&options, passphrase) {
+        Ok(app) => app,
+        Err(message) => {
+            terminal::restore_terminal()?;
+            eprintln!("{message}");
+            std::process::exit(2);
+        }
+    };
+    if let Some(path) = log_file {
+        app.debug = Some(app::DebugInfo::default());
+        app.notify(format!("Logging to {}", path.display()));
+    }
 
     while app.running {
+        app.check_disk_changes();
+        app.record_edits_for_recovery();
+        terminal::set_mouse_capture(app.settings.mouse)?;
+        let started = Instant::now();
         terminal.draw(|frame| ui::render(frame, &mut app))?;
+        if let Some(debug) = &mut app.debug {
+            debug.frame_time = started.elapsed();
+        }
+        app.start_shell_commands();
 
-        if event::poll(std::time::Duration::from_millis(250))?
-            && let Event::Key(key) = event::read()?
-        {
-            let mut should_save = false;
-
-            if app.help_visible {
-                match key.code {
-                    KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.toggle_help();
-                    }
-                    KeyCode::Esc => {
-                        app.close_help();
-                    }
-                    KeyCode::Up => {
-                        app.scroll_help_up();
-                    }
-                    KeyCode::Down => {
-                        app.scroll_help_down(ui::HELP_CONTENT_HEIGHT);
-                    }
-                    KeyCode::PageUp => {
-                        for _ in 0..10 {
-                            app.scroll_help_up();
-                        }
-                    }
-                    KeyCode::PageDown => {
-                        for _ in 0..10 {
-                            app.scroll_help_down(ui::HELP_CONTENT_HEIGHT);
-                        }
-                    }
-                    _ => {}
-                }
-            } else {
-                match key.code {
-                    KeyCode::Char('c' | 'q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.quit();
-                    }
-                    KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.toggle_help();
-                    }
-                    KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.clear_all();
-                        app.last_edit_time = Some(Instant::now());
-                        should_save = true;
-                    }
-                    KeyCode::Esc => {
-                        app.quit();
-                    }
-                    KeyCode::Char(c) => {
-                        app.buffer.insert_char(c);
-                        app.last_edit_time = Some(Instant::now());
-                        should_save = true;
-                    }
-                    KeyCode::Enter => {
-                        app.buffer.insert_newline();
-                        app.last_edit_time = Some(Instant::now());
-                        should_save = true;
-                    }
-                    KeyCode::Backspace => {
-                        app.buffer.delete_char_before();
-                        app.last_edit_time = Some(Instant::now());
-                        should_save = true;
-                    }
-                    KeyCode::Delete => {
-                        app.buffer.delete_char_at();
-                        app.last_edit_time = Some(Instant::now());
-                        should_save = true;
-                    }
-                    KeyCode::Left | KeyCode::Right
-                        if key.modifiers.contains(KeyModifiers::CONTROL) =>
-                    {
-                        app.toggle_memory_pane_position();
-                    }
-                    KeyCode::Left => {
-                        app.buffer.move_cursor_left();
-                    }
-                    KeyCode::Right => {
-                        app.buffer.move_cursor_right();
-                    }
-                    KeyCode::Up => {
-                        app.buffer.move_cursor_up();
-                    }
-                    KeyCode::Down => {
-                        app.buffer.move_cursor_down();
-                    }
-                    KeyCode::Home => {
-                        app.buffer.move_cursor_to_line_start();
-                    }
-                    KeyCode::End => {
-                        app.buffer.move_cursor_to_line_end();
-                    }
-                    _ => {}
+        if event::poll(std::time::Duration::from_millis(250))? {
+            for effect in app.handle_event(&event::read()?) {
+                tracing::debug!(?effect, "effect");
+                match effect {
+                    Effect::Save => app.save_state(),
+                    Effect::SaveSettings => app.save_settings(),
+                    Effect::Suspend => terminal::suspend(&mut terminal)?,
+                    Effect::CopyResults => terminal::copy_to_clipboard(&app.share_text())?,
+                    Effect::CopyResult(line) => {
+                        let value = app.result_value(line).unwrap_or_default();
+                        terminal::copy_to_clipboard(&value)?;
+                    }
+                    Effect::ReadClipboard => app.paste_column(terminal::read_clipboard()),
                 }
             }
-
-            if should_save {
-                app.save_state();
-            }
         }
     }
 
     terminal::restore_terminal()?;
+    app.save_position();
+    tracing::info!("quit");
+    // Left in the scrollback like the output of `bc`.
+    let worksheet = app.share_text();
+    if (options.print || app.settings.printexit) && !worksheet.is_empty() {
+        println!("{worksheet}");
+    }
     Ok(())
 }
+
+/// Asks for the passphrase of an encrypted buffer file, or for a new one
+/// with `--encrypt`.
+#[cfg(feature = "encryption")]
+fn passphrase(options: &Options) -> Result<Option<storage::Passphrase>, String> {
+    let Some(path) = options.persistence.path() else {
+        return Ok(None);
+    };
+    if storage::is_encrypted_file(&path) {
+        let passphrase = storage::Passphrase::new(
+            terminal::read_passphrase("Passphrase: ").map_err(|e| e.to_string())?,
+        );
+        storage::read_buffer(&path, Some(&passphrase))
+            .map_err(|e| format!("Cannot open {}: {e}", path.display()))?;
+        Ok(Some(passphrase))
+    } else if options.encrypt {
+        let passphrase =
+            terminal::read_passphrase("New passphrase: ").map_err(|e| e.to_string())?;
+        let repeated =
+            terminal::read_passphrase("Repeat passphrase: ").map_err(|e| e.to_string())?;
+        if passphrase.is_empty() {
+            Err("The passphrase must not be empty".to_string())
+        } else if passphrase == repeated {
+            Ok(Some(storage::Passphrase::new(passphrase)))
+        } else {
+            Err("The passphrases do not match".to_string())
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+/// Refuses encryption, and encrypted buffer files, in builds without it.
+#[cfg(not(feature = "encryption"))]
+fn passphrase(options: &Options) -> Result<Option<storage::Passphrase>, String> {
+    let encrypted = options
+        .persistence
+        .path()
+        .is_some_and(|path| storage::is_encrypted_file(&path));
+    if options.encrypt || options.decrypt || encrypted {
+        Err("Encryption needs a build with `--features encryption`".to_string())
+    } else {
+        Ok(None)
+    }
+}
+
+/// Opens the worksheet, applies the profile and evaluates the expressions
+/// given on the command line.
+fn open(options: &Options, passphrase: Option<storage::Passphrase>) -> Result<app::App, String> {
+    let mut app = app::App::open(options.persistence.clone(), passphrase)
+        .map_err(|e| format!("Cannot open worksheet: {e}"))?;
+    if let Some(project) = &app.untrusted_project {
+        app.notify(format!(
+            "Project {} is not trusted: shell commands and its prelude are off until :trust",
+            project.display()
+        ));
+    } else if options.persistence == Persistence::State
+        && let Some(project) = storage::paths::project_dir()
+        && storage::state_dir() == Some(project.clone())
+    {
+        app.notify(format!("Project worksheet in {}", project.display()));
+    }
+    if options.decrypt && app.passphrase.take().is_some() {
+        app.save_state();
+        app.notify("The buffer is saved unencrypted from now on");
+    }
+    if let Some(name) = &options.profile {
+        let message = app.apply_profile(name)?;
+        app.notify(message);
+    }
+    if let Some((row, error)) = app.extend_prelude(&options.expressions) {
+        return Err(format!(
+            "--expr '{}': [{}] {}",
+            options.expressions[row],
+            error.code(),
+            error.message()
+        ));
+    }
+    Ok(app)
+}
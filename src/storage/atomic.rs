@@ -0,0 +1,67 @@
+//! Atomic file writes.
+//!
+//! Files are written to a temporary file next to them, which then replaces
+//! the file in one rename, so an interrupted or failed write leaves the old
+//! contents in place instead of a truncated file.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Writes `contents` to the file at `path`, replacing it atomically and
+/// creating its directory if needed.
+///
+/// A symbolic link is followed, so the file it points to is replaced, and
+/// the permissions of the replaced file are kept.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created, or the temporary
+/// file cannot be written or renamed. The temporary file is removed on
+/// failure.
+pub fn write_atomically(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp = temp_path(&path);
+    let written = fs::write(&temp, contents)
+        .and_then(|()| {
+            fs::metadata(&path).map_or(Ok(()), |metadata| {
+                fs::set_permissions(&temp, metadata.permissions())
+            })
+        })
+        .and_then(|()| fs::rename(&temp, &path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
+}
+
+/// Returns the temporary file `path` is written to, in the same directory
+/// and named after this process so that instances do not collide.
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{}.tmp", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomically_replaces_the_file() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("nested").join("state.txt");
+
+        write_atomically(&path, "a = 1").unwrap();
+        write_atomically(&path, "a = 2").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a = 2");
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+        let blocked = dir.path().join("blocked");
+        fs::create_dir_all(blocked.join("state.txt")).unwrap();
+        assert!(write_atomically(&blocked.join("state.txt"), "a = 3").is_err());
+        assert_eq!(fs::read_dir(&blocked).unwrap().count(), 1);
+    }
+}
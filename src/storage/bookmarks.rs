@@ -0,0 +1,45 @@
+//! Bookmark persistence for Crabculator.
+//!
+//! Bookmarks are stored next to the buffer as `name = line` lines, in the
+//! same format as the settings file.
+
+use std::io::{self, ErrorKind};
+
+use super::paths;
+use super::settings::{load_settings_from_path, save_settings_to_path};
+
+/// Loads the bookmarks from the bookmarks file as `name = line` pairs.
+///
+/// # Returns
+///
+/// The pairs in file order, or none if the file doesn't exist.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read (e.g., permission denied).
+pub fn load_bookmarks() -> io::Result<Vec<(String, String)>> {
+    let Some(bookmarks_file) = paths::bookmarks_file() else {
+        return Ok(Vec::new());
+    };
+
+    load_settings_from_path(&bookmarks_file)
+}
+
+/// Saves the given `name = line` pairs to the bookmarks file.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The bookmarks file path cannot be determined
+/// - The directory cannot be created
+/// - The file cannot be written
+pub fn save_bookmarks(entries: &[(String, String)]) -> io::Result<()> {
+    let bookmarks_file = paths::bookmarks_file().ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::NotFound,
+            "Could not determine bookmarks file path",
+        )
+    })?;
+
+    save_settings_to_path(entries, &bookmarks_file)
+}
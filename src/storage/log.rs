@@ -0,0 +1,39 @@
+//! The debug log written with `--debug`.
+//!
+//! Events are recorded with `tracing` throughout the application; without
+//! `--debug` no subscriber is installed and they cost next to nothing.
+
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use tracing::Level;
+
+use super::paths;
+
+/// Appends all events from now on to `debug.log` in the state directory.
+///
+/// # Returns
+///
+/// The path of the log file.
+///
+/// # Errors
+///
+/// Returns an error if the state directory cannot be determined or the log
+/// file cannot be opened.
+pub fn start_logging() -> io::Result<PathBuf> {
+    let path =
+        paths::log_file().ok_or_else(|| io::Error::other("Could not determine log file path"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    tracing_subscriber::fmt()
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_max_level(Level::DEBUG)
+        .try_init()
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(path)
+}
@@ -2,8 +2,37 @@
 //!
 //! Provides functionality for saving and loading application state.
 
+pub mod atomic;
+pub mod bookmarks;
+pub mod log;
 pub mod paths;
+pub mod prelude;
+pub mod profiles;
+pub mod recovery;
+pub mod session;
+pub mod settings;
+pub mod share;
 pub mod state;
+pub mod templates;
+pub mod trust;
+pub mod variables;
 
-pub use paths::{state_dir, state_file};
-pub use state::{PersistedState, load, load_from_path, save, save_to_path};
+pub use bookmarks::{load_bookmarks, save_bookmarks};
+pub use log::start_logging;
+pub use paths::{
+    bookmarks_file, log_file, prelude_file, profiles_dir, recovery_file, session_file,
+    settings_file, state_dir, state_file, templates_dir,
+};
+pub use prelude::load_prelude;
+pub use profiles::{load_profile, profile_names};
+pub use session::{load_position, save_position};
+pub use settings::{load_settings, load_settings_from_path, save_settings, save_settings_to_path};
+pub use share::{read_share_file, write_share_file};
+pub use state::{
+    ENCRYPTED_MAGIC, Passphrase, PersistedState, Position, RESULTS_HEADER, RecoveredBuffer,
+    Revision, is_encrypted_file, load, load_from_path, modified_time, read_buffer, recover_buffer,
+    save, save_to_path, write_buffer,
+};
+pub use templates::{load_template, template_names};
+pub use trust::{trust_project, untrusted_project};
+pub use variables::read_variables_file;
@@ -1,38 +1,316 @@
 //! Path utilities for state storage.
 //!
 //! Provides functions to determine where state files should be stored.
-//! State is stored in `~/.crabculator/` directory across all platforms.
+//! The state directory is chosen in this order:
+//!
+//! 1. The `CRABCULATOR_STATE_DIR` environment variable.
+//! 2. The `.crabculator/` folder of a project: the working directory or, in
+//!    a git repository, the nearest of its parents up to the repository
+//!    root that has one. Folders at or above the home directory are never
+//!    projects.
+//! 3. The `state_dir` key of `crabculator/config.txt` in the platform's
+//!    configuration directory.
+//! 4. `~/.crabculator/`, if it exists from an earlier version.
+//! 5. `crabculator/` in the platform's data directory: `$XDG_DATA_HOME` or
+//!    `~/.local/share` on Linux, `~/Library/Application Support` on macOS,
+//!    and `%APPDATA%` on Windows.
 
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 
-/// Returns the directory where state files are stored.
+use super::settings::load_settings_from_path;
+
+/// Environment variable overriding the state directory.
+pub const STATE_DIR_VAR: &str = "CRABCULATOR_STATE_DIR";
+
+/// Key of the configuration file naming the state directory.
+pub const STATE_DIR_KEY: &str = "state_dir";
+
+/// Name of the folder that keeps the worksheet and settings of a project.
+pub const PROJECT_DIR_NAME: &str = ".crabculator";
+
+/// Kinds of per-user directories that differ by platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirKind {
+    Config,
+    Data,
+}
+
+/// Returns the `crabculator` directory of `kind` on the platform `os`, as
+/// named by `std::env::consts::OS`.
+///
+/// Environment variables are looked up with `var`, so that every platform
+/// can be tested on any other.
+fn platform_dir(
+    os: &str,
+    home: &Path,
+    kind: DirKind,
+    var: impl Fn(&str) -> Option<OsString>,
+) -> PathBuf {
+    let absolute = |name: &str| {
+        var(name)
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+    };
+    let base = match os {
+        "windows" => absolute("APPDATA").unwrap_or_else(|| home.join("AppData").join("Roaming")),
+        "macos" => home.join("Library").join("Application Support"),
+        _ => match kind {
+            DirKind::Config => absolute("XDG_CONFIG_HOME").unwrap_or_else(|| home.join(".config")),
+            DirKind::Data => {
+                absolute("XDG_DATA_HOME").unwrap_or_else(|| home.join(".local").join("share"))
+            }
+        },
+    };
+    base.join("crabculator")
+}
+
+/// Returns the path to the configuration file, `config.txt` in the
+/// platform's configuration directory.
 ///
-/// Returns `~/.crabculator/` on all platforms:
-/// - Unix (Linux/macOS): Uses `$HOME/.crabculator/`
-/// - Windows: Uses `%USERPROFILE%\.crabculator\`
+/// Unlike the settings, which are kept in the state directory, it is read
+/// before the state directory is known.
 ///
 /// # Returns
 ///
-/// `Some(PathBuf)` containing the state directory path, or `None` if the
+/// `Some(PathBuf)` containing the configuration file path, or `None` if the
 /// home directory cannot be determined.
 #[must_use]
+pub fn config_file() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| {
+        platform_dir(std::env::consts::OS, &home, DirKind::Config, |name| {
+            std::env::var_os(name)
+        })
+        .join("config.txt")
+    })
+}
+
+/// Returns the path to the list of trusted projects, `trusted.txt` in the
+/// platform's configuration directory.
+///
+/// It is kept outside of every project so that a project cannot trust
+/// itself.
+///
+/// # Returns
+///
+/// `Some(PathBuf)` containing the trusted projects file path, or `None` if
+/// the home directory cannot be determined.
+#[must_use]
+pub fn trusted_file() -> Option<PathBuf> {
+    config_file().map(|path| path.with_file_name("trusted.txt"))
+}
+
+/// Returns the state directory named by the `state_dir` key of the
+/// configuration file at `path`, if any.
+fn configured_state_dir(path: &Path) -> Option<PathBuf> {
+    load_settings_from_path(path)
+        .ok()?
+        .into_iter()
+        .find(|(key, value)| key == STATE_DIR_KEY && !value.is_empty())
+        .map(|(_, value)| expand_home(&value))
+}
+
+/// Replaces a leading `~/` of `path` with the home directory.
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Returns the project folder for the working directory `start`: the
+/// `.crabculator/` folder of `start` or, if `start` is inside a git
+/// repository, of the nearest of its parents up to the repository root that
+/// has one.
+///
+/// The search never reaches `home`, whose `.crabculator/` is the legacy
+/// state directory rather than a project, nor anything above it, so that a
+/// folder planted in a shared parent such as `/tmp` is not picked up.
+fn find_project_dir(start: &Path, home: Option<&Path>) -> Option<PathBuf> {
+    let below_home: Vec<&Path> = start
+        .ancestors()
+        .take_while(|dir| home.is_none_or(|home| !home.starts_with(dir)))
+        .collect();
+    let searched = below_home
+        .iter()
+        .position(|dir| dir.join(".git").exists())
+        .map_or(1, |root| root + 1);
+    below_home
+        .into_iter()
+        .take(searched)
+        .map(|dir| dir.join(PROJECT_DIR_NAME))
+        .find(|dir| dir.is_dir())
+}
+
+/// Returns the project folder for the current working directory, if it is
+/// inside a project.
+#[must_use]
+pub fn project_dir() -> Option<PathBuf> {
+    let start = std::env::current_dir().ok()?;
+    find_project_dir(&start, dirs::home_dir().as_deref())
+}
+
+/// Chooses the state directory from the candidates in order of precedence.
+///
+/// The legacy directory is only used if it exists.
+fn resolve_state_dir(
+    overridden: Option<PathBuf>,
+    project: Option<PathBuf>,
+    configured: Option<PathBuf>,
+    legacy: Option<PathBuf>,
+    platform: Option<PathBuf>,
+) -> Option<PathBuf> {
+    overridden
+        .or(project)
+        .or(configured)
+        .or_else(|| legacy.filter(|dir| dir.is_dir()))
+        .or(platform)
+}
+
+/// Returns the directory where state files are stored.
+///
+/// See the module documentation for how it is chosen.
+///
+/// # Returns
+///
+/// `Some(PathBuf)` containing the state directory path, or `None` if it is
+/// not overridden and the home directory cannot be determined.
+#[must_use]
 pub fn state_dir() -> Option<PathBuf> {
-    dirs::home_dir().map(|home| home.join(".crabculator"))
+    let home = dirs::home_dir();
+    resolve_state_dir(
+        std::env::var_os(STATE_DIR_VAR)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from),
+        project_dir(),
+        config_file().and_then(|path| configured_state_dir(&path)),
+        home.as_ref().map(|home| home.join(".crabculator")),
+        home.map(|home| {
+            platform_dir(std::env::consts::OS, &home, DirKind::Data, |name| {
+                std::env::var_os(name)
+            })
+        }),
+    )
 }
 
 /// Returns the path to the state file.
 ///
-/// Returns `~/.crabculator/state.txt` on all platforms.
+/// Returns `state.txt` in the state directory.
 ///
 /// # Returns
 ///
 /// `Some(PathBuf)` containing the state file path, or `None` if the
-/// home directory cannot be determined.
+/// state directory cannot be determined.
 #[must_use]
 pub fn state_file() -> Option<PathBuf> {
     state_dir().map(|dir| dir.join("state.txt"))
 }
 
+/// Returns the path to the crash recovery file.
+///
+/// Returns `recovery.txt` in the state directory.
+///
+/// # Returns
+///
+/// `Some(PathBuf)` containing the recovery file path, or `None` if the
+/// state directory cannot be determined.
+#[must_use]
+pub fn recovery_file() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("recovery.txt"))
+}
+
+/// Returns the path to the settings file.
+///
+/// Returns `settings.txt` in the state directory.
+///
+/// # Returns
+///
+/// `Some(PathBuf)` containing the settings file path, or `None` if the
+/// state directory cannot be determined.
+#[must_use]
+pub fn settings_file() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("settings.txt"))
+}
+
+/// Returns the path to the bookmarks file.
+///
+/// Returns `bookmarks.txt` in the state directory.
+///
+/// # Returns
+///
+/// `Some(PathBuf)` containing the bookmarks file path, or `None` if the
+/// state directory cannot be determined.
+#[must_use]
+pub fn bookmarks_file() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("bookmarks.txt"))
+}
+
+/// Returns the path to the session file, where the cursor and scroll
+/// position are kept between sessions.
+///
+/// Returns `session.txt` in the state directory.
+///
+/// # Returns
+///
+/// `Some(PathBuf)` containing the session file path, or `None` if the
+/// state directory cannot be determined.
+#[must_use]
+pub fn session_file() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("session.txt"))
+}
+
+/// Returns the directory holding the profile files.
+///
+/// Returns `profiles/` in the state directory.
+///
+/// # Returns
+///
+/// `Some(PathBuf)` containing the profiles directory path, or `None` if the
+/// state directory cannot be determined.
+#[must_use]
+pub fn profiles_dir() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("profiles"))
+}
+
+/// Returns the directory holding the template worksheets.
+///
+/// Returns `templates/` in the state directory.
+///
+/// # Returns
+///
+/// `Some(PathBuf)` containing the templates directory path, or `None` if the
+/// state directory cannot be determined.
+#[must_use]
+pub fn templates_dir() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("templates"))
+}
+
+/// Returns the path to the prelude worksheet.
+///
+/// Returns `prelude.txt` in the state directory.
+///
+/// # Returns
+///
+/// `Some(PathBuf)` containing the prelude file path, or `None` if the
+/// state directory cannot be determined.
+#[must_use]
+pub fn prelude_file() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("prelude.txt"))
+}
+
+/// Returns the path to the debug log written with `--debug`.
+///
+/// Returns `debug.log` in the state directory.
+///
+/// # Returns
+///
+/// `Some(PathBuf)` containing the log file path, or `None` if the
+/// state directory cannot be determined.
+#[must_use]
+pub fn log_file() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("debug.log"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -55,18 +333,6 @@ mod tests {
         );
     }
 
-    #[test]
-    fn state_dir_parent_is_home() {
-        let dir = state_dir().expect("state_dir should return Some");
-        let home = dirs::home_dir().expect("home_dir should be available");
-
-        let parent = dir.parent().expect("state_dir should have a parent");
-        assert_eq!(
-            parent, home,
-            "state_dir parent {parent:?} should equal home {home:?}",
-        );
-    }
-
     #[test]
     fn state_file_returns_some() {
         let file = state_file();
@@ -97,24 +363,257 @@ mod tests {
         );
     }
 
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<OsString> {
+        let vars: Vec<(String, OsString)> = vars
+            .iter()
+            .map(|(name, value)| ((*name).to_string(), OsString::from(value)))
+            .collect();
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| var == name)
+                .map(|(_, value)| value.clone())
+        }
+    }
+
     #[test]
-    fn state_dir_matches_expected_format() {
-        let dir = state_dir().expect("state_dir should return Some");
-        let home = dirs::home_dir().expect("home_dir should be available");
+    fn platform_dir_follows_xdg_on_linux() {
+        let home = Path::new("/home/user");
+        assert_eq!(
+            platform_dir("linux", home, DirKind::Data, env(&[])),
+            Path::new("/home/user/.local/share/crabculator")
+        );
+        assert_eq!(
+            platform_dir("linux", home, DirKind::Config, env(&[])),
+            Path::new("/home/user/.config/crabculator")
+        );
+        let vars = env(&[("XDG_DATA_HOME", "/data"), ("XDG_CONFIG_HOME", "/conf")]);
+        assert_eq!(
+            platform_dir("linux", home, DirKind::Data, &vars),
+            Path::new("/data/crabculator")
+        );
+        assert_eq!(
+            platform_dir("linux", home, DirKind::Config, &vars),
+            Path::new("/conf/crabculator")
+        );
+        // The XDG specification requires absolute paths.
+        assert_eq!(
+            platform_dir(
+                "freebsd",
+                home,
+                DirKind::Data,
+                env(&[("XDG_DATA_HOME", "data")])
+            ),
+            Path::new("/home/user/.local/share/crabculator")
+        );
+    }
 
-        let expected = home.join(".crabculator");
-        assert_eq!(dir, expected, "state_dir {dir:?} should equal {expected:?}",);
+    #[test]
+    fn platform_dir_uses_application_support_on_macos() {
+        let home = Path::new("/Users/user");
+        let expected = Path::new("/Users/user/Library/Application Support/crabculator");
+        assert_eq!(
+            platform_dir("macos", home, DirKind::Data, env(&[])),
+            expected
+        );
+        assert_eq!(
+            platform_dir(
+                "macos",
+                home,
+                DirKind::Config,
+                env(&[("XDG_CONFIG_HOME", "/c")])
+            ),
+            expected
+        );
     }
 
     #[test]
-    fn state_file_matches_expected_format() {
-        let file = state_file().expect("state_file should return Some");
+    fn platform_dir_uses_appdata_on_windows() {
+        let home = Path::new("/Users/user");
+        assert_eq!(
+            platform_dir(
+                "windows",
+                home,
+                DirKind::Data,
+                env(&[("APPDATA", "/AppData")])
+            ),
+            Path::new("/AppData/crabculator")
+        );
+        assert_eq!(
+            platform_dir("windows", home, DirKind::Config, env(&[])),
+            Path::new("/Users/user/AppData/Roaming/crabculator")
+        );
+    }
+
+    #[test]
+    fn resolve_state_dir_in_order_of_precedence() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let legacy = dir.path().to_path_buf();
+        let missing = dir.path().join("missing");
+        let path = |name: &str| Some(PathBuf::from(name));
+
+        assert_eq!(
+            resolve_state_dir(
+                path("/env"),
+                path("/project"),
+                path("/config"),
+                Some(legacy.clone()),
+                path("/data")
+            ),
+            path("/env")
+        );
+        assert_eq!(
+            resolve_state_dir(
+                None,
+                path("/project"),
+                path("/config"),
+                Some(legacy.clone()),
+                path("/data")
+            ),
+            path("/project")
+        );
+        assert_eq!(
+            resolve_state_dir(
+                None,
+                None,
+                path("/config"),
+                Some(legacy.clone()),
+                path("/data")
+            ),
+            path("/config")
+        );
+        assert_eq!(
+            resolve_state_dir(None, None, None, Some(legacy.clone()), path("/data")),
+            Some(legacy)
+        );
+        assert_eq!(
+            resolve_state_dir(None, None, None, Some(missing), path("/data")),
+            path("/data")
+        );
+    }
+
+    #[test]
+    fn find_project_dir_searches_parents_up_to_the_repository_root() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let home = dir.path().join("home");
+        let project = home.join("budget");
+        let nested = project.join("2026").join("q1");
+        std::fs::create_dir_all(&nested).expect("create dirs");
+        std::fs::create_dir(project.join(PROJECT_DIR_NAME)).expect("create project");
+        assert_eq!(find_project_dir(&nested, Some(&home)), None);
+        assert_eq!(
+            find_project_dir(&project, Some(&home)),
+            Some(project.join(PROJECT_DIR_NAME))
+        );
+
+        std::fs::create_dir(project.join(".git")).expect("create repository");
+        assert_eq!(
+            find_project_dir(&nested, Some(&home)),
+            Some(project.join(PROJECT_DIR_NAME))
+        );
+    }
+
+    #[test]
+    fn find_project_dir_stops_below_home() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let home = dir.path().join("home");
+        let nested = home.join("notes");
+        std::fs::create_dir_all(&nested).expect("create dirs");
+        std::fs::create_dir(nested.join(".git")).expect("create repository");
+        std::fs::create_dir(home.join(PROJECT_DIR_NAME)).expect("create legacy dir");
+        std::fs::create_dir(dir.path().join(PROJECT_DIR_NAME)).expect("create planted dir");
+        assert_eq!(find_project_dir(&nested, Some(&home)), None);
+        assert_eq!(find_project_dir(&home, Some(&home)), None);
+        assert_eq!(find_project_dir(dir.path(), Some(&home)), None);
+    }
+
+    #[test]
+    fn configured_state_dir_reads_key() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("config.txt");
+        assert_eq!(configured_state_dir(&path), None);
+
+        std::fs::write(&path, "state_dir = /srv/crabculator\n").expect("write file");
+        assert_eq!(
+            configured_state_dir(&path),
+            Some(PathBuf::from("/srv/crabculator"))
+        );
+
         let home = dirs::home_dir().expect("home_dir should be available");
+        std::fs::write(&path, "state_dir = ~/calc\n").expect("write file");
+        assert_eq!(configured_state_dir(&path), Some(home.join("calc")));
+    }
+
+    #[test]
+    fn state_file_is_in_state_dir() {
+        let dir = state_dir().expect("state_dir should return Some");
+        assert_eq!(state_file(), Some(dir.join("state.txt")));
+    }
+
+    #[test]
+    fn recovery_file_parent_is_state_dir() {
+        let dir = state_dir().expect("state_dir should return Some");
+        let file = recovery_file().expect("recovery_file should return Some");
+
+        assert_eq!(file.parent(), Some(dir.as_path()));
+        assert_eq!(
+            file.file_name().and_then(|n| n.to_str()),
+            Some("recovery.txt")
+        );
+    }
+
+    #[test]
+    fn settings_file_parent_is_state_dir() {
+        let dir = state_dir().expect("state_dir should return Some");
+        let file = settings_file().expect("settings_file should return Some");
+
+        assert_eq!(file.parent(), Some(dir.as_path()));
+        assert_eq!(
+            file.file_name().and_then(|n| n.to_str()),
+            Some("settings.txt")
+        );
+    }
+
+    #[test]
+    fn session_file_parent_is_state_dir() {
+        let dir = state_dir().expect("state_dir should return Some");
+        let file = session_file().expect("session_file should return Some");
+
+        assert_eq!(file.parent(), Some(dir.as_path()));
+        assert_eq!(
+            file.file_name().and_then(|n| n.to_str()),
+            Some("session.txt")
+        );
+    }
+
+    #[test]
+    fn bookmarks_file_parent_is_state_dir() {
+        let dir = state_dir().expect("state_dir should return Some");
+        let file = bookmarks_file().expect("bookmarks_file should return Some");
+
+        assert_eq!(file.parent(), Some(dir.as_path()));
+        assert_eq!(
+            file.file_name().and_then(|n| n.to_str()),
+            Some("bookmarks.txt")
+        );
+    }
+
+    #[test]
+    fn profiles_dir_parent_is_state_dir() {
+        let dir = state_dir().expect("state_dir should return Some");
+        let profiles = profiles_dir().expect("profiles_dir should return Some");
+
+        assert_eq!(profiles.parent(), Some(dir.as_path()));
+    }
+
+    #[test]
+    fn prelude_file_parent_is_state_dir() {
+        let dir = state_dir().expect("state_dir should return Some");
+        let file = prelude_file().expect("prelude_file should return Some");
 
-        let expected = home.join(".crabculator").join("state.txt");
+        assert_eq!(file.parent(), Some(dir.as_path()));
         assert_eq!(
-            file, expected,
-            "state_file {file:?} should equal {expected:?}",
+            file.file_name().and_then(|n| n.to_str()),
+            Some("prelude.txt")
         );
     }
 }
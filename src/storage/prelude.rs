@@ -0,0 +1,61 @@
+//! The prelude worksheet for Crabculator.
+//!
+//! `~/.crabculator/prelude.txt` holds worksheet lines, such as common rates
+//! and conversion factors, that are evaluated before the buffer of every
+//! session, like a shell rc file.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::paths;
+
+/// Loads the lines of the prelude worksheet.
+///
+/// # Returns
+///
+/// The lines in file order, or `None` if there is no prelude file.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read (e.g., permission denied).
+pub fn load_prelude() -> io::Result<Option<Vec<String>>> {
+    let Some(path) = paths::prelude_file() else {
+        return Ok(None);
+    };
+
+    load_prelude_from_path(&path)
+}
+
+/// Loads the lines of the prelude worksheet at `path`.
+///
+/// This is primarily used for testing with temporary directories.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read (e.g., permission denied).
+pub fn load_prelude_from_path(path: &Path) -> io::Result<Option<Vec<String>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(path)?;
+    Ok(Some(contents.lines().map(str::to_string).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_prelude_reads_lines() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("prelude.txt");
+        assert_eq!(load_prelude_from_path(&path).unwrap(), None);
+
+        fs::write(&path, "# rates\nvat = 0.19\n").expect("write file");
+        assert_eq!(
+            load_prelude_from_path(&path).unwrap(),
+            Some(vec!["# rates".to_string(), "vat = 0.19".to_string()])
+        );
+    }
+}
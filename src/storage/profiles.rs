@@ -0,0 +1,100 @@
+//! Profile files for Crabculator.
+//!
+//! Each profile is a `name.txt` file in `~/.crabculator/profiles/` holding
+//! `key = value` lines in the same format as the settings file.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::paths;
+use super::settings::load_settings_from_path;
+
+/// Loads the profile `name` as `key = value` pairs.
+///
+/// # Returns
+///
+/// The pairs in file order, or `None` if there is no such profile file.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read (e.g., permission denied).
+pub fn load_profile(name: &str) -> io::Result<Option<Vec<(String, String)>>> {
+    let Some(dir) = paths::profiles_dir() else {
+        return Ok(None);
+    };
+
+    load_profile_from_dir(&dir, name)
+}
+
+/// Loads the profile `name` from the profile files in `dir`.
+///
+/// This is primarily used for testing with temporary directories.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read (e.g., permission denied).
+pub fn load_profile_from_dir(dir: &Path, name: &str) -> io::Result<Option<Vec<(String, String)>>> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        return Ok(None);
+    }
+    let path = dir.join(format!("{name}.txt"));
+    if !path.exists() {
+        return Ok(None);
+    }
+    load_settings_from_path(&path).map(Some)
+}
+
+/// Returns the names of the profile files, sorted.
+#[must_use]
+pub fn profile_names() -> Vec<String> {
+    paths::profiles_dir().map_or_else(Vec::new, |dir| profile_names_in(&dir))
+}
+
+/// Returns the names of the profile files in `dir`, sorted.
+#[must_use]
+pub fn profile_names_in(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            name.strip_suffix(".txt").map(str::to_string)
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_profile_reads_entries_and_names() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        fs::write(
+            dir.path().join("shop.txt"),
+            "# Shop\nformat = usd\nvat = 0.19\n",
+        )
+        .expect("write profile");
+        fs::write(dir.path().join("notes.md"), "not a profile").expect("write file");
+
+        let entries = load_profile_from_dir(dir.path(), "shop").expect("read profile");
+        assert_eq!(
+            entries,
+            Some(vec![
+                ("format".to_string(), "usd".to_string()),
+                ("vat".to_string(), "0.19".to_string()),
+            ])
+        );
+        assert_eq!(load_profile_from_dir(dir.path(), "none").unwrap(), None);
+        assert_eq!(load_profile_from_dir(dir.path(), "../shop").unwrap(), None);
+        assert_eq!(profile_names_in(dir.path()), vec!["shop".to_string()]);
+    }
+}
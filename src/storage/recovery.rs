@@ -0,0 +1,254 @@
+//! Crash recovery for Crabculator.
+//!
+//! When the application panics, the panic hook writes a crash report to the
+//! state directory containing the panic message, a backtrace, and the buffer
+//! contents at the time of the crash. On the next launch the buffer can be
+//! restored from that report.
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::paths;
+use super::state::PersistedState;
+
+/// Marker line separating the report header from the recovered buffer lines.
+const BUFFER_MARKER: &str = "# --- buffer ---";
+
+/// Snapshot of the most recent buffer contents, read by the panic hook.
+static BUFFER_SNAPSHOT: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Records the current buffer lines so they can be included in a crash report.
+///
+/// Called when the buffer is opened or saved, and at most once a second
+/// while it is edited; the panic hook has no access to the application
+/// state and relies on this snapshot instead.
+pub fn record_buffer(lines: &[String]) {
+    if let Ok(mut snapshot) = BUFFER_SNAPSHOT.lock() {
+        snapshot.clear();
+        snapshot.extend_from_slice(lines);
+    }
+}
+
+/// Returns a copy of the last recorded buffer lines.
+#[must_use]
+pub fn recorded_buffer() -> Vec<String> {
+    BUFFER_SNAPSHOT
+        .lock()
+        .map(|snapshot| snapshot.clone())
+        .unwrap_or_default()
+}
+
+/// Builds the crash report text.
+///
+/// Header lines are prefixed with `#`; every line after the buffer marker is
+/// a verbatim buffer line.
+#[must_use]
+pub fn format_crash_report(message: &str, backtrace: &str, buffer_lines: &[String]) -> String {
+    let mut report = String::from("# crabculator crash report\n");
+    for line in message.lines() {
+        report.push_str("# panic: ");
+        report.push_str(line);
+        report.push('\n');
+    }
+    report.push_str("# backtrace:\n");
+    for line in backtrace.lines() {
+        report.push_str("# ");
+        report.push_str(line);
+        report.push('\n');
+    }
+    report.push_str(BUFFER_MARKER);
+    report.push('\n');
+    report.push_str(&buffer_lines.join("\n"));
+    report
+}
+
+/// Extracts the buffer lines from a crash report.
+///
+/// Returns `None` if the report has no buffer marker.
+#[must_use]
+pub fn parse_crash_report(contents: &str) -> Option<PersistedState> {
+    let mut lines = contents.lines();
+    lines.by_ref().find(|line| *line == BUFFER_MARKER)?;
+    Some(PersistedState::new(lines.map(String::from).collect()))
+}
+
+/// Writes a crash report to the recovery file in the state directory.
+///
+/// # Errors
+///
+/// Returns an error if the recovery file path cannot be determined or the
+/// file cannot be written.
+pub fn write_crash_report(message: &str, backtrace: &str) -> io::Result<()> {
+    let recovery_file = paths::recovery_file().ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::NotFound,
+            "Could not determine recovery file path",
+        )
+    })?;
+
+    write_crash_report_to_path(message, backtrace, &recorded_buffer(), &recovery_file)
+}
+
+/// Writes a crash report to a specific path.
+///
+/// This is primarily used for testing with temporary files.
+///
+/// # Errors
+///
+/// Returns an error if the parent directory cannot be created or the file
+/// cannot be written.
+pub fn write_crash_report_to_path(
+    message: &str,
+    backtrace: &str,
+    buffer_lines: &[String],
+    path: &Path,
+) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, format_crash_report(message, backtrace, buffer_lines))
+}
+
+/// Loads the buffer from the recovery file, if a crash report exists.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read.
+pub fn load_recovery() -> io::Result<Option<PersistedState>> {
+    let Some(recovery_file) = paths::recovery_file() else {
+        return Ok(None);
+    };
+
+    load_recovery_from_path(&recovery_file)
+}
+
+/// Loads the buffer from a crash report at a specific path.
+///
+/// Returns `Ok(None)` if the file does not exist or is not a valid report.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read.
+pub fn load_recovery_from_path(path: &Path) -> io::Result<Option<PersistedState>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(parse_crash_report(&contents)),
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::InvalidData) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Removes the recovery file once it has been restored or dismissed.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be removed.
+pub fn discard_recovery() -> io::Result<()> {
+    let Some(recovery_file) = paths::recovery_file() else {
+        return Ok(());
+    };
+
+    discard_recovery_at_path(&recovery_file)
+}
+
+/// Removes a recovery file at a specific path, ignoring a missing file.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be removed.
+pub fn discard_recovery_at_path(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn lines(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| (*s).to_string()).collect()
+    }
+
+    #[test]
+    fn test_format_crash_report_contains_message_and_backtrace() {
+        let report = format_crash_report("boom", "frame 0\nframe 1", &lines(&["1 + 2"]));
+
+        assert!(report.contains("# panic: boom"));
+        assert!(report.contains("# frame 0"));
+        assert!(report.contains("# frame 1"));
+        assert!(report.ends_with("# --- buffer ---\n1 + 2"));
+    }
+
+    #[test]
+    fn test_parse_crash_report_extracts_buffer() {
+        let report = format_crash_report("boom", "", &lines(&["a = 5", "# not a header", "a*2"]));
+
+        let state = parse_crash_report(&report).expect("should parse report");
+
+        assert_eq!(
+            state.buffer_lines,
+            lines(&["a = 5", "# not a header", "a*2"])
+        );
+    }
+
+    #[test]
+    fn test_parse_crash_report_without_marker_returns_none() {
+        assert!(parse_crash_report("# crabculator crash report\n").is_none());
+    }
+
+    #[test]
+    fn test_write_and_load_recovery_roundtrip() {
+        let dir = tempdir().expect("should create temp dir");
+        let path = dir.path().join("recovery.txt");
+
+        write_crash_report_to_path("boom", "bt", &lines(&["x = 1", "x + 1"]), &path)
+            .expect("write should succeed");
+        let state = load_recovery_from_path(&path)
+            .expect("load should succeed")
+            .expect("should have state");
+
+        assert_eq!(state.buffer_lines, lines(&["x = 1", "x + 1"]));
+    }
+
+    #[test]
+    fn test_write_crash_report_to_path_creates_directory() {
+        let dir = tempdir().expect("should create temp dir");
+        let path = dir.path().join("state").join("recovery.txt");
+
+        write_crash_report_to_path("boom", "frame 0", &lines(&["1 + 2"]), &path)
+            .expect("write should succeed");
+        let contents = fs::read_to_string(&path).expect("should read report");
+
+        assert_eq!(
+            contents,
+            format_crash_report("boom", "frame 0", &lines(&["1 + 2"]))
+        );
+    }
+
+    #[test]
+    fn test_load_recovery_from_missing_path_returns_none() {
+        let dir = tempdir().expect("should create temp dir");
+        let path = dir.path().join("missing.txt");
+
+        assert!(
+            load_recovery_from_path(&path)
+                .expect("load should not error")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_discard_recovery_removes_file_and_ignores_missing() {
+        let dir = tempdir().expect("should create temp dir");
+        let path = dir.path().join("recovery.txt");
+        fs::write(&path, "x").expect("should write file");
+
+        discard_recovery_at_path(&path).expect("discard should succeed");
+        assert!(!path.exists());
+        discard_recovery_at_path(&path).expect("discarding twice should succeed");
+    }
+}
@@ -0,0 +1,121 @@
+//! Session persistence for Crabculator.
+//!
+//! The cursor and scroll position in the buffer are stored next to it as
+//! `key = value` lines, in the same format as the settings file, so that the
+//! next session starts where the last one left off.
+
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+use super::paths;
+use super::settings::{load_settings_from_path, save_settings_to_path};
+use super::state::Position;
+
+/// Loads the position from the session file.
+///
+/// # Returns
+///
+/// The saved position, or the start of the buffer if the file doesn't exist.
+/// Unknown keys and values that are not numbers are ignored.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read (e.g., permission denied).
+pub fn load_position() -> io::Result<Position> {
+    let Some(session_file) = paths::session_file() else {
+        return Ok(Position::default());
+    };
+
+    load_position_from_path(&session_file)
+}
+
+/// Loads the position from the session file at `path`.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read.
+pub fn load_position_from_path(path: &Path) -> io::Result<Position> {
+    let mut position = Position::default();
+    for (key, value) in load_settings_from_path(path)? {
+        let Ok(value) = value.parse() else {
+            continue;
+        };
+        match key.as_str() {
+            "row" => position.cursor_row = value,
+            "col" => position.cursor_col = value,
+            "scroll" => position.scroll_offset = value,
+            "hscroll" => position.horizontal_scroll_offset = value,
+            _ => {}
+        }
+    }
+    Ok(position)
+}
+
+/// Saves `position` to the session file.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The session file path cannot be determined
+/// - The directory cannot be created
+/// - The file cannot be written
+pub fn save_position(position: Position) -> io::Result<()> {
+    let session_file = paths::session_file().ok_or_else(|| {
+        io::Error::new(ErrorKind::NotFound, "Could not determine session file path")
+    })?;
+
+    save_position_to_path(position, &session_file)
+}
+
+/// Saves `position` to the session file at `path`.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created or the file cannot
+/// be written.
+pub fn save_position_to_path(position: Position, path: &Path) -> io::Result<()> {
+    let entries = [
+        ("row", position.cursor_row),
+        ("col", position.cursor_col),
+        ("scroll", position.scroll_offset),
+        ("hscroll", position.horizontal_scroll_offset),
+    ]
+    .map(|(key, value)| (key.to_string(), value.to_string()));
+    save_settings_to_path(&entries, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_position_round_trips() {
+        let dir = tempdir().expect("should create temp dir");
+        let path = dir.path().join("session.txt");
+        let position = Position {
+            cursor_row: 120,
+            cursor_col: 7,
+            scroll_offset: 100,
+            horizontal_scroll_offset: 4,
+        };
+
+        save_position_to_path(position, &path).expect("should save");
+        assert_eq!(load_position_from_path(&path).unwrap(), position);
+    }
+
+    #[test]
+    fn test_load_position_skips_bad_entries() {
+        let dir = tempdir().expect("should create temp dir");
+        let path = dir.path().join("session.txt");
+        std::fs::write(&path, "row = 3\ncol = x\nzoom = 2").expect("should write");
+
+        let position = load_position_from_path(&path).unwrap();
+        assert_eq!(position.cursor_row, 3);
+        assert_eq!(position.cursor_col, 0);
+        assert_eq!(
+            load_position_from_path(&dir.path().join("missing.txt")).unwrap(),
+            Position::default()
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Settings persistence for Crabculator.
+//!
+//! Settings are stored as plain `key = value` lines. Blank lines and lines
+//! starting with `#` are ignored, so the file can be edited by hand.
+
+use std::fmt::Write;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+use super::atomic::write_atomically;
+use super::paths;
+
+/// Loads the settings from the settings file as `key = value` pairs.
+///
+/// # Returns
+///
+/// The pairs in file order, or none if the file doesn't exist.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read (e.g., permission denied).
+pub fn load_settings() -> io::Result<Vec<(String, String)>> {
+    let Some(settings_file) = paths::settings_file() else {
+        return Ok(Vec::new());
+    };
+
+    load_settings_from_path(&settings_file)
+}
+
+/// Loads settings from a specific path.
+///
+/// Lines without an `=` are skipped.
+/// This is primarily used for testing with temporary files.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read (e.g., permission denied).
+pub fn load_settings_from_path(path: &Path) -> io::Result<Vec<(String, String)>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::InvalidData) => {
+            return Ok(Vec::new());
+        }
+        Err(e) => return Err(e),
+    };
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect())
+}
+
+/// Saves the given `key = value` pairs to the settings file.
+///
+/// Creates the state directory if it doesn't exist.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The settings file path cannot be determined
+/// - The directory cannot be created
+/// - The file cannot be written
+pub fn save_settings(entries: &[(String, String)]) -> io::Result<()> {
+    let settings_file = paths::settings_file().ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::NotFound,
+            "Could not determine settings file path",
+        )
+    })?;
+
+    save_settings_to_path(entries, &settings_file)
+}
+
+/// Saves `key = value` pairs to a specific path, one per line, replacing
+/// the file atomically.
+///
+/// This is primarily used for testing with temporary files.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The parent directory cannot be created
+/// - The file cannot be written
+pub fn save_settings_to_path(entries: &[(String, String)], path: &Path) -> io::Result<()> {
+    let mut content = String::new();
+    for (key, value) in entries {
+        let _ = writeln!(content, "{key} = {value}");
+    }
+    write_atomically(path, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_save_and_load_settings_roundtrip() {
+        let dir = tempdir().expect("should create temp dir");
+        let file_path = dir.path().join("settings.txt");
+
+        let entries = [
+            ("precision".to_string(), "4".to_string()),
+            ("angle".to_string(), "deg".to_string()),
+        ];
+        save_settings_to_path(&entries, &file_path).expect("save should succeed");
+
+        let contents = fs::read_to_string(&file_path).expect("should read file");
+        assert_eq!(contents, "precision = 4\nangle = deg\n");
+
+        let loaded = load_settings_from_path(&file_path).expect("load should succeed");
+        assert_eq!(
+            loaded,
+            vec![
+                ("precision".to_string(), "4".to_string()),
+                ("angle".to_string(), "deg".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_settings_skips_comments_and_malformed_lines() {
+        let dir = tempdir().expect("should create temp dir");
+        let file_path = dir.path().join("settings.txt");
+
+        fs::write(&file_path, "# display\n\nwrap=off\nnonsense\n").expect("should write file");
+
+        let loaded = load_settings_from_path(&file_path).expect("load should succeed");
+        assert_eq!(loaded, vec![("wrap".to_string(), "off".to_string())]);
+    }
+
+    #[test]
+    fn test_load_settings_missing_file_is_empty() {
+        let dir = tempdir().expect("should create temp dir");
+
+        let loaded = load_settings_from_path(&dir.path().join("missing.txt"))
+            .expect("load should not error");
+        assert!(loaded.is_empty());
+    }
+}
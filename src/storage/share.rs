@@ -0,0 +1,35 @@
+//! Share files for Crabculator.
+//!
+//! A worksheet is written with `:share` to a single text file anywhere on
+//! disk, and read back with `:import`.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use super::paths;
+
+/// Returns the path of a share file typed by the user, with a leading `~/`
+/// standing for the home directory.
+#[must_use]
+pub fn share_path(path: &str) -> PathBuf {
+    paths::expand_home(path)
+}
+
+/// Reads the share file at `path`, see `share_path`.
+///
+/// # Errors
+///
+/// Returns an error if the file does not exist or cannot be read.
+pub fn read_share_file(path: &str) -> io::Result<String> {
+    fs::read_to_string(share_path(path))
+}
+
+/// Writes `contents` to the share file at `path`, see `share_path`.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written.
+pub fn write_share_file(path: &str, contents: &str) -> io::Result<()> {
+    fs::write(share_path(path), contents)
+}
@@ -3,35 +3,157 @@
 //! Provides functionality to save and load buffer lines to/from disk as plain text.
 
 use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::io::{self, ErrorKind};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+use super::atomic::write_atomically;
 use super::paths;
 
+#[cfg(feature = "encryption")]
+pub mod encrypted;
+
+/// First bytes of a buffer file encrypted with a passphrase.
+///
+/// Files are recognized as encrypted even in builds without the
+/// `encryption` feature, so that they are never read as text and
+/// overwritten.
+pub const ENCRYPTED_MAGIC: &[u8] = b"crabculator-encrypted-v1\n";
+
+/// Separates a line from the comment its result is written in, such as
+/// `price * 1.2  # => 48`.
+pub const RESULT_COMMENT: &str = "  # => ";
+
+/// First line of a buffer file saved with its results as comments. Result
+/// comments are only removed from files that start with it.
+pub const RESULTS_HEADER: &str = "# crabculator: results saved as # => comments";
+
+/// Appended to the name of a buffer file that is not valid UTF-8 to name
+/// the copy kept of it.
+const QUARANTINE_SUFFIX: &str = ".corrupt";
+
 /// Represents the persisted application state.
 ///
-/// Contains the buffer lines that should be saved between sessions.
+/// Contains the buffer lines that should be saved between sessions, and
+/// where in them the user left off.
 /// Variables are not persisted; they are computed from evaluating buffer lines.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PersistedState {
     /// The lines from the buffer.
     pub buffer_lines: Vec<String>,
+    /// The cursor and scroll position in the buffer.
+    ///
+    /// Only the lines are written to the buffer file; the position is kept
+    /// in the session file.
+    pub position: Position,
+    /// The results written as comments after the lines, one per line, or
+    /// none to write the lines alone.
+    pub results: Vec<Option<String>>,
+}
+
+/// Where the cursor and the view were in the buffer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Position {
+    /// The line of the cursor.
+    pub cursor_row: usize,
+    /// The byte offset of the cursor in its line.
+    pub cursor_col: usize,
+    /// The first line shown.
+    pub scroll_offset: usize,
+    /// The first column shown.
+    pub horizontal_scroll_offset: usize,
 }
 
 impl PersistedState {
-    /// Creates a new `PersistedState` with the given buffer lines.
+    /// Creates a new `PersistedState` with the given buffer lines, with the
+    /// cursor at their start.
     #[must_use]
-    pub const fn new(buffer_lines: Vec<String>) -> Self {
-        Self { buffer_lines }
+    pub fn new(buffer_lines: Vec<String>) -> Self {
+        Self {
+            buffer_lines,
+            position: Position::default(),
+            results: Vec::new(),
+        }
     }
 
-    /// Creates an empty `PersistedState` with no buffer lines.
+    /// Creates a `PersistedState` from the text of a buffer file, one line
+    /// per line of text, without their result comments if it was saved
+    /// with them.
     #[must_use]
-    pub const fn empty() -> Self {
-        Self {
-            buffer_lines: Vec::new(),
+    pub fn from_text(text: &str) -> Self {
+        let mut lines = text.lines().peekable();
+        if lines.next_if_eq(&RESULTS_HEADER).is_some() {
+            Self::new(
+                lines
+                    .map(|line| strip_result_comment(line).to_string())
+                    .collect(),
+            )
+        } else {
+            Self::new(lines.map(str::to_string).collect())
         }
     }
+
+    /// Returns the text of the buffer file, with each result after its line
+    /// as a comment.
+    ///
+    /// With results, the text starts with `RESULTS_HEADER`, and a line that
+    /// contains `RESULT_COMMENT` itself is given an empty comment when it
+    /// has no result, so that the last comment of a line is always the one
+    /// appended to it.
+    #[must_use]
+    pub fn text(&self) -> String {
+        if self.results.is_empty() {
+            return self.buffer_lines.join("\n");
+        }
+        let lines = self.buffer_lines.iter().enumerate().map(|(i, line)| {
+            let result = self
+                .results
+                .get(i)
+                .and_then(Option::as_deref)
+                .filter(|result| !result.contains(RESULT_COMMENT));
+            match result {
+                Some(result) => format!("{line}{RESULT_COMMENT}{result}"),
+                None if line.contains(RESULT_COMMENT) => format!("{line}{RESULT_COMMENT}"),
+                None => line.clone(),
+            }
+        });
+        std::iter::once(RESULTS_HEADER.to_string())
+            .chain(lines)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Creates an empty `PersistedState` with no buffer lines.
+    #[must_use]
+    pub fn empty() -> Self {
+        Self::new(Vec::new())
+    }
+
+    /// Returns this state with the cursor and scroll position `position`.
+    #[must_use]
+    pub fn with_position(self, position: Position) -> Self {
+        Self { position, ..self }
+    }
+
+    /// Returns this state with `results` to write as comments after the
+    /// lines.
+    #[must_use]
+    pub fn with_results(self, results: Vec<Option<String>>) -> Self {
+        Self { results, ..self }
+    }
+}
+
+/// Returns a line of a file saved with results without the result comment
+/// written after it, if any.
+///
+/// Results are written as comments for people reading the file, such as in
+/// a diff, and always recomputed; removing exactly what was appended gives
+/// back the line as it was.
+#[must_use]
+pub fn strip_result_comment(line: &str) -> &str {
+    line.rfind(RESULT_COMMENT)
+        .map_or(line, |start| &line[..start])
 }
 
 impl Default for PersistedState {
@@ -40,10 +162,48 @@ impl Default for PersistedState {
     }
 }
 
+/// Identifies the buffer lines of a state file.
+///
+/// An instance remembers the revision it last loaded or saved; a different
+/// revision on disk means another instance, or another program, changed the
+/// file in the meantime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Revision(u64);
+
+impl Revision {
+    /// Returns the revision of a file holding `lines`.
+    #[must_use]
+    pub fn of(lines: &[String]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        lines.hash(&mut hasher);
+        Self(hasher.finish())
+    }
+}
+
+impl PersistedState {
+    /// Returns the revision of a file holding this state.
+    #[must_use]
+    pub fn revision(&self) -> Revision {
+        Revision::of(&self.buffer_lines)
+    }
+}
+
+/// Returns when the file at `path` was last modified, or `None` if it does
+/// not exist or the platform does not record it.
+///
+/// This is cheap enough to poll, to notice changes made by other programs.
+#[must_use]
+pub fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
 /// Saves the given state to the state file as plain text.
 ///
 /// Creates the state directory if it doesn't exist.
-/// Each buffer line is written as one line in the file.
+/// Each buffer line is written as one line in the file, followed by its
+/// result comment if the state has results.
 ///
 /// # Errors
 ///
@@ -65,8 +225,7 @@ pub fn save(state: &PersistedState) -> io::Result<()> {
 
     fs::create_dir_all(&state_dir)?;
 
-    let content = state.buffer_lines.join("\n");
-    fs::write(&state_file, content)
+    write_atomically(&state_file, state.text())
 }
 
 /// Loads the state from the state file.
@@ -75,7 +234,8 @@ pub fn save(state: &PersistedState) -> io::Result<()> {
 ///
 /// - `Ok(Some(state))` if the file exists and contains valid content
 /// - `Ok(None)` if the file doesn't exist
-/// - `Ok(None)` if the file is corrupted (logs a warning)
+/// - the readable lines if the file is not valid UTF-8, as `decode_lossy`
+///   recovers them
 ///
 /// # Errors
 ///
@@ -97,25 +257,203 @@ pub fn load() -> io::Result<Option<PersistedState>> {
 ///
 /// Returns an error if the file exists but cannot be read (e.g., permission denied).
 pub fn load_from_path(path: &Path) -> io::Result<Option<PersistedState>> {
-    let contents = match fs::read_to_string(path) {
+    let contents = match fs::read(path) {
         Ok(contents) => contents,
         Err(e) if e.kind() == ErrorKind::NotFound => {
             return Ok(None);
         }
-        Err(e) if e.kind() == ErrorKind::InvalidData => {
-            eprintln!("Warning: State file contains invalid UTF-8, using empty state. Error: {e}");
-            return Ok(None);
+        Err(e) => return Err(e),
+    };
+
+    match String::from_utf8(contents) {
+        Ok(text) => Ok(Some(PersistedState::from_text(&text))),
+        Err(e) => Ok(decode_lossy(e.as_bytes())),
+    }
+}
+
+/// Recovers the lines of buffer file contents that are not valid UTF-8.
+///
+/// Invalid sequences in text become U+FFFD. Contents with NUL bytes are
+/// taken as binary garbage rather than text and give `None`.
+fn decode_lossy(contents: &[u8]) -> Option<PersistedState> {
+    if contents.contains(&0) {
+        return None;
+    }
+    Some(PersistedState::from_text(&String::from_utf8_lossy(
+        contents,
+    )))
+}
+
+/// The buffer recovered from a file that is not valid UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveredBuffer {
+    /// The readable lines, or `None` if the file is binary garbage.
+    pub state: Option<PersistedState>,
+    /// The copy kept of the original file.
+    pub backup: PathBuf,
+}
+
+/// Recovers the buffer file at `path`, which `read_buffer` found not to be
+/// valid UTF-8.
+///
+/// The original is first copied next to it with a `.corrupt` suffix, so
+/// saving the recovered buffer over it loses nothing.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or the copy cannot be
+/// written.
+pub fn recover_buffer(path: &Path) -> io::Result<RecoveredBuffer> {
+    let contents = fs::read(path)?;
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(QUARANTINE_SUFFIX);
+    let backup = PathBuf::from(backup);
+    fs::write(&backup, &contents)?;
+    Ok(RecoveredBuffer {
+        state: decode_lossy(&contents),
+        backup,
+    })
+}
+
+/// Returns whether the file at `path` is an encrypted buffer file.
+#[must_use]
+pub fn is_encrypted_file(path: &Path) -> bool {
+    let mut magic = vec![0; ENCRYPTED_MAGIC.len()];
+    fs::File::open(path)
+        .and_then(|mut file| io::Read::read_exact(&mut file, &mut magic))
+        .is_ok_and(|()| magic == ENCRYPTED_MAGIC)
+}
+
+/// The passphrase a buffer file is encrypted with.
+///
+/// Deriving a key from it is slow on purpose, so the key is derived once and
+/// kept for the session with its salt. Files are written with that salt, and
+/// a file written with it, such as the last save, is read without deriving
+/// the key again. Only a file saved elsewhere with another salt derives a
+/// new key.
+pub struct Passphrase {
+    #[cfg_attr(not(feature = "encryption"), allow(dead_code))]
+    text: String,
+    #[cfg(feature = "encryption")]
+    key: std::cell::RefCell<Option<encrypted::Key>>,
+}
+
+impl Passphrase {
+    /// Creates a passphrase whose key is derived when first needed.
+    #[must_use]
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            #[cfg(feature = "encryption")]
+            key: std::cell::RefCell::default(),
         }
+    }
+
+    /// Calls `f` with the key for `salt`, or with the cached key if no salt
+    /// is given, deriving and caching a key if needed.
+    #[cfg(feature = "encryption")]
+    fn with_key<T>(
+        &self,
+        salt: Option<encrypted::Salt>,
+        f: impl FnOnce(&encrypted::Key) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut cached = self.key.borrow_mut();
+        let key = match cached.take() {
+            Some(key) if salt.is_none_or(|salt| *key.salt() == salt) => key,
+            _ => encrypted::Key::derive(&self.text, salt)?,
+        };
+        f(cached.insert(key))
+    }
+}
+
+impl std::fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Passphrase(..)")
+    }
+}
+
+/// Reads the buffer file at `path`, decrypting it with `passphrase` if one
+/// is given.
+///
+/// # Returns
+///
+/// `Ok(None)` if the file doesn't exist.
+///
+/// # Errors
+///
+/// Returns an `InvalidData` error if the file is encrypted and no passphrase,
+/// or a wrong one, is given, or if it is not valid UTF-8. Returns an
+/// `Unsupported` error for a passphrase in builds without the `encryption`
+/// feature.
+pub fn read_buffer(
+    path: &Path,
+    passphrase: Option<&Passphrase>,
+) -> io::Result<Option<PersistedState>> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
         Err(e) => return Err(e),
     };
+    let text = match passphrase {
+        #[cfg(feature = "encryption")]
+        Some(passphrase) if contents.starts_with(ENCRYPTED_MAGIC) => passphrase
+            .with_key(encrypted::salt(&contents), |key| {
+                encrypted::decrypt(&contents, key)
+            })?,
+        #[cfg(not(feature = "encryption"))]
+        Some(_) => return Err(encryption_unsupported()),
+        _ if contents.starts_with(ENCRYPTED_MAGIC) => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "the file is encrypted",
+            ));
+        }
+        _ => String::from_utf8(contents).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?,
+    };
+    Ok(Some(PersistedState::from_text(&text)))
+}
+
+/// Writes `state` to the buffer file at `path`, encrypted with `passphrase`
+/// if one is given.
+///
+/// # Errors
+///
+/// Returns an error if the parent directory cannot be created or the file
+/// cannot be written. Returns an `Unsupported` error for a passphrase in
+/// builds without the `encryption` feature.
+pub fn write_buffer(
+    state: &PersistedState,
+    path: &Path,
+    passphrase: Option<&Passphrase>,
+) -> io::Result<()> {
+    let Some(passphrase) = passphrase else {
+        return save_to_path(state, path);
+    };
+    #[cfg(feature = "encryption")]
+    {
+        let contents = passphrase.with_key(None, |key| encrypted::encrypt(&state.text(), key))?;
+        write_atomically(path, contents)
+    }
+    #[cfg(not(feature = "encryption"))]
+    {
+        let _ = passphrase;
+        Err(encryption_unsupported())
+    }
+}
 
-    let buffer_lines: Vec<String> = contents.lines().map(String::from).collect();
-    Ok(Some(PersistedState::new(buffer_lines)))
+/// The error for a passphrase given to a build without encryption.
+#[cfg(not(feature = "encryption"))]
+fn encryption_unsupported() -> io::Error {
+    io::Error::new(
+        ErrorKind::Unsupported,
+        "this build has no encryption support",
+    )
 }
 
 /// Saves state to a specific path as plain text.
 ///
-/// Each buffer line is written as one line in the file.
+/// Each buffer line is written as one line in the file, followed by its
+/// result comment if the state has results.
 /// This is primarily used for testing with temporary files.
 ///
 /// # Errors
@@ -124,12 +462,7 @@ pub fn load_from_path(path: &Path) -> io::Result<Option<PersistedState>> {
 /// - The parent directory cannot be created
 /// - The file cannot be written
 pub fn save_to_path(state: &PersistedState, path: &Path) -> io::Result<()> {
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
-    }
-
-    let content = state.buffer_lines.join("\n");
-    fs::write(path, content)
+    write_atomically(path, state.text())
 }
 
 #[cfg(test)]
@@ -263,6 +596,76 @@ mod tests {
         assert_eq!(result.buffer_lines, vec!["single line"]);
     }
 
+    #[test]
+    fn test_revision_changes_with_the_lines() {
+        let state = PersistedState::new(vec!["a = 1".to_string(), "a".to_string()]);
+        assert_eq!(
+            state.revision(),
+            Revision::of(&["a = 1".to_string(), "a".to_string()])
+        );
+        assert_ne!(state.revision(), Revision::of(&["a = 1".to_string()]));
+        assert_ne!(state.revision(), Revision::of(&["a = 1a".to_string()]));
+    }
+
+    #[test]
+    fn test_modified_time_of_missing_file_is_none() {
+        let dir = tempdir().expect("should create temp dir");
+        let file_path = dir.path().join("state.txt");
+        assert_eq!(modified_time(&file_path), None);
+
+        fs::write(&file_path, "1").expect("should write file");
+        assert!(modified_time(&file_path).is_some());
+    }
+
+    #[test]
+    fn test_read_buffer_refuses_encrypted_file_without_passphrase() {
+        let dir = tempdir().expect("should create temp dir");
+        let file_path = dir.path().join("state.txt");
+        assert!(read_buffer(&file_path, None).unwrap().is_none());
+
+        fs::write(&file_path, "a = 1\nb").expect("should write file");
+        assert!(!is_encrypted_file(&file_path));
+        assert_eq!(
+            read_buffer(&file_path, None).unwrap().unwrap().buffer_lines,
+            vec!["a = 1", "b"]
+        );
+
+        fs::write(&file_path, [0xff, 0xfe]).expect("should write file");
+        assert!(read_buffer(&file_path, None).is_err());
+
+        let mut contents = ENCRYPTED_MAGIC.to_vec();
+        contents.extend_from_slice(&[0xff; 40]);
+        fs::write(&file_path, contents).expect("should write file");
+        assert!(is_encrypted_file(&file_path));
+        let error = read_buffer(&file_path, None).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_write_buffer_encrypts_with_passphrase() {
+        let dir = tempdir().expect("should create temp dir");
+        let file_path = dir.path().join("nested").join("state.txt");
+        let state = PersistedState::new(vec!["salary = 5000".to_string()]);
+
+        let passphrase = Passphrase::new("secret");
+        write_buffer(&state, &file_path, Some(&passphrase)).expect("should write");
+        assert!(is_encrypted_file(&file_path));
+        assert_eq!(
+            read_buffer(&file_path, Some(&Passphrase::new("secret"))).unwrap(),
+            Some(state.clone())
+        );
+        assert!(read_buffer(&file_path, Some(&Passphrase::new("guess"))).is_err());
+
+        let salt = encrypted::salt(&fs::read(&file_path).unwrap());
+        write_buffer(&state, &file_path, Some(&passphrase)).expect("should write");
+        assert_eq!(encrypted::salt(&fs::read(&file_path).unwrap()), salt);
+        assert_eq!(
+            read_buffer(&file_path, Some(&passphrase)).unwrap(),
+            Some(state)
+        );
+    }
+
     #[test]
     fn test_save_and_load_roundtrip() {
         let dir = tempdir().expect("should create temp dir");
@@ -278,6 +681,45 @@ mod tests {
         assert_eq!(original, loaded);
     }
 
+    #[test]
+    fn test_result_comments_are_written_and_ignored_on_load() {
+        let dir = tempdir().expect("should create temp dir");
+        let file_path = dir.path().join("state.txt");
+        let lines = vec![
+            "price = 40 ".to_string(),
+            "# => note".to_string(),
+            "price * 2".to_string(),
+            "# see  # => below".to_string(),
+        ];
+        let state = PersistedState::new(lines.clone()).with_results(vec![
+            Some("price = 40".to_string()),
+            None,
+            Some("80".to_string()),
+            None,
+        ]);
+
+        save_to_path(&state, &file_path).expect("save should succeed");
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            format!(
+                "{RESULTS_HEADER}\nprice = 40   # => price = 40\n# => note\nprice * 2  # => 80\n# see  # => below  # => "
+            )
+        );
+        let loaded = read_buffer(&file_path, None).unwrap().unwrap();
+        assert_eq!(loaded.buffer_lines, lines);
+        assert_eq!(loaded.revision(), state.revision());
+    }
+
+    #[test]
+    fn test_result_comments_are_kept_in_files_saved_without_results() {
+        let dir = tempdir().expect("should create temp dir");
+        let file_path = dir.path().join("state.txt");
+        fs::write(&file_path, "x = 5  # => note\nx * 2").unwrap();
+
+        let loaded = read_buffer(&file_path, None).unwrap().unwrap();
+        assert_eq!(loaded.buffer_lines, ["x = 5  # => note", "x * 2"]);
+    }
+
     #[test]
     fn test_save_and_load_empty_state() {
         let dir = tempdir().expect("should create temp dir");
@@ -322,6 +764,43 @@ mod tests {
         assert!(result.unwrap().is_none());
     }
 
+    #[test]
+    fn test_load_from_path_replaces_invalid_utf8() {
+        let dir = tempdir().expect("should create temp dir");
+        let file_path = dir.path().join("latin1.txt");
+
+        fs::write(&file_path, b"price = 5\nna\xEFve = 2").expect("should write file");
+
+        let result = load_from_path(&file_path).expect("load should not error");
+        assert_eq!(
+            result,
+            Some(PersistedState::new(vec![
+                "price = 5".to_string(),
+                "na\u{FFFD}ve = 2".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_recover_buffer_keeps_a_copy() {
+        let dir = tempdir().expect("should create temp dir");
+        let file_path = dir.path().join("state.txt");
+        fs::write(&file_path, b"a = 1\n\xFF").expect("should write file");
+
+        let recovered = recover_buffer(&file_path).expect("should recover");
+
+        let backup = dir.path().join("state.txt.corrupt");
+        assert_eq!(recovered.backup, backup);
+        assert_eq!(fs::read(&backup).unwrap(), b"a = 1\n\xFF");
+        assert_eq!(
+            recovered.state.map(|state| state.buffer_lines),
+            Some(vec!["a = 1".to_string(), "\u{FFFD}".to_string()])
+        );
+
+        fs::write(&file_path, [0x00, 0xFF, 0xFE, 0x89]).expect("should write file");
+        assert_eq!(recover_buffer(&file_path).unwrap().state, None);
+    }
+
     #[test]
     fn test_graceful_handling_binary_garbage_returns_none() {
         let dir = tempdir().expect("should create temp dir");
@@ -0,0 +1,153 @@
+//! Passphrase encryption of saved buffers.
+//!
+//! An encrypted file starts with `ENCRYPTED_MAGIC`, followed by a random
+//! salt, a random nonce and the buffer text sealed with
+//! XChaCha20-Poly1305. The key is derived from the passphrase and the salt
+//! with Argon2id, which is slow on purpose, so a [`Key`] is derived once and
+//! kept with its salt: every save uses the salt of the key and a fresh
+//! nonce.
+
+use std::io::{self, ErrorKind};
+
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+use super::ENCRYPTED_MAGIC;
+
+/// Length of the random salt for key derivation, in bytes.
+const SALT_LEN: usize = 16;
+
+/// Length of an `XChaCha20` nonce, in bytes.
+const NONCE_LEN: usize = 24;
+
+/// The salt a key is derived with.
+pub type Salt = [u8; SALT_LEN];
+
+/// A key derived from a passphrase, with the salt it was derived with.
+pub struct Key {
+    salt: Salt,
+    cipher: XChaCha20Poly1305,
+}
+
+impl Key {
+    /// Derives the key for `passphrase` and `salt`, or for a fresh random
+    /// salt if none is given.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the key cannot be derived.
+    pub fn derive(passphrase: &str, salt: Option<Salt>) -> io::Result<Self> {
+        let salt = salt.unwrap_or_else(|| {
+            let mut salt = [0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+            salt
+        });
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        Ok(Self {
+            salt,
+            cipher: XChaCha20Poly1305::new(&key.into()),
+        })
+    }
+
+    /// Returns the salt the key was derived with.
+    #[must_use]
+    pub const fn salt(&self) -> &Salt {
+        &self.salt
+    }
+}
+
+/// Returns the salt of the contents of an encrypted file, or `None` if they
+/// are not encrypted or too short.
+#[must_use]
+pub fn salt(contents: &[u8]) -> Option<Salt> {
+    contents
+        .strip_prefix(ENCRYPTED_MAGIC)?
+        .get(..SALT_LEN)?
+        .try_into()
+        .ok()
+}
+
+/// Encrypts `text` with `key` into the contents of an encrypted file.
+///
+/// # Errors
+///
+/// Returns an error if encryption fails.
+pub fn encrypt(text: &str, key: &Key) -> io::Result<Vec<u8>> {
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    let sealed = key
+        .cipher
+        .encrypt(XNonce::from_slice(&nonce), text.as_bytes())
+        .map_err(|_| io::Error::other("encryption failed"))?;
+
+    let mut contents = ENCRYPTED_MAGIC.to_vec();
+    contents.extend_from_slice(&key.salt);
+    contents.extend_from_slice(&nonce);
+    contents.extend_from_slice(&sealed);
+    Ok(contents)
+}
+
+/// Decrypts the contents of an encrypted file with `key`, which must have
+/// been derived with the salt of the file.
+///
+/// # Errors
+///
+/// Returns an `InvalidData` error if the passphrase is wrong or the file is
+/// damaged; the two cannot be told apart.
+pub fn decrypt(contents: &[u8], key: &Key) -> io::Result<String> {
+    let invalid = || io::Error::new(ErrorKind::InvalidData, "wrong passphrase or damaged file");
+    if salt(contents) != Some(key.salt) {
+        return Err(invalid());
+    }
+    let rest = &contents[ENCRYPTED_MAGIC.len() + SALT_LEN..];
+    if rest.len() < NONCE_LEN {
+        return Err(invalid());
+    }
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+
+    let text = key
+        .cipher
+        .decrypt(XNonce::from_slice(nonce), sealed)
+        .map_err(|_| invalid())?;
+    String::from_utf8(text).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_round_trips() {
+        let key = Key::derive("secret", None).unwrap();
+        let contents = encrypt("salary = 5000", &key).unwrap();
+        assert!(contents.starts_with(ENCRYPTED_MAGIC));
+        assert!(!contents.windows(6).any(|w| w == b"salary"));
+        assert_eq!(decrypt(&contents, &key).unwrap(), "salary = 5000");
+        assert_ne!(encrypt("salary = 5000", &key).unwrap(), contents);
+
+        let again = Key::derive("secret", salt(&contents)).unwrap();
+        assert_eq!(decrypt(&contents, &again).unwrap(), "salary = 5000");
+    }
+
+    #[test]
+    fn test_decrypt_rejects_wrong_passphrase_and_damage() {
+        let key = Key::derive("secret", None).unwrap();
+        let mut contents = encrypt("a = 1", &key).unwrap();
+        let guess = Key::derive("guess", salt(&contents)).unwrap();
+        let error = decrypt(&contents, &guess).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        let other_salt = Key::derive("secret", None).unwrap();
+        assert!(decrypt(&contents, &other_salt).is_err());
+
+        let last = contents.len() - 1;
+        contents[last] ^= 1;
+        assert!(decrypt(&contents, &key).is_err());
+        assert!(decrypt(ENCRYPTED_MAGIC, &key).is_err());
+    }
+}
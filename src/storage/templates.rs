@@ -0,0 +1,76 @@
+//! Template files for Crabculator.
+//!
+//! Each template is a `name.txt` worksheet in `~/.crabculator/templates/`.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::paths;
+use super::profiles::profile_names_in;
+
+/// Loads the lines of the template `name`.
+///
+/// # Returns
+///
+/// The lines of the file, or `None` if there is no such template file.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read (e.g., permission denied).
+pub fn load_template(name: &str) -> io::Result<Option<Vec<String>>> {
+    let Some(dir) = paths::templates_dir() else {
+        return Ok(None);
+    };
+
+    load_template_from_dir(&dir, name)
+}
+
+/// Loads the template `name` from the template files in `dir`.
+///
+/// This is primarily used for testing with temporary directories.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read (e.g., permission denied).
+pub fn load_template_from_dir(dir: &Path, name: &str) -> io::Result<Option<Vec<String>>> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        return Ok(None);
+    }
+    let path = dir.join(format!("{name}.txt"));
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = fs::read_to_string(path)?;
+    Ok(Some(text.lines().map(str::to_string).collect()))
+}
+
+/// Returns the names of the template files, sorted. They are named like
+/// profile files.
+#[must_use]
+pub fn template_names() -> Vec<String> {
+    paths::templates_dir().map_or_else(Vec::new, |dir| profile_names_in(&dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_template_reads_lines() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        fs::write(dir.path().join("rent.txt"), "# Rent\nrent = 900\n").expect("write template");
+
+        let lines = load_template_from_dir(dir.path(), "rent").expect("read template");
+        assert_eq!(
+            lines,
+            Some(vec!["# Rent".to_string(), "rent = 900".to_string()])
+        );
+        assert_eq!(load_template_from_dir(dir.path(), "none").unwrap(), None);
+        assert_eq!(load_template_from_dir(dir.path(), "../rent").unwrap(), None);
+    }
+}
@@ -0,0 +1,107 @@
+//! Trusted projects for Crabculator.
+//!
+//! A project's `.crabculator/` folder comes with whoever made the project,
+//! so its settings may not turn on shell commands and its prelude is not
+//! evaluated until the project is trusted with `:trust`. The trusted
+//! projects are listed one per line in `trusted.txt` in the platform's
+//! configuration directory, outside of every project.
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use super::paths;
+
+/// Returns the project folder that is the state directory, unless it is
+/// trusted.
+///
+/// # Returns
+///
+/// `Some(PathBuf)` containing the project folder, or `None` if the state
+/// directory is not a project or the project is trusted.
+#[must_use]
+pub fn untrusted_project() -> Option<PathBuf> {
+    let project = paths::project_dir().filter(|dir| paths::state_dir().as_ref() == Some(dir))?;
+    let trusted =
+        paths::trusted_file().is_some_and(|path| is_trusted_in(&path, &project).unwrap_or(false));
+    (!trusted).then_some(project)
+}
+
+/// Adds the project folder `project` to the trusted projects.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The trusted projects file path cannot be determined
+/// - The directory cannot be created
+/// - The file cannot be read or written
+pub fn trust_project(project: &Path) -> io::Result<()> {
+    let path = paths::trusted_file().ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::NotFound,
+            "Could not determine trusted projects file path",
+        )
+    })?;
+
+    trust_project_in(&path, project)
+}
+
+/// Returns whether the trusted projects file at `path` lists `project`.
+///
+/// This is primarily used for testing with temporary directories.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read (e.g., permission denied).
+pub fn is_trusted_in(path: &Path, project: &Path) -> io::Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let contents = fs::read_to_string(path)?;
+    Ok(contents.lines().any(|line| Path::new(line) == project))
+}
+
+/// Adds `project` to the trusted projects file at `path`, creating it if
+/// needed.
+///
+/// This is primarily used for testing with temporary directories.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created or the file cannot
+/// be read or written.
+pub fn trust_project_in(path: &Path, project: &Path) -> io::Result<()> {
+    if is_trusted_in(path, project)? {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut contents = if path.exists() {
+        fs::read_to_string(path)?
+    } else {
+        String::new()
+    };
+    contents.push_str(&project.to_string_lossy());
+    contents.push('\n');
+    fs::write(path, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trust_project_in_lists_project_once() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("config").join("trusted.txt");
+        let project = dir.path().join("budget").join(".crabculator");
+        assert!(!is_trusted_in(&path, &project).unwrap());
+
+        trust_project_in(&path, &project).unwrap();
+        trust_project_in(&path, &project).unwrap();
+        assert!(is_trusted_in(&path, &project).unwrap());
+        assert!(!is_trusted_in(&path, dir.path()).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+    }
+}
@@ -0,0 +1,49 @@
+//! Variable files for Crabculator.
+//!
+//! Variables are imported with `:loadvars` from JSON files anywhere on disk,
+//! such as a shared file of company tax rates.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use super::paths;
+
+/// Returns the path of a variable file typed by the user, with a leading
+/// `~/` standing for the home directory.
+#[must_use]
+pub fn variables_path(path: &str) -> PathBuf {
+    paths::expand_home(path)
+}
+
+/// Reads the variable file at `path`, see `variables_path`.
+///
+/// # Errors
+///
+/// Returns an error if the file does not exist or cannot be read.
+pub fn read_variables_file(path: &str) -> io::Result<String> {
+    fs::read_to_string(variables_path(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variables_path_expands_home() {
+        let home = dirs::home_dir().expect("home_dir should be available");
+        assert_eq!(variables_path("~/rates.json"), home.join("rates.json"));
+        assert_eq!(variables_path("rates.json"), PathBuf::from("rates.json"));
+    }
+
+    #[test]
+    fn read_variables_file_reads_contents() {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        let path = dir.path().join("rates.json");
+        fs::write(&path, "{\"vat\": 0.19}").expect("write file");
+
+        let contents = read_variables_file(path.to_str().unwrap()).expect("read file");
+        assert_eq!(contents, "{\"vat\": 0.19}");
+        assert!(read_variables_file("/nonexistent/rates.json").is_err());
+    }
+}
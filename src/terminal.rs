@@ -3,15 +3,26 @@
 //! This module provides functions to set up and restore the terminal state,
 //! including handling panics to ensure the terminal is always restored.
 
+use std::backtrace::Backtrace;
 use std::io::{self, Stdout};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crossterm::{
+    clipboard::CopyToClipboard,
     cursor::{Hide, Show},
+    event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use ratatui::{Terminal, backend::CrosstermBackend};
 
+use crate::storage::recovery;
+
+/// Whether mouse events are captured, so that capture is restored after a
+/// suspension.
+static MOUSE_CAPTURE: AtomicBool = AtomicBool::new(false);
+
 /// Sets up the terminal for TUI rendering.
 ///
 /// This function:
@@ -19,6 +30,8 @@ use ratatui::{Terminal, backend::CrosstermBackend};
 /// - Enters the alternate screen buffer
 /// - Hides the cursor
 ///
+/// The mouse is only captured once `set_mouse_capture` turns it on.
+///
 /// # Errors
 ///
 /// Returns an error if any terminal setup operation fails.
@@ -29,10 +42,30 @@ pub fn setup_terminal() -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
     Terminal::new(CrosstermBackend::new(stdout))
 }
 
+/// Starts or stops capturing mouse clicks, as the `mouse` setting says.
+///
+/// While the mouse is captured, the terminal only selects text with `Shift`
+/// held.
+///
+/// # Errors
+///
+/// Returns an error if writing to the terminal fails.
+pub fn set_mouse_capture(on: bool) -> io::Result<()> {
+    if MOUSE_CAPTURE.swap(on, Ordering::Relaxed) == on {
+        return Ok(());
+    }
+    if on {
+        execute!(io::stdout(), EnableMouseCapture)
+    } else {
+        execute!(io::stdout(), DisableMouseCapture)
+    }
+}
+
 /// Restores the terminal to its original state.
 ///
 /// This function:
 /// - Disables raw mode
+/// - Stops capturing the mouse, if it is captured
 /// - Leaves the alternate screen buffer
 /// - Shows the cursor
 ///
@@ -41,33 +74,180 @@ pub fn setup_terminal() -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
 /// Returns an error if any terminal restoration operation fails.
 pub fn restore_terminal() -> io::Result<()> {
     disable_raw_mode()?;
+    if MOUSE_CAPTURE.load(Ordering::Relaxed) {
+        execute!(io::stdout(), DisableMouseCapture)?;
+    }
     execute!(io::stdout(), LeaveAlternateScreen, Show)?;
     Ok(())
 }
 
+/// Asks for a passphrase on stderr and reads it without echoing it.
+///
+/// Called before the terminal is set up for rendering; raw mode is only
+/// enabled while the passphrase is typed.
+///
+/// # Errors
+///
+/// Returns an `Interrupted` error if the user presses Esc or Ctrl+C, or an
+/// error if the terminal cannot be read.
+#[cfg(feature = "encryption")]
+pub fn read_passphrase(prompt: &str) -> io::Result<String> {
+    use std::io::Write;
+
+    eprint!("{prompt}");
+    io::stderr().flush()?;
+    enable_raw_mode()?;
+    let passphrase = read_hidden_line();
+    disable_raw_mode()?;
+    eprintln!();
+    passphrase
+}
+
+/// Reads key presses up to Enter into a line, echoing nothing.
+#[cfg(feature = "encryption")]
+fn read_hidden_line() -> io::Result<String> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+
+    let mut line = String::new();
+    loop {
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Enter => return Ok(line),
+            KeyCode::Backspace => {
+                line.pop();
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+            }
+            KeyCode::Esc => return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled")),
+            KeyCode::Char(c) => line.push(c),
+            _ => {}
+        }
+    }
+}
+
+/// Suspends the process so the shell can take over the terminal (Ctrl+Z).
+///
+/// Because raw mode disables signal generation, Ctrl+Z arrives as a key event
+/// instead of `SIGTSTP`. This function:
+/// - Restores the terminal to its original state
+/// - Raises `SIGTSTP`, stopping the process until the shell sends `SIGCONT`
+/// - Re-enters raw mode and the alternate screen on resume
+/// - Clears the terminal so the next draw repaints the whole screen
+///
+/// On non-Unix platforms job control is unavailable and this is a no-op.
+///
+/// # Errors
+///
+/// Returns an error if restoring or re-initializing the terminal fails.
+pub fn suspend(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        restore_terminal()?;
+        signal_hook::low_level::raise(signal_hook::consts::SIGTSTP)?;
+        resume(terminal)?;
+    }
+    #[cfg(not(unix))]
+    let _ = terminal;
+    Ok(())
+}
+
+/// Copies `text` to the system clipboard through the terminal.
+///
+/// Uses the OSC 52 escape sequence, which works over SSH but is ignored by
+/// terminals that do not support it.
+///
+/// # Errors
+///
+/// Returns an error if writing to the terminal fails.
+pub fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    execute!(io::stdout(), CopyToClipboard::to_clipboard_from(text))
+}
+
+/// Programs that print the system clipboard, tried in order.
+///
+/// Terminals cannot be asked for the clipboard reliably, so it is read
+/// through the tool of the desktop: `pbpaste` on macOS, PowerShell on
+/// Windows, and `wl-paste`, `xclip` or `xsel` elsewhere.
+const CLIPBOARD_READERS: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+    &[("pbpaste", &[])]
+} else if cfg!(windows) {
+    &[("powershell", &["-NoProfile", "-Command", "Get-Clipboard"])]
+} else {
+    &[
+        ("wl-paste", &["--no-newline"]),
+        ("xclip", &["-selection", "clipboard", "-out"]),
+        ("xsel", &["--clipboard", "--output"]),
+    ]
+};
+
+/// Reads the text on the system clipboard.
+///
+/// # Errors
+///
+/// Returns a `NotFound` error if no clipboard program is installed, or the
+/// error of the last one that failed.
+pub fn read_clipboard() -> io::Result<String> {
+    let mut error = io::Error::new(
+        io::ErrorKind::NotFound,
+        "no clipboard program found (install wl-clipboard, xclip or xsel)",
+    );
+    for &(program, args) in CLIPBOARD_READERS {
+        match Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+        {
+            Ok(output) if output.status.success() => {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+            }
+            Ok(output) => {
+                error = io::Error::other(format!("{program} failed with {}", output.status));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+/// Re-enters raw mode and the alternate screen after a suspension, and
+/// captures the mouse again if it was captured.
+///
+/// # Errors
+///
+/// Returns an error if any terminal setup operation fails.
+#[cfg(unix)]
+fn resume(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+    if MOUSE_CAPTURE.load(Ordering::Relaxed) {
+        execute!(io::stdout(), EnableMouseCapture)?;
+    }
+    terminal.clear()
+}
+
 /// Installs a panic hook that restores the terminal before panicking.
 ///
 /// This ensures that even if the application panics, the terminal is left
 /// in a usable state. Without this, a panic would leave the terminal in
 /// raw mode with the cursor hidden.
+///
+/// The hook also writes a crash report with the panic message, a backtrace,
+/// and the last recorded buffer contents to the recovery file, so the buffer
+/// can be restored on the next launch.
 pub fn install_panic_hook() {
     let original_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |panic_info| {
         // Attempt to restore terminal; ignore errors since we're already panicking
         let _ = restore_terminal();
+        let backtrace = Backtrace::force_capture();
+        let _ = recovery::write_crash_report(&panic_info.to_string(), &backtrace.to_string());
         original_hook(panic_info);
     }));
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_install_panic_hook_does_not_panic() {
-        // This test verifies that installing the panic hook doesn't cause issues.
-        // We can't easily test the actual panic behavior without triggering a panic,
-        // but we can verify the hook installation succeeds.
-        install_panic_hook();
-    }
-}
@@ -0,0 +1,75 @@
+//! ASCII fallback for terminals and fonts that draw box-drawing characters,
+//! arrows and markers poorly.
+//!
+//! The frame is drawn as usual and then rewritten cell by cell, so every
+//! panel and popup falls back the same way.
+
+use ratatui::buffer::Buffer;
+use ratatui::style::Color;
+
+/// The symbols the interface draws and their ASCII replacements.
+const REPLACEMENTS: &[(&str, &str)] = &[
+    ("─", "-"),
+    ("━", "="),
+    ("│", "|"),
+    ("┃", "|"),
+    ("╭", "+"),
+    ("╮", "+"),
+    ("╰", "+"),
+    ("╯", "+"),
+    ("┌", "+"),
+    ("┐", "+"),
+    ("└", "+"),
+    ("┘", "+"),
+    ("█", "#"),
+    ("●", "*"),
+    ("▲", "!"),
+    ("≈", "~"),
+    ("↑", "^"),
+    ("↓", "v"),
+    ("←", "<"),
+    ("→", ">"),
+    ("🦀", " "),
+];
+
+/// Returns the ASCII replacement of `symbol`, or `None` if it has none.
+fn replacement(symbol: &str) -> Option<&'static str> {
+    REPLACEMENTS
+        .iter()
+        .find(|(from, _)| *from == symbol)
+        .map(|(_, to)| *to)
+}
+
+/// Replaces the interface's symbols in `buffer` with ASCII and removes
+/// background highlights.
+pub fn to_ascii(buffer: &mut Buffer) {
+    for cell in &mut buffer.content {
+        if let Some(ascii) = replacement(cell.symbol()) {
+            cell.set_symbol(ascii);
+        }
+        cell.bg = Color::Reset;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Rect;
+    use ratatui::style::Style;
+
+    #[test]
+    fn test_to_ascii_replaces_symbols_and_backgrounds() {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 8, 1));
+        buffer.set_string(0, 0, "╭─● é↑", Style::default().bg(Color::DarkGray));
+
+        to_ascii(&mut buffer);
+
+        let text: String = buffer
+            .content
+            .iter()
+            .map(ratatui::buffer::Cell::symbol)
+            .collect();
+        assert_eq!(text, "+-* é^  ");
+        assert!(buffer.content.iter().all(|cell| cell.bg == Color::Reset));
+    }
+}
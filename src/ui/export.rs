@@ -0,0 +1,125 @@
+//! Plain-text export of the worksheet for sharing.
+//!
+//! Each line is written as `expression  =>  result`, with the arrows lined up
+//! so that the block reads well in a monospace font, such as a code block in
+//! a chat message or an issue.
+
+use crate::eval::LineResult;
+use crate::ui::render::{NumberFormat, format_result, format_table};
+
+/// Separator between a line and its result.
+const RESULT_ARROW: &str = "  =>  ";
+
+/// Formats the buffer `lines` with their `results` as a shareable text block.
+///
+/// Lines without a result, such as comments and section markers, are copied
+/// as they are. Errors are written in place of the result, and the rows of a
+/// `table` are indented below it. Trailing blank lines are left out.
+#[must_use]
+pub fn share_text(lines: &[String], results: &[LineResult]) -> String {
+    let texts: Vec<Vec<String>> = lines
+        .iter()
+        .enumerate()
+        .map(|(i, _)| results.get(i).map_or_else(Vec::new, result_texts))
+        .collect();
+    let width = lines
+        .iter()
+        .zip(&texts)
+        .filter(|(_, texts)| !texts.is_empty())
+        .map(|(line, _)| line.trim_end().chars().count())
+        .max()
+        .unwrap_or(0);
+    let indent = " ".repeat(width + RESULT_ARROW.len());
+
+    let mut output: Vec<String> = Vec::with_capacity(lines.len());
+    for (line, texts) in lines.iter().zip(&texts) {
+        let line = line.trim_end();
+        match texts.split_first() {
+            None => output.push(line.to_string()),
+            Some((first, rows)) => {
+                output.push(format!("{line:<width$}{RESULT_ARROW}{first}"));
+                output.extend(rows.iter().map(|row| format!("{indent}{row}")));
+            }
+        }
+    }
+    while output.last().is_some_and(String::is_empty) {
+        output.pop();
+    }
+    output.join("\n")
+}
+
+/// Returns the result of each line as the text of a result comment in the
+/// buffer file, `None` for lines without a result.
+///
+/// Tables are shown by their first row and errors by their message, so that
+/// every comment fits on its line.
+#[must_use]
+pub fn result_comments(results: &[LineResult]) -> Vec<Option<String>> {
+    results
+        .iter()
+        .map(|result| result_texts(result).into_iter().next())
+        .collect()
+}
+
+/// Returns the result text of a line, one entry per output row.
+fn result_texts(result: &LineResult) -> Vec<String> {
+    match result {
+        LineResult::Table(table) => format_table(table, NumberFormat::default()),
+        LineResult::Error(err) => vec![format!("error[{}]: {}", err.code(), err.message())],
+        result => format_result(result).into_iter().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::evaluate_all_lines;
+
+    #[test]
+    fn test_result_comments() {
+        let lines = ["price = 40", "", "# note", "price * 2", "oops"];
+        let results = evaluate_all_lines(lines.iter().copied());
+
+        assert_eq!(
+            result_comments(&results),
+            [
+                Some("price = 40".to_string()),
+                None,
+                None,
+                Some("80".to_string()),
+                Some("error[E001]: undefined variable 'oops'".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_share_text_aligns_results() {
+        let lines: Vec<String> = ["price = 40", "", "# note", "price * 1.2", "oops + 1", ""]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let results = evaluate_all_lines(lines.iter().map(String::as_str));
+
+        assert_eq!(
+            share_text(&lines, &results),
+            "price = 40   =>  price = 40\n\
+             \n\
+             # note\n\
+             price * 1.2  =>  48\n\
+             oops + 1     =>  error[E001]: undefined variable 'oops'"
+        );
+    }
+
+    #[test]
+    fn test_share_text_indents_table_rows() {
+        let lines = vec!["table(x, x, 1, 2)".to_string()];
+        let results = evaluate_all_lines(lines.iter().map(String::as_str));
+
+        assert_eq!(
+            share_text(&lines, &results),
+            "table(x, x, 1, 2)  =>  x │ x\n\
+             \x20                      1 │ 1\n\
+             \x20                      2 │ 2"
+        );
+    }
+}
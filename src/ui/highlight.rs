@@ -8,12 +8,28 @@
 //! - Parentheses: default color
 //! - Functions: cyan color (like variables)
 //! - Whitespace: default color
+//! - Text literals: green
+//! - Invalid input: red
+//!
+//! Occurrences of the variable under the cursor are additionally shown in
+//! bold and underlined, and parentheses can be colored by nesting depth using
+//! a theme palette.
+
+use std::cell::RefCell;
+use std::rc::Rc;
 
 use ratatui::{
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::Span,
 };
 
+use crate::editor::{Block, Selection, floor_boundary};
+use crate::eval::cache::{LINE_CACHE_CAPACITY, LineCache};
+use crate::eval::constants::MATH_CONSTANTS;
+use crate::eval::directive::{DIRECTIVE_SEPARATOR, FormatPresets, split_format_directive};
+use crate::eval::functions::is_builtin_function;
+use crate::eval::token::{Token as EvalToken, TokenOptions, Tokenizer};
+
 /// Token types for syntax highlighting.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TokenType {
@@ -29,6 +45,10 @@ pub enum TokenType {
     Whitespace,
     /// Function names (sqrt, sin, cos, etc.)
     Function,
+    /// Quoted text literals such as unit names
+    Text,
+    /// Text the evaluator cannot tokenize
+    Invalid,
 }
 
 /// A token with its type and text content.
@@ -51,18 +71,36 @@ impl Token {
     }
 }
 
-/// Known function names that should be highlighted as functions.
-const KNOWN_FUNCTIONS: &[&str] = &[
-    "sqrt", "sin", "cos", "tan", "asin", "acos", "atan", "sinh", "cosh", "tanh", "asinh", "acosh",
-    "atanh", "ln", "log", "log2", "log10", "exp", "exp2", "floor", "ceil", "round", "abs", "min",
-    "max", "pow",
-];
-
-/// Known constants that should be highlighted as numbers.
-const KNOWN_CONSTANTS: &[&str] = &["pi", "e"];
+/// Optional emphasis applied on top of syntax highlighting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HighlightOptions<'a> {
+    /// Variable whose occurrences are shown bold and underlined.
+    pub symbol: Option<&'a str>,
+    /// Colors cycled through by parenthesis nesting depth; `None` disables bracket coloring.
+    pub bracket_colors: Option<&'a [Color]>,
+    /// Selected part of a line, shown reversed in the input panel.
+    pub selection: Option<Selection>,
+    /// Block of lines edited at once, shown reversed in the input panel.
+    pub block: Option<Block>,
+    /// Whether tokens keep the terminal's default colors, for high contrast.
+    pub plain: bool,
+    /// How number literals are read; `None` reads them with the defaults.
+    pub token_options: Option<&'a TokenOptions>,
+    /// Format presets a trailing directive can name besides the built-in
+    /// formats.
+    pub format_presets: Option<&'a FormatPresets>,
+}
 
 /// Tokenizes an expression string into tokens for syntax highlighting.
 ///
+/// Uses the evaluator's tokenizer so that colors always match evaluation
+/// semantics, and fills the gaps between its tokens so that the returned
+/// token texts concatenate back to `input`. Whitespace becomes
+/// `TokenType::Whitespace`; text the evaluator cannot tokenize becomes
+/// `TokenType::Invalid`, and tokenizing resumes after it. A trailing
+/// formatting directive such as `| hex` is shown as an operator followed by
+/// a function name.
+///
 /// # Arguments
 /// * `input` - The expression string to tokenize
 ///
@@ -70,81 +108,136 @@ const KNOWN_CONSTANTS: &[&str] = &["pi", "e"];
 /// A vector of tokens representing the expression.
 #[must_use]
 pub fn tokenize(input: &str) -> Vec<Token> {
+    tokenize_cached(input, &TokenOptions::default(), &FormatPresets::default()).to_vec()
+}
+
+/// Appends the tokens of a trailing formatting directive such as ` | hex`:
+/// the separator as an operator and the name as a function.
+fn tokenize_directive(text: &str, tokens: &mut Vec<Token>) {
+    let (before, name) = text.split_once(DIRECTIVE_SEPARATOR).unwrap_or((text, ""));
+    push_whitespace(tokens, before);
+    tokens.push(Token::new(
+        TokenType::Operator,
+        DIRECTIVE_SEPARATOR.to_string(),
+    ));
+    let trimmed = name.trim_start();
+    push_whitespace(tokens, &name[..name.len() - trimmed.len()]);
+    let name = trimmed.trim_end();
+    tokens.push(Token::new(TokenType::Function, name));
+    push_whitespace(tokens, &trimmed[name.len()..]);
+}
+
+/// Splits an expression into tokens, marking text that cannot be tokenized
+/// as invalid.
+fn tokenize_expression(input: &str, options: &TokenOptions) -> Vec<Token> {
     let mut tokens = Vec::new();
-    let chars: Vec<char> = input.chars().collect();
-    let mut i = 0;
+    let mut offset = 0;
 
-    while i < chars.len() {
-        let c = chars[i];
+    while offset < input.len() {
+        let rest = &input[offset..];
+        let (spanned, error) = Tokenizer::with_options(rest, options).tokenize_partial();
 
-        if c.is_whitespace() {
-            let start = i;
-            while i < chars.len() && chars[i].is_whitespace() {
-                i += 1;
-            }
-            tokens.push(Token::new(
-                TokenType::Whitespace,
-                chars[start..i].iter().collect::<String>(),
-            ));
-        } else if c.is_ascii_digit()
-            || (c == '.' && i + 1 < chars.len() && chars[i + 1].is_ascii_digit())
-        {
-            let start = i;
-            while i < chars.len()
-                && (chars[i].is_ascii_digit()
-                    || chars[i] == '.'
-                    || chars[i] == 'e'
-                    || chars[i] == 'E'
-                    || ((chars[i] == '+' || chars[i] == '-')
-                        && i > 0
-                        && (chars[i - 1] == 'e' || chars[i - 1] == 'E')))
-            {
-                i += 1;
-            }
+        let mut pos = 0;
+        for (token, span) in spanned {
+            push_whitespace(&mut tokens, &rest[pos..span.start]);
             tokens.push(Token::new(
-                TokenType::Number,
-                chars[start..i].iter().collect::<String>(),
+                classify_token(&token),
+                &rest[span.start..span.end],
             ));
-        } else if c.is_alphabetic() || c == '_' {
-            let start = i;
-            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
-                i += 1;
-            }
-            let text: String = chars[start..i].iter().collect();
-            let token_type = classify_identifier(&text);
-            tokens.push(Token::new(token_type, text));
-        } else if is_operator(c) {
-            tokens.push(Token::new(TokenType::Operator, c.to_string()));
-            i += 1;
-        } else if c == '(' || c == ')' {
-            tokens.push(Token::new(TokenType::Parenthesis, c.to_string()));
-            i += 1;
-        } else {
-            tokens.push(Token::new(TokenType::Operator, c.to_string()));
-            i += 1;
+            pos = span.end;
         }
+
+        let Some(error) = error else {
+            push_whitespace(&mut tokens, &rest[pos..]);
+            break;
+        };
+
+        // Skip the whitespace the tokenizer consumed before failing, then
+        // mark everything up to and including the offending character.
+        let invalid_start = pos + (rest[pos..].len() - rest[pos..].trim_start().len());
+        push_whitespace(&mut tokens, &rest[pos..invalid_start]);
+        let error_pos = error.position.clamp(invalid_start, rest.len());
+        let invalid_end = rest[error_pos..]
+            .chars()
+            .next()
+            .map_or(rest.len(), |c| error_pos + c.len_utf8());
+        tokens.push(Token::new(
+            TokenType::Invalid,
+            &rest[invalid_start..invalid_end],
+        ));
+        offset += invalid_end;
     }
 
     tokens
 }
 
+thread_local! {
+    /// Highlighting tokens by expression text, so unchanged lines are not
+    /// tokenized again.
+    static LINE_TOKENS: RefCell<LineCache<TokenOptions, Rc<[Token]>>> =
+        RefCell::new(LineCache::new(LINE_CACHE_CAPACITY));
+}
+
+/// Like `tokenize`, reading number literals as `options` say and
+/// directives naming one of the `presets`, and reusing the tokens of an
+/// expression tokenized before.
+fn tokenize_cached(line: &str, options: &TokenOptions, presets: &FormatPresets) -> Rc<[Token]> {
+    let (expression, directive) = split_format_directive(line, presets);
+    let tokens = LINE_TOKENS.with_borrow_mut(|cache| {
+        cache.get_or_insert_with(expression, options, |expression| {
+            tokenize_expression(expression, options).into()
+        })
+    });
+    if directive.is_none() {
+        return tokens;
+    }
+    let mut tokens = tokens.to_vec();
+    tokenize_directive(&line[expression.len()..], &mut tokens);
+    tokens.into()
+}
+
+/// Appends `text` as a whitespace token unless it is empty.
+fn push_whitespace(tokens: &mut Vec<Token>, text: &str) {
+    if !text.is_empty() {
+        tokens.push(Token::new(TokenType::Whitespace, text));
+    }
+}
+
+/// Maps an evaluator token to its highlighting category.
+fn classify_token(token: &EvalToken) -> TokenType {
+    match token {
+        EvalToken::Number(_)
+        | EvalToken::Duration(_)
+        | EvalToken::Size { .. }
+        | EvalToken::Quantity { .. }
+        | EvalToken::Money { .. } => TokenType::Number,
+        EvalToken::Text(_) | EvalToken::Command(_) => TokenType::Text,
+        EvalToken::Identifier(name) => classify_identifier(name),
+        EvalToken::LParen | EvalToken::RParen => TokenType::Parenthesis,
+        EvalToken::Plus
+        | EvalToken::Minus
+        | EvalToken::Star
+        | EvalToken::Slash
+        | EvalToken::DoubleSlash
+        | EvalToken::Percent
+        | EvalToken::Caret
+        | EvalToken::Comma
+        | EvalToken::Equals
+        | EvalToken::Exclaim => TokenType::Operator,
+    }
+}
+
 /// Classifies an identifier as either a function, constant (number), or variable.
 fn classify_identifier(text: &str) -> TokenType {
-    let lower = text.to_lowercase();
-    if KNOWN_FUNCTIONS.contains(&lower.as_str()) {
+    if is_builtin_function(text) {
         TokenType::Function
-    } else if KNOWN_CONSTANTS.contains(&lower.as_str()) {
+    } else if MATH_CONSTANTS.iter().any(|&(name, _)| name == text) {
         TokenType::Number
     } else {
         TokenType::Variable
     }
 }
 
-/// Checks if a character is an operator.
-const fn is_operator(c: char) -> bool {
-    matches!(c, '+' | '-' | '*' | '/' | '%' | '^' | '=')
-}
-
 /// Returns the style for a given token type.
 ///
 /// Colors:
@@ -154,15 +247,67 @@ const fn is_operator(c: char) -> bool {
 /// - Parentheses: Default
 /// - Functions: Cyan (like variables)
 /// - Whitespace: Default
+/// - Text: Green
+/// - Invalid: Red
 #[must_use]
 pub fn token_style(token_type: &TokenType) -> Style {
     match token_type {
         TokenType::Variable | TokenType::Function => Style::default().fg(Color::Cyan),
         TokenType::Number | TokenType::Parenthesis | TokenType::Whitespace => Style::default(),
         TokenType::Operator => Style::default().fg(Color::Gray),
+        TokenType::Text => Style::default().fg(Color::Green),
+        TokenType::Invalid => Style::default().fg(Color::Red),
     }
 }
 
+/// Returns the style added to occurrences of the variable under the cursor.
+#[must_use]
+pub fn symbol_highlight_style() -> Style {
+    Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+}
+
+/// Returns the depth color for a parenthesis token and updates `depth`.
+///
+/// Opening and closing parentheses of the same pair share a color. A closing
+/// parenthesis without a match, or an empty palette, yields `None`.
+fn bracket_color(text: &str, depth: &mut usize, colors: &[Color]) -> Option<Color> {
+    if colors.is_empty() {
+        return None;
+    }
+    match text {
+        "(" => {
+            let color = colors[*depth % colors.len()];
+            *depth += 1;
+            Some(color)
+        }
+        ")" if *depth > 0 => {
+            *depth -= 1;
+            Some(colors[*depth % colors.len()])
+        }
+        _ => None,
+    }
+}
+
+/// Returns the variable name at the given column, if any.
+///
+/// A column directly after the name also counts, so the variable stays
+/// highlighted while the cursor sits at its end.
+#[must_use]
+pub fn variable_at(line: &str, col: usize) -> Option<&str> {
+    let mut pos = 0;
+    for token in tokenize(line) {
+        let end = pos + token.text.len();
+        if token.token_type == TokenType::Variable && pos <= col && col <= end {
+            return line.get(pos..end);
+        }
+        if end > col {
+            return None;
+        }
+        pos = end;
+    }
+    None
+}
+
 /// Converts a line of text into styled spans with syntax highlighting.
 ///
 /// # Arguments
@@ -172,12 +317,12 @@ pub fn token_style(token_type: &TokenType) -> Style {
 /// A vector of styled spans representing the highlighted line.
 #[must_use]
 pub fn highlight_line(line: &str) -> Vec<Span<'_>> {
-    let tokens = tokenize(line);
+    let tokens = tokenize_cached(line, &TokenOptions::default(), &FormatPresets::default());
 
     let mut spans = Vec::new();
     let mut pos = 0;
 
-    for token in tokens {
+    for token in tokens.iter() {
         let len = token.text.len();
         if pos + len <= line.len() {
             let style = token_style(&token.token_type);
@@ -207,19 +352,59 @@ pub fn highlight_line_with_offset(
     horizontal_offset: usize,
     visible_width: usize,
 ) -> Vec<Span<'_>> {
+    highlight_line_with_options(
+        line,
+        horizontal_offset,
+        visible_width,
+        HighlightOptions::default(),
+    )
+}
+
+/// Like `highlight_line_with_offset`, additionally applying `options`.
+///
+/// Bracket depth is tracked from the start of the line, so parentheses keep
+/// their color when the line is scrolled horizontally.
+///
+/// # Arguments
+/// * `line` - The full line of text to highlight
+/// * `horizontal_offset` - The first visible column index (0-based)
+/// * `visible_width` - The number of visible columns
+/// * `options` - Symbol emphasis and bracket coloring to apply
+///
+/// # Returns
+/// A vector of styled spans representing the visible portion of the highlighted line.
+#[must_use]
+pub fn highlight_line_with_options<'a>(
+    line: &'a str,
+    horizontal_offset: usize,
+    visible_width: usize,
+    options: HighlightOptions<'_>,
+) -> Vec<Span<'a>> {
     if horizontal_offset >= line.len() {
         return vec![];
     }
 
-    let tokens = tokenize(line);
+    let (default_options, default_presets) = (TokenOptions::default(), FormatPresets::default());
+    let tokens = tokenize_cached(
+        line,
+        options.token_options.unwrap_or(&default_options),
+        options.format_presets.unwrap_or(&default_presets),
+    );
 
     let mut spans = Vec::new();
     let mut pos = 0;
-    let visible_end = (horizontal_offset + visible_width).min(line.len());
+    // Columns are bytes; edges inside a character move back to its start.
+    let visible_end = floor_boundary(line, horizontal_offset + visible_width);
+    let horizontal_offset = floor_boundary(line, horizontal_offset);
+
+    let mut depth = 0_usize;
 
-    for token in tokens {
+    for token in tokens.iter() {
         let token_start = pos;
         let token_end = pos + token.text.len();
+        let bracket_color = options
+            .bracket_colors
+            .and_then(|colors| bracket_color(&token.text, &mut depth, colors));
 
         if token_end <= horizontal_offset {
             pos = token_end;
@@ -234,7 +419,17 @@ pub fn highlight_line_with_offset(
         let visible_token_end = token_end.min(visible_end);
 
         if visible_start < visible_token_end && visible_token_end <= line.len() {
-            let style = token_style(&token.token_type);
+            let mut style = if options.plain {
+                Style::default()
+            } else {
+                token_style(&token.token_type)
+            };
+            if token.token_type == TokenType::Variable && options.symbol == Some(&token.text) {
+                style = style.patch(symbol_highlight_style());
+            }
+            if let Some(color) = bracket_color {
+                style = style.fg(color);
+            }
             spans.push(Span::styled(&line[visible_start..visible_token_end], style));
         }
 
@@ -248,6 +443,98 @@ pub fn highlight_line_with_offset(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_variable_at_inside_and_after_name() {
+        assert_eq!(variable_at("total + tax", 2), Some("total"));
+        assert_eq!(variable_at("total + tax", 5), Some("total"));
+        assert_eq!(variable_at("total + tax", 11), Some("tax"));
+    }
+
+    #[test]
+    fn test_variable_at_ignores_numbers_and_functions() {
+        assert_eq!(variable_at("12 + sqrt(4)", 1), None);
+        assert_eq!(variable_at("12 + sqrt(4)", 6), None);
+        assert_eq!(variable_at("", 0), None);
+    }
+
+    #[test]
+    fn test_highlight_line_with_symbol_emphasizes_matches_only() {
+        let options = HighlightOptions {
+            symbol: Some("a"),
+            ..HighlightOptions::default()
+        };
+        let spans = highlight_line_with_options("a + ab + a", 0, 80, options);
+
+        let emphasized: Vec<&str> = spans
+            .iter()
+            .filter(|span| span.style.add_modifier.contains(Modifier::UNDERLINED))
+            .map(|span| span.content.as_ref())
+            .collect();
+        assert_eq!(emphasized, ["a", "a"]);
+    }
+
+    #[test]
+    fn test_highlight_line_plain_uses_default_colors() {
+        let options = HighlightOptions {
+            plain: true,
+            ..HighlightOptions::default()
+        };
+        let spans = highlight_line_with_options("x = sqrt(16) + 2", 0, 80, options);
+        assert!(spans.iter().all(|span| span.style.fg.is_none()));
+    }
+
+    fn paren_colors(line: &str, horizontal_offset: usize) -> Vec<Option<Color>> {
+        let options = HighlightOptions {
+            bracket_colors: Some(&[Color::Red, Color::Green]),
+            ..HighlightOptions::default()
+        };
+        highlight_line_with_options(line, horizontal_offset, 80, options)
+            .iter()
+            .filter(|span| span.content == "(" || span.content == ")")
+            .map(|span| span.style.fg)
+            .collect()
+    }
+
+    #[test]
+    fn test_bracket_colors_cycle_by_depth() {
+        assert_eq!(
+            paren_colors("((a)(b))", 0),
+            [
+                Some(Color::Red),
+                Some(Color::Green),
+                Some(Color::Green),
+                Some(Color::Green),
+                Some(Color::Green),
+                Some(Color::Red),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bracket_colors_wrap_around_palette() {
+        assert_eq!(paren_colors("(((", 0)[2], Some(Color::Red));
+    }
+
+    #[test]
+    fn test_bracket_colors_track_depth_before_scroll_offset() {
+        assert_eq!(
+            paren_colors("((1))", 2),
+            [Some(Color::Green), Some(Color::Red)]
+        );
+    }
+
+    #[test]
+    fn test_unmatched_closing_paren_keeps_default_style() {
+        let default_fg = token_style(&TokenType::Parenthesis).fg;
+        assert_eq!(paren_colors(")", 0), [default_fg]);
+    }
+
+    #[test]
+    fn test_bracket_colors_disabled_by_default() {
+        let spans = highlight_line_with_offset("(1)", 0, 80);
+        assert_eq!(spans[0].style, token_style(&TokenType::Parenthesis));
+    }
+
     #[test]
     fn test_tokenize_simple_number() {
         let tokens = tokenize("42");
@@ -256,6 +543,25 @@ mod tests {
         assert_eq!(tokens[0].text, "42");
     }
 
+    #[test]
+    fn test_tokenize_format_directive() {
+        let tokens = tokenize("255 |  hex ");
+        let types: Vec<_> = tokens.iter().map(|t| t.token_type.clone()).collect();
+        assert_eq!(
+            types,
+            [
+                TokenType::Number,
+                TokenType::Whitespace,
+                TokenType::Operator,
+                TokenType::Whitespace,
+                TokenType::Function,
+                TokenType::Whitespace,
+            ]
+        );
+        let text: String = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(text, "255 |  hex ");
+    }
+
     #[test]
     fn test_tokenize_decimal_number() {
         let tokens = tokenize("3.14");
@@ -451,6 +757,85 @@ mod tests {
         assert!(tokens.is_empty());
     }
 
+    #[test]
+    fn test_tokenize_highlights_every_registered_function() {
+        for f in crate::eval::functions::BUILTIN_FUNCTIONS {
+            assert_eq!(
+                tokenize(f.name)[0].token_type,
+                TokenType::Function,
+                "{} should be highlighted as a function",
+                f.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_tokenize_text_literal_includes_quotes() {
+        let tokens = tokenize("convert(5, \"mi\", \"km\")");
+        assert_eq!(tokens[5], Token::new(TokenType::Text, "\"mi\""));
+        assert_eq!(token_style(&TokenType::Text).fg, Some(Color::Green));
+    }
+
+    #[test]
+    fn test_tokenize_hex_literal_is_single_number() {
+        let tokens = tokenize("0xff + 1");
+        assert_eq!(tokens[0], Token::new(TokenType::Number, "0xff"));
+    }
+
+    #[test]
+    fn test_tokenize_factorial_and_comma_are_operators() {
+        let tokens = tokenize("max(3!,2)");
+        let types: Vec<&TokenType> = tokens.iter().map(|t| &t.token_type).collect();
+        assert_eq!(
+            types,
+            [
+                &TokenType::Function,
+                &TokenType::Parenthesis,
+                &TokenType::Number,
+                &TokenType::Operator,
+                &TokenType::Operator,
+                &TokenType::Number,
+                &TokenType::Parenthesis,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_constants_are_case_sensitive() {
+        assert_eq!(tokenize("tau")[0].token_type, TokenType::Number);
+        assert_eq!(tokenize("PI")[0].token_type, TokenType::Variable);
+    }
+
+    #[test]
+    fn test_tokenize_invalid_character_resumes_after_it() {
+        let tokens = tokenize("1 $ x");
+        assert_eq!(
+            tokens,
+            [
+                Token::new(TokenType::Number, "1"),
+                Token::new(TokenType::Whitespace, " "),
+                Token::new(TokenType::Invalid, "$"),
+                Token::new(TokenType::Whitespace, " "),
+                Token::new(TokenType::Variable, "x"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_preserves_input_text() {
+        for input in [
+            "  2e-3 *\tfoo ",
+            "0b101 + .5",
+            "1e",
+            "@section a",
+            "é + 1",
+            "((",
+        ] {
+            let joined: String = tokenize(input).into_iter().map(|t| t.text).collect();
+            assert_eq!(joined, input);
+        }
+    }
+
     #[test]
     fn test_tokenize_negative_number_context() {
         // In an expression like "5 + -3", the - is an operator followed by a number
@@ -607,6 +992,16 @@ mod tests {
         assert_eq!(reconstructed, "= 10");
     }
 
+    #[test]
+    fn test_highlight_line_with_offset_inside_multibyte_character() {
+        let line = "# préférences";
+        let reconstructed: String = highlight_line_with_offset(line, 5, 3)
+            .iter()
+            .map(|s| s.content.as_ref())
+            .collect();
+        assert_eq!(reconstructed, "éf");
+    }
+
     #[test]
     fn test_highlight_line_with_offset_partial_token_at_end() {
         // Line: "x = 10" - visible width cuts off part of the line
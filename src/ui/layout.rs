@@ -44,20 +44,70 @@ pub fn create_main_layout(area: Rect) -> LayoutAreas {
     }
 }
 
-/// Creates the horizontal panel layout with 80/20 split.
+/// Maximum number of pinned expressions shown in the watch panel.
+pub const MAX_WATCH_ROWS: usize = 6;
+
+/// Splits the content area into the editing panels and the watch panel.
+///
+/// The watch panel sits below the panels with a 1-row title border plus one
+/// row per pinned expression, up to `MAX_WATCH_ROWS`. With nothing pinned it
+/// is omitted and the panels keep the whole content area.
+///
+/// # Returns
+/// The area for the input and memory panels, and the watch panel area if any.
+#[must_use]
+pub fn split_watch_area(content_area: Rect, watch_count: usize) -> (Rect, Option<Rect>) {
+    if watch_count == 0 {
+        return (content_area, None);
+    }
+    let rows = u16::try_from(watch_count.min(MAX_WATCH_ROWS)).unwrap_or(0) + 1;
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(rows)])
+        .split(content_area);
+    (chunks[0], Some(chunks[1]))
+}
+
+/// Width of the overview strip beside the memory pane.
+pub const OVERVIEW_WIDTH: u16 = 1;
+
+/// Splits the overview strip off the right edge of the memory pane.
+///
+/// # Returns
+/// The remaining memory pane area and the overview strip area.
+#[must_use]
+pub fn split_overview_strip(memory_area: Rect) -> (Rect, Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(OVERVIEW_WIDTH)])
+        .split(memory_area);
+    (chunks[0], chunks[1])
+}
+
+/// Default share of the width, in percent, taken by the memory pane.
+pub const DEFAULT_MEMORY_PERCENT: u16 = 20;
+
+/// Smallest and largest share of the width, in percent, the memory pane may
+/// be given with the `ratio` setting.
+pub const MEMORY_PERCENT_RANGE: std::ops::RangeInclusive<u16> = 10..=60;
+
+/// Creates the horizontal panel layout, by default with an 80/20 split.
 ///
 /// The layout divides the content area into two panels:
-/// - Input/expression area (80%)
-/// - Memory/results area (20%)
+/// - Input/expression area (the rest)
+/// - Memory/results area (`memory_percent`)
 ///
 /// # Arguments
 /// * `memory_pane_left` - When true, memory pane is on left (20%/80%); when false, on right (80%/20%)
+/// * `memory_percent` - The share of the width taken by the memory pane
 #[must_use]
-pub fn create_panel_layout(memory_pane_left: bool) -> Layout {
+pub fn create_panel_layout(memory_pane_left: bool, memory_percent: u16) -> Layout {
+    let memory = Constraint::Percentage(memory_percent);
+    let input = Constraint::Percentage(100 - memory_percent.min(100));
     let constraints = if memory_pane_left {
-        [Constraint::Percentage(20), Constraint::Percentage(80)]
+        [memory, input]
     } else {
-        [Constraint::Percentage(80), Constraint::Percentage(20)]
+        [input, memory]
     };
     Layout::default()
         .direction(Direction::Horizontal)
@@ -68,6 +118,14 @@ pub fn create_panel_layout(memory_pane_left: bool) -> Layout {
 mod tests {
     use super::*;
 
+    #[test]
+    fn overview_strip_is_right_of_memory_pane() {
+        let (memory, strip) = split_overview_strip(Rect::new(0, 0, 20, 30));
+
+        assert_eq!(memory, Rect::new(0, 0, 19, 30));
+        assert_eq!(strip, Rect::new(19, 0, 1, 30));
+    }
+
     #[test]
     fn main_layout_creates_two_areas() {
         let area = Rect::new(0, 0, 100, 50);
@@ -130,9 +188,28 @@ mod tests {
         assert_eq!(areas.command_bar.x, 0, "Command bar should start at x=0");
     }
 
+    #[test]
+    fn watch_area_omitted_without_watches() {
+        let area = Rect::new(0, 0, 100, 48);
+
+        assert_eq!(split_watch_area(area, 0), (area, None));
+    }
+
+    #[test]
+    fn watch_area_below_panels_with_capped_height() {
+        let area = Rect::new(0, 0, 100, 48);
+
+        let (panels, watch) = split_watch_area(area, 2);
+        assert_eq!(panels.height, 45);
+        assert_eq!(watch, Some(Rect::new(0, 45, 100, 3)));
+
+        let (_, watch) = split_watch_area(area, 20);
+        assert_eq!(watch.map(|w| w.height), Some(7));
+    }
+
     #[test]
     fn panel_layout_creates_two_chunks() {
-        let layout = create_panel_layout(false);
+        let layout = create_panel_layout(false, DEFAULT_MEMORY_PERCENT);
         let area = Rect::new(0, 0, 100, 49);
         let chunks = layout.split(area);
 
@@ -141,7 +218,7 @@ mod tests {
 
     #[test]
     fn panel_layout_splits_80_20_memory_right() {
-        let layout = create_panel_layout(false);
+        let layout = create_panel_layout(false, DEFAULT_MEMORY_PERCENT);
         let area = Rect::new(0, 0, 100, 49);
         let chunks = layout.split(area);
 
@@ -153,7 +230,7 @@ mod tests {
 
     #[test]
     fn panel_layout_splits_20_80_memory_left() {
-        let layout = create_panel_layout(true);
+        let layout = create_panel_layout(true, DEFAULT_MEMORY_PERCENT);
         let area = Rect::new(0, 0, 100, 49);
         let chunks = layout.split(area);
 
@@ -163,9 +240,18 @@ mod tests {
         assert_eq!(chunks[1].width, 80, "Input panel should be 80% width");
     }
 
+    #[test]
+    fn panel_layout_follows_memory_percent() {
+        let area = Rect::new(0, 0, 100, 49);
+        let chunks = create_panel_layout(false, 35).split(area);
+
+        assert_eq!(chunks[0].width, 65);
+        assert_eq!(chunks[1].width, 35);
+    }
+
     #[test]
     fn panel_layout_preserves_height() {
-        let layout = create_panel_layout(false);
+        let layout = create_panel_layout(false, DEFAULT_MEMORY_PERCENT);
         let area = Rect::new(0, 0, 100, 49);
         let chunks = layout.split(area);
 
@@ -175,7 +261,7 @@ mod tests {
 
     #[test]
     fn panel_layout_is_horizontal() {
-        let layout = create_panel_layout(false);
+        let layout = create_panel_layout(false, DEFAULT_MEMORY_PERCENT);
         let area = Rect::new(0, 0, 100, 49);
         let chunks = layout.split(area);
 
@@ -2,26 +2,121 @@
 //!
 //! Contains layout management and panel rendering functionality.
 
+mod ascii;
+mod export;
 mod highlight;
 mod layout;
 mod render;
+mod rows;
 mod theme;
 
+pub use export::{result_comments, share_text};
+pub use rows::{ErrorPlacement, Hit, RowMap, ScreenMap};
 pub use theme::AppTheme;
 
-pub use highlight::{Token, TokenType, highlight_line, token_style, tokenize};
+pub use highlight::{
+    HighlightOptions, Token, TokenType, highlight_line, highlight_line_with_offset,
+    highlight_line_with_options, symbol_highlight_style, token_style, tokenize, variable_at,
+};
 
-pub use layout::{LayoutAreas, create_main_layout, create_panel_layout};
+pub use layout::{
+    DEFAULT_MEMORY_PERCENT, LayoutAreas, MEMORY_PERCENT_RANGE, create_main_layout,
+    create_panel_layout, split_overview_strip, split_watch_area,
+};
 pub use render::{
-    HELP_CONTENT_HEIGHT, build_help_content_lines, build_input_lines, build_result_lines,
-    build_visible_input_lines, build_visible_result_lines, centered_rect, format_result,
-    help_content_lines, render_command_bar, render_help_overlay, render_input_panel,
-    render_result_panel,
+    Annotations, ChangeHighlight, Gutter, HELP_CONTENT_HEIGHT, LineNumbers, NumberFormat,
+    build_conflict_prompt_lines, build_debug_lines, build_explanation_lines, build_go_to_line_text,
+    build_help_content_lines, build_input_lines, build_inspection_lines, build_notification_text,
+    build_paste_list_text, build_quick_sum_text, build_recovery_prompt_lines, build_result_lines,
+    build_settings_lines, build_template_picker_lines, build_visible_input_lines,
+    build_visible_result_lines, build_watch_lines, build_welcome_lines, build_what_if_text,
+    centered_rect, format_duration, format_result, help_content_lines, line_message,
+    render_command_bar, render_conflict_prompt, render_debug_overlay, render_dependency_popup,
+    render_diff_popup, render_explanation_popup, render_help_overlay, render_input_panel,
+    render_inspection_popup, render_overview_strip, render_prompt_bar, render_quit_prompt,
+    render_recovery_prompt, render_result_panel, render_settings_popup, render_table_popup,
+    render_template_picker, render_version_popup, render_watch_panel, render_welcome_banner,
 };
 
-use crate::app::App;
-use crate::eval::evaluate_all_lines_with_context;
+use std::time::{Duration, Instant};
+
+use crate::app::{App, WELCOME_MESSAGE};
+use crate::eval::{LineResult, evaluate_all_lines_timed, evaluate_all_lines_with_context};
 use ratatui::Frame;
+use ratatui::layout::Rect;
+
+/// Returns how the input panel's gutter is drawn under the current settings.
+const fn gutter(app: &App) -> Gutter {
+    Gutter {
+        numbers: LineNumbers::new(
+            app.settings.number,
+            app.settings.relativenumber,
+            app.buffer.cursor().row(),
+        ),
+        markers: app.settings.markers,
+    }
+}
+
+/// Returns the message of the cursor line for the command bar when errors
+/// are shown there, or `None` if it has no error or warning.
+fn bar_error(app: &App, results: &[LineResult], show: bool) -> Option<String> {
+    let row = app.buffer.cursor().row();
+    results
+        .get(row)
+        .filter(|_| show)
+        .and_then(|result| line_message(row, result))
+}
+
+/// Returns the intermediate result shown after the cursor line while it is
+/// an error, such as `8` for `5 + 3 +`.
+fn ghost_text(app: &App, results: &[LineResult]) -> Option<String> {
+    results
+        .get(app.buffer.cursor().row())
+        .filter(|result| matches!(result, LineResult::Error(_)))
+        .and_then(|_| app.ghost_result())
+        .map(|value| app.settings.number_format().format(value))
+}
+
+/// Evaluates every line of the buffer into the app's context, with the time
+/// each line took if the memory pane shows timings.
+fn evaluate(app: &mut App) -> (Vec<LineResult>, Option<Vec<Duration>>) {
+    let started = Instant::now();
+    let lines = app.buffer.lines().iter().map(String::as_str);
+    let evaluated = if app.show_timings {
+        let (results, timings) = evaluate_all_lines_timed(lines, &mut app.context);
+        (results, Some(timings))
+    } else {
+        (
+            evaluate_all_lines_with_context(lines, &mut app.context),
+            None,
+        )
+    };
+    if let Some(debug) = &mut app.debug {
+        debug.eval_time = started.elapsed();
+    }
+    evaluated
+}
+
+/// Splits the panel area into the input panel, the memory pane and the
+/// overview strip if shown. The accessible mode gives the input panel the
+/// whole area, leaving the memory pane empty.
+fn panel_areas(app: &App, area: Rect) -> (Rect, Rect, Option<Rect>) {
+    if app.settings.accessible {
+        return (area, Rect::default(), None);
+    }
+    let panels = create_panel_layout(app.memory_pane_left, app.settings.ratio).split(area);
+    let (input, memory) = if app.memory_pane_left {
+        (panels[1], panels[0]) // Input on right, memory on left
+    } else {
+        (panels[0], panels[1]) // Input on left, memory on right
+    };
+    if app.settings.overview {
+        let (memory, overview) = split_overview_strip(memory);
+        (input, memory, Some(overview))
+    } else {
+        (input, memory, None)
+    }
+}
 
 /// Renders the main UI layout with input, results panels, and command bar.
 ///
@@ -36,47 +131,184 @@ use ratatui::Frame;
 pub fn render(frame: &mut Frame, app: &mut App) {
     let areas = create_main_layout(frame.area());
 
-    let panels = create_panel_layout(app.memory_pane_left).split(areas.content_area);
-
-    let (input_panel_idx, memory_panel_idx) = if app.memory_pane_left {
-        (1, 0) // Input on right, memory on left
-    } else {
-        (0, 1) // Input on left, memory on right
-    };
+    let (panel_area, watch_area) = split_watch_area(areas.content_area, app.watches.len());
+    let accessible = app.settings.accessible;
+    let (input_area, memory_area, overview_area) = panel_areas(app, panel_area);
 
-    let visible_height = panels[input_panel_idx].height.saturating_sub(2) as usize;
-    let visible_width = panels[input_panel_idx].width.saturating_sub(2) as usize;
+    let visible_height = input_area.height.saturating_sub(2) as usize;
+    let visible_width = input_area.width.saturating_sub(2) as usize;
 
     app.adjust_scroll(visible_height);
     app.adjust_horizontal_scroll(visible_width);
 
-    let results = evaluate_all_lines_with_context(
-        app.buffer.lines().iter().map(String::as_str),
-        &mut app.context,
-    );
+    let (results, timings) = evaluate(app);
+    let mut changed = app.track_result_changes(&results);
+    if !app.settings.motion {
+        changed.clear();
+    }
+    let gutter = gutter(app);
+    let show_errors = render::should_show_error_message(app.last_edit_time);
+    let inline_errors = app.settings.errors == ErrorPlacement::Inline;
+    app.screen = ScreenMap {
+        input: input_area,
+        memory: memory_area,
+        gutter_width: gutter.width(app.buffer.line_count()),
+        rows: RowMap::new(&results, show_errors && inline_errors && !accessible),
+    };
+    let rows = &app.screen.rows;
+    let ghost = ghost_text(app, &results);
 
     render_input_panel(
         frame,
-        panels[input_panel_idx],
+        input_area,
         &app.buffer,
+        &app.context,
+        &results,
+        rows,
         app.scroll_offset,
         app.horizontal_scroll_offset,
-        app.last_edit_time,
+        gutter,
+        app.bracket_colors.then(|| app.theme.bracket_colors()),
+        ghost.as_deref(),
+        accessible.then(|| Annotations {
+            format: app.settings.number_format(),
+            errors: show_errors,
+        }),
     );
 
-    render_result_panel(
-        frame,
-        panels[memory_panel_idx],
-        &results,
-        app.scroll_offset,
-        app.memory_pane_left,
-    );
+    if !accessible {
+        render_result_panel(
+            frame,
+            memory_area,
+            &results,
+            rows,
+            app.scroll_offset,
+            app.memory_pane_left,
+            app.align_decimals,
+            app.settings.number_format(),
+            ChangeHighlight {
+                lines: &changed,
+                color: app.theme.changed_result_color(),
+            },
+            timings.as_deref(),
+        );
+    }
+
+    if let Some(area) = overview_area {
+        render_overview_strip(frame, area, &results, app.scroll_offset, visible_height);
+    }
+
+    if let Some(area) = watch_area {
+        render_watch_panel(
+            frame,
+            area,
+            &app.watches,
+            &app.context,
+            app.settings.number_format(),
+        );
+    }
 
-    render_command_bar(frame, areas.command_bar);
+    if app.welcome_visible {
+        render_welcome_banner(frame, areas.content_area, WELCOME_MESSAGE);
+    }
+
+    let prompt = app
+        .go_to_line_prompt
+        .as_deref()
+        .map(build_go_to_line_text)
+        .or_else(|| app.list_prompt.as_ref().map(build_paste_list_text))
+        .or_else(|| {
+            app.what_if_prompt
+                .as_deref()
+                .map(|prompt| build_what_if_text(prompt, app.context.overrides()))
+        })
+        .or_else(|| {
+            app.quick_sum()
+                .map(|quick_sum| build_quick_sum_text(&quick_sum, app.settings.number_format()))
+        });
+    if let Some(text) = prompt {
+        render_prompt_bar(frame, areas.command_bar, text, app.active_notification());
+    } else {
+        let error = bar_error(app, &results, show_errors && !inline_errors && !accessible);
+        let message = app.active_notification().or(error.as_deref());
+        render_command_bar(frame, areas.command_bar, message);
+    }
 
+    render_popups(frame, app);
+
+    if app.settings.ascii {
+        ascii::to_ascii(frame.buffer_mut());
+    }
+}
+
+/// Renders the overlays and popups that are open, over the whole screen.
+fn render_popups(frame: &mut Frame, app: &App) {
     if app.help_visible {
         render_help_overlay(frame, frame.area(), app.help_scroll_offset);
     }
+
+    if let Some(inspection) = &app.inspection {
+        render_inspection_popup(frame, frame.area(), inspection);
+    }
+
+    if let Some(explanation) = &app.explanation {
+        render_explanation_popup(frame, frame.area(), explanation);
+    }
+
+    if let Some(graph) = &app.dependency_graph {
+        render_dependency_popup(frame, frame.area(), graph);
+    }
+
+    if let Some(info) = &app.version_info {
+        render_version_popup(frame, frame.area(), info);
+    }
+
+    if let Some(view) = &app.table_view {
+        render_table_popup(
+            frame,
+            frame.area(),
+            &view.table,
+            view.page_rows(),
+            view.page,
+            view.page_count(),
+            app.settings.number_format(),
+        );
+    }
+
+    if let Some(view) = &app.diff_view {
+        render_diff_popup(
+            frame,
+            frame.area(),
+            view.page_lines(),
+            view.page,
+            view.page_count(),
+        );
+    }
+
+    if let Some(picker) = &app.template_picker {
+        render_template_picker(frame, frame.area(), &picker.names, picker.selected);
+    }
+
+    if let Some(prompt) = &app.settings_prompt {
+        render_settings_popup(frame, frame.area(), &app.settings.entries(), prompt);
+    }
+
+    if let Some(lines) = &app.pending_recovery {
+        render_recovery_prompt(frame, frame.area(), lines.len());
+    } else if let Some(lines) = &app.pending_conflict {
+        render_conflict_prompt(frame, frame.area(), lines.len());
+    } else if app.quit_pending {
+        render_quit_prompt(frame, frame.area(), app.buffer.line_count());
+    }
+
+    if let Some(debug) = &app.debug {
+        let lines = build_debug_lines(
+            debug.frame_time,
+            debug.eval_time,
+            debug.events.iter().map(String::as_str),
+        );
+        render_debug_overlay(frame, frame.area(), lines);
+    }
 }
 
 #[cfg(test)]
@@ -95,7 +327,7 @@ mod tests {
 
     #[test]
     fn panel_layout_creates_two_chunks() {
-        let layout = create_panel_layout(false);
+        let layout = create_panel_layout(false, DEFAULT_MEMORY_PERCENT);
         let area = Rect::new(0, 0, 100, 49);
         let chunks = layout.split(area);
 
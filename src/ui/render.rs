@@ -5,9 +5,15 @@
 //! - Error message display below error lines
 //! - Result panel with aligned evaluation results
 
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
 use std::time::{Duration, Instant};
 
 use crate::eval::constants::recognize_constant;
+use crate::eval::datasize::{format_size, size_prefixes};
+use crate::eval::money::Money;
+use crate::eval::numformat::{FormatSpec, format_plain};
 use ratatui::{
     Frame,
     layout::{Alignment, Rect},
@@ -16,12 +22,18 @@ use ratatui::{
     widgets::{Block, BorderType, Borders, Paragraph},
 };
 
-use crate::editor::Buffer;
-use crate::eval::{EvalError, LineResult, evaluate_all_lines};
-use crate::ui::highlight::{highlight_line, highlight_line_with_offset};
-
-/// Threshold above which whole numbers are displayed in scientific notation.
-const MAX_WHOLE_NUMBER_DISPLAY: f64 = 1e15;
+use crate::app::{DiffKind, DiffLine, ListPrompt, QuickSum};
+use crate::editor::{self, Buffer};
+use crate::eval::calculus::Table;
+use crate::eval::explain::Explanation;
+use crate::eval::inspect::Inspection;
+use crate::eval::{
+    EvalContext, EvalError, LineResult, ParsedLine, Severity, evaluate_expression, parse_line,
+};
+use crate::ui::highlight::{
+    HighlightOptions, highlight_line, highlight_line_with_options, variable_at,
+};
+use crate::ui::rows::{RowMap, has_hint_line, table_rows};
 
 /// Maximum character length before a value is truncated in the memory pane.
 const TRUNCATED_DISPLAY_MAX: usize = 12;
@@ -29,12 +41,48 @@ const TRUNCATED_DISPLAY_MAX: usize = 12;
 /// Number of leading characters to keep when truncating a value.
 const TRUNCATED_DISPLAY_PREFIX: usize = 9;
 
+/// Label shown before the sum on a `total` marker line.
+const TOTAL_LABEL: &str = "Σ";
+
+/// Spaces borrowed for padding, so that most padding needs no allocation.
+const BLANK: &str = match std::str::from_utf8(&[b' '; 128]) {
+    Ok(blank) => blank,
+    Err(_) => "",
+};
+
+/// Width of the evaluation timing column, including a separating space.
+const TIMING_WIDTH: usize = 8;
+
 /// Width percentage for the help overlay relative to the terminal.
 const HELP_OVERLAY_WIDTH_PERCENT: u16 = 70;
 
 /// Height percentage for the help overlay relative to the terminal.
 const HELP_OVERLAY_HEIGHT_PERCENT: u16 = 80;
 
+/// Width percentage for the inspection popup relative to the terminal.
+const INSPECTION_POPUP_WIDTH_PERCENT: u16 = 60;
+
+/// Height percentage for the inspection popup relative to the terminal.
+const INSPECTION_POPUP_HEIGHT_PERCENT: u16 = 70;
+
+/// Width percentage for the crash recovery and conflict prompts relative to
+/// the terminal.
+const RECOVERY_PROMPT_WIDTH_PERCENT: u16 = 60;
+
+/// Height of the crash recovery and conflict prompts in rows (including
+/// borders).
+const RECOVERY_PROMPT_HEIGHT: u16 = 6;
+
+/// Width of the settings popup as a percentage of the screen width.
+const SETTINGS_POPUP_WIDTH_PERCENT: u16 = 60;
+
+/// Width of the setting name column in the settings popup.
+const SETTINGS_KEY_WIDTH: usize = 12;
+
+/// Height of the welcome banner, including its borders and room for the
+/// message to wrap once.
+const WELCOME_BANNER_HEIGHT: u16 = 5;
+
 /// Formats a `LineResult` for display in the result panel.
 ///
 /// # Returns
@@ -47,6 +95,22 @@ pub fn format_result(result: &LineResult) -> Option<String> {
         LineResult::Assignment { name, value } => {
             Some(format!("{name} = {}", format_value(*value)))
         }
+        LineResult::Assignments(assigned) => Some(
+            assigned
+                .iter()
+                .map(|(name, value)| format!("{name} = {}", format_value(*value)))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        LineResult::Bytes { .. } => format_size_result(result),
+        LineResult::Money { name, money } => Some(format_money_result(name.as_deref(), *money)),
+        LineResult::Text { .. } => format_text_result(result),
+        LineResult::Symbolic(text) => Some(text.clone()),
+        LineResult::Table(table) => format_table(table, NumberFormat::default())
+            .into_iter()
+            .next(),
+        LineResult::Total(value) => Some(format!("{TOTAL_LABEL} = {}", format_value(*value))),
+        LineResult::Warning { result, .. } => format_result(result),
         LineResult::Empty | LineResult::Error(_) => None,
     }
 }
@@ -57,10 +121,31 @@ pub fn format_result(result: &LineResult) -> Option<String> {
 /// Other floats are displayed with their natural decimal representation.
 #[must_use]
 fn format_value(value: f64) -> String {
-    if value.fract() == 0.0 && value.abs() < MAX_WHOLE_NUMBER_DISPLAY {
-        format!("{value:.0}")
-    } else {
-        value.to_string()
+    format_plain(value)
+}
+
+/// How numeric results are displayed, as chosen in the settings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NumberFormat {
+    /// Decimal places to round to, `None` for full precision.
+    pub precision: Option<usize>,
+    /// Whether to group thousands with commas.
+    pub separators: bool,
+}
+
+impl NumberFormat {
+    /// Formats a value, rounding it and grouping its digits as configured.
+    ///
+    /// Rounded values drop trailing zeros, so `2.5` stays `2.5` at precision 4.
+    #[must_use]
+    pub fn format(self, value: f64) -> String {
+        let spec = FormatSpec {
+            decimals: self.precision,
+            trim_zeros: true,
+            thousands: self.separators,
+            ..FormatSpec::default()
+        };
+        spec.format(value).unwrap_or_else(|_| format_value(value))
     }
 }
 
@@ -89,6 +174,29 @@ fn annotate_with_constant(value: f64, truncated: &str) -> String {
     )
 }
 
+/// Formats a `table` result as a header naming the variable and function,
+/// followed by one row per point, with the points right-aligned in a column.
+#[must_use]
+pub fn format_table(table: &Table, format: NumberFormat) -> Vec<String> {
+    let points: Vec<String> = table.rows.iter().map(|(x, _)| format.format(*x)).collect();
+    let width = points
+        .iter()
+        .map(|point| point.chars().count())
+        .chain([table.variable.chars().count()])
+        .max()
+        .unwrap_or(0);
+
+    let header = format!("{:>width$} │ {}", table.variable, table.function);
+    std::iter::once(header)
+        .chain(
+            points
+                .iter()
+                .zip(&table.rows)
+                .map(|(point, (_, y))| format!("{point:>width$} │ {}", format.format(*y))),
+        )
+        .collect()
+}
+
 /// Formats a `LineResult` for display in the memory pane with truncation.
 ///
 /// Applies truncation to numeric values that exceed 12 characters.
@@ -99,23 +207,79 @@ fn annotate_with_constant(value: f64, truncated: &str) -> String {
 /// - `Some(String)` with the formatted (possibly truncated) result for values and assignments
 /// - `None` for empty lines or errors
 #[must_use]
-fn format_result_for_memory_pane(result: &LineResult) -> Option<String> {
+fn format_result_for_memory_pane(result: &LineResult, format: NumberFormat) -> Option<String> {
     match result {
         LineResult::Value(value) => {
-            let formatted = format_value(*value);
+            let formatted = format.format(*value);
             let truncated = format_value_truncated(&formatted);
             Some(annotate_with_constant(*value, &truncated))
         }
         LineResult::Assignment { name, value } => {
-            let formatted_value = format_value(*value);
+            let formatted_value = format.format(*value);
             let truncated_value = format_value_truncated(&formatted_value);
             let annotated = annotate_with_constant(*value, &truncated_value);
             Some(format!("{name} = {annotated}"))
         }
+        LineResult::Assignments(assigned) => Some(
+            assigned
+                .iter()
+                .map(|(name, value)| {
+                    format!(
+                        "{name} = {}",
+                        format_value_truncated(&format.format(*value))
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        LineResult::Bytes { .. } => format_size_result(result),
+        LineResult::Money { name, money } => Some(format_money_result(name.as_deref(), *money)),
+        LineResult::Text { .. } => format_text_result(result),
+        LineResult::Symbolic(text) => Some(text.clone()),
+        LineResult::Table(table) => format_table(table, format).into_iter().next(),
+        LineResult::Total(value) => {
+            let truncated = format_value_truncated(&format.format(*value));
+            Some(format!("{TOTAL_LABEL} = {truncated}"))
+        }
+        LineResult::Warning { result, .. } => format_result_for_memory_pane(result, format),
         LineResult::Empty | LineResult::Error(_) => None,
     }
 }
 
+/// Formats a data-size result, e.g. `disk = 1.5 TB`.
+fn format_size_result(result: &LineResult) -> Option<String> {
+    let LineResult::Bytes {
+        name,
+        value,
+        binary,
+    } = result
+    else {
+        return None;
+    };
+    let size = format_size(*value, *binary);
+    Some(
+        name.as_ref()
+            .map_or_else(|| size.clone(), |name| format!("{name} = {size}")),
+    )
+}
+
+/// Formats an amount of money, e.g. `rent = €1,250.00`.
+fn format_money_result(name: Option<&str>, money: Money) -> String {
+    let amount = money.format();
+    name.map_or_else(|| amount.clone(), |name| format!("{name} = {amount}"))
+}
+
+/// Formats a number shown as text, e.g. `year = MMXXIV`.
+fn format_text_result(result: &LineResult) -> Option<String> {
+    let LineResult::Text { name, text, .. } = result else {
+        return None;
+    };
+    Some(
+        name.as_ref()
+            .map_or_else(|| text.clone(), |name| format!("{name} = {text}")),
+    )
+}
+
 /// Builds styled text lines for the input panel.
 ///
 /// Handles:
@@ -142,18 +306,82 @@ pub fn build_input_lines<'a>(lines: &'a [String], results: &'a [LineResult]) ->
         };
         output.push(styled_line);
 
-        if let Some(LineResult::Error(err)) = result {
-            let error_line = Line::from(Span::styled(
-                format!("  ^ {}", err.message()),
-                Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC),
-            ));
-            output.push(error_line);
+        if let Some(hint) = result.and_then(|result| build_hint_line(0, result)) {
+            output.push(hint);
+        }
+        for _ in 0..result.map_or(0, table_rows) {
+            output.push(Line::from(""));
         }
     }
 
     output
 }
 
+/// Formats an evaluation time compactly, e.g. `12µs`, `3.4ms` or `1.25s`.
+#[must_use]
+pub fn format_duration(duration: Duration) -> String {
+    let micros = duration.as_micros();
+    if micros < 1000 {
+        format!("{micros}µs")
+    } else if micros < 1_000_000 {
+        format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
+
+/// Returns the style of the evaluation timing column.
+fn timing_style() -> Style {
+    Style::default().add_modifier(Modifier::DIM)
+}
+
+/// Builds the message shown below a line with an error (dimmed) or a
+/// warning (yellow), prefixed with its code and indented by `indent` columns.
+///
+/// The message is borrowed from the result rather than copied.
+fn build_hint_line(indent: usize, result: &LineResult) -> Option<Line<'_>> {
+    let problem = result.problem()?;
+    let (marker, style) = match problem.severity() {
+        Severity::Error => (
+            "^ [",
+            Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC),
+        ),
+        Severity::Warning => (
+            "! [",
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::ITALIC),
+        ),
+    };
+    Some(Line::from(vec![
+        Span::styled(blank(indent + 2), style),
+        Span::styled(marker, style),
+        Span::styled(problem.code().as_str(), style),
+        Span::styled("] ", style),
+        Span::styled(problem.message(), style),
+    ]))
+}
+
+/// Returns the error or warning message of line `line` (0-based) for the
+/// command bar, such as `Line 3: [E001] undefined variable 'x'`.
+#[must_use]
+pub fn line_message(line: usize, result: &LineResult) -> Option<String> {
+    let problem = result.problem()?;
+    Some(format!(
+        "Line {}: [{}] {}",
+        line + 1,
+        problem.code(),
+        problem.message()
+    ))
+}
+
+/// Returns `width` spaces, borrowed from a static string when short enough.
+fn blank(width: usize) -> Cow<'static, str> {
+    BLANK
+        .get(..width)
+        .map_or_else(|| " ".repeat(width).into(), Cow::Borrowed)
+}
+
 /// Builds a styled line with error highlighting.
 ///
 /// If the error has a span, only that portion is underlined.
@@ -166,8 +394,8 @@ fn build_error_line<'a>(line_text: &'a str, error: &EvalError) -> Line<'a> {
     error.span().map_or_else(
         || Line::from(Span::styled(line_text, error_style)),
         |span| {
-            let start = span.start.min(line_text.len());
-            let end = span.end.min(line_text.len()).max(start);
+            let start = editor::floor_boundary(line_text, span.start);
+            let end = editor::floor_boundary(line_text, span.end).max(start);
 
             let mut spans = Vec::new();
 
@@ -211,9 +439,17 @@ pub fn build_result_lines(results: &[LineResult]) -> Vec<Line<'_>> {
         );
         output.push(line);
 
-        if matches!(result, LineResult::Error(_)) {
+        if has_hint_line(result) {
             output.push(Line::from(""));
         }
+        if let LineResult::Table(table) = result {
+            output.extend(
+                format_table(table, NumberFormat::default())
+                    .into_iter()
+                    .skip(1)
+                    .map(Line::from),
+            );
+        }
     }
 
     output
@@ -227,7 +463,7 @@ pub fn build_visible_input_lines<'a>(
     scroll_offset: usize,
     visible_height: usize,
 ) -> Vec<Line<'a>> {
-    let mut output: Vec<Line<'a>> = Vec::new();
+    let mut output: Vec<Line<'a>> = Vec::with_capacity(visible_height);
 
     let start = scroll_offset.min(lines.len());
     let end = (scroll_offset + visible_height).min(lines.len());
@@ -242,59 +478,197 @@ pub fn build_visible_input_lines<'a>(
 
         output.push(styled_line);
 
-        if let Some(LineResult::Error(err)) = result {
-            let error_line = Line::from(Span::styled(
-                format!("  ^ {}", err.message()),
-                Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC),
-            ));
-            output.push(error_line);
+        if let Some(hint) = result.and_then(|result| build_hint_line(0, result)) {
+            output.push(hint);
+        }
+        for _ in 0..result.map_or(0, table_rows) {
+            output.push(Line::from(""));
         }
     }
 
     output
 }
 
+/// Results shown in a different color because an edit elsewhere changed them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChangeHighlight<'a> {
+    /// Line indices of the changed results.
+    pub lines: &'a [usize],
+    /// Foreground color of the changed results.
+    pub color: Color,
+}
+
+/// Returns the memory pane style for a result: `total` lines are bold, like
+/// the sum under a column of figures.
+fn result_style(result: &LineResult) -> Style {
+    if matches!(result.without_warning(), LineResult::Total(_)) {
+        Style::default().add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    }
+}
+
+/// Pads formatted results so that their decimal points line up.
+///
+/// Each numeric result, as marked in `numeric`, is split into an optional
+/// `name = ` label, the integer part of the value and the rest starting at
+/// the decimal point. Labels are left-aligned, integer parts right-aligned
+/// and the rest left-aligned, so all numeric results end up the same width.
+/// Other results, such as text and symbolic ones, are left as they are, as
+/// are all results when the column would be wider than `width`. Empty
+/// results stay empty.
+#[must_use]
+pub fn align_decimal_points(texts: &[String], numeric: &[bool], width: usize) -> Vec<String> {
+    let split = |text: &str| {
+        let (label, value) = text
+            .find(" = ")
+            .map_or(("", text), |i| text.split_at(i + " = ".len()));
+        let (integer, fraction) = value.split_at(value.find('.').unwrap_or(value.len()));
+        (label.to_string(), integer.to_string(), fraction.to_string())
+    };
+    let parts: Vec<_> = texts.iter().map(|text| split(text)).collect();
+    let aligned = |index: usize| numeric.get(index).copied().unwrap_or(false);
+
+    let width_of = |part: fn(&(String, String, String)) -> &String| {
+        parts
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| aligned(*index))
+            .map(|(_, p)| part(p).chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    let label_width = width_of(|p| &p.0);
+    let integer_width = width_of(|p| &p.1);
+    let fraction_width = width_of(|p| &p.2);
+    if label_width + integer_width + fraction_width > width {
+        return texts.to_vec();
+    }
+
+    texts
+        .iter()
+        .zip(&parts)
+        .enumerate()
+        .map(|(index, (text, (label, integer, fraction)))| {
+            if text.is_empty() || !aligned(index) {
+                text.clone()
+            } else {
+                format!("{label:<label_width$}{integer:>integer_width$}{fraction:<fraction_width$}")
+            }
+        })
+        .collect()
+}
+
+/// Returns true if `result` is shown as a number whose decimal point can be
+/// lined up with others.
+fn has_decimal_point(result: &LineResult) -> bool {
+    matches!(
+        result.without_warning(),
+        LineResult::Value(_)
+            | LineResult::Assignment { .. }
+            | LineResult::Bytes { .. }
+            | LineResult::Money { .. }
+            | LineResult::Total(_)
+    )
+}
+
 /// Builds visible result lines with scrolling.
 ///
-/// Results are aligned with their corresponding input lines, including
-/// empty lines for error messages to maintain visual alignment.
+/// Results are aligned with their corresponding input lines: each result
+/// takes as many rows as its line does in `rows`, including the rows of
+/// error messages and tables shown below the input line. With
+/// `align_decimals`, the visible results are also padded into a column with
+/// their decimal points lined up. Numbers are displayed using `format`.
+/// Results listed in `changed` are colored. With `timings`, each line's evaluation time is shown in a dimmed column at
+/// the outer edge of the pane.
 #[must_use]
-pub fn build_visible_result_lines(
-    results: &[LineResult],
+#[allow(clippy::too_many_arguments)]
+pub fn build_visible_result_lines<'a>(
+    results: &'a [LineResult],
+    rows: &RowMap,
     scroll_offset: usize,
     visible_height: usize,
     panel_width: usize,
     memory_pane_left: bool,
-) -> Vec<Line<'_>> {
-    let mut output: Vec<Line<'_>> = Vec::new();
+    align_decimals: bool,
+    format: NumberFormat,
+    changed: ChangeHighlight<'_>,
+    timings: Option<&[Duration]>,
+) -> Vec<Line<'a>> {
+    let mut output: Vec<Line<'_>> = Vec::with_capacity(visible_height);
 
     let start = scroll_offset.min(results.len());
     let end = (scroll_offset + visible_height).min(results.len());
+    let visible = &results[start..end];
 
-    for result in results.iter().take(end).skip(start) {
-        let text = format_result_for_memory_pane(result).unwrap_or_default();
-        let content_width = text.len();
+    let panel_width = panel_width.saturating_sub(if timings.is_some() { TIMING_WIDTH } else { 0 });
 
-        let spans = if memory_pane_left && content_width < panel_width {
-            let padding = " ".repeat(panel_width - content_width);
+    let texts: Vec<String> = visible
+        .iter()
+        .map(|result| format_result_for_memory_pane(result, format).unwrap_or_default())
+        .collect();
+    let texts = if align_decimals {
+        let numeric: Vec<bool> = visible.iter().map(has_decimal_point).collect();
+        align_decimal_points(&texts, &numeric, panel_width)
+    } else {
+        texts
+    };
+
+    for (index, (result, text)) in (start..).zip(visible.iter().zip(texts)) {
+        let content_width = text.chars().count();
+        let mut style = result_style(result);
+        if changed.lines.contains(&index) {
+            style = style.fg(changed.color);
+        }
+
+        let mut spans = if memory_pane_left && content_width < panel_width {
+            let padding = blank(panel_width - content_width);
             if text.is_empty() {
                 vec![Span::raw(padding)]
             } else {
-                vec![Span::raw(padding), Span::styled(text, Style::default())]
+                vec![Span::raw(padding), Span::styled(text, style)]
             }
         } else if text.is_empty() {
             vec![]
         } else {
-            vec![Span::styled(text, Style::default())]
+            vec![Span::styled(text, style)]
         };
 
-        let line = Line::from(spans);
-
-        output.push(line);
+        if let Some(timings) = timings {
+            let timing = match timings.get(index) {
+                Some(duration) if *result != LineResult::Empty => format_duration(*duration),
+                _ => String::new(),
+            };
+            if memory_pane_left {
+                spans.insert(
+                    0,
+                    Span::styled(format!("{timing:<TIMING_WIDTH$}"), timing_style()),
+                );
+            } else {
+                if content_width < panel_width {
+                    spans.push(Span::raw(blank(panel_width - content_width)));
+                }
+                spans.push(Span::styled(
+                    format!("{timing:>TIMING_WIDTH$}"),
+                    timing_style(),
+                ));
+            }
+        }
 
-        if matches!(result, LineResult::Error(_)) {
-            output.push(Line::from(""));
+        let first_row = output.len();
+        output.push(Line::from(spans));
+
+        if let LineResult::Table(table) = result {
+            for row in format_table(table, format).into_iter().skip(1) {
+                let width = row.chars().count();
+                output.push(if memory_pane_left && width < panel_width {
+                    Line::from(vec![Span::raw(blank(panel_width - width)), Span::raw(row)])
+                } else {
+                    Line::from(row)
+                });
+            }
         }
+        output.resize(first_row + rows.height(index), Line::from(""));
     }
 
     output
@@ -331,13 +705,100 @@ pub const fn calculate_gutter_width(line_count: usize) -> usize {
     if width < 3 { 3 } else { width }
 }
 
+/// How the input panel's gutter numbers lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineNumbers {
+    /// No gutter.
+    Off,
+    /// Each line shows its own number.
+    #[default]
+    Absolute,
+    /// Each line shows its distance from the cursor line, which shows 0.
+    Relative { cursor: usize },
+    /// Each line shows its distance from the cursor line, which shows its
+    /// own number.
+    Hybrid { cursor: usize },
+}
+
+impl LineNumbers {
+    /// Chooses the numbering from the `number` and `relativenumber`
+    /// settings, with the cursor on line `cursor` (0-based).
+    #[must_use]
+    pub const fn new(number: bool, relative: bool, cursor: usize) -> Self {
+        match (number, relative) {
+            (false, false) => Self::Off,
+            (true, false) => Self::Absolute,
+            (false, true) => Self::Relative { cursor },
+            (true, true) => Self::Hybrid { cursor },
+        }
+    }
+
+    /// Returns the width of the gutter for a buffer of `line_count` lines.
+    #[must_use]
+    pub const fn gutter_width(self, line_count: usize) -> usize {
+        match self {
+            Self::Off => 0,
+            _ => calculate_gutter_width(line_count),
+        }
+    }
+
+    /// Returns the number shown in the gutter of `line` (0-based).
+    #[must_use]
+    pub const fn label(self, line: usize) -> Option<usize> {
+        match self {
+            Self::Off => None,
+            Self::Absolute => Some(line + 1),
+            Self::Hybrid { cursor } if line == cursor => Some(line + 1),
+            Self::Relative { cursor } | Self::Hybrid { cursor } => Some(line.abs_diff(cursor)),
+        }
+    }
+}
+
+/// What the input panel's gutter shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Gutter {
+    /// How lines are numbered.
+    pub numbers: LineNumbers,
+    /// Whether a marker column left of the numbers shows which lines assign
+    /// variables, have errors or warnings, or are disabled.
+    pub markers: bool,
+}
+
+impl Gutter {
+    /// Returns the width of the gutter for a buffer of `line_count` lines.
+    #[must_use]
+    pub const fn width(self, line_count: usize) -> usize {
+        let numbers = self.numbers.gutter_width(line_count);
+        if self.markers {
+            // The marker needs a space after it even without numbers.
+            1 + if numbers == 0 { 1 } else { numbers }
+        } else {
+            numbers
+        }
+    }
+}
+
+/// Returns the gutter marker of a line: a red `▲` for an error, a yellow `▲`
+/// for a warning, a dimmed `●` for a disabled line and a cyan `●` for an
+/// assignment, otherwise a blank.
+fn gutter_marker(line: &str, result: Option<&LineResult>) -> Span<'static> {
+    let (symbol, style) = match (result, result.and_then(LineResult::problem)) {
+        (_, Some(problem)) if problem.is_warning() => ("▲", Style::default().fg(Color::Yellow)),
+        (_, Some(_)) => ("▲", Style::default().fg(Color::Red)),
+        _ if matches!(parse_line(line), ParsedLine::Disabled) => ("●", disabled_line_style()),
+        (Some(result), _) if assigns_variable(result) => ("●", Style::default().fg(Color::Cyan)),
+        _ => (" ", Style::default()),
+    };
+    Span::styled(symbol, style)
+}
+
 /// Formats a line number for display in the gutter.
 ///
 /// Line numbers are right-aligned within the gutter width,
 /// with a trailing space to separate from content.
 ///
 /// # Arguments
-/// * `line_number` - The 1-based line number to format
+/// * `line_number` - The number to show, such as the 1-based line number
 /// * `gutter_width` - The total gutter width (including trailing space)
 ///
 /// # Returns
@@ -357,6 +818,30 @@ pub fn gutter_style() -> Style {
     Style::default().fg(Color::Gray)
 }
 
+/// Returns the gutter style for lines that define the variable under the cursor.
+#[must_use]
+pub fn definition_gutter_style() -> Style {
+    Style::default()
+        .fg(Color::Cyan)
+        .add_modifier(Modifier::BOLD)
+}
+
+/// Returns the style of lines disabled with a leading `#`: dimmed, without
+/// syntax colors.
+#[must_use]
+pub fn disabled_line_style() -> Style {
+    Style::default().add_modifier(Modifier::DIM)
+}
+
+/// Returns `true` if `line` assigns the variable `name`.
+fn defines_variable(line: &str, name: &str) -> bool {
+    match parse_line(line) {
+        ParsedLine::Assignment { name: assigned, .. } => assigned == name,
+        ParsedLine::MultipleAssignment { names, .. } => names.iter().any(|n| n == name),
+        _ => false,
+    }
+}
+
 /// Builds spans for a line with error highlighting and horizontal offset support.
 ///
 /// If the error has a span, only that portion is underlined.
@@ -378,18 +863,19 @@ fn build_error_spans_with_offset<'a>(
         .fg(Color::Red)
         .add_modifier(Modifier::UNDERLINED);
 
-    let start_col = horizontal_offset.min(line_text.len());
-    let end_col = (horizontal_offset + visible_width).min(line_text.len());
+    let start_col = editor::floor_boundary(line_text, horizontal_offset);
+    let end_col = editor::floor_boundary(line_text, horizontal_offset + visible_width);
     let visible_text = &line_text[start_col..end_col];
 
     error.span().map_or_else(
         || vec![Span::styled(visible_text, error_style)],
         |span| {
-            let span_start = span.start.saturating_sub(horizontal_offset);
-            let span_end = span.end.saturating_sub(horizontal_offset);
+            let span_start = span.start.saturating_sub(start_col);
+            let span_end = span.end.saturating_sub(start_col);
 
-            let visible_span_start = span_start.min(visible_text.len());
-            let visible_span_end = span_end.min(visible_text.len()).max(visible_span_start);
+            let visible_span_start = editor::floor_boundary(visible_text, span_start);
+            let visible_span_end =
+                editor::floor_boundary(visible_text, span_end).max(visible_span_start);
 
             let mut spans = Vec::new();
 
@@ -424,7 +910,7 @@ const ERROR_DEBOUNCE_MS: u64 = 500;
 ///
 /// The error underline is always shown regardless of this debounce check.
 #[must_use]
-fn should_show_error_message(last_edit_time: Option<Instant>) -> bool {
+pub fn should_show_error_message(last_edit_time: Option<Instant>) -> bool {
     last_edit_time
         .is_none_or(|edit_time| edit_time.elapsed() >= Duration::from_millis(ERROR_DEBOUNCE_MS))
 }
@@ -440,7 +926,9 @@ fn should_show_error_message(last_edit_time: Option<Instant>) -> bool {
 /// * `visible_height` - The number of visible lines in the viewport
 /// * `horizontal_scroll_offset` - The first visible column index (0-based)
 /// * `visible_width` - The number of visible columns in the viewport (including gutter)
-/// * `last_edit_time` - The time of the last buffer modification, for error message debouncing
+/// * `rows` - The rows each line takes; messages are shown when it has rows for them
+/// * `gutter` - How the gutter numbers and marks lines
+/// * `options` - Symbol and bracket highlighting; definition lines of the symbol are marked in the gutter
 ///
 /// # Returns
 /// A tuple of (styled lines, gutter width) for rendering.
@@ -453,11 +941,14 @@ pub fn build_visible_input_lines_with_gutter<'a>(
     visible_height: usize,
     horizontal_scroll_offset: usize,
     visible_width: usize,
-    last_edit_time: Option<Instant>,
+    rows: &RowMap,
+    gutter: Gutter,
+    options: HighlightOptions<'_>,
 ) -> (Vec<Line<'a>>, usize) {
-    let gutter_width = calculate_gutter_width(lines.len());
+    let gutter_width = gutter.width(lines.len());
+    let number_width = gutter.numbers.gutter_width(lines.len());
     let gutter_style_val = gutter_style();
-    let mut output: Vec<Line<'a>> = Vec::new();
+    let mut output: Vec<Line<'a>> = Vec::with_capacity(visible_height);
 
     let start = scroll_offset.min(lines.len());
     let end = (scroll_offset + visible_height).min(lines.len());
@@ -465,45 +956,161 @@ pub fn build_visible_input_lines_with_gutter<'a>(
     let content_width = visible_width.saturating_sub(gutter_width);
 
     for (i, line_text) in lines.iter().enumerate().take(end).skip(start) {
-        let line_number = i + 1;
         let result = results.get(i);
 
-        let line_num_str = format_line_number(line_number, gutter_width);
-        let line_num_span = Span::styled(line_num_str, gutter_style_val);
+        let line_num_str = gutter.numbers.label(i).map_or_else(
+            || " ".repeat(gutter_width.saturating_sub(usize::from(gutter.markers))),
+            |number| format_line_number(number, number_width),
+        );
+        let line_num_style = if options
+            .symbol
+            .is_some_and(|name| defines_variable(line_text, name))
+        {
+            definition_gutter_style()
+        } else {
+            gutter_style_val
+        };
+        let line_num_span = Span::styled(line_num_str, line_num_style);
 
         // Note: We need to highlight the visible portion only
         let content_spans = match result {
-            Some(LineResult::Error(err)) => build_error_spans_with_offset(
+            Some(LineResult::Error(err)) if !options.plain => build_error_spans_with_offset(
                 line_text,
                 err,
                 horizontal_scroll_offset,
                 content_width,
             ),
-            _ => highlight_line_with_offset(line_text, horizontal_scroll_offset, content_width),
+            _ if matches!(parse_line(line_text), ParsedLine::Disabled) => {
+                let mut spans = highlight_line_with_options(
+                    line_text,
+                    horizontal_scroll_offset,
+                    content_width,
+                    HighlightOptions {
+                        token_options: options.token_options,
+                        format_presets: options.format_presets,
+                        ..HighlightOptions::default()
+                    },
+                );
+                for span in &mut spans {
+                    span.style = disabled_line_style();
+                }
+                spans
+            }
+            _ => highlight_line_with_options(
+                line_text,
+                horizontal_scroll_offset,
+                content_width,
+                options,
+            ),
         };
 
-        let mut all_spans = vec![line_num_span];
-        all_spans.extend(content_spans);
+        let line_offset = editor::floor_boundary(line_text, horizontal_scroll_offset);
+        let content_spans = match (options.selection, options.block) {
+            (Some(selection), _) if selection.row == i => {
+                reverse_range(content_spans, line_offset, selection.start, selection.end)
+            }
+            (_, Some(block)) if block.contains_row(i) => {
+                reverse_block(content_spans, line_text, line_offset, block)
+            }
+            _ => content_spans,
+        };
 
-        let styled_line = Line::from(all_spans);
+        let mut all_spans = Vec::with_capacity(content_spans.len() + 2);
+        if gutter.markers {
+            all_spans.push(gutter_marker(line_text, result));
+        }
+        all_spans.push(line_num_span);
+        all_spans.extend(content_spans);
 
-        output.push(styled_line);
+        let first_row = output.len();
+        output.push(Line::from(all_spans));
 
-        if let Some(LineResult::Error(err)) = result
-            && should_show_error_message(last_edit_time)
+        if let Some(hint) = result.and_then(|result| build_hint_line(gutter_width, result))
+            && rows.shows_hints()
         {
-            let indent = " ".repeat(gutter_width);
-            let error_line = Line::from(Span::styled(
-                format!("{}  ^ {}", indent, err.message()),
-                Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC),
-            ));
-            output.push(error_line);
+            output.push(hint);
         }
+        output.resize(first_row + rows.height(i), Line::from(""));
     }
 
     (output, gutter_width)
 }
 
+/// Shows the bytes `start..end` of a line reversed, splitting spans at the
+/// ends of the range. The spans show the line from byte `offset` on. Ends
+/// inside a character are moved back to its start.
+fn reverse_range(spans: Vec<Span<'_>>, offset: usize, start: usize, end: usize) -> Vec<Span<'_>> {
+    let mut output = Vec::with_capacity(spans.len() + 2);
+    let mut span_start = offset;
+    for span in spans {
+        let len = span.content.len();
+        let from = editor::floor_boundary(
+            &span.content,
+            start.clamp(span_start, span_start + len) - span_start,
+        );
+        let to = editor::floor_boundary(
+            &span.content,
+            end.clamp(span_start, span_start + len) - span_start,
+        );
+        span_start += len;
+        if from == to {
+            output.push(span);
+            continue;
+        }
+        let selected = span.style.add_modifier(Modifier::REVERSED);
+        for (range, style) in [
+            (0..from, span.style),
+            (from..to, selected),
+            (to..len, span.style),
+        ] {
+            if range.is_empty() {
+                continue;
+            }
+            let content = match &span.content {
+                Cow::Borrowed(text) => Cow::Borrowed(&text[range]),
+                Cow::Owned(text) => Cow::Owned(text[range].to_string()),
+            };
+            output.push(Span::styled(content, style));
+        }
+    }
+    output
+}
+
+/// Shows the columns of `block` on a line reversed. A block that is only an
+/// insertion column shows one reversed cell, at the end of the line if the
+/// block goes to the line ends. Lines too short to reach the block are
+/// shown as they are.
+fn reverse_block<'a>(
+    spans: Vec<Span<'a>>,
+    line: &str,
+    offset: usize,
+    block: editor::Block,
+) -> Vec<Span<'a>> {
+    let start = if block.to_end { line.len() } else { block.left };
+    if start > line.len() || !line.is_char_boundary(start) {
+        return spans;
+    }
+    if start == line.len() {
+        let mut spans = spans;
+        if start >= offset {
+            spans.push(Span::styled(
+                " ",
+                Style::default().add_modifier(Modifier::REVERSED),
+            ));
+        }
+        return spans;
+    }
+    let end = if block.left < block.right {
+        block.right.min(line.len())
+    } else {
+        start + 1
+    };
+    let end = (end..=line.len())
+        .find(|&col| line.is_char_boundary(col))
+        .unwrap_or(line.len());
+    reverse_range(spans, offset, start, end)
+}
+
 /// Determines if the terminal likely supports emoji rendering.
 ///
 /// Uses the `TERM` environment variable to heuristically detect modern terminals
@@ -602,53 +1209,104 @@ pub fn memory_panel_block(memory_pane_left: bool) -> Block<'static> {
 /// * `frame` - The ratatui Frame to render to
 /// * `area` - The area to render the panel in
 /// * `buffer` - The text buffer containing input lines
+/// * `context` - The evaluation context, whose settings decide how lines are
+///   highlighted
+/// * `results` - The evaluation results of the lines
+/// * `rows` - The rows each line takes, shared with the result panel
 /// * `scroll_offset` - The first visible line index (0-based)
 /// * `horizontal_scroll_offset` - The first visible column index (0-based)
-/// * `last_edit_time` - The time of the last buffer modification, for error message debouncing
+/// * `gutter` - How the gutter numbers and marks lines
+/// * `bracket_colors` - Palette for depth-based parenthesis coloring, or `None` to disable it
+/// * `ghost` - Intermediate result of the cursor line while it is incomplete
+/// * `annotations` - In the accessible mode, how results are described after
+///   their lines, which are then shown without syntax colors
+#[allow(clippy::too_many_arguments)]
 pub fn render_input_panel(
     frame: &mut Frame,
     area: Rect,
     buffer: &Buffer,
+    context: &EvalContext,
+    results: &[LineResult],
+    rows: &RowMap,
     scroll_offset: usize,
     horizontal_scroll_offset: usize,
-    last_edit_time: Option<Instant>,
+    gutter: Gutter,
+    bracket_colors: Option<&[Color]>,
+    ghost: Option<&str>,
+    annotations: Option<Annotations>,
 ) {
-    let results = evaluate_all_lines(buffer.lines().iter().map(String::as_str));
-
     let cursor_row = buffer.cursor().row();
 
     let visible_height = area.height.saturating_sub(1) as usize;
 
     let visible_width = area.width as usize;
 
-    let (styled_lines, gutter_width) = build_visible_input_lines_with_gutter(
+    let options = HighlightOptions {
+        symbol: variable_at(buffer.current_line(), buffer.cursor().col()),
+        bracket_colors: bracket_colors.filter(|_| annotations.is_none()),
+        selection: buffer.selection(),
+        block: buffer.block(),
+        plain: annotations.is_some(),
+        token_options: Some(context.token_options()),
+        format_presets: Some(context.format_presets()),
+    };
+
+    let (mut styled_lines, gutter_width) = build_visible_input_lines_with_gutter(
         buffer.lines(),
-        &results,
+        results,
         scroll_offset,
         visible_height,
         horizontal_scroll_offset,
         visible_width,
-        last_edit_time,
+        rows,
+        gutter,
+        options,
     );
 
+    if let Some(ghost) = ghost
+        && (scroll_offset..scroll_offset + visible_height).contains(&cursor_row)
+        && let Some(line) = styled_lines.get_mut(rows.first_row(cursor_row, scroll_offset))
+    {
+        line.push_span(ghost_span(ghost));
+    }
+
+    if let Some(annotations) = annotations {
+        let end = (scroll_offset + visible_height).min(results.len());
+        for (i, result) in results.iter().enumerate().take(end).skip(scroll_offset) {
+            let first_row = rows.first_row(i, scroll_offset);
+            for (offset, text) in describe_result(result, annotations).into_iter().enumerate() {
+                let Some(line) = styled_lines.get_mut(first_row + offset) else {
+                    break;
+                };
+                if offset == 0 {
+                    line.push_span(Span::styled(format!("  {text}"), annotation_style()));
+                } else {
+                    *line = Line::from(vec![
+                        Span::raw(blank(gutter_width)),
+                        Span::styled(text, annotation_style()),
+                    ]);
+                }
+            }
+        }
+    }
+
     let paragraph = Paragraph::new(Text::from(styled_lines)).block(input_panel_block());
 
     frame.render_widget(paragraph, area);
 
     let cursor_col = buffer.cursor().col();
 
-    let mut actual_row = 0;
-    for i in scroll_offset..cursor_row.min(scroll_offset + visible_height) {
-        if i == cursor_row {
-            break;
-        }
-        actual_row += 1;
-        if matches!(results.get(i), Some(LineResult::Error(_))) {
-            actual_row += 1;
-        }
-    }
+    let actual_row = rows.first_row(
+        cursor_row.min(scroll_offset + visible_height),
+        scroll_offset,
+    );
 
-    let adjusted_cursor_col = cursor_col.saturating_sub(horizontal_scroll_offset);
+    // The cursor is a byte offset; count the characters before it on screen.
+    let line = buffer.current_line();
+    let scrolled = editor::floor_boundary(line, horizontal_scroll_offset);
+    let adjusted_cursor_col = line
+        .get(scrolled..cursor_col)
+        .map_or(0, |visible| visible.chars().count());
     let cursor_x = area.x
         + u16::try_from(gutter_width).unwrap_or(0)
         + u16::try_from(adjusted_cursor_col).unwrap_or(0);
@@ -659,6 +1317,66 @@ pub fn render_input_panel(
     }
 }
 
+/// How the input panel describes results in text in the accessible mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Annotations {
+    /// How numbers are displayed.
+    pub format: NumberFormat,
+    /// Whether errors are described; they are held back while typing.
+    pub errors: bool,
+}
+
+/// Style of the result descriptions of the accessible mode: bold in the
+/// terminal's default colors, for high contrast.
+fn annotation_style() -> Style {
+    Style::default().add_modifier(Modifier::BOLD)
+}
+
+/// Describes `result` in text for the accessible mode: the first string
+/// follows its line, such as `= 8`, `x = 5` or `ERR: [E001] undefined
+/// variable 'y'`, and any further strings fill the rows of a table.
+#[must_use]
+pub fn describe_result(result: &LineResult, annotations: Annotations) -> Vec<String> {
+    let format = annotations.format;
+    let value = match result {
+        LineResult::Error(_) if !annotations.errors => None,
+        LineResult::Error(error) => Some(format!("ERR: [{}] {}", error.code(), error.message())),
+        LineResult::Warning { result, warning } => {
+            let mut lines = describe_result(result, annotations);
+            let text = format!("WARN: [{}] {}", warning.code(), warning.message());
+            match lines.first_mut() {
+                Some(first) => *first = format!("{first}  {text}"),
+                None => lines.push(text),
+            }
+            return lines;
+        }
+        LineResult::Table(table) => {
+            let mut lines = format_table(table, format);
+            if let Some(header) = lines.first_mut() {
+                *header = format!("= {header}");
+            }
+            return lines;
+        }
+        LineResult::Value(value) => Some(format!("= {}", format.format(*value))),
+        LineResult::Assignment { name, value } => {
+            Some(format!("{name} = {}", format.format(*value)))
+        }
+        LineResult::Total(value) => Some(format!("{TOTAL_LABEL} = {}", format.format(*value))),
+        _ if assigns_variable(result) => format_result(result),
+        _ => format_result(result).map(|text| format!("= {text}")),
+    };
+    value.into_iter().collect()
+}
+
+/// Builds the dimmed preview shown after the line being typed, such as
+/// `  ≈ 8` after `5 + 3 +`.
+fn ghost_span(value: &str) -> Span<'static> {
+    Span::styled(
+        format!("  ≈ {value}"),
+        Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC),
+    )
+}
+
 /// Renders the result panel with evaluation results and scrolling.
 ///
 /// Results are aligned with their corresponding input lines.
@@ -668,14 +1386,25 @@ pub fn render_input_panel(
 /// * `frame` - The ratatui Frame to render to
 /// * `area` - The area to render the panel in
 /// * `results` - The evaluation results to display
+/// * `rows` - The rows each line takes, shared with the input panel
 /// * `scroll_offset` - The first visible line index (0-based)
 /// * `memory_pane_left` - Whether the memory pane is on the left side
+/// * `align_decimals` - Whether to line up the decimal points of the results
+/// * `format` - How numbers are displayed
+/// * `changed` - Results to color because an edit elsewhere changed them
+/// * `timings` - Evaluation time of each line, shown with their total when present
+#[allow(clippy::too_many_arguments)]
 pub fn render_result_panel(
     frame: &mut Frame,
     area: Rect,
     results: &[LineResult],
+    rows: &RowMap,
     scroll_offset: usize,
     memory_pane_left: bool,
+    align_decimals: bool,
+    format: NumberFormat,
+    changed: ChangeHighlight<'_>,
+    timings: Option<&[Duration]>,
 ) {
     let visible_height = area.height.saturating_sub(2) as usize;
 
@@ -683,14 +1412,161 @@ pub fn render_result_panel(
 
     let styled_lines = build_visible_result_lines(
         results,
+        rows,
         scroll_offset,
         visible_height,
         panel_width,
         memory_pane_left,
+        align_decimals,
+        format,
+        changed,
+        timings,
     );
 
-    let paragraph =
-        Paragraph::new(Text::from(styled_lines)).block(memory_panel_block(memory_pane_left));
+    let mut block = memory_panel_block(memory_pane_left);
+    if let Some(timings) = timings {
+        let total = format_duration(timings.iter().sum());
+        block = block.title(Span::styled(format!(" {total} total"), timing_style()));
+    }
+    let paragraph = Paragraph::new(Text::from(styled_lines)).block(block);
+
+    frame.render_widget(paragraph, area);
+}
+
+/// Builds the overview strip of the whole worksheet, `height` rows tall.
+///
+/// Each row stands for an equal slice of the lines: red if a line of the
+/// slice has an error, otherwise cyan if one assigns a variable. Rows of the
+/// `visible_lines` lines from `scroll_offset` are shaded to show the viewport.
+#[must_use]
+pub fn build_overview_lines(
+    results: &[LineResult],
+    scroll_offset: usize,
+    visible_lines: usize,
+    height: usize,
+) -> Vec<Line<'static>> {
+    let count = results.len();
+    let viewport = scroll_offset..scroll_offset + visible_lines;
+    (0..height.min(count))
+        .map(|row| {
+            let slice = if count > height {
+                (row * count / height)..((row + 1) * count / height)
+            } else {
+                row..row + 1
+            };
+            let slice_results = &results[slice.clone()];
+            let style = if slice_results
+                .iter()
+                .any(|result| matches!(result, LineResult::Error(_)))
+            {
+                Style::default().fg(Color::Red)
+            } else if slice_results.iter().any(assigns_variable) {
+                Style::default().fg(Color::Cyan)
+            } else {
+                Style::default().add_modifier(Modifier::DIM)
+            };
+            let in_view = slice.start < viewport.end && viewport.start < slice.end;
+            let symbol = if style.fg.is_some() {
+                "█"
+            } else if in_view {
+                "┃"
+            } else {
+                "│"
+            };
+            let style = if in_view {
+                style.bg(Color::DarkGray)
+            } else {
+                style
+            };
+            Line::from(Span::styled(symbol, style))
+        })
+        .collect()
+}
+
+/// Returns true if `result` comes from a line assigning a variable.
+fn assigns_variable(result: &LineResult) -> bool {
+    match result {
+        LineResult::Assignment { .. }
+        | LineResult::Assignments(_)
+        | LineResult::Bytes { name: Some(_), .. }
+        | LineResult::Money { name: Some(_), .. }
+        | LineResult::Text { name: Some(_), .. } => true,
+        LineResult::Warning { result, .. } => assigns_variable(result),
+        _ => false,
+    }
+}
+
+/// Renders the overview strip below a blank row level with the panel titles.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The area of the strip
+/// * `results` - The evaluation results of every line
+/// * `scroll_offset` - The first visible line index (0-based)
+/// * `visible_lines` - The number of lines shown in the input panel
+pub fn render_overview_strip(
+    frame: &mut Frame,
+    area: Rect,
+    results: &[LineResult],
+    scroll_offset: usize,
+    visible_lines: usize,
+) {
+    let height = area.height.saturating_sub(1) as usize;
+    let mut lines = vec![Line::from("")];
+    lines.extend(build_overview_lines(
+        results,
+        scroll_offset,
+        visible_lines,
+        height,
+    ));
+    frame.render_widget(Paragraph::new(Text::from(lines)), area);
+}
+
+/// Builds one line per pinned expression: the expression and its current
+/// value, or the evaluation error in a dimmed style.
+#[must_use]
+pub fn build_watch_lines<'a>(
+    watches: &'a [String],
+    context: &EvalContext,
+    format: NumberFormat,
+) -> Vec<Line<'a>> {
+    watches
+        .iter()
+        .map(|watch| {
+            let value = match evaluate_expression(watch, context) {
+                Ok(value) => Span::styled(
+                    size_prefixes(watch, context)
+                        .map_or_else(|| format.format(value), |binary| format_size(value, binary)),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Err(e) => Span::styled(
+                    e.message().to_string(),
+                    Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC),
+                ),
+            };
+            Line::from(vec![Span::raw(watch.as_str()), Span::raw(" = "), value])
+        })
+        .collect()
+}
+
+/// Renders the watch panel with the pinned expressions evaluated against the
+/// variables left by the whole buffer.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The area to render the panel in
+/// * `watches` - The pinned expressions
+/// * `context` - The context after evaluating the buffer
+/// * `format` - How numbers are displayed
+pub fn render_watch_panel(
+    frame: &mut Frame,
+    area: Rect,
+    watches: &[String],
+    context: &EvalContext,
+    format: NumberFormat,
+) {
+    let paragraph = Paragraph::new(Text::from(build_watch_lines(watches, context, format)))
+        .block(Block::default().title("Watch").borders(Borders::TOP));
 
     frame.render_widget(paragraph, area);
 }
@@ -717,19 +1593,156 @@ pub fn build_command_bar_text<'a>() -> Line<'a> {
     ])
 }
 
-/// Renders the command bar at the bottom of the screen.
+/// Builds the styled text line for a command bar notification.
 ///
-/// Displays available keyboard commands: "CTRL+Q: quit  CTRL+R: clear  CTRL+H: help  ↑↓: history"
+/// Notifications are displayed in italics so they stand apart from the
+/// keyboard shortcuts.
+#[must_use]
+pub fn build_notification_text(message: &str) -> Line<'_> {
+    Line::from(Span::styled(
+        message,
+        Style::default().add_modifier(Modifier::ITALIC),
+    ))
+}
+
+/// Renders the command bar at the bottom of the screen.
+///
+/// Displays available keyboard commands: "CTRL+Q: quit  CTRL+R: clear  CTRL+H: help  ↑↓: history"
 /// A horizontal line separator appears above the command text.
+/// When a notification is active, it is shown right-aligned in the same row.
 ///
 /// # Arguments
 /// * `frame` - The ratatui Frame to render to
 /// * `area` - The area to render the command bar in (should be 2 rows: 1 for separator, 1 for text)
-pub fn render_command_bar(frame: &mut Frame, area: Rect) {
+/// * `notification` - The transient message to display, if any
+pub fn render_command_bar(frame: &mut Frame, area: Rect, notification: Option<&str>) {
     let command_text = build_command_bar_text();
     let command_bar = Paragraph::new(command_text).block(Block::default().borders(Borders::TOP));
 
     frame.render_widget(command_bar, area);
+
+    if let Some(message) = notification {
+        let message_bar = Paragraph::new(build_notification_text(message))
+            .alignment(Alignment::Right)
+            .block(Block::default().borders(Borders::TOP));
+        frame.render_widget(message_bar, area);
+    }
+}
+
+/// Builds the command bar line shown in what-if mode: the active overrides,
+/// sorted by name, followed by the override being typed.
+///
+/// # Arguments
+/// * `prompt` - The override typed so far
+/// * `overrides` - The active overrides by variable name
+#[must_use]
+pub fn build_what_if_text<S: BuildHasher>(
+    prompt: &str,
+    overrides: &HashMap<String, f64, S>,
+) -> Line<'static> {
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+    let hint_style = Style::default().add_modifier(Modifier::DIM);
+
+    let mut names: Vec<&String> = overrides.keys().collect();
+    names.sort();
+    let active = names
+        .iter()
+        .map(|name| format!("{name} = {}", format_value(overrides[*name])))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut spans = vec![Span::styled("WHAT-IF ", key_style)];
+    if !active.is_empty() {
+        spans.push(Span::raw(format!("[{active}] ")));
+    }
+    spans.push(Span::raw(format!("› {prompt}")));
+    spans.push(Span::styled(
+        " ",
+        Style::default().add_modifier(Modifier::REVERSED),
+    ));
+    spans.push(Span::styled("  name = value, ESC: reset", hint_style));
+    Line::from(spans)
+}
+
+/// Builds the command bar text of the go-to-line prompt.
+#[must_use]
+pub fn build_go_to_line_text(prompt: &str) -> Line<'static> {
+    Line::from(vec![
+        Span::styled("GO TO LINE ", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(format!("› {prompt}")),
+        Span::styled(" ", Style::default().add_modifier(Modifier::REVERSED)),
+        Span::styled(
+            "  ENTER: jump, ESC: cancel",
+            Style::default().add_modifier(Modifier::DIM),
+        ),
+    ])
+}
+
+/// Builds the command bar text of the prompt naming a pasted list.
+#[must_use]
+pub fn build_paste_list_text(prompt: &ListPrompt) -> Line<'static> {
+    let count = prompt.values.len();
+    let numbers = if count == 1 { "number" } else { "numbers" };
+    Line::from(vec![
+        Span::styled("LIST NAME ", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(format!(
+            "[{count} {numbers}, sum {}] › {}",
+            format_value(prompt.sum()),
+            prompt.name
+        )),
+        Span::styled(" ", Style::default().add_modifier(Modifier::REVERSED)),
+        Span::styled(
+            "  ENTER: insert, ESC: cancel",
+            Style::default().add_modifier(Modifier::DIM),
+        ),
+    ])
+}
+
+/// Builds the command bar text shown while a block spans several lines:
+/// the sum, mean and count of their numeric results.
+#[must_use]
+pub fn build_quick_sum_text(quick_sum: &QuickSum, format: NumberFormat) -> Line<'static> {
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+    let mut spans = vec![
+        Span::styled("SUM ", key_style),
+        Span::raw(format.format(quick_sum.sum)),
+    ];
+    if let Some(mean) = quick_sum.mean() {
+        spans.push(Span::styled("  MEAN ", key_style));
+        spans.push(Span::raw(format.format(mean)));
+    }
+    spans.push(Span::styled("  COUNT ", key_style));
+    spans.push(Span::raw(quick_sum.count.to_string()));
+    spans.push(Span::styled(
+        format!("  of {} lines", quick_sum.lines),
+        Style::default().add_modifier(Modifier::DIM),
+    ));
+    Line::from(spans)
+}
+
+/// Renders the command bar with a prompt, in place of the key hints.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The area to render the command bar in
+/// * `text` - The line built by `build_what_if_text`, `build_go_to_line_text`,
+///   `build_paste_list_text` or `build_quick_sum_text`
+/// * `notification` - The transient message to display, if any
+pub fn render_prompt_bar(
+    frame: &mut Frame,
+    area: Rect,
+    text: Line<'_>,
+    notification: Option<&str>,
+) {
+    let bar = Paragraph::new(text).block(Block::default().borders(Borders::TOP));
+    frame.render_widget(bar, area);
+
+    if let Some(message) = notification {
+        let message_bar = Paragraph::new(build_notification_text(message))
+            .alignment(Alignment::Right)
+            .block(Block::default().borders(Borders::TOP));
+        frame.render_widget(message_bar, area);
+    }
 }
 
 /// Help content lines for the General Usage section.
@@ -740,6 +1753,7 @@ const HELP_GENERAL_USAGE: &[&str] = &[
     "",
     "Basic Operations:",
     "  + - * /    Arithmetic operators",
+    "  //         Floor division",
     "  %          Modulo",
     "  ^          Exponentiation",
     "  ( )        Grouping",
@@ -747,13 +1761,86 @@ const HELP_GENERAL_USAGE: &[&str] = &[
     "Variables:",
     "  x = 5      Assign value to variable",
     "  x + 10     Use variable in expression",
+    "  const k = 2  Declare a constant (cannot be reassigned)",
+    "  a, b = 3, 4  Assign several variables at once",
+    "  a, b = b, a  Swap two variables",
+    "  unset x    Remove a variable",
+    "  clearvars  Remove all variables",
+    "  :loadvars rates.json  Import variables from a JSON file",
+    "",
+    "Sections:",
+    "  @section n Start a section (variables stay local)",
+    "  @end       End the section",
+    "",
+    "Bookmarks (type in settings, CTRL+O):",
+    "  :mark b    Bookmark the cursor line as b",
+    "  :'b        Jump to bookmark b",
+    "  :unmark b  Remove bookmark b",
+    "  :marks     List bookmarks",
+    "",
+    "Tidying lines (type in settings, CTRL+O):",
+    "  :sort      Sort lines alphabetically",
+    "  :sort value 3-10  Sort lines 3 to 10 by result",
+    "  :uniq      Remove repeated lines",
+    "",
+    "Running totals:",
+    "  total      Sum of the results since the last total",
+    "  ----       Same as total",
+    "",
+    "Durations:",
+    "  1h30m      Duration literal (d h m s, no spaces)",
+    "  3h20m / 5  Shown as a duration: 40m",
+    "  42 / 3h30m Shown as a rate: 12 per hour",
+    "",
+    "Result format (end a line with | name):",
+    "  255 | hex  Show as 0xff (also bin, oct)",
+    "  1/3 | 2dp  Show with 2 decimals: 0.33",
+    "  x | sci    Show in scientific notation",
+    "  x | eur    Show as money: €1,234.50 (usd, gbp)",
+    "  :set format.money 2dp, thousands, prefix \"€\"",
+    "             Define a preset for x | money",
+    "  :set format money  Use a format for every line",
     "",
     "Keyboard Shortcuts:",
-    "  CTRL+Q     Quit",
+    "  CTRL+Q     Quit (also CTRL+C, :q)",
     "  CTRL+R     Clear all",
     "  CTRL+H     Toggle help",
-    "  ESC        Close help / Quit",
+    "  CTRL+E     Inspect result",
+    "  CTRL+F     Format line",
+    "  CTRL+T     Explain evaluation steps",
+    "  CTRL+G     Show line dependency graph",
+    "  CTRL+Y     Cycle earlier results of line",
+    "  CTRL+X     What-if mode (override variables)",
+    "  CTRL+S     Copy worksheet and results",
+    "  ALT+F      Format all lines",
+    "  TAB        Complete function name",
+    "  CTRL+B     Toggle bracket colors",
+    "  CTRL+D     Toggle decimal alignment",
+    "  CTRL+P     Toggle per-line evaluation timing",
+    "  CTRL+W     Pin/unpin line in watch panel",
+    "  CTRL+K     Disable/enable line (# prefix)",
+    "  CTRL+O     Settings (:set key value)",
+    "  :profile finance  Apply a profile (also --profile)",
+    "  :version   Version, build and file locations",
+    "  :diff      Changes since the last save (:diff f for file f)",
+    "  :paste     Paste a column of numbers as a list",
+    "  :trust     Trust the project's shell setting and prelude",
+    "  :share f   Write worksheet and results to file f",
+    "  :import f  Replace worksheet with shared file f",
+    "  :new loan  New worksheet from a template (:new lists them)",
+    "  CTRL+L     Go to line (also :42 in settings)",
+    "  ALT+↑/↓    Previous/next bookmark",
+    "  ALT+1..9   Go to bookmark 1 to 9",
+    "  CTRL+A     Select enclosing expression (repeat to grow)",
+    "  ALT+E      Extract selection into a variable",
+    "  ALT+T      Show the table of the line, a page at a time",
+    "  CTRL+U     Undo",
+    "  CTRL+V     Block mode: edit a column of lines (END: line ends)",
+    "             Shows sum, mean and count of the lines' results",
+    "  CTRL+Z     Suspend to shell",
+    "  ESC        Close popup / Leave selection or block",
     "  Arrow keys Navigate / Scroll help",
+    "  Click      Move cursor to the line of a result (:set mouse on)",
     "",
 ];
 
@@ -782,7 +1869,7 @@ const HELP_FUNCTION_REFERENCE: &[&str] = &[
     "",
     "Logarithmic & Exponential:",
     "  ln(x)      Natural logarithm",
-    "  log(x)     Base-10 logarithm",
+    "  log(x,b)   Logarithm to base b",
     "  log2(x)    Base-2 logarithm",
     "  exp(x)     e^x",
     "",
@@ -792,6 +1879,51 @@ const HELP_FUNCTION_REFERENCE: &[&str] = &[
     "  round(x)   Round to nearest",
     "  trunc(x)   Truncate to integer",
     "",
+    "Conversions:",
+    "  roman(\"XIV\")       Value of a Roman numeral",
+    "  to_roman(2024)     Show as a Roman numeral",
+    "  words(1234)        Show spelled out in words",
+    "  frombase(\"z1\", 36) Read digits in any base",
+    "  tobase(1261, 36)   Show in any base",
+    "  ord(\"A\")           Code point of a character",
+    "  chr(65)            Show the character for a code",
+    "  hexdump(\"Hi\")      Show UTF-8 bytes in hex",
+    "",
+    "Networks (IPv4):",
+    "  cidr_hosts(\"a/24\") Usable hosts in a network",
+    "  netmask(24)        Show the mask: 255.255.255.0",
+    "  in_subnet(ip, net) Is the address in the network",
+    "",
+    "Equations:",
+    "  solve(2x+3=11, x)  Solve for x (linear/quadratic)",
+    "  solve(eq, x, 2)    Second root of a quadratic",
+    "  root(f, x, a, b)   Root of f between a and b",
+    "  deriv(f, x, at)    Derivative of f at a point",
+    "  table(f, x, a, b)  Values of f from a to b",
+    "  simplify(2x+3x)    Collect like terms: 5 * x",
+    "  vars(a * b)        List variables and values",
+    "",
+    "Financial (paid out < 0, received > 0):",
+    "  fv(r,n,pmt,[pv])   Future value",
+    "  pv(r,n,pmt,[fv])   Present value",
+    "  pmt(r,n,pv,[fv])   Payment per period",
+    "  npv(r,cf1,...)     Net present value",
+    "  irr(cf0,cf1,...)   Internal rate of return",
+    "  split(t,n)         Shares of t split n ways",
+    "  ratio(a,b,t)       Parts of t in a:b",
+    "",
+    "Data:",
+    "  sum(a, b, ...)     Sum of the values",
+    "  avg(a, b, ...)     Mean of the values",
+    "  count(a, b, ...)   Number of values",
+    "  csv(\"f\", \"col\")    Column of a CSV file, as a list",
+    "  list(a, b, ...)    List of values, e.g. x = list(1, 2)",
+    "  sum(csv(\"f\", \"c\")) Total of a column",
+    "",
+    "Shell (needs :set shell on):",
+    "  `wc -l < notes`    Number printed by a command",
+    "  shell(\"cmd\")       Same as `cmd`",
+    "",
     "Constants:",
     "  pi         3.14159...",
     "  e          2.71828...",
@@ -804,16 +1936,41 @@ const HELP_FUNCTION_REFERENCE: &[&str] = &[
     "",
 ];
 
+/// Help content lines for the Unit Conversion section.
+const HELP_UNIT_REFERENCE: &[&str] = &[
+    "=== Unit Conversion ===",
+    "",
+    "  convert(5, \"mi\", \"km\")  Convert between units",
+    "  Names match loosely: \"miles\", \"Kilometers\", \"fahr\"",
+    "",
+    "Length:",
+    "  m km cm mm mi yd ft in nmi",
+    "Mass:",
+    "  kg g mg t lb oz st",
+    "Temperature:",
+    "  K C F",
+    "Volume:",
+    "  l ml m3 gal qt pt cup floz tbsp tsp",
+    "Data:",
+    "  b B kB MB GB TB KiB MiB GiB TiB kbit Mbit Gbit",
+    "Speed:",
+    "  m/s km/h mph kn ft/s",
+    "",
+];
+
 /// Returns all help content lines combined.
 #[must_use]
 pub fn help_content_lines() -> Vec<&'static str> {
-    let mut lines = Vec::with_capacity(HELP_GENERAL_USAGE.len() + HELP_FUNCTION_REFERENCE.len());
+    let mut lines = Vec::with_capacity(
+        HELP_GENERAL_USAGE.len() + HELP_FUNCTION_REFERENCE.len() + HELP_UNIT_REFERENCE.len(),
+    );
     lines.extend_from_slice(HELP_GENERAL_USAGE);
     lines.extend_from_slice(HELP_FUNCTION_REFERENCE);
+    lines.extend_from_slice(HELP_UNIT_REFERENCE);
     lines
 }
 
-pub const HELP_CONTENT_HEIGHT: usize = 64;
+pub const HELP_CONTENT_HEIGHT: usize = 201;
 
 /// Calculates the centered area for an overlay of the given dimensions.
 ///
@@ -849,77 +2006,1074 @@ pub fn build_help_content_lines(scroll_offset: usize, visible_height: usize) ->
     let start = scroll_offset.min(all_lines.len());
     let end = (scroll_offset + visible_height).min(all_lines.len());
 
-    all_lines[start..end]
+    all_lines[start..end]
+        .iter()
+        .map(|&line| {
+            if line.starts_with("===") {
+                Line::from(Span::styled(line, header_style))
+            } else {
+                Line::from(line)
+            }
+        })
+        .collect()
+}
+
+/// Renders the help overlay panel.
+///
+/// Displays a centered, bordered panel with title "Help" containing
+/// the help content. Supports vertical scrolling.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The full screen area
+/// * `scroll_offset` - The scroll position for the content
+#[allow(clippy::manual_checked_ops)]
+pub fn render_help_overlay(frame: &mut Frame, area: Rect, scroll_offset: usize) {
+    use ratatui::widgets::Clear;
+
+    let overlay_area = centered_rect(
+        area,
+        HELP_OVERLAY_WIDTH_PERCENT,
+        HELP_OVERLAY_HEIGHT_PERCENT,
+    );
+
+    frame.render_widget(Clear, overlay_area);
+
+    let visible_height = overlay_area.height.saturating_sub(2) as usize;
+
+    let content_lines = build_help_content_lines(scroll_offset, visible_height);
+    let content = Text::from(content_lines);
+
+    let total_lines = HELP_CONTENT_HEIGHT;
+    let scroll_info = if total_lines > visible_height {
+        let max_scroll = total_lines.saturating_sub(visible_height);
+        let percent = if max_scroll > 0 {
+            (scroll_offset * 100) / max_scroll
+        } else {
+            0
+        };
+        format!(" [{percent}%] ")
+    } else {
+        String::new()
+    };
+
+    let title = if scroll_info.is_empty() {
+        " Help ".to_string()
+    } else {
+        format!(" Help {scroll_info}")
+    };
+
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default());
+
+    let paragraph = Paragraph::new(content).block(block);
+
+    frame.render_widget(paragraph, overlay_area);
+}
+
+/// Builds styled lines for the result inspection popup.
+///
+/// Shows the full-precision value, alternative representations that apply
+/// to the value, and the parsed expression tree.
+#[must_use]
+pub fn build_inspection_lines(inspection: &Inspection) -> Vec<Line<'static>> {
+    let label_style = Style::default().add_modifier(Modifier::BOLD);
+    let row = |label: &'static str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{label:<12}"), label_style),
+            Span::raw(value),
+        ])
+    };
+
+    let mut lines = vec![
+        row("Value", inspection.full_precision.clone()),
+        row("Scientific", inspection.scientific.clone()),
+    ];
+    if let Some(hex) = &inspection.hex {
+        lines.push(row("Hex", hex.clone()));
+    }
+    if let Some(binary) = &inspection.binary {
+        lines.push(row("Binary", binary.clone()));
+    }
+    if let Some(fraction) = &inspection.fraction {
+        lines.push(row("Fraction", fraction.clone()));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled("Expression tree", label_style)));
+    lines.extend(inspection.ast.iter().map(|l| Line::from(l.clone())));
+    lines
+}
+
+/// Renders the result inspection popup.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The full screen area
+/// * `inspection` - The inspected line details
+pub fn render_inspection_popup(frame: &mut Frame, area: Rect, inspection: &Inspection) {
+    use ratatui::widgets::Clear;
+
+    let popup_area = centered_rect(
+        area,
+        INSPECTION_POPUP_WIDTH_PERCENT,
+        INSPECTION_POPUP_HEIGHT_PERCENT,
+    );
+
+    frame.render_widget(Clear, popup_area);
+
+    let title = inspection.name.as_ref().map_or_else(
+        || " Inspect ".to_string(),
+        |name| format!(" Inspect {name} "),
+    );
+
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default());
+
+    let paragraph = Paragraph::new(Text::from(build_inspection_lines(inspection))).block(block);
+
+    frame.render_widget(paragraph, popup_area);
+}
+
+/// Builds styled lines for the explain popup.
+///
+/// Each reduction step is shown on its own line, prefixed with an arrow
+/// after the first. An error that stopped the reduction is shown last.
+#[must_use]
+pub fn build_explanation_lines(explanation: &Explanation) -> Vec<Line<'static>> {
+    let step_style = Style::default().add_modifier(Modifier::DIM);
+    let error_style = Style::default().fg(Color::Red);
+
+    let mut lines: Vec<Line<'static>> = explanation
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let marker = if i == 0 { "  " } else { "→ " };
+            Line::from(vec![
+                Span::styled(marker, step_style),
+                Span::raw(step.clone()),
+            ])
+        })
+        .collect();
+
+    if let Some(error) = &explanation.error {
+        lines.push(Line::from(Span::styled(format!("✗ {error}"), error_style)));
+    }
+    lines
+}
+
+/// Renders the explain popup.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The full screen area
+/// * `explanation` - The reduction steps for the cursor line
+pub fn render_explanation_popup(frame: &mut Frame, area: Rect, explanation: &Explanation) {
+    use ratatui::widgets::Clear;
+
+    let popup_area = centered_rect(
+        area,
+        INSPECTION_POPUP_WIDTH_PERCENT,
+        INSPECTION_POPUP_HEIGHT_PERCENT,
+    );
+
+    frame.render_widget(Clear, popup_area);
+
+    let title = explanation.name.as_ref().map_or_else(
+        || " Explain ".to_string(),
+        |name| format!(" Explain {name} "),
+    );
+
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default());
+
+    let paragraph = Paragraph::new(Text::from(build_explanation_lines(explanation))).block(block);
+
+    frame.render_widget(paragraph, popup_area);
+}
+
+/// Renders the dependency graph popup.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The full screen area
+/// * `graph` - The dependency tree, one drawn line per entry
+pub fn render_dependency_popup(frame: &mut Frame, area: Rect, graph: &[String]) {
+    use ratatui::widgets::Clear;
+
+    let popup_area = centered_rect(
+        area,
+        INSPECTION_POPUP_WIDTH_PERCENT,
+        INSPECTION_POPUP_HEIGHT_PERCENT,
+    );
+
+    frame.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .title(" Dependencies ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default());
+
+    let lines: Vec<Line<'static>> = graph.iter().map(|line| Line::from(line.clone())).collect();
+    let paragraph = Paragraph::new(Text::from(lines)).block(block);
+
+    frame.render_widget(paragraph, popup_area);
+}
+
+/// Width of the debug overlay in columns (including borders).
+const DEBUG_OVERLAY_WIDTH: u16 = 36;
+
+/// Builds the lines of the debug overlay: the frame and evaluation times,
+/// followed by the most recent events.
+///
+/// # Arguments
+/// * `frame_time` - How long drawing the last frame took
+/// * `eval_time` - How long evaluating the buffer took for the last frame
+/// * `events` - The most recent events, oldest first
+#[must_use]
+pub fn build_debug_lines<'a>(
+    frame_time: Duration,
+    eval_time: Duration,
+    events: impl Iterator<Item = &'a str>,
+) -> Vec<Line<'a>> {
+    let dim = Style::default().add_modifier(Modifier::DIM);
+    let mut lines = vec![
+        Line::from(format!("frame {}", format_duration(frame_time))),
+        Line::from(format!("eval  {}", format_duration(eval_time))),
+    ];
+    lines.extend(events.map(|event| Line::styled(event, dim)));
+    lines
+}
+
+/// Renders the debug overlay in the top right corner of `area`.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The full screen area
+/// * `lines` - The lines built by `build_debug_lines`
+pub fn render_debug_overlay(frame: &mut Frame, area: Rect, lines: Vec<Line<'_>>) {
+    use ratatui::widgets::Clear;
+
+    let width = DEBUG_OVERLAY_WIDTH.min(area.width);
+    let height = u16::try_from(lines.len() + 2)
+        .unwrap_or(u16::MAX)
+        .min(area.height);
+    let overlay_area = Rect::new(area.right() - width, area.y, width, height);
+
+    frame.render_widget(Clear, overlay_area);
+
+    let block = Block::default()
+        .title(" Debug ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default());
+
+    frame.render_widget(Paragraph::new(Text::from(lines)).block(block), overlay_area);
+}
+
+/// Renders the `:version` popup overlay.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The full screen area
+/// * `info` - The version, build and file locations, one line per entry
+pub fn render_version_popup(frame: &mut Frame, area: Rect, info: &[String]) {
+    use ratatui::widgets::Clear;
+
+    let popup_area = centered_rect(
+        area,
+        INSPECTION_POPUP_WIDTH_PERCENT,
+        INSPECTION_POPUP_HEIGHT_PERCENT,
+    );
+
+    frame.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .title(" Version ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default());
+
+    let lines: Vec<Line<'static>> = info.iter().map(|line| Line::from(line.clone())).collect();
+    let paragraph = Paragraph::new(Text::from(lines)).block(block);
+
+    frame.render_widget(paragraph, popup_area);
+}
+
+/// Renders the table popup: one page of the rows of a `table` result under
+/// column headers naming the variable and the function, with the page
+/// number and keys below.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The full screen area
+/// * `table` - The table being shown
+/// * `rows` - The rows of the page in view
+/// * `page` - The page in view, from 0
+/// * `page_count` - The number of pages
+/// * `format` - How numbers are displayed
+pub fn render_table_popup(
+    frame: &mut Frame,
+    area: Rect,
+    table: &Table,
+    rows: &[(f64, f64)],
+    page: usize,
+    page_count: usize,
+    format: NumberFormat,
+) {
+    use ratatui::layout::Constraint;
+    use ratatui::widgets::{Cell, Clear, Row, Table as TableWidget};
+
+    let popup_area = centered_rect(
+        area,
+        INSPECTION_POPUP_WIDTH_PERCENT,
+        INSPECTION_POPUP_HEIGHT_PERCENT,
+    );
+
+    frame.render_widget(Clear, popup_area);
+
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+    let block = Block::default()
+        .title(" Table ")
+        .title_bottom(Line::from(vec![
+            Span::raw(format!(" Page {} of {page_count}  ", page + 1)),
+            Span::styled("PGUP/PGDN", key_style),
+            Span::raw(": page  "),
+            Span::styled("ESC", key_style),
+            Span::raw(": close "),
+        ]))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default());
+
+    let points: Vec<String> = rows.iter().map(|(x, _)| format.format(*x)).collect();
+    let width = points
+        .iter()
+        .map(|point| point.chars().count())
+        .chain([table.variable.chars().count()])
+        .max()
+        .unwrap_or(0);
+    let header = Row::new([
+        Cell::from(Line::from(table.variable.clone()).alignment(Alignment::Right)),
+        Cell::from(table.function.clone()),
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED));
+    let body = points.into_iter().zip(rows).map(|(point, (_, y))| {
+        Row::new([
+            Cell::from(Line::from(point).alignment(Alignment::Right)),
+            Cell::from(format.format(*y)),
+        ])
+    });
+    let widget = TableWidget::new(
+        body,
+        [
+            Constraint::Length(u16::try_from(width).unwrap_or(u16::MAX)),
+            Constraint::Fill(1),
+        ],
+    )
+    .header(header)
+    .column_spacing(3)
+    .block(block);
+
+    frame.render_widget(widget, popup_area);
+}
+
+/// Builds styled lines for a page of the `:diff` popup: added lines green,
+/// removed ones red, changed ones yellow and changed results cyan.
+#[must_use]
+pub fn build_diff_lines(rows: &[DiffLine]) -> Vec<Line<'_>> {
+    rows.iter()
+        .map(|row| {
+            let style = match row.kind {
+                DiffKind::Summary => Style::default().add_modifier(Modifier::BOLD),
+                DiffKind::Added => Style::default().fg(Color::Green),
+                DiffKind::Removed => Style::default().fg(Color::Red),
+                DiffKind::Changed => Style::default().fg(Color::Yellow),
+                DiffKind::Result => Style::default().fg(Color::Cyan),
+            };
+            Line::styled(row.text.as_str(), style)
+        })
+        .collect()
+}
+
+/// Renders the `:diff` popup: one page of the differences between the
+/// worksheet and its saved version, with the page number and keys below.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The full screen area
+/// * `rows` - The rows of the page in view
+/// * `page` - The page in view, from 0
+/// * `page_count` - The number of pages
+pub fn render_diff_popup(
+    frame: &mut Frame,
+    area: Rect,
+    rows: &[DiffLine],
+    page: usize,
+    page_count: usize,
+) {
+    use ratatui::widgets::Clear;
+
+    let popup_area = centered_rect(
+        area,
+        INSPECTION_POPUP_WIDTH_PERCENT,
+        INSPECTION_POPUP_HEIGHT_PERCENT,
+    );
+
+    frame.render_widget(Clear, popup_area);
+
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+    let block = Block::default()
+        .title(" Diff ")
+        .title_bottom(Line::from(vec![
+            Span::raw(format!(" Page {} of {page_count}  ", page + 1)),
+            Span::styled("PGUP/PGDN", key_style),
+            Span::raw(": page  "),
+            Span::styled("ESC", key_style),
+            Span::raw(": close "),
+        ]))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default());
+
+    let paragraph = Paragraph::new(Text::from(build_diff_lines(rows))).block(block);
+
+    frame.render_widget(paragraph, popup_area);
+}
+
+/// Builds styled lines for the `:new` template picker: one line per
+/// template with the chosen one reversed, and the keys below.
+///
+/// # Arguments
+/// * `names` - The names of the templates
+/// * `selected` - The index of the chosen template
+#[must_use]
+pub fn build_template_picker_lines(names: &[String], selected: usize) -> Vec<Line<'_>> {
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines: Vec<Line<'_>> = names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            if i == selected {
+                Line::from(Span::styled(
+                    format!("> {name}"),
+                    Style::default().add_modifier(Modifier::REVERSED),
+                ))
+            } else {
+                Line::from(format!("  {name}"))
+            }
+        })
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::styled("↑↓", key_style),
+        Span::raw(": choose  "),
+        Span::styled("ENTER", key_style),
+        Span::raw(": start  "),
+        Span::styled("ESC", key_style),
+        Span::raw(": cancel"),
+    ]));
+    lines
+}
+
+/// Renders the `:new` template picker popup.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The full screen area
+/// * `names` - The names of the templates
+/// * `selected` - The index of the chosen template
+pub fn render_template_picker(frame: &mut Frame, area: Rect, names: &[String], selected: usize) {
+    use ratatui::widgets::Clear;
+
+    let popup_area = centered_rect(
+        area,
+        INSPECTION_POPUP_WIDTH_PERCENT,
+        INSPECTION_POPUP_HEIGHT_PERCENT,
+    );
+
+    frame.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .title(" New worksheet from template ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default());
+
+    let paragraph =
+        Paragraph::new(Text::from(build_template_picker_lines(names, selected))).block(block);
+
+    frame.render_widget(paragraph, popup_area);
+}
+
+/// Builds styled lines for the crash recovery prompt.
+///
+/// # Arguments
+/// * `line_count` - The number of buffer lines found in the crash report
+#[must_use]
+pub fn build_recovery_prompt_lines(line_count: usize) -> Vec<Line<'static>> {
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+    let noun = if line_count == 1 { "line" } else { "lines" };
+
+    vec![
+        Line::from("Crabculator did not exit cleanly last time."),
+        Line::from(format!(
+            "Restore the {line_count} {noun} from the crash report?"
+        )),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Y", key_style),
+            Span::raw(": restore  "),
+            Span::styled("N", key_style),
+            Span::raw("/"),
+            Span::styled("ESC", key_style),
+            Span::raw(": discard"),
+        ]),
+    ]
+}
+
+/// Builds styled lines for the prompt shown when the buffer file was
+/// changed on disk, such as by another instance.
+///
+/// # Arguments
+/// * `line_count` - The number of lines in the file on disk
+#[must_use]
+pub fn build_conflict_prompt_lines(line_count: usize) -> Vec<Line<'static>> {
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+    let noun = if line_count == 1 { "line" } else { "lines" };
+
+    vec![
+        Line::from("The buffer file was changed on disk."),
+        Line::from(format!("It now has {line_count} {noun}.")),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("R", key_style),
+            Span::raw(": reload from disk  "),
+            Span::styled("O", key_style),
+            Span::raw(": overwrite with this buffer"),
+        ]),
+    ]
+}
+
+/// Builds styled lines for the prompt confirming to quit with changes that
+/// would be lost.
+///
+/// # Arguments
+/// * `line_count` - The number of lines in the buffer
+#[must_use]
+pub fn build_quit_prompt_lines(line_count: usize) -> Vec<Line<'static>> {
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+    let noun = if line_count == 1 { "line" } else { "lines" };
+
+    vec![
+        Line::from("This buffer is not saved (--no-state)."),
+        Line::from(format!("Quit and discard its {line_count} {noun}?")),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Y", key_style),
+            Span::raw(": quit  "),
+            Span::styled("N", key_style),
+            Span::raw("/"),
+            Span::styled("ESC", key_style),
+            Span::raw(": keep editing"),
+        ]),
+    ]
+}
+
+/// Renders a centered, bordered prompt overlay with the given title.
+fn render_prompt(frame: &mut Frame, area: Rect, title: &str, lines: Vec<Line<'static>>) {
+    use ratatui::widgets::Clear;
+
+    let width = area.width * RECOVERY_PROMPT_WIDTH_PERCENT / 100;
+    let height = RECOVERY_PROMPT_HEIGHT.min(area.height);
+    let prompt_area = Rect::new(
+        area.x + area.width.saturating_sub(width) / 2,
+        area.y + area.height.saturating_sub(height) / 2,
+        width,
+        height,
+    );
+
+    frame.render_widget(Clear, prompt_area);
+
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default());
+
+    let paragraph = Paragraph::new(Text::from(lines)).block(block);
+
+    frame.render_widget(paragraph, prompt_area);
+}
+
+/// Renders the crash recovery prompt overlay.
+///
+/// Displays a centered, bordered panel asking whether the buffer from the
+/// previous session's crash report should be restored.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The full screen area
+/// * `line_count` - The number of buffer lines found in the crash report
+pub fn render_recovery_prompt(frame: &mut Frame, area: Rect, line_count: usize) {
+    render_prompt(
+        frame,
+        area,
+        " Recover ",
+        build_recovery_prompt_lines(line_count),
+    );
+}
+
+/// Renders the prompt asking whether to reload or overwrite a buffer file
+/// changed on disk.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The full screen area
+/// * `line_count` - The number of lines in the file on disk
+pub fn render_conflict_prompt(frame: &mut Frame, area: Rect, line_count: usize) {
+    render_prompt(
+        frame,
+        area,
+        " Changed on disk ",
+        build_conflict_prompt_lines(line_count),
+    );
+}
+
+/// Renders the prompt confirming to quit with changes that would be lost.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The full screen area
+/// * `line_count` - The number of lines in the buffer
+pub fn render_quit_prompt(frame: &mut Frame, area: Rect, line_count: usize) {
+    render_prompt(frame, area, " Quit ", build_quit_prompt_lines(line_count));
+}
+
+/// Builds styled lines for the settings popup: each setting with its value,
+/// followed by the `:set` command being typed.
+///
+/// # Arguments
+/// * `entries` - Each setting name with its current value
+/// * `prompt` - The command typed so far
+#[must_use]
+pub fn build_settings_lines<'a>(entries: &'a [(String, String)], prompt: &'a str) -> Vec<Line<'a>> {
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+    let hint_style = Style::default().add_modifier(Modifier::DIM);
+
+    let mut lines: Vec<Line<'a>> = entries
         .iter()
-        .map(|&line| {
-            if line.starts_with("===") {
-                Line::from(Span::styled(line, header_style))
-            } else {
-                Line::from(line)
-            }
+        .map(|(key, value)| {
+            Line::from(vec![
+                Span::raw(format!("{key:<SETTINGS_KEY_WIDTH$}")),
+                Span::styled(value.as_str(), key_style),
+            ])
         })
-        .collect()
+        .collect();
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::raw(":"),
+        Span::raw(prompt),
+        Span::styled(" ", Style::default().add_modifier(Modifier::REVERSED)),
+    ]));
+    lines.push(Line::from(Span::styled(
+        "set key value, ENTER: apply, ESC: close",
+        hint_style,
+    )));
+    lines
 }
 
-/// Renders the help overlay panel.
-///
-/// Displays a centered, bordered panel with title "Help" containing
-/// the help content. Supports vertical scrolling.
+/// Renders the settings popup.
 ///
 /// # Arguments
 /// * `frame` - The ratatui Frame to render to
 /// * `area` - The full screen area
-/// * `scroll_offset` - The scroll position for the content
-pub fn render_help_overlay(frame: &mut Frame, area: Rect, scroll_offset: usize) {
+/// * `entries` - Each setting name with its current value
+/// * `prompt` - The command typed so far
+pub fn render_settings_popup(
+    frame: &mut Frame,
+    area: Rect,
+    entries: &[(String, String)],
+    prompt: &str,
+) {
     use ratatui::widgets::Clear;
 
-    let overlay_area = centered_rect(
-        area,
-        HELP_OVERLAY_WIDTH_PERCENT,
-        HELP_OVERLAY_HEIGHT_PERCENT,
+    let lines = build_settings_lines(entries, prompt);
+    let width = area.width * SETTINGS_POPUP_WIDTH_PERCENT / 100;
+    let height = u16::try_from(lines.len() + 2)
+        .unwrap_or(u16::MAX)
+        .min(area.height);
+    let popup_area = Rect::new(
+        area.x + area.width.saturating_sub(width) / 2,
+        area.y + area.height.saturating_sub(height) / 2,
+        width,
+        height,
     );
 
-    frame.render_widget(Clear, overlay_area);
+    frame.render_widget(Clear, popup_area);
 
-    let visible_height = overlay_area.height.saturating_sub(2) as usize;
+    let block = Block::default()
+        .title(" Settings ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default());
 
-    let content_lines = build_help_content_lines(scroll_offset, visible_height);
-    let content = Text::from(content_lines);
+    let paragraph = Paragraph::new(Text::from(lines)).block(block);
 
-    let total_lines = HELP_CONTENT_HEIGHT;
-    let scroll_info = if total_lines > visible_height {
-        let max_scroll = total_lines.saturating_sub(visible_height);
-        let percent = if max_scroll > 0 {
-            (scroll_offset * 100) / max_scroll
-        } else {
-            0
-        };
-        format!(" [{percent}%] ")
-    } else {
-        String::new()
-    };
+    frame.render_widget(paragraph, popup_area);
+}
 
-    let title = if scroll_info.is_empty() {
-        " Help ".to_string()
-    } else {
-        format!(" Help {scroll_info}")
-    };
+/// Builds styled lines for the first-run welcome banner.
+///
+/// # Arguments
+/// * `message` - The welcome text
+#[must_use]
+pub fn build_welcome_lines(message: &str) -> Vec<Line<'_>> {
+    let key_style = Style::default().add_modifier(Modifier::BOLD);
+
+    vec![
+        Line::from(message),
+        Line::from(vec![
+            Span::styled("CTRL+H", key_style),
+            Span::raw(": help  "),
+            Span::styled("ESC", key_style),
+            Span::raw(": dismiss"),
+        ]),
+    ]
+}
+
+/// Renders the welcome banner across the bottom of `area`.
+///
+/// # Arguments
+/// * `frame` - The ratatui Frame to render to
+/// * `area` - The content area the banner is drawn over
+/// * `message` - The welcome text
+pub fn render_welcome_banner(frame: &mut Frame, area: Rect, message: &str) {
+    use ratatui::widgets::{Clear, Wrap};
+
+    let height = WELCOME_BANNER_HEIGHT.min(area.height);
+    let banner_area = Rect::new(area.x, area.y + area.height - height, area.width, height);
+
+    frame.render_widget(Clear, banner_area);
 
     let block = Block::default()
-        .title(title)
+        .title(" Welcome ")
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
         .border_style(Style::default());
 
-    let paragraph = Paragraph::new(content).block(block);
+    let paragraph = Paragraph::new(Text::from(build_welcome_lines(message)))
+        .wrap(Wrap { trim: true })
+        .block(block);
 
-    frame.render_widget(paragraph, overlay_area);
+    frame.render_widget(paragraph, banner_area);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::eval::ErrorSpan;
+    use crate::editor::Selection;
+    use crate::eval::{ErrorSpan, evaluate_all_lines};
+
+    #[test]
+    fn test_build_recovery_prompt_lines_mentions_line_count() {
+        let lines = build_recovery_prompt_lines(3);
+        let text: String = lines[1].spans.iter().map(|s| s.content.as_ref()).collect();
+
+        assert!(text.contains("3 lines"));
+    }
+
+    #[test]
+    fn test_build_recovery_prompt_lines_singular() {
+        let lines = build_recovery_prompt_lines(1);
+        let text: String = lines[1].spans.iter().map(|s| s.content.as_ref()).collect();
+
+        assert!(text.contains("1 line "));
+    }
+
+    #[test]
+    fn test_build_conflict_prompt_lines_offers_reload_and_overwrite() {
+        let text: Vec<String> = build_conflict_prompt_lines(4)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(text[1], "It now has 4 lines.");
+        assert_eq!(
+            text[3],
+            "R: reload from disk  O: overwrite with this buffer"
+        );
+    }
+
+    #[test]
+    fn test_build_debug_lines_shows_times_and_events() {
+        let lines = build_debug_lines(
+            Duration::from_micros(1500),
+            Duration::from_micros(250),
+            ["InsertChar('1')", "Quit"].into_iter(),
+        );
+        let text: Vec<String> = lines.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            text,
+            ["frame 1.5ms", "eval  250µs", "InsertChar('1')", "Quit"]
+        );
+    }
+
+    #[test]
+    fn test_build_quit_prompt_lines_offers_quit_and_keep_editing() {
+        let text: Vec<String> = build_quit_prompt_lines(1)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(text[1], "Quit and discard its 1 line?");
+        assert_eq!(text[3], "Y: quit  N/ESC: keep editing");
+    }
+
+    #[test]
+    fn test_render_table_popup_shows_headers_and_page() {
+        use ratatui::Terminal;
+        use ratatui::backend::TestBackend;
+
+        let mut terminal = Terminal::new(TestBackend::new(60, 20)).unwrap();
+        let table = Table {
+            variable: "x".to_string(),
+            function: "x ^ 2".to_string(),
+            rows: vec![(10.0, 100.0), (11.0, 121.0)],
+        };
+
+        terminal
+            .draw(|frame| {
+                render_table_popup(
+                    frame,
+                    frame.area(),
+                    &table,
+                    &table.rows,
+                    1,
+                    2,
+                    NumberFormat::default(),
+                );
+            })
+            .unwrap();
+
+        let screen: Vec<String> = (0..20)
+            .map(|y| {
+                (0..60)
+                    .map(|x| terminal.backend().buffer()[(x, y)].symbol().to_string())
+                    .collect()
+            })
+            .collect();
+        assert!(
+            screen.iter().any(|row| row.contains(" x   x ^ 2")),
+            "{screen:#?}"
+        );
+        assert!(
+            screen.iter().any(|row| row.contains("10   100")),
+            "{screen:#?}"
+        );
+        assert!(
+            screen.iter().any(|row| row.contains("Page 2 of 2")),
+            "{screen:#?}"
+        );
+    }
+
+    #[test]
+    fn test_build_template_picker_lines_marks_choice() {
+        let names = ["loan".to_string(), "tip".to_string()];
+        let lines = build_template_picker_lines(&names, 1);
+        let text: Vec<String> = lines.iter().map(ToString::to_string).collect();
+
+        assert_eq!(text[0], "  loan");
+        assert_eq!(text[1], "> tip");
+        assert!(
+            lines[1].spans[0]
+                .style
+                .add_modifier
+                .contains(Modifier::REVERSED)
+        );
+        assert_eq!(text[3], "↑↓: choose  ENTER: start  ESC: cancel");
+    }
+
+    #[test]
+    fn test_build_settings_lines_lists_values_and_prompt() {
+        let entries = [
+            ("precision".to_string(), "4".to_string()),
+            ("wrap".to_string(), "on".to_string()),
+        ];
+        let lines = build_settings_lines(&entries, "set wrap off");
+        let text =
+            |line: &Line| -> String { line.spans.iter().map(|s| s.content.as_ref()).collect() };
+
+        assert_eq!(text(&lines[0]), "precision   4");
+        assert_eq!(text(&lines[1]), "wrap        on");
+        assert_eq!(text(&lines[3]), ":set wrap off ");
+    }
+
+    #[test]
+    fn test_build_welcome_lines_mention_dismiss_key() {
+        let lines = build_welcome_lines("Hello");
+        let text: String = lines[1].spans.iter().map(|s| s.content.as_ref()).collect();
+
+        assert_eq!(lines[0].spans[0].content, "Hello");
+        assert!(text.contains("ESC: dismiss"));
+    }
+
+    #[test]
+    fn test_build_notification_text_is_italic() {
+        let line = build_notification_text("Saved");
+
+        assert_eq!(line.spans[0].content, "Saved");
+        assert!(line.spans[0].style.add_modifier.contains(Modifier::ITALIC));
+    }
+
+    #[test]
+    fn test_build_inspection_lines_shows_applicable_representations() {
+        let inspection = crate::eval::inspect::inspect_line("10", &crate::eval::EvalContext::new())
+            .expect("should inspect");
+
+        let text: Vec<String> = build_inspection_lines(&inspection)
+            .iter()
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect();
+
+        assert!(
+            text.iter()
+                .any(|l| l.starts_with("Hex") && l.ends_with("0xA"))
+        );
+        assert!(
+            text.iter()
+                .any(|l| l.starts_with("Binary") && l.ends_with("0b1010"))
+        );
+        assert!(!text.iter().any(|l| l.starts_with("Fraction")));
+        assert!(text.iter().any(|l| l == "Expression tree"));
+    }
+
+    #[test]
+    fn test_build_explanation_lines_marks_steps_and_error() {
+        let explanation = Explanation {
+            name: None,
+            steps: vec!["1 + y".to_string()],
+            error: Some("undefined variable 'y'".to_string()),
+        };
+
+        let lines = build_explanation_lines(&explanation);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].spans[0].content, "✗ undefined variable 'y'");
+    }
+
+    #[test]
+    fn test_visible_input_lines_highlight_definition_gutter() {
+        let lines = vec!["rate = 2".to_string(), "rate * 3".to_string()];
+        let results = evaluate_all_lines(lines.iter().map(String::as_str));
+
+        let (output, _) = build_visible_input_lines_with_gutter(
+            &lines,
+            &results,
+            0,
+            10,
+            0,
+            80,
+            &RowMap::new(&results, true),
+            Gutter::default(),
+            HighlightOptions {
+                symbol: Some("rate"),
+                ..HighlightOptions::default()
+            },
+        );
+
+        assert_eq!(output[0].spans[0].style, definition_gutter_style());
+        assert_eq!(output[1].spans[0].style, gutter_style());
+    }
+
+    #[test]
+    fn test_visible_input_lines_reverse_block() {
+        let lines = vec!["x = 10".to_string(), "y".to_string(), "z = 3".to_string()];
+        let results = evaluate_all_lines(lines.iter().map(String::as_str));
+        let reversed = |block: editor::Block| -> Vec<String> {
+            let (output, _) = build_visible_input_lines_with_gutter(
+                &lines,
+                &results,
+                0,
+                10,
+                0,
+                80,
+                &RowMap::new(&results, false),
+                Gutter::default(),
+                HighlightOptions {
+                    block: Some(block),
+                    ..HighlightOptions::default()
+                },
+            );
+            output
+                .iter()
+                .map(|line| {
+                    line.spans
+                        .iter()
+                        .filter(|span| span.style.add_modifier.contains(Modifier::REVERSED))
+                        .map(|span| span.content.as_ref())
+                        .collect()
+                })
+                .collect()
+        };
+        let mut block = editor::Block {
+            top: 0,
+            bottom: 2,
+            left: 4,
+            right: 6,
+            to_end: false,
+        };
+
+        assert_eq!(reversed(block), ["10", "", "3"]);
+        block.right = 4;
+        assert_eq!(reversed(block), ["1", "", "3"]);
+        block.to_end = true;
+        assert_eq!(reversed(block), [" ", " ", " "]);
+    }
+
+    #[test]
+    fn test_visible_input_lines_reverse_selection() {
+        let lines = vec!["1 + 2".to_string(), "2 * (3 + 4)".to_string()];
+        let results = evaluate_all_lines(lines.iter().map(String::as_str));
+
+        let (output, _) = build_visible_input_lines_with_gutter(
+            &lines,
+            &results,
+            0,
+            10,
+            2,
+            80,
+            &RowMap::new(&results, true),
+            Gutter::default(),
+            HighlightOptions {
+                selection: Some(Selection::new(1, 4, 11)),
+                ..HighlightOptions::default()
+            },
+        );
+
+        let selected: String = output[1]
+            .spans
+            .iter()
+            .filter(|span| span.style.add_modifier.contains(Modifier::REVERSED))
+            .map(|span| span.content.as_ref())
+            .collect();
+        assert_eq!(selected, "(3 + 4)");
+        assert!(
+            !output[0]
+                .spans
+                .iter()
+                .any(|span| span.style.add_modifier.contains(Modifier::REVERSED))
+        );
+    }
 
     #[test]
     fn test_format_result_integer_value() {
@@ -963,6 +3117,28 @@ mod tests {
         assert_eq!(format_result(&result), Some("rate = 1.23456".to_string()));
     }
 
+    #[test]
+    fn test_format_result_bytes_uses_size_unit() {
+        let result = LineResult::Bytes {
+            name: Some("disk".to_string()),
+            value: 1_910_612_736.0,
+            binary: true,
+        };
+        assert_eq!(format_result(&result), Some("disk = 1.78 GiB".to_string()));
+    }
+
+    #[test]
+    fn test_format_result_money_shows_currency() {
+        let result = LineResult::Money {
+            name: Some("rent".to_string()),
+            money: Money {
+                minor: 125_000,
+                currency: crate::eval::money::currency_by_code("EUR").unwrap(),
+            },
+        };
+        assert_eq!(format_result(&result), Some("rent = €1,250.00".to_string()));
+    }
+
     #[test]
     fn test_format_result_empty_returns_none() {
         let result = LineResult::Empty;
@@ -993,6 +3169,52 @@ mod tests {
         assert_eq!(format_value(value), "0.001");
     }
 
+    #[test]
+    fn test_number_format_rounds_to_precision() {
+        let format = NumberFormat {
+            precision: Some(2),
+            separators: false,
+        };
+        assert_eq!(format.format(std::f64::consts::PI), "3.14");
+        assert_eq!(format.format(2.5), "2.5");
+        assert_eq!(format.format(1.999), "2");
+        assert_eq!(format.format(-0.001), "0");
+        assert_eq!(format.format(1e20), "100000000000000000000");
+    }
+
+    #[test]
+    fn test_number_format_groups_thousands() {
+        let format = NumberFormat {
+            precision: None,
+            separators: true,
+        };
+        assert_eq!(format.format(1_234_567.5), "1,234,567.5");
+        assert_eq!(format.format(-1000.0), "-1,000");
+        assert_eq!(format.format(999.0), "999");
+        assert_eq!(format.format(f64::INFINITY), "inf");
+    }
+
+    #[test]
+    fn test_format_text_result_with_and_without_name() {
+        let result = LineResult::Text {
+            name: Some("year".to_string()),
+            value: 2024.0,
+            text: "MMXXIV".to_string(),
+        };
+        assert_eq!(format_result(&result), Some("year = MMXXIV".to_string()));
+        assert_eq!(
+            format_result_for_memory_pane(
+                &LineResult::Text {
+                    name: None,
+                    value: 4.0,
+                    text: "four".to_string(),
+                },
+                NumberFormat::default()
+            ),
+            Some("four".to_string())
+        );
+    }
+
     #[test]
     fn test_format_value_truncated_short_number() {
         // 12 chars or fewer should display in full
@@ -1056,6 +3278,87 @@ mod tests {
         assert_eq!(output.len(), 2);
     }
 
+    #[test]
+    fn test_build_input_lines_adds_warning_message() {
+        let lines = vec!["1e20 + 1".to_string()];
+        let results = vec![LineResult::Warning {
+            result: Box::new(LineResult::Value(1e20)),
+            warning: EvalError::warning("precision loss"),
+        }];
+
+        let output = build_input_lines(&lines, &results);
+
+        assert_eq!(output.len(), 2);
+        assert_eq!(output[1].to_string(), "  ! [W000] precision loss");
+        assert_eq!(output[1].spans[0].style.fg, Some(Color::Yellow));
+        assert_eq!(
+            build_result_lines(&results)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["100000000000000000000", ""]
+        );
+    }
+
+    #[test]
+    fn test_hint_line_borrows_message_and_padding() {
+        let result = LineResult::Error(EvalError::new("undefined variable"));
+
+        let hint = build_hint_line(3, &result).unwrap();
+
+        assert_eq!(hint.to_string(), "     ^ [E000] undefined variable");
+        assert!(
+            hint.spans
+                .iter()
+                .all(|span| matches!(span.content, Cow::Borrowed(_)))
+        );
+        assert!(build_hint_line(0, &LineResult::Value(1.0)).is_none());
+    }
+
+    #[test]
+    fn test_hint_line_style_follows_severity() {
+        let error = LineResult::Error(EvalError::new("undefined variable"));
+        let warning = LineResult::Error(EvalError::warning("deprecated"));
+
+        let error_hint = build_hint_line(0, &error).unwrap();
+        let warning_hint = build_hint_line(0, &warning).unwrap();
+
+        assert_eq!(error_hint.to_string(), "  ^ [E000] undefined variable");
+        assert_eq!(error_hint.spans[2].style.fg, None);
+        assert_eq!(warning_hint.to_string(), "  ! [W000] deprecated");
+        assert_eq!(warning_hint.spans[2].style.fg, Some(Color::Yellow));
+        assert_eq!(
+            gutter_marker("x", Some(&warning)).style.fg,
+            Some(Color::Yellow)
+        );
+    }
+
+    #[test]
+    fn test_line_message_numbers_lines_from_one() {
+        let error = LineResult::Error(EvalError::new("undefined variable"));
+        let warning = LineResult::Warning {
+            result: Box::new(LineResult::Value(1.0)),
+            warning: EvalError::warning("rounded"),
+        };
+
+        assert_eq!(
+            line_message(2, &error).as_deref(),
+            Some("Line 3: [E000] undefined variable")
+        );
+        assert_eq!(
+            line_message(0, &warning).as_deref(),
+            Some("Line 1: [W000] rounded")
+        );
+        assert_eq!(line_message(0, &LineResult::Value(1.0)), None);
+    }
+
+    #[test]
+    fn test_blank_falls_back_to_allocating_when_wide() {
+        assert_eq!(blank(4), "    ");
+        assert!(matches!(blank(BLANK.len()), Cow::Borrowed(_)));
+        assert_eq!(blank(BLANK.len() + 2).len(), BLANK.len() + 2);
+    }
+
     #[test]
     fn test_build_input_lines_multiple_lines_with_error() {
         let lines = vec![
@@ -1134,10 +3437,48 @@ mod tests {
         let line = "abc";
         let error = EvalError::with_span("error", ErrorSpan::new(0, 100));
 
-        let styled_line = build_error_line(line, &error);
+        let styled_line = build_error_line(line, &error);
+
+        // Should clamp to line length and have 1 span
+        assert_eq!(styled_line.spans.len(), 1);
+    }
+
+    #[test]
+    fn test_error_spans_on_multibyte_characters() {
+        let text = |spans: &[Span]| -> Vec<String> {
+            spans.iter().map(|s| s.content.to_string()).collect()
+        };
+        for line in ["5 ÷ 2", "€", "5€", "¢"] {
+            let results = evaluate_all_lines([line]);
+            let LineResult::Error(error) = &results[0] else {
+                panic!("{line} should be an error");
+            };
+            let spans = build_error_spans_with_offset(line, error, 0, 80);
+            assert_eq!(text(&spans).concat(), line);
+            assert!(build_error_spans_with_offset(line, error, 1, 80).len() <= 3);
+        }
+
+        let results = evaluate_all_lines(["5 ÷ 2"]);
+        let LineResult::Error(error) = &results[0] else {
+            panic!("5 ÷ 2 should be an error");
+        };
+        let spans = build_error_spans_with_offset("5 ÷ 2", error, 0, 80);
+        assert_eq!(text(&spans), ["5 ", "÷", " 2"]);
+
+        let error = EvalError::with_span("error", ErrorSpan::new(3, 4));
+        let spans = build_error_spans_with_offset("🦀🦀 + x", &error, 2, 3);
+        assert_eq!(text(&spans).concat(), "🦀");
+        assert_eq!(build_error_line("5 ÷ 2", &error).spans.len(), 3);
+    }
 
-        // Should clamp to line length and have 1 span
-        assert_eq!(styled_line.spans.len(), 1);
+    #[test]
+    fn test_reverse_range_floors_to_character_boundaries() {
+        let spans = vec![Span::raw("🦀🦀"), Span::raw(" + 1")];
+        let reversed = reverse_range(spans, 4, 6, 9);
+        let text: Vec<&str> = reversed.iter().map(|s| s.content.as_ref()).collect();
+        assert_eq!(text, ["🦀", "🦀", " + 1"]);
+        assert!(reversed[0].style.add_modifier.contains(Modifier::REVERSED));
+        assert!(!reversed[1].style.add_modifier.contains(Modifier::REVERSED));
     }
 
     #[test]
@@ -1309,6 +3650,107 @@ mod tests {
         assert_eq!(format_line_number(1234, 5), "1234 ");
     }
 
+    #[test]
+    fn test_line_numbers_follow_settings() {
+        assert_eq!(LineNumbers::new(false, false, 3).label(5), None);
+        assert_eq!(LineNumbers::new(false, false, 3).gutter_width(20), 0);
+        assert_eq!(LineNumbers::new(true, false, 3).label(5), Some(6));
+        assert_eq!(LineNumbers::new(false, true, 3).label(1), Some(2));
+        assert_eq!(LineNumbers::new(false, true, 3).label(3), Some(0));
+        assert_eq!(LineNumbers::new(true, true, 3).label(3), Some(4));
+        assert_eq!(LineNumbers::new(true, true, 3).label(5), Some(2));
+    }
+
+    #[test]
+    fn test_visible_input_lines_with_relative_numbers_and_no_gutter() {
+        let lines = vec!["1".to_string(), "2".to_string(), "3".to_string()];
+        let results = evaluate_all_lines(lines.iter().map(String::as_str));
+        let build = |numbers| {
+            let gutter = Gutter {
+                numbers,
+                markers: false,
+            };
+            build_visible_input_lines_with_gutter(
+                &lines,
+                &results,
+                0,
+                10,
+                0,
+                80,
+                &RowMap::new(&results, true),
+                gutter,
+                HighlightOptions::default(),
+            )
+        };
+
+        let (output, gutter_width) = build(LineNumbers::Relative { cursor: 1 });
+        let gutter: Vec<_> = output
+            .iter()
+            .map(|line| line.spans[0].content.as_ref())
+            .collect();
+        assert_eq!(gutter_width, 3);
+        assert_eq!(gutter, [" 1 ", " 0 ", " 1 "]);
+
+        let (output, gutter_width) = build(LineNumbers::Off);
+        assert_eq!(gutter_width, 0);
+        assert_eq!(output[2].spans[1].content, "3");
+    }
+
+    #[test]
+    fn test_visible_input_lines_with_gutter_markers() {
+        let lines: Vec<String> = ["x = 5", "x + 1", "1 + nope", "# off", "y = 2 +"]
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let results = evaluate_all_lines(lines.iter().map(String::as_str));
+        let gutter = Gutter {
+            numbers: LineNumbers::Absolute,
+            markers: true,
+        };
+
+        let (output, gutter_width) = build_visible_input_lines_with_gutter(
+            &lines,
+            &results,
+            0,
+            10,
+            0,
+            80,
+            &RowMap::new(&results, false),
+            gutter,
+            HighlightOptions::default(),
+        );
+
+        assert_eq!(gutter_width, 4);
+        let markers: Vec<_> = output
+            .iter()
+            .map(|line| (line.spans[0].content.as_ref(), line.spans[0].style.fg))
+            .collect();
+        assert_eq!(
+            markers,
+            [
+                ("●", Some(Color::Cyan)),
+                (" ", None),
+                ("▲", Some(Color::Red)),
+                ("●", None),
+                ("▲", Some(Color::Red)),
+            ]
+        );
+        assert_eq!(output[3].spans[0].style, disabled_line_style());
+        assert_eq!(output[0].spans[1].content, " 1 ");
+    }
+
+    #[test]
+    fn test_gutter_width_with_markers() {
+        let markers = |numbers| Gutter {
+            numbers,
+            markers: true,
+        };
+
+        assert_eq!(markers(LineNumbers::Absolute).width(5), 4);
+        assert_eq!(markers(LineNumbers::Off).width(5), 2);
+        assert_eq!(Gutter::default().width(5), 3);
+    }
+
     #[test]
     fn test_gutter_style_uses_subtle_styling() {
         let style = gutter_style();
@@ -1330,8 +3772,17 @@ mod tests {
         let lines: Vec<String> = (0..50).map(|i| format!("line {i}")).collect();
         let results: Vec<LineResult> = (0..50).map(|_| LineResult::Empty).collect();
 
-        let (output, gutter_width) =
-            build_visible_input_lines_with_gutter(&lines, &results, 0, 10, 0, 80, None);
+        let (output, gutter_width) = build_visible_input_lines_with_gutter(
+            &lines,
+            &results,
+            0,
+            10,
+            0,
+            80,
+            &RowMap::new(&results, true),
+            Gutter::default(),
+            HighlightOptions::default(),
+        );
 
         // Should return only 10 visible lines
         assert_eq!(output.len(), 10);
@@ -1340,13 +3791,51 @@ mod tests {
         assert_eq!(gutter_width, 3);
     }
 
+    #[test]
+    fn test_build_visible_input_lines_with_gutter_dims_disabled_line() {
+        let lines = vec!["# x = 5".to_string()];
+        let results = vec![LineResult::Empty];
+
+        let (output, _) = build_visible_input_lines_with_gutter(
+            &lines,
+            &results,
+            0,
+            10,
+            0,
+            80,
+            &RowMap::new(&results, true),
+            Gutter::default(),
+            HighlightOptions::default(),
+        );
+
+        let content: String = output[0].spans[1..]
+            .iter()
+            .map(|s| s.content.as_ref())
+            .collect();
+        assert_eq!(content, "# x = 5");
+        assert!(
+            output[0].spans[1..]
+                .iter()
+                .all(|s| s.style == disabled_line_style())
+        );
+    }
+
     #[test]
     fn test_build_visible_input_lines_with_gutter_scrolled_shows_correct_numbers() {
         let lines: Vec<String> = (0..20).map(|i| format!("line {i}")).collect();
         let results: Vec<LineResult> = (0..20).map(|_| LineResult::Empty).collect();
 
-        let (output, _) =
-            build_visible_input_lines_with_gutter(&lines, &results, 10, 5, 0, 80, None);
+        let (output, _) = build_visible_input_lines_with_gutter(
+            &lines,
+            &results,
+            10,
+            5,
+            0,
+            80,
+            &RowMap::new(&results, true),
+            Gutter::default(),
+            HighlightOptions::default(),
+        );
 
         // Should return 5 lines starting at offset 10
         assert_eq!(output.len(), 5);
@@ -1396,8 +3885,17 @@ mod tests {
         let lines = vec!["5+".to_string()];
         let results = vec![LineResult::Error(EvalError::new("Incomplete expression"))];
 
-        let (output, _) =
-            build_visible_input_lines_with_gutter(&lines, &results, 0, 10, 0, 80, None);
+        let (output, _) = build_visible_input_lines_with_gutter(
+            &lines,
+            &results,
+            0,
+            10,
+            0,
+            80,
+            &RowMap::new(&results, true),
+            Gutter::default(),
+            HighlightOptions::default(),
+        );
 
         // Should have 2 lines: input line + error message line
         assert_eq!(
@@ -1421,7 +3919,9 @@ mod tests {
             10,
             0,
             80,
-            Some(recent_time),
+            &RowMap::new(&results, should_show_error_message(Some(recent_time))),
+            Gutter::default(),
+            HighlightOptions::default(),
         );
 
         // Should have only 1 line: input line (error message debounced)
@@ -1441,8 +3941,17 @@ mod tests {
         let old_time = Instant::now()
             .checked_sub(Duration::from_millis(600))
             .unwrap();
-        let (output, _) =
-            build_visible_input_lines_with_gutter(&lines, &results, 0, 10, 0, 80, Some(old_time));
+        let (output, _) = build_visible_input_lines_with_gutter(
+            &lines,
+            &results,
+            0,
+            10,
+            0,
+            80,
+            &RowMap::new(&results, should_show_error_message(Some(old_time))),
+            Gutter::default(),
+            HighlightOptions::default(),
+        );
 
         // Should have 2 lines: input line + error message line
         assert_eq!(
@@ -1470,7 +3979,9 @@ mod tests {
             10,
             0,
             80,
-            Some(recent_time),
+            &RowMap::new(&results, should_show_error_message(Some(recent_time))),
+            Gutter::default(),
+            HighlightOptions::default(),
         );
 
         // Should have only 1 line (error message debounced)
@@ -1482,6 +3993,124 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_render_input_panel_shows_ghost_result_on_cursor_line() {
+        use crate::editor::Buffer;
+        use ratatui::Terminal;
+        use ratatui::backend::TestBackend;
+
+        let mut terminal = Terminal::new(TestBackend::new(30, 5)).unwrap();
+        let buffer = Buffer::from_lines(vec!["5 + 3 +".to_string()]);
+        let results = vec![LineResult::Error(EvalError::new("incomplete"))];
+
+        terminal
+            .draw(|frame| {
+                let rows = RowMap::new(&results, false);
+                render_input_panel(
+                    frame,
+                    Rect::new(0, 0, 30, 5),
+                    &buffer,
+                    &EvalContext::new(),
+                    &results,
+                    &rows,
+                    0,
+                    0,
+                    Gutter::default(),
+                    None,
+                    Some("8"),
+                    None,
+                );
+            })
+            .unwrap();
+
+        let row: String = (0..30)
+            .map(|x| terminal.backend().buffer()[(x, 1)].symbol().to_string())
+            .collect();
+        assert!(row.contains("5 + 3 +  ≈ 8"), "got {row:?}");
+        let x = u16::try_from(row.chars().position(|c| c == '≈').unwrap()).unwrap();
+        assert!(
+            terminal.backend().buffer()[(x, 1)]
+                .modifier
+                .contains(Modifier::DIM)
+        );
+    }
+
+    #[test]
+    fn test_render_input_panel_describes_results_in_text() {
+        use crate::editor::Buffer;
+        use ratatui::Terminal;
+        use ratatui::backend::TestBackend;
+
+        let mut terminal = Terminal::new(TestBackend::new(50, 5)).unwrap();
+        let lines = vec!["x = 2 + 3".to_string(), "x * y".to_string()];
+        let results = evaluate_all_lines(lines.iter().map(String::as_str));
+        let buffer = Buffer::from_lines(lines);
+
+        terminal
+            .draw(|frame| {
+                let rows = RowMap::new(&results, false);
+                render_input_panel(
+                    frame,
+                    Rect::new(0, 0, 50, 5),
+                    &buffer,
+                    &EvalContext::new(),
+                    &results,
+                    &rows,
+                    0,
+                    0,
+                    Gutter::default(),
+                    None,
+                    None,
+                    Some(Annotations {
+                        format: NumberFormat::default(),
+                        errors: true,
+                    }),
+                );
+            })
+            .unwrap();
+
+        let row = |y: u16| -> String {
+            (0..50)
+                .map(|x| terminal.backend().buffer()[(x, y)].symbol().to_string())
+                .collect()
+        };
+        assert!(row(1).contains("x = 2 + 3  x = 5"), "got {:?}", row(1));
+        assert!(row(2).contains("x * y  ERR: [E001]"), "got {:?}", row(2));
+        let line = row(2);
+        let start = line[..line.find("x * y").unwrap()].chars().count();
+        let start = u16::try_from(start).unwrap();
+        assert!(
+            (start..start + 5).all(|x| terminal.backend().buffer()[(x, 2)].fg == Color::Reset),
+            "the error line should keep the default colors"
+        );
+    }
+
+    #[test]
+    fn test_describe_result_marks_values_errors_and_warnings() {
+        let annotations = Annotations {
+            format: NumberFormat::default(),
+            errors: true,
+        };
+        let describe = |line: &str| {
+            let results = evaluate_all_lines(["a = 4", line]);
+            describe_result(&results[1], annotations)
+        };
+
+        assert_eq!(describe("a * 2"), ["= 8"]);
+        assert_eq!(describe("b = a + 1"), ["b = 5"]);
+        assert_eq!(describe(""), Vec::<String>::new());
+        let error = describe("a +");
+        assert!(error[0].starts_with("ERR: ["), "got {error:?}");
+        let hidden = describe_result(
+            &LineResult::Error(EvalError::new("incomplete")),
+            Annotations {
+                errors: false,
+                ..annotations
+            },
+        );
+        assert!(hidden.is_empty());
+    }
+
     #[test]
     fn test_render_input_panel_sets_cursor_position() {
         use crate::editor::Buffer;
@@ -1492,11 +4121,26 @@ mod tests {
         let mut terminal = Terminal::new(backend).unwrap();
 
         let buffer = Buffer::from_lines(vec!["hello".to_string()]);
+        let results = vec![LineResult::Empty];
         let area = Rect::new(0, 0, 40, 10);
 
         terminal
             .draw(|frame| {
-                render_input_panel(frame, area, &buffer, 0, 0, None);
+                let rows = RowMap::new(&results, true);
+                render_input_panel(
+                    frame,
+                    area,
+                    &buffer,
+                    &EvalContext::new(),
+                    &results,
+                    &rows,
+                    0,
+                    0,
+                    Gutter::default(),
+                    None,
+                    None,
+                    None,
+                );
             })
             .unwrap();
 
@@ -1803,7 +4447,18 @@ mod tests {
         let panel_width = 10;
         let memory_pane_left = true;
 
-        let output = build_visible_result_lines(&results, 0, 10, panel_width, memory_pane_left);
+        let output = build_visible_result_lines(
+            &results,
+            &RowMap::new(&results, true),
+            0,
+            10,
+            panel_width,
+            memory_pane_left,
+            false,
+            NumberFormat::default(),
+            ChangeHighlight::default(),
+            None,
+        );
 
         // Content should be right-aligned: 8 spaces + "42"
         let first_line = &output[0];
@@ -1819,6 +4474,309 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_format_result_multiple_assignment() {
+        let result = LineResult::Assignments(vec![("a".to_string(), 3.0), ("b".to_string(), 0.5)]);
+
+        assert_eq!(format_result(&result), Some("a = 3, b = 0.5".to_string()));
+    }
+
+    #[test]
+    fn test_format_result_total_is_labeled() {
+        assert_eq!(
+            format_result(&LineResult::Total(1770.0)),
+            Some("Σ = 1770".to_string())
+        );
+    }
+
+    #[test]
+    fn test_build_visible_result_lines_shows_table_rows_below_line() {
+        let table = Table {
+            variable: "x".to_string(),
+            function: "x^2".to_string(),
+            rows: vec![(0.5, 0.25), (10.0, 100.0)],
+        };
+        let results = vec![LineResult::Table(table), LineResult::Value(7.0)];
+
+        let output = build_visible_result_lines(
+            &results,
+            &RowMap::new(&results, true),
+            0,
+            10,
+            20,
+            false,
+            false,
+            NumberFormat::default(),
+            ChangeHighlight::default(),
+            None,
+        );
+
+        let texts: Vec<String> = output.iter().map(ToString::to_string).collect();
+        assert_eq!(texts, ["  x │ x^2", "0.5 │ 0.25", " 10 │ 100", "7"]);
+
+        let lines = ["t".to_string(), "7".to_string()];
+        assert_eq!(build_input_lines(&lines, &results).len(), 4);
+    }
+
+    #[test]
+    fn test_result_lines_stay_level_with_input_while_messages_are_hidden() {
+        let lines = vec!["5+".to_string(), "7".to_string()];
+        let results = evaluate_all_lines(lines.iter().map(String::as_str));
+
+        for show_hints in [true, false] {
+            let rows = RowMap::new(&results, show_hints);
+            let (input, _) = build_visible_input_lines_with_gutter(
+                &lines,
+                &results,
+                0,
+                10,
+                0,
+                80,
+                &rows,
+                Gutter::default(),
+                HighlightOptions::default(),
+            );
+            let output = build_visible_result_lines(
+                &results,
+                &rows,
+                0,
+                10,
+                20,
+                false,
+                false,
+                NumberFormat::default(),
+                ChangeHighlight::default(),
+                None,
+            );
+
+            assert_eq!(input.len(), output.len());
+            assert_eq!(output.last().unwrap().to_string(), "7");
+        }
+    }
+
+    #[test]
+    fn test_overview_marks_errors_assignments_and_viewport() {
+        let mut results = vec![LineResult::Value(1.0); 300];
+        results[0] = LineResult::Assignment {
+            name: "x".to_string(),
+            value: 1.0,
+        };
+        results[155] = LineResult::Error(EvalError::new("Incomplete expression"));
+
+        let lines = build_overview_lines(&results, 0, 20, 30);
+
+        assert_eq!(lines.len(), 30);
+        assert_eq!(lines[0].spans[0].style.fg, Some(Color::Cyan));
+        assert_eq!(lines[0].spans[0].style.bg, Some(Color::DarkGray));
+        assert_eq!(lines[1].to_string(), "┃");
+        assert_eq!(lines[2].to_string(), "│");
+        assert_eq!(lines[15].spans[0].style.fg, Some(Color::Red));
+    }
+
+    #[test]
+    fn test_overview_of_short_worksheet_has_a_row_per_line() {
+        let results = vec![LineResult::Value(1.0); 3];
+
+        assert_eq!(build_overview_lines(&results, 0, 20, 30).len(), 3);
+    }
+
+    #[test]
+    fn test_build_visible_result_lines_total_is_bold() {
+        let results = vec![LineResult::Value(2.0), LineResult::Total(2.0)];
+
+        let output = build_visible_result_lines(
+            &results,
+            &RowMap::new(&results, true),
+            0,
+            10,
+            10,
+            false,
+            false,
+            NumberFormat::default(),
+            ChangeHighlight::default(),
+            None,
+        );
+
+        assert_eq!(output[1].spans[0].content.as_ref(), "Σ = 2");
+        assert!(
+            output[1].spans[0]
+                .style
+                .add_modifier
+                .contains(Modifier::BOLD)
+        );
+        assert!(
+            !output[0].spans[0]
+                .style
+                .add_modifier
+                .contains(Modifier::BOLD)
+        );
+    }
+
+    #[test]
+    fn test_build_visible_result_lines_colors_changed_results() {
+        let results = vec![LineResult::Value(1.0), LineResult::Value(2.0)];
+        let changed = ChangeHighlight {
+            lines: &[1],
+            color: Color::Yellow,
+        };
+
+        let output = build_visible_result_lines(
+            &results,
+            &RowMap::new(&results, true),
+            0,
+            10,
+            10,
+            false,
+            false,
+            NumberFormat::default(),
+            changed,
+            None,
+        );
+
+        assert_eq!(output[0].spans[0].style.fg, None);
+        assert_eq!(output[1].spans[0].style.fg, Some(Color::Yellow));
+    }
+
+    #[test]
+    fn test_build_visible_result_lines_shows_timings_at_outer_edge() {
+        let results = vec![LineResult::Value(42.0), LineResult::Empty];
+        let timings = [Duration::from_micros(12), Duration::from_micros(3)];
+        let render = |memory_pane_left| -> Vec<String> {
+            build_visible_result_lines(
+                &results,
+                &RowMap::new(&results, true),
+                0,
+                10,
+                12,
+                memory_pane_left,
+                false,
+                NumberFormat::default(),
+                ChangeHighlight::default(),
+                Some(&timings),
+            )
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+        };
+
+        assert_eq!(
+            render(true),
+            vec!["12µs      42".to_string(), " ".repeat(12)]
+        );
+        assert_eq!(
+            render(false),
+            vec!["42      12µs".to_string(), " ".repeat(12)]
+        );
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_micros(0)), "0µs");
+        assert_eq!(format_duration(Duration::from_micros(999)), "999µs");
+        assert_eq!(format_duration(Duration::from_micros(3_460)), "3.5ms");
+        assert_eq!(format_duration(Duration::from_millis(1_250)), "1.25s");
+    }
+
+    #[test]
+    fn test_build_watch_lines_shows_values_and_errors() {
+        let mut context = EvalContext::new();
+        context.set_variable("budget", 500.0);
+        context.set_variable("spent", 120.5);
+        let watches = vec!["budget - spent".to_string(), "missing".to_string()];
+
+        let lines: Vec<String> = build_watch_lines(&watches, &context, NumberFormat::default())
+            .iter()
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect();
+
+        assert_eq!(lines[0], "budget - spent = 379.5");
+        assert!(lines[1].starts_with("missing = "));
+        assert!(lines[1].contains("missing"));
+    }
+
+    #[test]
+    fn test_align_decimal_points_lines_up_values() {
+        let texts = vec![
+            "x = 4".to_string(),
+            String::new(),
+            "12.5".to_string(),
+            "total = 0.125".to_string(),
+        ];
+
+        assert_eq!(
+            align_decimal_points(&texts, &[true; 4], 40),
+            vec!["x =      4    ", "", "        12.5  ", "total =  0.125",]
+        );
+        assert_eq!(align_decimal_points(&texts, &[true; 4], 10), texts);
+        assert_eq!(
+            align_decimal_points(&texts, &[true, false, false, true], 40),
+            vec!["x =     4    ", "", "12.5", "total = 0.125",]
+        );
+    }
+
+    #[test]
+    fn test_build_visible_result_lines_aligns_only_numbers_that_fit() {
+        let results = vec![
+            LineResult::Money {
+                name: None,
+                money: Money {
+                    minor: 3750,
+                    currency: crate::eval::money::currency_by_code("USD").unwrap(),
+                },
+            },
+            LineResult::Symbolic("5.5 * x + 2.25".to_string()),
+            LineResult::Value(1.5),
+        ];
+        let render = |width: usize| -> Vec<String> {
+            build_visible_result_lines(
+                &results,
+                &RowMap::new(&results, true),
+                0,
+                10,
+                width,
+                true,
+                true,
+                NumberFormat::default(),
+                ChangeHighlight::default(),
+                None,
+            )
+            .iter()
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect()
+        };
+
+        assert_eq!(
+            render(14),
+            vec!["        $37.50", "5.5 * x + 2.25", "          1.5 "]
+        );
+        assert_eq!(render(6), vec!["$37.50", "5.5 * x + 2.25", "  1.5 "]);
+        assert_eq!(render(5), vec!["$37.50", "5.5 * x + 2.25", "  1.5"]);
+    }
+
+    #[test]
+    fn test_build_visible_result_lines_aligned_decimals_right_justified() {
+        let results = vec![LineResult::Value(1.5), LineResult::Value(100.25)];
+
+        let output = build_visible_result_lines(
+            &results,
+            &RowMap::new(&results, true),
+            0,
+            10,
+            10,
+            true,
+            true,
+            NumberFormat::default(),
+            ChangeHighlight::default(),
+            None,
+        );
+
+        let lines: Vec<String> = output
+            .iter()
+            .map(|line| line.spans.iter().map(|s| s.content.as_ref()).collect())
+            .collect();
+        assert_eq!(lines, vec!["      1.5 ", "    100.25"]);
+    }
+
     #[test]
     fn test_help_content_lines_returns_all_content() {
         let lines = help_content_lines();
@@ -1838,6 +4796,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_help_lists_every_unit() {
+        use crate::eval::units::UNITS;
+
+        let listed: Vec<&str> = HELP_UNIT_REFERENCE
+            .iter()
+            .flat_map(|line| line.split_whitespace())
+            .collect();
+        for unit in UNITS {
+            assert!(listed.contains(&unit.symbol), "{} not in help", unit.symbol);
+        }
+    }
+
     #[test]
     fn test_help_content_height_matches_actual_content() {
         let lines = help_content_lines();
@@ -1946,4 +4917,30 @@ mod tests {
             "Command bar should contain 'CTRL+H: help'"
         );
     }
+
+    #[test]
+    fn test_quick_sum_text_shows_sum_mean_and_count() {
+        let format = NumberFormat {
+            precision: Some(2),
+            separators: true,
+        };
+        let quick_sum = QuickSum {
+            lines: 4,
+            count: 3,
+            sum: 1200.5,
+        };
+        assert_eq!(
+            build_quick_sum_text(&quick_sum, format).to_string(),
+            "SUM 1,200.5  MEAN 400.17  COUNT 3  of 4 lines"
+        );
+        let empty = QuickSum {
+            lines: 2,
+            count: 0,
+            sum: 0.0,
+        };
+        assert_eq!(
+            build_quick_sum_text(&empty, format).to_string(),
+            "SUM 0  COUNT 0  of 2 lines"
+        );
+    }
 }
@@ -0,0 +1,219 @@
+//! Mapping of buffer lines to screen rows.
+//!
+//! A line can take more than one row on screen: an error or warning message
+//! is shown below it, and a table lists its rows under it. The input and
+//! memory panels both lay out their lines with the same `RowMap`, so a result
+//! always sits on the first row of its line and the two panels never drift
+//! apart. The same map turns mouse clicks back into buffer lines.
+
+use ratatui::layout::{Position, Rect};
+
+use crate::eval::LineResult;
+
+/// The number of screen rows each buffer line takes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RowMap {
+    heights: Vec<usize>,
+    show_hints: bool,
+}
+
+impl RowMap {
+    /// Lays out one line per result. With `show_hints`, lines with an error
+    /// or warning get an extra row for the message.
+    #[must_use]
+    pub fn new(results: &[LineResult], show_hints: bool) -> Self {
+        let heights = results
+            .iter()
+            .map(|result| {
+                let hint = show_hints && has_hint_line(result);
+                1 + usize::from(hint) + table_rows(result)
+            })
+            .collect();
+        Self {
+            heights,
+            show_hints,
+        }
+    }
+
+    /// Returns true when error and warning messages take a row below their line.
+    #[must_use]
+    pub const fn shows_hints(&self) -> bool {
+        self.show_hints
+    }
+
+    /// Returns the number of rows `line` takes; lines past the results take one.
+    #[must_use]
+    pub fn height(&self, line: usize) -> usize {
+        self.heights.get(line).copied().unwrap_or(1)
+    }
+
+    /// Returns the first row of `line`, counted from the top of the panel
+    /// when it is scrolled to `scroll_offset`.
+    #[must_use]
+    pub fn first_row(&self, line: usize, scroll_offset: usize) -> usize {
+        (scroll_offset..line).map(|index| self.height(index)).sum()
+    }
+
+    /// Returns the line shown on `row`, counted from the top of the panel
+    /// when it is scrolled to `scroll_offset`, or `None` below the last line.
+    #[must_use]
+    pub fn line_at_row(&self, row: usize, scroll_offset: usize) -> Option<usize> {
+        let mut top = 0;
+        for line in scroll_offset..self.heights.len() {
+            top += self.heights[line];
+            if row < top {
+                return Some(line);
+            }
+        }
+        None
+    }
+}
+
+/// A buffer position found under the mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hit {
+    /// A column of a line in the input panel.
+    Input { line: usize, col: usize },
+    /// The result of a line in the memory panel.
+    Result(usize),
+}
+
+/// Where the panels were last drawn, for mapping mouse clicks to lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScreenMap {
+    /// The input panel, including its title row.
+    pub input: Rect,
+    /// The memory panel, including its title row.
+    pub memory: Rect,
+    /// Width of the line number gutter of the input panel.
+    pub gutter_width: usize,
+    /// The rows each line took.
+    pub rows: RowMap,
+}
+
+impl ScreenMap {
+    /// Finds the buffer position under the screen cell at `column`, `row`.
+    ///
+    /// Both panels are scrolled to `scroll_offset`; columns of the input
+    /// panel are counted past the gutter and `horizontal_scroll_offset`.
+    /// Message and table rows belong to the line above them.
+    #[must_use]
+    pub fn hit(
+        &self,
+        column: u16,
+        row: u16,
+        scroll_offset: usize,
+        horizontal_scroll_offset: usize,
+    ) -> Option<Hit> {
+        let position = Position::new(column, row);
+        if self.input.contains(position) {
+            let line = self.line_at(self.input, row, scroll_offset)?;
+            let x = usize::from(column - self.input.x);
+            let col = x.saturating_sub(self.gutter_width) + horizontal_scroll_offset;
+            Some(Hit::Input { line, col })
+        } else if self.memory.contains(position) {
+            self.line_at(self.memory, row, scroll_offset)
+                .map(Hit::Result)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the line on screen `row` of a panel drawn in `area`, below
+    /// its title row.
+    fn line_at(&self, area: Rect, row: u16, scroll_offset: usize) -> Option<usize> {
+        let row = row.checked_sub(area.y + 1)?;
+        self.rows.line_at_row(usize::from(row), scroll_offset)
+    }
+}
+
+/// Where error and warning messages are shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPlacement {
+    /// On a row of their own below their line.
+    #[default]
+    Inline,
+    /// In the command bar, for the line the cursor is on.
+    Bar,
+}
+
+/// Returns true when `result` shows an error or warning message below its
+/// line.
+pub const fn has_hint_line(result: &LineResult) -> bool {
+    matches!(result, LineResult::Error(_) | LineResult::Warning { .. })
+}
+
+/// Returns the number of table rows shown below the line of `result`.
+pub const fn table_rows(result: &LineResult) -> usize {
+    match result {
+        LineResult::Table(table) => table.rows.len(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::EvalError;
+
+    fn results() -> Vec<LineResult> {
+        vec![
+            LineResult::Value(1.0),
+            LineResult::Error(EvalError::new("Incomplete expression")),
+            LineResult::Empty,
+        ]
+    }
+
+    #[test]
+    fn test_error_line_takes_a_row_for_its_message() {
+        let rows = RowMap::new(&results(), true);
+
+        assert_eq!(rows.height(1), 2);
+        assert_eq!(rows.first_row(2, 0), 3);
+        assert_eq!(rows.first_row(2, 1), 2);
+    }
+
+    #[test]
+    fn test_hidden_messages_take_no_row() {
+        let rows = RowMap::new(&results(), false);
+
+        assert!(!rows.shows_hints());
+        assert_eq!(rows.height(1), 1);
+        assert_eq!(rows.first_row(2, 0), 2);
+    }
+
+    #[test]
+    fn test_line_at_row_counts_message_rows_to_their_line() {
+        let rows = RowMap::new(&results(), true);
+
+        assert_eq!(rows.line_at_row(0, 0), Some(0));
+        assert_eq!(rows.line_at_row(2, 0), Some(1));
+        assert_eq!(rows.line_at_row(3, 0), Some(2));
+        assert_eq!(rows.line_at_row(0, 2), Some(2));
+        assert_eq!(rows.line_at_row(4, 0), None);
+    }
+
+    #[test]
+    fn test_hit_accounts_for_gutter_and_scrolling() {
+        let screen = ScreenMap {
+            input: Rect::new(0, 0, 30, 10),
+            memory: Rect::new(30, 0, 20, 10),
+            gutter_width: 3,
+            rows: RowMap::new(&results(), true),
+        };
+
+        assert_eq!(screen.hit(5, 1, 0, 0), Some(Hit::Input { line: 0, col: 2 }));
+        assert_eq!(screen.hit(1, 2, 1, 4), Some(Hit::Input { line: 1, col: 4 }));
+        assert_eq!(screen.hit(35, 4, 0, 0), Some(Hit::Result(2)));
+        assert_eq!(screen.hit(35, 0, 0, 0), None);
+        assert_eq!(screen.hit(35, 9, 0, 0), None);
+    }
+
+    #[test]
+    fn test_lines_past_results_take_one_row() {
+        let rows = RowMap::new(&[], true);
+
+        assert_eq!(rows.height(4), 1);
+        assert_eq!(rows.first_row(3, 1), 2);
+    }
+}
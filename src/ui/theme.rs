@@ -1,7 +1,19 @@
 //! Theme detection for Crabculator.
 
+use ratatui::style::Color;
 use terminal_colorsaurus::{QueryOptions, ThemeMode, theme_mode};
 
+/// Bracket depth colors for dark backgrounds.
+const DARK_BRACKET_COLORS: &[Color] = &[
+    Color::LightYellow,
+    Color::LightMagenta,
+    Color::LightBlue,
+    Color::LightGreen,
+];
+
+/// Bracket depth colors for light backgrounds.
+const LIGHT_BRACKET_COLORS: &[Color] = &[Color::Blue, Color::Magenta, Color::Red, Color::Green];
+
 /// Detected terminal theme.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AppTheme {
@@ -14,6 +26,7 @@ impl AppTheme {
     ///
     /// Falls back to Dark theme if detection fails.
     #[must_use]
+    #[allow(clippy::map_unwrap_or)]
     pub fn detect() -> Self {
         theme_mode(QueryOptions::default())
             .map(|mode| match mode {
@@ -22,6 +35,24 @@ impl AppTheme {
             })
             .unwrap_or(Self::Dark)
     }
+
+    /// Returns the color of results that changed after the last edit.
+    #[must_use]
+    pub const fn changed_result_color(self) -> Color {
+        match self {
+            Self::Light => Color::Blue,
+            Self::Dark => Color::LightYellow,
+        }
+    }
+
+    /// Returns the palette cycled through by parenthesis nesting depth.
+    #[must_use]
+    pub const fn bracket_colors(self) -> &'static [Color] {
+        match self {
+            Self::Light => LIGHT_BRACKET_COLORS,
+            Self::Dark => DARK_BRACKET_COLORS,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -48,6 +79,15 @@ mod tests {
         assert_ne!(AppTheme::Light, AppTheme::Dark);
     }
 
+    #[test]
+    fn app_theme_bracket_colors_differ_per_theme() {
+        assert!(!AppTheme::Dark.bracket_colors().is_empty());
+        assert_ne!(
+            AppTheme::Dark.bracket_colors(),
+            AppTheme::Light.bracket_colors()
+        );
+    }
+
     #[test]
     fn app_theme_clone() {
         let theme = AppTheme::Dark;
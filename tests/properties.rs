@@ -0,0 +1,131 @@
+//! Property-based tests for the tokenizer, parser, formatter and evaluator.
+//!
+//! These check laws that must hold for any input rather than examples:
+//! printing a parsed expression and parsing it again gives the same tree,
+//! `+` and `*` are commutative, and token spans follow each other through
+//! the input.
+
+use crabculator::eval::ast::{BinaryOp, Expr};
+use crabculator::eval::format::{format_ast, format_expression};
+use crabculator::eval::token::Tokenizer;
+use crabculator::eval::{EvalContext, evaluate_expression, parse_expression};
+use proptest::prelude::*;
+
+/// Variable names that are not units, constants or keywords.
+const NAMES: &[&str] = &["a", "b", "price", "rate", "x1", "total_2"];
+
+/// Functions taking one argument.
+const FUNCTIONS: &[&str] = &["sqrt", "abs", "floor", "round", "ln", "sin"];
+
+/// Non-negative literals; a negative number is written as unary minus.
+fn number() -> impl Strategy<Value = f64> {
+    prop_oneof![
+        (0u32..1000).prop_map(f64::from),
+        (0u32..100_000).prop_map(|n| f64::from(n) / 100.0),
+    ]
+}
+
+fn binary_op() -> impl Strategy<Value = BinaryOp> {
+    prop_oneof![
+        Just(BinaryOp::Add),
+        Just(BinaryOp::Sub),
+        Just(BinaryOp::Mul),
+        Just(BinaryOp::Div),
+        Just(BinaryOp::FloorDiv),
+        Just(BinaryOp::Mod),
+        Just(BinaryOp::Pow),
+    ]
+}
+
+/// Expression trees as the parser builds them.
+fn expr() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![
+        number().prop_map(Expr::Number),
+        prop::sample::select(NAMES).prop_map(|name| Expr::Variable(name.to_string())),
+    ];
+    leaf.prop_recursive(4, 24, 2, |inner| {
+        prop_oneof![
+            (inner.clone(), binary_op(), inner.clone()).prop_map(|(left, op, right)| {
+                Expr::BinaryOp {
+                    left: Box::new(left),
+                    op,
+                    right: Box::new(right),
+                }
+            }),
+            inner.clone().prop_map(|e| Expr::UnaryMinus(Box::new(e))),
+            (prop::sample::select(FUNCTIONS), inner.clone()).prop_map(|(name, arg)| {
+                Expr::FunctionCall {
+                    name: name.to_string(),
+                    args: vec![arg],
+                }
+            }),
+            (inner.clone(), inner).prop_map(|(a, b)| Expr::FunctionCall {
+                name: "max".to_string(),
+                args: vec![a, b],
+            }),
+        ]
+    })
+}
+
+/// Expressions over numbers only, which always evaluate.
+fn arithmetic() -> impl Strategy<Value = String> {
+    let leaf = number().prop_map(|n| n.to_string());
+    leaf.prop_recursive(3, 12, 2, |inner| {
+        (
+            inner.clone(),
+            prop::sample::select(&["+", "-", "*", "/"][..]),
+            inner,
+        )
+            .prop_map(|(left, op, right)| format!("({left}) {op} ({right})"))
+    })
+}
+
+/// Returns `true` if `a` and `b` are equal within a relative tolerance, the
+/// same infinity, or both NaN.
+fn close(a: f64, b: f64) -> bool {
+    (a.is_nan() && b.is_nan())
+        || a.total_cmp(&b).is_eq()
+        || (a - b).abs() <= 1e-9 * a.abs().max(b.abs())
+}
+
+proptest! {
+    #[test]
+    fn parse_print_parse_round_trips(expr in expr()) {
+        let printed = format_ast(&expr);
+        let parsed = parse_expression(&printed);
+        prop_assert_eq!(parsed.ok(), Some(expr), "printed as {}", printed);
+    }
+
+    #[test]
+    fn formatting_is_idempotent(expr in expr()) {
+        let printed = format_ast(&expr);
+        let formatted = format_expression(&printed, &EvalContext::new()).expect("printed expressions parse");
+        prop_assert_eq!(&formatted, &printed);
+        prop_assert_eq!(format_expression(&formatted, &EvalContext::new()).ok(), Some(formatted));
+    }
+
+    #[test]
+    fn addition_and_multiplication_commute(a in arithmetic(), b in arithmetic()) {
+        let context = EvalContext::new();
+        for op in ["+", "*"] {
+            let ab = evaluate_expression(&format!("({a}) {op} ({b})"), &context);
+            let ba = evaluate_expression(&format!("({b}) {op} ({a})"), &context);
+            match (ab, ba) {
+                (Ok(ab), Ok(ba)) => prop_assert!(close(ab, ba), "{} vs {}", ab, ba),
+                (ab, ba) => prop_assert_eq!(ab.is_ok(), ba.is_ok()),
+            }
+        }
+    }
+
+    #[test]
+    fn token_spans_are_ordered_within_the_input(input in "\\PC{0,40}") {
+        let (tokens, _) = Tokenizer::new(&input).tokenize_partial();
+        let mut previous_end = 0;
+        for (_, span) in tokens {
+            prop_assert!(previous_end <= span.start, "{:?} starts before {}", span, previous_end);
+            prop_assert!(span.start < span.end, "{:?} is empty", span);
+            prop_assert!(input.get(span.start..span.end).is_some(), "{:?} is not in the input", span);
+            previous_end = span.end;
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Golden-file snapshot tests for the TUI.
+//!
+//! Each test drives an `App` through `App::handle_key`, renders it to a
+//! ratatui `TestBackend`, and compares the screen text against a file in
+//! `tests/snapshots/`. Run with `UPDATE_SNAPSHOTS=1` to (re)write the golden
+//! files after an intentional rendering change.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crabculator::app::{self, App};
+use crabculator::editor::Buffer;
+use crabculator::ui;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::Terminal;
+use ratatui::backend::TestBackend;
+
+const SCREEN_WIDTH: u16 = 60;
+const SCREEN_HEIGHT: u16 = 16;
+
+// ============================================================
+// Harness
+// ============================================================
+
+/// Creates an app with an empty buffer that does not touch disk.
+fn new_app() -> App {
+    App::with_buffer(Buffer::new())
+}
+
+fn press(app: &mut App, code: KeyCode) {
+    app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+}
+
+fn press_ctrl(app: &mut App, c: char) {
+    app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL));
+}
+
+fn type_text(app: &mut App, text: &str) {
+    for c in text.chars() {
+        if c == '\n' {
+            press(app, KeyCode::Enter);
+        } else {
+            press(app, KeyCode::Char(c));
+        }
+    }
+}
+
+/// Renders the app and returns the screen as text, one line per row.
+///
+/// The error debounce is reset first so that snapshots do not depend on
+/// how quickly the keys were replayed.
+fn render_to_string(app: &mut App) -> String {
+    app.last_edit_time = None;
+
+    let mut terminal =
+        Terminal::new(TestBackend::new(SCREEN_WIDTH, SCREEN_HEIGHT)).expect("create terminal");
+    terminal
+        .draw(|frame| ui::render(frame, app))
+        .expect("draw frame");
+
+    let buffer = terminal.backend().buffer();
+    let mut screen = String::new();
+    for y in 0..buffer.area.height {
+        let row: String = (0..buffer.area.width)
+            .map(|x| buffer[(x, y)].symbol())
+            .collect();
+        screen.push_str(row.trim_end());
+        screen.push('\n');
+    }
+    screen
+}
+
+/// Compares the rendered screen against `tests/snapshots/<name>.txt`.
+fn assert_snapshot(name: &str, app: &mut App) {
+    let actual = render_to_string(app);
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.txt"));
+
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, &actual).expect("write snapshot");
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "missing snapshot {}; run with UPDATE_SNAPSHOTS=1 to create it\n{actual}",
+            path.display()
+        )
+    });
+    assert_eq!(
+        actual, expected,
+        "snapshot {name} differs; run with UPDATE_SNAPSHOTS=1 to accept"
+    );
+}
+
+// ============================================================
+// Snapshot Tests
+// ============================================================
+
+#[test]
+fn snapshot_empty_editor() {
+    let mut app = new_app();
+    assert_snapshot("empty_editor", &mut app);
+}
+
+#[test]
+fn snapshot_typed_expressions() {
+    let mut app = new_app();
+    type_text(&mut app, "a = 5\nb = a * 2\na + b");
+    assert_snapshot("typed_expressions", &mut app);
+}
+
+#[test]
+fn snapshot_error_line() {
+    let mut app = new_app();
+    type_text(&mut app, "1 + unknown");
+    assert_snapshot("error_line", &mut app);
+}
+
+#[test]
+fn snapshot_error_in_command_bar() {
+    let mut app = new_app();
+    app.settings.errors = ui::ErrorPlacement::Bar;
+    type_text(&mut app, "1 + unknown\n2");
+    press(&mut app, KeyCode::Up);
+    assert_snapshot("error_in_command_bar", &mut app);
+}
+
+#[test]
+fn snapshot_memory_pane_right() {
+    let mut app = new_app();
+    type_text(&mut app, "2 ^ 10");
+    app.handle_key(KeyEvent::new(KeyCode::Right, KeyModifiers::CONTROL));
+    app.notifications.clear();
+    assert_snapshot("memory_pane_right", &mut app);
+}
+
+#[test]
+fn snapshot_help_overlay() {
+    let mut app = new_app();
+    press_ctrl(&mut app, 'h');
+    assert_snapshot("help_overlay", &mut app);
+}
+
+#[test]
+fn snapshot_inspection_popup() {
+    let mut app = new_app();
+    type_text(&mut app, "255");
+    press_ctrl(&mut app, 'e');
+    assert_snapshot("inspection_popup", &mut app);
+}
+
+#[test]
+fn snapshot_explain_popup() {
+    let mut app = new_app();
+    type_text(&mut app, "2 + 3 * 4");
+    press_ctrl(&mut app, 't');
+    assert_snapshot("explain_popup", &mut app);
+}
+
+#[test]
+fn snapshot_format_line_notification() {
+    let mut app = new_app();
+    type_text(&mut app, "5+3*2");
+    press_ctrl(&mut app, 'f');
+    assert_snapshot("format_line", &mut app);
+}
+
+#[test]
+fn snapshot_welcome_banner() {
+    let mut app = App::with_buffer(Buffer::from_lines(
+        app::SAMPLE_WORKSHEET
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+    ));
+    app.welcome_visible = true;
+    assert_snapshot("welcome_banner", &mut app);
+}